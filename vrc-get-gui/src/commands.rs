@@ -366,7 +366,7 @@ impl From<AddPackageErr> for RustError {
     fn from(value: AddPackageErr) -> Self {
         let message = value.to_string();
         match value {
-            AddPackageErr::DependenciesNotFound { dependencies } => {
+            AddPackageErr::DependenciesNotFound { dependencies, .. } => {
                 RustError::handleable_missing_dependencies(message, dependencies)
             }
             _ => RustError::unrecoverable(message),
@@ -64,7 +64,7 @@ pub async fn environment_refetch_packages(
     packages: State<'_, PackagesState>,
     settings: State<'_, SettingsState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
 ) -> Result<(), RustError> {
     let settings = settings.load(io.inner()).await?;
     packages
@@ -80,7 +80,7 @@ pub async fn environment_packages(
     packages: State<'_, PackagesState>,
     settings: State<'_, SettingsState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
 ) -> Result<Vec<TauriPackage>, RustError> {
     let settings = settings.load(io.inner()).await?;
     let packages = packages.load(&settings, io.inner(), http.inner()).await?;
@@ -216,7 +216,7 @@ pub enum TauriDuplicatedReason {
 pub async fn environment_download_repository(
     settings: State<'_, SettingsState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     url: String,
     headers: IndexMap<Box<str>, Box<str>>,
 ) -> Result<TauriDownloadRepository, RustError> {
@@ -362,7 +362,7 @@ pub async fn environment_add_repository(
     settings: State<'_, SettingsState>,
     packages: State<'_, PackagesState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     url: String,
     headers: IndexMap<Box<str>, Box<str>>,
 ) -> Result<TauriAddRepositoryResult, RustError> {
@@ -489,7 +489,7 @@ pub async fn environment_import_download_repositories(
                 let user_repo_urls_ref = &user_repo_urls;
                 let user_repo_ids_ref = &user_repo_ids;
 
-                let http = window.state::<reqwest::Client>();
+                let http = window.state::<Client>();
                 let mut results = try_join_all(repositories.into_iter().map(|adding_repo| {
                     let ctx = ctx.clone();
                     let http = http.clone();
@@ -539,7 +539,7 @@ pub async fn environment_import_download_repositories(
 pub async fn environment_import_add_repositories(
     settings: State<'_, SettingsState>,
     packages: State<'_, PackagesState>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     io: State<'_, DefaultEnvironmentIo>,
     repositories: Vec<TauriRepositoryDescriptor>,
 ) -> Result<(), RustError> {
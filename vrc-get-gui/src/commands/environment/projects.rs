@@ -618,7 +618,7 @@ pub async fn environment_create_project(
     packages_state: State<'_, PackagesState>,
     settings: State<'_, SettingsState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     base_path: String,
     project_name: String,
     template: TauriProjectTemplate,
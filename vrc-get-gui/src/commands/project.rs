@@ -215,7 +215,7 @@ pub async fn project_reinstall_packages(
     packages: State<'_, PackagesState>,
     changes: State<'_, ChangesState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     project_path: String,
     package_ids: Vec<String>,
 ) -> Result<TauriPendingProjectChanges, RustError> {
@@ -240,7 +240,7 @@ pub async fn project_resolve(
     packages: State<'_, PackagesState>,
     changes: State<'_, ChangesState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     project_path: String,
 ) -> Result<TauriPendingProjectChanges, RustError> {
     let settings = settings.load(io.inner()).await?;
@@ -273,7 +273,7 @@ pub async fn project_remove_packages(
 pub async fn project_apply_pending_changes(
     changes: State<'_, ChangesState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     project_path: String,
     changes_version: u32,
 ) -> Result<(), RustError> {
@@ -310,7 +310,7 @@ pub async fn project_migrate_project_to_2022(
     settings: State<'_, SettingsState>,
     packages: State<'_, PackagesState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     project_path: String,
 ) -> Result<(), RustError> {
     {
@@ -416,7 +416,7 @@ pub async fn project_migrate_project_to_vpm(
     settings: State<'_, SettingsState>,
     packages: State<'_, PackagesState>,
     io: State<'_, DefaultEnvironmentIo>,
-    http: State<'_, reqwest::Client>,
+    http: State<'_, Client>,
     project_path: String,
 ) -> Result<(), RustError> {
     let settings = settings.load(io.inner()).await?;
@@ -56,6 +56,9 @@ pub fn startup(app: &mut App) {
     async fn open_main(app: AppHandle) -> tauri::Result<()> {
         let io = app.state::<DefaultEnvironmentIo>();
         let config = GuiConfigState::new_load(io.inner()).await?;
+        // the client is created with the config, so the config is applied on restart
+        let http = crate::state::new_http_client(config.get().http_max_attempts);
+        app.manage(http);
         app.manage(config);
 
         let handle = app.clone();
@@ -36,6 +36,9 @@ pub struct GuiConfig {
     pub gui_animation: bool,
     #[serde(default)]
     pub unity_hub_access_method: UnityHubAccessMethod,
+    // the number of attempts of http requests failed with transient errors
+    #[serde(default = "http_max_attempts_default")]
+    pub http_max_attempts: u32,
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize, Default, specta::Type)]
@@ -65,6 +68,7 @@ impl Default for GuiConfig {
             logs_level: log_level_default(),
             gui_animation: true,
             unity_hub_access_method: UnityHubAccessMethod::ReadConfig,
+            http_max_attempts: http_max_attempts_default(),
         }
     }
 }
@@ -134,6 +138,10 @@ fn gui_animation_default() -> bool {
     true
 }
 
+fn http_max_attempts_default() -> u32 {
+    3
+}
+
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub struct WindowSize {
     pub width: u32,
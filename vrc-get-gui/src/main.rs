@@ -52,7 +52,6 @@ fn main() {
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_dialog::init())
         .manage(io.clone())
-        .manage(state::new_http_client())
         .manage(state::SettingsState::new())
         .manage(state::UpdaterState::new())
         .manage(state::ProjectsState::new())
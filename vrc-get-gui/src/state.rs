@@ -11,9 +11,13 @@ pub use packages::*;
 pub use projects::*;
 pub use settings::*;
 pub use updater::*;
+use vrc_get_vpm::RetryingClient;
 
-pub fn new_http_client() -> reqwest::Client {
-    reqwest::Client::builder()
+/// The http client retrying requests failed with transient errors
+pub type Client = RetryingClient<reqwest::Client>;
+
+pub fn new_http_client(max_attempts: u32) -> Client {
+    let client = reqwest::Client::builder()
         .user_agent(concat!(
             "vrc-get-gui/",
             env!("CARGO_PKG_VERSION"),
@@ -25,5 +29,6 @@ pub fn new_http_client() -> reqwest::Client {
         .read_timeout(std::time::Duration::from_secs(10))
         .timeout(std::time::Duration::from_secs(10 * 60)) // 10 minutes
         .build()
-        .expect("building client")
+        .expect("building client");
+    RetryingClient::new(client, tokio::time::sleep).with_max_attempts(max_attempts)
 }
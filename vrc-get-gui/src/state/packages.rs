@@ -1,3 +1,4 @@
+use crate::state::Client;
 use crate::utils::YokeExt;
 use arc_swap::ArcSwapOption;
 use std::future::Future;
@@ -68,7 +69,7 @@ impl PackagesState {
         &self,
         settings: &Settings,
         io: &DefaultEnvironmentIo,
-        http: &reqwest::Client,
+        http: &Client,
     ) -> io::Result<PackagesStateRef<'_>> {
         let inner = self.inner.load_full();
 
@@ -87,7 +88,7 @@ impl PackagesState {
         &self,
         settings: &Settings,
         io: &DefaultEnvironmentIo,
-        http: &reqwest::Client,
+        http: &Client,
     ) -> io::Result<PackagesStateRef> {
         self.load_impl(settings, io, http, true).await
     }
@@ -96,7 +97,7 @@ impl PackagesState {
         &self,
         settings: &Settings,
         io: &DefaultEnvironmentIo,
-        http: &reqwest::Client,
+        http: &Client,
         force: bool,
     ) -> io::Result<PackagesStateRef> {
         // We won't allow multiple threads to load the data at the same time.
@@ -1,3 +1,4 @@
+mod archive_package;
 mod repo_holder;
 mod repo_source;
 mod uesr_package_collection;
@@ -15,10 +16,10 @@ mod settings;
 mod unity_management;
 
 use crate::io;
-use crate::repository::RemoteRepository;
 use crate::repository::local::LocalCachedRepository;
+use crate::repository::{LoadRepositoryErr, RemoteRepository};
 use crate::traits::HttpClient;
-use crate::utils::to_vec_pretty_os_eol;
+use crate::utils::{to_vec_pretty_os_eol, write_atomic};
 use futures::prelude::*;
 use indexmap::IndexMap;
 use std::collections::HashSet;
@@ -35,10 +36,11 @@ pub(crate) use repo_source::RepoSource;
 #[cfg(feature = "experimental-unity-management")]
 pub use unity_management::*;
 
+pub use archive_package::ArchivePackage;
 #[cfg(feature = "vrc-get-litedb")]
 pub use litedb::VccDatabaseConnection;
-pub use package_collection::PackageCollection;
-pub use package_installer::PackageInstaller;
+pub use package_collection::{PackageCollection, RepositoryLoadError};
+pub use package_installer::{PackageChecksumMismatch, PackageInstaller, PackageSizeMismatch};
 pub use settings::Settings;
 pub use uesr_package_collection::UserPackageCollection;
 
@@ -56,20 +58,37 @@ pub async fn add_remote_repo(
     io: &impl EnvironmentIo,
     http: &impl HttpClient,
 ) -> Result<(), AddRepositoryErr> {
-    let (remote_repo, etag) = RemoteRepository::download(http, &url, &headers).await?;
+    // the environment variables are substituted only for the requests, and the settings and the
+    // cache keep the references
+    let mut source = RepoSource::new("".as_ref(), &headers, Some(&url));
+    source.substitute_env_vars()?;
+    let request_url = source.request_url().unwrap_or(&url);
+    let request_headers = source.substituted_headers().unwrap_or(&headers);
+
+    let local_cache = if url.scheme() == "file" {
+        RepoHolder::load_file_repo(io, &headers, request_url).await?
+    } else {
+        let Some((remote_repo, etag, last_modified)) = RemoteRepository::download_with_validators(
+            http,
+            request_url,
+            request_headers,
+            None,
+            None,
+        )
+        .await?
+        else {
+            return Err(LoadRepositoryErr::not_modified_without_validators().into());
+        };
+        let mut local_cache = LocalCachedRepository::new(remote_repo, headers.clone());
+        local_cache.set_etag(etag);
+        local_cache.set_last_modified(last_modified);
+        local_cache
+    };
 
-    if !settings.can_add_remote_repo(&url, &remote_repo) {
+    if !settings.can_add_remote_repo(&url, local_cache.repo()) {
         return Err(AddRepositoryErr::AlreadyAdded);
     }
 
-    let mut local_cache = LocalCachedRepository::new(remote_repo, headers.clone());
-    if let Some(etag) = etag {
-        local_cache
-            .vrc_get
-            .get_or_insert_with(Default::default)
-            .etag = etag;
-    }
-
     io.create_dir_all(REPO_CACHE_FOLDER.as_ref()).await?;
     let file_name = write_new_repo(&local_cache, io).await?;
     let repo_path = io.resolve(format!("{}/{}", REPO_CACHE_FOLDER, file_name).as_ref());
@@ -150,13 +169,12 @@ async fn write_new_repo(
     let guid_names = std::iter::from_fn(|| Some(format!("{}.json", uuid::Uuid::new_v4())));
 
     for file_name in id_names.chain(guid_names) {
-        match io
-            .create_new(format!("{}/{}", REPO_CACHE_FOLDER, file_name).as_ref())
-            .await
-        {
-            Ok(mut file) => {
-                file.write_all(&to_vec_pretty_os_eol(&local_cache)?).await?;
-                file.flush().await?;
+        let path = format!("{}/{}", REPO_CACHE_FOLDER, file_name);
+        match io.create_new(path.as_ref()).await {
+            Ok(file) => {
+                // the file is created only to reserve the name
+                drop(file);
+                write_atomic(io, path.as_ref(), &to_vec_pretty_os_eol(&local_cache)?).await?;
 
                 return Ok(file_name);
             }
@@ -229,6 +247,7 @@ pub enum AddRepositoryErr {
     Io(io::Error),
     AlreadyAdded,
     OfflineMode,
+    RepositoryAuthFailed(crate::RepositoryAuthFailed),
 }
 
 impl fmt::Display for AddRepositoryErr {
@@ -239,6 +258,7 @@ impl fmt::Display for AddRepositoryErr {
             AddRepositoryErr::OfflineMode => {
                 f.write_str("you can't add remote repo in offline mode")
             }
+            AddRepositoryErr::RepositoryAuthFailed(err) => fmt::Display::fmt(err, f),
         }
     }
 }
@@ -247,10 +267,21 @@ impl std::error::Error for AddRepositoryErr {}
 
 impl From<io::Error> for AddRepositoryErr {
     fn from(value: io::Error) -> Self {
+        if let Some(auth_failed) = crate::RepositoryAuthFailed::from_io_error(&value) {
+            return Self::RepositoryAuthFailed(crate::RepositoryAuthFailed::new(
+                auth_failed.url().clone(),
+            ));
+        }
         Self::Io(value)
     }
 }
 
+impl From<LoadRepositoryErr> for AddRepositoryErr {
+    fn from(value: LoadRepositoryErr) -> Self {
+        io::Error::from(value).into()
+    }
+}
+
 impl From<serde_json::Error> for AddRepositoryErr {
     fn from(value: serde_json::Error) -> Self {
         Self::Io(value.into())
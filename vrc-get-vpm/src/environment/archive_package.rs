@@ -0,0 +1,156 @@
+use crate::io;
+use crate::io::EnvironmentIo;
+use crate::repository::{LocalCachedRepository, RemoteRepository};
+use crate::utils::{MapResultExt, Sha256AsyncWrite, is_tarball_path, read_tarball};
+use crate::{PackageInfo, PackageManifest};
+use async_zip::base::read::seek::ZipFileReader;
+use futures::prelude::*;
+use indexmap::IndexMap;
+use serde_json::{Map, Value, json};
+use std::path::Path;
+use url::Url;
+
+/// The package in a zip file or a gzipped tarball on the local file system.
+///
+/// The package is installed like packages in remote repositories, and the locked entry records
+/// the `file://` url of the archive as the repository.
+#[derive(Debug, Clone)]
+pub struct ArchivePackage {
+    repo: LocalCachedRepository,
+    manifest: PackageManifest,
+}
+
+impl ArchivePackage {
+    /// Reads the `package.json` at the root of the zip file at `path`.
+    ///
+    /// If `path` ends with `.tgz` or `.tar.gz`, the file is read as a tarball like npm, so the
+    /// `package.json` is in the top-level folder of the tarball, which is usually `package`.
+    pub async fn load(io: &impl EnvironmentIo, path: &Path) -> io::Result<Self> {
+        let url = Url::from_file_path(io.resolve(path)).map_err(|()| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: not an absolute path", path.display()),
+            )
+        })?;
+
+        let mut hasher = Sha256AsyncWrite::new(io::sink());
+        io::copy(&mut io.open(path).await?, &mut hasher).await?;
+        let hash = hex::encode(&hasher.finalize().1[..]);
+
+        let mut package_json = read_package_json(io, path).await?;
+
+        let (Some(Value::String(name)), Some(Value::String(version))) = (
+            package_json.get("name").cloned(),
+            package_json.get("version").cloned(),
+        ) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{}: package.json does not have name or version",
+                    path.display()
+                ),
+            ));
+        };
+
+        package_json.insert("url".into(), url.as_str().into());
+        package_json.insert("zipSHA256".into(), hash.into());
+
+        let mut versions = Map::new();
+        versions.insert(version, Value::Object(package_json));
+        let mut packages = Map::new();
+        packages.insert(name, json!({ "versions": versions }));
+
+        let mut repo = Map::new();
+        repo.insert("name".into(), path.display().to_string().into());
+        repo.insert("url".into(), url.as_str().into());
+        repo.insert("packages".into(), Value::Object(packages));
+
+        let repo = RemoteRepository::parse(repo)?;
+        // the archive has exactly one version, which is accepted even if it's yanked
+        let Some(manifest) = (repo.get_packages().next())
+            .and_then(|x| x.all_versions().next())
+            .cloned()
+        else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: package.json is not valid", path.display()),
+            ));
+        };
+
+        Ok(Self {
+            repo: LocalCachedRepository::new_local(repo, IndexMap::new()),
+            manifest,
+        })
+    }
+
+    /// Returns the package to pass to the resolver.
+    pub fn package_info(&self) -> PackageInfo {
+        PackageInfo::remote(&self.manifest, &self.repo)
+    }
+}
+
+async fn read_package_json(io: &impl EnvironmentIo, path: &Path) -> io::Result<Map<String, Value>> {
+    let buffer = if path.to_str().is_some_and(is_tarball_path) {
+        read_tarball_package_json(io, path).await?
+    } else {
+        read_zip_package_json(io, path).await?
+    };
+
+    let slice = buffer.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(&buffer);
+    serde_json::from_slice(slice).map_err(|e| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: parsing package.json: {e}", path.display()),
+        )
+    })
+}
+
+async fn read_zip_package_json(io: &impl EnvironmentIo, path: &Path) -> io::Result<Vec<u8>> {
+    let file = io::BufReader::new(io.open(path).await?);
+    let mut zip_reader = ZipFileReader::new(file).await.err_mapped()?;
+
+    let index = (zip_reader.file().entries().iter())
+        .position(|x| x.filename().as_str().ok() == Some("package.json"))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{}: package.json not found at the root of the archive",
+                    path.display()
+                ),
+            )
+        })?;
+
+    let mut buffer = Vec::new();
+    zip_reader
+        .reader_without_entry(index)
+        .await
+        .err_mapped()?
+        .read_to_end(&mut buffer)
+        .await?;
+    Ok(buffer)
+}
+
+async fn read_tarball_package_json(io: &impl EnvironmentIo, path: &Path) -> io::Result<Vec<u8>> {
+    let mut tgz = Vec::new();
+    io.open(path).await?.read_to_end(&mut tgz).await?;
+
+    let entries = read_tarball(&tgz).map_err(|e| {
+        io::Error::new(
+            e.kind(),
+            format!("{}: reading tarball: {e}", path.display()),
+        )
+    })?;
+    (entries.into_iter())
+        .find(|x| x.path.as_os_str() == "package.json")
+        .and_then(|x| x.content)
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!(
+                    "{}: package.json not found in the top-level folder of the tarball",
+                    path.display()
+                ),
+            )
+        })
+}
@@ -1,20 +1,64 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use crate::PackageCollection as _;
 use crate::environment::{RepoHolder, Settings, UserPackageCollection};
 use crate::io::EnvironmentIo;
-use crate::repository::LocalCachedRepository;
+use crate::repositories_file::RepositoriesFile;
+use crate::repository::{LoadRepositoryErr, LocalCachedRepository};
 use crate::{HttpClient, PackageInfo, PackageManifest, UserRepoSetting, VersionSelector, io};
 use futures::prelude::*;
 use itertools::Itertools;
 use log::error;
+use url::Url;
 
 /// A immutable structure that holds information about all the packages.
 #[derive(Debug, Clone)]
 pub struct PackageCollection {
     pub(super) repositories: HashMap<Box<Path>, LocalCachedRepository>,
     pub(super) user_packages: Vec<(PathBuf, PackageManifest)>,
+    pub(super) load_errors: Vec<RepositoryLoadError>,
+}
+
+/// The error occurred while loading a repository.
+///
+/// Repositories failed to load are skipped so other repositories are still available.
+#[derive(Debug, Clone)]
+pub struct RepositoryLoadError {
+    path: Box<Path>,
+    url: Option<Url>,
+    error: Arc<io::Error>,
+}
+
+impl RepositoryLoadError {
+    pub(crate) fn new(path: Box<Path>, url: Option<Url>, error: io::Error) -> Self {
+        Self {
+            path,
+            url,
+            error: Arc::new(error),
+        }
+    }
+
+    /// The path to the repository cache file, or the local repository file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn url(&self) -> Option<&Url> {
+        self.url.as_ref()
+    }
+
+    pub fn error(&self) -> &io::Error {
+        &self.error
+    }
+
+    /// The reason of the failure downloading the repository, if the repository was downloaded.
+    ///
+    /// This returns `None` for network errors. See [`LoadRepositoryErr::from_io_error`].
+    pub fn load_error(&self) -> Option<&LoadRepositoryErr> {
+        LoadRepositoryErr::from_io_error(&self.error)
+    }
 }
 
 impl PackageCollection {
@@ -22,22 +66,85 @@ impl PackageCollection {
         Self {
             repositories: HashMap::new(),
             user_packages: Vec::new(),
+            load_errors: Vec::new(),
         }
     }
 
+    /// Loads the packages from the repositories in the settings.
+    ///
+    /// Without `http`, this is the offline mode: the repositories are read only from the cache
+    /// and never downloaded. Repositories not cached fail to load with
+    /// [`LoadRepositoryErr::RepositoryNotCached`], reported in [`Self::repository_errors`].
     pub async fn load(
         settings: &Settings,
         io: &impl EnvironmentIo,
         http: Option<&impl HttpClient>,
+    ) -> io::Result<Self> {
+        Self::load_inner(settings, io, http, None).await
+    }
+
+    /// Loads the packages from the repositories in the settings, caching the remote repositories
+    /// in `cache_root` instead of the paths in the settings.
+    ///
+    /// The cache files are named after the urls of the repositories, so environments and jobs
+    /// pointing at the same cache root share one cache of each repository, like a pre-warmed
+    /// volume in CI. The cache files are replaced atomically, so loading concurrently from
+    /// multiple processes doesn't corrupt the cache; the last download wins.
+    /// A relative `cache_root` is resolved from the environment folder.
+    pub async fn load_with_cache_root(
+        settings: &Settings,
+        io: &impl EnvironmentIo,
+        http: Option<&impl HttpClient>,
+        cache_root: &Path,
+    ) -> io::Result<Self> {
+        Self::load_inner(settings, io, http, Some(cache_root)).await
+    }
+
+    async fn load_inner(
+        settings: &Settings,
+        io: &impl EnvironmentIo,
+        http: Option<&impl HttpClient>,
+        cache_root: Option<&Path>,
     ) -> io::Result<Self> {
         let (repositories, user_packages) = futures::try_join!(
-            RepoHolder::load(settings, io, http),
+            RepoHolder::load(settings, io, http, cache_root),
             UserPackageCollection::load(settings, io).map(Ok)
         )?;
 
+        let (repositories, load_errors) = repositories.into_repos();
+
         Ok(Self {
-            repositories: repositories.into_repos(),
+            repositories,
             user_packages: user_packages.into_packages(),
+            load_errors,
+        })
+    }
+
+    /// Loads the packages from the repositories in the list instead of the ones in the settings.
+    ///
+    /// This is useful to resolve projects reproducibly regardless of the settings of the machine.
+    /// The precedence rules are:
+    /// - Only the repositories in the list are loaded. The official and curated repositories
+    ///   are not added automatically, so list them if needed.
+    /// - User repositories and user packages in the settings are ignored.
+    /// - Other settings, like the number of repositories fetched concurrently, are still used.
+    ///
+    /// The repositories are cached in `Repos/override` folder of the environment separately from
+    /// the repositories in the settings.
+    pub async fn load_with_repositories(
+        settings: &Settings,
+        io: &impl EnvironmentIo,
+        http: Option<&impl HttpClient>,
+        repositories: &RepositoriesFile,
+    ) -> io::Result<Self> {
+        let repositories = RepoHolder::load_overridden(settings, io, http, repositories).await?;
+
+        let (repositories, load_errors) = repositories.into_repos();
+
+        Ok(Self {
+            repositories,
+            user_packages: Vec::new(),
+            load_errors,
         })
     }
 
@@ -66,13 +173,52 @@ impl PackageCollection {
         &self.user_packages
     }
 
+    /// Returns the errors of repositories failed to load.
+    pub fn repository_errors(&self) -> &[RepositoryLoadError] {
+        &self.load_errors
+    }
+
+    /// Returns the collection with only the repositories whose id or url is in `repositories`.
+    ///
+    /// User packages are not included, so resolving with the returned collection checks whether the
+    /// packages are available from the repositories alone, like only from the official repository.
+    /// Errors of the repositories not in the list are also dropped.
+    pub fn only_repositories(&self, repositories: &[&str]) -> Self {
+        let matches = |repository: &str, id: Option<&str>, url: Option<&Url>| {
+            id == Some(repository) || url.is_some_and(|url| url.as_str() == repository)
+        };
+        let allowed = |id: Option<&str>, url: Option<&Url>| {
+            (repositories.iter()).any(|&repository| matches(repository, id, url))
+        };
+
+        let filtered = (self.repositories.iter())
+            .filter(|(_, repo)| allowed(repo.id(), repo.url()))
+            .map(|(path, repo)| (path.clone(), repo.clone()))
+            .collect::<HashMap<_, _>>();
+
+        for &repository in repositories {
+            if !(filtered.values()).any(|repo| matches(repository, repo.id(), repo.url())) {
+                log::warn!("repository {repository} is not in the collection");
+            }
+        }
+
+        Self {
+            repositories: filtered,
+            user_packages: Vec::new(),
+            load_errors: (self.load_errors.iter())
+                .filter(|x| allowed(None, x.url()))
+                .cloned()
+                .collect(),
+        }
+    }
+
     pub fn find_whole_all_packages(
         &self,
         version_selector: VersionSelector,
         filter: impl Fn(&PackageManifest) -> bool,
     ) -> Vec<PackageInfo> {
         self.get_all_packages()
-            .filter(|x| version_selector.satisfies(x.package_json()))
+            .filter(|x| version_selector.satisfies_package(x))
             .into_group_map_by(|x| x.name())
             .values()
             .map(|versions| versions.iter().max_by_key(|x| x.version()).unwrap())
@@ -99,12 +245,7 @@ impl crate::PackageCollection for PackageCollection {
     }
 
     fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
-        let remote = self.repositories.values().flat_map(|repo| {
-            repo.repo
-                .get_packages()
-                .flat_map(|x| x.all_versions())
-                .map(|pkg| PackageInfo::remote(pkg, repo))
-        });
+        let remote = (self.repositories.values()).flat_map(|repo| repo.all_packages());
         let local = self
             .user_packages
             .iter()
@@ -138,7 +279,7 @@ impl crate::PackageCollection for PackageCollection {
             repo.repo
                 .get_package(package)
                 .into_iter()
-                .flat_map(|pkg| pkg.get_latest(package_selector))
+                .flat_map(|pkg| pkg.get_latest(package_selector.in_repository(repo.id())))
                 .map(|pkg| PackageInfo::remote(pkg, repo))
         });
 
@@ -148,6 +289,12 @@ impl crate::PackageCollection for PackageCollection {
             .filter(move |(_, json)| json.name() == package && package_selector.satisfies(json))
             .map(|(path, json)| PackageInfo::local(json, path));
 
-        remote.chain(local).max_by_key(|x| x.version())
+        let preferred = |x: &PackageInfo| package_selector.is_preferred(x.package_json());
+        let deprioritized = |x: &PackageInfo| package_selector.is_deprioritized(x.package_json());
+        if package_selector.prefers_lowest() {
+            (remote.chain(local)).min_by_key(|x| (!preferred(x), deprioritized(x), x.version()))
+        } else {
+            (remote.chain(local)).max_by_key(|x| (preferred(x), !deprioritized(x), x.version()))
+        }
     }
 }
@@ -1,13 +1,18 @@
 use crate::environment::REPO_CACHE_FOLDER;
 use crate::io::{EnvironmentIo, ProjectIo};
 use crate::repository::LocalCachedRepository;
-use crate::traits::AbortCheck;
-use crate::utils::Sha256AsyncWrite;
+use crate::traits::{AbortCheck, InstallProgress, rewritten_url_error};
+use crate::utils::{
+    Sha256AsyncWrite, atomic_temp_path, is_tarball_path, load_json_or_default, save_json,
+    write_atomic,
+};
 use crate::{HttpClient, PackageInfo, PackageManifest, io};
+use futures::lock::Mutex;
 use futures::prelude::*;
 use hex::FromHex;
 use indexmap::IndexMap;
-use log::{debug, error};
+use log::debug;
+use serde::{Deserialize, Serialize};
 use std::io::SeekFrom;
 use std::path::{Path, PathBuf};
 use std::pin::pin;
@@ -16,11 +21,51 @@ use url::Url;
 pub struct PackageInstaller<'a, T: HttpClient, IO: EnvironmentIo> {
     pub(super) io: &'a IO,
     pub(super) http: Option<&'a T>,
+    pub(super) progress: Option<&'a dyn InstallProgress>,
+    pub(super) trust_on_first_use: Option<TrustOnFirstUse>,
+    pub(super) cache_root: Option<&'a Path>,
 }
 
 impl<'a, T: HttpClient, IO: EnvironmentIo> PackageInstaller<'a, T, IO> {
     pub fn new(io: &'a IO, http: Option<&'a T>) -> Self {
-        Self { io, http }
+        Self {
+            io,
+            http,
+            progress: None,
+            trust_on_first_use: None,
+            cache_root: None,
+        }
+    }
+
+    /// Reports the progress of downloading and extracting packages to `progress`.
+    pub fn with_progress(mut self, progress: &'a dyn InstallProgress) -> Self {
+        self.progress = Some(progress);
+        self
+    }
+
+    /// Verifies packages without `zipSHA256` in the repository against the first download.
+    ///
+    /// The hash and size of the zip file are recorded in the environment on the first download,
+    /// keyed by the repository, the name, and the version of the package. Later downloads differing
+    /// from the record fail with [`PackageChecksumMismatch`] or [`PackageSizeMismatch`], which
+    /// detects repositories re-publishing different files under the same version.
+    ///
+    /// This is opt-in since the first download is trusted without any verification.
+    pub fn with_trust_on_first_use(mut self, trust_on_first_use: bool) -> Self {
+        self.trust_on_first_use = trust_on_first_use.then(TrustOnFirstUse::new);
+        self
+    }
+
+    /// Caches the zip files of the packages in `cache_root` instead of the `Repos` folder.
+    ///
+    /// This is for sharing the download cache across environments, like the repository caches
+    /// with [`PackageCollection::load_with_cache_root`]. A relative `cache_root` is resolved
+    /// from the environment folder.
+    ///
+    /// [`PackageCollection::load_with_cache_root`]: crate::environment::PackageCollection::load_with_cache_root
+    pub fn with_cache_root(mut self, cache_root: &'a Path) -> Self {
+        self.cache_root = Some(cache_root);
+        self
     }
 }
 
@@ -37,20 +82,40 @@ impl<T: HttpClient, IO: EnvironmentIo> crate::PackageInstaller for PackageInstal
         let dest_folder = PathBuf::from(format!("Packages/{}", package.name()));
         match package.inner {
             PackageInfoInner::Remote(package, user_repo) => {
-                let zip_file = get_package(self.io, self.http, user_repo, package).await?;
+                let zip_file = get_package(
+                    self.io,
+                    self.http,
+                    self.progress,
+                    self.trust_on_first_use.as_ref(),
+                    self.cache_root,
+                    abort,
+                    user_repo,
+                    package,
+                )
+                .await?;
 
                 // downloading may take a long time, so check abort again
                 abort.check()?;
 
-                let zip_file = io::BufReader::new(zip_file);
-
                 debug!(
                     "Extracting zip file for {}@{}",
                     package.name(),
                     package.version()
                 );
                 // remove dest folder before extract if exists
-                if let Err(e) = crate::utils::extract_zip(zip_file, io, &dest_folder).await {
+                let on_progress = |done, total| {
+                    if let Some(progress) = self.progress {
+                        progress.extract(package.name(), done, total);
+                    }
+                };
+                let extracted = if is_tarball_package(package) {
+                    crate::utils::extract_tarball(zip_file, io, &dest_folder, abort, on_progress)
+                        .await
+                } else {
+                    let zip_file = io::BufReader::new(zip_file);
+                    crate::utils::extract_zip(zip_file, io, &dest_folder, abort, on_progress).await
+                };
+                if let Err(e) = extracted {
                     // if an error occurs, try to remove the dest folder
                     log::debug!(
                         "Error occurred while extracting zip file for {}@{}: {}",
@@ -73,77 +138,364 @@ impl<T: HttpClient, IO: EnvironmentIo> crate::PackageInstaller for PackageInstal
                 crate::utils::copy_recursive(self.io, path.into(), io, dest_folder).await?;
                 Ok(())
             }
+            PackageInfoInner::Git(git) => {
+                let path = git.path().to_path_buf();
+                crate::utils::copy_recursive(self.io, path, io, dest_folder.clone()).await?;
+                // the git metadata is not a part of the package
+                io.remove_dir_all(&dest_folder.join(".git")).await.ok();
+                Ok(())
+            }
+            PackageInfoInner::Hypothetical(package) => Err(hypothetical_error(package)),
+        }
+    }
+
+    async fn check_package(&self, package: PackageInfo<'_>) -> io::Result<()> {
+        use crate::PackageInfoInner;
+        match package.inner {
+            PackageInfoInner::Remote(package, user_repo) => {
+                check_remote_package(self.io, self.http, self.cache_root, user_repo, package).await
+            }
+            PackageInfoInner::Local(_, path) => self.io.metadata(path).await.map(|_| ()),
+            PackageInfoInner::Git(git) => self.io.metadata(git.path()).await.map(|_| ()),
+            PackageInfoInner::Hypothetical(package) => Err(hypothetical_error(package)),
         }
     }
 }
 
+fn hypothetical_error(package: &PackageManifest) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "{}@{} is hypothetical and cannot be installed",
+            package.name(),
+            package.version()
+        ),
+    )
+}
+
+/// Returns the headers for downloading the package from the repository.
+fn package_headers<'a>(
+    repository: &'a LocalCachedRepository,
+    package: &'a PackageManifest,
+) -> IndexMap<&'a str, &'a str> {
+    IndexMap::from_iter(
+        (repository
+            .headers()
+            .iter()
+            .map(|(k, v)| (k.as_ref(), v.as_ref())))
+        .chain(
+            package
+                .headers()
+                .iter()
+                .map(|(k, v)| (k.as_ref(), v.as_ref())),
+        ),
+    )
+}
+
+/// Returns true if the package is distributed as a gzipped tarball instead of a zip file.
+fn is_tarball_package(package: &PackageManifest) -> bool {
+    package.url().is_some_and(|x| is_tarball_path(x.path()))
+}
+
+/// Returns the paths to the cached archive of the package and its sha256 file.
+fn package_cache_paths(cache_root: Option<&Path>, package: &PackageManifest) -> (PathBuf, PathBuf) {
+    let extension = if is_tarball_package(package) {
+        "tgz"
+    } else {
+        "zip"
+    };
+    let zip_file_name = format!(
+        "vrc-get-{}-{}.{extension}",
+        package.name(),
+        package.version()
+    );
+    let zip_path = (cache_root.unwrap_or(REPO_CACHE_FOLDER.as_ref()))
+        .join(package.name())
+        .join(zip_file_name);
+    let sha_path = zip_path.with_extension(format!("{extension}.sha256"));
+    (zip_path, sha_path)
+}
+
+/// Returns the path of the local file if the url of the package is a `file://` url.
+///
+/// Only packages in repositories loaded from the local file system may refer to local files,
+/// so remote repositories cannot make the installer read or probe arbitrary local files.
+fn local_package_path(
+    repository: &LocalCachedRepository,
+    url: &Url,
+) -> io::Result<Option<PathBuf>> {
+    if url.scheme() != "file" {
+        return Ok(None);
+    }
+    if !repository.local {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{url}: file urls are allowed only in local repositories"),
+        ));
+    }
+    url.to_file_path().map(Some).map_err(|()| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid file url: {url}"),
+        )
+    })
+}
+
+/// Checks the zip file of the package is cached or can be downloaded, without downloading it.
+async fn check_remote_package<T: HttpClient, IO: EnvironmentIo>(
+    io: &IO,
+    http: Option<&T>,
+    cache_root: Option<&Path>,
+    repository: &LocalCachedRepository,
+    package: &PackageManifest,
+) -> io::Result<()> {
+    let (zip_path, sha_path) = package_cache_paths(cache_root, package);
+
+    if (try_load_package_cache(io, &zip_path, &sha_path, package.zip_sha_256()).await).is_some() {
+        return Ok(());
+    }
+
+    let url = package.url().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "URL field of the package.json in the repository empty",
+        )
+    })?;
+
+    if let Some(local_path) = local_package_path(repository, url)? {
+        return io.metadata(&local_path).await.map(|_| ());
+    }
+
+    let Some(http) = http else {
+        return Err(io::Error::new(io::ErrorKind::NotFound, "Offline mode"));
+    };
+
+    let rewritten = http.rewrite_url(url)?;
+    (http.head(
+        rewritten.as_ref().unwrap_or(url),
+        &package_headers(repository, package),
+    ))
+    .await
+    .map_err(|err| rewritten_url_error(url, rewritten.as_ref(), err))
+}
+
 async fn get_package<T: HttpClient, IO: EnvironmentIo>(
     io: &IO,
     http: Option<&T>,
+    progress: Option<&dyn InstallProgress>,
+    trust_on_first_use: Option<&TrustOnFirstUse>,
+    cache_root: Option<&Path>,
+    abort: &AbortCheck,
     repository: &LocalCachedRepository,
     package: &PackageManifest,
 ) -> io::Result<IO::FileStream> {
-    let zip_file_name = format!("vrc-get-{}-{}.zip", &package.name(), package.version());
-    let zip_path = PathBuf::from(format!(
-        "{}/{}/{}",
-        REPO_CACHE_FOLDER,
-        package.name(),
-        &zip_file_name
-    ));
-    let sha_path = zip_path.with_extension("zip.sha256");
+    let (zip_path, sha_path) = package_cache_paths(cache_root, package);
 
-    if let Some(cache_file) =
+    if let Some((cache_file, size, hash)) =
         try_load_package_cache(io, &zip_path, &sha_path, package.zip_sha_256()).await
     {
         debug!("using cache for {}@{}", package.name(), package.version());
+        // the cache may be downloaded before the first download is recorded, or replaced
+        if let Some(trust) = trust_on_first_use {
+            trust.verify(io, repository, package, size, &hash).await?;
+        }
         Ok(cache_file)
     } else {
         io.create_dir_all(zip_path.parent().unwrap()).await?;
 
-        let new_headers = IndexMap::from_iter(
-            (repository
-                .headers()
-                .iter()
-                .map(|(k, v)| (k.as_ref(), v.as_ref())))
-            .chain(
-                package
-                    .headers()
-                    .iter()
-                    .map(|(k, v)| (k.as_ref(), v.as_ref())),
-            ),
-        );
-
-        let (zip_file, zip_hash) = download_package_zip(
+        let new_headers = package_headers(repository, package);
+
+        let zip_file = download_package_zip(
             http,
             io,
             &new_headers,
             &zip_path,
             &sha_path,
-            &zip_file_name,
-            package.url().ok_or_else(|| {
-                io::Error::new(
-                    io::ErrorKind::InvalidData,
-                    "URL field of the package.json in the repository empty",
-                )
-            })?,
+            progress,
+            trust_on_first_use,
+            abort,
+            repository,
+            package,
         )
         .await?;
 
-        if let Some(repo_hash) = package
-            .zip_sha_256()
-            .and_then(|x| <[u8; 256 / 8] as FromHex>::from_hex(x).ok())
-        {
-            if repo_hash != zip_hash {
-                error!(
-                    "Package hash mismatched! This will be hard error in the future!: {} v{}",
-                    package.name(),
-                    package.version()
-                );
-                //return None;
+        Ok(zip_file)
+    }
+}
+
+/// The error returned when the hash of the downloaded zip file doesn't match the `zipSHA256`
+/// declared in the repository.
+///
+/// This is wrapped in [`io::Error`] with [`io::ErrorKind::InvalidData`].
+#[derive(Debug)]
+pub struct PackageChecksumMismatch {
+    pub expected: Box<str>,
+    pub actual: Box<str>,
+}
+
+impl std::fmt::Display for PackageChecksumMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "package checksum mismatched: expected {}, actual {}",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for PackageChecksumMismatch {}
+
+/// The error returned when the size of the downloaded zip file doesn't match the `zipSize`
+/// declared in the repository.
+///
+/// This is wrapped in [`io::Error`] with [`io::ErrorKind::InvalidData`].
+#[derive(Debug)]
+pub struct PackageSizeMismatch {
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl std::fmt::Display for PackageSizeMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "package size mismatched: expected {} bytes, actual {} bytes",
+            self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for PackageSizeMismatch {}
+
+/// Verifies the downloaded zip file against the size and hash declared in the repository.
+fn verify_package_zip(
+    package: &PackageManifest,
+    size: u64,
+    hash: &[u8; 256 / 8],
+) -> io::Result<()> {
+    // check the size first since it's cheaper and catches truncated downloads
+    if let Some(expected) = package.zip_size() {
+        if expected != size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                PackageSizeMismatch {
+                    expected,
+                    actual: size,
+                },
+            ));
+        }
+    }
+
+    if let Some(expected) = package.zip_sha_256() {
+        if let Ok(repo_hash) = <[u8; 256 / 8] as FromHex>::from_hex(expected) {
+            if &repo_hash != hash {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    PackageChecksumMismatch {
+                        expected: expected.to_ascii_lowercase().into(),
+                        actual: hex::encode(hash).into(),
+                    },
+                ));
             }
         }
+    }
 
-        Ok(zip_file)
+    Ok(())
+}
+
+const INTEGRITY_CACHE_PATH: &str = "vrc-get/package-integrity.json";
+
+/// The hash and size of the zip file of a package on the first download.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+struct FirstSeenIntegrity {
+    sha256: Box<str>,
+    size: u64,
+}
+
+// repository -> name@version -> integrity
+type IntegrityRecords = IndexMap<Box<str>, IndexMap<Box<str>, FirstSeenIntegrity>>;
+
+/// The records of the first downloads of packages without hashes in the repository.
+pub(super) struct TrustOnFirstUse {
+    // the records loaded on the first verification. this is locked while updating the records
+    // since packages are downloaded concurrently
+    records: Mutex<Option<IntegrityRecords>>,
+}
+
+impl TrustOnFirstUse {
+    fn new() -> Self {
+        Self {
+            records: Mutex::new(None),
+        }
+    }
+
+    /// Verifies the zip file against the first download, or records it if it's the first download.
+    ///
+    /// The records are saved only when a new record is added.
+    async fn verify(
+        &self,
+        io: &impl EnvironmentIo,
+        repository: &LocalCachedRepository,
+        package: &PackageManifest,
+        size: u64,
+        hash: &[u8; 256 / 8],
+    ) -> io::Result<()> {
+        // packages with hashes are verified against the repository
+        if package.zip_sha_256().is_some() {
+            return Ok(());
+        }
+        let Some(repository_key) = (repository.url().map(|x| x.as_str())).or(repository.id())
+        else {
+            return Ok(());
+        };
+
+        let mut guard = self.records.lock().await;
+        if guard.is_none() {
+            *guard = Some(load_json_or_default(io, INTEGRITY_CACHE_PATH.as_ref()).await?);
+        }
+        let records = guard.as_mut().unwrap();
+        let key = format!("{}@{}", package.name(), package.version());
+        let actual = FirstSeenIntegrity {
+            sha256: hex::encode(hash).into(),
+            size,
+        };
+
+        let packages = records.entry(repository_key.into()).or_default();
+        match packages.get(key.as_str()) {
+            Some(first_seen) if first_seen == &actual => Ok(()),
+            Some(first_seen) => {
+                log::error!(
+                    "the zip file of {key} from {repository_key} differs from the first download! \
+                    was the version re-published? first seen: sha256 {} ({} bytes), now: sha256 {} ({} bytes)",
+                    first_seen.sha256,
+                    first_seen.size,
+                    actual.sha256,
+                    actual.size,
+                );
+                if first_seen.size != size {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        PackageSizeMismatch {
+                            expected: first_seen.size,
+                            actual: size,
+                        },
+                    ))
+                } else {
+                    Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        PackageChecksumMismatch {
+                            expected: first_seen.sha256.clone(),
+                            actual: actual.sha256,
+                        },
+                    ))
+                }
+            }
+            None => {
+                debug!("recording the integrity of {key} from {repository_key} on first use");
+                packages.insert(key.into(), actual);
+                save_json(io, INTEGRITY_CACHE_PATH.as_ref(), records).await
+            }
+        }
     }
 }
 
@@ -155,13 +507,13 @@ async fn get_package<T: HttpClient, IO: EnvironmentIo>(
 /// * `sha_path`: the path to sha256 file
 /// * `sha256`: sha256 hash if specified
 ///
-/// returns: Option<File> readable zip file or None
+/// returns: Option<(File, u64, [u8; 32])> readable zip file with the size and the hash, or None
 async fn try_load_package_cache<IO: EnvironmentIo>(
     io: &IO,
     zip_path: &Path,
     sha_path: &Path,
     sha256: Option<&str>,
-) -> Option<IO::FileStream> {
+) -> Option<(IO::FileStream, u64, [u8; 256 / 8])> {
     let mut cache_file = io.open(zip_path).await.ok()?;
 
     let mut buf = [0u8; 256 / 4];
@@ -183,16 +535,16 @@ async fn try_load_package_cache<IO: EnvironmentIo>(
 
     let mut hasher = Sha256AsyncWrite::new(io::sink());
 
-    io::copy(&mut cache_file, &mut hasher).await.ok()?;
+    let size = io::copy(&mut cache_file, &mut hasher).await.ok()?;
 
-    let hash = &hasher.finalize().1[..];
-    if hash != &hex[..] {
+    let hash: [u8; 256 / 8] = hasher.finalize().1.into();
+    if hash != hex {
         return None;
     }
 
     cache_file.seek(SeekFrom::Start(0)).await.ok()?;
 
-    Some(cache_file)
+    Some((cache_file, size, hash))
 }
 
 /// downloads the zip file from the url to the specified path
@@ -202,45 +554,131 @@ async fn try_load_package_cache<IO: EnvironmentIo>(
 /// * `http`: http client. returns error if none
 /// * `zip_path`: the path to zip file
 /// * `sha_path`: the path to sha256 file
-/// * `zip_file_name`: the name of zip file. will be used in the sha file
-/// * `url`: url to zip file
+/// * `progress`: the reporter of downloading progress
+/// * `trust_on_first_use`: the records of first downloads the zip file is verified against
+/// * `abort`: checked while downloading. the partially downloaded file is removed on abort
+/// * `repository`: the repository of the package. only local repositories may use `file://` urls
+/// * `package`: the package manifest with the url, and the declared size and hash of the zip file
 ///
 /// returns: Result<File, Error> the readable zip file.
+/// The file is removed if it doesn't match the size or hash declared in the repository,
+/// or the first download with `trust_on_first_use`.
+#[allow(clippy::too_many_arguments)]
 async fn download_package_zip<IO: EnvironmentIo>(
     http: Option<&impl HttpClient>,
     io: &IO,
     headers: &IndexMap<&str, &str>,
     zip_path: &Path,
     sha_path: &Path,
-    zip_file_name: &str,
-    url: &Url,
-) -> io::Result<(IO::FileStream, [u8; 256 / 8])> {
-    let Some(http) = http else {
+    progress: Option<&dyn InstallProgress>,
+    trust_on_first_use: Option<&TrustOnFirstUse>,
+    abort: &AbortCheck,
+    repository: &LocalCachedRepository,
+    package: &PackageManifest,
+) -> io::Result<IO::FileStream> {
+    let url = package.url().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            "URL field of the package.json in the repository empty",
+        )
+    })?;
+
+    // packages in local repositories can be copied even in offline mode
+    let local_path = local_package_path(repository, url)?;
+
+    if local_path.is_none() && http.is_none() {
         return Err(io::Error::new(io::ErrorKind::NotFound, "Offline mode"));
-    };
+    }
 
-    // file not found: err
-    let cache_file = io.create(zip_path).await?;
+    // the zip file is downloaded to the temporary file and renamed after verified, so other
+    // processes sharing the cache never see the partially downloaded file
+    let temp_path = atomic_temp_path(zip_path);
+    let temp_file = io.create(&temp_path).await?;
+    let mut writer = Sha256AsyncWrite::new(temp_file);
 
     debug!("Download started for {}", url);
-    let mut response = pin!(http.get(url, headers).await?);
-
-    let mut writer = Sha256AsyncWrite::new(cache_file);
-    io::copy(&mut response, &mut writer).await?;
+    let on_progress = |done| {
+        if let Some(progress) = progress {
+            progress.download(package.name(), done, package.zip_size());
+        }
+    };
+    let downloaded = async {
+        let mut size = 0;
+        if let Some(local_path) = local_path {
+            let file = io.open(&local_path).await?;
+            size = copy_with_progress(file, &mut writer, abort, on_progress).await?;
+        } else if let Some(http) = http {
+            let rewritten = http.rewrite_url(url)?;
+            let response = http
+                .get(rewritten.as_ref().unwrap_or(url), headers)
+                .await
+                .map_err(|err| rewritten_url_error(url, rewritten.as_ref(), err))?;
+            let response = pin!(response);
+            size = copy_with_progress(response, &mut writer, abort, on_progress).await?;
+        }
+        Ok(size)
+    }
+    .await;
+    let size = match downloaded {
+        Ok(size) => size,
+        Err(e) => {
+            // do not leave the partially downloaded file
+            drop(writer);
+            io.remove_file(&temp_path).await.ok();
+            return Err(e);
+        }
+    };
     debug!("finished downloading {}", url);
 
-    let (mut cache_file, hash) = writer.finalize();
+    let (mut temp_file, hash) = writer.finalize();
     let hash: [u8; 256 / 8] = hash.into();
 
-    cache_file.flush().await?;
-    cache_file.seek(SeekFrom::Start(0)).await?;
+    let verified = match (verify_package_zip(package, size, &hash), trust_on_first_use) {
+        (Ok(()), Some(trust)) => trust.verify(io, repository, package, size, &hash).await,
+        (verified, _) => verified,
+    };
+    // the temporary file is closed before renamed, and dropped on errors
+    let saved = async {
+        verified?;
+        temp_file.flush().await?;
+        drop(temp_file);
+        io.rename(&temp_path, zip_path).await
+    }
+    .await;
+    if let Err(e) = saved {
+        io.remove_file(&temp_path).await.ok();
+        return Err(e);
+    }
 
     // write sha file
-    io.write(
+    let zip_file_name = zip_path.file_name().unwrap_or_default().to_string_lossy();
+    write_atomic(
+        io,
         sha_path,
         format!("{} {}\n", hex::encode(&hash[..]), zip_file_name).as_bytes(),
     )
     .await?;
 
-    Ok((cache_file, hash))
+    io.open(zip_path).await
+}
+
+/// copies `reader` to `writer` like [`io::copy`], calling `on_progress` with the copied bytes so far
+async fn copy_with_progress(
+    mut reader: impl AsyncRead + Unpin,
+    writer: &mut (impl AsyncWrite + Unpin),
+    abort: &AbortCheck,
+    mut on_progress: impl FnMut(u64),
+) -> io::Result<u64> {
+    let mut buf = vec![0u8; 64 * 1024];
+    let mut done = 0;
+    loop {
+        abort.check()?;
+        let read = reader.read(&mut buf).await?;
+        if read == 0 {
+            return Ok(done);
+        }
+        writer.write_all(&buf[..read]).await?;
+        done += read as u64;
+        on_progress(done);
+    }
 }
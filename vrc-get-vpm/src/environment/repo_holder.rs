@@ -1,40 +1,57 @@
-use crate::environment::repo_source::RepoSource;
+use crate::environment::repo_source::{RepoSource, cache_file_name};
 use crate::environment::{
-    CURATED_URL_STR, LOCAL_CURATED_PATH, LOCAL_OFFICIAL_PATH, OFFICIAL_URL_STR, Settings,
+    CURATED_URL_STR, LOCAL_CURATED_PATH, LOCAL_OFFICIAL_PATH, OFFICIAL_URL_STR,
+    RepositoryLoadError, Settings,
 };
 use crate::io::EnvironmentIo;
-use crate::repository::RemoteRepository;
+use crate::repositories_file::RepositoriesFile;
 use crate::repository::local::LocalCachedRepository;
+use crate::repository::{LoadRepositoryErr, RemoteRepository};
 use crate::traits::HttpClient;
-use crate::utils::{read_json_file, to_vec_pretty_os_eol, try_load_json};
+use crate::utils::{
+    deserialize_json, read_json_file, to_vec_pretty_os_eol, try_load_json, write_atomic,
+};
 use crate::{UserRepoSetting, io};
-use futures::future::join_all;
+use futures::prelude::*;
+use futures::stream;
 use indexmap::IndexMap;
 use lazy_static::lazy_static;
 use log::{error, warn};
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::path::Path;
 use url::Url;
 
+type JsonMap = Map<String, Value>;
+
+const OVERRIDE_CACHE_FOLDER: &str = "Repos/override";
+
 #[derive(Debug)]
 pub(crate) struct RepoHolder {
     cached_repos_new: HashMap<Box<Path>, LocalCachedRepository>,
+    load_errors: Vec<RepositoryLoadError>,
 }
 
 impl RepoHolder {
     pub(crate) fn new() -> Self {
         RepoHolder {
             cached_repos_new: HashMap::new(),
+            load_errors: Vec::new(),
         }
     }
 }
 
 // new system
 impl RepoHolder {
+    /// Loads the predefined and user repositories.
+    ///
+    /// If `cache_root` is specified, the caches of the remote repositories are stored there
+    /// instead of the paths in the settings. See [`RepoSource::with_cache_root`].
     pub(crate) async fn load(
         settings: &Settings,
         io: &impl EnvironmentIo,
         http: Option<&impl HttpClient>,
+        cache_root: Option<&Path>,
     ) -> io::Result<Self> {
         let predefined_repos = Self::get_predefined_repos(settings).into_iter();
         let user_repos = settings
@@ -42,9 +59,50 @@ impl RepoHolder {
             .iter()
             .map(UserRepoSetting::to_source);
         io.create_dir_all("Repos".as_ref()).await?;
+        if let Some(cache_root) = cache_root {
+            io.create_dir_all(cache_root).await?;
+        }
+        let sources = predefined_repos
+            .chain(user_repos)
+            .map(|src| match cache_root {
+                Some(cache_root) => src.with_cache_root(cache_root),
+                None => src,
+            });
         let mut repo_cache = Self::new();
         repo_cache
-            .load_repos(http, io, predefined_repos.chain(user_repos))
+            .load_repos(
+                http,
+                io,
+                sources,
+                settings.max_concurrent_repository_fetches(),
+            )
+            .await?;
+
+        Ok(repo_cache)
+    }
+
+    /// Loads the repositories in the list instead of predefined and user repositories.
+    pub(crate) async fn load_overridden(
+        settings: &Settings,
+        io: &impl EnvironmentIo,
+        http: Option<&impl HttpClient>,
+        repositories: &RepositoriesFile,
+    ) -> io::Result<Self> {
+        let cache_paths = (repositories.repositories().iter())
+            .map(|repo| Path::new(OVERRIDE_CACHE_FOLDER).join(cache_file_name(repo.url())))
+            .collect::<Vec<_>>();
+        let sources = (repositories.repositories().iter())
+            .zip(&cache_paths)
+            .map(|(repo, path)| RepoSource::new(path, repo.headers(), Some(repo.url())));
+        io.create_dir_all(OVERRIDE_CACHE_FOLDER.as_ref()).await?;
+        let mut repo_cache = Self::new();
+        repo_cache
+            .load_repos(
+                http,
+                io,
+                sources,
+                settings.max_concurrent_repository_fetches(),
+            )
             .await?;
 
         Ok(repo_cache)
@@ -87,24 +145,45 @@ impl RepoHolder {
         http: Option<&impl HttpClient>,
         io: &IO,
         sources: impl Iterator<Item = RepoSource<'a>>,
+        max_concurrency: usize,
     ) -> io::Result<()> {
         let start = std::time::Instant::now();
-        let repos = join_all(sources.map(|src| async move {
-            match Self::load_repo_from_source(http, io, &src).await {
-                Ok(Some(v)) => Some((v, src.cache_path().into())),
-                Ok(None) => None,
+        let repos = stream::iter(sources)
+            .map(|mut src| async move {
+                let result = match src.substitute_env_vars() {
+                    Ok(()) => Self::load_repo_from_source(http, io, &src).await,
+                    Err(e) => Err(e),
+                };
+                (src, result)
+            })
+            .buffer_unordered(max_concurrency.max(1))
+            .collect::<Vec<_>>()
+            .await;
+        let duration = std::time::Instant::now() - start;
+        log::info!("downloading repos took {:?}", duration);
+
+        for (src, result) in repos {
+            match result {
+                Ok(Some(mut repo)) => {
+                    let ignored = repo.repo.retain_packages(|name| src.allows_package(name));
+                    for name in ignored {
+                        warn!(
+                            "ignoring package {name} in repo '{}': not in the allowed prefixes",
+                            src.cache_path().display()
+                        );
+                    }
+                    self.cached_repos_new.insert(src.cache_path().into(), repo);
+                }
+                Ok(None) => {}
                 Err(e) => {
                     error!("loading repo '{}': {}", src.cache_path().display(), e);
-                    None
+                    self.load_errors.push(RepositoryLoadError::new(
+                        src.cache_path().into(),
+                        src.url().cloned(),
+                        e,
+                    ));
                 }
             }
-        }))
-        .await;
-        let duration = std::time::Instant::now() - start;
-        log::info!("downloading repos took {:?}", duration);
-
-        for (repo, path) in repos.into_iter().flatten() {
-            self.cached_repos_new.insert(path, repo);
         }
 
         Ok(())
@@ -115,10 +194,22 @@ impl RepoHolder {
         io: &IO,
         source: &RepoSource<'_>,
     ) -> io::Result<Option<LocalCachedRepository>> {
-        if let Some(url) = &source.url() {
-            RepoHolder::load_remote_repo(client, io, source.headers(), source.cache_path(), url)
+        if let Some(url) = source.request_url().filter(|x| x.scheme() == "file") {
+            RepoHolder::load_file_repo(io, source.headers(), url)
                 .await
                 .map(Some)
+        } else if let Some(url) = source.request_url() {
+            RepoHolder::load_remote_repo(
+                client,
+                io,
+                source.headers(),
+                &source.cached_headers(),
+                source.substituted_headers(),
+                source.storage_path(),
+                url,
+            )
+            .await
+            .map(Some)
         } else {
             RepoHolder::load_local_repo(io, source.cache_path())
                 .await
@@ -126,79 +217,187 @@ impl RepoHolder {
         }
     }
 
+    /// Loads the remote repository, updating the cache at `path` if `client` is available.
+    ///
+    /// `headers` are used for the requests, or `substituted_headers` instead of them if the headers
+    /// reference environment variables. Only `cached_headers`, which don't have the secret headers,
+    /// are stored in the cache.
+    #[allow(clippy::too_many_arguments)]
     async fn load_remote_repo(
         client: Option<&impl HttpClient>,
         io: &impl EnvironmentIo,
         headers: &IndexMap<Box<str>, Box<str>>,
+        cached_headers: &IndexMap<Box<str>, Box<str>>,
+        substituted_headers: Option<&IndexMap<Box<str>, Box<str>>>,
         path: &Path,
         remote_url: &Url,
     ) -> io::Result<LocalCachedRepository> {
         if let Some(mut loaded) = try_load_json::<LocalCachedRepository>(io, path).await? {
+            // the cache doesn't have the secret headers, so use the headers in the settings
+            loaded.headers = headers.clone();
             if let Some(client) = client {
+                if loaded.repo.supports_changes() {
+                    // fetch only the changes since the cached revision if the repository supports
+                    log::debug!("downloading changes of remote repo '{}'", remote_url);
+                    match loaded
+                        .repo
+                        .update_with_changes(client, substituted_headers.unwrap_or(headers))
+                        .await
+                    {
+                        Ok(changed) => {
+                            log::debug!("downloaded changes of '{}'", remote_url);
+                            if changed {
+                                // the validators are for the whole repository, not the merged one
+                                loaded.set_etag(None);
+                                loaded.set_last_modified(None);
+
+                                let json = cache_json(&mut loaded, cached_headers)?;
+                                write_atomic(io, path, &json).await.unwrap_or_else(|e| {
+                                    error!("writing local repo cache '{}': {}", path.display(), e)
+                                });
+                            }
+                            return Ok(loaded);
+                        }
+                        Err(e) => {
+                            log::warn!(
+                                "fetching changes of remote repo '{}': {}, downloading whole repository",
+                                remote_url,
+                                e
+                            );
+                        }
+                    }
+                }
+
                 // if it's possible to download remote repo, try to update with that
                 log::debug!("downloading remote repo '{}' with local cache", remote_url);
-                match RemoteRepository::download_with_etag(
+                let current_etag = (loaded.vrc_get.as_ref())
+                    .map(|x| x.etag.as_ref())
+                    .filter(|x| !x.is_empty());
+                let current_last_modified = (loaded.vrc_get.as_ref())
+                    .map(|x| x.last_modified.as_ref())
+                    .filter(|x| !x.is_empty());
+                match RemoteRepository::download_with_validators(
                     client,
                     remote_url,
-                    loaded.headers(),
-                    loaded.vrc_get.as_ref().map(|x| x.etag.as_ref()),
+                    substituted_headers.unwrap_or(headers),
+                    current_etag,
+                    current_last_modified,
                 )
                 .await
                 {
                     Ok(None) => log::debug!("cache matched downloading '{}'", remote_url),
-                    Ok(Some((remote_repo, etag))) => {
+                    Ok(Some((remote_repo, etag, last_modified))) => {
                         log::debug!("downloaded finished '{}'", remote_url);
                         loaded.set_repo(remote_repo);
                         loaded.set_etag(etag);
+                        loaded.set_last_modified(last_modified);
 
-                        io.write(path, &to_vec_pretty_os_eol(&loaded)?)
-                            .await
-                            .unwrap_or_else(|e| {
-                                error!("writing local repo cache '{}': {}", path.display(), e)
-                            });
+                        let json = cache_json(&mut loaded, cached_headers)?;
+                        write_atomic(io, path, &json).await.unwrap_or_else(|e| {
+                            error!("writing local repo cache '{}': {}", path.display(), e)
+                        });
                     }
                     Err(e) => {
                         error!("fetching remote repo '{}': {}", remote_url, e);
                     }
                 }
+            } else {
+                log::info!("using cached remote repo '{}' (offline mode)", remote_url);
             }
 
             Ok(loaded)
         } else {
             // if local repository not found: try downloading remote one
             let Some(client) = client else {
-                return Err(io::Error::new(
-                    io::ErrorKind::ConnectionAborted,
-                    "offline mode",
-                ));
+                return Err(LoadRepositoryErr::RepositoryNotCached {
+                    url: remote_url.clone(),
+                }
+                .into());
             };
             log::debug!("downloading remote repo '{}'", remote_url);
-            let (remote_repo, etag) =
-                RemoteRepository::download(client, remote_url, headers).await?;
+            let Some((remote_repo, etag, last_modified)) =
+                RemoteRepository::download_with_validators(
+                    client,
+                    remote_url,
+                    substituted_headers.unwrap_or(headers),
+                    None,
+                    None,
+                )
+                .await?
+            else {
+                return Err(LoadRepositoryErr::not_modified_without_validators());
+            };
             log::debug!("downloaded finished '{}'", remote_url);
 
             let mut local_cache = LocalCachedRepository::new(remote_repo, headers.clone());
 
             local_cache.set_etag(etag);
+            local_cache.set_last_modified(last_modified);
 
-            io.write(path, &to_vec_pretty_os_eol(&local_cache)?)
-                .await
-                .unwrap_or_else(|e| {
-                    error!("writing local repo cache '{}': {}", path.display(), e);
-                });
+            let json = cache_json(&mut local_cache, cached_headers)?;
+            write_atomic(io, path, &json).await.unwrap_or_else(|e| {
+                error!("writing local repo cache '{}': {}", path.display(), e);
+            });
 
             Ok(local_cache)
         }
     }
 
+    /// Loads the repository at `file://` url. Those repositories are read directly every time,
+    /// so no cache is used.
+    pub(crate) async fn load_file_repo(
+        io: &impl EnvironmentIo,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        url: &Url,
+    ) -> io::Result<LocalCachedRepository> {
+        let path = url.to_file_path().map_err(|()| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid file url: {url}"),
+            )
+        })?;
+        let json = read_json_file::<JsonMap>(io.open(&path).await?, &path).await?;
+        let mut repo = RemoteRepository::parse_with_base(json, url)?;
+        repo.set_url_if_none(|| url.clone());
+        Ok(LocalCachedRepository::new_local(repo, headers.clone()))
+    }
+
     async fn load_local_repo(
         io: &impl EnvironmentIo,
         path: &Path,
     ) -> io::Result<LocalCachedRepository> {
-        read_json_file::<LocalCachedRepository>(io.open(path).await?, path).await
+        let json = read_json_file::<JsonMap>(io.open(path).await?, path).await?;
+        if let Some(Value::Object(_)) = json.get("repo") {
+            let mut repo: LocalCachedRepository = deserialize_json(Value::Object(json))?;
+            repo.local = true;
+            return Ok(repo);
+        }
+
+        // plain repository json, not a vrc-get cache file
+        let repo = match Url::from_file_path(io.resolve(path)) {
+            Ok(base) => RemoteRepository::parse_with_base(json, &base)?,
+            Err(()) => RemoteRepository::parse(json)?,
+        };
+        Ok(LocalCachedRepository::new_local(repo, IndexMap::new()))
     }
 
-    pub(crate) fn into_repos(self) -> HashMap<Box<Path>, LocalCachedRepository> {
-        self.cached_repos_new
+    pub(crate) fn into_repos(
+        self,
+    ) -> (
+        HashMap<Box<Path>, LocalCachedRepository>,
+        Vec<RepositoryLoadError>,
+    ) {
+        (self.cached_repos_new, self.load_errors)
     }
 }
+
+/// Serializes the cache of the repository with `cached_headers` instead of the headers of it.
+fn cache_json(
+    repo: &mut LocalCachedRepository,
+    cached_headers: &IndexMap<Box<str>, Box<str>>,
+) -> io::Result<Vec<u8>> {
+    let headers = std::mem::replace(&mut repo.headers, cached_headers.clone());
+    let json = to_vec_pretty_os_eol(repo);
+    repo.headers = headers;
+    json
+}
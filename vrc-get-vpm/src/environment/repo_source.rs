@@ -1,11 +1,22 @@
+use crate::io;
+use crate::utils::substitute_env_vars;
 use indexmap::IndexMap;
-use std::path::Path;
+use sha1::{Digest, Sha1};
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
 use url::Url;
 
 pub(crate) struct RepoSource<'a> {
     cache_path: &'a Path,
+    // the path of the cache file in the shared cache root, if the cache root is overridden
+    shared_cache_path: Option<PathBuf>,
     headers: &'a IndexMap<Box<str>, Box<str>>,
     url: Option<&'a Url>,
+    // the headers and the url with the environment variables substituted, if any is referenced
+    request_headers: Option<IndexMap<Box<str>, Box<str>>>,
+    request_url: Option<Url>,
+    allowed_package_prefixes: &'a [Box<str>],
+    secret_headers: &'a [Box<str>],
 }
 
 impl<'a> RepoSource<'a> {
@@ -16,20 +27,127 @@ impl<'a> RepoSource<'a> {
     ) -> Self {
         Self {
             cache_path,
+            shared_cache_path: None,
             headers,
             url,
+            request_headers: None,
+            request_url: None,
+            allowed_package_prefixes: &[],
+            secret_headers: &[],
         }
     }
 
-    pub fn cache_path(&self) -> &Path {
+    pub fn with_allowed_package_prefixes(mut self, prefixes: &'a [Box<str>]) -> Self {
+        self.allowed_package_prefixes = prefixes;
+        self
+    }
+
+    /// Keeps the values of the headers with the names out of the cache of the repository.
+    pub fn with_secret_headers(mut self, names: &'a [Box<str>]) -> Self {
+        self.secret_headers = names;
+        self
+    }
+
+    /// Stores the cache of the remote repository in the cache root instead of `cache_path`.
+    ///
+    /// The cache file is named after the url, so the environments sharing the cache root share
+    /// the cache of the same repository. `cache_path` is still used to identify the repository.
+    pub fn with_cache_root(mut self, cache_root: &Path) -> Self {
+        if let Some(url) = self.url.filter(|x| x.scheme() != "file") {
+            self.shared_cache_path = Some(cache_root.join(cache_file_name(url)));
+        }
+        self
+    }
+
+    /// Substitutes `${NAME}` in the url and the header values with the environment variables.
+    ///
+    /// This is done once on loading the repositories, and the substituted values are used only for
+    /// the requests, so the values are never written to the settings or the cache.
+    pub fn substitute_env_vars(&mut self) -> io::Result<()> {
+        let lookup = |name: &str| std::env::var(name).ok();
+
+        if let Some(url) = self.url {
+            if let Cow::Owned(substituted) = substitute_env_vars(url.as_str(), lookup)? {
+                let parsed = Url::parse(&substituted).map_err(|e| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("invalid url after substituting environment variables: {e}"),
+                    )
+                })?;
+                self.request_url = Some(parsed);
+            }
+        }
+
+        let mut substituted_any = false;
+        let mut request_headers = IndexMap::with_capacity(self.headers.len());
+        for (name, value) in self.headers {
+            let value = substitute_env_vars(value, lookup)?;
+            substituted_any |= matches!(value, Cow::Owned(_));
+            request_headers.insert(name.clone(), value.into());
+        }
+        if substituted_any {
+            self.request_headers = Some(request_headers);
+        }
+
+        Ok(())
+    }
+
+    pub fn cache_path(&self) -> &'a Path {
         self.cache_path
     }
 
+    /// Returns the path the cache of the repository is read from and written to.
+    pub fn storage_path(&self) -> &Path {
+        self.shared_cache_path.as_deref().unwrap_or(self.cache_path)
+    }
+
     pub fn headers(&self) -> &IndexMap<Box<str>, Box<str>> {
         self.headers
     }
 
-    pub fn url(&self) -> Option<&Url> {
+    /// Returns the headers stored in the cache, which are the headers except for the secret ones.
+    pub fn cached_headers(&self) -> Cow<'a, IndexMap<Box<str>, Box<str>>> {
+        let is_secret = |name: &str| {
+            (self.secret_headers.iter()).any(|secret| secret.eq_ignore_ascii_case(name))
+        };
+        if self.headers.keys().any(|name| is_secret(name)) {
+            Cow::Owned(
+                (self.headers.iter())
+                    .filter(|(name, _)| !is_secret(name))
+                    .map(|(name, value)| (name.clone(), value.clone()))
+                    .collect(),
+            )
+        } else {
+            Cow::Borrowed(self.headers)
+        }
+    }
+
+    pub fn url(&self) -> Option<&'a Url> {
         self.url
     }
+
+    /// Returns the headers with the environment variables substituted.
+    ///
+    /// None if no header references environment variables.
+    pub fn substituted_headers(&self) -> Option<&IndexMap<Box<str>, Box<str>>> {
+        self.request_headers.as_ref()
+    }
+
+    /// Returns the url to request the repository at.
+    pub fn request_url(&self) -> Option<&Url> {
+        self.request_url.as_ref().or(self.url)
+    }
+
+    /// Returns true if the package from the repository should be used.
+    pub fn allows_package(&self, package: &str) -> bool {
+        self.allowed_package_prefixes.is_empty()
+            || (self.allowed_package_prefixes.iter())
+                .any(|prefix| package.starts_with(prefix.strip_suffix('*').unwrap_or(prefix)))
+    }
+}
+
+/// Returns the name of the cache file of the repository at the url.
+pub(crate) fn cache_file_name(url: &Url) -> String {
+    let hash = hex::encode(Sha1::digest(url.as_str().as_bytes()));
+    format!("{hash}.json")
 }
@@ -125,6 +125,13 @@ impl Settings {
     pub fn ignore_official_repository(&self) -> bool {
         self.vrc_get.ignore_official_repository()
     }
+
+    /// The maximum number of repositories fetched at the same time. Defaults to 8.
+    pub fn max_concurrent_repository_fetches(&self) -> usize {
+        (self.vrc_get.max_concurrent_repository_fetches())
+            .filter(|&x| x != 0)
+            .unwrap_or(8)
+    }
 }
 
 /// User Package Managements
@@ -248,6 +255,33 @@ impl Settings {
         true
     }
 
+    /// Marks headers of the repository with the url as secret.
+    ///
+    /// The values of secret headers are not exported with [`Settings::export_repositories`],
+    /// nor stored in the cache of the repository.
+    /// Returns false if there is no repository with the url.
+    pub fn set_secret_headers(&mut self, url: &Url, names: Vec<Box<str>>) -> bool {
+        let Some(repo) = (self.vpm.user_repos_mut().iter_mut()).find(|x| x.url() == Some(url))
+        else {
+            return false;
+        };
+        repo.secret_headers = names;
+        true
+    }
+
+    /// Sets the prefixes of the ids of the packages used from the repository with the url.
+    ///
+    /// See [`UserRepoSetting::allowed_package_prefixes`] for how the prefixes are matched.
+    /// Returns false if there is no repository with the url.
+    pub fn set_allowed_package_prefixes(&mut self, url: &Url, prefixes: Vec<Box<str>>) -> bool {
+        let Some(repo) = (self.vpm.user_repos_mut().iter_mut()).find(|x| x.url() == Some(url))
+        else {
+            return false;
+        };
+        repo.allowed_package_prefixes = prefixes;
+        true
+    }
+
     pub fn remove_repo(
         &mut self,
         condition: impl Fn(&UserRepoSetting) -> bool,
@@ -294,7 +328,10 @@ impl Settings {
 
         for setting in self.get_user_repos() {
             let Some(url) = setting.url() else { continue };
-            if setting.headers().is_empty() {
+            let headers = (setting.headers().iter())
+                .filter(|(name, _)| !setting.is_secret_header(name))
+                .collect::<Vec<_>>();
+            if headers.is_empty() {
                 writeln!(builder, "{url}").unwrap();
             } else {
                 let mut add_url = Url::parse("vcc://vpm/addRepo").unwrap();
@@ -302,7 +339,7 @@ impl Settings {
                 query_builder.clear();
                 query_builder.append_pair("url", url.as_str());
 
-                for (header_name, value) in setting.headers() {
+                for (header_name, value) in headers {
                     query_builder.append_pair("headers[]", &format!("{}:{}", header_name, value));
                 }
                 drop(query_builder);
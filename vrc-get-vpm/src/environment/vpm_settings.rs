@@ -128,6 +128,10 @@ impl VpmSettings {
         removed
     }
 
+    pub(crate) fn user_repos_mut(&mut self) -> &mut [UserRepoSetting] {
+        &mut self.parsed.user_repos
+    }
+
     pub(crate) fn add_user_repo(&mut self, repo: UserRepoSetting) {
         self.parsed.user_repos.push(repo);
     }
@@ -11,6 +11,8 @@ struct AsJson {
     ignore_official_repository: bool,
     #[serde(default)]
     ignore_curated_repository: bool,
+    #[serde(default)]
+    max_concurrent_repository_fetches: Option<usize>,
 }
 
 #[derive(Debug, Clone)]
@@ -43,4 +45,8 @@ impl VrcGetSettings {
     pub fn ignore_curated_repository(&self) -> bool {
         self.parsed.ignore_curated_repository
     }
+
+    pub fn max_concurrent_repository_fetches(&self) -> Option<usize> {
+        self.parsed.max_concurrent_repository_fetches
+    }
 }
@@ -0,0 +1,245 @@
+//! Packages installed directly from git repositories.
+//!
+//! A git package is fetched into the `Repos/git` folder of the environment and resolved like
+//! any other package by adding it to the package collection with [`GitPackageCollection`].
+
+use crate::{PackageCollection, PackageInfo, PackageManifest, VersionSelector};
+use std::path::Path;
+use url::Url;
+
+/// A package fetched from a git repository at some ref.
+#[derive(Debug, Clone)]
+pub struct GitPackage {
+    url: Url,
+    git_ref: Box<str>,
+    commit: Box<str>,
+    path: Box<Path>,
+    manifest: PackageManifest,
+}
+
+impl GitPackage {
+    /// Creates the git package from already fetched working tree.
+    ///
+    /// `path` is the path to the folder with the `package.json` in the environment.
+    /// Use [`GitPackage::fetch`] to fetch the package with git.
+    pub fn new(
+        url: Url,
+        git_ref: impl Into<Box<str>>,
+        commit: impl Into<Box<str>>,
+        path: impl Into<Box<Path>>,
+        manifest: PackageManifest,
+    ) -> Self {
+        Self {
+            url,
+            git_ref: git_ref.into(),
+            commit: commit.into(),
+            path: path.into(),
+            manifest,
+        }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    /// The ref requested, like branch name, tag name, or commit hash.
+    pub fn git_ref(&self) -> &str {
+        &self.git_ref
+    }
+
+    /// The hash of the commit the ref is resolved to.
+    pub fn commit(&self) -> &str {
+        &self.commit
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn manifest(&self) -> &PackageManifest {
+        &self.manifest
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod fetch {
+    use super::GitPackage;
+    use crate::io;
+    use crate::io::EnvironmentIo;
+    use crate::package_manifest::LooseManifest;
+    use crate::utils::read_json_file;
+    use sha1::{Digest, Sha1};
+    use std::path::{Component, Path, PathBuf};
+    use tokio::process::Command;
+    use url::Url;
+
+    const GIT_CACHE_FOLDER: &str = "Repos/git";
+
+    impl GitPackage {
+        /// Fetches the package from the git repository at `git_ref` with the `git` command.
+        ///
+        /// `subdirectory` is the folder with the `package.json` in the git repository, which must
+        /// be a relative path inside the repository.
+        /// The repository is kept in the `Repos/git` folder of the environment and reused for
+        /// later fetches, and each commit is checked out to its own folder so packages
+        /// fetched at different refs of the same repository do not overwrite each other.
+        pub async fn fetch(
+            io: &impl EnvironmentIo,
+            url: &Url,
+            git_ref: &str,
+            subdirectory: Option<&Path>,
+        ) -> io::Result<Self> {
+            // the ref is passed to git as an argument, so it must not be parsed as an option
+            if git_ref.is_empty() || git_ref.starts_with('-') {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("invalid git ref: {git_ref}"),
+                ));
+            }
+            if let Some(subdirectory) = subdirectory {
+                let inside = (subdirectory.components())
+                    .all(|x| matches!(x, Component::Normal(_) | Component::CurDir));
+                if !inside {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "subdirectory must be a relative path inside the repository: {}",
+                            subdirectory.display()
+                        ),
+                    ));
+                }
+            }
+
+            let hash = hex::encode(Sha1::digest(url.as_str().as_bytes()));
+            let repository = PathBuf::from(format!("{GIT_CACHE_FOLDER}/{hash}.git"));
+            let repository_abs = io.resolve(&repository);
+
+            if io.metadata(&repository.join("HEAD")).await.is_err() {
+                io.create_dir_all(&repository).await?;
+                run_git(&repository_abs, None, &["init", "--quiet", "--bare"]).await?;
+                run_git(
+                    &repository_abs,
+                    None,
+                    &["remote", "add", "origin", url.as_str()],
+                )
+                .await?;
+            }
+
+            run_git(
+                &repository_abs,
+                None,
+                &["fetch", "--quiet", "--depth", "1", "origin", "--", git_ref],
+            )
+            .await?;
+            let commit = run_git(&repository_abs, None, &["rev-parse", "FETCH_HEAD^{commit}"])
+                .await?
+                .trim()
+                .to_owned();
+
+            let work_tree = PathBuf::from(format!("{GIT_CACHE_FOLDER}/{hash}/{commit}"));
+            if io.metadata(&work_tree).await.is_err() {
+                io.create_dir_all(&work_tree).await?;
+                let checkout = run_git(
+                    &repository_abs,
+                    Some(&io.resolve(&work_tree)),
+                    &["checkout", "--quiet", "--force", &commit, "--", "."],
+                )
+                .await;
+                if let Err(e) = checkout {
+                    // do not leave partially checked out tree, which would be reused later
+                    io.remove_dir_all(&work_tree).await.ok();
+                    return Err(e);
+                }
+            }
+
+            let path = match subdirectory {
+                Some(subdirectory) => work_tree.join(subdirectory),
+                None => work_tree,
+            };
+            let package_json = path.join("package.json");
+            let manifest =
+                read_json_file::<LooseManifest>(io.open(&package_json).await?, &package_json)
+                    .await?
+                    .0;
+
+            Ok(Self::new(url.clone(), git_ref, commit, path, manifest))
+        }
+    }
+
+    async fn run_git(
+        repository: &Path,
+        work_tree: Option<&Path>,
+        args: &[&str],
+    ) -> io::Result<String> {
+        let mut command = Command::new("git");
+        command.arg("--git-dir").arg(repository);
+        if let Some(work_tree) = work_tree {
+            command.arg("--work-tree").arg(work_tree);
+        }
+        let output = command.args(args).output().await?;
+
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "git {} failed: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+/// The package collection adding git packages to another collection.
+///
+/// If a git package has the same name and version as a package in the base collection,
+/// the git package shadows the package in the base collection.
+pub struct GitPackageCollection<'a, C: PackageCollection> {
+    base: &'a C,
+    packages: &'a [GitPackage],
+}
+
+impl<'a, C: PackageCollection> GitPackageCollection<'a, C> {
+    pub fn new(base: &'a C, packages: &'a [GitPackage]) -> Self {
+        for git in packages {
+            let manifest = &git.manifest;
+            if (base.find_packages(manifest.name())).any(|x| x.version() == manifest.version()) {
+                log::warn!(
+                    "{}@{} is provided by both git and repository. using git one",
+                    manifest.name(),
+                    manifest.version()
+                );
+            }
+        }
+        Self { base, packages }
+    }
+
+    fn shadowed_by_git(&self, package: PackageInfo) -> bool {
+        self.packages.iter().any(|git| {
+            git.manifest.name() == package.name() && git.manifest.version() == package.version()
+        })
+    }
+}
+
+impl<C: PackageCollection> PackageCollection for GitPackageCollection<'_, C> {
+    fn get_curated_packages(
+        &self,
+        version_selector: VersionSelector,
+    ) -> impl Iterator<Item = PackageInfo> {
+        self.base.get_curated_packages(version_selector)
+    }
+
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        let git = self.packages.iter().map(PackageInfo::git);
+        let base = (self.base.get_all_packages()).filter(|&x| !self.shadowed_by_git(x));
+        git.chain(base)
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        let git = (self.packages.iter())
+            .filter(move |x| x.manifest.name() == package)
+            .map(PackageInfo::git);
+        let base = (self.base.find_packages(package)).filter(|&x| !self.shadowed_by_git(x));
+        git.chain(base)
+    }
+}
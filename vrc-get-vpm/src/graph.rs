@@ -0,0 +1,179 @@
+//! Renders the dependency graph of a resolution as text for Graphviz and Mermaid.
+//!
+//! Nodes are the packages with the selected versions, and edges are the requirements labeled with
+//! the ranges. Requirements not satisfied by the selected version and the replacements of
+//! legacy packages are styled differently from others.
+
+use crate::unity_project::{AbsenceReason, PackageResolutionResult};
+use crate::version::Version;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+/// The label of the node for the dependencies of the project.
+const PROJECT_LABEL: &str = "(project)";
+
+struct Graph {
+    // package name -> selected version, sorted by the name. the project is the empty name
+    nodes: BTreeMap<Box<str>, Option<Version>>,
+    // (from, to, range, satisfied)
+    requirements: Vec<(Box<str>, Box<str>, String, bool)>,
+    // (modern, legacy)
+    replacements: Vec<(Box<str>, Box<str>)>,
+}
+
+impl Graph {
+    fn new(result: &PackageResolutionResult) -> Self {
+        let mut versions = result.version_map();
+        for row in result.satisfaction_matrix().rows {
+            versions.entry(row.package).or_insert(row.version);
+        }
+
+        let mut nodes = BTreeMap::new();
+        let mut add_node = |name: &str| {
+            if !nodes.contains_key(name) {
+                nodes.insert(name.into(), versions.get(name).cloned());
+            }
+        };
+
+        let mut requirements = Vec::new();
+        for edge in &result.dependency_edges {
+            add_node(&edge.from);
+            add_node(&edge.to);
+            requirements.push((
+                edge.from.clone(),
+                edge.to.clone(),
+                edge.range.to_string(),
+                edge.satisfied,
+            ));
+        }
+
+        let mut replacements = Vec::new();
+        for legacy in &result.found_legacy_packages {
+            let AbsenceReason::Legacy { replaced_by } = result.explain_absence(legacy) else {
+                continue;
+            };
+            add_node(legacy);
+            for modern in replaced_by {
+                add_node(&modern);
+                replacements.push((modern, legacy.clone()));
+            }
+        }
+        replacements.sort();
+
+        Self {
+            nodes,
+            requirements,
+            replacements,
+        }
+    }
+
+    fn label(&self, name: &str) -> String {
+        match self.nodes.get(name) {
+            _ if name.is_empty() => PROJECT_LABEL.to_owned(),
+            Some(Some(version)) => format!("{name}@{version}"),
+            _ => name.to_owned(),
+        }
+    }
+}
+
+/// Renders the dependency graph of the resolution in the DOT language of Graphviz.
+///
+/// Unsatisfied requirements are drawn in red dashed lines, and replacements of legacy packages
+/// are drawn in dotted lines from the modern packages.
+pub fn to_dot(result: &PackageResolutionResult) -> String {
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+
+    let graph = Graph::new(result);
+    let mut dot = String::new();
+
+    dot.push_str("digraph dependencies {\n");
+    for name in graph.nodes.keys() {
+        writeln!(
+            dot,
+            "    {} [label={}];",
+            quote(name),
+            quote(&graph.label(name))
+        )
+        .unwrap();
+    }
+    for (from, to, range, satisfied) in &graph.requirements {
+        let style = if *satisfied {
+            ""
+        } else {
+            ", color=red, style=dashed"
+        };
+        writeln!(
+            dot,
+            "    {} -> {} [label={}{style}];",
+            quote(from),
+            quote(to),
+            quote(range)
+        )
+        .unwrap();
+    }
+    for (modern, legacy) in &graph.replacements {
+        writeln!(
+            dot,
+            "    {} -> {} [label=\"replaces\", style=dotted];",
+            quote(modern),
+            quote(legacy)
+        )
+        .unwrap();
+    }
+    dot.push_str("}\n");
+
+    dot
+}
+
+/// Renders the dependency graph of the resolution as a Mermaid flowchart.
+///
+/// Unsatisfied requirements are drawn with crossed arrows, and replacements of legacy packages
+/// are drawn in dotted arrows from the modern packages.
+pub fn to_mermaid(result: &PackageResolutionResult) -> String {
+    fn quote(value: &str) -> String {
+        format!("\"{}\"", value.replace('"', "#quot;"))
+    }
+
+    let graph = Graph::new(result);
+    let mut mermaid = String::new();
+
+    // package names are not valid ids in mermaid, so the nodes are numbered
+    let ids = (graph.nodes.keys().enumerate())
+        .map(|(i, name)| (name.as_ref(), format!("n{i}")))
+        .collect::<HashMap<_, _>>();
+
+    mermaid.push_str("flowchart LR\n");
+    for name in graph.nodes.keys() {
+        writeln!(
+            mermaid,
+            "    {}[{}]",
+            ids[name.as_ref()],
+            quote(&graph.label(name))
+        )
+        .unwrap();
+    }
+    for (from, to, range, satisfied) in &graph.requirements {
+        let arrow = if *satisfied { "-->" } else { "--x" };
+        writeln!(
+            mermaid,
+            "    {} {arrow}|{}| {}",
+            ids[from.as_ref()],
+            quote(range),
+            ids[to.as_ref()]
+        )
+        .unwrap();
+    }
+    for (modern, legacy) in &graph.replacements {
+        writeln!(
+            mermaid,
+            "    {} -.->|\"replaces\"| {}",
+            ids[modern.as_ref()],
+            ids[legacy.as_ref()]
+        )
+        .unwrap();
+    }
+
+    mermaid
+}
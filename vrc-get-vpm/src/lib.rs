@@ -4,18 +4,22 @@
 
 #![deny(unsafe_code)]
 
+use std::collections::HashMap;
 use std::fmt::Display;
 use std::path::Path;
 
+use crate::git_package::GitPackage;
 use indexmap::IndexMap;
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-use version::{ReleaseType, UnityVersion, Version, VersionRange};
+use version::{ReleaseType, UnityVersion, UnityVersionRange, Version, VersionRange};
 
 pub mod environment;
+pub mod git_package;
 pub mod io;
 mod package_manifest;
 pub mod repository;
+pub mod search;
 mod structs;
 mod traits;
 pub mod unity_project;
@@ -25,6 +29,10 @@ mod version_selector;
 
 pub mod repositories_file;
 
+#[cfg(feature = "graph")]
+pub mod graph;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 #[cfg(feature = "unity")]
 pub mod unity;
 #[cfg(feature = "unity-hub")]
@@ -32,13 +40,21 @@ pub mod unity_hub;
 
 use crate::repository::local::LocalCachedRepository;
 
+pub use package_manifest::PackageAvailability;
 pub use package_manifest::PackageManifest;
 pub use package_manifest::PartialUnityVersion;
 pub use structs::setting::UserRepoSetting;
 pub use traits::AbortCheck;
+pub use traits::CancellationToken;
+pub use traits::DynPackageCollection;
 pub use traits::HttpClient;
+pub use traits::HttpStatusError;
+pub use traits::InstallProgress;
 pub use traits::PackageCollection;
 pub use traits::PackageInstaller;
+pub use traits::RepositoryAuthFailed;
+pub use traits::RetryingClient;
+pub use traits::UrlRewritingClient;
 pub use unity_project::UnityProject;
 pub use version_selector::VersionSelector;
 
@@ -57,6 +73,8 @@ impl std::fmt::Debug for PackageInfo<'_> {
         enum SourceEnum<'a> {
             Local(&'a Path),
             Remote(&'a str),
+            Git(&'a str, &'a str),
+            Hypothetical,
         }
 
         let source = match self.inner {
@@ -66,6 +84,8 @@ impl std::fmt::Debug for PackageInfo<'_> {
                     .unwrap_or("(unknown id)"),
             ),
             PackageInfoInner::Local(_, path) => SourceEnum::Local(path),
+            PackageInfoInner::Git(git) => SourceEnum::Git(git.url().as_str(), git.commit()),
+            PackageInfoInner::Hypothetical(_) => SourceEnum::Hypothetical,
         };
 
         f.debug_struct("PackageInfo")
@@ -79,6 +99,8 @@ impl std::fmt::Debug for PackageInfo<'_> {
 enum PackageInfoInner<'a> {
     Remote(&'a PackageManifest, &'a LocalCachedRepository),
     Local(&'a PackageManifest, &'a Path),
+    Git(&'a GitPackage),
+    Hypothetical(&'a PackageManifest),
 }
 
 impl<'a> PackageInfo<'a> {
@@ -87,6 +109,8 @@ impl<'a> PackageInfo<'a> {
         match self.inner {
             PackageInfoInner::Remote(pkg, _) => pkg,
             PackageInfoInner::Local(pkg, _) => pkg,
+            PackageInfoInner::Git(git) => git.manifest(),
+            PackageInfoInner::Hypothetical(pkg) => pkg,
         }
     }
 
@@ -102,10 +126,66 @@ impl<'a> PackageInfo<'a> {
         }
     }
 
+    pub fn git(git: &'a GitPackage) -> Self {
+        Self {
+            inner: PackageInfoInner::Git(git),
+        }
+    }
+
+    /// Creates the package not published anywhere, to simulate the resolution with the package.
+    ///
+    /// Hypothetical packages cannot be installed. See [`HypotheticalCollection`].
+    ///
+    /// [`HypotheticalCollection`]: unity_project::HypotheticalCollection
+    pub fn hypothetical(json: &'a PackageManifest) -> Self {
+        Self {
+            inner: PackageInfoInner::Hypothetical(json),
+        }
+    }
+
     pub fn repo(self) -> Option<&'a LocalCachedRepository> {
         match self.inner {
             PackageInfoInner::Remote(_, repo) => Some(repo),
             PackageInfoInner::Local(_, _) => None,
+            PackageInfoInner::Git(_) => None,
+            PackageInfoInner::Hypothetical(_) => None,
+        }
+    }
+
+    /// Returns the git repository the package came from.
+    pub fn git_source(self) -> Option<&'a GitPackage> {
+        match self.inner {
+            PackageInfoInner::Git(git) => Some(git),
+            _ => None,
+        }
+    }
+
+    /// Returns the id of the repository the package came from.
+    pub fn repository_id(self) -> Option<&'a str> {
+        self.repo()?.id()
+    }
+
+    /// Feeds where the package came from into the hasher to identify the package.
+    pub(crate) fn hash_source(self, state: &mut impl std::hash::Hasher) {
+        use std::hash::Hash;
+        match self.inner {
+            PackageInfoInner::Remote(_, repo) => {
+                0u8.hash(state);
+                repo.id().hash(state);
+                repo.url().map(url::Url::as_str).hash(state);
+            }
+            PackageInfoInner::Local(_, path) => {
+                1u8.hash(state);
+                path.hash(state);
+            }
+            PackageInfoInner::Git(git) => {
+                2u8.hash(state);
+                git.url().as_str().hash(state);
+                git.commit().hash(state);
+            }
+            PackageInfoInner::Hypothetical(_) => {
+                3u8.hash(state);
+            }
         }
     }
 
@@ -119,6 +199,12 @@ impl<'a> PackageInfo<'a> {
         matches!(self.inner, PackageInfoInner::Local(_, _))
     }
 
+    /// Returns true if the package is created with [`PackageInfo::hypothetical`], not from
+    /// any repository.
+    pub fn is_hypothetical(self) -> bool {
+        matches!(self.inner, PackageInfoInner::Hypothetical(_))
+    }
+
     pub fn name(self) -> &'a str {
         self.package_json().name()
     }
@@ -131,10 +217,30 @@ impl<'a> PackageInfo<'a> {
         self.package_json().vpm_dependencies()
     }
 
+    /// The dependencies resolved only if the package is already installed or being installed.
+    pub fn vpm_optional_dependencies(self) -> &'a IndexMap<Box<str>, VersionRange> {
+        self.package_json().vpm_optional_dependencies()
+    }
+
+    /// The UPM `dependencies` of the package. These are not resolved by vrc-get.
+    pub fn upm_dependencies(self) -> &'a IndexMap<Box<str>, Box<str>> {
+        self.package_json().upm_dependencies()
+    }
+
     pub fn legacy_packages(self) -> &'a [Box<str>] {
         self.package_json().legacy_packages()
     }
 
+    /// The folders to be removed on installing the package, with the GUIDs of them if declared.
+    pub fn legacy_folders(self) -> &'a HashMap<Box<str>, Option<Box<str>>> {
+        self.package_json().legacy_folders()
+    }
+
+    /// The files to be removed on installing the package, with the GUIDs of them if declared.
+    pub fn legacy_files(self) -> &'a HashMap<Box<str>, Option<Box<str>>> {
+        self.package_json().legacy_files()
+    }
+
     pub fn unity(self) -> Option<&'a PartialUnityVersion> {
         self.package_json().unity()
     }
@@ -143,13 +249,90 @@ impl<'a> PackageInfo<'a> {
         self.package_json().is_yanked()
     }
 
+    pub fn is_deprecated(self) -> bool {
+        self.package_json().is_deprecated()
+    }
+
+    pub fn deprecation_message(self) -> Option<&'a str> {
+        self.package_json().deprecation_message()
+    }
+
+    pub fn availability(self) -> PackageAvailability<'a> {
+        self.package_json().availability()
+    }
+
     pub fn display_name(self) -> Option<&'a str> {
         self.package_json().display_name()
     }
 
+    pub fn description(self) -> Option<&'a str> {
+        self.package_json().description()
+    }
+
+    pub fn documentation_url(self) -> Option<&'a url::Url> {
+        self.package_json().documentation_url()
+    }
+
+    /// The url of the changelog of the package, for linking the release notes.
+    pub fn changelog_url(self) -> Option<&'a str> {
+        self.package_json().changelog_url().map(url::Url::as_str)
+    }
+
+    /// The url of the zip file of the package.
+    pub fn url(self) -> Option<&'a str> {
+        self.package_json().url().map(url::Url::as_str)
+    }
+
+    /// The name of the author of the package.
+    pub fn author(self) -> Option<&'a str> {
+        self.package_json().author()
+    }
+
+    /// The url of the author of the package.
+    pub fn author_url(self) -> Option<&'a str> {
+        self.package_json().author_url()
+    }
+
+    pub fn license(self) -> Option<&'a str> {
+        self.package_json().license()
+    }
+
+    /// The build targets the package declares not to work on.
+    pub fn incompatible_platforms(self) -> &'a [Box<str>] {
+        self.package_json().incompatible_platforms()
+    }
+
+    /// The dependencies added if the feature is enabled. See [`PackageManifest::feature_dependencies`].
+    pub fn feature_dependencies(
+        self,
+        feature: &str,
+    ) -> Option<&'a IndexMap<Box<str>, VersionRange>> {
+        self.package_json().feature_dependencies(feature)
+    }
+
+    /// The ranges of unity versions the package declares not to work on.
+    pub fn unity_excludes(self) -> &'a [UnityVersionRange] {
+        self.package_json().unity_excludes()
+    }
+
+    /// The setup step declared to be run after installation. See [`PackageManifest::post_install`].
+    pub fn post_install(self) -> Option<&'a str> {
+        self.package_json().post_install()
+    }
+
+    /// The object under the key of `package.json` not known to vrc-get, like a vendor-specific
+    /// namespace. See [`PackageManifest::extension`].
+    pub fn extension(self, namespace: &str) -> Option<&'a serde_json::Value> {
+        self.package_json().extension(namespace)
+    }
+
     pub fn aliases(self) -> &'a [Box<str>] {
         self.package_json().aliases()
     }
+
+    pub fn recommended_packages(self) -> &'a IndexMap<Box<str>, VersionRange> {
+        self.package_json().recommended_packages()
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize_repr, Deserialize_repr)]
@@ -203,6 +386,37 @@ impl Display for ProjectType {
 }
 
 fn unity_compatible(package: &PackageManifest, unity: UnityVersion) -> bool {
+    let supported = package.supported_unity();
+    unity_requirement(package).contains(unity)
+        && (supported.is_empty() || supported.iter().any(|x| x.contains(unity)))
+        && unity_exclusion(package, unity).is_none()
+}
+
+/// Returns the range the package declares not to work on containing the unity version, if any.
+fn unity_exclusion(package: &PackageManifest, unity: UnityVersion) -> Option<&UnityVersionRange> {
+    (package.unity_excludes().iter()).find(|x| x.contains(unity))
+}
+
+/// Returns the range of unity versions the package is compatible with.
+///
+/// For packages declaring multiple supported ranges, this is the smallest range containing all of them,
+/// so some versions in the range may not be compatible.
+fn unity_requirement(package: &PackageManifest) -> UnityVersionRange {
+    let declared = declared_unity_requirement(package);
+    let mut supported = package.supported_unity().iter();
+    let Some(first) = supported.next() else {
+        return declared;
+    };
+    let hull = supported.fold(*first, |a, b| {
+        UnityVersionRange::new(
+            a.minimum().zip(b.minimum()).map(|(a, b)| a.min(b)),
+            a.maximum().zip(b.maximum()).map(|(a, b)| a.max(b)),
+        )
+    });
+    declared.intersect(&hull)
+}
+
+fn declared_unity_requirement(package: &PackageManifest) -> UnityVersionRange {
     fn is_vrcsdk_for_2019(version: &Version) -> bool {
         version.major == 3 && version.minor <= 4
     }
@@ -211,32 +425,39 @@ fn unity_compatible(package: &PackageManifest, unity: UnityVersion) -> bool {
         version.major == 0 && version.minor == 1 && version.patch <= 26
     }
 
+    const UNITY_2019: UnityVersionRange = UnityVersionRange::new(
+        Some(UnityVersion::new(2019, 0, 0, ReleaseType::Alpha, 0)),
+        Some(UnityVersion::new(2020, 0, 0, ReleaseType::Alpha, 0)),
+    );
+
     match package.name() {
         "com.vrchat.avatars" | "com.vrchat.worlds" | "com.vrchat.base"
             if is_vrcsdk_for_2019(package.version()) =>
         {
             // this version of VRCSDK is only for unity 2019 so for other version(s) of unity, it's not satisfied.
-            unity.major() == 2019
+            UNITY_2019
         }
         "com.vrchat.core.vpm-resolver" if is_resolver_for_2019(package.version()) => {
             // this version of Resolver is only for unity 2019 so for other version(s) of unity, it's not satisfied.
-            unity.major() == 2019
+            UNITY_2019
         }
         _ => {
             // otherwice, check based on package info
 
             if let Some(min_unity) = package.unity() {
-                unity
-                    >= UnityVersion::new(
+                UnityVersionRange::new(
+                    Some(UnityVersion::new(
                         min_unity.major(),
                         min_unity.minor(),
                         0,
                         ReleaseType::Alpha,
                         0,
-                    )
+                    )),
+                    None,
+                )
             } else {
                 // if there are no info, satisfies for all unity versions
-                true
+                UnityVersionRange::default()
             }
         }
     }
@@ -0,0 +1,49 @@
+use serde::{Deserialize, Deserializer};
+
+/// The `author` of the package, either `"name"` or `{ "name": "name", "url": "url" }`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Author {
+    name: Option<Box<str>>,
+    url: Option<Box<str>>,
+}
+
+impl Author {
+    pub fn new(name: Box<str>) -> Self {
+        Self {
+            name: Some(name),
+            url: None,
+        }
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    pub fn url(&self) -> Option<&str> {
+        self.url.as_deref()
+    }
+}
+
+impl<'de> Deserialize<'de> for Author {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum AuthorJson {
+            Name(Box<str>),
+            Object {
+                #[serde(default)]
+                name: Option<Box<str>>,
+                #[serde(default)]
+                url: Option<Box<str>>,
+            },
+        }
+
+        Ok(match AuthorJson::deserialize(deserializer)? {
+            AuthorJson::Name(name) => Self::new(name),
+            AuthorJson::Object { name, url } => Self { name, url },
+        })
+    }
+}
@@ -0,0 +1,43 @@
+use serde::{Deserialize, Deserializer};
+
+/// The `deprecated` field in the npm style: `true` or the deprecation message.
+#[derive(Debug, Clone, Default)]
+pub(crate) enum Deprecation {
+    #[default]
+    NotDeprecated,
+    NoMessage,
+    Message(Box<str>),
+}
+
+impl Deprecation {
+    pub fn is_deprecated(&self) -> bool {
+        !matches!(self, Deprecation::NotDeprecated)
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        match self {
+            Deprecation::Message(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Deprecation {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Raw {
+            Flag(bool),
+            Message(Box<str>),
+        }
+
+        Ok(match Raw::deserialize(deserializer)? {
+            Raw::Flag(false) => Deprecation::NotDeprecated,
+            Raw::Flag(true) => Deprecation::NoMessage,
+            Raw::Message(message) => Deprecation::Message(message),
+        })
+    }
+}
@@ -0,0 +1,21 @@
+use crate::version::VersionRange;
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+/// The dependencies added to `vpmDependencies` if the feature is enabled for the resolution.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Feature {
+    #[serde(default)]
+    vpm_dependencies: IndexMap<Box<str>, VersionRange>,
+}
+
+impl Feature {
+    pub fn vpm_dependencies(&self) -> &IndexMap<Box<str>, VersionRange> {
+        &self.vpm_dependencies
+    }
+
+    pub fn vpm_dependencies_mut(&mut self) -> &mut IndexMap<Box<str>, VersionRange> {
+        &mut self.vpm_dependencies
+    }
+}
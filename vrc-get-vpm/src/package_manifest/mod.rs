@@ -1,16 +1,41 @@
+mod author;
+mod deprecation;
+mod features;
 mod partial_unity_version;
+mod supported_unity;
+mod upm_dependencies;
 mod yank_state;
 
 use crate::utils::DedupForwarder;
-use crate::version::{Version, VersionRange};
+use crate::version::{UnityVersionRange, Version, VersionRange};
 use indexmap::IndexMap;
 use serde::{Deserialize, Deserializer};
+use serde_json::Value;
 use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
 use url::Url;
 
+use crate::package_manifest::author::Author;
+use crate::package_manifest::deprecation::Deprecation;
+use crate::package_manifest::features::Feature;
+use crate::package_manifest::supported_unity::SupportedUnity;
+use crate::package_manifest::upm_dependencies::UpmDependencies;
 use crate::package_manifest::yank_state::YankState;
 pub use partial_unity_version::PartialUnityVersion;
 
+/// Whether a version of a package can be installed, parsed from `deprecated` and `vrc-get.yanked`
+/// of the manifest. See [`PackageManifest::availability`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PackageAvailability<'a> {
+    /// The version can be installed.
+    Available,
+    /// The version can be installed, but other versions are preferred. The message if any.
+    Deprecated(Option<&'a str>),
+    /// The version is never selected for new installs, but kept if locked. The reason if any.
+    Yanked(Option<&'a str>),
+}
+
 macro_rules! initialize_from_package_json_like {
     ($source: expr) => {
         PackageManifest {
@@ -21,16 +46,31 @@ macro_rules! initialize_from_package_json_like {
             unity: $source.unity,
             url: $source.url,
             zip_sha_256: $source.zip_sha_256,
+            zip_size: $source.zip_size,
             vpm_dependencies: $source.vpm_dependencies,
+            vpm_optional_dependencies: $source.vpm_optional_dependencies,
+            upm_dependencies: $source.upm_dependencies,
             legacy_folders: $source.legacy_folders,
             legacy_files: $source.legacy_files,
             legacy_packages: $source.legacy_packages,
             headers: $source.headers,
             changelog_url: $source.changelog_url,
+            documentation_url: $source.documentation_url,
+            license: $source.license,
+            author: $source.author,
+            deprecated: $source.deprecated,
             vrc_get: VrcGetMeta {
                 yanked: $source.vrc_get.yanked,
                 aliases: $source.vrc_get.aliases,
+                recommended_packages: $source.vrc_get.recommended_packages,
+                supported_unity: $source.vrc_get.supported_unity,
+                published_at: $source.vrc_get.published_at,
+                post_install: $source.vrc_get.post_install,
+                incompatible_platforms: $source.vrc_get.incompatible_platforms,
+                unity_excludes: $source.vrc_get.unity_excludes,
+                features: $source.vrc_get.features,
             },
+            extensions: $source.extensions,
         }
     };
 }
@@ -68,9 +108,18 @@ macro_rules! package_json_struct {
             $(#[$optional])?
             #[serde(rename = "zipSHA256")]
             $optional_vis zip_sha_256: Option<Box<str>>,
+            $(#[$optional])?
+            #[serde(rename = "zipSize")]
+            $optional_vis zip_size: Option<u64>,
 
             $(#[$optional])?
             $optional_vis vpm_dependencies: IndexMap<Box<str>, VersionRange>,
+            /// dependencies installed only if the package is already in the project.
+            $(#[$optional])?
+            $optional_vis vpm_optional_dependencies: IndexMap<Box<str>, VersionRange>,
+            $(#[$optional])?
+            #[serde(rename = "dependencies")]
+            $optional_vis upm_dependencies: UpmDependencies,
 
             $(#[$optional])?
             $optional_vis legacy_folders: HashMap<Box<str>, Option<Box<str>>>,
@@ -84,10 +133,23 @@ macro_rules! package_json_struct {
 
             $(#[$optional])?
             $optional_vis changelog_url: Option<Url>,
+            $(#[$optional])?
+            $optional_vis documentation_url: Option<Url>,
+
+            $(#[$optional])?
+            $optional_vis license: Option<Box<str>>,
+            $(#[$optional])?
+            $optional_vis author: Option<Author>,
+            $(#[$optional])?
+            $optional_vis deprecated: Deprecation,
 
             $(#[$optional])?
             #[serde(rename = "vrc-get")]
             $optional_vis vrc_get: $vrc_get_meta_name,
+
+            // unknown keys with object values
+            #[serde(flatten)]
+            $optional_vis extensions: Extensions,
         }
 
         // Note: please keep in sync with package_manifest
@@ -100,6 +162,28 @@ macro_rules! package_json_struct {
             /// aliases for `vrc-get i --name <name> <version>` command.
             $(#[$vrc_get_optional])?
             $vrc_get_optional_vis aliases: Vec<Box<str>>,
+            /// packages recommended to be installed with this package.
+            $(#[$vrc_get_optional])?
+            $vrc_get_optional_vis recommended_packages: IndexMap<Box<str>, VersionRange>,
+            /// ranges of unity versions the package supports in addition to `unity`.
+            $(#[$vrc_get_optional])?
+            $vrc_get_optional_vis supported_unity: SupportedUnity,
+            /// the time the version is published at, in seconds since the unix epoch.
+            $(#[$vrc_get_optional])?
+            $vrc_get_optional_vis published_at: Option<u64>,
+            /// the setup step the package declares to be run after installation.
+            /// vrc-get never runs this.
+            $(#[$vrc_get_optional])?
+            $vrc_get_optional_vis post_install: Option<Box<str>>,
+            /// the build targets the package doesn't work on, like `Android`.
+            $(#[$vrc_get_optional])?
+            $vrc_get_optional_vis incompatible_platforms: Vec<Box<str>>,
+            /// ranges of unity versions the package explicitly doesn't work on, like regressions.
+            $(#[$vrc_get_optional])?
+            $vrc_get_optional_vis unity_excludes: SupportedUnity,
+            /// the dependencies added only if the feature is enabled for the resolution.
+            $(#[$vrc_get_optional])?
+            $vrc_get_optional_vis features: IndexMap<Box<str>, Feature>,
         }
     };
 }
@@ -116,6 +200,23 @@ package_json_struct! {
     }
 }
 
+/// The objects under unknown keys of the manifest, like the fields of vendor-specific namespaces.
+///
+/// Values other than objects are ignored since extensions are namespaced.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Extensions(IndexMap<Box<str>, Value>);
+
+impl<'de> Deserialize<'de> for Extensions {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let mut map = IndexMap::<Box<str>, Value>::deserialize(deserializer)?;
+        map.retain(|_, value| value.is_object());
+        Ok(Self(map))
+    }
+}
+
 impl PackageManifest {
     pub fn name(&self) -> &str {
         &self.name
@@ -126,6 +227,15 @@ impl PackageManifest {
     pub fn vpm_dependencies(&self) -> &IndexMap<Box<str>, VersionRange> {
         &self.vpm_dependencies
     }
+    /// The UPM `dependencies` resolved by unity, not by vrc-get. The versions are not parsed.
+    /// The dependencies resolved only if the package is already installed or being installed.
+    pub fn vpm_optional_dependencies(&self) -> &IndexMap<Box<str>, VersionRange> {
+        &self.vpm_optional_dependencies
+    }
+
+    pub fn upm_dependencies(&self) -> &IndexMap<Box<str>, Box<str>> {
+        self.upm_dependencies.get()
+    }
     pub fn legacy_folders(&self) -> &HashMap<Box<str>, Option<Box<str>>> {
         &self.legacy_folders
     }
@@ -152,18 +262,105 @@ impl PackageManifest {
     pub fn zip_sha_256(&self) -> Option<&str> {
         self.zip_sha_256.as_deref()
     }
+    /// The size of the zip file in bytes, if the repository provides it.
+    pub fn zip_size(&self) -> Option<u64> {
+        self.zip_size
+    }
     pub fn changelog_url(&self) -> Option<&Url> {
         self.changelog_url.as_ref()
     }
+    pub fn documentation_url(&self) -> Option<&Url> {
+        self.documentation_url.as_ref()
+    }
     pub fn unity(&self) -> Option<&PartialUnityVersion> {
         self.unity.as_ref()
     }
+    pub fn license(&self) -> Option<&str> {
+        self.license.as_deref()
+    }
+    /// The name of the author of the package.
+    pub fn author(&self) -> Option<&str> {
+        self.author.as_ref().and_then(Author::name)
+    }
+    /// The url of the author of the package.
+    pub fn author_url(&self) -> Option<&str> {
+        self.author.as_ref().and_then(Author::url)
+    }
     pub fn is_yanked(&self) -> bool {
         self.vrc_get.yanked.is_yanked()
     }
+    /// Returns true if the version is deprecated. Unlike yanked versions, deprecated versions
+    /// are still selected if no other version satisfies the requirements.
+    pub fn is_deprecated(&self) -> bool {
+        self.deprecated.is_deprecated()
+    }
+    /// The message of the deprecation, if the version is deprecated with the message.
+    pub fn deprecation_message(&self) -> Option<&str> {
+        self.deprecated.message()
+    }
+    /// Returns whether the version can be installed. Yanked takes precedence over deprecated.
+    pub fn availability(&self) -> PackageAvailability<'_> {
+        if self.vrc_get.yanked.is_yanked() {
+            PackageAvailability::Yanked(self.vrc_get.yanked.reason())
+        } else if self.deprecated.is_deprecated() {
+            PackageAvailability::Deprecated(self.deprecated.message())
+        } else {
+            PackageAvailability::Available
+        }
+    }
     pub fn aliases(&self) -> &[Box<str>] {
         self.vrc_get.aliases.as_slice()
     }
+    pub fn recommended_packages(&self) -> &IndexMap<Box<str>, VersionRange> {
+        &self.vrc_get.recommended_packages
+    }
+    /// The ranges of unity versions the package supports. Empty if the package does not declare.
+    ///
+    /// The package supports the unity version if any of the ranges contains the version.
+    pub fn supported_unity(&self) -> &[UnityVersionRange] {
+        self.vrc_get.supported_unity.ranges()
+    }
+    /// The time the version is published at, if the repository provides it.
+    pub fn published_at(&self) -> Option<SystemTime> {
+        (self.vrc_get.published_at).map(|x| SystemTime::UNIX_EPOCH + Duration::from_secs(x))
+    }
+    /// The ranges of unity versions the package declares not to work on, even if supported by
+    /// `unity` or [`Self::supported_unity`]. Empty if the package does not declare.
+    pub fn unity_excludes(&self) -> &[UnityVersionRange] {
+        self.vrc_get.unity_excludes.ranges()
+    }
+    /// The names of the features the package declares conditional dependencies for.
+    pub fn features(&self) -> impl Iterator<Item = &str> {
+        self.vrc_get.features.keys().map(AsRef::as_ref)
+    }
+    /// The dependencies added to [`Self::vpm_dependencies`] if the feature is enabled for
+    /// the resolution. None if the package does not declare the feature.
+    pub fn feature_dependencies(&self, feature: &str) -> Option<&IndexMap<Box<str>, VersionRange>> {
+        self.vrc_get
+            .features
+            .get(feature)
+            .map(Feature::vpm_dependencies)
+    }
+    /// The build targets the package declares not to work on. Empty if the package does not declare.
+    pub fn incompatible_platforms(&self) -> &[Box<str>] {
+        self.vrc_get.incompatible_platforms.as_slice()
+    }
+    /// Returns false if the package declares not to work on the platform. Compared case-insensitively.
+    pub fn is_compatible_with_platform(&self, platform: &str) -> bool {
+        !(self.vrc_get.incompatible_platforms.iter()).any(|x| x.eq_ignore_ascii_case(platform))
+    }
+    /// The setup step the package declares to be run after installation, like generating files.
+    ///
+    /// This is only a declaration for the host application, which may prompt the user to run it.
+    /// vrc-get never runs this by itself.
+    pub fn post_install(&self) -> Option<&str> {
+        self.vrc_get.post_install.as_deref()
+    }
+    /// The object under the key not known to vrc-get, like the fields of a vendor-specific
+    /// namespace. The resolution doesn't use extensions.
+    pub fn extension(&self, namespace: &str) -> Option<&Value> {
+        self.extensions.0.get(namespace)
+    }
 }
 
 /// Constructing PackageJson. Especially for testing.
@@ -175,6 +372,8 @@ impl PackageManifest {
             display_name: None,
             description: None,
             vpm_dependencies: IndexMap::new(),
+            vpm_optional_dependencies: IndexMap::new(),
+            upm_dependencies: UpmDependencies::default(),
             url: None,
             unity: None,
             legacy_folders: HashMap::new(),
@@ -183,16 +382,131 @@ impl PackageManifest {
             headers: IndexMap::new(),
             vrc_get: VrcGetMeta::default(),
             zip_sha_256: None,
+            zip_size: None,
             changelog_url: None,
+            documentation_url: None,
+            license: None,
+            author: None,
+            deprecated: Deprecation::NotDeprecated,
+            extensions: Extensions::default(),
         }
     }
 
+    pub fn with_display_name(mut self, display_name: impl Into<Box<str>>) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    pub fn with_description(mut self, description: impl Into<Box<str>>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
     pub fn add_vpm_dependency(mut self, name: impl Into<Box<str>>, range: &str) -> Self {
         self.vpm_dependencies
             .insert(name.into(), range.parse().unwrap());
         self
     }
 
+    pub fn add_vpm_optional_dependency(mut self, name: impl Into<Box<str>>, range: &str) -> Self {
+        self.vpm_optional_dependencies
+            .insert(name.into(), range.parse().unwrap());
+        self
+    }
+
+    pub fn add_feature_dependency(
+        mut self,
+        feature: impl Into<Box<str>>,
+        name: impl Into<Box<str>>,
+        range: &str,
+    ) -> Self {
+        (self.vrc_get.features.entry(feature.into()).or_default())
+            .vpm_dependencies_mut()
+            .insert(name.into(), range.parse().unwrap());
+        self
+    }
+
+    pub fn add_upm_dependency(mut self, name: impl Into<Box<str>>, version: &str) -> Self {
+        self.upm_dependencies.insert(name.into(), version.into());
+        self
+    }
+
+    pub fn add_recommended_package(mut self, name: impl Into<Box<str>>, range: &str) -> Self {
+        self.vrc_get
+            .recommended_packages
+            .insert(name.into(), range.parse().unwrap());
+        self
+    }
+
+    pub fn with_unity(mut self, major: u16, minor: u8) -> Self {
+        self.unity = Some(PartialUnityVersion::new(major, minor));
+        self
+    }
+
+    /// Sets the supported unity ranges like `>=2019.4 <2022.3 || >=2022.3.20f1`.
+    pub fn with_supported_unity(mut self, ranges: &str) -> Self {
+        self.vrc_get.supported_unity = ranges.parse().unwrap();
+        self
+    }
+
+    /// Sets the excluded unity ranges like `>=2022.3.20f1 <2022.3.22f1`.
+    pub fn with_unity_excludes(mut self, ranges: &str) -> Self {
+        self.vrc_get.unity_excludes = ranges.parse().unwrap();
+        self
+    }
+
+    /// Sets the publish time, truncated to seconds.
+    pub fn with_published_at(mut self, published_at: SystemTime) -> Self {
+        self.vrc_get.published_at = published_at
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .ok()
+            .map(|x| x.as_secs());
+        self
+    }
+
+    pub fn add_incompatible_platform(mut self, platform: impl Into<Box<str>>) -> Self {
+        self.vrc_get.incompatible_platforms.push(platform.into());
+        self
+    }
+
+    pub fn with_post_install(mut self, action: impl Into<Box<str>>) -> Self {
+        self.vrc_get.post_install = Some(action.into());
+        self
+    }
+
+    pub fn with_changelog_url(mut self, changelog_url: Url) -> Self {
+        self.changelog_url = Some(changelog_url);
+        self
+    }
+
+    pub fn with_author(mut self, author: impl Into<Box<str>>) -> Self {
+        self.author = Some(Author::new(author.into()));
+        self
+    }
+
+    pub fn with_deprecated(mut self, message: impl Into<Box<str>>) -> Self {
+        self.deprecated = Deprecation::Message(message.into());
+        self
+    }
+
+    pub fn with_yanked(mut self, reason: impl Into<Box<str>>) -> Self {
+        self.vrc_get.yanked = YankState::Reason(reason.into());
+        self
+    }
+
+    pub fn with_license(mut self, license: impl Into<Box<str>>) -> Self {
+        self.license = Some(license.into());
+        self
+    }
+
+    /// Sets the extension object under the namespace. Values other than objects are ignored.
+    pub fn with_extension(mut self, namespace: impl Into<Box<str>>, value: Value) -> Self {
+        if value.is_object() {
+            self.extensions.0.insert(namespace.into(), value);
+        }
+        self
+    }
+
     pub fn add_legacy_package(mut self, name: impl Into<Box<str>>) -> Self {
         self.legacy_packages.push(name.into());
         self
@@ -248,6 +562,66 @@ impl<'de> Deserialize<'de> for LooseManifest {
     }
 }
 
+#[test]
+fn deserialize_extensions() {
+    let json = r#"{
+        "name": "com.anatawa12.example",
+        "version": "1.0.0",
+        "vrc": {
+            "features": ["quest"]
+        },
+        "keywords": ["example"],
+        "unityRelease": "22f1"
+    }"#;
+    let package_json: PackageManifest = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        package_json.extension("vrc"),
+        Some(&serde_json::json!({ "features": ["quest"] }))
+    );
+    assert_eq!(package_json.extension("keywords"), None);
+    assert_eq!(package_json.extension("unityRelease"), None);
+    assert_eq!(package_json.extension("name"), None);
+
+    let package_json: LooseManifest = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        package_json.0.extension("vrc"),
+        Some(&serde_json::json!({ "features": ["quest"] }))
+    );
+}
+
+#[test]
+fn deserialize_features() {
+    let json = r#"{
+        "name": "com.anatawa12.example",
+        "version": "1.0.0",
+        "vrc-get": {
+            "features": {
+                "udon-sharp": {
+                    "vpmDependencies": { "com.vrchat.udonsharp": "^1.0.0" }
+                },
+                "empty": {}
+            }
+        }
+    }"#;
+    let package_json: PackageManifest = serde_json::from_str(json).unwrap();
+    assert_eq!(
+        package_json.features().collect::<Vec<_>>(),
+        vec!["udon-sharp", "empty"]
+    );
+    let dependencies = package_json.feature_dependencies("udon-sharp").unwrap();
+    assert_eq!(
+        dependencies.get("com.vrchat.udonsharp").unwrap(),
+        &"^1.0.0".parse::<VersionRange>().unwrap()
+    );
+    assert!(
+        package_json
+            .feature_dependencies("empty")
+            .unwrap()
+            .is_empty()
+    );
+    assert!(package_json.feature_dependencies("missing").is_none());
+}
+
 #[test]
 fn deserialize_partially_bad() {
     let json = r#"{
@@ -256,12 +630,20 @@ fn deserialize_partially_bad() {
         "vpmDependencies": {
             "vrc-get": ">=0.1.0"
         },
+        "dependencies": {
+            "vrc-get": "0.2.0",
+            "com.unity.bad": 1
+        },
         "comment": "Thre following is duplicated key url",
         "legacyPackages": ["vrc-get"],
         "legacyPackages": ["vrc-2"],
         "comment": "Thre following is invalid url",
         "changelog_url": "",
         "url": "",
+        "description": "The package for testing",
+        "documentationUrl": "https://vrc-get.anatawa12.com/",
+        "author": { "name": "anatawa12", "url": "https://anatawa12.com/" },
+        "deprecated": "use vrc-get-vpm-next instead",
         "vrc-get": {
             "yanked": false,
             "aliases": ["vpm"]
@@ -279,8 +661,25 @@ fn deserialize_partially_bad() {
         );
         map
     });
+    assert_eq!(package_json.upm_dependencies(), &{
+        let mut map = IndexMap::new();
+        map.insert("vrc-get".into(), "0.2.0".into());
+        map
+    });
     assert_eq!(package_json.legacy_packages(), &["vrc-get".into()]);
     assert!(!package_json.is_yanked());
     assert_eq!(package_json.aliases(), &["vpm".into()]);
     assert_eq!(package_json.changelog_url(), None);
+    assert_eq!(package_json.description(), Some("The package for testing"));
+    assert_eq!(
+        package_json.documentation_url().map(Url::as_str),
+        Some("https://vrc-get.anatawa12.com/")
+    );
+    assert_eq!(package_json.author(), Some("anatawa12"));
+    assert_eq!(package_json.author_url(), Some("https://anatawa12.com/"));
+    assert!(package_json.is_deprecated());
+    assert_eq!(
+        package_json.deprecation_message(),
+        Some("use vrc-get-vpm-next instead")
+    );
 }
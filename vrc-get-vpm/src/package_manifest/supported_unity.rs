@@ -0,0 +1,39 @@
+use crate::version::UnityVersionRange;
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use std::str::FromStr;
+
+/// The ranges of unity versions separated by `||`, like `>=2019.4 <2022.3 || >=2022.3.20f1`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SupportedUnity(Vec<UnityVersionRange>);
+
+impl SupportedUnity {
+    pub fn ranges(&self) -> &[UnityVersionRange] {
+        &self.0
+    }
+}
+
+impl FromStr for SupportedUnity {
+    type Err = &'static str;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        (s.split("||"))
+            .map(|x| UnityVersionRange::parse(x.trim()).ok_or("invalid unity version range"))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SupportedUnity {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(Error::custom)
+    }
+}
@@ -0,0 +1,34 @@
+use indexmap::IndexMap;
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// The UPM `dependencies` of the package. Entries with non-string versions are ignored.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UpmDependencies(IndexMap<Box<str>, Box<str>>);
+
+impl UpmDependencies {
+    pub fn get(&self) -> &IndexMap<Box<str>, Box<str>> {
+        &self.0
+    }
+
+    pub fn insert(&mut self, name: Box<str>, version: Box<str>) {
+        self.0.insert(name, version);
+    }
+}
+
+impl<'de> Deserialize<'de> for UpmDependencies {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let map = IndexMap::<Box<str>, Value>::deserialize(deserializer)?;
+        Ok(Self(
+            (map.into_iter())
+                .filter_map(|(name, version)| match version {
+                    Value::String(version) => Some((name, version.into())),
+                    _ => None,
+                })
+                .collect(),
+        ))
+    }
+}
@@ -19,7 +19,6 @@ impl YankState {
         }
     }
 
-    #[allow(dead_code)]
     pub fn reason(&self) -> Option<&str> {
         match self {
             YankState::Reason(s) => Some(s),
@@ -42,7 +42,7 @@ impl RepositoriesFile {
                 "vcc" => {
                     repository = parse_add_repo_link(url);
                 }
-                "http" | "https" => {
+                "http" | "https" | "file" => {
                     repository = Some(RepositoryInfo {
                         url,
                         headers: IndexMap::new(),
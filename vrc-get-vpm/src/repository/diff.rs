@@ -0,0 +1,170 @@
+use crate::repository::RemoteRepository;
+use crate::version::Version;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+type JsonMap = Map<String, Value>;
+
+/// The changes between two listings of a repository. See [`diff_repositories`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RepositoryDiff {
+    // names of packages only in the new listing
+    pub added_packages: Vec<Box<str>>,
+    // names of packages only in the old listing
+    pub removed_packages: Vec<Box<str>>,
+    // (package, version) of versions only in the new listing, including ones of added packages
+    pub added_versions: Vec<(Box<str>, Version)>,
+    // (package, version) of versions only in the old listing, including ones of removed packages
+    pub removed_versions: Vec<(Box<str>, Version)>,
+    // (package, version) of versions yanked in the new listing but not in the old one
+    pub yanked_versions: Vec<(Box<str>, Version)>,
+    // versions in both listings with different manifests, which means re-published with the same version
+    pub changed_versions: Vec<ChangedVersion>,
+}
+
+/// The version in both listings with different manifests.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedVersion {
+    pub package: Box<str>,
+    pub version: Version,
+    // the keys of the manifest with different values, sorted
+    pub fields: Vec<Box<str>>,
+}
+
+impl ChangedVersion {
+    /// Returns true if the dependencies of the version are changed, which is suspicious for
+    /// a published version.
+    pub fn changes_dependencies(&self) -> bool {
+        self.fields.iter().any(|x| x.as_ref() == "vpmDependencies")
+    }
+}
+
+impl RepositoryDiff {
+    /// Returns true if the listings have the same packages.
+    pub fn is_empty(&self) -> bool {
+        self.added_packages.is_empty()
+            && self.removed_packages.is_empty()
+            && self.added_versions.is_empty()
+            && self.removed_versions.is_empty()
+            && self.yanked_versions.is_empty()
+            && self.changed_versions.is_empty()
+    }
+}
+
+impl fmt::Display for RepositoryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.added_packages {
+            writeln!(f, "+ {name} (new package)")?;
+        }
+        for name in &self.removed_packages {
+            writeln!(f, "- {name} (removed package)")?;
+        }
+        for (name, version) in &self.added_versions {
+            writeln!(f, "+ {name} {version}")?;
+        }
+        for (name, version) in &self.removed_versions {
+            writeln!(f, "- {name} {version}")?;
+        }
+        for (name, version) in &self.yanked_versions {
+            writeln!(f, "! {name} {version} yanked")?;
+        }
+        for changed in &self.changed_versions {
+            writeln!(
+                f,
+                "~ {} {} changed: {}",
+                changed.package,
+                changed.version,
+                changed.fields.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares two listings of the same repository, like ones fetched at different times.
+///
+/// The packages and versions are sorted by name and version.
+/// Manifests are compared as json in the listings, so changes of any field are reported in
+/// `changed_versions`, including ones vrc-get doesn't know.
+pub fn diff_repositories(old: &RemoteRepository, new: &RemoteRepository) -> RepositoryDiff {
+    let mut diff = RepositoryDiff::default();
+
+    let mut old_names = package_names(old);
+    let mut new_names = package_names(new);
+    old_names.sort();
+    new_names.sort();
+
+    for &name in &new_names {
+        if old.get_package(name).is_none() {
+            diff.added_packages.push(name.into());
+        }
+
+        let mut versions = new.get_versions_of(name).collect::<Vec<_>>();
+        versions.sort_by(|a, b| a.version().cmp(b.version()));
+
+        for manifest in versions {
+            let version = manifest.version();
+            let Some(old_manifest) = old.get_package_version(name, version) else {
+                diff.added_versions.push((name.into(), version.clone()));
+                continue;
+            };
+
+            if manifest.is_yanked() && !old_manifest.is_yanked() {
+                diff.yanked_versions.push((name.into(), version.clone()));
+            }
+
+            let fields = changed_fields(
+                old.get_package_version_json(name, version),
+                new.get_package_version_json(name, version),
+            );
+            if !fields.is_empty() {
+                diff.changed_versions.push(ChangedVersion {
+                    package: name.into(),
+                    version: version.clone(),
+                    fields,
+                });
+            }
+        }
+    }
+
+    for &name in &old_names {
+        if new.get_package(name).is_none() {
+            diff.removed_packages.push(name.into());
+        }
+
+        let mut versions = old.get_versions_of(name).collect::<Vec<_>>();
+        versions.sort_by(|a, b| a.version().cmp(b.version()));
+
+        for manifest in versions {
+            if new.get_package_version(name, manifest.version()).is_none() {
+                diff.removed_versions
+                    .push((name.into(), manifest.version().clone()));
+            }
+        }
+    }
+
+    diff
+}
+
+fn package_names(listing: &RemoteRepository) -> Vec<&str> {
+    (listing.raw_json().get("packages"))
+        .and_then(Value::as_object)
+        .into_iter()
+        .flat_map(|x| x.keys().map(String::as_str))
+        .collect()
+}
+
+fn changed_fields(old: Option<&JsonMap>, new: Option<&JsonMap>) -> Vec<Box<str>> {
+    let empty = JsonMap::new();
+    let old = old.unwrap_or(&empty);
+    let new = new.unwrap_or(&empty);
+
+    let mut fields = (old.keys().chain(new.keys()))
+        .filter(|&key| old.get(key) != new.get(key))
+        .map(|key| Box::<str>::from(key.as_str()))
+        .collect::<Vec<_>>();
+    fields.sort();
+    fields.dedup();
+    fields
+}
@@ -1,5 +1,5 @@
-use crate::PackageManifest;
 use crate::repository::{RemotePackages, RemoteRepository};
+use crate::{PackageInfo, PackageManifest};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use url::Url;
@@ -12,6 +12,10 @@ pub struct LocalCachedRepository {
     #[serde(rename = "vrc-get")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub(crate) vrc_get: Option<VrcGetMeta>,
+    // whether the repository is loaded from the local file system. packages in only those
+    // repositories may refer to local files with `file://` urls
+    #[serde(skip)]
+    pub(crate) local: bool,
 }
 
 impl LocalCachedRepository {
@@ -20,6 +24,15 @@ impl LocalCachedRepository {
             repo,
             headers,
             vrc_get: None,
+            local: false,
+        }
+    }
+
+    /// Creates the repository loaded from the local file system.
+    pub(crate) fn new_local(repo: RemoteRepository, headers: IndexMap<Box<str>, Box<str>>) -> Self {
+        Self {
+            local: true,
+            ..Self::new(repo, headers)
         }
     }
 
@@ -49,6 +62,16 @@ impl LocalCachedRepository {
         }
     }
 
+    pub(crate) fn set_last_modified(&mut self, last_modified: Option<Box<str>>) {
+        if let Some(last_modified) = last_modified {
+            self.vrc_get
+                .get_or_insert_with(Default::default)
+                .last_modified = last_modified;
+        } else if let Some(x) = self.vrc_get.as_mut() {
+            x.last_modified = "".into();
+        }
+    }
+
     pub fn url(&self) -> Option<&Url> {
         self.repo().url()
     }
@@ -71,10 +94,25 @@ impl LocalCachedRepository {
     pub fn get_packages(&self) -> impl Iterator<Item = &'_ RemotePackages> {
         self.repo().get_packages()
     }
+
+    /// Returns every version of every package in the repository, including yanked ones.
+    ///
+    /// The packages are parsed while iterating, so this does not materialize all of them at once.
+    pub fn all_packages(&self) -> impl Iterator<Item = PackageInfo<'_>> {
+        (self.get_packages())
+            .flat_map(|x| x.all_versions())
+            .map(|pkg| PackageInfo::remote(pkg, self))
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct VrcGetMeta {
     #[serde(default, skip_serializing_if = "str::is_empty")]
     pub etag: Box<str>,
+    #[serde(
+        default,
+        rename = "lastModified",
+        skip_serializing_if = "str::is_empty"
+    )]
+    pub last_modified: Box<str>,
 }
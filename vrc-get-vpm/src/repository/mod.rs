@@ -1,6 +1,14 @@
+mod diff;
 pub(crate) mod local;
 mod remote;
+mod validate;
 
+pub use diff::ChangedVersion;
+pub use diff::RepositoryDiff;
+pub use diff::diff_repositories;
 pub use local::LocalCachedRepository;
+pub use remote::LoadRepositoryErr;
 pub use remote::RemotePackages;
 pub use remote::RemoteRepository;
+pub use validate::RepositoryWarning;
+pub use validate::validate_repository;
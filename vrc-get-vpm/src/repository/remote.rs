@@ -1,6 +1,6 @@
 use crate::PackageManifest;
-use crate::traits::HttpClient;
-use crate::utils::{deserialize_json, deserialize_json_slice};
+use crate::traits::{HttpClient, HttpStatusError, rewritten_url_error};
+use crate::utils::deserialize_json;
 use crate::version::Version;
 use crate::{VersionSelector, io};
 use futures::prelude::*;
@@ -9,11 +9,31 @@ use serde::de::{DeserializeSeed, Visitor};
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{Map, Value};
 use std::collections::HashMap;
+use std::fmt;
 use std::pin::pin;
+use std::sync::OnceLock;
 use url::Url;
 
 type JsonMap = Map<String, Value>;
 
+/// The number of packages in the repository from which [`RemoteRepository::parse`] parses the
+/// packages on demand.
+const LAZY_PARSING_THRESHOLD: usize = 256;
+
+/// The top-level keys of the repository known to vrc-get or other clients.
+const KNOWN_REPOSITORY_KEYS: &[&str] = &[
+    "$schema",
+    "name",
+    "id",
+    "url",
+    "author",
+    "infoLink",
+    "bannerUrl",
+    "packages",
+    "changesUrl",
+    "revision",
+];
+
 #[derive(Debug, Clone)]
 pub struct RemoteRepository {
     actual: JsonMap,
@@ -30,24 +50,116 @@ struct ParsedRepository {
     id: Option<Box<str>>,
     #[serde(default)]
     #[serde(deserialize_with = "deserialize_packages")]
-    packages: IndexMap<Box<str>, RemotePackages>,
+    // the packages not parsed yet are empty
+    packages: IndexMap<Box<str>, OnceLock<RemotePackages>>,
 }
 
 impl RemoteRepository {
-    pub fn parse(cache: JsonMap) -> io::Result<Self> {
+    /// Parses the repository.
+    ///
+    /// Malformed packages are logged and have no versions instead of failing the whole repository.
+    /// Large repositories are parsed with [`Self::parse_lazy`].
+    pub fn parse(mut cache: JsonMap) -> io::Result<Self> {
+        normalize_repository(&mut cache);
+        Self::parse_normalized(cache)
+    }
+
+    fn parse_normalized(cache: JsonMap) -> io::Result<Self> {
+        let packages = (cache.get("packages"))
+            .and_then(Value::as_object)
+            .map_or(0, |x| x.len());
+        if packages > LAZY_PARSING_THRESHOLD {
+            Self::parse_lazy_normalized(cache)
+        } else {
+            Self::parse_eager(cache)
+        }
+    }
+
+    fn parse_eager(cache: JsonMap) -> io::Result<Self> {
         Ok(Self {
             parsed: deserialize_json(Value::Object(cache.clone()))?,
             actual: cache,
         })
     }
 
+    /// Parses the repository except for the packages, which are parsed when they are looked up.
+    ///
+    /// The packages are found with the index of the names built here, so looking up a few packages
+    /// doesn't parse every package in the repository.
+    /// Malformed packages are logged when they are parsed, and have no versions as with [`Self::parse`].
+    pub fn parse_lazy(mut cache: JsonMap) -> io::Result<Self> {
+        normalize_repository(&mut cache);
+        Self::parse_lazy_normalized(cache)
+    }
+
+    fn parse_lazy_normalized(mut cache: JsonMap) -> io::Result<Self> {
+        let index = match cache.get("packages") {
+            Some(Value::Object(packages)) => (packages.keys())
+                .map(|name| (name.as_str().into(), OnceLock::new()))
+                .collect(),
+            // let the error be reported as usual
+            _ => return Self::parse_eager(cache),
+        };
+
+        // parse without the packages, keeping the order of the keys
+        let packages = cache.get_mut("packages").unwrap();
+        let packages = std::mem::replace(packages, Value::Object(JsonMap::new()));
+        let mut parsed: ParsedRepository = deserialize_json(Value::Object(cache.clone()))?;
+        parsed.packages = index;
+        *cache.get_mut("packages").unwrap() = packages;
+
+        Ok(Self {
+            parsed,
+            actual: cache,
+        })
+    }
+
+    fn packages_of<'a>(
+        &'a self,
+        name: &str,
+        lazy: &'a OnceLock<RemotePackages>,
+    ) -> &'a RemotePackages {
+        lazy.get_or_init(|| {
+            let json = (self.actual.get("packages"))
+                .and_then(|packages| packages.get(name))
+                .unwrap_or(&Value::Null);
+            remote_packages_or_warn(name, json)
+        })
+    }
+
+    /// Parses the repository, resolving relative `url` of packages against `base`.
+    ///
+    /// The `base` is the url the repository is loaded from, so packages may refer to zip files
+    /// next to the repository file. Absolute `url` of packages, including ones on another host,
+    /// are used as is.
+    pub fn parse_with_base(mut cache: JsonMap, base: &Url) -> io::Result<Self> {
+        normalize_repository(&mut cache);
+        if let Some(Value::Object(packages)) = cache.get_mut("packages") {
+            for package in packages.values_mut() {
+                let Some(Value::Object(versions)) = package.get_mut("versions") else {
+                    continue;
+                };
+                for version in versions.values_mut() {
+                    if let Some(Value::String(url)) = version.get_mut("url") {
+                        if let Err(url::ParseError::RelativeUrlWithoutBase) = Url::parse(url) {
+                            if let Ok(joined) = base.join(url) {
+                                *url = joined.into();
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Self::parse_normalized(cache)
+    }
+
     pub async fn download(
         client: &impl HttpClient,
         url: &Url,
         headers: &IndexMap<Box<str>, Box<str>>,
-    ) -> io::Result<(RemoteRepository, Option<Box<str>>)> {
+    ) -> Result<(RemoteRepository, Option<Box<str>>), LoadRepositoryErr> {
         match Self::download_with_etag(client, url, headers, None).await {
-            Ok(None) => unreachable!("downloading without etag should must return Ok(Some)"),
+            Ok(None) => Err(LoadRepositoryErr::not_modified_without_validators()),
             Ok(Some(repo_and_etag)) => Ok(repo_and_etag),
             Err(err) => Err(err),
         }
@@ -58,23 +170,222 @@ impl RemoteRepository {
         url: &Url,
         headers: &IndexMap<Box<str>, Box<str>>,
         current_etag: Option<&str>,
-    ) -> io::Result<Option<(RemoteRepository, Option<Box<str>>)>> {
-        let Some((mut stream, etag)) = client.get_with_etag(url, headers, current_etag).await?
+    ) -> Result<Option<(RemoteRepository, Option<Box<str>>)>, LoadRepositoryErr> {
+        Ok(
+            Self::download_with_validators(client, url, headers, current_etag, None)
+                .await?
+                .map(|(repo, etag, _)| (repo, etag)),
+        )
+    }
+
+    /// Downloads the repository with `If-None-Match` and `If-Modified-Since` validators.
+    ///
+    /// Returns `Ok(None)` if the cached repository is still up to date, otherwise the downloaded
+    /// repository with the new etag and last modified date.
+    #[allow(clippy::type_complexity)]
+    pub async fn download_with_validators(
+        client: &impl HttpClient,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+        current_last_modified: Option<&str>,
+    ) -> Result<Option<(RemoteRepository, Option<Box<str>>, Option<Box<str>>)>, LoadRepositoryErr>
+    {
+        let rewritten = client
+            .rewrite_url(url)
+            .map_err(LoadRepositoryErr::from_client_error)?;
+        let Some((stream, etag, last_modified)) = client
+            .get_with_validators(
+                rewritten.as_ref().unwrap_or(url),
+                headers,
+                current_etag,
+                current_last_modified,
+            )
+            .await
+            .map_err(|err| rewritten_url_error(url, rewritten.as_ref(), err))
+            .map_err(LoadRepositoryErr::from_client_error)?
         else {
             return Ok(None);
         };
 
-        let mut bytes = Vec::new();
-        pin!(stream).read_to_end(&mut bytes).await?;
-
-        let no_bom = bytes
-            .strip_prefix(b"\xEF\xBB\xBF")
-            .unwrap_or(bytes.as_ref());
-        let json = deserialize_json_slice(no_bom)?;
+        let json = read_json(stream).await?;
+        warn_unknown_keys(&json, url);
 
-        let mut repo = RemoteRepository::parse(json)?;
+        let mut repo = RemoteRepository::parse_with_base(json, url)
+            .map_err(|err| LoadRepositoryErr::UnsupportedSchema(err.to_string().into()))?;
         repo.set_url_if_none(|| url.clone());
-        Ok(Some((repo, etag)))
+        Ok(Some((repo, etag, last_modified)))
+    }
+
+    /// Returns the url of the changes endpoint declared with `changesUrl` in the repository.
+    ///
+    /// The relative url is resolved against the url of the repository.
+    /// The endpoint responds with the changes since the `since` query parameter, in the form
+    /// described in [`Self::update_with_changes`]. Every changed package must be `null` for the
+    /// removal, or have `versions`, which may be empty.
+    pub fn changes_url(&self) -> Option<Url> {
+        let changes_url = self.actual.get("changesUrl")?.as_str()?;
+        match Url::parse(changes_url) {
+            Ok(url) => Some(url),
+            Err(url::ParseError::RelativeUrlWithoutBase) => self.url()?.join(changes_url).ok(),
+            Err(_) => None,
+        }
+    }
+
+    /// Returns the revision of the repository the changes are fetched since.
+    pub fn revision(&self) -> Option<&str> {
+        self.actual.get("revision")?.as_str()
+    }
+
+    /// Returns true if the repository can be updated with [`Self::update_with_changes`].
+    pub fn supports_changes(&self) -> bool {
+        self.changes_url().is_some() && self.revision().is_some()
+    }
+
+    /// Updates the repository with the changes since [`Self::revision`] instead of downloading
+    /// the whole repository again.
+    ///
+    /// The changes are fetched from [`Self::changes_url`] with the `since` query parameter,
+    /// and the response is in the following form:
+    ///
+    /// ```json
+    /// {
+    ///   "revision": "<new revision>",
+    ///   "packages": {
+    ///     "<added or updated package>": { "versions": { "<version>": { /* manifest */ } } },
+    ///     "<package with removed version>": { "versions": { "<version>": null } },
+    ///     "<removed package>": null
+    ///   }
+    /// }
+    /// ```
+    ///
+    /// Returns true if the repository is changed. The repository is kept as is on errors,
+    /// including the changed packages that are neither `null` nor have `versions`, so the caller
+    /// can fall back to downloading the whole repository.
+    pub async fn update_with_changes(
+        &mut self,
+        client: &impl HttpClient,
+        headers: &IndexMap<Box<str>, Box<str>>,
+    ) -> Result<bool, LoadRepositoryErr> {
+        let (Some(mut changes_url), Some(revision)) = (self.changes_url(), self.revision()) else {
+            return Err(LoadRepositoryErr::UnsupportedSchema(
+                "the repository does not support changes".into(),
+            ));
+        };
+        changes_url.query_pairs_mut().append_pair("since", revision);
+
+        let rewritten = client
+            .rewrite_url(&changes_url)
+            .map_err(LoadRepositoryErr::from_client_error)?;
+        let Some((stream, _, _)) = client
+            .get_with_validators(
+                rewritten.as_ref().unwrap_or(&changes_url),
+                headers,
+                None,
+                None,
+            )
+            .await
+            .map_err(|err| rewritten_url_error(&changes_url, rewritten.as_ref(), err))
+            .map_err(LoadRepositoryErr::from_client_error)?
+        else {
+            return Err(LoadRepositoryErr::not_modified_without_validators());
+        };
+
+        let changes = read_json(stream).await?;
+        let Some(Value::String(new_revision)) = changes.get("revision") else {
+            return Err(LoadRepositoryErr::UnsupportedSchema(
+                "the changes have no revision".into(),
+            ));
+        };
+        let changed_packages = match changes.get("packages") {
+            None => JsonMap::new(),
+            Some(Value::Object(packages)) => packages.clone(),
+            Some(_) => {
+                return Err(LoadRepositoryErr::UnsupportedSchema(
+                    "the packages of the changes is not an object".into(),
+                ));
+            }
+        };
+        if changed_packages.is_empty() && new_revision == revision {
+            return Ok(false);
+        }
+
+        let mut actual = self.actual.clone();
+        let packages = actual
+            .entry("packages")
+            .or_insert_with(|| Value::Object(JsonMap::new()));
+        let Value::Object(packages) = packages else {
+            return Err(LoadRepositoryErr::UnsupportedSchema(
+                "the packages of the repository is not an object".into(),
+            ));
+        };
+        for (name, changed) in changed_packages {
+            let mut changed = match changed {
+                Value::Null => {
+                    packages.remove(&name);
+                    continue;
+                }
+                Value::Object(changed) => changed,
+                _ => {
+                    return Err(LoadRepositoryErr::UnsupportedSchema(
+                        format!("the change of package {name} is not an object").into(),
+                    ));
+                }
+            };
+            let Some(Value::Object(changed_versions)) = changed.remove("versions") else {
+                return Err(LoadRepositoryErr::UnsupportedSchema(
+                    format!("the change of package {name} has no versions").into(),
+                ));
+            };
+            let package = packages
+                .entry(name.as_str())
+                .or_insert_with(|| Value::Object(JsonMap::new()));
+            let versions = (package.as_object_mut())
+                .map(|package| {
+                    (package.entry("versions")).or_insert_with(|| Value::Object(JsonMap::new()))
+                })
+                .and_then(Value::as_object_mut);
+            let Some(versions) = versions else {
+                return Err(LoadRepositoryErr::UnsupportedSchema(
+                    format!("the cached package {name} has no versions to update").into(),
+                ));
+            };
+            for (version, manifest) in changed_versions {
+                if manifest.is_null() {
+                    versions.remove(&version);
+                } else {
+                    versions.insert(version, manifest);
+                }
+            }
+        }
+        actual.insert("revision".to_owned(), Value::String(new_revision.clone()));
+
+        let repo = match self.url() {
+            Some(url) => Self::parse_with_base(actual, url),
+            None => Self::parse(actual),
+        };
+        *self = repo.map_err(|err| LoadRepositoryErr::UnsupportedSchema(err.to_string().into()))?;
+        Ok(true)
+    }
+
+    /// Removes the packages whose names don't satisfy the predicate, and returns the removed names.
+    pub(crate) fn retain_packages(&mut self, mut f: impl FnMut(&str) -> bool) -> Vec<Box<str>> {
+        let mut removed = Vec::new();
+        self.parsed.packages.retain(|name, _| {
+            let retain = f(name);
+            if !retain {
+                removed.push(name.clone());
+            }
+            retain
+        });
+        if !removed.is_empty() {
+            if let Some(Value::Object(packages)) = self.actual.get_mut("packages") {
+                for name in &removed {
+                    packages.remove(name.as_ref());
+                }
+            }
+        }
+        removed
     }
 
     pub(crate) fn set_id_if_none(&mut self, f: impl FnOnce() -> Box<str>) {
@@ -115,24 +426,273 @@ impl RemoteRepository {
         &self,
         package: &str,
     ) -> impl Iterator<Item = &'_ PackageManifest> + use<'_> {
-        self.parsed
-            .packages
-            .get(package)
+        self.get_package(package)
             .map(RemotePackages::all_versions)
             .into_iter()
             .flatten()
     }
 
     pub fn get_package(&self, package: &str) -> Option<&RemotePackages> {
-        self.parsed.packages.get(package)
+        let (_, name, lazy) = self.parsed.packages.get_full(package)?;
+        Some(self.packages_of(name, lazy))
     }
 
     pub fn get_packages(&self) -> impl Iterator<Item = &'_ RemotePackages> {
-        self.parsed.packages.values()
+        (self.parsed.packages.iter()).map(|(name, lazy)| self.packages_of(name, lazy))
     }
 
     pub fn get_package_version(&self, name: &str, version: &Version) -> Option<&PackageManifest> {
-        self.parsed.packages.get(name)?.versions.get(version)
+        self.get_package(name)?.versions.get(version)
+    }
+
+    /// Returns the raw json of the repository, after normalizing the shape of the packages.
+    pub(crate) fn raw_json(&self) -> &JsonMap {
+        &self.actual
+    }
+
+    /// Returns the raw json of the package manifest as is in the repository.
+    pub(crate) fn get_package_version_json(
+        &self,
+        name: &str,
+        version: &Version,
+    ) -> Option<&JsonMap> {
+        let versions = self
+            .actual
+            .get("packages")?
+            .as_object()?
+            .get(name)?
+            .as_object()?
+            .get("versions")?
+            .as_object()?;
+
+        if let Some(Value::Object(json)) = versions.get(&version.to_string()) {
+            return Some(json);
+        }
+
+        // the key may be formatted differently
+        versions.values().filter_map(Value::as_object).find(|json| {
+            json.get("version")
+                .and_then(Value::as_str)
+                .and_then(|x| x.parse::<Version>().ok())
+                .as_ref()
+                == Some(version)
+        })
+    }
+}
+
+async fn read_json(stream: impl AsyncRead) -> Result<JsonMap, LoadRepositoryErr> {
+    let mut bytes = Vec::new();
+    pin!(stream)
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(LoadRepositoryErr::from_client_error)?;
+
+    let no_bom = bytes
+        .strip_prefix(b"\xEF\xBB\xBF")
+        .unwrap_or(bytes.as_ref());
+    if no_bom.iter().all(u8::is_ascii_whitespace) {
+        return Err(LoadRepositoryErr::Empty);
+    }
+    serde_json::from_slice::<JsonMap>(no_bom).map_err(LoadRepositoryErr::from_json_error)
+}
+
+/// Normalizes the packages of the repository into the `packages.<name>.versions.<version>` form.
+///
+/// Some repositories list the packages in flatter forms: `packages` as a list of the manifests,
+/// or `versions` of a package as a list of the manifests. Those are converted to maps here,
+/// so the packages in them are found as usual.
+fn normalize_repository(cache: &mut JsonMap) {
+    fn versions_map(name: Option<&str>, manifests: Vec<Value>) -> JsonMap {
+        let mut versions = JsonMap::new();
+        for manifest in manifests {
+            let version = manifest.get("version").and_then(Value::as_str);
+            let manifest_name = manifest.get("name").and_then(Value::as_str);
+            match (version, manifest_name) {
+                (Some(version), Some(manifest_name)) if name.is_none_or(|x| x == manifest_name) => {
+                    versions.insert(version.to_owned(), manifest);
+                }
+                _ => {
+                    log::warn!(
+                        "ignoring package manifest without valid name or version in the list of {}",
+                        name.unwrap_or("packages")
+                    );
+                }
+            }
+        }
+        versions
+    }
+
+    match cache.get_mut("packages") {
+        Some(packages @ Value::Array(_)) => {
+            let Value::Array(manifests) = std::mem::take(packages) else {
+                unreachable!()
+            };
+            let mut grouped = IndexMap::<String, Vec<Value>>::new();
+            for manifest in manifests {
+                let name = manifest.get("name").and_then(Value::as_str).unwrap_or("");
+                grouped.entry(name.to_owned()).or_default().push(manifest);
+            }
+            *packages = Value::Object(
+                (grouped.into_iter())
+                    .filter_map(|(name, manifests)| {
+                        if name.is_empty() {
+                            log::warn!("ignoring package manifests without name in packages");
+                            return None;
+                        }
+                        let versions = versions_map(Some(&name), manifests);
+                        let mut package = JsonMap::new();
+                        package.insert("versions".into(), Value::Object(versions));
+                        Some((name, Value::Object(package)))
+                    })
+                    .collect(),
+            );
+        }
+        Some(Value::Object(packages)) => {
+            for (name, package) in packages.iter_mut() {
+                if let Some(versions @ Value::Array(_)) = package.get_mut("versions") {
+                    let Value::Array(manifests) = std::mem::take(versions) else {
+                        unreachable!()
+                    };
+                    *versions = Value::Object(versions_map(Some(name), manifests));
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Warns the unknown top-level keys of the downloaded repository, which are kept as is.
+///
+/// This is done only on downloading, so the cached repositories don't warn on every load.
+fn warn_unknown_keys(cache: &JsonMap, url: &Url) {
+    for key in cache.keys() {
+        if !KNOWN_REPOSITORY_KEYS.contains(&key.as_str()) {
+            log::warn!("unknown key '{key}' in repository {url}");
+        }
+    }
+}
+
+/// The error occurred while downloading a remote repository.
+///
+/// This can be converted to [`io::Error`] for callers not interested in the reason.
+/// Use [`LoadRepositoryErr::from_io_error`] to get this error back from the converted error.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum LoadRepositoryErr {
+    /// Failed to connect to the server or to read the response.
+    Network(io::Error),
+    /// The server responded with an error status code.
+    HttpStatus(u16),
+    /// The response is not a valid JSON.
+    InvalidJson { line: usize, column: usize },
+    /// The response is a JSON but not a repository.
+    UnsupportedSchema(Box<str>),
+    /// The response is empty.
+    Empty,
+    /// The repository is not cached and cannot be downloaded in offline mode.
+    RepositoryNotCached { url: Url },
+    /// The environment variable referenced with `${NAME}` in the url or the headers is not set.
+    MissingEnvVar { name: Box<str> },
+}
+
+impl LoadRepositoryErr {
+    fn from_client_error(error: io::Error) -> Self {
+        match HttpStatusError::from_io_error(&error) {
+            Some(error) => Self::HttpStatus(error.status()),
+            None => Self::Network(error),
+        }
+    }
+
+    /// The error for the not modified response to the request without validators,
+    /// which no server should respond.
+    pub(crate) fn not_modified_without_validators() -> Self {
+        Self::Network(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the server responded not modified to the request without validators",
+        ))
+    }
+
+    fn from_json_error(error: serde_json::Error) -> Self {
+        use serde_json::error::Category;
+        match error.classify() {
+            Category::Syntax | Category::Eof => Self::InvalidJson {
+                line: error.line(),
+                column: error.column(),
+            },
+            Category::Data => Self::UnsupportedSchema(error.to_string().into()),
+            Category::Io => Self::Network(error.into()),
+        }
+    }
+
+    /// Returns true if downloading the repository again may succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            LoadRepositoryErr::Network(_) => true,
+            LoadRepositoryErr::HttpStatus(status) => *status == 429 || *status >= 500,
+            LoadRepositoryErr::InvalidJson { .. }
+            | LoadRepositoryErr::UnsupportedSchema(_)
+            | LoadRepositoryErr::Empty
+            | LoadRepositoryErr::RepositoryNotCached { .. }
+            | LoadRepositoryErr::MissingEnvVar { .. } => false,
+        }
+    }
+
+    /// Gets the error converted to [`io::Error`].
+    ///
+    /// Network errors are converted to the underlying [`io::Error`], so this returns `None` for them.
+    pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for LoadRepositoryErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadRepositoryErr::Network(err) => fmt::Display::fmt(err, f),
+            LoadRepositoryErr::HttpStatus(status) => {
+                write!(f, "the server responded with status {status}")
+            }
+            LoadRepositoryErr::InvalidJson { line, column } => {
+                write!(f, "invalid JSON at line {line} column {column}")
+            }
+            LoadRepositoryErr::UnsupportedSchema(message) => {
+                write!(f, "not a repository: {message}")
+            }
+            LoadRepositoryErr::Empty => f.write_str("the repository is empty"),
+            LoadRepositoryErr::RepositoryNotCached { url } => {
+                write!(f, "the repository {url} is not cached (offline mode)")
+            }
+            LoadRepositoryErr::MissingEnvVar { name } => {
+                write!(f, "the environment variable {name} is not set")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LoadRepositoryErr {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoadRepositoryErr::Network(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<LoadRepositoryErr> for io::Error {
+    fn from(value: LoadRepositoryErr) -> Self {
+        let kind = match value {
+            // keep the error from the http client, like authentication failures, detectable
+            LoadRepositoryErr::Network(err) => return err,
+            LoadRepositoryErr::HttpStatus(404) => io::ErrorKind::NotFound,
+            LoadRepositoryErr::HttpStatus(_) => io::ErrorKind::Other,
+            LoadRepositoryErr::InvalidJson { .. } | LoadRepositoryErr::UnsupportedSchema(_) => {
+                io::ErrorKind::InvalidData
+            }
+            LoadRepositoryErr::Empty => io::ErrorKind::UnexpectedEof,
+            LoadRepositoryErr::RepositoryNotCached { .. } => io::ErrorKind::NotFound,
+            LoadRepositoryErr::MissingEnvVar { .. } => io::ErrorKind::InvalidInput,
+        };
+        io::Error::new(kind, value)
     }
 }
 
@@ -158,14 +718,14 @@ impl<'de> Deserialize<'de> for RemoteRepository {
 
 fn deserialize_packages<'de, D>(
     deserializer: D,
-) -> Result<IndexMap<Box<str>, RemotePackages>, D::Error>
+) -> Result<IndexMap<Box<str>, OnceLock<RemotePackages>>, D::Error>
 where
     D: Deserializer<'de>,
 {
     struct VisitorImpl;
 
     impl<'de> Visitor<'de> for VisitorImpl {
-        type Value = IndexMap<Box<str>, RemotePackages>;
+        type Value = IndexMap<Box<str>, OnceLock<RemotePackages>>;
 
         fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
             formatter.write_str("a map of package names to package versions")
@@ -177,8 +737,9 @@ where
         {
             let mut packages = IndexMap::new();
             while let Some(name) = map.next_key::<Box<str>>()? {
-                let versions = map.next_value_seed(PackageNameToRemotePackages(&name))?;
-                packages.insert(name, versions);
+                let value = map.next_value::<serde_value::Value>()?;
+                let versions = remote_packages_or_warn(&name, value);
+                packages.insert(name, OnceLock::from(versions));
             }
             Ok(packages)
         }
@@ -187,7 +748,23 @@ where
     deserializer.deserialize_map(VisitorImpl)
 }
 
-#[derive(Debug, Clone)]
+/// Parses the versions of the package, or no versions with the warning if the package is malformed.
+///
+/// This is shared by the eager and lazy parsing to report malformed packages in the same way.
+fn remote_packages_or_warn<'de>(
+    name: &str,
+    deserializer: impl Deserializer<'de>,
+) -> RemotePackages {
+    match PackageNameToRemotePackages(name).deserialize(deserializer) {
+        Ok(packages) => packages,
+        Err(err) => {
+            log::warn!("Error deserializing versions of package {name}: {err}");
+            RemotePackages::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
 pub struct RemotePackages {
     versions: HashMap<Version, PackageManifest>,
 }
@@ -199,10 +776,21 @@ impl RemotePackages {
 
     pub fn get_latest_may_yanked(&self, selector: VersionSelector) -> Option<&PackageManifest> {
         self.get_latest(selector).or_else(|| {
-            self.versions
+            let candidates = self
+                .versions
                 .values()
-                .filter(|json| selector.satisfies(json))
-                .max_by_key(|json| json.version())
+                .filter(|json| selector.satisfies(json));
+            if selector.prefers_lowest() {
+                candidates.min_by_key(|json| {
+                    let preferred = selector.is_preferred(json);
+                    (!preferred, selector.is_deprioritized(json), json.version())
+                })
+            } else {
+                candidates.max_by_key(|json| {
+                    let preferred = selector.is_preferred(json);
+                    (preferred, !selector.is_deprioritized(json), json.version())
+                })
+            }
         })
     }
 
@@ -211,12 +799,23 @@ impl RemotePackages {
             return self.versions.get(version);
         }
 
-        self.versions
+        let candidates = self
+            .versions
             .values()
             .filter(|json| selector.satisfies(json))
             .clone()
-            .filter(|json| !json.is_yanked())
-            .max_by_key(|json| json.version())
+            .filter(|json| !json.is_yanked());
+        if selector.prefers_lowest() {
+            candidates.min_by_key(|json| {
+                let preferred = selector.is_preferred(json);
+                (!preferred, selector.is_deprioritized(json), json.version())
+            })
+        } else {
+            candidates.max_by_key(|json| {
+                let preferred = selector.is_preferred(json);
+                (preferred, !selector.is_deprioritized(json), json.version())
+            })
+        }
     }
 
     pub fn get_version(&self, version: &Version) -> Option<&PackageManifest> {
@@ -247,9 +846,12 @@ impl<'de> DeserializeSeed<'de> for PackageNameToRemotePackages<'_> {
                 A: serde::de::MapAccess<'de>,
             {
                 let mut versions = HashMap::new();
-                while let Some(key) = map.next_key::<&'de str>()? {
-                    if key == "versions" {
+                // the keys are owned since the packages may be parsed from the buffered value
+                while let Some(key) = map.next_key::<Box<str>>()? {
+                    if key.as_ref() == "versions" {
                         versions = map.next_value_seed(PackageNameToVersions(self.0))?;
+                    } else {
+                        map.next_value::<serde::de::IgnoredAny>()?;
                     }
                 }
                 Ok(RemotePackages { versions })
@@ -0,0 +1,161 @@
+use crate::repository::RemoteRepository;
+use crate::traits::rewritten_url_error;
+use crate::version::Version;
+use crate::{HttpClient, PackageCollection};
+use indexmap::IndexMap;
+use serde_json::Value;
+use std::fmt;
+use url::Url;
+
+/// The problem found in the repository by [`validate_repository`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RepositoryWarning {
+    /// The key or the `version` of the version entry is not a valid version.
+    InvalidVersion {
+        package: Box<str>,
+        version: Box<str>,
+    },
+    /// The dependency is found neither in the repository nor in the collection.
+    MissingDependency {
+        package: Box<str>,
+        version: Version,
+        dependency: Box<str>,
+    },
+    /// The package lists itself in `legacyPackages`.
+    LegacyOfItself { package: Box<str>, version: Version },
+    /// The version has no `url` to download the zip file from.
+    MissingUrl { package: Box<str>, version: Version },
+    /// Requesting the `url` of the version failed.
+    UnreachableUrl {
+        package: Box<str>,
+        version: Version,
+        url: Url,
+        error: Box<str>,
+    },
+}
+
+impl fmt::Display for RepositoryWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepositoryWarning::InvalidVersion { package, version } => {
+                write!(f, "{package}: invalid version '{version}'")
+            }
+            RepositoryWarning::MissingDependency {
+                package,
+                version,
+                dependency,
+            } => write!(f, "{package}@{version}: dependency {dependency} not found"),
+            RepositoryWarning::LegacyOfItself { package, version } => {
+                write!(
+                    f,
+                    "{package}@{version}: the package is a legacy package of itself"
+                )
+            }
+            RepositoryWarning::MissingUrl { package, version } => {
+                write!(f, "{package}@{version}: no url of the zip file")
+            }
+            RepositoryWarning::UnreachableUrl {
+                package,
+                version,
+                url,
+                error,
+            } => write!(f, "{package}@{version}: {url} is not reachable: {error}"),
+        }
+    }
+}
+
+/// Checks the consistency of the repository, for maintainers of repositories.
+///
+/// Dependencies are looked up in the repository first, then in `env`, which should have the
+/// upstream repositories like the official one.
+/// If `http` is given, the `url` of every version is requested with [`HttpClient::head`].
+/// Urls with the `file` scheme are not checked.
+/// The warnings are grouped by the kind, in the order of the packages in the repository.
+pub async fn validate_repository(
+    listing: &RemoteRepository,
+    env: &impl PackageCollection,
+    http: Option<&impl HttpClient>,
+) -> Vec<RepositoryWarning> {
+    let mut warnings = Vec::new();
+
+    // invalid versions are not parsed, so those are found in the json
+    let packages = (listing.raw_json().get("packages")).and_then(Value::as_object);
+    for (package, value) in packages.into_iter().flatten() {
+        let versions = (value.get("versions")).and_then(Value::as_object);
+        for (key, manifest) in versions.into_iter().flatten() {
+            let version = manifest.get("version").and_then(Value::as_str);
+            let invalid = ([Some(key.as_str()), version].into_iter().flatten())
+                .find(|x| x.parse::<Version>().is_err());
+            if let Some(invalid) = invalid {
+                warnings.push(RepositoryWarning::InvalidVersion {
+                    package: package.as_str().into(),
+                    version: invalid.into(),
+                });
+            }
+        }
+    }
+
+    let mut manifests = Vec::new();
+    for package in listing.get_packages() {
+        let mut versions = package.all_versions().collect::<Vec<_>>();
+        versions.sort_by(|a, b| b.version().cmp(a.version()));
+        manifests.extend(versions);
+    }
+
+    for manifest in &manifests {
+        for dependency in manifest.vpm_dependencies().keys() {
+            if listing.get_package(dependency).is_none()
+                && env.find_packages(dependency).next().is_none()
+            {
+                warnings.push(RepositoryWarning::MissingDependency {
+                    package: manifest.name().into(),
+                    version: manifest.version().clone(),
+                    dependency: dependency.clone(),
+                });
+            }
+        }
+    }
+
+    for manifest in &manifests {
+        if (manifest.legacy_packages().iter()).any(|x| x.as_ref() == manifest.name()) {
+            warnings.push(RepositoryWarning::LegacyOfItself {
+                package: manifest.name().into(),
+                version: manifest.version().clone(),
+            });
+        }
+    }
+
+    for manifest in &manifests {
+        let Some(url) = manifest.url() else {
+            warnings.push(RepositoryWarning::MissingUrl {
+                package: manifest.name().into(),
+                version: manifest.version().clone(),
+            });
+            continue;
+        };
+        let Some(http) = http else { continue };
+        if url.scheme() == "file" {
+            continue;
+        }
+
+        let headers = IndexMap::<&str, &str>::from_iter(
+            (manifest.headers().iter()).map(|(k, v)| (k.as_ref(), v.as_ref())),
+        );
+        let result = match http.rewrite_url(url) {
+            Ok(rewritten) => (http.head(rewritten.as_ref().unwrap_or(url), &headers).await)
+                .map_err(|err| rewritten_url_error(url, rewritten.as_ref(), err)),
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            warnings.push(RepositoryWarning::UnreachableUrl {
+                package: manifest.name().into(),
+                version: manifest.version().clone(),
+                url: url.clone(),
+                error: err.to_string().into(),
+            });
+        }
+    }
+
+    warnings
+}
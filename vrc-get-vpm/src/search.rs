@@ -0,0 +1,155 @@
+//! Searching packages in the repositories.
+
+use crate::{PackageCollection, PackageInfo, VersionSelector};
+use std::collections::HashMap;
+
+/// The number of packages returned by [`search_packages`] by default.
+pub const DEFAULT_SEARCH_LIMIT: usize = 50;
+
+/// Options for [`search_packages`].
+pub struct SearchOptions {
+    allow_prerelease: bool,
+    offset: usize,
+    limit: usize,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SearchOptions {
+    pub fn new() -> Self {
+        Self {
+            allow_prerelease: false,
+            offset: 0,
+            limit: DEFAULT_SEARCH_LIMIT,
+        }
+    }
+
+    /// Makes prerelease versions the latest version of the packages.
+    pub fn allow_prerelease(mut self, allow_prerelease: bool) -> Self {
+        self.allow_prerelease = allow_prerelease;
+        self
+    }
+
+    /// Skips the specified number of matched packages, for showing the following pages.
+    pub fn offset(mut self, offset: usize) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Sets the maximum number of packages returned.
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+/// How the query matched the package. Better matches are smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SearchMatch {
+    /// The query is the id of the package.
+    ExactId,
+    /// All the words in the query are in the id of the package.
+    Id,
+    /// All the words in the query are in the id or the display name of the package.
+    DisplayName,
+    /// All the words in the query are in the id, the display name, or the description of the package.
+    Description,
+}
+
+/// The package matched by [`search_packages`].
+#[derive(Debug, Clone, Copy)]
+pub struct PackageSummary<'a> {
+    // the latest version of the package. use PackageInfo::repo for the repository
+    pub package: PackageInfo<'a>,
+    pub matched: SearchMatch,
+}
+
+/// The result of [`search_packages`].
+#[derive(Debug, Clone)]
+pub struct SearchResult<'a> {
+    // the matched packages in the page, better matches first
+    pub packages: Vec<PackageSummary<'a>>,
+    // the number of all the matched packages
+    pub total: usize,
+}
+
+impl SearchResult<'_> {
+    /// Returns true if there are more matched packages after this page.
+    pub fn has_more(&self, options: &SearchOptions) -> bool {
+        options.offset + self.packages.len() < self.total
+    }
+}
+
+fn match_package(package: PackageInfo, query: &str, words: &[String]) -> Option<SearchMatch> {
+    if package.name().eq_ignore_ascii_case(query) {
+        return Some(SearchMatch::ExactId);
+    }
+
+    let name = package.name().to_lowercase();
+    let display_name = package.display_name().map(str::to_lowercase);
+    let description = package.description().map(str::to_lowercase);
+
+    let in_name = |word: &str| name.contains(word);
+    let in_display_name = |word: &str| display_name.as_ref().is_some_and(|x| x.contains(word));
+    let in_description = |word: &str| description.as_ref().is_some_and(|x| x.contains(word));
+
+    if words.iter().all(|x| in_name(x)) {
+        Some(SearchMatch::Id)
+    } else if words.iter().all(|x| in_name(x) || in_display_name(x)) {
+        Some(SearchMatch::DisplayName)
+    } else if (words.iter()).all(|x| in_name(x) || in_display_name(x) || in_description(x)) {
+        Some(SearchMatch::Description)
+    } else {
+        None
+    }
+}
+
+/// Searches the latest version of the packages matching the query.
+///
+/// The query is split into words by whitespaces, and the package matches if all the words are
+/// in the id, the display name, or the description of the latest version, case-insensitively.
+/// Yanked versions are ignored. The packages are sorted by how well they match, then the id.
+pub fn search_packages<'env>(
+    env: &'env impl PackageCollection,
+    query: &str,
+    options: &SearchOptions,
+) -> SearchResult<'env> {
+    let query = query.trim();
+    let words = (query.split_whitespace())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>();
+
+    let selector = VersionSelector::latest_for(None, options.allow_prerelease);
+    let mut latest = HashMap::<&str, PackageInfo>::new();
+    for package in env.get_all_packages() {
+        if package.is_yanked() || !selector.satisfies(package.package_json()) {
+            continue;
+        }
+        let entry = latest.entry(package.name()).or_insert(package);
+        if entry.version() < package.version() {
+            *entry = package;
+        }
+    }
+
+    let mut matched = (latest.into_values())
+        .filter_map(|package| {
+            let matched = match_package(package, query, &words)?;
+            Some(PackageSummary { package, matched })
+        })
+        .collect::<Vec<_>>();
+    matched.sort_by(|a, b| {
+        (a.matched.cmp(&b.matched)).then_with(|| a.package.name().cmp(b.package.name()))
+    });
+
+    let total = matched.len();
+    let packages = (matched.into_iter())
+        .skip(options.offset)
+        .take(options.limit)
+        .collect();
+
+    SearchResult { packages, total }
+}
@@ -18,6 +18,20 @@ pub mod setting {
         pub(crate) id: Option<Box<str>>,
         #[serde(default)]
         pub(crate) headers: IndexMap<Box<str>, Box<str>>,
+        // names of headers whose values should not be shown or exported, like auth tokens.
+        #[serde(
+            default,
+            rename = "vrc-get-secret-headers",
+            skip_serializing_if = "Vec::is_empty"
+        )]
+        pub(crate) secret_headers: Vec<Box<str>>,
+        // prefixes of the package ids trusted from the repository. empty means all packages.
+        #[serde(
+            default,
+            rename = "vrc-get-allowed-package-prefixes",
+            skip_serializing_if = "Vec::is_empty"
+        )]
+        pub(crate) allowed_package_prefixes: Vec<Box<str>>,
     }
 
     impl UserRepoSetting {
@@ -33,6 +47,8 @@ pub mod setting {
                 id: id.or(url.as_ref().map(Url::to_string).map(Into::into)),
                 url,
                 headers: IndexMap::new(),
+                secret_headers: Vec::new(),
+                allowed_package_prefixes: Vec::new(),
             }
         }
 
@@ -56,8 +72,28 @@ pub mod setting {
             &self.headers
         }
 
+        pub fn secret_headers(&self) -> &[Box<str>] {
+            &self.secret_headers
+        }
+
+        /// Returns true if the value of the header should be kept secret.
+        pub fn is_secret_header(&self, name: &str) -> bool {
+            (self.secret_headers.iter()).any(|x| x.eq_ignore_ascii_case(name))
+        }
+
+        /// The prefixes of the ids of the packages used from the repository.
+        ///
+        /// Packages whose id doesn't start with any of the prefixes are ignored. A trailing `*` of
+        /// the prefix is ignored, so `com.vendor.*` allows `com.vendor.tool`.
+        /// Empty if all the packages in the repository are used.
+        pub fn allowed_package_prefixes(&self) -> &[Box<str>] {
+            &self.allowed_package_prefixes
+        }
+
         pub(crate) fn to_source(&self) -> RepoSource {
             RepoSource::new(&self.local_path, &self.headers, self.url.as_ref())
+                .with_allowed_package_prefixes(&self.allowed_package_prefixes)
+                .with_secret_headers(&self.secret_headers)
         }
     }
 }
@@ -0,0 +1,128 @@
+//! The utilities for testing the code using this crate.
+//!
+//! This module is available with the `testing` feature.
+
+use crate::version::Version;
+use crate::{PackageCollection, PackageInfo, PackageManifest};
+use std::path::PathBuf;
+
+/// The package collection with the packages on memory.
+///
+/// This is useful for testing the resolution without any I/O.
+/// Each package is treated as a local package at `Packages/<name>/<version>`.
+pub struct InMemoryPackageCollection {
+    packages: Vec<(PackageManifest, PathBuf)>,
+}
+
+impl InMemoryPackageCollection {
+    /// Creates the empty collection.
+    pub fn new() -> Self {
+        Self { packages: vec![] }
+    }
+
+    /// Creates the collection from the list of `(name, version, vpm dependencies, legacy packages)`.
+    ///
+    /// The vpm dependencies are the list of `(name, range)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any ranges of the vpm dependencies are invalid.
+    pub fn from_tuples<'a>(
+        packages: impl IntoIterator<Item = (&'a str, Version, Vec<(&'a str, &'a str)>, Vec<&'a str>)>,
+    ) -> Self {
+        let mut collection = Self::new();
+        for (name, version, dependencies, legacy_packages) in packages {
+            let mut manifest = PackageManifest::new(name, version);
+            for (dependency, range) in dependencies {
+                manifest = manifest.add_vpm_dependency(dependency, range);
+            }
+            for legacy in legacy_packages {
+                manifest = manifest.add_legacy_package(legacy);
+            }
+            collection = collection.add_package(manifest);
+        }
+        collection
+    }
+
+    /// Adds the package to the collection.
+    pub fn add_package(mut self, manifest: PackageManifest) -> Self {
+        let path = format!("Packages/{}/{}", manifest.name(), manifest.version());
+        self.packages.push((manifest, path.into()));
+        self
+    }
+}
+
+impl Default for InMemoryPackageCollection {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PackageCollection for InMemoryPackageCollection {
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        (self.packages.iter()).map(|(json, path)| PackageInfo::local(json, path))
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        self.get_all_packages()
+            .filter(move |pkg| pkg.name() == package)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::unity_project::ResolutionOptions;
+    use crate::unity_project::resolve_dependencies;
+    use crate::version::DependencyRange;
+
+    #[test]
+    fn resolve_in_memory() {
+        let collection = InMemoryPackageCollection::from_tuples([
+            (
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                vec![("com.vrchat.base", "^1.0.0")],
+                vec![],
+            ),
+            ("com.vrchat.base", Version::new(1, 0, 0), vec![], vec![]),
+            ("com.vrchat.base", Version::new(1, 1, 0), vec![], vec![]),
+            (
+                "com.vrchat.base",
+                Version::new(2, 0, 0),
+                vec![],
+                vec!["com.vrchat.legacy"],
+            ),
+        ]);
+
+        let root = "1.x".parse::<DependencyRange>().unwrap();
+        let options = ResolutionOptions::new();
+        let result =
+            resolve_dependencies([("com.vrchat.avatars", &root)], &collection, None, &options)
+                .unwrap();
+
+        let mut installed = (result.new_packages.iter())
+            .map(|x| (x.name(), x.version().clone()))
+            .collect::<Vec<_>>();
+        installed.sort();
+        assert_eq!(
+            installed,
+            vec![
+                ("com.vrchat.avatars", Version::new(1, 0, 0)),
+                ("com.vrchat.base", Version::new(1, 1, 0)),
+            ]
+        );
+        assert!(result.conflicts.is_empty());
+
+        let missing = "1.x".parse::<DependencyRange>().unwrap();
+        assert!(
+            resolve_dependencies(
+                [("com.vrchat.missing", &missing)],
+                &collection,
+                None,
+                &options
+            )
+            .is_err()
+        );
+    }
+}
@@ -1,13 +1,17 @@
 use crate::io;
 use crate::io::ProjectIo;
+use crate::unity_project::ResolutionCache;
 use crate::utils::MapResultExt;
+use crate::version::Version;
 use crate::{PackageInfo, VersionSelector};
 use core::iter::Iterator;
 use core::option::Option;
 use futures::prelude::*;
 use indexmap::IndexMap;
 use std::convert::Infallible;
+use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 use url::Url;
 
 pub trait PackageCollection {
@@ -20,17 +24,238 @@ pub trait PackageCollection {
     }
 
     /// get all packages in the collection
+    ///
+    /// Every version of every package is returned, including yanked ones, not only the latest
+    /// version of each package. Implementations should return them lazily.
     fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo>;
 
     /// get all package versions of the specified package
     fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo>;
 
+    /// get all package versions of the specified package, sorted newest-first
+    ///
+    /// Versions from multiple repositories are all returned, including yanked ones.
+    fn find_packages_by_name(&self, package: &str) -> Vec<PackageInfo> {
+        let mut packages = self.find_packages(package).collect::<Vec<_>>();
+        packages.sort_by(|a, b| b.version().cmp(a.version()));
+        packages
+    }
+
     /// get specified version of specified package
+    ///
+    /// The default implementation returns the newest version satisfying the selector,
+    /// or the oldest one if the selector prefers the lowest version.
+    /// The version preferred by the selector is returned if it satisfies the selector.
+    fn find_package_by_name(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+    ) -> Option<PackageInfo> {
+        let mut candidates = (self.find_packages_by_name(package).into_iter())
+            .filter(|x| package_selector.satisfies_package(x))
+            .collect::<Vec<_>>();
+        if let Some(&preferred) =
+            (candidates.iter()).find(|x| package_selector.is_preferred(x.package_json()))
+        {
+            return Some(preferred);
+        }
+        if package_selector.prefers_lowest() {
+            candidates.reverse();
+        }
+        (candidates.iter())
+            .find(|x| !package_selector.is_deprioritized(x.package_json()))
+            .or(candidates.first())
+            .copied()
+    }
+
+    /// get the exact version of specified package
+    ///
+    /// The resolver uses this when it knows the exact version it wants, like pinned versions.
+    /// Collections backed by a registry with an endpoint for each version can override this to
+    /// fetch just the version instead of the whole listing of the package.
+    /// The default implementation looks up the version with [`find_package_by_name`].
+    ///
+    /// [`find_package_by_name`]: PackageCollection::find_package_by_name
+    fn fetch_package(&self, package: &str, version: &Version) -> Option<PackageInfo> {
+        self.find_package_by_name(package, VersionSelector::specific_version(version))
+    }
+
+    /// get specified version of specified package, preferring repositories listed earlier in `priority`
+    ///
+    /// If multiple repositories have the version found by [`find_package_by_name`], the one from
+    /// the repository with the highest priority is returned.
+    /// Repositories not in `priority` have lower priority than any listed.
+    ///
+    /// [`find_package_by_name`]: PackageCollection::find_package_by_name
+    fn find_package_by_name_with_priority(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+        priority: &[&str],
+    ) -> Option<PackageInfo> {
+        let found = self.find_package_by_name(package, package_selector)?;
+
+        let priority_of = |package: PackageInfo| {
+            (package.repository_id())
+                .and_then(|id| priority.iter().position(|&x| x == id))
+                .unwrap_or(usize::MAX)
+        };
+
+        let preferred = (self.find_packages(package))
+            .filter(|x| x.version() == found.version())
+            .filter(|x| package_selector.satisfies_package(x))
+            .min_by_key(|&x| priority_of(x))
+            .filter(|&x| priority_of(x) < priority_of(found));
+
+        Some(preferred.unwrap_or(found))
+    }
+
+    /// get the cache of resolution results for this collection
+    ///
+    /// The resolver reuses the cached result if the same inputs are resolved again.
+    /// See [`CachedPackageCollection`] for the collection with the cache.
+    ///
+    /// [`CachedPackageCollection`]: crate::unity_project::CachedPackageCollection
+    fn resolution_cache(&self) -> Option<&ResolutionCache> {
+        None
+    }
+}
+
+/// The object-safe version of [`PackageCollection`], for collections composed at runtime.
+///
+/// [`PackageCollection`] returns `impl Iterator`, so it cannot be used as a trait object.
+/// Every [`PackageCollection`] implements this trait, and `&dyn DynPackageCollection`
+/// implements [`PackageCollection`] by forwarding to the methods here.
+/// So a `&dyn DynPackageCollection` can be passed to every function taking `impl PackageCollection`,
+/// and all of them are compiled once for the trait object, instead of for each collection type.
+/// Only [`PackageCollection::get_all_packages`] is lazy through the trait object. The packages
+/// found by name and the curated packages are collected since the iterators borrow the arguments.
+///
+/// Implement [`PackageCollection`] instead of this trait for custom collections.
+pub trait DynPackageCollection {
+    /// See [`PackageCollection::get_curated_packages`]
+    fn dyn_get_curated_packages(&self, version_selector: VersionSelector) -> Vec<PackageInfo<'_>>;
+
+    /// See [`PackageCollection::get_all_packages`]
+    fn dyn_get_all_packages(&self) -> Box<dyn Iterator<Item = PackageInfo<'_>> + '_>;
+
+    /// See [`PackageCollection::find_packages`]
+    fn dyn_find_packages(&self, package: &str) -> Vec<PackageInfo<'_>>;
+
+    /// See [`PackageCollection::find_packages_by_name`]
+    fn dyn_find_packages_by_name(&self, package: &str) -> Vec<PackageInfo<'_>>;
+
+    /// See [`PackageCollection::find_package_by_name`]
+    fn dyn_find_package_by_name(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+    ) -> Option<PackageInfo<'_>>;
+
+    /// See [`PackageCollection::fetch_package`]
+    fn dyn_fetch_package(&self, package: &str, version: &Version) -> Option<PackageInfo<'_>>;
+
+    /// See [`PackageCollection::find_package_by_name_with_priority`]
+    fn dyn_find_package_by_name_with_priority(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+        priority: &[&str],
+    ) -> Option<PackageInfo<'_>>;
+
+    /// See [`PackageCollection::resolution_cache`]
+    fn dyn_resolution_cache(&self) -> Option<&ResolutionCache>;
+}
+
+impl<T: PackageCollection> DynPackageCollection for T {
+    fn dyn_get_curated_packages(&self, version_selector: VersionSelector) -> Vec<PackageInfo<'_>> {
+        self.get_curated_packages(version_selector).collect()
+    }
+
+    fn dyn_get_all_packages(&self) -> Box<dyn Iterator<Item = PackageInfo<'_>> + '_> {
+        Box::new(self.get_all_packages())
+    }
+
+    fn dyn_find_packages(&self, package: &str) -> Vec<PackageInfo<'_>> {
+        self.find_packages(package).collect()
+    }
+
+    fn dyn_find_packages_by_name(&self, package: &str) -> Vec<PackageInfo<'_>> {
+        self.find_packages_by_name(package)
+    }
+
+    fn dyn_find_package_by_name(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+    ) -> Option<PackageInfo<'_>> {
+        self.find_package_by_name(package, package_selector)
+    }
+
+    fn dyn_fetch_package(&self, package: &str, version: &Version) -> Option<PackageInfo<'_>> {
+        self.fetch_package(package, version)
+    }
+
+    fn dyn_find_package_by_name_with_priority(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+        priority: &[&str],
+    ) -> Option<PackageInfo<'_>> {
+        self.find_package_by_name_with_priority(package, package_selector, priority)
+    }
+
+    fn dyn_resolution_cache(&self) -> Option<&ResolutionCache> {
+        self.resolution_cache()
+    }
+}
+
+impl PackageCollection for &dyn DynPackageCollection {
+    fn get_curated_packages(
+        &self,
+        version_selector: VersionSelector,
+    ) -> impl Iterator<Item = PackageInfo> {
+        (**self)
+            .dyn_get_curated_packages(version_selector)
+            .into_iter()
+    }
+
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        (**self).dyn_get_all_packages()
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        (**self).dyn_find_packages(package).into_iter()
+    }
+
+    fn find_packages_by_name(&self, package: &str) -> Vec<PackageInfo> {
+        (**self).dyn_find_packages_by_name(package)
+    }
+
     fn find_package_by_name(
         &self,
         package: &str,
         package_selector: VersionSelector,
-    ) -> Option<PackageInfo>;
+    ) -> Option<PackageInfo> {
+        (**self).dyn_find_package_by_name(package, package_selector)
+    }
+
+    fn fetch_package(&self, package: &str, version: &Version) -> Option<PackageInfo> {
+        (**self).dyn_fetch_package(package, version)
+    }
+
+    fn find_package_by_name_with_priority(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+        priority: &[&str],
+    ) -> Option<PackageInfo> {
+        (**self).dyn_find_package_by_name_with_priority(package, package_selector, priority)
+    }
+
+    fn resolution_cache(&self) -> Option<&ResolutionCache> {
+        (**self).dyn_resolution_cache()
+    }
 }
 
 /// The trait for installing package
@@ -44,16 +269,43 @@ pub trait PackageInstaller {
         package: PackageInfo<'_>,
         abort: &AbortCheck,
     ) -> impl Future<Output = io::Result<()>>;
+
+    /// Checks the package can be installed, without installing it.
+    ///
+    /// The default implementation fails with [`io::ErrorKind::Unsupported`] since nothing is
+    /// known about the packages the installer can install.
+    fn check_package(&self, package: PackageInfo<'_>) -> impl Future<Output = io::Result<()>> {
+        let _ = package;
+        std::future::ready(Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "checking packages is not supported by the installer",
+        )))
+    }
+}
+
+/// The trait for receiving the progress of installing packages
+///
+/// Packages may be installed in parallel, so the methods may be called concurrently.
+pub trait InstallProgress: Send + Sync {
+    /// Called when some bytes of the zip file of the package are downloaded.
+    ///
+    /// `bytes_total` is `None` if the repository doesn't declare the size of the zip file.
+    fn download(&self, _package_name: &str, _bytes_done: u64, _bytes_total: Option<u64>) {}
+
+    /// Called when an entry of the zip file of the package is extracted.
+    fn extract(&self, _package_name: &str, _files_done: usize, _files_total: usize) {}
 }
 
 pub struct AbortCheck {
     abort: AtomicBool,
+    cancellation: Option<CancellationToken>,
 }
 
 impl AbortCheck {
-    pub(crate) fn new() -> Self {
+    pub(crate) fn new(cancellation: Option<&CancellationToken>) -> Self {
         Self {
             abort: AtomicBool::new(false),
+            cancellation: cancellation.cloned(),
         }
     }
 
@@ -61,6 +313,9 @@ impl AbortCheck {
         if self.abort.load(Ordering::Relaxed) {
             return Err(io::Error::new(io::ErrorKind::Interrupted, "Aborted"));
         }
+        if (self.cancellation.as_ref()).is_some_and(CancellationToken::is_cancelled) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "Cancelled"));
+        }
         Ok(())
     }
 
@@ -69,11 +324,37 @@ impl AbortCheck {
     }
 }
 
+/// The token to cancel installing packages from another task or thread.
+///
+/// Clones of the token share the state. See [`UnityProject::apply_pending_changes_cancellable`].
+///
+/// [`UnityProject::apply_pending_changes_cancellable`]: crate::UnityProject::apply_pending_changes_cancellable
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Requests cancellation. The operation stops at the next check, not immediately.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
 /// The HTTP Client.
 pub trait HttpClient: Sync {
     /// Get resource from the URL with specified headers
     ///
-    /// Note: If remote server returns error status code, this function should return error.
+    /// Note: If remote server returns error status code, this function should return error,
+    /// with [`HttpStatusError`] to report the status code.
     fn get(
         &self,
         url: &Url,
@@ -86,13 +367,180 @@ pub trait HttpClient: Sync {
     /// Returning `Ok(Some((stream, etag)))` means cache not matched and get from remote server.
     /// Returning `Err(_)` means error.
     ///
-    /// Note: If remote server returns error status code, this function should return error.
+    /// Note: If remote server returns error status code, this function should return error,
+    /// with [`HttpStatusError`] to report the status code.
     fn get_with_etag(
         &self,
         url: &Url,
         headers: &IndexMap<Box<str>, Box<str>>,
         current_etag: Option<&str>,
     ) -> impl Future<Output = io::Result<Option<(impl AsyncRead + Send, Option<Box<str>>)>>> + Send;
+
+    /// Get resource from the URL with specified headers, etag, and last modified date
+    ///
+    /// Returning `Ok(None)` means cache matched.
+    /// Returning `Ok(Some((stream, etag, last_modified)))` means cache not matched and get from remote server.
+    /// Returning `Err(_)` means error.
+    ///
+    /// The default implementation ignores `current_last_modified` and calls [`Self::get_with_etag`].
+    ///
+    /// Note: If remote server returns error status code, this function should return error,
+    /// with [`HttpStatusError`] to report the status code.
+    #[allow(clippy::type_complexity)]
+    fn get_with_validators(
+        &self,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+        current_last_modified: Option<&str>,
+    ) -> impl Future<
+        Output = io::Result<Option<(impl AsyncRead + Send, Option<Box<str>>, Option<Box<str>>)>>,
+    > + Send {
+        let _ = current_last_modified;
+        async move {
+            Ok(self
+                .get_with_etag(url, headers, current_etag)
+                .await?
+                .map(|(stream, etag)| (stream, etag, None)))
+        }
+    }
+
+    /// Checks the resource exists at the URL without getting the body, like the `HEAD` request.
+    ///
+    /// The default implementation gets the resource with [`Self::get`] and drops the body.
+    ///
+    /// Note: If remote server returns error status code, this function should return error,
+    /// with [`HttpStatusError`] to report the status code.
+    fn head(
+        &self,
+        url: &Url,
+        headers: &IndexMap<&str, &str>,
+    ) -> impl Future<Output = io::Result<()>> + Send {
+        async move {
+            self.get(url, headers).await?;
+            Ok(())
+        }
+    }
+
+    /// Returns the URL actually requested instead of the URL, or None to request the URL as is.
+    ///
+    /// This crate calls this before every request of repositories and packages.
+    /// The default implementation doesn't rewrite any URL.
+    fn rewrite_url(&self, url: &Url) -> io::Result<Option<Url>> {
+        let _ = url;
+        Ok(None)
+    }
+}
+
+/// The error returned by [`HttpClient`] when the server rejected the request with `401 Unauthorized`
+/// or `403 Forbidden`.
+///
+/// This is wrapped in [`io::Error`] with [`io::ErrorKind::PermissionDenied`].
+/// Use [`RepositoryAuthFailed::from_io_error`] to detect this error.
+#[derive(Debug)]
+pub struct RepositoryAuthFailed {
+    url: Url,
+}
+
+impl RepositoryAuthFailed {
+    pub fn new(url: Url) -> Self {
+        Self { url }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+
+    pub fn into_io_error(self) -> io::Error {
+        io::Error::new(io::ErrorKind::PermissionDenied, self)
+    }
+}
+
+impl std::fmt::Display for RepositoryAuthFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "authentication failed for {}. please check the headers of the repository",
+            self.url
+        )
+    }
+}
+
+impl std::error::Error for RepositoryAuthFailed {}
+
+/// The error returned by [`HttpClient`] when the server responded with an error status code.
+///
+/// This is wrapped in [`io::Error`] with [`io::ErrorKind::NotFound`] for `404 Not Found`,
+/// and with [`io::ErrorKind::Other`] for other status codes.
+/// Authentication failures are reported with [`RepositoryAuthFailed`] instead.
+/// Use [`HttpStatusError::from_io_error`] to detect this error.
+#[derive(Debug)]
+pub struct HttpStatusError {
+    url: Url,
+    status: u16,
+}
+
+impl HttpStatusError {
+    pub fn new(url: Url, status: u16) -> Self {
+        Self { url, status }
+    }
+
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
+    pub fn status(&self) -> u16 {
+        self.status
+    }
+
+    /// Finds this error in the error, including the errors wrapping this error.
+    pub fn from_io_error(err: &io::Error) -> Option<&Self> {
+        let mut current: &(dyn std::error::Error + 'static) = err.get_ref()?;
+        loop {
+            if let Some(err) = current.downcast_ref::<Self>() {
+                return Some(err);
+            }
+            current = match current.downcast_ref::<io::Error>() {
+                Some(err) => err.get_ref()?,
+                None => current.source()?,
+            };
+        }
+    }
+
+    pub fn into_io_error(self) -> io::Error {
+        let kind = match self.status {
+            404 => io::ErrorKind::NotFound,
+            _ => io::ErrorKind::Other,
+        };
+        io::Error::new(kind, self)
+    }
+}
+
+impl std::fmt::Display for HttpStatusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "the server responded with status {} for {}",
+            self.status, self.url
+        )
+    }
+}
+
+impl std::error::Error for HttpStatusError {}
+
+fn check_status(response: reqwest::Response) -> io::Result<reqwest::Response> {
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        return Err(RepositoryAuthFailed::new(response.url().clone()).into_io_error());
+    }
+    if status.is_client_error() || status.is_server_error() {
+        return Err(HttpStatusError::new(response.url().clone(), status.as_u16()).into_io_error());
+    }
+    Ok(response)
 }
 
 impl HttpClient for reqwest::Client {
@@ -105,22 +553,43 @@ impl HttpClient for reqwest::Client {
             request = request.header(name, header);
         }
 
-        Ok(request
-            .send()
-            .await
-            .and_then(reqwest::Response::error_for_status)
-            .err_mapped()?
+        Ok(check_status(request.send().await.err_mapped()?)?
             .bytes_stream()
             .map(|x| x.err_mapped())
             .into_async_read())
     }
 
+    async fn head(&self, url: &Url, headers: &IndexMap<&str, &str>) -> io::Result<()> {
+        let mut request = self.head(url.to_owned());
+
+        for (&name, &header) in headers {
+            request = request.header(name, header);
+        }
+
+        check_status(request.send().await.err_mapped()?)?;
+        Ok(())
+    }
+
     async fn get_with_etag(
         &self,
         url: &Url,
         headers: &IndexMap<Box<str>, Box<str>>,
         current_etag: Option<&str>,
     ) -> io::Result<Option<(impl AsyncRead, Option<Box<str>>)>> {
+        Ok(
+            HttpClient::get_with_validators(self, url, headers, current_etag, None)
+                .await?
+                .map(|(stream, etag, _)| (stream, etag)),
+        )
+    }
+
+    async fn get_with_validators(
+        &self,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+        current_last_modified: Option<&str>,
+    ) -> io::Result<Option<(impl AsyncRead, Option<Box<str>>, Option<Box<str>>)>> {
         let mut request = self.get(url.to_owned());
         for (name, value) in headers {
             request = request.header(name.as_ref(), value.as_ref());
@@ -128,11 +597,13 @@ impl HttpClient for reqwest::Client {
         if let Some(etag) = current_etag {
             request = request.header("If-None-Match", etag.to_owned())
         }
-        let response = request.send().await.err_mapped()?;
-        let response = response.error_for_status().err_mapped()?;
+        if let Some(last_modified) = current_last_modified {
+            request = request.header("If-Modified-Since", last_modified.to_owned())
+        }
+        let response = check_status(request.send().await.err_mapped()?)?;
 
-        if current_etag.is_some() && response.status() == 304 {
-            // for requests with etag, 304 means cache matched
+        if (current_etag.is_some() || current_last_modified.is_some()) && response.status() == 304 {
+            // for conditional requests, 304 means cache matched
             return Ok(None);
         }
 
@@ -141,6 +612,11 @@ impl HttpClient for reqwest::Client {
             .get("Etag")
             .and_then(|x| x.to_str().ok())
             .map(Into::into);
+        let last_modified = response
+            .headers()
+            .get("Last-Modified")
+            .and_then(|x| x.to_str().ok())
+            .map(Into::into);
 
         // response.json() doesn't support BOM
         let response_stream = response
@@ -148,7 +624,7 @@ impl HttpClient for reqwest::Client {
             .map(|x| x.err_mapped())
             .into_async_read();
 
-        Ok(Some((response_stream, etag)))
+        Ok(Some((response_stream, etag, last_modified)))
     }
 }
 
@@ -166,3 +642,341 @@ impl HttpClient for Infallible {
         Ok(Some((io::empty(), None)))
     }
 }
+
+/// The [`HttpClient`] rewriting every requested URL, for example to redirect requests to a mirror.
+///
+/// Both fetching repositories and downloading packages rewrite the URL with
+/// [`HttpClient::rewrite_url`] just before the request, so wrapping the client of the environment
+/// with this redirects all requests. Calling [`HttpClient::get`] directly doesn't rewrite the URL.
+/// Errors for rewritten URLs mention both the original and the rewritten URL.
+pub struct UrlRewritingClient<C, F> {
+    inner: C,
+    rewrite: F,
+}
+
+impl<C: HttpClient, F: Fn(&str) -> String + Sync> UrlRewritingClient<C, F> {
+    pub fn new(inner: C, rewrite: F) -> Self {
+        Self { inner, rewrite }
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C: HttpClient, F: Fn(&str) -> String + Sync> HttpClient for UrlRewritingClient<C, F> {
+    async fn get(
+        &self,
+        url: &Url,
+        headers: &IndexMap<&str, &str>,
+    ) -> io::Result<impl AsyncRead + Send> {
+        self.inner.get(url, headers).await
+    }
+
+    async fn head(&self, url: &Url, headers: &IndexMap<&str, &str>) -> io::Result<()> {
+        self.inner.head(url, headers).await
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+    ) -> io::Result<Option<(impl AsyncRead + Send, Option<Box<str>>)>> {
+        self.inner.get_with_etag(url, headers, current_etag).await
+    }
+
+    #[allow(clippy::type_complexity)]
+    async fn get_with_validators(
+        &self,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+        current_last_modified: Option<&str>,
+    ) -> io::Result<Option<(impl AsyncRead + Send, Option<Box<str>>, Option<Box<str>>)>> {
+        (self.inner)
+            .get_with_validators(url, headers, current_etag, current_last_modified)
+            .await
+    }
+
+    fn rewrite_url(&self, url: &Url) -> io::Result<Option<Url>> {
+        let rewritten = (self.rewrite)(url.as_str());
+        if rewritten == url.as_str() {
+            return Ok(None);
+        }
+        match Url::parse(&rewritten) {
+            Ok(rewritten) => Ok(Some(rewritten)),
+            Err(err) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{url} is rewritten to invalid url {rewritten}: {err}"),
+            )),
+        }
+    }
+}
+
+/// The [`HttpClient`] retrying failed requests with exponential backoff.
+///
+/// Only transient errors are retried: timeouts, connection errors, and `5xx` responses.
+/// Other errors including `4xx` responses are returned immediately.
+/// The delay before the n-th retry is `base_delay * 2^(n - 1)`, and the error after all attempts
+/// failed reports the number of attempts.
+/// Like [`UrlRewritingClient`], wrap the client of the environment with this to retry both
+/// fetching repositories and downloading packages.
+/// Errors while reading the body after the response is received are not retried.
+///
+/// This library doesn't depend on any async runtime, so the client waits for the delay with
+/// the `sleep` function of the runtime passed to [`RetryingClient::new`], like `tokio::time::sleep`.
+pub struct RetryingClient<C> {
+    inner: C,
+    sleep: Box<dyn Fn(Duration) -> future::BoxFuture<'static, ()> + Send + Sync>,
+    max_attempts: u32,
+    base_delay: Duration,
+}
+
+impl<C: HttpClient> RetryingClient<C> {
+    pub fn new<F>(inner: C, sleep: impl Fn(Duration) -> F + Send + Sync + 'static) -> Self
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            inner,
+            sleep: Box::new(move |duration| sleep(duration).boxed()),
+            max_attempts: 3,
+            base_delay: Duration::from_millis(500),
+        }
+    }
+
+    /// Sets the number of attempts including the first request. The default is 3.
+    ///
+    /// With 1, requests are never retried and errors are returned as is.
+    pub fn with_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+
+    /// Sets the delay before the first retry. The default is 500 milliseconds.
+    pub fn with_base_delay(mut self, base_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self
+    }
+
+    pub fn inner(&self) -> &C {
+        &self.inner
+    }
+
+    async fn retry<T, Fut: Future<Output = io::Result<T>>>(
+        &self,
+        url: &Url,
+        mut request: impl FnMut() -> Fut,
+    ) -> io::Result<T> {
+        let mut attempts = 1;
+        loop {
+            let error = match request().await {
+                Ok(value) => return Ok(value),
+                Err(error) => error,
+            };
+            if !is_transient_error(&error) {
+                return Err(error);
+            }
+            if attempts >= self.max_attempts {
+                if self.max_attempts == 1 {
+                    // retrying is disabled so there is no attempt count to report
+                    return Err(error);
+                }
+                return Err(io::Error::new(
+                    error.kind(),
+                    RetryFailedError { attempts, error },
+                ));
+            }
+            let delay = self.base_delay * 2u32.saturating_pow(attempts - 1);
+            log::debug!("request to {url} failed: {error}, retrying in {delay:?}");
+            (self.sleep)(delay).await;
+            attempts += 1;
+        }
+    }
+}
+
+impl<C: HttpClient> HttpClient for RetryingClient<C> {
+    async fn get(
+        &self,
+        url: &Url,
+        headers: &IndexMap<&str, &str>,
+    ) -> io::Result<impl AsyncRead + Send> {
+        self.retry(url, || self.inner.get(url, headers)).await
+    }
+
+    async fn head(&self, url: &Url, headers: &IndexMap<&str, &str>) -> io::Result<()> {
+        self.retry(url, || self.inner.head(url, headers)).await
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+    ) -> io::Result<Option<(impl AsyncRead + Send, Option<Box<str>>)>> {
+        self.retry(url, || self.inner.get_with_etag(url, headers, current_etag))
+            .await
+    }
+
+    #[allow(clippy::type_complexity)]
+    async fn get_with_validators(
+        &self,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+        current_last_modified: Option<&str>,
+    ) -> io::Result<Option<(impl AsyncRead + Send, Option<Box<str>>, Option<Box<str>>)>> {
+        self.retry(url, || {
+            (self.inner).get_with_validators(url, headers, current_etag, current_last_modified)
+        })
+        .await
+    }
+
+    fn rewrite_url(&self, url: &Url) -> io::Result<Option<Url>> {
+        self.inner.rewrite_url(url)
+    }
+}
+
+/// Returns true if the request may succeed if it's sent again.
+fn is_transient_error(error: &io::Error) -> bool {
+    if matches!(
+        error.kind(),
+        io::ErrorKind::TimedOut
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::BrokenPipe
+    ) {
+        return true;
+    }
+
+    if let Some(error) = HttpStatusError::from_io_error(error) {
+        return error.status() >= 500;
+    }
+
+    // errors of reqwest may be wrapped in other errors
+    let mut source = error
+        .get_ref()
+        .map(|x| x as &(dyn std::error::Error + 'static));
+    while let Some(error) = source {
+        if let Some(error) = error.downcast_ref::<reqwest::Error>() {
+            return error.is_timeout() || error.is_connect();
+        }
+        source = error.source();
+    }
+    false
+}
+
+#[derive(Debug)]
+struct RetryFailedError {
+    attempts: u32,
+    error: io::Error,
+}
+
+impl std::fmt::Display for RetryFailedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (failed after {} attempts)",
+            self.error, self.attempts
+        )
+    }
+}
+
+impl std::error::Error for RetryFailedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Adds the rewritten URL to the error of the request to the URL rewritten by [`HttpClient::rewrite_url`].
+pub(crate) fn rewritten_url_error(
+    original: &Url,
+    rewritten: Option<&Url>,
+    error: io::Error,
+) -> io::Error {
+    let Some(rewritten) = rewritten else {
+        return error;
+    };
+    // keep the authentication error detectable, that has the rewritten url
+    if RepositoryAuthFailed::from_io_error(&error).is_some() {
+        return error;
+    }
+    io::Error::new(
+        error.kind(),
+        UrlRewrittenError {
+            original: original.clone(),
+            rewritten: rewritten.clone(),
+            error,
+        },
+    )
+}
+
+#[derive(Debug)]
+struct UrlRewrittenError {
+    original: Url,
+    rewritten: Url,
+    error: io::Error,
+}
+
+impl std::fmt::Display for UrlRewrittenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} (requested {} rewritten from {})",
+            self.error, self.rewritten, self.original
+        )
+    }
+}
+
+impl std::error::Error for UrlRewrittenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    #[test]
+    fn retry_failed_error_message() {
+        let error = io::Error::new(io::ErrorKind::TimedOut, "timed out");
+        let error = RetryFailedError { attempts: 3, error };
+
+        assert_eq!(error.to_string(), "timed out (failed after 3 attempts)");
+        let source = std::error::Error::source(&error).unwrap();
+        assert_eq!(source.to_string(), "timed out");
+    }
+
+    #[test]
+    fn retry_reports_attempts() {
+        let url = Url::parse("https://example.com/vpm.json").unwrap();
+        let retry = |max_attempts: u32, kind: io::ErrorKind| {
+            let client = RetryingClient::new(reqwest::Client::new(), |_| async {})
+                .with_max_attempts(max_attempts)
+                .with_base_delay(Duration::ZERO);
+            let mut requests = 0;
+            let result = block_on(client.retry(&url, || {
+                requests += 1;
+                async move { Err::<(), _>(io::Error::new(kind, "failed")) }
+            }));
+            (requests, result.unwrap_err())
+        };
+
+        let (requests, error) = retry(3, io::ErrorKind::TimedOut);
+        assert_eq!(requests, 3);
+        assert_eq!(error.kind(), io::ErrorKind::TimedOut);
+        assert_eq!(error.to_string(), "failed (failed after 3 attempts)");
+
+        let (requests, error) = retry(1, io::ErrorKind::TimedOut);
+        assert_eq!(requests, 1);
+        assert_eq!(error.to_string(), "failed");
+
+        let (requests, error) = retry(3, io::ErrorKind::UnexpectedEof);
+        assert_eq!(requests, 1);
+        assert_eq!(error.to_string(), "failed");
+    }
+}
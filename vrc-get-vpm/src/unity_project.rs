@@ -1,14 +1,29 @@
 mod add_package;
+mod broken_packages;
+mod changes_json;
+mod check_outdated;
+mod dependency_tree;
+mod embedded_collection;
 mod find_legacy_assets;
+mod freeze;
+mod hypothetical_collection;
 mod migrate_unity_2022;
 mod migrate_vpm;
 mod package_resolution;
 pub mod pending_project_changes;
+mod plan;
+mod project_config;
+mod project_health;
 mod project_type;
+mod rebuild_lockfile;
 mod reinstall;
 mod remove_package;
+mod resolution_cache;
 mod resolve;
+mod reverse_dependencies;
+mod scoped_registries;
 mod upm_manifest;
+mod verify_integrity;
 mod vpm_manifest;
 
 use crate::unity_project::upm_manifest::UpmManifest;
@@ -29,13 +44,87 @@ use std::path::{Path, PathBuf};
 use crate::io::{DirEntry, FileSystemProjectIo, ProjectIo};
 use crate::package_manifest::LooseManifest;
 pub use add_package::AddPackageErr;
+pub use add_package::AddPackageFailure;
 pub use add_package::AddPackageOperation;
+pub use add_package::LegacyRemoval;
+pub use broken_packages::BrokenPackage;
+pub use changes_json::ChangesJson;
+pub use check_outdated::OutdatedPackage;
+pub use dependency_tree::TreeNode;
+pub use embedded_collection::EmbeddedCollection;
+pub use freeze::FrozenPackage;
+pub use freeze::FrozenPackages;
+pub use freeze::InstallFrozenError;
+pub use hypothetical_collection::HypotheticalCollection;
 pub use migrate_unity_2022::MigrateUnity2022Error;
 pub use migrate_vpm::MigrateVpmError;
+pub use package_resolution::AbsenceReason;
+pub use package_resolution::AmbiguousLegacyReplacement;
+pub use package_resolution::CompatibilityReport;
+pub use package_resolution::ConflictContext;
+pub use package_resolution::ConflictDecision;
+pub use package_resolution::ConflictPolicy;
+pub use package_resolution::ConflictSource;
+pub use package_resolution::ConflictStrategy;
+pub use package_resolution::ConflictSuggestion;
+pub use package_resolution::DependencyEdge;
+pub use package_resolution::DropRequirement;
+pub use package_resolution::FailFast;
+pub use package_resolution::HoldbackCap;
+pub use package_resolution::HoldbackExplanation;
+pub use package_resolution::IncrementalResolution;
+pub use package_resolution::LicensePolicy;
+pub use package_resolution::LockedDependencyMismatch;
+pub use package_resolution::LockedPreference;
+pub use package_resolution::LockfileDrift;
+pub use package_resolution::MAX_CONFLICT_RETRIES;
+pub use package_resolution::MissingRequirement;
+pub use package_resolution::MissingSourceRepository;
+pub use package_resolution::PackageOrigin;
+pub use package_resolution::PackageResolutionResult;
+pub use package_resolution::PinViolation;
+pub use package_resolution::PrereleasePropagation;
+pub use package_resolution::PrereleaseReason;
+pub use package_resolution::QueueOrder;
+pub use package_resolution::RejectionReason;
+pub use package_resolution::RelaxToAnyVersion;
+pub use package_resolution::ResolutionDetails;
+pub use package_resolution::ResolutionDiagnostics;
+pub use package_resolution::ResolutionDiff;
+pub use package_resolution::ResolutionEventSink;
+pub use package_resolution::ResolutionOptions;
+pub use package_resolution::ResolutionReason;
+pub use package_resolution::ResolutionStats;
+pub use package_resolution::ResolutionSummary;
+pub use package_resolution::SatisfactionCell;
+pub use package_resolution::SatisfactionMatrix;
+pub use package_resolution::SatisfactionRow;
+pub use package_resolution::SkipReason;
+pub use package_resolution::ViolationContext;
+pub use package_resolution::ViolationDecision;
+pub use package_resolution::ViolationHandler;
+pub use package_resolution::check_compatibility;
+pub use package_resolution::dependency_closure;
+pub use package_resolution::minimum_unity_version;
+pub use package_resolution::resolve_dependencies;
 pub use pending_project_changes::PendingProjectChanges;
+pub use plan::PlanErr;
+pub use plan::ProjectChangedSincePlan;
+pub use plan::ProjectOperation;
+pub use plan::ResolutionPlan;
+pub use project_config::ProjectConfig;
+pub use project_health::HealthReport;
+pub use rebuild_lockfile::RebuildLockfileResult;
 pub use reinstall::ReinstalPackagesError;
 pub use remove_package::RemovePackageErr;
+pub use resolution_cache::CachedPackageCollection;
+pub use resolution_cache::ResolutionCache;
 pub use resolve::ResolvePackageErr;
+pub use resolve::UpdateAllResult;
+pub use reverse_dependencies::ReverseDependencies;
+pub use scoped_registries::ScopedRegistryPackage;
+pub use verify_integrity::IntegrityReport;
+pub use vpm_manifest::UnsupportedManifestVersion;
 
 #[derive(Debug)]
 pub struct UnityProject<IO: ProjectIo> {
@@ -44,6 +133,8 @@ pub struct UnityProject<IO: ProjectIo> {
     manifest: VpmManifest,
     // manifest.json
     upm_manifest: UpmManifest,
+    // vrc-get-project.json
+    config: ProjectConfig,
     /// unity version parsed
     unity_version: Option<UnityVersion>,
     /// unity revision parsed
@@ -59,6 +150,7 @@ impl<IO: ProjectIo> UnityProject<IO> {
     pub async fn load(io: IO) -> io::Result<Self> {
         let manifest = VpmManifest::load(&io).await?;
         let upm_manifest = UpmManifest::load(&io).await?;
+        let config = ProjectConfig::load(&io).await?;
 
         let mut installed_packages = HashMap::new();
         let mut unlocked_packages = vec![];
@@ -99,6 +191,7 @@ impl<IO: ProjectIo> UnityProject<IO> {
             io,
             manifest,
             upm_manifest,
+            config,
             unity_version,
             unity_revision,
             unlocked_packages,
@@ -231,7 +324,9 @@ impl<IO: ProjectIo> UnityProject<IO> {
             .unlocked_packages
             .iter()
             .filter_map(|(_, json)| json.as_ref())
-            .map(|x| LockedDependencyInfo::new(x.name(), x.version(), Some(x.vpm_dependencies())));
+            .map(|x| {
+                LockedDependencyInfo::new(x.name(), x.version(), Some(x.vpm_dependencies()), None)
+            });
 
         dependencies_locked.chain(dependencies_unlocked)
     }
@@ -258,6 +353,11 @@ impl<IO: ProjectIo> UnityProject<IO> {
         )
     }
 
+    /// Returns the configuration of the resolution in `Packages/vrc-get-project.json`.
+    pub fn project_config(&self) -> &ProjectConfig {
+        &self.config
+    }
+
     pub fn unity_version(&self) -> Option<UnityVersion> {
         self.unity_version
     }
@@ -282,6 +382,8 @@ pub struct LockedDependencyInfo<'a> {
     name: &'a str,
     version: &'a Version,
     dependencies: &'a IndexMap<Box<str>, VersionRange>,
+    repository_url: Option<&'a str>,
+    git_commit: Option<&'a str>,
 }
 
 impl<'a> LockedDependencyInfo<'a> {
@@ -289,6 +391,7 @@ impl<'a> LockedDependencyInfo<'a> {
         name: &'a str,
         version: &'a Version,
         dependencies: Option<&'a IndexMap<Box<str>, VersionRange>>,
+        repository_url: Option<&'a str>,
     ) -> Self {
         lazy_static! {
             static ref EMPTY_DEPENDENCIES: IndexMap<Box<str>, VersionRange> = IndexMap::new();
@@ -297,9 +400,16 @@ impl<'a> LockedDependencyInfo<'a> {
             name,
             version,
             dependencies: dependencies.unwrap_or(&*EMPTY_DEPENDENCIES),
+            repository_url,
+            git_commit: None,
         }
     }
 
+    fn with_git_commit(mut self, git_commit: Option<&'a str>) -> Self {
+        self.git_commit = git_commit;
+        self
+    }
+
     pub fn name(&self) -> &'a str {
         self.name
     }
@@ -311,4 +421,16 @@ impl<'a> LockedDependencyInfo<'a> {
     pub fn dependencies(&self) -> &'a IndexMap<Box<str>, VersionRange> {
         self.dependencies
     }
+
+    /// The URL of the repository the package is installed from, if recorded.
+    pub fn repository_url(&self) -> Option<&'a str> {
+        self.repository_url
+    }
+
+    /// The hash of the commit the package is installed from, if installed from git.
+    ///
+    /// For git packages, [`repository_url`](Self::repository_url) is the url of the git repository.
+    pub fn git_commit(&self) -> Option<&'a str> {
+        self.git_commit
+    }
 }
@@ -1,26 +1,83 @@
 use crate::io::ProjectIo;
-use crate::unity_project::package_resolution::MissingDependencies;
+use crate::unity_project::package_resolution::{
+    AbsenceReason, ConflictSource, MissingDependencies, MissingRequirement,
+    PackageResolutionResult, ResolutionOptions,
+};
 use crate::unity_project::pending_project_changes::RemoveReason;
 use crate::unity_project::vpm_manifest::VpmManifest;
 use crate::unity_project::{PendingProjectChanges, package_resolution};
-use crate::version::DependencyRange;
+use crate::version::{DependencyRange, UnityVersion, VersionRange};
 use crate::{PackageCollection, PackageInfo, UnityProject};
 use log::debug;
+use std::collections::HashMap;
 use std::fmt;
 
 #[derive(Debug)]
 #[non_exhaustive]
 pub enum AddPackageErr {
-    DependenciesNotFound { dependencies: Vec<Box<str>> },
-    UpgradingNonLockedPackage { package_name: Box<str> },
-    DowngradingNonLockedPackage { package_name: Box<str> },
-    UpgradingWithDowngrade { package_name: Box<str> },
+    DependenciesNotFound {
+        dependencies: Vec<Box<str>>,
+        // the packages requiring the dependencies with the ranges, if known
+        requirements: Vec<MissingRequirement>,
+    },
+    UpgradingNonLockedPackage {
+        package_name: Box<str>,
+    },
+    DowngradingNonLockedPackage {
+        package_name: Box<str>,
+    },
+    UpgradingWithDowngrade {
+        package_name: Box<str>,
+    },
+    DisallowedLicense {
+        package_name: Box<str>,
+        license: Option<Box<str>>,
+    },
+    UnsatisfiableRequirements {
+        // (dependency, source, range)
+        requirements: Vec<(Box<str>, Box<str>, VersionRange)>,
+    },
+    DependencyCycle {
+        path: Vec<Box<str>>,
+    },
+    UnityVersionTooOld {
+        dependency_name: Box<str>,
+        required_unity: UnityVersion,
+        project_unity: UnityVersion,
+    },
+    IncompatibleRootDependencies {
+        package_name: Box<str>,
+        range: VersionRange,
+        other_range: VersionRange,
+    },
+    ResolutionLimitExceeded {
+        // the names of the packages processed last, oldest first
+        recent_packages: Vec<Box<str>>,
+    },
+    ResolutionAborted {
+        dependency_name: Box<str>,
+    },
+    UnresolvableConflicts {
+        // all the conflicts of the resolution, same as `conflicts` of the result
+        conflicts: HashMap<Box<str>, Vec<ConflictSource>>,
+    },
+    PlatformIncompatible {
+        dependency_name: Box<str>,
+        platform: Box<str>,
+    },
+    TargetUnityIncompatible {
+        dependency_name: Box<str>,
+        unity_versions: Vec<UnityVersion>,
+    },
+    AllCandidatesYanked {
+        dependency_name: Box<str>,
+    },
 }
 
 impl fmt::Display for AddPackageErr {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            AddPackageErr::DependenciesNotFound { dependencies } => {
+            AddPackageErr::DependenciesNotFound { dependencies, .. } => {
                 write!(f, "Following dependencies are not found: ")?;
                 let mut first = true;
                 for dep in dependencies {
@@ -44,12 +101,145 @@ impl fmt::Display for AddPackageErr {
                 f,
                 "Package {package_name} is locked, so it cannot be downgraded"
             ),
+            AddPackageErr::DisallowedLicense {
+                package_name,
+                license: Some(license),
+            } => write!(
+                f,
+                "Package {package_name} has license {license} which is not allowed"
+            ),
+            AddPackageErr::DisallowedLicense {
+                package_name,
+                license: None,
+            } => write!(f, "Package {package_name} does not declare license"),
+            AddPackageErr::UnsatisfiableRequirements { requirements } => {
+                write!(f, "No version was published for following requirements: ")?;
+                let mut first = true;
+                for (dependency, source, range) in requirements {
+                    if !first {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{dependency}@{range} (required by {source})")?;
+                    first = false;
+                }
+                Ok(())
+            }
+            AddPackageErr::DependencyCycle { path } => {
+                write!(f, "Dependency cycle found: {}", path.join(" -> "))
+            }
+            AddPackageErr::UnityVersionTooOld {
+                dependency_name,
+                required_unity,
+                project_unity,
+            } => write!(
+                f,
+                "Package {dependency_name} requires unity {required_unity} or later, but the project uses unity {project_unity}"
+            ),
+            AddPackageErr::IncompatibleRootDependencies {
+                package_name,
+                range,
+                other_range,
+            } => write!(
+                f,
+                "No version of {package_name} satisfies both {range} and {other_range}"
+            ),
+            AddPackageErr::ResolutionLimitExceeded { recent_packages } => write!(
+                f,
+                "Resolution limit exceeded. Last processed packages: {}",
+                recent_packages.join(", ")
+            ),
+            AddPackageErr::ResolutionAborted { dependency_name } => {
+                write!(f, "Resolution aborted at package {dependency_name}")
+            }
+            AddPackageErr::PlatformIncompatible {
+                dependency_name,
+                platform,
+            } => write!(
+                f,
+                "No version of package {dependency_name} supports platform {platform}"
+            ),
+            AddPackageErr::TargetUnityIncompatible {
+                dependency_name,
+                unity_versions,
+            } => {
+                write!(
+                    f,
+                    "No version of package {dependency_name} supports all of unity "
+                )?;
+                for (i, unity) in unity_versions.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{unity}")?;
+                }
+                Ok(())
+            }
+            AddPackageErr::AllCandidatesYanked { dependency_name } => write!(
+                f,
+                "All versions of package {dependency_name} satisfying the requirements are yanked"
+            ),
+            AddPackageErr::UnresolvableConflicts { conflicts } => {
+                write!(f, "Following packages have conflicts: ")?;
+                let mut names = conflicts.keys().collect::<Vec<_>>();
+                names.sort();
+                let mut first = true;
+                for name in names {
+                    if !first {
+                        write!(f, ", ")?;
+                    }
+                    let sources = (conflicts[name].iter())
+                        .map(|x| match x.package.as_ref() {
+                            "" => "the project",
+                            package => package,
+                        })
+                        .collect::<Vec<_>>();
+                    write!(f, "{name} (required by {})", sources.join(", "))?;
+                    first = false;
+                }
+                Ok(())
+            }
         }
     }
 }
 
 impl std::error::Error for AddPackageErr {}
 
+/// The error of adding packages with the result of the resolution if the resolution is done.
+#[derive(Debug)]
+pub struct AddPackageFailure<'env> {
+    pub error: AddPackageErr,
+    // the best-effort result. None if the request fails before the resolution
+    pub partial_result: Option<PackageResolutionResult<'env>>,
+}
+
+impl From<AddPackageErr> for AddPackageFailure<'_> {
+    fn from(error: AddPackageErr) -> Self {
+        Self {
+            error,
+            partial_result: None,
+        }
+    }
+}
+
+impl fmt::Display for AddPackageFailure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.error, f)
+    }
+}
+
+impl std::error::Error for AddPackageFailure<'_> {}
+
+/// The legacy package to be removed by adding packages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyRemoval {
+    // the name of the legacy package
+    pub package: Box<str>,
+    // the names of the packages superseding the legacy package, sorted by name
+    pub superseded_by: Vec<Box<str>>,
+    // true if the legacy package is in the Packages directory
+    pub installed: bool,
+}
+
 #[non_exhaustive]
 #[derive(Debug)]
 pub enum AddPackageOperation {
@@ -71,6 +261,43 @@ impl<IO: ProjectIo> UnityProject<IO> {
         operation: AddPackageOperation,
         allow_prerelease: bool,
     ) -> Result<PendingProjectChanges<'env>, AddPackageErr> {
+        self.add_package_request_with_options(
+            env,
+            packages,
+            operation,
+            &ResolutionOptions::new()
+                .allow_prerelease(allow_prerelease)
+                .detect_stuck_prereleases(true),
+        )
+        .await
+    }
+
+    /// Creates a new `AddPackageRequest` to add the specified packages with the resolution options.
+    ///
+    /// You should call `apply_pending_changes` to apply the changes after confirming to the user.
+    pub async fn add_package_request_with_options<'env>(
+        &self,
+        env: &'env impl PackageCollection,
+        packages: &[PackageInfo<'env>],
+        operation: AddPackageOperation,
+        options: &ResolutionOptions<'_>,
+    ) -> Result<PendingProjectChanges<'env>, AddPackageErr> {
+        self.add_package_request_with_partial_result(env, packages, operation, options)
+            .await
+            .map_err(|failure| failure.error)
+    }
+
+    /// Creates a new `AddPackageRequest` like [`Self::add_package_request_with_options`], but
+    /// the error contains the result of the resolution if the resolution fails.
+    ///
+    /// With the partial result, you can show which packages are resolved successfully.
+    pub async fn add_package_request_with_partial_result<'env>(
+        &self,
+        env: &'env impl PackageCollection,
+        packages: &[PackageInfo<'env>],
+        operation: AddPackageOperation,
+        options: &ResolutionOptions<'_>,
+    ) -> Result<PendingProjectChanges<'env>, AddPackageFailure<'env>> {
         // if same or newer requested package is in locked dependencies,
         // just add requested version into dependencies
         let mut adding_packages = Vec::with_capacity(packages.len());
@@ -162,7 +389,8 @@ impl<IO: ProjectIo> UnityProject<IO> {
                             // if package is not locked, it cannot be updated
                             return Err(AddPackageErr::UpgradingNonLockedPackage {
                                 package_name: request.name().into(),
-                            });
+                            }
+                            .into());
                         }
 
                         upgrade_locked(request, self, &mut adding_packages, &mut changes)?;
@@ -172,7 +400,8 @@ impl<IO: ProjectIo> UnityProject<IO> {
                             // if package is not locked, it cannot be updated
                             return Err(AddPackageErr::DowngradingNonLockedPackage {
                                 package_name: request.name().into(),
-                            });
+                            }
+                            .into());
                         };
 
                         if locked_version.version() < request.version() {
@@ -180,7 +409,8 @@ impl<IO: ProjectIo> UnityProject<IO> {
                             // it cannot be downgraded
                             return Err(AddPackageErr::UpgradingWithDowngrade {
                                 package_name: request.name().into(),
-                            });
+                            }
+                            .into());
                         }
 
                         downgrade(request, self, &mut adding_packages, &mut changes)?;
@@ -268,7 +498,7 @@ impl<IO: ProjectIo> UnityProject<IO> {
 
         debug!("Validation finished");
 
-        if adding_packages.is_empty() {
+        if adding_packages.is_empty() && options.must_include.is_empty() {
             debug!("No new packages to add, returning early");
             // early return: nothing new to install
             return Ok(changes.build_no_resolve());
@@ -291,18 +521,20 @@ impl<IO: ProjectIo> UnityProject<IO> {
             self.unity_version(),
             env,
             adding_packages,
-            allow_prerelease,
+            options,
+            &self.config,
             &mut missing_dependencies,
         );
-        if !missing_dependencies.is_empty() {
-            return Err(AddPackageErr::DependenciesNotFound {
-                dependencies: missing_dependencies.into_vec(),
+        if let Some(error) = self.resolution_error(&result, missing_dependencies, options) {
+            return Err(AddPackageFailure {
+                error,
+                partial_result: Some(result),
             });
         }
 
         debug!("Resolving finished");
 
-        for pkg in result.new_packages {
+        for &pkg in &result.new_packages {
             debug!("Installing package {}@{}", pkg.name(), pkg.version());
             changes.install_to_locked(pkg);
 
@@ -317,20 +549,54 @@ impl<IO: ProjectIo> UnityProject<IO> {
             }
         }
 
-        for (package, conflicts_with) in result.conflicts {
+        for (package, conflicts_with) in &result.conflicts {
             debug!("package {} conflicts with {:?}", package, conflicts_with);
-            changes.conflict_multiple(package, conflicts_with);
+            changes.conflict_multiple(
+                package.clone(),
+                conflicts_with.iter().map(|x| x.package.clone()),
+            );
         }
 
         for name in result
             .found_legacy_packages
-            .into_iter()
+            .iter()
             .filter(|name| self.is_locked(name))
         {
             debug!("removing legacy package {}", name);
-            changes.remove(name, RemoveReason::Legacy);
+            changes.remove(name.clone(), RemoveReason::Legacy);
+        }
+
+        for name in &result.stuck_on_prerelease {
+            log::warn!(
+                "package {name} is using prerelease version and no stable version available"
+            );
+        }
+
+        for missing in &result.missing_source_repositories {
+            log::warn!(
+                "repository {} of locked package {} is not found. was the repository removed?",
+                missing.url,
+                missing.package
+            );
         }
 
+        for drift in &result.lockfile_drift {
+            log::warn!(
+                "dependencies of locked package {} differ from the package on disk",
+                drift.package
+            );
+        }
+
+        for mismatch in &result.locked_dependency_mismatches {
+            log::warn!(
+                "dependencies of locked package {}@{} differ from the repository. was the version re-published?",
+                mismatch.package,
+                mismatch.version
+            );
+        }
+
+        changes.set_resolution(result);
+
         debug!("Building changes (finding legacy assets, checking conflicts)");
 
         let changes = changes.build_resolve(self).await;
@@ -339,4 +605,126 @@ impl<IO: ProjectIo> UnityProject<IO> {
 
         Ok(changes)
     }
+
+    /// Lists the legacy packages to be removed by adding the packages, for confirming to the user.
+    ///
+    /// This resolves like [`Self::add_package_request_with_options`] but returns only the legacy
+    /// packages removed with the packages superseding them, sorted by name.
+    pub async fn plan_legacy_removals<'env>(
+        &self,
+        env: &'env impl PackageCollection,
+        packages: &[PackageInfo<'env>],
+        operation: AddPackageOperation,
+        options: &ResolutionOptions<'_>,
+    ) -> Result<Vec<LegacyRemoval>, AddPackageErr> {
+        let changes =
+            (self.add_package_request_with_options(env, packages, operation, options)).await?;
+        let Some(result) = changes.resolution() else {
+            return Ok(vec![]);
+        };
+
+        let removals = (result.found_legacy_packages.iter())
+            .filter(|name| self.is_locked(name))
+            .map(|name| {
+                let superseded_by = match result.explain_absence(name) {
+                    AbsenceReason::Legacy { replaced_by } => replaced_by,
+                    _ => vec![],
+                };
+                let installed = self.installed_packages.contains_key(name)
+                    || (self.unlocked_packages.iter()).any(|(dir, unlocked)| {
+                        dir == name || unlocked.as_ref().is_some_and(|x| x.name() == name.as_ref())
+                    });
+                LegacyRemoval {
+                    package: name.clone(),
+                    superseded_by,
+                    installed,
+                }
+            })
+            .collect();
+        Ok(removals)
+    }
+
+    /// Returns the error if the resolution for adding packages failed.
+    fn resolution_error(
+        &self,
+        result: &PackageResolutionResult,
+        missing_dependencies: MissingDependencies,
+        options: &ResolutionOptions<'_>,
+    ) -> Option<AddPackageErr> {
+        if let Some(recent_packages) = &result.limit_exceeded {
+            return Some(AddPackageErr::ResolutionLimitExceeded {
+                recent_packages: recent_packages.clone(),
+            });
+        }
+        if let Some(dependency_name) = &result.aborted_at {
+            return Some(AddPackageErr::ResolutionAborted {
+                dependency_name: dependency_name.clone(),
+            });
+        }
+        if let Some(path) = result.dependency_cycles.first() {
+            return Some(AddPackageErr::DependencyCycle { path: path.clone() });
+        }
+        if let (Some((dependency_name, required_unity)), Some(project_unity)) =
+            (result.unity_gated.first(), self.unity_version())
+        {
+            return Some(AddPackageErr::UnityVersionTooOld {
+                dependency_name: dependency_name.clone(),
+                required_unity: *required_unity,
+                project_unity,
+            });
+        }
+        if !result.unsatisfiable_requirements.is_empty() {
+            return Some(AddPackageErr::UnsatisfiableRequirements {
+                requirements: result.unsatisfiable_requirements.clone(),
+            });
+        }
+        if let (Some(dependency_name), Some(platform)) = (
+            result.platform_incompatible.first(),
+            options.target_platform,
+        ) {
+            return Some(AddPackageErr::PlatformIncompatible {
+                dependency_name: dependency_name.clone(),
+                platform: platform.into(),
+            });
+        }
+        if let Some(dependency_name) = result.target_unity_incompatible.first() {
+            return Some(AddPackageErr::TargetUnityIncompatible {
+                dependency_name: dependency_name.clone(),
+                unity_versions: options.target_unity_versions.clone(),
+            });
+        }
+        if let Some(dependency_name) = result.all_candidates_yanked.first() {
+            return Some(AddPackageErr::AllCandidatesYanked {
+                dependency_name: dependency_name.clone(),
+            });
+        }
+        if !missing_dependencies.is_empty() {
+            return Some(AddPackageErr::DependenciesNotFound {
+                requirements: missing_dependencies.requirements.clone(),
+                dependencies: missing_dependencies.into_vec(),
+            });
+        }
+
+        // conflicts with overridden versions are only reported
+        let conflicts = (result.conflicts.iter())
+            .filter(|(name, _)| !options.overrides.contains_key(name.as_ref()))
+            .map(|(name, sources)| (name.clone(), sources.clone()))
+            .collect();
+        if options.conflict_policy.fails_with(&conflicts) {
+            return Some(AddPackageErr::UnresolvableConflicts { conflicts });
+        }
+
+        if let Some(policy) = &options.license_policy {
+            if policy.is_strict() {
+                if let Some(name) = result.disallowed_licenses.iter().min() {
+                    return Some(AddPackageErr::DisallowedLicense {
+                        package_name: name.clone(),
+                        license: result.licenses[name].clone(),
+                    });
+                }
+            }
+        }
+
+        None
+    }
 }
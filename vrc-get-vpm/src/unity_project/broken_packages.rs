@@ -0,0 +1,50 @@
+use crate::UnityProject;
+use crate::io::ProjectIo;
+use std::io;
+use std::path::PathBuf;
+
+/// A directory in `Packages` without readable package.json
+///
+/// This is usually a package partially extracted by an interrupted installation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenPackage {
+    // the name of the directory in Packages
+    pub dir_name: Box<str>,
+    // true if the package is locked in vpm-manifest.json, which will be installed again by resolving
+    pub locked: bool,
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Finds the directories in `Packages` with missing or invalid package.json.
+    ///
+    /// Directories with valid package.json not locked in vpm-manifest.json are user-installed
+    /// (embedded) packages, so they are not reported.
+    pub fn scan_broken_packages(&self) -> Vec<BrokenPackage> {
+        let mut broken = (self.unlocked_packages.iter())
+            .filter(|(_, manifest)| manifest.is_none())
+            .map(|(dir_name, _)| BrokenPackage {
+                dir_name: dir_name.clone(),
+                locked: self.manifest.get_locked(dir_name).is_some(),
+            })
+            .collect::<Vec<_>>();
+        broken.sort_by(|a, b| a.dir_name.cmp(&b.dir_name));
+        broken
+    }
+
+    /// Removes the directories of the broken packages.
+    ///
+    /// The locked packages are kept in vpm-manifest.json so resolving the project installs them again.
+    pub async fn remove_broken_packages(&mut self, packages: &[BrokenPackage]) -> io::Result<()> {
+        for package in packages {
+            let path = PathBuf::from("Packages").join(package.dir_name.as_ref());
+            match self.io.remove_dir_all(&path).await {
+                Ok(()) => {}
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            self.unlocked_packages
+                .retain(|(dir_name, _)| dir_name != &package.dir_name);
+        }
+        Ok(())
+    }
+}
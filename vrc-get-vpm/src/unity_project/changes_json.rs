@@ -0,0 +1,142 @@
+use crate::unity_project::PendingProjectChanges;
+use crate::unity_project::pending_project_changes::{PackageChange, RemoveReason};
+use crate::version::Version;
+use itertools::Itertools;
+use serde::Serialize;
+
+/// The machine-readable document describing [`PendingProjectChanges`], serialized with serde.
+///
+/// Lists in the document are sorted by the package name or the path.
+#[derive(Debug, Serialize)]
+pub struct ChangesJson<'a> {
+    new_packages: Vec<NewPackageJson<'a>>,
+    adding_to_dependencies: Vec<DependencyJson<'a>>,
+    removed_packages: Vec<RemovedPackageJson<'a>>,
+    removed_legacy_assets: Vec<String>,
+    conflicts: Vec<ConflictJson<'a>>,
+    found_legacy_packages: &'a [Box<str>],
+    warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct NewPackageJson<'a> {
+    name: &'a str,
+    version: &'a Version,
+    // the url of the repository the package is installed from
+    repository: Option<&'a str>,
+    is_yanked: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct DependencyJson<'a> {
+    name: &'a str,
+    range: String,
+}
+
+#[derive(Debug, Serialize)]
+struct RemovedPackageJson<'a> {
+    name: &'a str,
+    reason: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct ConflictJson<'a> {
+    package: &'a str,
+    conflicts_with: &'a [Box<str>],
+    incompatible_unity: bool,
+    unlocked_directories: &'a [Box<str>],
+}
+
+impl<'a> ChangesJson<'a> {
+    /// The version of the document format, incremented on breaking changes.
+    pub const FORMAT_VERSION: u32 = 1;
+
+    pub fn new(changes: &'a PendingProjectChanges<'_>) -> Self {
+        let mut new_packages = Vec::new();
+        let mut adding_to_dependencies = Vec::new();
+        let mut removed_packages = Vec::new();
+
+        for (name, change) in changes.package_changes() {
+            match change {
+                PackageChange::Install(change) => {
+                    if let Some(package) = change.install_package() {
+                        new_packages.push(NewPackageJson {
+                            name: package.name(),
+                            version: package.version(),
+                            repository: (package.repo()).and_then(|x| x.url()).map(|x| x.as_str()),
+                            is_yanked: package.is_yanked(),
+                        });
+                    }
+                    if let Some(range) = change.to_dependencies() {
+                        adding_to_dependencies.push(DependencyJson {
+                            name,
+                            range: range.to_string(),
+                        });
+                    }
+                }
+                PackageChange::Remove(change) => {
+                    let reason = match change.reason() {
+                        RemoveReason::Requested => "requested",
+                        RemoveReason::Legacy => "legacy",
+                        RemoveReason::Unused => "unused",
+                    };
+                    removed_packages.push(RemovedPackageJson { name, reason });
+                }
+            }
+        }
+
+        new_packages.sort_by_key(|x| x.name);
+        adding_to_dependencies.sort_by_key(|x| x.name);
+        removed_packages.sort_by_key(|x| x.name);
+
+        let removed_legacy_assets = (changes.remove_legacy_folders().iter())
+            .chain(changes.remove_legacy_files())
+            .map(|(path, _)| path.display().to_string())
+            .sorted()
+            .collect();
+
+        let conflicts = (changes.conflicts().iter())
+            .map(|(package, conflict)| ConflictJson {
+                package,
+                conflicts_with: conflict.conflicting_packages(),
+                incompatible_unity: conflict.conflicts_with_unity(),
+                unlocked_directories: conflict.unlocked_names(),
+            })
+            .sorted_by_key(|x| x.package)
+            .collect();
+
+        let mut warnings = Vec::new();
+        let mut found_legacy_packages: &[Box<str>] = &[];
+
+        if let Some(resolution) = changes.resolution() {
+            found_legacy_packages = &resolution.found_legacy_packages;
+            for (name, locked, new) in &resolution.downgrades {
+                warnings.push(format!("downgrading {name} version {locked} -> {new}"));
+            }
+            for (name, version) in &resolution.transitive_prereleases {
+                warnings.push(format!("installing prerelease {name} version {version}"));
+            }
+            for name in &resolution.stuck_on_prerelease {
+                warnings.push(format!(
+                    "package {name} is using prerelease version and no stable version available"
+                ));
+            }
+            for missing in &resolution.missing_source_repositories {
+                warnings.push(format!(
+                    "repository {} of locked package {} is not found",
+                    missing.url, missing.package
+                ));
+            }
+        }
+
+        Self {
+            new_packages,
+            adding_to_dependencies,
+            removed_packages,
+            removed_legacy_assets,
+            conflicts,
+            found_legacy_packages,
+            warnings,
+        }
+    }
+}
@@ -0,0 +1,82 @@
+use crate::io::ProjectIo;
+use crate::version::{PrereleaseAcceptance, Version};
+use crate::{PackageCollection, UnityProject, VersionSelector};
+
+/// The locked package with newer version available. Returned by [`UnityProject::check_outdated`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutdatedPackage {
+    // the name of the package
+    pub name: Box<str>,
+    // the version currently locked
+    pub installed: Version,
+    // the latest stable version newer than installed one, if any
+    pub latest_stable: Option<Version>,
+    // the latest prerelease version newer than installed and latest stable one, if any
+    pub latest_prerelease: Option<Version>,
+    // the package depending on this package with a range excluding the newer version.
+    // the newer version is latest_stable if exists, latest_prerelease otherwise.
+    pub held_back_by: Option<Box<str>>,
+}
+
+impl OutdatedPackage {
+    /// Returns true if some locked package requires the currently installed version range.
+    pub fn is_held_back(&self) -> bool {
+        self.held_back_by.is_some()
+    }
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Lists the locked packages with newer version in the `collection`.
+    ///
+    /// Versions not compatible with the unity version of the project are not considered like
+    /// resolving packages. The result is sorted by the package name.
+    pub fn check_outdated(&self, collection: &impl PackageCollection) -> Vec<OutdatedPackage> {
+        let unity_version = self.unity_version();
+        let mut outdated = Vec::new();
+
+        for locked in self.locked_packages() {
+            let newer = |include_prerelease: bool| {
+                collection
+                    .find_package_by_name(
+                        locked.name(),
+                        VersionSelector::latest_for(unity_version, include_prerelease),
+                    )
+                    .map(|x| x.version())
+                    .filter(|&x| x > locked.version())
+                    .cloned()
+            };
+
+            let latest_stable = newer(false);
+            let latest_prerelease = newer(true)
+                .filter(|x| !x.is_stable())
+                .filter(|x| latest_stable.as_ref().is_none_or(|stable| x > stable));
+
+            let Some((target, acceptance)) = (latest_stable.as_ref())
+                .map(|x| (x, PrereleaseAcceptance::Minimum))
+                .or_else(|| (latest_prerelease.as_ref()).map(|x| (x, PrereleaseAcceptance::Allow)))
+            else {
+                continue;
+            };
+
+            let held_back_by = self
+                .all_packages()
+                .find(|dependant| {
+                    (dependant.dependencies().get(locked.name()))
+                        .is_some_and(|range| !range.match_pre(target, acceptance))
+                })
+                .map(|dependant| dependant.name().into());
+
+            outdated.push(OutdatedPackage {
+                name: locked.name().into(),
+                installed: locked.version().clone(),
+                latest_stable,
+                latest_prerelease,
+                held_back_by,
+            });
+        }
+
+        outdated.sort_by(|a, b| a.name.cmp(&b.name));
+
+        outdated
+    }
+}
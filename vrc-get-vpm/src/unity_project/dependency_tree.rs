@@ -0,0 +1,85 @@
+use crate::UnityProject;
+use crate::io::ProjectIo;
+use crate::version::{Version, VersionRange};
+use std::collections::HashSet;
+
+/// The node of the dependency tree. Returned by [`UnityProject::dependency_tree`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TreeNode {
+    // the name of the package
+    pub name: Box<str>,
+    // the locked version, or None if the package is not locked
+    pub version: Option<Version>,
+    // the range required by the parent, or the dependencies section for top level nodes.
+    // None for top level nodes of locked packages not required by anything
+    pub range: Option<VersionRange>,
+    // whether the locked version satisfies the range. false if the package is not locked
+    pub satisfied: bool,
+    // true if the package is already expanded in another place of the tree.
+    // dependencies of the package are omitted so the tree is finite even with cycles
+    pub deduplicated: bool,
+    // the dependencies of the package in the order of declaration
+    pub children: Vec<TreeNode>,
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Builds the dependency tree from the locked packages.
+    ///
+    /// The top level nodes are the dependencies in `vpm-manifest.json` in the order of declaration,
+    /// followed by locked packages not required by anything.
+    /// Each package is expanded only once, other occurrences are marked as deduplicated.
+    pub fn dependency_tree(&self) -> Vec<TreeNode> {
+        let mut expanded = HashSet::new();
+
+        let mut tree = (self.manifest.dependencies())
+            .map(|(name, range)| self.tree_node(name, Some(range.as_range()), &mut expanded))
+            .collect::<Vec<_>>();
+
+        let orphans = (self.manifest.all_locked())
+            .filter(|locked| !expanded.contains(locked.name()))
+            .map(|locked| locked.name())
+            .collect::<Vec<_>>();
+        for name in orphans {
+            // some orphans may be expanded as dependencies of other orphans
+            if !expanded.contains(name) {
+                tree.push(self.tree_node(name, None, &mut expanded));
+            }
+        }
+
+        tree
+    }
+
+    fn tree_node<'a>(
+        &'a self,
+        name: &'a str,
+        range: Option<VersionRange>,
+        expanded: &mut HashSet<&'a str>,
+    ) -> TreeNode {
+        let locked = self.manifest.get_locked(name);
+        let version = locked.as_ref().map(|x| x.version().clone());
+        let satisfied = match (&version, &range) {
+            (Some(version), Some(range)) => range.matches(version),
+            (Some(_), None) => true,
+            (None, _) => false,
+        };
+
+        let deduplicated = !expanded.insert(name);
+        let children = match locked {
+            Some(locked) if !deduplicated => (locked.dependencies().iter())
+                .map(|(dependency, range)| {
+                    self.tree_node(dependency, Some(range.clone()), expanded)
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        TreeNode {
+            name: name.into(),
+            version,
+            range,
+            satisfied,
+            deduplicated,
+            children,
+        }
+    }
+}
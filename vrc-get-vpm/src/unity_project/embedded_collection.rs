@@ -0,0 +1,104 @@
+use crate::io::ProjectIo;
+use crate::repository::{LocalCachedRepository, RemoteRepository};
+use crate::{PackageCollection, PackageInfo, UnityProject, VersionSelector, io};
+use indexmap::IndexMap;
+use serde_json::{Map, Value, json};
+
+/// The package collection serving packages embedded in the `vpm-manifest.json`.
+///
+/// With this collection, you can resolve and install locked packages without any access to
+/// repositories. Use [`UnityProject::embed_locked_packages`] to embed packages.
+#[derive(Debug, Clone)]
+pub struct EmbeddedCollection {
+    repo: LocalCachedRepository,
+}
+
+impl EmbeddedCollection {
+    /// Creates the collection from the package manifests embedded in the `vpm-manifest.json`.
+    pub fn from_lockfile<IO: ProjectIo>(project: &UnityProject<IO>) -> io::Result<Self> {
+        let mut packages = Map::<String, Value>::new();
+
+        for embedded in project.manifest.all_embedded() {
+            let (Some(Value::String(name)), Some(Value::String(version))) =
+                (embedded.get("name"), embedded.get("version"))
+            else {
+                log::warn!("embedded package manifest without name or version");
+                continue;
+            };
+
+            let versions = packages
+                .entry(name.clone())
+                .or_insert_with(|| json!({ "versions": {} }));
+            versions["versions"][version] = Value::Object(embedded.clone());
+        }
+
+        let mut repo = Map::new();
+        repo.insert("name".into(), "Embedded Packages".into());
+        repo.insert("id".into(), "vrc-get.embedded".into());
+        repo.insert("packages".into(), Value::Object(packages));
+
+        Ok(Self {
+            repo: LocalCachedRepository::new(RemoteRepository::parse(repo)?, IndexMap::new()),
+        })
+    }
+}
+
+impl PackageCollection for EmbeddedCollection {
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        self.repo.all_packages()
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        self.repo
+            .get_versions_of(package)
+            .map(|pkg| PackageInfo::remote(pkg, &self.repo))
+    }
+
+    fn find_package_by_name(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+    ) -> Option<PackageInfo> {
+        self.repo
+            .repo()
+            .get_package(package)?
+            .get_latest(package_selector)
+            .map(|pkg| PackageInfo::remote(pkg, &self.repo))
+    }
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Embeds the package manifests of locked packages into the `vpm-manifest.json`.
+    ///
+    /// Only packages from remote repositories can be embedded.
+    /// Returns the names of locked packages not embedded. You have to call [`UnityProject::save`]
+    /// to write the embedded manifests.
+    pub fn embed_locked_packages(&mut self, env: &impl PackageCollection) -> Vec<Box<str>> {
+        let mut embedded = Vec::new();
+        let mut not_embedded = Vec::new();
+
+        for locked in self.manifest.all_locked() {
+            let json = env
+                .find_package_by_name(
+                    locked.name(),
+                    VersionSelector::specific_version(locked.version()),
+                )
+                .and_then(|pkg| {
+                    pkg.repo()?
+                        .repo()
+                        .get_package_version_json(pkg.name(), pkg.version())
+                });
+
+            match json {
+                Some(json) => embedded.push((Box::<str>::from(locked.name()), json.clone())),
+                None => not_embedded.push(locked.name().into()),
+            }
+        }
+
+        for (name, json) in embedded {
+            self.manifest.set_embedded(&name, json);
+        }
+
+        not_embedded
+    }
+}
@@ -16,6 +16,8 @@ use std::pin::pin;
 pub(crate) struct LegacyAssets<'a> {
     pub(crate) files: Vec<(Box<Path>, &'a str)>,
     pub(crate) folders: Vec<(Box<Path>, &'a str)>,
+    // (declared path, package) of the assets escaping the project
+    pub(crate) refused: Vec<(Box<str>, &'a str)>,
 }
 
 pub(crate) async fn collect_legacy_assets<'a>(
@@ -76,8 +78,9 @@ pub(crate) async fn collect_legacy_assets<'a>(
     }
 
     debug!("Collecting legacy assets by Path notation");
-    let (mut found_files, mut found_folders, find_guids) =
+    let (mut found_files, mut found_folders, find_guids, mut refused) =
         find_legacy_assets_by_path(io, assets.into_iter()).await;
+    refused.sort();
 
     if !find_guids.is_empty() {
         debug!("Collecting legacy assets with GUID");
@@ -87,6 +90,7 @@ pub(crate) async fn collect_legacy_assets<'a>(
     LegacyAssets {
         files: found_files.into_iter().collect(),
         folders: found_folders.into_iter().collect(),
+        refused,
     }
 }
 
@@ -106,6 +110,16 @@ fn valid_path(path: &Path) -> bool {
     true
 }
 
+/// Returns true if the path may point outside the project, like absolute paths or `..`.
+fn escapes_project(path: &Path) -> bool {
+    (path.components()).any(|x| {
+        !matches!(
+            x,
+            std::path::Component::Normal(_) | std::path::Component::CurDir
+        )
+    })
+}
+
 async fn find_legacy_assets_by_path<'a>(
     io: &impl ProjectIo,
     assets: impl Iterator<Item = DefinedLegacyInfo<'a>>,
@@ -113,6 +127,7 @@ async fn find_legacy_assets_by_path<'a>(
     HashMap<Box<Path>, &'a str>,
     HashMap<Box<Path>, &'a str>,
     HashMap<Guid, (&'a str, bool)>,
+    Vec<(Box<str>, &'a str)>,
 ) {
     use LegacySearchResult::*;
 
@@ -121,9 +136,9 @@ async fn find_legacy_assets_by_path<'a>(
             .map(|info| async move {
                 // some packages uses '/' as path separator.
                 let relative_path = PathBuf::from(info.path.replace('\\', "/")).into_boxed_path();
-                // for security, deny absolute path.
-                if relative_path.is_absolute() {
-                    return None;
+                // for security, deny paths escaping the project, even with the guid.
+                if relative_path.is_absolute() || escapes_project(&relative_path) {
+                    return Some(Refused(info.package_name, info.path));
                 }
                 #[allow(clippy::manual_map)] // it's parallel, not just a if-else
                 if valid_path(&relative_path)
@@ -151,6 +166,7 @@ async fn find_legacy_assets_by_path<'a>(
     let mut found_files = HashMap::new();
     let mut found_folders = HashMap::new();
     let mut find_guids = HashMap::new();
+    let mut refused = Vec::new();
 
     while let Some(info) = futures.next().await {
         match info {
@@ -163,11 +179,17 @@ async fn find_legacy_assets_by_path<'a>(
             Some(SearchWithGuid(package_name, guid, is_file)) => {
                 find_guids.insert(guid, (package_name, is_file));
             }
+            Some(Refused(package_name, path)) => {
+                log::error!(
+                    "refusing legacy asset {path} of {package_name}: outside of the project"
+                );
+                refused.push((path.into(), package_name));
+            }
             None => (),
         }
     }
 
-    (found_files, found_folders, find_guids)
+    (found_files, found_folders, find_guids, refused)
 }
 
 async fn check_guid(io: &impl ProjectIo, path: &Path, guid: Option<Guid>) -> bool {
@@ -268,6 +290,7 @@ impl<'a> DefinedLegacyInfo<'a> {
 enum LegacySearchResult<'a> {
     FoundWithPath(&'a str, Box<Path>, bool),
     SearchWithGuid(&'a str, Guid, bool),
+    Refused(&'a str, &'a str),
 }
 
 #[derive(Copy, Clone, Hash, Eq, PartialEq)]
@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::io::ProjectIo;
+use crate::unity_project::{
+    AddPackageErr, AddPackageOperation, PendingProjectChanges, ResolutionOptions,
+};
+use crate::version::Version;
+use crate::{PackageCollection, PackageInfo, UnityProject, VersionSelector};
+
+/// The exact versions of the packages in the project. See [`UnityProject::freeze`].
+///
+/// This is serializable, so you can save this to a file and install exactly the same versions
+/// in another project with [`UnityProject::install_frozen_request`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrozenPackages {
+    // the names of the packages in the dependencies of the project
+    pub dependencies: Vec<Box<str>>,
+    // all the locked packages
+    pub packages: Vec<FrozenPackage>,
+}
+
+/// The package in [`FrozenPackages`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FrozenPackage {
+    pub name: Box<str>,
+    pub version: Version,
+    // the url of the repository the package is from, if known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository_url: Option<Box<str>>,
+}
+
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum InstallFrozenError {
+    VersionsNotFound { packages: Vec<(Box<str>, Version)> },
+    AddPackageErr(AddPackageErr),
+}
+
+impl fmt::Display for InstallFrozenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallFrozenError::VersionsNotFound { packages } => {
+                write!(f, "Following versions are not found in any repository: ")?;
+                let mut first = true;
+                for (name, version) in packages {
+                    if !first {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{name}@{version}")?;
+                    first = false;
+                }
+                Ok(())
+            }
+            InstallFrozenError::AddPackageErr(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for InstallFrozenError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InstallFrozenError::AddPackageErr(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<AddPackageErr> for InstallFrozenError {
+    fn from(err: AddPackageErr) -> Self {
+        InstallFrozenError::AddPackageErr(err)
+    }
+}
+
+fn repository_url(package: PackageInfo) -> Option<&str> {
+    (package.repo())
+        .and_then(|repo| repo.url())
+        .map(|url| url.as_str())
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Takes the snapshot of the exact versions of the locked packages.
+    ///
+    /// The repository url is the one recorded in the lockfile, or the one of the repository
+    /// the locked version is found in the collection.
+    pub fn freeze(&self, env: &impl PackageCollection) -> FrozenPackages {
+        let mut dependencies = (self.manifest.dependencies())
+            .map(|(name, _)| name.into())
+            .collect::<Vec<Box<str>>>();
+        dependencies.sort();
+
+        let mut packages = (self.manifest.all_locked())
+            .map(|locked| {
+                let repository_url = locked.repository_url().map(Into::into).or_else(|| {
+                    let selector = VersionSelector::specific_version(locked.version());
+                    (env.find_package_by_name(locked.name(), selector))
+                        .and_then(repository_url)
+                        .map(Into::into)
+                });
+                FrozenPackage {
+                    name: locked.name().into(),
+                    version: locked.version().clone(),
+                    repository_url,
+                }
+            })
+            .collect::<Vec<_>>();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        FrozenPackages {
+            dependencies,
+            packages,
+        }
+    }
+
+    /// Creates the request to install exactly the versions in the snapshot.
+    ///
+    /// The packages are found from the recorded repository if possible, and from any repository
+    /// otherwise. Unlike installing packages normally, this fails if some version in the snapshot
+    /// is not found instead of choosing another version.
+    pub async fn install_frozen_request<'env>(
+        &self,
+        env: &'env impl PackageCollection,
+        frozen: &FrozenPackages,
+    ) -> Result<PendingProjectChanges<'env>, InstallFrozenError> {
+        let mut found = HashMap::new();
+        let mut not_found = Vec::new();
+
+        for package in &frozen.packages {
+            let mut candidates = (env.find_packages(&package.name))
+                .filter(|x| x.version() == &package.version)
+                .collect::<Vec<_>>();
+            // prefer the package from the recorded repository
+            candidates.sort_by_key(|&x| repository_url(x) != package.repository_url.as_deref());
+
+            match candidates.first() {
+                Some(&info) => {
+                    found.insert(package.name.as_ref(), info);
+                }
+                None => not_found.push((package.name.clone(), package.version.clone())),
+            }
+        }
+
+        if !not_found.is_empty() {
+            return Err(InstallFrozenError::VersionsNotFound {
+                packages: not_found,
+            });
+        }
+
+        let requested = (frozen.dependencies.iter())
+            .filter_map(|name| found.get(name.as_ref()).copied())
+            .collect::<Vec<_>>();
+        let pins = (frozen.packages.iter())
+            .map(|x| (x.name.as_ref(), x.version.clone()))
+            .collect();
+
+        Ok(self
+            .add_package_request_with_options(
+                env,
+                &requested,
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().pins(pins),
+            )
+            .await?)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::version::Version;
+use crate::{PackageCollection, PackageInfo, VersionSelector};
+
+/// The package collection with hypothetical packages added ahead of another collection.
+///
+/// This is useful to simulate the resolution with unreleased versions, like checking installing
+/// the next version of your package alongside other packages doesn't conflict.
+/// The hypothetical packages shadow the same versions in the base collection.
+/// Create them with [`PackageInfo::hypothetical`], and check [`PackageInfo::is_hypothetical`]
+/// in the result to find the packages not from any repository.
+pub struct HypotheticalCollection<'a, C: PackageCollection> {
+    base: &'a C,
+    extra_packages: Vec<PackageInfo<'a>>,
+}
+
+impl<'a, C: PackageCollection> HypotheticalCollection<'a, C> {
+    pub fn new(base: &'a C, extra_packages: Vec<PackageInfo<'a>>) -> Self {
+        Self {
+            base,
+            extra_packages,
+        }
+    }
+
+    fn is_shadowed(&self, package: PackageInfo) -> bool {
+        (self.extra_packages.iter())
+            .any(|x| x.name() == package.name() && x.version() == package.version())
+    }
+}
+
+impl<C: PackageCollection> PackageCollection for HypotheticalCollection<'_, C> {
+    fn get_curated_packages(
+        &self,
+        version_selector: VersionSelector,
+    ) -> impl Iterator<Item = PackageInfo> {
+        self.base.get_curated_packages(version_selector)
+    }
+
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        (self.extra_packages.iter().copied())
+            .chain((self.base.get_all_packages()).filter(|&x| !self.is_shadowed(x)))
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        (self.extra_packages.iter().copied())
+            .filter(move |x| x.name() == package)
+            .chain((self.base.find_packages(package)).filter(|&x| !self.is_shadowed(x)))
+    }
+
+    fn fetch_package(&self, package: &str, version: &Version) -> Option<PackageInfo> {
+        (self.extra_packages.iter().copied())
+            .find(|x| x.name() == package && x.version() == version)
+            .or_else(|| self.base.fetch_package(package, version))
+    }
+}
@@ -1,12 +1,34 @@
 use crate::traits::PackageCollection;
-use crate::unity_project::LockedDependencyInfo;
-use crate::version::{DependencyRange, PrereleaseAcceptance, UnityVersion, Version, VersionRange};
-use crate::{PackageInfo, PackageManifest, VersionSelector};
-use std::collections::{HashMap, HashSet, VecDeque};
+use crate::unity_project::{
+    AddPackageErr, LockedDependencyInfo, ProjectConfig, ResolvePackageErr, resolution_cache,
+};
+use crate::version::{
+    DependencyRange, PrereleaseAcceptance, PrereleaseMatching, UnityVersion, UnityVersionRange,
+    Version, VersionRange,
+};
+use crate::{
+    PackageInfo, PackageManifest, VersionSelector, unity_compatible, unity_exclusion,
+    unity_requirement,
+};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::time::SystemTime;
 
+#[derive(Clone)]
 struct PackageQueue<'a> {
     force_count: usize,
     pending_queue: VecDeque<PackageInfo<'a>>,
+    order: QueueOrder,
+    // package name -> the dependencies of the pending package. recorded only for DependentsFirst
+    pending_dependencies: HashMap<&'a str, Vec<&'a str>>,
+    // package name -> the number of pending packages depending on the package
+    pending_dependents: HashMap<&'a str, usize>,
 }
 
 impl<'a> PackageQueue<'a> {
@@ -14,25 +36,91 @@ impl<'a> PackageQueue<'a> {
         Self {
             force_count: packages.len(),
             pending_queue: VecDeque::from_iter(packages),
+            order: QueueOrder::LastAdded,
+            pending_dependencies: HashMap::new(),
+            pending_dependents: HashMap::new(),
         }
     }
 
     pub(crate) fn next_package(&mut self) -> Option<(PackageInfo<'a>, bool)> {
         let force = self.force_count > 0;
         self.force_count = self.force_count.saturating_sub(1);
-        self.pending_queue.pop_back().map(|x| (x, force))
+        let last = self.pending_queue.len().checked_sub(1)?;
+        let index = match self.order {
+            QueueOrder::LastAdded => last,
+            // forced packages are at the back and must be processed first
+            QueueOrder::DependentsFirst if force => last,
+            QueueOrder::DependentsFirst => (self.pending_queue.iter())
+                .rposition(|x| !self.has_pending_dependent(x.name()))
+                .unwrap_or(last),
+        };
+        let package = self.pending_queue.remove(index)?;
+        self.forget_dependencies(package.name());
+        Some((package, force))
+    }
+
+    fn has_pending_dependent(&self, name: &str) -> bool {
+        self.pending_dependents.get(name).is_some_and(|&x| x > 0)
+    }
+
+    fn record_dependencies(&mut self, name: &'a str, dependencies: Vec<&'a str>) {
+        if dependencies.is_empty() {
+            return;
+        }
+        for &dependency in &dependencies {
+            *self.pending_dependents.entry(dependency).or_default() += 1;
+        }
+        self.pending_dependencies.insert(name, dependencies);
+    }
+
+    fn forget_dependencies(&mut self, name: &str) {
+        for dependency in self.pending_dependencies.remove(name).unwrap_or_default() {
+            if let Some(count) = self.pending_dependents.get_mut(dependency) {
+                *count -= 1;
+            }
+        }
+    }
+
+    fn remove_pending_package(&mut self, name: &str) {
+        self.pending_queue.retain(|x| x.name() != name);
+        self.forget_dependencies(name);
     }
 
     fn find_pending_package(&self, name: &str) -> Option<&PackageInfo<'a>> {
         self.pending_queue.iter().find(|x| x.name() == name)
     }
 
-    pub(crate) fn add_pending_package(&mut self, package: PackageInfo<'a>) {
-        self.pending_queue.retain(|x| x.name() != package.name());
+    pub(crate) fn add_pending_package(
+        &mut self,
+        package: PackageInfo<'a>,
+        dependencies: Vec<&'a str>,
+    ) {
+        self.remove_pending_package(package.name());
+        self.record_dependencies(package.name(), dependencies);
+        self.pending_queue.push_back(package);
+    }
+
+    // the package will be processed before other pending packages, and will be forced
+    fn add_forced_package(&mut self, package: PackageInfo<'a>, dependencies: Vec<&'a str>) {
+        self.remove_pending_package(package.name());
+        self.record_dependencies(package.name(), dependencies);
         self.pending_queue.push_back(package);
+        self.force_count += 1;
+    }
+
+    // the package will be processed after all other packages, and will not be forced
+    pub(crate) fn add_low_priority_package(
+        &mut self,
+        package: PackageInfo<'a>,
+        dependencies: Vec<&'a str>,
+    ) {
+        self.remove_pending_package(package.name());
+        self.record_dependencies(package.name(), dependencies);
+        self.pending_queue.push_front(package);
     }
 }
 
+#[derive(Clone)]
 struct ResolutionContext<'env, 'a>
 where
     'env: 'a,
@@ -41,8 +129,90 @@ where
     pub pending_queue: PackageQueue<'env>,
     dependencies: HashMap<&'a str, DependencyInfo<'env, 'a>>,
     unlocked_names: HashSet<&'a str>,
+    // packages in the supplied locked dependencies
+    locked_names: HashSet<&'a str>,
+    // package name -> the version in the supplied locked dependencies
+    locked_versions: HashMap<&'a str, &'a Version>,
+    // packages vendored by the user which must be kept as is
+    vendored_names: HashSet<&'a str>,
+    // packages provided outside of vpm. those are also in vendored_names
+    provided_names: HashSet<&'a str>,
+    // locked packages managed by the user in the project config. those are also in vendored_names
+    user_managed_names: HashSet<&'a str>,
+    // package name -> the version the package is pinned to
+    pins: HashMap<&'a str, &'a Version>,
+    // (package, version) of requested packages rejected since the package is pinned to another version
+    rejected_by_pins: Vec<(&'env str, Version)>,
+    // pinned packages whose pins are overrides. those are also in pins
+    overridden_names: HashSet<&'a str>,
+    // packages treated as already satisfied, managed outside the resolution
+    excluded_names: HashSet<&'a str>,
+    // packages explicitly requested by the caller
+    requested_names: HashSet<&'env str>,
+    // (dependency, source) of requirements not satisfied to satisfy higher-priority ones
+    sacrificed_requirements: Vec<(&'env str, &'env str)>,
+    added_recommendations: Vec<&'env str>,
+    skipped_recommendations: Vec<&'env str>,
+    // (package, dependency) of optional dependencies activated since the dependency is present
+    activated_optionals: Vec<(&'env str, &'env str)>,
+    // features whose conditional dependencies are added to the dependencies of packages
+    enabled_features: HashSet<&'a str>,
+    // (dependency, source, range) of requirements no published version satisfies
+    unsatisfiable_requirements: Vec<(&'env str, &'env str, VersionRange)>,
+    // (legacy, modern) -> whether the removal is approved
+    removal_decisions: HashMap<(&'a str, &'a str), bool>,
+    // (legacy, modern) of legacy removals suppressed by the callback
+    suppressed_removals: Vec<(&'a str, &'a str)>,
+    // (dependency, source, range) of requirements rewritten by the range rewriter
+    rewritten_requirements: Vec<(&'a str, &'a str, VersionRange)>,
+    // (package, repository id, installed package) of choices by the naming convention
+    naming_tiebreaks: Vec<(&'env str, Box<str>, Box<str>)>,
+    // package name -> the version the package must be older than. used for backtracking
+    version_caps: HashMap<&'env str, Version>,
+    // packages whose dependencies cannot be found
+    dead_ends: Vec<&'env str>,
+    // package name -> the package whose dependency added the package
+    parents: HashMap<&'env str, &'env str>,
+    // the package names forming dependency cycles
+    dependency_cycles: Vec<Vec<&'env str>>,
+    // package name -> whether prerelease is allowed, overriding other flags
+    prerelease_overrides: HashMap<&'a str, bool>,
+    // ids of repositories whose prerelease versions are allowed
+    prerelease_repositories: Vec<&'a str>,
+    // (package, minimum unity) of packages chosen although they require newer unity
+    unity_gated: Vec<(&'env str, UnityVersion)>,
+    // locked packages whose dependencies on disk differ from the recorded ones
+    lockfile_drift: Vec<LockfileDrift>,
+    // locked packages whose dependencies in the collection differ from the recorded ones
+    locked_dependency_mismatches: Vec<LockedDependencyMismatch>,
+    // the counters of the work done. None unless collecting stats
+    stats: Option<ResolutionStats>,
+    // dependencies not found since all the versions are for other platforms
+    platform_incompatible: Vec<&'env str>,
+    // dependencies not found since no version is compatible with all the target unity versions
+    target_unity_incompatible: Vec<&'env str>,
+    // dependencies not found since all the versions in the ranges are yanked
+    all_candidates_yanked: Vec<&'env str>,
+    // the number of packages processed from the pending queue
+    processed_count: usize,
+    // the names of the packages processed most recently, oldest first
+    recently_processed: VecDeque<&'env str>,
+    // whether the resolution stopped since the iteration limit is exceeded
+    limit_exceeded: bool,
+    // the dependency the violation handler stopped the resolution at
+    aborted_at: Option<&'env str>,
+    // old name -> name of the package in the collection for renamed packages
+    aliases: HashMap<Box<str>, &'env str>,
+    // locked packages not found since the repository they are installed from is missing
+    missing_source_repositories: Vec<MissingSourceRepository>,
+    // urls of the repositories in the collection, built on the first locked package needing them
+    repository_urls: Option<HashSet<&'env str>>,
+    // the rule for prerelease versions matching ranges without prerelease allowed
+    prerelease_matching: PrereleaseMatching,
+    prerelease_propagation: PrereleasePropagation,
 }
 
+#[derive(Clone)]
 struct Legacy<'env>(&'env [Box<str>]);
 
 impl Default for Legacy<'_> {
@@ -52,12 +222,12 @@ impl Default for Legacy<'_> {
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 struct DependencyInfo<'env, 'a> {
     using: Option<PackageInfo<'env>>,
     current: Option<&'a Version>,
     // "" key for root dependencies
-    requirements: HashMap<&'a str, &'a VersionRange>,
+    requirements: HashMap<&'a str, Cow<'a, VersionRange>>,
     dependencies: HashSet<&'a str>,
 
     modern_packages: HashSet<&'a str>,
@@ -71,7 +241,7 @@ impl<'env, 'a> DependencyInfo<'env, 'a>
 where
     'env: 'a,
 {
-    fn new_dependency(version_range: &'a VersionRange, allow_pre: bool) -> Self {
+    fn new_dependency(version_range: Cow<'a, VersionRange>, allow_pre: bool) -> Self {
         let mut requirements = HashMap::new();
         requirements.insert("", version_range);
         DependencyInfo {
@@ -88,7 +258,7 @@ where
         }
     }
 
-    fn add_range(&mut self, source: &'a str, range: &'a VersionRange) {
+    fn add_range(&mut self, source: &'a str, range: Cow<'a, VersionRange>) {
         self.requirements.insert(source, range);
         self.touched = true;
     }
@@ -112,8 +282,40 @@ where
         !self.modern_packages.is_empty()
     }
 
-    pub(crate) fn set_using_info(&mut self, version: &'a Version, dependencies: HashSet<&'a str>) {
-        self.allow_pre |= !version.pre.is_empty();
+    /// Returns the names of the modern packages replacing this legacy package, sorted.
+    fn replaced_by(&self) -> Vec<Box<str>> {
+        let mut replaced_by = (self.modern_packages.iter())
+            .map(|&x| Box::<str>::from(x))
+            .collect::<Vec<_>>();
+        replaced_by.sort();
+        replaced_by
+    }
+
+    /// Returns the source of the requirement with the highest lower bound among ones satisfied by the version.
+    ///
+    /// Ties are broken by the source name to make the result stable.
+    fn binding_requirement(
+        &self,
+        version: &Version,
+        is_valid_source: impl Fn(&str) -> bool,
+    ) -> Option<&'a str> {
+        (self.requirements.iter())
+            .filter(|&(&source, _)| is_valid_source(source))
+            .filter(|(_, range)| range.match_pre(version, PrereleaseAcceptance::Allow))
+            .filter_map(|(&source, range)| Some((range.lower_bound()?, source)))
+            .max_by(|(a, a_source), (b, b_source)| a.cmp(b).then(b_source.cmp(a_source)))
+            .map(|(_, source)| source)
+    }
+
+    pub(crate) fn set_using_info(
+        &mut self,
+        version: &'a Version,
+        dependencies: HashSet<&'a str>,
+        allow_installed_pre: bool,
+    ) {
+        if allow_installed_pre {
+            self.allow_pre |= !version.pre.is_empty();
+        }
         self.current = Some(version);
         self.dependencies = dependencies;
     }
@@ -122,10 +324,52 @@ where
 impl<'env> ResolutionContext<'env, '_> {
     fn new(allow_prerelease: bool, packages: Vec<PackageInfo<'env>>) -> Self {
         let mut this = Self {
+            requested_names: packages.iter().map(|x| x.name()).collect(),
             dependencies: HashMap::new(),
             pending_queue: PackageQueue::new(packages),
             allow_prerelease,
             unlocked_names: HashSet::new(),
+            locked_names: HashSet::new(),
+            locked_versions: HashMap::new(),
+            vendored_names: HashSet::new(),
+            provided_names: HashSet::new(),
+            user_managed_names: HashSet::new(),
+            pins: HashMap::new(),
+            rejected_by_pins: Vec::new(),
+            overridden_names: HashSet::new(),
+            excluded_names: HashSet::new(),
+            sacrificed_requirements: Vec::new(),
+            added_recommendations: Vec::new(),
+            skipped_recommendations: Vec::new(),
+            activated_optionals: Vec::new(),
+            enabled_features: HashSet::new(),
+            unsatisfiable_requirements: Vec::new(),
+            removal_decisions: HashMap::new(),
+            suppressed_removals: Vec::new(),
+            rewritten_requirements: Vec::new(),
+            naming_tiebreaks: Vec::new(),
+            version_caps: HashMap::new(),
+            dead_ends: Vec::new(),
+            parents: HashMap::new(),
+            dependency_cycles: Vec::new(),
+            prerelease_overrides: HashMap::new(),
+            prerelease_repositories: Vec::new(),
+            unity_gated: Vec::new(),
+            lockfile_drift: Vec::new(),
+            locked_dependency_mismatches: Vec::new(),
+            stats: None,
+            platform_incompatible: Vec::new(),
+            target_unity_incompatible: Vec::new(),
+            all_candidates_yanked: Vec::new(),
+            processed_count: 0,
+            recently_processed: VecDeque::new(),
+            limit_exceeded: false,
+            aborted_at: None,
+            aliases: HashMap::new(),
+            missing_source_repositories: Vec::new(),
+            repository_urls: None,
+            prerelease_matching: PrereleaseMatching::AnyPrerelease,
+            prerelease_propagation: PrereleasePropagation::Propagate,
         };
 
         for pkg in &this.pending_queue.pending_queue {
@@ -144,23 +388,84 @@ where
     pub(crate) fn add_root_dependency(
         &mut self,
         name: &'a str,
-        range: &'a VersionRange,
+        range: Cow<'a, VersionRange>,
         allow_pre: bool,
     ) {
         self.dependencies
             .get_mut("")
             .unwrap()
             .requirements
-            .insert(name, range);
+            .insert(name, range.clone());
         self.dependencies
             .insert(name, DependencyInfo::new_dependency(range, allow_pre));
     }
 
+    /// Adds or replaces the root dependency, keeping what is known about the package.
+    fn update_root_dependency(&mut self, name: &'a str, range: VersionRange) {
+        let allow_pre = range.contains_pre();
+        self.dependencies
+            .get_mut("")
+            .unwrap()
+            .requirements
+            .insert(name, Cow::Owned(range.clone()));
+        let info = self.dependencies.entry(name).or_default();
+        info.add_range("", Cow::Owned(range));
+        info.allow_pre |= allow_pre;
+    }
+
+    fn remove_root_dependency(&mut self, name: &str) {
+        self.dependencies
+            .get_mut("")
+            .unwrap()
+            .requirements
+            .remove(name);
+        if let Some(info) = self.dependencies.get_mut(name) {
+            info.remove_range("");
+        }
+    }
+
+    /// Requests the package as if it's passed to [`ResolutionContext::new`].
+    fn request_package(&mut self, package: PackageInfo<'env>) {
+        self.requested_names.insert(package.name());
+        self.dependencies
+            .entry(package.name())
+            .or_default()
+            .allow_pre = true;
+        let dependencies = self.queued_dependencies(package);
+        self.pending_queue.add_forced_package(package, dependencies);
+    }
+
     pub(crate) fn add_unlocked_name(&mut self, name: &'a str) {
         self.unlocked_names.insert(name);
     }
 
-    pub(crate) fn add_unlocked_manifest(&mut self, manifest: &'a PackageManifest) {
+    pub(crate) fn add_vendored_package(&mut self, name: &'a str, version: &'a Version) {
+        let info = self.dependencies.entry(name).or_default();
+        info.allow_pre |= version.is_pre();
+        info.current = Some(version);
+        // to report conflicts with the vendored version
+        info.touched = true;
+        self.vendored_names.insert(name);
+    }
+
+    pub(crate) fn add_provided_package(&mut self, name: &'a str, version: &'a Version) {
+        // provided packages are kept as is like vendored ones
+        self.add_vendored_package(name, version);
+        self.provided_names.insert(name);
+    }
+
+    pub(crate) fn add_pinned_package(&mut self, name: &'a str, version: &'a Version) {
+        let info = self.dependencies.entry(name).or_default();
+        info.allow_pre |= version.is_pre();
+        self.pins.insert(name, version);
+    }
+
+    pub(crate) fn add_unlocked_manifest(
+        &mut self,
+        manifest: &'a PackageManifest,
+        options: &ResolutionOptions,
+    ) {
+        let allow_installed_pre = self.propagates_pre_to(manifest.name());
         let info = self.dependencies.entry(manifest.name()).or_default();
         info.set_using_info(
             manifest.version(),
@@ -169,6 +474,7 @@ where
                 .keys()
                 .map(|x| x.as_ref())
                 .collect(),
+            allow_installed_pre,
         );
 
         info.legacy_packages = Legacy(manifest.legacy_packages());
@@ -182,6 +488,7 @@ where
         }
 
         for (dependency, range) in manifest.vpm_dependencies() {
+            let range = self.rewrite_range(dependency, manifest.name(), range, options);
             self.dependencies
                 .entry(dependency)
                 .or_default()
@@ -194,19 +501,54 @@ where
         &mut self,
         locked: LockedDependencyInfo<'a>,
         env: &'env impl PackageCollection,
+        options: &ResolutionOptions,
     ) {
+        self.locked_names.insert(locked.name());
+        (self.locked_versions).insert(locked.name(), locked.version());
+
+        if let Some(on_disk_dependencies) = &options.on_disk_dependencies {
+            if let Some(on_disk) = on_disk_dependencies(locked.name()) {
+                if let Some(diff) = diff_dependencies(locked.dependencies(), &on_disk) {
+                    log::debug!("dependencies of locked package {} drifted", locked.name());
+                    self.lockfile_drift.push(LockfileDrift {
+                        package: locked.name().into(),
+                        only_locked: diff.only_recorded,
+                        only_on_disk: diff.only_other,
+                    });
+                }
+            }
+        }
+
+        let dependencies = (locked.dependencies().keys())
+            .map(|x| self.canonical_name(x))
+            .collect();
+        let allow_installed_pre = self.propagates_pre_to(locked.name());
         let info = self.dependencies.entry(locked.name()).or_default();
-        info.set_using_info(
-            locked.version(),
-            locked.dependencies().keys().map(|x| x.as_ref()).collect(),
-        );
+        info.set_using_info(locked.version(), dependencies, allow_installed_pre);
 
-        if let Some(pkg) = env.find_package_by_name(
-            locked.name(),
-            VersionSelector::specific_version(locked.version()),
-        ) {
+        // prefer the exact build locked since == ignores the build metadata
+        let package = (env.find_packages(locked.name()))
+            .find(|x| x.version().is_same_build(locked.version()))
+            .or_else(|| env.fetch_package(locked.name(), locked.version()));
+
+        if let Some(pkg) = package {
             info.legacy_packages = Legacy(pkg.legacy_packages());
 
+            // the same version may be re-published with other dependencies
+            if let Some(diff) = diff_dependencies(locked.dependencies(), pkg.vpm_dependencies()) {
+                log::debug!(
+                    "dependencies of locked package {} differ from the collection",
+                    locked.name()
+                );
+                self.locked_dependency_mismatches
+                    .push(LockedDependencyMismatch {
+                        package: locked.name().into(),
+                        version: locked.version().clone(),
+                        only_locked: diff.only_recorded,
+                        only_in_collection: diff.only_other,
+                    });
+            }
+
             for legacy in pkg.legacy_packages() {
                 self.dependencies
                     .entry(legacy)
@@ -214,9 +556,30 @@ where
                     .modern_packages
                     .insert(locked.name());
             }
+        } else if let Some(url) = locked.repository_url() {
+            // legacy packages are unknown without the package, so warn if the repository is missing
+            let repository_urls = self.repository_urls.get_or_insert_with(|| {
+                (env.get_all_packages())
+                    .filter_map(|x| x.repo()?.url())
+                    .map(|x| x.as_str())
+                    .collect()
+            });
+            if !repository_urls.contains(url) {
+                log::debug!(
+                    "repository {url} of locked package {} is missing",
+                    locked.name()
+                );
+                self.missing_source_repositories
+                    .push(MissingSourceRepository {
+                        package: locked.name().into(),
+                        url: url.into(),
+                    });
+            }
         }
 
         for (dependency, range) in locked.dependencies() {
+            let dependency = self.canonical_name(dependency);
+            let range = self.rewrite_range(dependency, locked.name(), range, options);
             self.dependencies
                 .entry(dependency)
                 .or_default()
@@ -225,29 +588,243 @@ where
         }
     }
 
-    pub(crate) fn add_package(&mut self, package: PackageInfo<'env>, force: bool) -> bool {
+    /// Returns the name of the package the name is renamed to, or the name itself.
+    fn canonical_name<'n>(&self, name: &'n str) -> &'n str
+    where
+        'env: 'n,
+    {
+        self.aliases.get(name).copied().unwrap_or(name)
+    }
+
+    /// Rewrites the range of the requirement with the range rewriter, and records the rewrite.
+    fn rewrite_range(
+        &mut self,
+        dependency: &'a str,
+        source: &'a str,
+        range: &'a VersionRange,
+        options: &ResolutionOptions,
+    ) -> Cow<'a, VersionRange> {
+        let Some(rewriter) = &options.range_rewriter else {
+            return Cow::Borrowed(range);
+        };
+        match rewriter(source, dependency, range) {
+            Some(rewritten) => {
+                log::debug!(
+                    "rewriting requirement {dependency} {range} from {source} to {rewritten}"
+                );
+                (self.rewritten_requirements).push((dependency, source, rewritten.clone()));
+                Cow::Owned(rewritten)
+            }
+            None => Cow::Borrowed(range),
+        }
+    }
+
+    /// Returns the range of the requirement if it's rewritten by the range rewriter.
+    fn rewritten_range(&self, dependency: &str, source: &str) -> Option<VersionRange> {
+        match self
+            .dependencies
+            .get(dependency)?
+            .requirements
+            .get(source)?
+        {
+            Cow::Owned(range) => Some(range.clone()),
+            Cow::Borrowed(_) => None,
+        }
+    }
+
+    /// Adds the package to be included regardless of whether something requires it.
+    ///
+    /// Returns false if no package satisfies the requirements known so far.
+    pub(crate) fn add_must_include_package(
+        &mut self,
+        name: &str,
+        env: &'env impl PackageCollection,
+        unity_version: Option<UnityVersion>,
+        options: &ResolutionOptions,
+    ) -> bool {
+        let mut ranges = Vec::new();
+        let allow_pre = self.allow_pre_of(name);
+
+        if let Some(info) = self.dependencies.get(name) {
+            if info.is_legacy() || self.unlocked_names.contains(name) {
+                return true;
+            }
+            if self.excluded_names.contains(name) {
+                return true;
+            }
+
+            ranges.extend(info.requirements.values().map(AsRef::as_ref));
+
+            if let Some(current) = info.current {
+                let acceptance = self.prerelease_matching.allow_or_minimum(allow_pre);
+                if ranges.iter().all(|x| x.match_pre(current, acceptance)) {
+                    log::debug!("must include package {name}: existing matches");
+                    return true;
+                }
+            }
+        }
+
+        // the release channel decides prereleases by itself
+        let allow_pre = allow_pre || options.channel.is_some();
+
+        let selector = if ranges.is_empty() {
+            // nothing constrains the package, so we pick the newest one
+            VersionSelector::latest_for(unity_version, allow_pre)
+        } else {
+            VersionSelector::ranges_for(
+                unity_version,
+                &ranges,
+                self.prerelease_matching.allow_or_minimum(allow_pre),
+            )
+        };
+        let selector = (selector.published_before(options.published_before))
+            .target_platform(options.target_platform)
+            .target_unity_versions(&options.target_unity_versions);
+
+        let found = if let Some(channel) = &options.channel {
+            find_in_channel(env, name, selector, channel)
+        } else {
+            env.find_package_by_name(name, selector)
+        };
+
+        let Some(package) = found else {
+            return false;
+        };
+
+        let package = self.tiebreak_by_repository(env, package, options);
+        let dependencies = self.queued_dependencies(package);
+        self.pending_queue
+            .add_low_priority_package(package, dependencies);
+        true
+    }
+
+    /// Chooses the package from the most preferred repository among packages with the same version.
+    ///
+    /// If the naming convention is preferred, the remaining ties are broken by the number of leading
+    /// name segments the repository id shares with installed packages.
+    fn tiebreak_by_repository(
+        &mut self,
+        env: &'env impl PackageCollection,
+        found: PackageInfo<'env>,
+        options: &ResolutionOptions,
+    ) -> PackageInfo<'env> {
+        if options.tiebreak_repo_priority.is_empty() && !options.prefer_naming_convention {
+            return found;
+        }
+
+        let priority_of = |package: PackageInfo| {
+            (package.repository_id())
+                .and_then(|id| options.tiebreak_repo_priority.iter().position(|&x| x == id))
+                .unwrap_or(usize::MAX)
+        };
+
+        let candidates = (env.find_packages(found.name()))
+            .filter(|x| x.version() == found.version())
+            .filter(|x| !x.is_yanked())
+            .collect::<Vec<_>>();
+
+        let chosen = (candidates.iter().copied())
+            .min_by_key(|&x| priority_of(x))
+            .filter(|&x| priority_of(x) < priority_of(found))
+            .unwrap_or(found);
+
+        if !options.prefer_naming_convention {
+            return chosen;
+        }
+
+        let installed = (self.dependencies.iter())
+            .filter(|&(&name, _)| !name.is_empty() && name != found.name())
+            .filter(|(_, info)| !info.is_legacy())
+            .filter(|(_, info)| info.current.is_some() || info.using.is_some())
+            .map(|(&name, _)| name)
+            .collect::<Vec<_>>();
+
+        // (shared segments, installed package) of the installed package sharing the most segments,
+        // or (0, "") if no installed package shares segments
+        let score_of = |package: PackageInfo| {
+            let id = package.repo().and_then(|repo| repo.id()).unwrap_or("");
+            (installed.iter())
+                .map(|&name| (shared_segments(id, name), name))
+                .max()
+                .filter(|&(shared, _)| shared > 0)
+                .unwrap_or((0, ""))
+        };
+
+        // the candidate replaces the chosen one only if it shares more segments, so the installed
+        // package of the replaced one is always an actual package
+        let mut best = (chosen, score_of(chosen));
+        for candidate in candidates {
+            if priority_of(candidate) != priority_of(chosen) {
+                continue;
+            }
+            let score = score_of(candidate);
+            if score.0 > best.1.0 {
+                best = (candidate, score);
+            }
+        }
+
+        let (package, (_, installed)) = best;
+        if !std::ptr::eq(package.package_json(), chosen.package_json()) {
+            let id = package.repo().and_then(|repo| repo.id()).unwrap_or("");
+            log::debug!(
+                "choosing package {} from {id}: sharing the naming convention with {installed}",
+                package.name()
+            );
+            (self.naming_tiebreaks).push((package.name(), id.into(), installed.into()));
+        }
+        package
+    }
+
+    pub(crate) fn add_package(
+        &mut self,
+        package: PackageInfo<'env>,
+        force: bool,
+        options: &ResolutionOptions,
+    ) -> bool {
+        let vpm_dependencies = self.effective_dependencies(package);
         let entry = self.dependencies.entry(package.name()).or_default();
 
         if entry.is_legacy() {
             return false;
         }
 
+        if self.vendored_names.contains(package.name()) {
+            return false;
+        }
+
+        if self.excluded_names.contains(package.name()) {
+            return false;
+        }
+
+        if let Some(&pin) = self.pins.get(package.name()) {
+            if package.version() != pin {
+                log::debug!(
+                    "adding package {} version {}: pinned to {pin}",
+                    package.name(),
+                    package.version()
+                );
+                if force {
+                    (self.rejected_by_pins).push((package.name(), package.version().clone()));
+                }
+                return false;
+            }
+        }
+
         if !force && self.unlocked_names.contains(package.name()) {
             return false;
         }
 
         entry.touched = true;
-        let vpm_dependencies = &package.vpm_dependencies();
         let legacy_packages = package.legacy_packages();
         let name = package.name();
 
         entry.current = Some(package.version());
         entry.using = Some(package);
 
-        let old_dependencies = std::mem::replace(
-            &mut entry.dependencies,
-            vpm_dependencies.keys().map(|x| x.as_ref()).collect(),
-        );
+        let canonical_dependencies = (vpm_dependencies.iter())
+            .map(|&(x, _)| (self.aliases.get(x).copied()).unwrap_or(x))
+            .collect();
+        let old_dependencies = std::mem::replace(&mut entry.dependencies, canonical_dependencies);
         let old_legacy_packages =
             std::mem::replace(&mut entry.legacy_packages, Legacy(legacy_packages));
 
@@ -256,7 +833,10 @@ where
         for dep in &old_dependencies {
             self.dependencies.get_mut(*dep).unwrap().remove_range(name);
         }
-        for (dependency, range) in vpm_dependencies.iter() {
+        (self.rewritten_requirements).retain(|&(_, source, _)| source != name);
+        for &(dependency, range) in &vpm_dependencies {
+            let dependency = self.canonical_name(dependency);
+            let range = self.rewrite_range(dependency, name, range, options);
             self.dependencies
                 .entry(dependency)
                 .or_default()
@@ -272,6 +852,9 @@ where
                 .remove_modern_package(name);
         }
         for legacy in legacy_packages {
+            if !self.approve_legacy_removal(legacy, name, options) {
+                continue;
+            }
             self.dependencies
                 .entry(legacy)
                 .or_default()
@@ -282,148 +865,3560 @@ where
         true
     }
 
-    pub(crate) fn should_add_package(&self, name: &'a str, range: &'a VersionRange) -> bool {
-        let entry = self.dependencies.get(name).unwrap();
+    /// Returns the dependencies of the package, including the activated optional dependencies
+    /// and the dependencies of the enabled features.
+    fn effective_dependencies(
+        &self,
+        package: PackageInfo<'env>,
+    ) -> Vec<(&'env str, &'env VersionRange)> {
+        let name = package.name();
+        let activated = |dependency: &str| (self.activated_optionals).contains(&(name, dependency));
+        let mut features = (self.enabled_features.iter().copied())
+            .filter_map(|feature| package.feature_dependencies(feature).map(|x| (feature, x)))
+            .collect::<Vec<_>>();
+        // the order of the features is stable for the same enabled features
+        features.sort_by_key(|&(feature, _)| feature);
+        (package.vpm_dependencies().iter())
+            .chain(
+                (package.vpm_optional_dependencies().iter())
+                    .filter(|(dependency, _)| activated(dependency)),
+            )
+            .chain(
+                features
+                    .into_iter()
+                    .flat_map(|(_, dependencies)| dependencies),
+            )
+            .map(|(dependency, range)| (dependency.as_ref(), range))
+            .collect()
+    }
 
-        if entry.is_legacy() {
-            log::debug!(
-                "processing package {name}: dependency {name} version {range}: legacy package"
-            );
-            return false;
+    /// Sets the order the pending packages are processed in, and records the dependencies of
+    /// the packages already pending.
+    fn set_queue_order(&mut self, order: QueueOrder) {
+        self.pending_queue.order = order;
+        for package in self.pending_queue.pending_queue.clone() {
+            let dependencies = self.queued_dependencies(package);
+            self.pending_queue.forget_dependencies(package.name());
+            self.pending_queue
+                .record_dependencies(package.name(), dependencies);
         }
+    }
 
-        if self.unlocked_names.contains(name) {
-            log::debug!(
-                "processing package {name}: dependency {name} version {range}: unlocked package"
-            );
-            return false;
+    /// Returns the dependencies the pending queue processes the package before.
+    ///
+    /// The dependencies are recorded only if the queue processes the dependents first.
+    fn queued_dependencies(&self, package: PackageInfo<'env>) -> Vec<&'env str> {
+        if self.pending_queue.order != QueueOrder::DependentsFirst {
+            return Vec::new();
         }
+        let name = package.name();
+        (self.effective_dependencies(package).into_iter())
+            .map(|(dependency, _)| self.canonical_name(dependency))
+            .filter(|&dependency| dependency != name)
+            .collect()
+    }
 
-        let mut install = true;
-        let allow_prerelease =
-            PrereleaseAcceptance::allow_or_minimum(entry.allow_pre || self.allow_prerelease);
+    /// Activates the optional dependencies on the packages installed or being installed,
+    /// and resolves the packages declaring them again with the activated ones.
+    ///
+    /// Optional dependencies on other packages are skipped.
+    pub(crate) fn activate_optional_dependencies(
+        &mut self,
+        env: &'env impl PackageCollection,
+        unity_version: Option<UnityVersion>,
+        options: &ResolutionOptions,
+        missing_dependencies: &mut MissingDependencies,
+    ) {
+        loop {
+            let is_present = |dependency: &str| {
+                (self.dependencies.get(self.canonical_name(dependency)))
+                    .is_some_and(|x| !x.is_legacy() && (x.using.is_some() || x.current.is_some()))
+            };
+            let activating = (self.dependencies.values())
+                .filter(|x| !x.is_legacy())
+                .filter_map(|x| x.using)
+                .flat_map(|package| {
+                    (package.vpm_optional_dependencies().keys())
+                        .map(move |dependency| (package, dependency.as_ref()))
+                })
+                .filter(|&(package, dependency)| {
+                    !(self.activated_optionals).contains(&(package.name(), dependency))
+                        && is_present(dependency)
+                })
+                .collect::<Vec<_>>();
 
-        if let Some(pending) = self.pending_queue.find_pending_package(name) {
-            if range.match_pre(pending.version(), allow_prerelease) {
-                // if installing version is good, no need to reinstall
-                install = false;
+            if activating.is_empty() {
+                break;
+            }
+
+            for (package, dependency) in activating {
                 log::debug!(
-                    "processing package {name}: dependency {name} version {range}: pending matches"
+                    "optional dependency {dependency} of {}: activated",
+                    package.name()
                 );
+                self.activated_optionals.push((package.name(), dependency));
+                let dependencies = self.queued_dependencies(package);
+                self.pending_queue
+                    .add_pending_package(package, dependencies);
             }
-        } else {
-            // if already installed version is good, no need to reinstall
-            if let Some(version) = &entry.current {
-                if range.match_pre(version, allow_prerelease) {
-                    log::debug!(
-                        "processing package {name}: dependency {name} version {range}: existing matches"
-                    );
-                    install = false;
-                }
-            }
+            process_pending_queue(self, env, unity_version, options, missing_dependencies);
         }
-
-        install
     }
-}
 
-impl<'env> ResolutionContext<'env, '_> {
-    pub(crate) fn build_result(self) -> PackageResolutionResult<'env> {
-        let mut conflicts = HashMap::<Box<str>, Vec<Box<str>>>::new();
-        for (&name, info) in &self.dependencies {
-            if !info.is_legacy() && info.touched {
-                if let Some(version) = &info.current {
-                    let conflicts_with_this = info
-                        .requirements
-                        .iter()
-                        .filter(|&(&source, _)| {
-                            self.dependencies
-                                .get(source)
-                                .map(|x| !x.is_legacy())
-                                .unwrap_or_default()
-                        })
-                        .filter(|(_, range)| {
-                            !range.match_pre(
-                                version,
-                                PrereleaseAcceptance::allow_or_minimum(
-                                    info.allow_pre || self.allow_prerelease,
-                                ),
-                            )
-                        })
-                        .map(|(source, _)| *source)
-                        .collect::<Vec<_>>();
+    /// Chooses the package for the requirement considering the priorities of requirements.
+    ///
+    /// Returns None if the requirement should be sacrificed to keep higher-priority requirements satisfied.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn arbitrate_priority(
+        &self,
+        dependency: &str,
+        source: &str,
+        range: &VersionRange,
+        found: PackageInfo<'env>,
+        options: &ResolutionOptions,
+        env: &'env impl PackageCollection,
+        unity_version: Option<UnityVersion>,
+    ) -> Option<PackageInfo<'env>> {
+        let Some(priority) = options.requirement_priority_of(source) else {
+            return Some(found);
+        };
+        let Some(entry) = self.dependencies.get(dependency) else {
+            // no other requirements to compete with
+            return Some(found);
+        };
+        let acceptance = self
+            .prerelease_matching
+            .allow_or_minimum(self.allow_pre_of(dependency));
 
-                    if !conflicts_with_this.is_empty()
-                        && (info.using.is_some()
-                            || conflicts_with_this
-                                .iter()
-                                .any(|x| self.dependencies[*x].using.is_some()))
-                    {
-                        let vec = conflicts.entry(name.into()).or_default();
-                        for source in conflicts_with_this {
-                            vec.push(source.into())
-                        }
-                    }
-                }
-            }
-        }
+        let higher = entry
+            .requirements
+            .iter()
+            .filter(|&(&other, _)| other != source)
+            .filter(|&(&other, _)| {
+                self.dependencies
+                    .get(other)
+                    .map(|x| !x.is_legacy())
+                    .unwrap_or_default()
+            })
+            .filter(|&(&other, _)| {
+                (options.requirement_priority_of(other)).is_some_and(|other| other > priority)
+            })
+            .map(|(_, range)| range.as_ref())
+            .collect::<Vec<_>>();
 
-        let found_legacy_packages = self
-            .dependencies
+        if higher
             .iter()
-            .filter(|(_, info)| info.is_legacy())
-            .map(|(&name, _)| name.into())
-            .collect();
+            .all(|x| x.match_pre(found.version(), acceptance))
+        {
+            return Some(found);
+        }
 
-        let new_packages = self
-            .dependencies
-            .into_values()
-            .filter(|info| !info.is_legacy())
-            .filter_map(|x| x.using)
-            .collect();
+        // try to find a version satisfying both
+        let mut ranges = higher.clone();
+        ranges.push(range);
+        if let Some(found) = env.find_package_by_name(
+            dependency,
+            VersionSelector::ranges_for(unity_version, &ranges, acceptance)
+                .published_before(options.published_before)
+                .target_platform(options.target_platform)
+                .target_unity_versions(&options.target_unity_versions),
+        ) {
+            return Some(found);
+        }
 
-        PackageResolutionResult {
-            new_packages,
-            conflicts,
-            found_legacy_packages,
+        let keeping = (self.pending_queue.find_pending_package(dependency))
+            .map(|x| x.version())
+            .or(entry.current);
+        if let Some(keeping) = keeping {
+            if higher.iter().all(|x| x.match_pre(keeping, acceptance)) {
+                return None;
+            }
         }
+
+        Some(found)
     }
-}
 
-pub struct PackageResolutionResult<'env> {
-    pub new_packages: Vec<PackageInfo<'env>>,
-    // conflict dependency -> conflicting package[])
-    pub conflicts: HashMap<Box<str>, Vec<Box<str>>>,
-    // list of names of legacy packages we found
-    pub found_legacy_packages: Vec<Box<str>>,
-}
+    /// Asks the violation handler which version to choose if the found package violates
+    /// requirements from other packages.
+    ///
+    /// Returns None if the handler aborts the resolution.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn decide_violation(
+        &self,
+        dependency: &str,
+        source: &str,
+        range: &VersionRange,
+        found: PackageInfo<'env>,
+        options: &ResolutionOptions,
+        env: &'env impl PackageCollection,
+        unity_version: Option<UnityVersion>,
+    ) -> Option<PackageInfo<'env>> {
+        let Some(handler) = options.violation_handler else {
+            return Some(found);
+        };
+        let Some(entry) = self.dependencies.get(dependency) else {
+            return Some(found);
+        };
+        let acceptance = self
+            .prerelease_matching
+            .allow_or_minimum(self.allow_pre_of(dependency));
 
-pub struct MissingDependencies {
-    pub dependencies: HashSet<Box<str>>,
-}
+        let requirements = (entry.requirements.iter())
+            .filter(|&(&other, _)| other != source)
+            .filter(|&(&other, _)| {
+                self.dependencies
+                    .get(other)
+                    .map(|x| !x.is_legacy())
+                    .unwrap_or_default()
+            })
+            .map(|(&other, range)| (other, range.as_ref()))
+            .collect::<Vec<_>>();
 
-impl MissingDependencies {
-    pub fn new() -> Self {
-        Self {
-            dependencies: HashSet::new(),
+        let mut violated = (requirements.iter().copied())
+            .filter(|(_, range)| !range.match_pre(found.version(), acceptance))
+            .collect::<Vec<_>>();
+        if violated.is_empty() {
+            return Some(found);
         }
-    }
+        violated.sort_by_key(|&(other, _)| other);
 
-    pub fn is_empty(&self) -> bool {
-        self.dependencies.is_empty()
-    }
+        let selector = VersionSelector::latest_for(unity_version, true)
+            .published_before(options.published_before)
+            .target_platform(options.target_platform)
+            .target_unity_versions(&options.target_unity_versions);
+        let mut candidates = (env.find_packages(dependency))
+            .filter(|x| selector.satisfies_package(x))
+            .collect::<Vec<_>>();
+        candidates.sort_by(|a, b| b.version().cmp(a.version()));
+        let versions = candidates.iter().map(|x| x.version()).collect::<Vec<_>>();
+
+        let violation = ViolationContext {
+            package: source,
+            dependency,
+            range,
+            selecting: found.version(),
+            violated: &violated,
+            candidates: &versions,
+        };
+
+        match handler.on_violation(&violation) {
+            ViolationDecision::SelectNewest => Some(found),
+            ViolationDecision::SatisfyMost => {
+                let satisfied = |package: &PackageInfo| {
+                    usize::from(range.match_pre(package.version(), acceptance))
+                        + (requirements.iter())
+                            .filter(|(_, range)| range.match_pre(package.version(), acceptance))
+                            .count()
+                };
+                // the newest one is chosen among the versions satisfying the same number of requirements
+                let best = (candidates.iter().copied()).min_by_key(|x| Reverse(satisfied(x)));
+                Some(best.unwrap_or(found))
+            }
+            ViolationDecision::Select(version) => match env.fetch_package(dependency, &version) {
+                Some(selected) => Some(selected),
+                None => {
+                    log::debug!(
+                        "violation of {dependency}: selected version {version} not found, keeping {}",
+                        found.version()
+                    );
+                    Some(found)
+                }
+            },
+            ViolationDecision::Abort => None,
+        }
+    }
+
+    /// Tries to add recommended packages of resolved packages one by one.
+    ///
+    /// If adding a recommended package causes missing dependencies or new conflicts,
+    /// the addition is rolled back and the package is skipped.
+    pub(crate) fn add_recommended_packages(
+        &mut self,
+        env: &'env impl PackageCollection,
+        unity_version: Option<UnityVersion>,
+        options: &ResolutionOptions,
+    ) {
+        let mut queue = (self.dependencies.values())
+            .filter(|x| !x.is_legacy())
+            .filter_map(|x| x.using)
+            .flat_map(|x| x.recommended_packages())
+            .collect::<VecDeque<_>>();
+        let mut visited = HashSet::new();
+
+        while let Some((name, range)) = queue.pop_front() {
+            let name = name.as_ref();
+            if !visited.insert(name) {
+                continue;
+            }
+
+            if let Some(info) = self.dependencies.get(name) {
+                if info.is_legacy() || info.current.is_some() || info.using.is_some() {
+                    // already installed
+                    continue;
+                }
+            }
+            if self.unlocked_names.contains(name) || self.excluded_names.contains(name) {
+                continue;
+            }
+
+            let Some(package) = env.find_package_by_name(
+                name,
+                VersionSelector::range_for(
+                    unity_version,
+                    range,
+                    self.prerelease_matching
+                        .allow_or_minimum(self.allow_pre_of(name)),
+                )
+                .published_before(options.published_before)
+                .target_platform(options.target_platform)
+                .target_unity_versions(&options.target_unity_versions),
+            ) else {
+                log::debug!("recommended package {name}: not found");
+                self.skipped_recommendations.push(name);
+                continue;
+            };
+
+            let snapshot = self.clone();
+            let conflicts_before = self.collect_conflicts();
+            let mut missing_dependencies = MissingDependencies::new();
+
+            let dependencies = self.queued_dependencies(package);
+            self.pending_queue
+                .add_pending_package(package, dependencies);
+            process_pending_queue(self, env, unity_version, options, &mut missing_dependencies);
+
+            let new_conflict = self.collect_conflicts().iter().any(|(name, sources)| {
+                conflicts_before
+                    .get(name)
+                    .map(|before| sources.iter().any(|x| !before.contains(x)))
+                    .unwrap_or(true)
+            });
+
+            if missing_dependencies.is_empty() && !new_conflict {
+                log::debug!("recommended package {name}: added");
+                self.added_recommendations.push(name);
+                queue.extend(package.recommended_packages());
+            } else {
+                log::debug!("recommended package {name}: rolled back");
+                *self = snapshot;
+                self.skipped_recommendations.push(name);
+            }
+        }
+    }
+
+    fn approve_legacy_removal(
+        &mut self,
+        legacy: &'a str,
+        modern: &'a str,
+        options: &ResolutionOptions,
+    ) -> bool {
+        if self.vendored_names.contains(legacy) {
+            log::debug!("removing legacy package {legacy} for {modern}: vendored package");
+            return false;
+        }
+
+        // we only ask for packages actually installed or installing
+        let installed = (self.dependencies.get(legacy))
+            .map(|x| x.current.is_some() || x.using.is_some())
+            .unwrap_or(false);
+
+        if options.keep_legacy.contains(legacy) {
+            log::debug!("removing legacy package {legacy} for {modern}: kept by option");
+            if installed && !self.suppressed_removals.contains(&(legacy, modern)) {
+                self.suppressed_removals.push((legacy, modern));
+            }
+            return false;
+        }
+
+        let Some(approve_removal) = &options.approve_removal else {
+            return true;
+        };
+
+        if !installed {
+            return true;
+        }
+
+        if let Some(&approved) = self.removal_decisions.get(&(legacy, modern)) {
+            return approved;
+        }
+
+        let approved = (approve_removal.borrow_mut())(legacy, modern);
+        self.removal_decisions.insert((legacy, modern), approved);
+        if !approved {
+            log::debug!("removing legacy package {legacy} for {modern}: suppressed");
+            self.suppressed_removals.push((legacy, modern));
+        }
+        approved
+    }
+
+    /// Returns whether prerelease versions are allowed for the package.
+    fn allow_pre_of(&self, name: &str) -> bool {
+        if let Some(&allow) = self.prerelease_overrides.get(name) {
+            return allow;
+        }
+        (self.allow_prerelease && self.propagates_pre_to(name))
+            || (self.dependencies.get(name))
+                .is_some_and(|x| x.allow_pre || self.is_from_prerelease_repository(x))
+    }
+
+    /// Returns whether the package uses a prerelease version from the repository allowing prereleases.
+    fn is_from_prerelease_repository(&self, info: &DependencyInfo) -> bool {
+        (info.using).is_some_and(|using| {
+            using.version().is_pre()
+                && (using.repository_id())
+                    .is_some_and(|id| self.prerelease_repositories.contains(&id))
+        })
+    }
+
+    /// Returns whether the global or propagated prerelease allowance applies to the package.
+    ///
+    /// With [`PrereleasePropagation::RootOnly`], it applies only to the root dependencies
+    /// and the requested packages.
+    fn propagates_pre_to(&self, name: &str) -> bool {
+        match self.prerelease_propagation {
+            PrereleasePropagation::Propagate => true,
+            PrereleasePropagation::RootOnly => {
+                self.requested_names.contains(name)
+                    || (self.dependencies.get(""))
+                        .is_some_and(|x| x.requirements.contains_key(name))
+            }
+        }
+    }
+
+    /// Returns the cycle if the dependency is on the chain of packages adding the package.
+    ///
+    /// The returned path starts and ends with the dependency.
+    fn find_cycle(&self, package: &'env str, dependency: &'env str) -> Option<Vec<&'env str>> {
+        let mut path = vec![package];
+        let mut current = package;
+        while current != dependency {
+            current = self.parents.get(current)?;
+            if path.contains(&current) {
+                // the chain is looping without the dependency
+                return None;
+            }
+            path.push(current);
+        }
+        path.reverse();
+        path.push(dependency);
+        Some(path)
+    }
+
+    pub(crate) fn skip_reason(&self, name: &'a str, range: &VersionRange) -> Option<SkipReason> {
+        let Some(entry) = self.dependencies.get(name) else {
+            // nothing is known about the dependency, so it must be resolved
+            return None;
+        };
+
+        if entry.is_legacy() {
+            log::debug!(
+                "processing package {name}: dependency {name} version {range}: legacy package"
+            );
+            return Some(SkipReason::Legacy);
+        }
+
+        if self.unlocked_names.contains(name) {
+            log::debug!(
+                "processing package {name}: dependency {name} version {range}: unlocked package"
+            );
+            return Some(SkipReason::Unlocked);
+        }
+
+        if self.vendored_names.contains(name) {
+            log::debug!(
+                "processing package {name}: dependency {name} version {range}: vendored package"
+            );
+            return Some(SkipReason::Vendored);
+        }
+
+        if self.excluded_names.contains(name) {
+            log::debug!(
+                "processing package {name}: dependency {name} version {range}: excluded package"
+            );
+            return Some(SkipReason::Excluded);
+        }
+
+        let mut reason = None;
+        let allow_prerelease = self
+            .prerelease_matching
+            .allow_or_minimum(self.allow_pre_of(name));
+
+        if let Some(&pin) = self.pins.get(name) {
+            if !range.match_pre(pin, allow_prerelease) {
+                log::debug!(
+                    "processing package {name}: dependency {name} version {range}: pinned to {pin}"
+                );
+                return Some(SkipReason::Pinned(pin.clone()));
+            }
+        }
+
+        if let Some(pending) = self.pending_queue.find_pending_package(name) {
+            if range.match_pre(pending.version(), allow_prerelease) {
+                // if installing version is good, no need to reinstall
+                reason = Some(SkipReason::PendingMatches);
+                log::debug!(
+                    "processing package {name}: dependency {name} version {range}: pending matches"
+                );
+            }
+        } else {
+            // if already installed version is good, no need to reinstall
+            if let Some(version) = &entry.current {
+                if range.match_pre(version, allow_prerelease) {
+                    log::debug!(
+                        "processing package {name}: dependency {name} version {range}: existing matches"
+                    );
+                    reason = Some(SkipReason::ExistingMatches);
+                }
+            }
+        }
+
+        reason
+    }
+}
+
+impl<'env> ResolutionContext<'env, '_> {
+    /// Returns true if the version satisfies all the requirements on the package.
+    fn satisfies_requirements(&self, name: &str, version: &Version) -> bool {
+        let acceptance = self
+            .prerelease_matching
+            .allow_or_minimum(self.allow_pre_of(name));
+        (self.dependencies.get(name)).is_none_or(|info| {
+            (info.requirements.values()).all(|range| range.match_pre(version, acceptance))
+        })
+    }
+
+    fn collect_pin_violations(&self) -> Vec<PinViolation> {
+        let mut violations = Vec::new();
+        for (&name, &pin) in &self.pins {
+            let acceptance = self
+                .prerelease_matching
+                .allow_or_minimum(self.allow_pre_of(name));
+            let Some(info) = self.dependencies.get(name) else {
+                continue;
+            };
+            for (&source, range) in &info.requirements {
+                let is_legacy_source =
+                    (self.dependencies.get(source)).is_some_and(|x| x.is_legacy());
+                if !is_legacy_source && !range.match_pre(pin, acceptance) {
+                    violations.push(PinViolation {
+                        package: name.into(),
+                        pinned: pin.clone(),
+                        source: source.into(),
+                        range: range.as_ref().clone(),
+                    });
+                }
+            }
+        }
+
+        for (name, version) in &self.rejected_by_pins {
+            violations.push(PinViolation {
+                package: (*name).into(),
+                pinned: self.pins[name].clone(),
+                source: "".into(),
+                range: VersionRange::exact(version.clone()),
+            });
+        }
+
+        violations.sort_by(|a, b| (&a.package, &a.source).cmp(&(&b.package, &b.source)));
+        violations
+    }
+
+    fn collect_conflicts(&self) -> HashMap<Box<str>, Vec<ConflictSource>> {
+        let mut conflicts = HashMap::<Box<str>, Vec<ConflictSource>>::new();
+        for (&name, info) in &self.dependencies {
+            if self.excluded_names.contains(name) {
+                continue;
+            }
+            if !info.is_legacy() && info.touched {
+                if let Some(version) = &info.current {
+                    let conflicts_with_this = info
+                        .requirements
+                        .iter()
+                        .filter(|&(&source, _)| {
+                            self.dependencies
+                                .get(source)
+                                .map(|x| !x.is_legacy())
+                                .unwrap_or_default()
+                        })
+                        .filter(|(_, range)| {
+                            !range.match_pre(
+                                version,
+                                self.prerelease_matching
+                                    .allow_or_minimum(self.allow_pre_of(name)),
+                            )
+                        })
+                        .collect::<Vec<_>>();
+
+                    if !conflicts_with_this.is_empty()
+                        && (info.using.is_some()
+                            || conflicts_with_this
+                                .iter()
+                                .any(|(x, _)| self.dependencies[*x].using.is_some()))
+                    {
+                        let vec = conflicts.entry(name.into()).or_default();
+                        for (source, range) in conflicts_with_this {
+                            vec.push(ConflictSource {
+                                package: (*source).into(),
+                                range: range.as_ref().clone(),
+                                selected: (*version).clone(),
+                            })
+                        }
+                    }
+                }
+            }
+        }
+
+        for sources in conflicts.values_mut() {
+            sources.sort_by(|a, b| a.package.cmp(&b.package));
+        }
+
+        conflicts
+    }
+
+    pub(crate) fn build_result(
+        self,
+        env: &'env impl PackageCollection,
+        unity_version: Option<UnityVersion>,
+        options: &ResolutionOptions,
+    ) -> PackageResolutionResult<'env> {
+        let mut conflicts = self.collect_conflicts();
+
+        // pin violations are conflicts even if the pinned package is not installed
+        let (override_violations, pin_violations) = (self.collect_pin_violations().into_iter())
+            .partition::<Vec<_>, _>(|x| self.overridden_names.contains(x.package.as_ref()));
+        for violation in pin_violations.iter().chain(&override_violations) {
+            let sources = conflicts.entry(violation.package.clone()).or_default();
+            if !sources.iter().any(|x| x.package == violation.source) {
+                sources.push(ConflictSource {
+                    package: violation.source.clone(),
+                    range: violation.range.clone(),
+                    selected: violation.pinned.clone(),
+                });
+                sources.sort_by(|a, b| a.package.cmp(&b.package));
+            }
+        }
+
+        // a locked package is unused if no root, requested, unlocked, or vendored package
+        // requires it directly or transitively
+        let mut used = HashSet::<&str>::new();
+        let mut stack = (self.dependencies[""].requirements.keys().copied())
+            .chain(self.requested_names.iter().copied())
+            .chain(self.unlocked_names.iter().copied())
+            .chain(self.vendored_names.iter().copied())
+            .collect::<Vec<_>>();
+        while let Some(name) = stack.pop() {
+            if !used.insert(name) {
+                continue;
+            }
+            if let Some(info) = self.dependencies.get(name) {
+                if !info.is_legacy() {
+                    stack.extend(info.dependencies.iter().copied());
+                }
+            }
+        }
+        let mut unused_locked = (self.locked_names.iter())
+            .filter(|&&name| !used.contains(name))
+            .filter(|&&name| !self.user_managed_names.contains(name))
+            .filter(|&&name| !self.dependencies[name].is_legacy())
+            .map(|&name| name.into())
+            .collect::<Vec<_>>();
+        unused_locked.sort();
+
+        let mut downgrades = (self.locked_versions.iter())
+            .filter_map(|(&name, &locked)| {
+                let using = self.dependencies.get(name)?.using?;
+                (using.version() < locked)
+                    .then(|| (name.into(), locked.clone(), using.version().clone()))
+            })
+            .collect::<Vec<_>>();
+        downgrades.sort();
+
+        // lists are sorted by name to make the result stable for identical inputs
+        let mut found_legacy_packages = self
+            .dependencies
+            .iter()
+            .filter(|(_, info)| info.is_legacy())
+            .map(|(&name, _)| name.into())
+            .collect::<Vec<_>>();
+        found_legacy_packages.sort();
+
+        // the legacy packages claimed by multiple modern packages
+        let mut ambiguous_legacy_replacements = (self.dependencies.iter())
+            .filter(|(_, info)| info.modern_packages.len() > 1)
+            .map(|(&name, info)| {
+                let mut modern = (info.modern_packages.iter())
+                    .map(|&x| Box::<str>::from(x))
+                    .collect::<Vec<_>>();
+                modern.sort();
+                AmbiguousLegacyReplacement {
+                    legacy: name.into(),
+                    modern,
+                }
+            })
+            .collect::<Vec<_>>();
+        ambiguous_legacy_replacements.sort_by(|a, b| a.legacy.cmp(&b.legacy));
+
+        // a prerelease is stuck if no stable version satisfies all the requirements for the package
+        let mut stuck_on_prerelease = (self.dependencies.iter())
+            .filter(|_| options.detect_stuck_prereleases)
+            .filter(|(_, info)| !info.is_legacy())
+            .filter(|(name, _)| !self.unlocked_names.contains(*name))
+            .filter(|(_, info)| info.current.map(Version::is_pre).unwrap_or(false))
+            .filter(|&(&name, info)| {
+                let ranges = info
+                    .requirements
+                    .iter()
+                    .filter(|&(&source, _)| {
+                        self.dependencies
+                            .get(source)
+                            .map(|x| !x.is_legacy())
+                            .unwrap_or_default()
+                    })
+                    .map(|(_, range)| range.as_ref())
+                    .collect::<Vec<_>>();
+                // the unity version and the other filters of the options are not part of the
+                // requirements, so stable versions filtered by them still give an upgrade path
+                env.find_package_by_name(
+                    name,
+                    VersionSelector::ranges_for(None, &ranges, PrereleaseAcceptance::Deny),
+                )
+                .is_none()
+            })
+            .map(|(&name, _)| name.into())
+            .collect::<Vec<_>>();
+        stuck_on_prerelease.sort();
+
+        // a prerelease is pulled in transitively if neither the request nor the dependencies
+        // of the project asks for a prerelease of the package
+        let mut transitive_prereleases = self
+            .dependencies
+            .iter()
+            .filter(|(name, _)| !self.requested_names.contains(*name))
+            .filter_map(|(&name, info)| info.using.map(|using| (name, info, using.version())))
+            .filter(|(_, _, version)| version.is_pre())
+            .filter(|(_, info, _)| {
+                (info.requirements.get("")).is_none_or(|range| !range.contains_pre())
+            })
+            .map(|(name, _, version)| (name.into(), version.clone()))
+            .collect::<Vec<_>>();
+        transitive_prereleases.sort();
+
+        // the choice is arbitrary if the collection has multiple candidates with the same version
+        // since which one is chosen depends on the collection
+        let mut arbitrary_choices = (self.dependencies.iter())
+            .filter(|_| options.detect_arbitrary_choices)
+            .filter(|(_, info)| !info.is_legacy())
+            .filter(|(name, _)| !self.requested_names.contains(*name))
+            .filter_map(|(&name, info)| info.using.map(|using| (name, using)))
+            .filter(|&(name, using)| {
+                env.find_packages(name)
+                    .filter(|x| x.version() == using.version())
+                    .count()
+                    > 1
+            })
+            .map(|(name, _)| name.into())
+            .collect::<Vec<_>>();
+        arbitrary_choices.sort();
+
+        let mut dependency_edges = Vec::new();
+        for (&name, info) in &self.dependencies {
+            if name.is_empty() || info.is_legacy() {
+                continue;
+            }
+            let version = info.using.map(|x| x.version()).or(info.current);
+            let acceptance = self
+                .prerelease_matching
+                .allow_or_minimum(self.allow_pre_of(name));
+            for (&source, range) in &info.requirements {
+                if (self.dependencies.get(source)).is_none_or(|x| x.is_legacy()) {
+                    continue;
+                }
+                // excluded packages are treated as already satisfied
+                let satisfied = self.excluded_names.contains(name)
+                    || version.is_some_and(|version| range.match_pre(version, acceptance));
+                dependency_edges.push(DependencyEdge {
+                    from: source.into(),
+                    to: name.into(),
+                    range: range.as_ref().clone(),
+                    satisfied,
+                });
+            }
+        }
+        dependency_edges.sort_by(|a, b| (&a.from, &a.to).cmp(&(&b.from, &b.to)));
+
+        let mut requirements = HashMap::new();
+        let mut resolution_trace = HashMap::new();
+        let mut selected_versions = HashMap::new();
+        let mut slack = HashMap::new();
+        for (&name, info) in &self.dependencies {
+            if name.is_empty() || info.is_legacy() {
+                continue;
+            }
+            let Some(version) = info.using.map(|x| x.version()).or(info.current) else {
+                continue;
+            };
+
+            let requirements_of_this = (info.requirements.iter())
+                .filter(|&(&source, _)| {
+                    self.dependencies
+                        .get(source)
+                        .map(|x| !x.is_legacy())
+                        .unwrap_or_default()
+                })
+                .map(|(&source, range)| (Box::<str>::from(source), range.as_ref().clone()))
+                .collect::<Vec<_>>();
+
+            if options.collect_slack {
+                // the unused portion is considered to exist if it contains published or next versions
+                let mut probes = env
+                    .find_packages(name)
+                    .map(|x| x.version().clone())
+                    .filter(|x| x > version)
+                    .collect::<Vec<_>>();
+                probes.push(Version::new(
+                    version.major,
+                    version.minor,
+                    version.patch + 1,
+                ));
+                probes.push(Version::new(version.major, version.minor + 1, 0));
+                probes.push(Version::new(version.major + 1, 0, 0));
+
+                let slack_of_this = (requirements_of_this.iter())
+                    .map(|(source, range)| (source.clone(), range.above(version)))
+                    .filter(|(_, unused)| {
+                        (probes.iter()).any(|x| unused.match_pre(x, PrereleaseAcceptance::Allow))
+                    })
+                    .collect::<Vec<_>>();
+                slack.insert(name.into(), slack_of_this);
+            }
+
+            if options.diagnostics {
+                let prerelease_reason = if !self.allow_pre_of(name) {
+                    None
+                } else if self.prerelease_overrides.contains_key(name) {
+                    Some(PrereleaseReason::Overridden)
+                } else if self.allow_prerelease {
+                    Some(PrereleaseReason::Global)
+                } else if self.requested_names.contains(name) {
+                    Some(PrereleaseReason::Requested)
+                } else if (info.requirements.values()).any(|x| x.contains_pre()) {
+                    Some(PrereleaseReason::PrereleaseRequirement)
+                } else if self.is_from_prerelease_repository(info) {
+                    Some(PrereleaseReason::Repository)
+                } else {
+                    Some(PrereleaseReason::InstalledPrerelease)
+                };
+                let reason = ResolutionReason {
+                    version: version.clone(),
+                    requirements: requirements_of_this.clone(),
+                    prerelease_reason,
+                    repository: (info.using).and_then(|x| x.repository_id()).map(Into::into),
+                    binding_requirement: info
+                        .binding_requirement(version, |source| {
+                            (self.dependencies.get(source)).is_some_and(|x| !x.is_legacy())
+                        })
+                        .map(Into::into),
+                };
+                resolution_trace.insert(name.into(), reason);
+            }
+
+            requirements.insert(name.into(), requirements_of_this);
+            selected_versions.insert(name.into(), version.clone());
+        }
+
+        let mut licenses = HashMap::new();
+        let mut disallowed_licenses = Vec::new();
+        if let Some(policy) = &options.license_policy {
+            for (&name, info) in &self.dependencies {
+                if info.is_legacy() {
+                    continue;
+                }
+                let Some(using) = info.using else { continue };
+                if !policy.is_allowed(using.license()) {
+                    disallowed_licenses.push(name.into());
+                }
+                licenses.insert(name.into(), using.license().map(Into::into));
+            }
+        }
+        disallowed_licenses.sort();
+
+        let legacy_replacements = (self.dependencies.iter())
+            .filter(|(_, info)| info.is_legacy())
+            .map(|(&name, info)| (name.into(), info.replaced_by()))
+            .collect();
+
+        let diagnostics = (options.diagnostics).then(|| self.build_diagnostics(resolution_trace));
+
+        // locked versions are kept even if yanked, so existing projects don't break
+        let mut kept_yanked = (self.dependencies.iter())
+            .filter(|_| options.detect_kept_yanked)
+            .filter(|&(&name, info)| !name.is_empty() && !info.is_legacy() && info.using.is_none())
+            .filter_map(|(&name, info)| {
+                let current = info.current?;
+                let mut same_version = (env.find_packages(name))
+                    .filter(|x| x.version() == current)
+                    .peekable();
+                if same_version.peek().is_none() || !same_version.all(|x| x.is_yanked()) {
+                    return None;
+                }
+                log::warn!("keeping locked {name}@{current} which is yanked");
+                Some((Box::<str>::from(name), current.clone()))
+            })
+            .collect::<Vec<_>>();
+        kept_yanked.sort();
+
+        let mut new_packages = self
+            .dependencies
+            .into_values()
+            .filter(|info| !info.is_legacy())
+            .filter_map(|x| x.using)
+            .collect::<Vec<_>>();
+        new_packages.sort_by_key(|x| x.name());
+
+        let deprecated_selected = (new_packages.iter())
+            .filter(|x| x.is_deprecated())
+            .map(|x| {
+                let message = x.deprecation_message().unwrap_or_default();
+                (x.name().into(), x.version().clone(), message.into())
+            })
+            .collect();
+
+        let sacrificed_requirements = self
+            .sacrificed_requirements
+            .into_iter()
+            .map(|(dependency, source)| (dependency.into(), source.into()))
+            .collect();
+
+        let unsatisfiable_requirements = (self.unsatisfiable_requirements.into_iter())
+            .map(|(dependency, source, range)| (dependency.into(), source.into(), range))
+            .collect();
+
+        let suppressed_removals = (self.suppressed_removals.into_iter())
+            .map(|(legacy, modern)| (legacy.into(), modern.into()))
+            .collect();
+
+        let dependency_cycles = (self.dependency_cycles.into_iter())
+            .map(|path| path.into_iter().map(Into::into).collect())
+            .collect();
+
+        let unity_gated = (self.unity_gated.into_iter())
+            .map(|(package, required)| (package.into(), required))
+            .collect();
+
+        let limit_exceeded = self.limit_exceeded.then(|| {
+            (self.recently_processed.into_iter())
+                .map(Into::into)
+                .collect()
+        });
+
+        let aborted_at = self.aborted_at.map(Into::into);
+
+        let mut missing_source_repositories = self.missing_source_repositories;
+        missing_source_repositories.sort_by(|a, b| a.package.cmp(&b.package));
+
+        let mut lockfile_drift = self.lockfile_drift;
+        lockfile_drift.sort_by(|a, b| a.package.cmp(&b.package));
+
+        let stats = self.stats;
+
+        let mut platform_incompatible = (self.platform_incompatible.into_iter())
+            .map(Box::<str>::from)
+            .collect::<Vec<_>>();
+        platform_incompatible.sort();
+        platform_incompatible.dedup();
+
+        let mut target_unity_incompatible = (self.target_unity_incompatible.into_iter())
+            .map(Box::<str>::from)
+            .collect::<Vec<_>>();
+        target_unity_incompatible.sort();
+        target_unity_incompatible.dedup();
+
+        let mut all_candidates_yanked = (self.all_candidates_yanked.into_iter())
+            .map(Box::<str>::from)
+            .collect::<Vec<_>>();
+        all_candidates_yanked.sort();
+        all_candidates_yanked.dedup();
+
+        let mut locked_dependency_mismatches = self.locked_dependency_mismatches;
+        locked_dependency_mismatches.sort_by(|a, b| a.package.cmp(&b.package));
+
+        let naming_tiebreaks = (self.naming_tiebreaks.into_iter())
+            .map(|(package, id, installed)| (package.into(), id, installed))
+            .collect();
+
+        let rewritten_requirements = (self.rewritten_requirements.into_iter())
+            .map(|(dependency, source, range)| (dependency.into(), source.into(), range))
+            .collect();
+
+        let added_recommendations = (self.added_recommendations.into_iter())
+            .map(Into::into)
+            .collect();
+        let skipped_recommendations = (self.skipped_recommendations.into_iter())
+            .map(Into::into)
+            .collect();
+        let activated_optionals = (self.activated_optionals.into_iter())
+            .map(|(package, dependency)| (package.into(), dependency.into()))
+            .collect();
+
+        let mut active_features = (new_packages.iter())
+            .flat_map(|package| {
+                (package.package_json().features())
+                    .filter(|feature| self.enabled_features.contains(feature))
+                    .map(move |feature| (package.name().into(), feature.into()))
+            })
+            .collect::<Vec<_>>();
+        active_features.sort();
+
+        PackageResolutionResult {
+            new_packages,
+            details: ResolutionDetails {
+                conflicts,
+                found_legacy_packages,
+                ambiguous_legacy_replacements,
+                stuck_on_prerelease,
+                transitive_prereleases,
+                arbitrary_choices,
+                sacrificed_requirements,
+                added_recommendations,
+                skipped_recommendations,
+                unsatisfiable_requirements,
+                licenses,
+                disallowed_licenses,
+                requirements,
+                selected_versions,
+                slack,
+                suppressed_removals,
+                legacy_replacements,
+                rewritten_requirements,
+                naming_tiebreaks,
+                dependency_cycles,
+                unused_locked,
+                unity_gated,
+                lockfile_drift,
+                locked_dependency_mismatches,
+                stats,
+                platform_incompatible,
+                target_unity_incompatible,
+                all_candidates_yanked,
+                dependency_edges,
+                limit_exceeded,
+                aborted_at,
+                missing_source_repositories,
+                downgrades,
+                pin_violations,
+                override_violations,
+                deprecated_selected,
+                kept_yanked,
+                activated_optionals,
+                active_features,
+                diagnostics,
+                unity_version,
+                arbitrary_choices_detected: options.detect_arbitrary_choices,
+            },
+        }
+    }
+
+    /// Builds the diagnostics of the result with the trace collected while building the result.
+    fn build_diagnostics(
+        &self,
+        trace: HashMap<Box<str>, ResolutionReason>,
+    ) -> ResolutionDiagnostics {
+        let prerelease_acceptance = (self.dependencies.keys())
+            .map(|&name| {
+                let acceptance =
+                    (self.prerelease_matching).allow_or_minimum(self.allow_pre_of(name));
+                (name.into(), acceptance)
+            })
+            .collect();
+
+        let mut absence = HashMap::new();
+        for (&name, info) in &self.dependencies {
+            if name.is_empty() {
+                continue;
+            }
+            let reason = if self.excluded_names.contains(name) {
+                AbsenceReason::Excluded
+            } else if self.provided_names.contains(name) {
+                // provided packages are added with the version, so this never skips the package
+                let Some(version) = info.current else {
+                    continue;
+                };
+                AbsenceReason::Provided {
+                    version: version.clone(),
+                }
+            } else if info.is_legacy() {
+                AbsenceReason::Legacy {
+                    replaced_by: info.replaced_by(),
+                }
+            } else if info.using.is_some() {
+                AbsenceReason::Included
+            } else if let Some(current) = info.current {
+                AbsenceReason::Existing {
+                    version: current.clone(),
+                }
+            } else if !info.requirements.is_empty() {
+                AbsenceReason::NoMatchingVersion
+            } else {
+                AbsenceReason::NotRequired
+            };
+            absence.insert(name.into(), reason);
+        }
+
+        let mut origins = HashMap::new();
+        for (&name, info) in &self.dependencies {
+            if name.is_empty()
+                || info.is_legacy()
+                || self.excluded_names.contains(name)
+                || self.provided_names.contains(name)
+            {
+                continue;
+            }
+            let origin = match (info.using, self.locked_versions.get(name)) {
+                (Some(_), None) => PackageOrigin::Added,
+                (Some(using), Some(&locked)) if using.version() != locked => {
+                    PackageOrigin::Changed {
+                        locked: locked.clone(),
+                    }
+                }
+                (_, Some(_)) => PackageOrigin::Locked,
+                (None, None) => continue,
+            };
+            origins.insert(name.into(), origin);
+        }
+
+        ResolutionDiagnostics {
+            trace,
+            origins,
+            absence,
+            prerelease_acceptance,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PackageResolutionResult<'env> {
+    pub new_packages: Vec<PackageInfo<'env>>,
+    // the details not borrowing the collection, which are available through `Deref`
+    details: ResolutionDetails,
+}
+
+/// The details of [`PackageResolutionResult`] other than the resolved packages.
+///
+/// Since the details don't borrow the package collection, they are kept as is when the result is
+/// reused with another collection.
+#[derive(Debug, Clone)]
+pub struct ResolutionDetails {
+    // conflict dependency -> requirements not satisfied by the selected version
+    pub conflicts: HashMap<Box<str>, Vec<ConflictSource>>,
+    // list of names of legacy packages we found
+    pub found_legacy_packages: Vec<Box<str>>,
+    // legacy packages replaced by more than one modern package, sorted by the legacy package.
+    // the legacy packages are removed anyway, so check this if the replacement matters
+    pub ambiguous_legacy_replacements: Vec<AmbiguousLegacyReplacement>,
+    // list of names of packages using prerelease version without stable version satisfies requirements.
+    // collected only if detecting stuck prereleases is enabled
+    pub stuck_on_prerelease: Vec<Box<str>>,
+    // (package, version) of prerelease versions selected without prerelease requested at the root
+    pub transitive_prereleases: Vec<(Box<str>, Version)>,
+    // list of names of packages chosen arbitrarily among candidates with the same version.
+    // collected only if detecting arbitrary choices is enabled
+    pub arbitrary_choices: Vec<Box<str>>,
+    // (dependency, source) of lower-priority requirements sacrificed for higher-priority ones
+    pub sacrificed_requirements: Vec<(Box<str>, Box<str>)>,
+    // list of names of recommended packages added
+    pub added_recommendations: Vec<Box<str>>,
+    // list of names of recommended packages skipped since not found or conflicts
+    pub skipped_recommendations: Vec<Box<str>>,
+    // (dependency, source, range) of requirements no published version satisfies
+    pub unsatisfiable_requirements: Vec<(Box<str>, Box<str>, VersionRange)>,
+    // package name -> declared license. collected only if the license policy is set
+    pub licenses: HashMap<Box<str>, Option<Box<str>>>,
+    // list of names of packages with missing or disallowed license
+    pub disallowed_licenses: Vec<Box<str>>,
+    // package name -> (source, range) of requirements from non-legacy packages
+    pub requirements: HashMap<Box<str>, Vec<(Box<str>, VersionRange)>>,
+    // package name -> selected version of packages in requirements
+    selected_versions: HashMap<Box<str>, Version>,
+    // package name -> (source, unused portion of range). collected only if slack is enabled
+    slack: HashMap<Box<str>, Vec<(Box<str>, VersionRange)>>,
+    // (legacy, modern) of legacy package removals suppressed by the callback
+    pub suppressed_removals: Vec<(Box<str>, Box<str>)>,
+    // legacy package -> modern packages replacing it, sorted
+    legacy_replacements: HashMap<Box<str>, Vec<Box<str>>>,
+    // (dependency, source, rewritten range) of requirements rewritten by the range rewriter
+    pub rewritten_requirements: Vec<(Box<str>, Box<str>, VersionRange)>,
+    // (package, repository id, installed package sharing the name) of choices by the naming convention
+    pub naming_tiebreaks: Vec<(Box<str>, Box<str>, Box<str>)>,
+    // the package names forming dependency cycles, starting and ending with the same package
+    pub dependency_cycles: Vec<Vec<Box<str>>>,
+    // list of names of locked packages no longer required by any package
+    pub unused_locked: Vec<Box<str>>,
+    // (package, minimum unity) of packages whose versions all require newer unity than the project
+    pub unity_gated: Vec<(Box<str>, UnityVersion)>,
+    // locked packages whose dependencies on disk differ from the lockfile. collected only if
+    // the on-disk dependencies callback is set
+    pub lockfile_drift: Vec<LockfileDrift>,
+    // locked packages whose dependencies differ from the same version in the collection, which
+    // means the version is re-published after locked. the recorded dependencies are used
+    pub locked_dependency_mismatches: Vec<LockedDependencyMismatch>,
+    // the counters of the work done by the resolution. collected only if stats are enabled
+    pub stats: Option<ResolutionStats>,
+    // names of dependencies not found since all the versions in the ranges declare not to work on
+    // the target platform. collected only if the target platform is set
+    pub platform_incompatible: Vec<Box<str>>,
+    // names of dependencies not found since no version in the ranges is compatible with all the
+    // target unity versions. collected only if the target unity versions are set
+    pub target_unity_incompatible: Vec<Box<str>>,
+    // names of dependencies not found since all the versions in the ranges are yanked
+    pub all_candidates_yanked: Vec<Box<str>>,
+    // every requirement of the resolution as the edges of the dependency graph, sorted by (from, to)
+    pub dependency_edges: Vec<DependencyEdge>,
+    // the names of the packages processed last, if the resolution stopped at the iteration limit
+    pub limit_exceeded: Option<Vec<Box<str>>>,
+    // the dependency the violation handler aborted the resolution at. None if not aborted
+    pub aborted_at: Option<Box<str>>,
+    // locked packages not found since the repository recorded in the lockfile is not in the collection
+    pub missing_source_repositories: Vec<MissingSourceRepository>,
+    // (package, locked version, new version) of locked packages replaced with older versions
+    pub downgrades: Vec<(Box<str>, Version, Version)>,
+    // requirements not satisfied by the pinned versions. those are also reported in conflicts
+    pub pin_violations: Vec<PinViolation>,
+    // requirements not satisfied by the overridden versions. those are also reported in conflicts
+    // but never fail the resolution
+    pub override_violations: Vec<PinViolation>,
+    // (package, version, message) of deprecated versions selected since no other version satisfies
+    // the requirements. the message is empty if the version is deprecated without the message
+    pub deprecated_selected: Vec<(Box<str>, Version, Box<str>)>,
+    // (package, version) of locked versions kept even though yanked in the collection, sorted.
+    // collected only if detecting kept yanked versions is enabled
+    pub kept_yanked: Vec<(Box<str>, Version)>,
+    // (package, dependency) of optional dependencies activated since the dependency is
+    // already installed or being installed
+    pub activated_optionals: Vec<(Box<str>, Box<str>)>,
+    // (package, feature) of enabled features declared by the resolved packages, sorted
+    pub active_features: Vec<(Box<str>, Box<str>)>,
+    // the diagnostics of the resolution. collected only if diagnostics are enabled
+    pub diagnostics: Option<ResolutionDiagnostics>,
+    // the unity version the resolution is for
+    unity_version: Option<UnityVersion>,
+    // whether arbitrary choices are detected. if not, `arbitrary_choices` is always empty
+    arbitrary_choices_detected: bool,
+}
+
+/// The diagnostics of the resolution, which are not needed to apply the changes.
+///
+/// These are collected only if [`ResolutionOptions::diagnostics`] is enabled.
+#[derive(Debug, Clone)]
+pub struct ResolutionDiagnostics {
+    // package name -> why the version is chosen
+    pub trace: HashMap<Box<str>, ResolutionReason>,
+    // package name -> whether the resolved version is carried over from the lockfile, newly added,
+    // or changed. locked packages not in new_packages are included as carried over
+    pub origins: HashMap<Box<str>, PackageOrigin>,
+    // package name -> why the package is (not) in new_packages
+    absence: HashMap<Box<str>, AbsenceReason>,
+    // package name -> prerelease acceptance the requirements are matched with
+    prerelease_acceptance: HashMap<Box<str>, PrereleaseAcceptance>,
+}
+
+impl Deref for PackageResolutionResult<'_> {
+    type Target = ResolutionDetails;
+
+    fn deref(&self) -> &Self::Target {
+        &self.details
+    }
+}
+
+impl DerefMut for PackageResolutionResult<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.details
+    }
+}
+
+impl<'env> PackageResolutionResult<'env> {
+    /// Returns the result with `new_packages` replaced with the packages.
+    pub(crate) fn with_packages<'b>(
+        self,
+        new_packages: Vec<PackageInfo<'b>>,
+    ) -> PackageResolutionResult<'b> {
+        PackageResolutionResult {
+            new_packages,
+            details: self.details,
+        }
+    }
+
+    /// Returns the details of the result, dropping the resolved packages.
+    pub fn into_details(self) -> ResolutionDetails {
+        self.details
+    }
+
+    /// Returns the map from the conflicting dependency to the names of the conflicting packages.
+    #[deprecated(note = "use `conflicts` which contains the ranges and the selected version")]
+    pub fn conflicting_package_names(&self) -> HashMap<Box<str>, Vec<Box<str>>> {
+        (self.conflicts.iter())
+            .map(|(name, sources)| {
+                let packages = sources.iter().map(|x| x.package.clone()).collect();
+                (name.clone(), packages)
+            })
+            .collect()
+    }
+
+    /// Summarizes the changes from the locked packages.
+    ///
+    /// Only legacy packages currently locked are counted as removed.
+    pub fn summary<'a>(
+        &self,
+        get_locked: impl Fn(&str) -> Option<LockedDependencyInfo<'a>>,
+    ) -> ResolutionSummary {
+        let mut summary = ResolutionSummary::default();
+
+        for package in &self.new_packages {
+            let name = package.name().into();
+            match get_locked(package.name()) {
+                None => summary.added.push(name),
+                Some(locked) => match locked.version().cmp(package.version()) {
+                    std::cmp::Ordering::Less => summary.updated.push(name),
+                    std::cmp::Ordering::Equal => summary.unchanged.push(name),
+                    std::cmp::Ordering::Greater => summary.downgraded.push(name),
+                },
+            }
+        }
+
+        for name in &self.found_legacy_packages {
+            if get_locked(name).is_some() {
+                summary.removed.push(name.clone());
+            }
+        }
+
+        summary.added.sort();
+        summary.updated.sort();
+        summary.downgraded.sort();
+        summary.removed.sort();
+        summary.unchanged.sort();
+
+        summary
+    }
+
+    /// Compares the result with the locked packages, for previewing the changes before applying.
+    ///
+    /// The packages are classified as [`Self::summary`] does, with the versions.
+    /// This also reports the conflicts not in the locked packages and the locked packages
+    /// no longer required after the resolution.
+    pub fn diff_against_locked(&self, locked: &[LockedDependencyInfo]) -> ResolutionDiff {
+        let locked_map = (locked.iter())
+            .map(|x| (x.name(), x))
+            .collect::<HashMap<_, _>>();
+        let new_versions = (self.new_packages.iter())
+            .map(|x| (x.name(), x.version()))
+            .collect::<HashMap<_, _>>();
+        let summary = self.summary(|name| locked_map.get(name).map(|x| (*x).clone()));
+        let changed = |names: Vec<Box<str>>| {
+            (names.into_iter())
+                .map(|name| {
+                    let locked = locked_map[name.as_ref()].version().clone();
+                    let version = new_versions[name.as_ref()].clone();
+                    (name, locked, version)
+                })
+                .collect()
+        };
+
+        let mut diff = ResolutionDiff {
+            added: (summary.added.into_iter())
+                .map(|name| {
+                    let version = new_versions[name.as_ref()].clone();
+                    (name, version)
+                })
+                .collect(),
+            removed: summary.removed,
+            upgraded: changed(summary.updated),
+            downgraded: changed(summary.downgraded),
+            ..ResolutionDiff::default()
+        };
+
+        // the dependencies already conflicting in the locked packages
+        let locked_conflicts = (locked.iter())
+            .flat_map(|x| x.dependencies())
+            .filter(|(dependency, range)| {
+                (locked_map.get(dependency.as_ref()))
+                    .is_some_and(|x| !range.match_pre(x.version(), PrereleaseAcceptance::Allow))
+            })
+            .map(|(dependency, _)| dependency.as_ref())
+            .collect::<HashSet<_>>();
+        diff.new_conflicts = (self.conflicts.keys())
+            .filter(|x| !locked_conflicts.contains(x.as_ref()))
+            .cloned()
+            .collect();
+
+        // the locked packages required by the locked packages still in use
+        let mut used = HashSet::<&str>::new();
+        let mut stack = (locked.iter())
+            .map(|x| x.name())
+            .filter(|x| {
+                !self
+                    .unused_locked
+                    .iter()
+                    .any(|unused| unused.as_ref() == *x)
+            })
+            .collect::<Vec<_>>();
+        while let Some(name) = stack.pop() {
+            if !used.insert(name) {
+                continue;
+            }
+            if let Some(locked) = locked_map.get(name) {
+                stack.extend(locked.dependencies().keys().map(AsRef::as_ref));
+            }
+        }
+        diff.orphaned = (self.unused_locked.iter())
+            .filter(|x| used.contains(x.as_ref()))
+            .cloned()
+            .collect();
+
+        diff.new_conflicts.sort();
+        diff.orphaned.sort();
+
+        diff
+    }
+
+    /// Returns the map from the name to the version of the resolved packages.
+    ///
+    /// Legacy packages removed by the resolution are not included.
+    pub fn version_map(&self) -> HashMap<Box<str>, Version> {
+        (self.new_packages.iter())
+            .map(|x| (x.name().into(), x.version().clone()))
+            .collect()
+    }
+
+    /// Returns the map from the name to the id of the repository each package is chosen from.
+    ///
+    /// The value will be None if the package is not from a remote repository or the repository has no id.
+    pub fn package_sources(&self) -> HashMap<Box<str>, Option<Box<str>>> {
+        (self.new_packages.iter())
+            .map(|x| {
+                (
+                    x.name().into(),
+                    x.repo().and_then(|r| r.id()).map(Into::into),
+                )
+            })
+            .collect()
+    }
+
+    /// Returns the portion of each requirement range above the selected version of the package.
+    ///
+    /// Requirements using all the range up to the selected version are not included.
+    /// This is useful to find ranges looser than needed.
+    /// This is empty unless [`ResolutionOptions::collect_slack`] is enabled.
+    pub fn slack(&self, name: &str) -> Vec<(Box<str>, VersionRange)> {
+        self.slack.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Returns the map from the name to the dependencies of the resolved packages.
+    ///
+    /// Legacy packages removed by the resolution are not included.
+    pub fn dependency_map(&self) -> HashMap<Box<str>, IndexMap<Box<str>, VersionRange>> {
+        (self.new_packages.iter())
+            .map(|x| (x.name().into(), x.vpm_dependencies().clone()))
+            .collect()
+    }
+
+    /// Returns whether the resolution made no arbitrary choice.
+    ///
+    /// If this returns false, resolving again with the same inputs may select different packages.
+    /// See `arbitrary_choices` for the packages chosen arbitrarily.
+    ///
+    /// This returns `None` unless [`ResolutionOptions::detect_arbitrary_choices`] is enabled
+    /// since the arbitrary choices are not looked up.
+    pub fn is_reproducible(&self) -> Option<bool> {
+        (self.arbitrary_choices_detected).then(|| self.arbitrary_choices.is_empty())
+    }
+
+    /// Returns the matrix of the requirements and whether the selected versions satisfy them.
+    ///
+    /// Rows are sorted by the package name and cells are sorted by the source.
+    /// The source of the root dependencies is `""`.
+    /// Prerelease versions are matched with the prerelease acceptance of the package in the
+    /// resolution, so the cells agree with `conflicts`. The acceptance is known only if
+    /// [`ResolutionOptions::diagnostics`] is enabled, and prerelease versions are not accepted otherwise.
+    pub fn satisfaction_matrix(&self) -> SatisfactionMatrix {
+        let mut rows = (self.requirements.iter())
+            .filter_map(|(name, requirements)| {
+                let version = self.selected_versions.get(name)?;
+                let acceptance = self.prerelease_acceptance_of(name);
+                let mut cells = (requirements.iter())
+                    .map(|(source, range)| SatisfactionCell {
+                        source: source.clone(),
+                        range: range.clone(),
+                        satisfied: range.match_pre(version, acceptance),
+                    })
+                    .collect::<Vec<_>>();
+                cells.sort_by(|a, b| a.source.cmp(&b.source));
+                Some(SatisfactionRow {
+                    package: name.clone(),
+                    version: version.clone(),
+                    cells,
+                })
+            })
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| a.package.cmp(&b.package));
+        SatisfactionMatrix { rows }
+    }
+
+    /// Returns the prerelease acceptance the requirements of the package are matched with.
+    ///
+    /// This is known only with the diagnostics, so prerelease versions are not accepted without them.
+    fn prerelease_acceptance_of(&self, name: &str) -> PrereleaseAcceptance {
+        (self.diagnostics.as_ref())
+            .and_then(|x| x.prerelease_acceptance.get(name).copied())
+            .unwrap_or(PrereleaseAcceptance::Deny)
+    }
+
+    /// Returns why the package is not in `new_packages`.
+    ///
+    /// Unless [`ResolutionOptions::diagnostics`] is enabled, only legacy packages and skipped
+    /// recommendations are explained, and other packages are reported as not required.
+    pub fn explain_absence(&self, name: &str) -> AbsenceReason {
+        if let Some(reason) = (self.diagnostics.as_ref()).and_then(|x| x.absence.get(name)) {
+            return reason.clone();
+        }
+        if let Some(replaced_by) = self.legacy_replacements.get(name) {
+            return AbsenceReason::Legacy {
+                replaced_by: replaced_by.clone(),
+            };
+        }
+        if self
+            .skipped_recommendations
+            .iter()
+            .any(|x| x.as_ref() == name)
+        {
+            AbsenceReason::RecommendationSkipped
+        } else {
+            AbsenceReason::NotRequired
+        }
+    }
+
+    /// Returns why the version of the package is not selected.
+    ///
+    /// The version is checked against the collection, the requirements, the prerelease policy,
+    /// and the unity version of the resolution, and all reasons disqualifying the version are returned.
+    /// If nothing disqualifies the version, [`RejectionReason::OtherSelected`] or
+    /// [`RejectionReason::NotRequired`] is returned.
+    /// Returns an empty list if the version is selected.
+    pub fn explain_rejection(
+        &self,
+        env: &impl PackageCollection,
+        name: &str,
+        version: &Version,
+    ) -> Vec<RejectionReason> {
+        let selected = self.selected_versions.get(name);
+        if selected == Some(version) {
+            return Vec::new();
+        }
+
+        let Some(package) = env.fetch_package(name, version) else {
+            return vec![RejectionReason::NotFound];
+        };
+
+        let mut reasons = Vec::new();
+        if package.package_json().is_yanked() {
+            reasons.push(RejectionReason::Yanked);
+        }
+
+        let requirements = self
+            .requirements
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut prerelease_excluded = false;
+        for (source, range) in requirements {
+            if !range.match_pre(version, PrereleaseAcceptance::Allow) {
+                reasons.push(RejectionReason::Requirement {
+                    source: source.clone(),
+                    range: range.clone(),
+                });
+            } else if !version.pre.is_empty() {
+                let acceptance = self.prerelease_acceptance_of(name);
+                prerelease_excluded |= !range.match_pre(version, acceptance);
+            }
+        }
+        if prerelease_excluded {
+            reasons.push(RejectionReason::PrereleaseNotAllowed);
+        }
+
+        if let Some(unity) = self.unity_version {
+            if let Some(excluded) = unity_exclusion(package.package_json(), unity) {
+                reasons.push(RejectionReason::UnityExcluded {
+                    excluded: *excluded,
+                });
+            } else if !unity_compatible(package.package_json(), unity) {
+                reasons.push(RejectionReason::UnityIncompatible {
+                    unity: unity_requirement(package.package_json()),
+                });
+            }
+        }
+
+        if reasons.is_empty() {
+            reasons.push(match selected {
+                Some(selected) => RejectionReason::OtherSelected {
+                    version: selected.clone(),
+                },
+                None => RejectionReason::NotRequired,
+            });
+        }
+        reasons
+    }
+
+    /// Explains why the selected version of the package is the maximum available one.
+    ///
+    /// The requirements rejecting the newest version in the collection are the caps of the
+    /// version, and each cap is explained recursively with why its source is at the version.
+    /// Yanked versions are not considered, and prerelease versions are considered only if
+    /// the selected version is a prerelease.
+    /// Returns None if the package is not selected by the resolution.
+    pub fn explain_holdback(
+        &self,
+        env: &impl PackageCollection,
+        name: &str,
+    ) -> Option<HoldbackExplanation> {
+        self.explain_holdback_inner(env, name, &mut HashSet::new())
+    }
+
+    fn explain_holdback_inner(
+        &self,
+        env: &impl PackageCollection,
+        name: &str,
+        visited: &mut HashSet<Box<str>>,
+    ) -> Option<HoldbackExplanation> {
+        let version = self.selected_versions.get(name)?.clone();
+        if !visited.insert(name.into()) {
+            // the package is explained by the caller in the cycle
+            return None;
+        }
+
+        let available = (env.find_packages_by_name(name).into_iter())
+            .filter(|x| !x.is_yanked())
+            .filter(|x| x.version().pre.is_empty() || !version.pre.is_empty())
+            .map(|x| x.version().clone())
+            .collect::<Vec<_>>();
+        let newest = (available.first().cloned())
+            .filter(|newest| newest > &version)
+            .unwrap_or_else(|| version.clone());
+
+        let mut caps = Vec::new();
+        let mut other_reasons = Vec::new();
+        if newest != version {
+            for reason in self.explain_rejection(env, name, &newest) {
+                let RejectionReason::Requirement { source, range } = reason else {
+                    other_reasons.push(reason);
+                    continue;
+                };
+                let allows_up_to = (available.iter())
+                    .find(|x| range.match_pre(x, PrereleaseAcceptance::Allow))
+                    .cloned();
+                let source_explanation = if source.is_empty() {
+                    None
+                } else {
+                    self.explain_holdback_inner(env, &source, visited)
+                        .map(Box::new)
+                };
+                caps.push(HoldbackCap {
+                    source,
+                    range,
+                    allows_up_to,
+                    source_explanation,
+                });
+            }
+        }
+        caps.sort_by(|a, b| {
+            (a.allows_up_to.as_ref(), &a.source).cmp(&(b.allows_up_to.as_ref(), &b.source))
+        });
+
+        Some(HoldbackExplanation {
+            package: name.into(),
+            version,
+            newest,
+            caps,
+            other_reasons,
+        })
+    }
+
+    /// Suggests changes to fix each conflict in `conflicts`, sorted by the conflicting package.
+    ///
+    /// This is best-effort: for each conflicting package, this looks for a version satisfying
+    /// all the requirements, then for a version of a requiring package making such version exist.
+    /// Only one package is changed in each suggestion, and dependencies of the suggested versions
+    /// are not checked.
+    pub fn suggest_conflict_fixes(&self, env: &impl PackageCollection) -> Vec<ConflictSuggestion> {
+        fn newest_matching(
+            env: &impl PackageCollection,
+            package: &str,
+            range: &VersionRange,
+        ) -> Option<Version> {
+            let allow_prerelease = PrereleaseAcceptance::allow_or_minimum(range.contains_pre());
+            (env.find_packages_by_name(package).into_iter())
+                .filter(|x| !x.is_yanked())
+                .find(|x| range.match_pre(x.version(), allow_prerelease))
+                .map(|x| x.version().clone())
+        }
+
+        fn intersect_all<'r>(
+            mut ranges: impl Iterator<Item = &'r VersionRange>,
+        ) -> Option<VersionRange> {
+            let first = ranges.next()?.clone();
+            ranges.try_fold(first, |a, b| a.intersection(b))
+        }
+
+        let mut conflicts = self.conflicts.keys().collect::<Vec<_>>();
+        conflicts.sort();
+
+        let mut suggestions = Vec::new();
+
+        'conflicts: for dependency in conflicts {
+            let requirements =
+                (self.requirements.get(dependency).map(Vec::as_slice)).unwrap_or_default();
+
+            // selecting another version of the package satisfying all the requirements
+            if let Some(range) = intersect_all(requirements.iter().map(|(_, range)| range)) {
+                if let Some(version) = newest_matching(env, dependency, &range) {
+                    suggestions.push(ConflictSuggestion::UseVersion {
+                        package: dependency.clone(),
+                        version,
+                    });
+                    continue;
+                }
+            }
+
+            // changing the version of one package requiring the package
+            for conflict in &self.conflicts[dependency] {
+                if conflict.package.is_empty() {
+                    // the root dependencies cannot be changed
+                    continue;
+                }
+
+                let others = (requirements.iter())
+                    .filter(|(source, _)| source != &conflict.package)
+                    .map(|(_, range)| range);
+
+                let requirer_requirements = (self.requirements.get(&conflict.package))
+                    .map(Vec::as_slice)
+                    .unwrap_or_default();
+
+                for candidate in env.find_packages_by_name(&conflict.package) {
+                    if candidate.is_yanked() {
+                        continue;
+                    }
+                    let satisfies_requirer = (requirer_requirements.iter()).all(|(_, x)| {
+                        x.match_pre(candidate.version(), PrereleaseAcceptance::Allow)
+                    });
+                    if !satisfies_requirer {
+                        continue;
+                    }
+                    let Some(range) = candidate.vpm_dependencies().get(dependency.as_ref()) else {
+                        continue;
+                    };
+                    let Some(range) = intersect_all(std::iter::once(range).chain(others.clone()))
+                    else {
+                        continue;
+                    };
+                    if let Some(dependency_version) = newest_matching(env, dependency, &range) {
+                        suggestions.push(ConflictSuggestion::ChangeRequirer {
+                            package: conflict.package.clone(),
+                            version: candidate.version().clone(),
+                            dependency: dependency.clone(),
+                            dependency_version,
+                        });
+                        continue 'conflicts;
+                    }
+                }
+            }
+
+            suggestions.push(ConflictSuggestion::NoCommonVersion {
+                package: dependency.clone(),
+            });
+        }
+
+        suggestions
+    }
+
+    /// Returns the range of unity versions all the resolved packages are compatible with.
+    ///
+    /// If no single unity version is compatible with all the packages, returns the packages
+    /// setting the conflicting bounds with their unity version ranges, sorted by the name.
+    pub fn unity_compatibility_window(
+        &self,
+    ) -> Result<UnityVersionRange, Vec<(Box<str>, UnityVersionRange)>> {
+        let window = (self.new_packages.iter())
+            .map(|x| unity_requirement(x.package_json()))
+            .fold(UnityVersionRange::default(), |a, b| a.intersect(&b));
+
+        if !window.is_empty() {
+            return Ok(window);
+        }
+
+        let mut offending = (self.new_packages.iter())
+            .map(|x| (x.name().into(), unity_requirement(x.package_json())))
+            .filter(|(_, range)| {
+                (range.minimum().is_some() && range.minimum() == window.minimum())
+                    || (range.maximum().is_some() && range.maximum() == window.maximum())
+            })
+            .collect::<Vec<(Box<str>, _)>>();
+        offending.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Err(offending)
+    }
+
+    /// Returns the names of `new_packages` sorted so dependencies are listed before dependents.
+    ///
+    /// Only dependencies between `new_packages`, including the activated optional dependencies,
+    /// are considered. Packages without order between them are sorted by name.
+    /// Dependency cycles are broken at the package with the smallest name with a warning.
+    pub fn install_order(&self) -> Vec<Box<str>> {
+        let names = (self.new_packages.iter())
+            .map(|x| x.name())
+            .collect::<HashSet<_>>();
+        let optionals = (self.activated_optionals.iter())
+            .map(|(package, dependency)| (package.as_ref(), dependency.as_ref()))
+            .collect::<HashSet<_>>();
+
+        // package name -> names of dependencies not listed yet
+        let mut remaining = (self.new_packages.iter())
+            .map(|x| {
+                let name = x.name();
+                let dependencies = (x.vpm_dependencies().keys())
+                    .chain(
+                        (x.vpm_optional_dependencies().keys())
+                            .filter(|dependency| optionals.contains(&(name, dependency.as_ref()))),
+                    )
+                    .map(|dependency| dependency.as_ref())
+                    .filter(|dependency| *dependency != name && names.contains(dependency))
+                    .collect::<HashSet<_>>();
+                (name, dependencies)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let mut order = Vec::with_capacity(remaining.len());
+        while !remaining.is_empty() {
+            let next = (remaining.iter())
+                .find(|(_, dependencies)| dependencies.is_empty())
+                .map(|(&name, _)| name);
+            let next = next.unwrap_or_else(|| {
+                let (&name, dependencies) = remaining.iter().next().unwrap();
+                let mut dependencies = dependencies.iter().copied().collect::<Vec<_>>();
+                dependencies.sort();
+                log::warn!(
+                    "dependency cycle found: installing {name} before {}",
+                    dependencies.join(", ")
+                );
+                name
+            });
+            remaining.remove(next);
+            for dependencies in remaining.values_mut() {
+                dependencies.remove(next);
+            }
+            order.push(next.into());
+        }
+        order
+    }
+
+    /// Returns the highest minimum unity version the resolved packages require,
+    /// with the name of the package requiring it.
+    ///
+    /// See [`minimum_unity_version`] for details.
+    pub fn minimum_unity_version(&self) -> Option<(UnityVersion, &str)> {
+        minimum_unity_version(self.new_packages.iter().copied())
+    }
+}
+
+/// The matrix of requirements on resolved packages. See [`PackageResolutionResult::satisfaction_matrix`].
+#[derive(Debug, Clone, Serialize)]
+pub struct SatisfactionMatrix {
+    pub rows: Vec<SatisfactionRow>,
+}
+
+/// The requirements on a resolved package.
+#[derive(Debug, Clone, Serialize)]
+pub struct SatisfactionRow {
+    pub package: Box<str>,
+    pub version: Version,
+    pub cells: Vec<SatisfactionCell>,
+}
+
+/// The requirement from a source on a resolved package.
+#[derive(Debug, Clone, Serialize)]
+pub struct SatisfactionCell {
+    pub source: Box<str>,
+    pub range: VersionRange,
+    pub satisfied: bool,
+}
+
+impl SatisfactionMatrix {
+    /// Returns the matrix in CSV with one line for each cell.
+    pub fn to_csv(&self) -> String {
+        fn escape(value: &str) -> Cow<'_, str> {
+            if value.contains([',', '"', '\n']) {
+                Cow::Owned(format!("\"{}\"", value.replace('"', "\"\"")))
+            } else {
+                Cow::Borrowed(value)
+            }
+        }
+
+        let mut csv = String::from("package,version,source,range,satisfied\n");
+        for row in &self.rows {
+            for cell in &row.cells {
+                csv.push_str(&format!(
+                    "{},{},{},{},{}\n",
+                    escape(&row.package),
+                    row.version,
+                    escape(&cell.source),
+                    escape(&cell.range.to_string()),
+                    cell.satisfied,
+                ));
+            }
+        }
+        csv
+    }
+}
+
+/// The change suggested by [`PackageResolutionResult::suggest_conflict_fixes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ConflictSuggestion {
+    /// The version of the conflicting package satisfies all the requirements.
+    UseVersion { package: Box<str>, version: Version },
+    /// With the version of the package requiring the conflicting package,
+    /// the version of the conflicting package satisfies all the requirements.
+    ChangeRequirer {
+        package: Box<str>,
+        version: Version,
+        dependency: Box<str>,
+        dependency_version: Version,
+    },
+    /// No version of the package satisfies the requirements even if a package requiring it is changed.
+    NoCommonVersion { package: Box<str> },
+}
+
+impl fmt::Display for ConflictSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConflictSuggestion::UseVersion { package, version } => {
+                write!(f, "use {package} {version} to satisfy all requirements")
+            }
+            ConflictSuggestion::ChangeRequirer {
+                package,
+                version,
+                dependency,
+                dependency_version,
+            } => write!(
+                f,
+                "change {package} to {version} to use {dependency} {dependency_version}"
+            ),
+            ConflictSuggestion::NoCommonVersion { package } => {
+                write!(f, "no common version exists for {package}")
+            }
+        }
+    }
+}
+
+/// The reason why a package is not in the `new_packages` of the resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum AbsenceReason {
+    /// The package is in `new_packages`.
+    Included,
+    /// No package requires the package.
+    NotRequired,
+    /// The package is required but no version satisfies the requirements.
+    /// The package may not exist or all versions may be filtered by unity version or prerelease.
+    NoMatchingVersion,
+    /// The package is already installed and the installed version is kept.
+    Existing { version: Version },
+    /// The package is a legacy package and removed in favor of the modern packages.
+    Legacy { replaced_by: Vec<Box<str>> },
+    /// The package is recommended but skipped since not found or conflicts.
+    RecommendationSkipped,
+    /// The package is excluded from the resolution by the options.
+    Excluded,
+    /// The package is provided outside of vpm with the version by the options.
+    Provided { version: Version },
+}
+
+/// Where the version of a package in the result of the resolution comes from.
+///
+/// See [`ResolutionDiagnostics::origins`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PackageOrigin {
+    /// The package is locked and the locked version is kept.
+    Locked,
+    /// The package is not locked and newly selected.
+    Added,
+    /// The package is locked and another version is selected.
+    Changed { locked: Version },
+}
+
+/// The reason why a version of a package is not selected by the resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum RejectionReason {
+    /// The version is not present in any repository in the collection.
+    NotFound,
+    /// The version is yanked.
+    Yanked,
+    /// The version does not satisfy the requirement from the source package.
+    Requirement {
+        source: Box<str>,
+        range: VersionRange,
+    },
+    /// The version is a prerelease version and prerelease versions are not allowed for the package.
+    PrereleaseNotAllowed,
+    /// The version is not compatible with the unity version of the project.
+    UnityIncompatible { unity: UnityVersionRange },
+    /// The version declares not to work on the unity versions including the one of the project.
+    UnityExcluded { excluded: UnityVersionRange },
+    /// Nothing disqualifies the version but another version is preferred.
+    OtherSelected { version: Version },
+    /// Nothing disqualifies the version but no package requires the package.
+    NotRequired,
+}
+
+/// Why the selected version of a package is the maximum available one.
+///
+/// See [`PackageResolutionResult::explain_holdback`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoldbackExplanation {
+    // the name of the package
+    pub package: Box<str>,
+    // the selected version
+    pub version: Version,
+    // the newest version in the collection, which is the selected version if not held back
+    pub newest: Version,
+    // requirements rejecting the newest version, sorted by allows_up_to. the first one binds the version
+    pub caps: Vec<HoldbackCap>,
+    // reasons other than requirements rejecting the newest version, like the unity version
+    pub other_reasons: Vec<RejectionReason>,
+}
+
+impl HoldbackExplanation {
+    /// Returns true if newer version than the selected version exists.
+    pub fn is_held_back(&self) -> bool {
+        self.newest != self.version
+    }
+}
+
+/// The requirement rejecting the newest version of a held back package.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HoldbackCap {
+    // the name of the package requiring the range, empty for the project
+    pub source: Box<str>,
+    // the range the package requires
+    pub range: VersionRange,
+    // the newest version in the collection the range allows. None if the range allows no version
+    pub allows_up_to: Option<Version>,
+    // why the source is at the version requiring the range. None for the project or a cycle
+    pub source_explanation: Option<Box<HoldbackExplanation>>,
+}
+
+/// The counters of the work done by the resolution. See [`ResolutionOptions::collect_stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolutionStats {
+    // the number of packages taken from the pending queue, including retries by backtracking
+    pub queue_pops: usize,
+    // the number of packages checked whether to be added
+    pub add_package_calls: usize,
+    // the number of checked packages added, replacing the version chosen before if any
+    pub packages_added: usize,
+    // the number of checked packages not added, like the chosen version is kept
+    pub packages_skipped: usize,
+    // the number of lookups finding versions of the dependencies of the added packages
+    pub package_lookups: usize,
+    // the maximum number of packages in the dependency map, including ones only required
+    pub peak_dependencies: usize,
+}
+
+/// Why the version of a package is chosen by the resolution.
+#[derive(Debug, Clone)]
+pub struct ResolutionReason {
+    // the chosen version
+    pub version: Version,
+    // (source, range) of requirements from non-legacy packages
+    pub requirements: Vec<(Box<str>, VersionRange)>,
+    // why prerelease versions are allowed. None if prerelease versions are not allowed
+    pub prerelease_reason: Option<PrereleaseReason>,
+    // the id of the repository the chosen package came from. None if not from a repository
+    pub repository: Option<Box<str>>,
+    // the source of the satisfied requirement with the highest lower bound, which pins the version from below
+    pub binding_requirement: Option<Box<str>>,
+}
+
+/// Why prerelease versions are allowed for a package.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum PrereleaseReason {
+    /// Prerelease versions are allowed for all packages.
+    Global,
+    /// The package is explicitly requested.
+    Requested,
+    /// Some requirement contains prerelease versions.
+    PrereleaseRequirement,
+    /// The package is installed with a prerelease version.
+    InstalledPrerelease,
+    /// Prerelease versions are allowed for the package by the override.
+    Overridden,
+    /// The prerelease version is from the repository allowing prerelease versions.
+    Repository,
+}
+
+impl fmt::Display for PrereleaseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PrereleaseReason::Global => f.write_str("prerelease is allowed"),
+            PrereleaseReason::Requested => f.write_str("explicitly requested"),
+            PrereleaseReason::PrereleaseRequirement => f.write_str("required with prerelease"),
+            PrereleaseReason::InstalledPrerelease => f.write_str("installed prerelease"),
+            PrereleaseReason::Overridden => f.write_str("overridden for the package"),
+            PrereleaseReason::Repository => f.write_str("allowed for the repository"),
+        }
+    }
+}
+
+/// The legacy package declared in `legacyPackages` of multiple modern packages in the resolution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbiguousLegacyReplacement {
+    // the name of the legacy package
+    pub legacy: Box<str>,
+    // the names of the modern packages replacing the legacy package, sorted
+    pub modern: Vec<Box<str>>,
+}
+
+/// The requirement of a package on a dependency, as an edge of the dependency graph.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DependencyEdge {
+    // the name of the package requiring the dependency, empty for the project
+    pub from: Box<str>,
+    // the name of the dependency
+    pub to: Box<str>,
+    // the range the package requires
+    pub range: VersionRange,
+    // true if the version selected or kept for the dependency satisfies the range
+    pub satisfied: bool,
+}
+
+/// The requirement not satisfied by the version selected for the dependency.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConflictSource {
+    // the name of the package requiring the dependency
+    pub package: Box<str>,
+    // the range the package requires
+    pub range: VersionRange,
+    // the version selected for the dependency
+    pub selected: Version,
+}
+
+/// The requirement fighting the version the package is pinned to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinViolation {
+    // the name of the pinned package
+    pub package: Box<str>,
+    // the version the package is pinned to
+    pub pinned: Version,
+    // the name of the package requiring another version. empty for the requests by the caller
+    pub source: Box<str>,
+    // the range the package requires
+    pub range: VersionRange,
+}
+
+/// The locked package whose dependencies on disk differ from the ones recorded in the lockfile.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockfileDrift {
+    // the name of the locked package
+    pub package: Box<str>,
+    // the dependencies recorded in the lockfile but missing or with another range on disk
+    pub only_locked: Vec<Box<str>>,
+    // the dependencies on disk but missing or with another range in the lockfile
+    pub only_on_disk: Vec<Box<str>>,
+}
+
+/// The locked package whose dependencies recorded in the lockfile differ from the same version
+/// in the collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LockedDependencyMismatch {
+    // the name of the locked package
+    pub package: Box<str>,
+    // the locked version
+    pub version: Version,
+    // the dependencies recorded in the lockfile but missing or with another range in the collection
+    pub only_locked: Vec<Box<str>>,
+    // the dependencies in the collection but missing or with another range in the lockfile
+    pub only_in_collection: Vec<Box<str>>,
+}
+
+/// The locked package whose repository recorded in the lockfile is not in the collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingSourceRepository {
+    // the name of the locked package
+    pub package: Box<str>,
+    // the URL of the repository the package is installed from
+    pub url: Box<str>,
+}
+
+/// The summary of changes made by the resolution. Each list is sorted by name.
+#[derive(Debug, Clone, Default)]
+pub struct ResolutionSummary {
+    pub added: Vec<Box<str>>,
+    pub updated: Vec<Box<str>>,
+    pub downgraded: Vec<Box<str>>,
+    pub removed: Vec<Box<str>>,
+    pub unchanged: Vec<Box<str>>,
+}
+
+/// The changes from the locked packages made by the resolution. Each list is sorted by name.
+///
+/// See [`PackageResolutionResult::diff_against_locked`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResolutionDiff {
+    // (package, version) of packages not locked
+    pub added: Vec<(Box<str>, Version)>,
+    // list of names of locked legacy packages to be removed
+    pub removed: Vec<Box<str>>,
+    // (package, locked version, new version) of packages updated to newer versions
+    pub upgraded: Vec<(Box<str>, Version, Version)>,
+    // (package, locked version, new version) of packages replaced with older versions
+    pub downgraded: Vec<(Box<str>, Version, Version)>,
+    // list of names of conflicting dependencies not conflicting in the locked packages
+    pub new_conflicts: Vec<Box<str>>,
+    // list of names of locked packages required before but no longer required by any package
+    pub orphaned: Vec<Box<str>>,
+}
+
+impl ResolutionDiff {
+    /// Returns true if the resolution changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.upgraded.is_empty()
+            && self.downgraded.is_empty()
+            && self.new_conflicts.is_empty()
+            && self.orphaned.is_empty()
+    }
+}
+
+impl fmt::Display for ResolutionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, version) in &self.added {
+            writeln!(f, "+ {name} {version}")?;
+        }
+        for name in &self.removed {
+            writeln!(f, "- {name}")?;
+        }
+        for (name, locked, version) in &self.upgraded {
+            writeln!(f, "^ {name} {locked} -> {version}")?;
+        }
+        for (name, locked, version) in &self.downgraded {
+            writeln!(f, "v {name} {locked} -> {version}")?;
+        }
+        for name in &self.new_conflicts {
+            writeln!(f, "! {name} conflicts")?;
+        }
+        for name in &self.orphaned {
+            writeln!(f, "? {name} is no longer required")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for ResolutionSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} to install, {} to update, {} to downgrade, {} to remove",
+            self.added.len(),
+            self.updated.len(),
+            self.downgraded.len(),
+            self.removed.len()
+        )
+    }
+}
+
+/// The information about a dependency that cannot be satisfied by any known package.
+pub struct ConflictContext<'a> {
+    /// The name of the package requiring the dependency
+    pub package: &'a str,
+    /// The version of the package requiring the dependency
+    pub package_version: &'a Version,
+    /// The name of the dependency that cannot be satisfied
+    pub dependency: &'a str,
+    /// The range we have tried to find the dependency with
+    pub range: &'a VersionRange,
+    /// The number of retries already made for this dependency
+    pub attempt: usize,
+}
+
+/// The number of [`ConflictDecision::RetryWithRange`] accepted for each dependency.
+pub const MAX_CONFLICT_RETRIES: usize = 8;
+
+/// The decision made by [`ConflictStrategy`] for an unsatisfiable dependency.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub enum ConflictDecision {
+    /// Find the dependency again with the specified range.
+    RetryWithRange(VersionRange),
+    /// Ignore the requirement and continue resolving without the dependency.
+    DropRequirement,
+    /// Report the dependency as missing.
+    GiveUp,
+}
+
+/// The strategy the resolver consults when some dependency cannot be satisfied.
+///
+/// Since the requirement itself is kept as is, a package found with a modified range
+/// will be reported as a conflict in the result.
+///
+/// The strategy is consulted at most [`MAX_CONFLICT_RETRIES`] + 1 times for each dependency.
+/// If the dependency is still not found after [`MAX_CONFLICT_RETRIES`] retries, the resolver
+/// gives up the dependency without consulting the strategy, so strategies always retrying
+/// never hang the resolution.
+pub trait ConflictStrategy {
+    fn on_conflict(&self, conflict: &ConflictContext) -> ConflictDecision;
+
+    /// Returns true if the decisions depend only on the [`ConflictContext`].
+    ///
+    /// Results are cached by [`CachedPackageCollection`] only with deterministic strategies.
+    /// Strategies reading their own fields, globals, or thread-locals must return false.
+    ///
+    /// [`CachedPackageCollection`]: crate::unity_project::CachedPackageCollection
+    fn is_deterministic(&self) -> bool {
+        false
+    }
+}
+
+impl<F: Fn(&ConflictContext) -> ConflictDecision> ConflictStrategy for F {
+    fn on_conflict(&self, conflict: &ConflictContext) -> ConflictDecision {
+        self(conflict)
+    }
+}
+
+/// Reports unsatisfiable dependencies as missing. This is the default strategy.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FailFast;
+
+impl ConflictStrategy for FailFast {
+    fn on_conflict(&self, _: &ConflictContext) -> ConflictDecision {
+        ConflictDecision::GiveUp
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// Drops unsatisfiable requirements and continues resolving without the dependency.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DropRequirement;
+
+impl ConflictStrategy for DropRequirement {
+    fn on_conflict(&self, _: &ConflictContext) -> ConflictDecision {
+        ConflictDecision::DropRequirement
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// Retries with any version of the dependency, then gives up if still not found.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RelaxToAnyVersion;
+
+impl ConflictStrategy for RelaxToAnyVersion {
+    fn on_conflict(&self, conflict: &ConflictContext) -> ConflictDecision {
+        if conflict.attempt == 0 {
+            ConflictDecision::RetryWithRange(VersionRange::same_or_later(Version::new(0, 0, 0)))
+        } else {
+            ConflictDecision::GiveUp
+        }
+    }
+
+    fn is_deterministic(&self) -> bool {
+        true
+    }
+}
+
+/// The information about a version violating requirements from other packages, about to be chosen.
+pub struct ViolationContext<'a> {
+    /// The name of the package requiring the dependency
+    pub package: &'a str,
+    /// The name of the dependency
+    pub dependency: &'a str,
+    /// The range the package requires
+    pub range: &'a VersionRange,
+    /// The version about to be chosen, the newest one in the range
+    pub selecting: &'a Version,
+    /// The (source, range) of the requirements from other packages the version violates, sorted by source
+    pub violated: &'a [(&'a str, &'a VersionRange)],
+    /// The known versions of the dependency for the project, newest first
+    pub candidates: &'a [&'a Version],
+}
+
+/// The decision made by [`ViolationHandler`] for a version violating requirements.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub enum ViolationDecision {
+    /// Choose the version about to be chosen. This is the behavior without the handler.
+    SelectNewest,
+    /// Choose the newest version satisfying the most requirements, including the new one.
+    SatisfyMost,
+    /// Choose the specified version. If the version is not found, the newest one is chosen.
+    Select(Version),
+    /// Stop the resolution. The dependency is reported in `aborted_at` of the result.
+    Abort,
+}
+
+/// The handler the resolver consults when the found version violates requirements from other packages.
+pub trait ViolationHandler {
+    fn on_violation(&self, violation: &ViolationContext) -> ViolationDecision;
+}
+
+impl<F: Fn(&ViolationContext) -> ViolationDecision> ViolationHandler for F {
+    fn on_violation(&self, violation: &ViolationContext) -> ViolationDecision {
+        self(violation)
+    }
+}
+
+/// The reason why the resolver doesn't add a dependency.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The dependency is removed as a legacy package of another package.
+    Legacy,
+    /// The dependency is installed without being locked.
+    Unlocked,
+    /// The dependency is vendored or provided by the project.
+    Vendored,
+    /// The dependency is excluded with [`ResolutionOptions::exclude`].
+    Excluded,
+    /// The dependency is pinned to a version not matching the range.
+    Pinned(Version),
+    /// The version pending to be installed matches the range.
+    PendingMatches,
+    /// The version already installed or chosen matches the range.
+    ExistingMatches,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Legacy => f.write_str("legacy package"),
+            SkipReason::Unlocked => f.write_str("unlocked package"),
+            SkipReason::Vendored => f.write_str("vendored package"),
+            SkipReason::Excluded => f.write_str("excluded package"),
+            SkipReason::Pinned(version) => write!(f, "pinned to {version}"),
+            SkipReason::PendingMatches => f.write_str("pending matches"),
+            SkipReason::ExistingMatches => f.write_str("existing matches"),
+        }
+    }
+}
+
+/// The sink receiving structured events while resolving packages.
+///
+/// The events are sent in addition to the `log` output, in the order the resolver processes
+/// the packages. All methods do nothing by default.
+pub trait ResolutionEventSink {
+    /// Called when the resolver starts processing the package.
+    fn on_package_processed(&self, package: PackageInfo) {
+        let _ = package;
+    }
+
+    /// Called for each dependency of the processed package with the range actually used.
+    fn on_dependency_considered(&self, package: &str, dependency: &str, range: &VersionRange) {
+        let _ = (package, dependency, range);
+    }
+
+    /// Called when the version of the dependency is selected to be processed.
+    fn on_package_selected(&self, package: &str, selected: PackageInfo) {
+        let _ = (package, selected);
+    }
+
+    /// Called when the dependency is not added.
+    fn on_skip_reason(&self, package: &str, dependency: &str, reason: &SkipReason) {
+        let _ = (package, dependency, reason);
+    }
+}
+
+/// The order the resolver processes the pending packages in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum QueueOrder {
+    /// Processes the package added to the queue last first.
+    #[default]
+    LastAdded,
+    /// Processes the packages no other pending package depends on first.
+    ///
+    /// The requirements from the dependents are known before the dependencies are processed,
+    /// so the dependencies are less likely to be processed again with the new requirements.
+    /// The dependencies include the activated optional dependencies and the dependencies of
+    /// the enabled features, with the aliases applied.
+    /// Falls back to [`QueueOrder::LastAdded`] if every pending package has pending dependents.
+    DependentsFirst,
+}
+
+/// How the locked versions affect the selection of versions when resolving a project again.
+///
+/// The `dependencies` in vpm-manifest.json are always the authoritative requirements.
+/// Regardless of the preference, locked packages the resolution doesn't need to change
+/// are kept as installed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LockedPreference {
+    /// The locked version is the minimum version of the root dependency, so the root dependency
+    /// is not downgraded even if an older version is requested. This is the default.
+    #[default]
+    Floor,
+    /// Like [`LockedPreference::Floor`], and the locked version is also chosen when a new version
+    /// has to be selected for a dependency if the locked version satisfies the requirements, even if
+    /// newer versions also satisfy them.
+    Prefer,
+    /// The locked version doesn't constrain the root dependencies, so the root dependencies
+    /// whose locked version doesn't satisfy the requested version are selected again,
+    /// even if an older version is selected.
+    Ignore,
+}
+
+/// How conflicts in the resolution are treated when adding packages.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum ConflictPolicy {
+    /// Reports conflicts in the result and the changes, leaving the decision to the caller.
+    /// This is the default.
+    #[default]
+    Report,
+    /// Fails with [`AddPackageErr::UnresolvableConflicts`] if there are any conflicts.
+    Fail,
+    /// Fails only if the dependencies of the project conflict, and reports conflicts of
+    /// requirements between packages.
+    FailOnDirect,
+}
+
+impl ConflictPolicy {
+    /// Returns true if the policy fails the resolution with the conflicts.
+    pub(crate) fn fails_with(self, conflicts: &HashMap<Box<str>, Vec<ConflictSource>>) -> bool {
+        match self {
+            ConflictPolicy::Report => false,
+            ConflictPolicy::Fail => !conflicts.is_empty(),
+            ConflictPolicy::FailOnDirect => {
+                (conflicts.values().flatten()).any(|x| x.package.is_empty())
+            }
+        }
+    }
+}
+
+/// The policy for licenses of the resolved packages.
+#[derive(Debug, Clone, Default, Hash)]
+pub struct LicensePolicy {
+    allowed: Vec<Box<str>>,
+    strict: bool,
+}
+
+impl LicensePolicy {
+    /// Creates the policy allowing the specified SPDX license ids.
+    ///
+    /// If no license is specified, any declared license is allowed.
+    pub fn new(allowed: impl IntoIterator<Item = impl Into<Box<str>>>) -> Self {
+        Self {
+            allowed: allowed.into_iter().map(Into::into).collect(),
+            strict: false,
+        }
+    }
+
+    /// Makes disallowed licenses an error instead of just reporting them.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Returns whether the license is allowed. Packages without license are never allowed.
+    pub fn is_allowed(&self, license: Option<&str>) -> bool {
+        match license {
+            None => false,
+            Some(_) if self.allowed.is_empty() => true,
+            Some(license) => self.allowed.iter().any(|x| x.as_ref() == license),
+        }
+    }
+}
+
+/// How the prerelease allowance spreads to the dependencies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PrereleasePropagation {
+    /// Prerelease versions are allowed for the dependencies of prerelease packages, and
+    /// the global allowance applies to every package. This is the default.
+    #[default]
+    Propagate,
+    /// The global allowance and the prerelease versions installed apply only to the root
+    /// dependencies and the requested packages, and the dependencies of prerelease packages
+    /// prefer stable versions as usual.
+    RootOnly,
+}
+
+/// Options for the package resolution.
+pub struct ResolutionOptions<'a> {
+    pub(crate) allow_prerelease: bool,
+    pub(crate) conflict_strategy: &'a dyn ConflictStrategy,
+    pub(crate) violation_handler: Option<&'a dyn ViolationHandler>,
+    pub(crate) conflict_policy: ConflictPolicy,
+    pub(crate) event_sink: Option<&'a dyn ResolutionEventSink>,
+    pub(crate) must_include: Vec<&'a str>,
+    pub(crate) requirement_priorities: HashMap<&'a str, u32>,
+    pub(crate) include_recommended: bool,
+    pub(crate) tiebreak_repo_priority: Vec<&'a str>,
+    pub(crate) prefer_naming_convention: bool,
+    pub(crate) backtracking: bool,
+    pub(crate) iteration_limit: usize,
+    pub(crate) queue_order: QueueOrder,
+    pub(crate) locked_preference: LockedPreference,
+    pub(crate) resolve_subset: bool,
+    pub(crate) diagnostics: bool,
+    pub(crate) collect_stats: bool,
+    pub(crate) detect_stuck_prereleases: bool,
+    pub(crate) detect_arbitrary_choices: bool,
+    pub(crate) detect_kept_yanked: bool,
+    pub(crate) collect_slack: bool,
+    pub(crate) target_platform: Option<&'a str>,
+    pub(crate) target_unity_versions: Vec<UnityVersion>,
+    pub(crate) minimal_versions: bool,
+    pub(crate) published_before: Option<SystemTime>,
+    pub(crate) prerelease_overrides: HashMap<&'a str, bool>,
+    pub(crate) prerelease_repositories: Vec<&'a str>,
+    pub(crate) license_policy: Option<LicensePolicy>,
+    pub(crate) channel: Option<Box<str>>,
+    #[allow(clippy::type_complexity)]
+    pub(crate) approve_removal: Option<RefCell<Box<dyn FnMut(&str, &str) -> bool + 'a>>>,
+    pub(crate) vendored: HashMap<Box<str>, Version>,
+    pub(crate) provided: HashMap<Box<str>, Version>,
+    pub(crate) pins: HashMap<&'a str, Version>,
+    pub(crate) overrides: HashMap<&'a str, Version>,
+    pub(crate) excluded: HashSet<&'a str>,
+    pub(crate) enabled_features: HashSet<&'a str>,
+    pub(crate) keep_legacy: HashSet<&'a str>,
+    pub(crate) aliases: HashMap<&'a str, &'a str>,
+    pub(crate) prerelease_matching: PrereleaseMatching,
+    pub(crate) prerelease_propagation: PrereleasePropagation,
+    #[allow(clippy::type_complexity)]
+    pub(crate) range_rewriter:
+        Option<Box<dyn Fn(&str, &str, &VersionRange) -> Option<VersionRange> + 'a>>,
+    #[allow(clippy::type_complexity)]
+    pub(crate) on_disk_dependencies:
+        Option<Box<dyn Fn(&str) -> Option<IndexMap<Box<str>, VersionRange>> + 'a>>,
+}
+
+impl Default for ResolutionOptions<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> ResolutionOptions<'a> {
+    pub fn new() -> Self {
+        Self {
+            allow_prerelease: false,
+            conflict_strategy: &FailFast,
+            violation_handler: None,
+            conflict_policy: ConflictPolicy::Report,
+            event_sink: None,
+            must_include: Vec::new(),
+            requirement_priorities: HashMap::new(),
+            include_recommended: false,
+            tiebreak_repo_priority: Vec::new(),
+            prefer_naming_convention: false,
+            backtracking: false,
+            iteration_limit: 100_000,
+            queue_order: QueueOrder::LastAdded,
+            locked_preference: LockedPreference::Floor,
+            resolve_subset: false,
+            diagnostics: false,
+            collect_stats: false,
+            detect_stuck_prereleases: false,
+            detect_arbitrary_choices: false,
+            detect_kept_yanked: false,
+            collect_slack: false,
+            target_platform: None,
+            target_unity_versions: Vec::new(),
+            minimal_versions: false,
+            published_before: None,
+            prerelease_overrides: HashMap::new(),
+            prerelease_repositories: Vec::new(),
+            license_policy: None,
+            channel: None,
+            approve_removal: None,
+            vendored: HashMap::new(),
+            provided: HashMap::new(),
+            pins: HashMap::new(),
+            overrides: HashMap::new(),
+            excluded: HashSet::new(),
+            enabled_features: HashSet::new(),
+            keep_legacy: HashSet::new(),
+            aliases: HashMap::new(),
+            prerelease_matching: PrereleaseMatching::AnyPrerelease,
+            prerelease_propagation: PrereleasePropagation::Propagate,
+            range_rewriter: None,
+            on_disk_dependencies: None,
+        }
+    }
+
+    pub fn allow_prerelease(mut self, allow_prerelease: bool) -> Self {
+        self.allow_prerelease = allow_prerelease;
+        self
+    }
+
+    /// Overrides whether prerelease versions are allowed for the package.
+    ///
+    /// This takes precedence over `allow_prerelease` and prerelease versions requested or installed.
+    pub fn prerelease_override(mut self, package: &'a str, allow_prerelease: bool) -> Self {
+        self.prerelease_overrides.insert(package, allow_prerelease);
+        self
+    }
+
+    /// Allows prerelease versions from the repository with the id.
+    ///
+    /// A prerelease version is allowed if any of `allow_prerelease`, the prerelease versions
+    /// requested or installed, or the repository of the version allows it.
+    /// Among the versions from the repositories, prerelease ones are chosen as if
+    /// `allow_prerelease` is set, and prerelease versions from other repositories are chosen
+    /// only if nothing else satisfies the requirements as usual.
+    /// [`Self::prerelease_override`] denying prerelease versions takes precedence over this.
+    pub fn allow_prerelease_from(mut self, repository: &'a str) -> Self {
+        self.prerelease_repositories.push(repository);
+        self
+    }
+
+    /// Enables the feature, adding the conditional dependencies of the feature to the
+    /// dependencies of packages declaring it.
+    ///
+    /// The features are shared by all the packages. Enabled features declared by the resolved
+    /// packages are reported in `active_features` of the result.
+    pub fn enable_feature(mut self, feature: &'a str) -> Self {
+        self.enabled_features.insert(feature);
+        self
+    }
+
+    pub fn conflict_strategy(mut self, conflict_strategy: &'a dyn ConflictStrategy) -> Self {
+        self.conflict_strategy = conflict_strategy;
+        self
+    }
+
+    /// Sets the handler deciding the version when the found version violates requirements
+    /// from other packages, for example to prompt the user.
+    ///
+    /// Without the handler, the found version is chosen and the violation is reported as
+    /// a conflict in the result.
+    pub fn violation_handler(mut self, violation_handler: &'a dyn ViolationHandler) -> Self {
+        self.violation_handler = Some(violation_handler);
+        self
+    }
+
+    /// Sets how conflicts in the resolution are treated. Defaults to [`ConflictPolicy::Report`].
+    ///
+    /// This only decides whether adding packages fails, so the resolution itself is the same.
+    pub fn conflict_policy(mut self, conflict_policy: ConflictPolicy) -> Self {
+        self.conflict_policy = conflict_policy;
+        self
+    }
+
+    /// Sends the structured events of the resolution to the sink.
+    ///
+    /// Since cached results would not send the events, installing the sink disables the cache.
+    pub fn event_sink(mut self, event_sink: &'a dyn ResolutionEventSink) -> Self {
+        self.event_sink = Some(event_sink);
+        self
+    }
+
+    /// Makes the package included in the result even if nothing requires it.
+    ///
+    /// The version is chosen from the requirements known before resolving, or the newest one if
+    /// nothing constrains the package. Requirements found while resolving may replace the version.
+    /// If the package is already installed with a version satisfying the requirements,
+    /// the package is kept as is.
+    pub fn must_include(mut self, package: &'a str) -> Self {
+        self.must_include.push(package);
+        self
+    }
+
+    /// Sets the priority of requirements from the package. Use `""` for the root dependencies.
+    ///
+    /// If the requirement from the package cannot be satisfied together with requirements with
+    /// higher priority, the requirement will be sacrificed.
+    /// The root dependencies have the highest priority unless specified,
+    /// and requirements from packages without priority will never be sacrificed.
+    pub fn requirement_priority(mut self, package: &'a str, priority: u32) -> Self {
+        self.requirement_priorities.insert(package, priority);
+        self
+    }
+
+    /// Retries resolving with older versions of packages if the resolution reaches a dead end.
+    ///
+    /// If a dependency of a package cannot be found or conflicts, the resolution is retried
+    /// with the next older version of the package. Packages explicitly requested are never replaced.
+    pub fn backtracking(mut self, backtracking: bool) -> Self {
+        self.backtracking = backtracking;
+        self
+    }
+
+    /// Sets how the locked versions affect the selection. Defaults to [`LockedPreference::Floor`].
+    pub fn locked_preference(mut self, locked_preference: LockedPreference) -> Self {
+        self.locked_preference = locked_preference;
+        self
+    }
+
+    /// Holds the locked packages not requested at the locked versions, as if they're pinned.
+    ///
+    /// Only the requested packages, the packages that must be included, and the packages newly
+    /// introduced by them can change versions, so adding a package never upgrades unrelated ones.
+    /// Requirements the held versions do not satisfy are reported in `pin_violations` and
+    /// `conflicts` of the result like [`Self::pins`].
+    /// Packages configured by other options are not held.
+    pub fn resolve_subset(mut self, resolve_subset: bool) -> Self {
+        self.resolve_subset = resolve_subset;
+        self
+    }
+
+    /// Allows root dependencies to select versions older than the locked ones.
+    ///
+    /// By default, the locked version is kept even if a root dependency requests a newer minimum
+    /// version. With this, locked root dependencies are selected again if the locked version
+    /// does not satisfy the requirements, even if an older version is selected.
+    /// Downgrades are reported in `downgrades` of the result.
+    ///
+    /// This is a shorthand of [`LockedPreference::Ignore`].
+    pub fn allow_downgrade(mut self, allow_downgrade: bool) -> Self {
+        if allow_downgrade {
+            self.locked_preference = LockedPreference::Ignore;
+        } else if self.locked_preference == LockedPreference::Ignore {
+            self.locked_preference = LockedPreference::Floor;
+        }
+        self
+    }
+
+    /// Sets the maximum number of packages processed in a resolution pass. Defaults to 100,000.
+    ///
+    /// The resolution stops when the limit is exceeded, which protects against repositories
+    /// with broken or malicious dependency graphs.
+    pub fn iteration_limit(mut self, iteration_limit: usize) -> Self {
+        self.iteration_limit = iteration_limit;
+        self
+    }
+
+    /// Sets the order the pending packages are processed in. Defaults to [`QueueOrder::LastAdded`].
+    ///
+    /// The resolved versions may differ between orders if there are conflicts or
+    /// the chosen versions depend on the order the requirements are found.
+    pub fn queue_order(mut self, queue_order: QueueOrder) -> Self {
+        self.queue_order = queue_order;
+        self
+    }
+
+    /// Collects the diagnostics to `diagnostics` of the result, like why each version is chosen.
+    ///
+    /// The diagnostics are not needed to apply the changes, so they are not collected by default.
+    pub fn diagnostics(mut self, diagnostics: bool) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Skips versions declaring not to work on the platform, like `Android`.
+    ///
+    /// This works like the unity version of the project, but versions for other platforms are
+    /// never chosen. Versions without the declaration are compatible with any platform.
+    pub fn target_platform(mut self, platform: &'a str) -> Self {
+        self.target_platform = Some(platform);
+        self
+    }
+
+    /// Chooses only versions compatible with all the unity versions, in addition to the project one.
+    ///
+    /// This is for bundles of packages targeting multiple unity versions at once, like 2019.4 and 2022.3.
+    /// If no version of a dependency is compatible with all of them, the dependency is reported in
+    /// `target_unity_incompatible` of the result.
+    pub fn target_unity_versions(mut self, unity_versions: Vec<UnityVersion>) -> Self {
+        self.target_unity_versions = unity_versions;
+        self
+    }
+
+    /// Counts the work done by the resolution to `stats` of the result, for profiling.
+    ///
+    /// The resolution is not cached while collecting stats, so the counters reflect the work
+    /// of each resolution.
+    pub fn collect_stats(mut self, collect_stats: bool) -> Self {
+        self.collect_stats = collect_stats;
+        self
+    }
+
+    /// Reports packages using prerelease versions without stable versions satisfying the
+    /// requirements to `stuck_on_prerelease` of the result.
+    pub fn detect_stuck_prereleases(mut self, detect_stuck_prereleases: bool) -> Self {
+        self.detect_stuck_prereleases = detect_stuck_prereleases;
+        self
+    }
+
+    /// Reports packages chosen arbitrarily among candidates with the same version to
+    /// `arbitrary_choices` of the result, which is used by `is_reproducible`.
+    ///
+    /// This looks up all the candidates of every resolved package.
+    pub fn detect_arbitrary_choices(mut self, detect_arbitrary_choices: bool) -> Self {
+        self.detect_arbitrary_choices = detect_arbitrary_choices;
+        self
+    }
+
+    /// Reports locked versions kept even though yanked in the collection to `kept_yanked` of
+    /// the result, with warnings.
+    pub fn detect_kept_yanked(mut self, detect_kept_yanked: bool) -> Self {
+        self.detect_kept_yanked = detect_kept_yanked;
+        self
+    }
+
+    /// Collects the portion of the requirement ranges above the selected versions for `slack`
+    /// of the result.
+    ///
+    /// This looks up all the published versions of every resolved package.
+    pub fn collect_slack(mut self, collect_slack: bool) -> Self {
+        self.collect_slack = collect_slack;
+        self
+    }
+
+    /// Chooses the lowest version satisfying the requirements for dependencies instead of the highest.
+    ///
+    /// This is useful to verify the declared minimum versions of the requirements.
+    /// Packages already satisfying the requirements are kept as is.
+    pub fn minimal_versions(mut self, minimal_versions: bool) -> Self {
+        self.minimal_versions = minimal_versions;
+        self
+    }
+
+    /// Keeps the locked version of a dependency if it satisfies the requirements on the dependency
+    /// while choosing a new version, even if newer versions also satisfy the requirements.
+    ///
+    /// This reduces upgrades of unrelated packages while adding packages.
+    ///
+    /// This is a shorthand of [`LockedPreference::Prefer`].
+    pub fn prefer_locked_versions(mut self, prefer_locked: bool) -> Self {
+        if prefer_locked {
+            self.locked_preference = LockedPreference::Prefer;
+        } else if self.locked_preference == LockedPreference::Prefer {
+            self.locked_preference = LockedPreference::Floor;
+        }
+        self
+    }
+
+    /// Resolves with only versions published before the time, for reproducing past resolutions.
+    ///
+    /// Versions without the publish time in the repository are considered as published before
+    /// any time, so the resolution is deterministic only if the repositories provide the time.
+    /// Pinned versions are used even if they're published after the time.
+    pub fn published_before(mut self, published_before: SystemTime) -> Self {
+        self.published_before = Some(published_before);
+        self
+    }
+
+    /// Tries to add recommended packages after resolving required packages.
+    ///
+    /// Recommended packages causing missing dependencies or conflicts will be skipped.
+    pub fn include_recommended(mut self, include_recommended: bool) -> Self {
+        self.include_recommended = include_recommended;
+        self
+    }
+
+    /// Sets the ids of repositories preferred when multiple repositories provide the same version.
+    ///
+    /// Repositories are preferred in the order of the list. This does not affect which version is
+    /// chosen, so the newest version is still chosen even if it's not in the preferred repositories.
+    pub fn tiebreak_repo_priority(mut self, repositories: Vec<&'a str>) -> Self {
+        self.tiebreak_repo_priority = repositories;
+        self
+    }
+
+    /// Prefers the repository whose id shares the naming convention with installed packages.
+    ///
+    /// This is consulted only when multiple repositories provide the same version and the
+    /// repository priority does not decide. For example, the repository `com.anatawa12.vpm` is
+    /// preferred if `com.anatawa12.avatar-optimizer` is installed.
+    pub fn prefer_naming_convention(mut self, prefer_naming_convention: bool) -> Self {
+        self.prefer_naming_convention = prefer_naming_convention;
+        self
+    }
+
+    /// Collects licenses of the resolved packages and checks them with the policy.
+    pub fn license_policy(mut self, license_policy: LicensePolicy) -> Self {
+        self.license_policy = Some(license_policy);
+        self
+    }
+
+    /// Tracks the release channel: the newest version in the channel will be chosen for dependencies.
+    ///
+    /// The channel of a version is the leading prerelease identifier like `beta` for `1.0.0-beta.1`,
+    /// and `stable` for versions without prerelease.
+    pub fn channel(mut self, channel: impl Into<Box<str>>) -> Self {
+        self.channel = Some(channel.into());
+        self
+    }
+
+    /// Sets the callback to approve each removal of installed legacy packages.
+    ///
+    /// The callback receives the name of the legacy package and the package replacing it.
+    /// If the callback returns false, the removal is suppressed and both packages are kept.
+    pub fn approve_removal(mut self, approve_removal: impl FnMut(&str, &str) -> bool + 'a) -> Self {
+        self.approve_removal = Some(RefCell::new(Box::new(approve_removal)));
+        self
+    }
+
+    /// Sets the legacy packages kept even if the packages replacing them are installed.
+    ///
+    /// The packages are never treated as legacy, so they're not reported in `found_legacy_packages`
+    /// and resolved like other packages. Removals kept installed are reported in
+    /// `suppressed_removals` of the result.
+    ///
+    /// Keeping a legacy package is risky since it usually conflicts with the replacing package,
+    /// for example with duplicated scripts or assets. Keep it only if you know it doesn't.
+    pub fn keep_legacy(mut self, keep_legacy: HashSet<&'a str>) -> Self {
+        self.keep_legacy = keep_legacy;
+        self
+    }
+
+    /// Sets the packages vendored by the user with the installed versions.
+    ///
+    /// Vendored packages are never replaced nor removed as legacy packages.
+    /// Requirements not satisfied by the vendored version are reported as conflicts.
+    pub fn vendored(mut self, vendored: HashMap<Box<str>, Version>) -> Self {
+        self.vendored = vendored;
+        self
+    }
+
+    /// Sets the packages provided outside of vpm with the versions, like embedded or UPM packages.
+    ///
+    /// Provided packages satisfy requirements like installed packages, but they are never installed,
+    /// replaced, nor removed, and never in `new_packages` of the result.
+    /// Unlike excluded packages, requirements not satisfied by the provided version are reported
+    /// as conflicts.
+    pub fn provided(mut self, provided: HashMap<Box<str>, Version>) -> Self {
+        self.provided = provided;
+        self
+    }
+
+    /// Sets the packages pinned to the exact versions.
+    ///
+    /// The pinned version is always chosen for the package, and locked packages with other versions
+    /// are replaced with the pinned version. Requirements the pinned version does not satisfy,
+    /// including the ones from transitive dependencies, are reported in `pin_violations` and
+    /// `conflicts` of the result instead of choosing another version.
+    pub fn pins(mut self, pins: HashMap<&'a str, Version>) -> Self {
+        self.pins = pins;
+        self
+    }
+
+    /// Sets the packages overridden to the exact versions, like `[patch]` of Cargo.
+    ///
+    /// Overridden packages are resolved like pinned packages, and overrides take precedence over pins.
+    /// Unlike pins, requirements the overridden version does not satisfy are reported in
+    /// `override_violations` and `conflicts` of the result only for transparency,
+    /// so they never fail the resolution with [`ConflictPolicy`].
+    pub fn overrides(mut self, overrides: HashMap<&'a str, Version>) -> Self {
+        self.overrides = overrides;
+        self
+    }
+
+    /// Sets the packages excluded from the resolution.
+    ///
+    /// Excluded packages are treated as already satisfied. They are never installed nor
+    /// reported as conflicts, even if some packages depend on them.
+    /// It's the responsibility of the caller to make sure that the packages depending on
+    /// the excluded packages work without them.
+    pub fn exclude(mut self, excluded: HashSet<&'a str>) -> Self {
+        self.excluded = excluded;
+        self
+    }
+
+    /// Sets the rule for when prerelease versions match ranges without prerelease allowed.
+    ///
+    /// Defaults to [`PrereleaseMatching::AnyPrerelease`].
+    /// See [`PrereleaseMatching`] for which versions match each rule.
+    pub fn prerelease_matching(mut self, prerelease_matching: PrereleaseMatching) -> Self {
+        self.prerelease_matching = prerelease_matching;
+        self
+    }
+
+    /// Sets how the prerelease allowance spreads to the dependencies.
+    ///
+    /// Defaults to [`PrereleasePropagation::Propagate`].
+    pub fn prerelease_propagation(mut self, prerelease_propagation: PrereleasePropagation) -> Self {
+        self.prerelease_propagation = prerelease_propagation;
+        self
+    }
+
+    /// Sets the map from the old name to the new name of renamed packages.
+    ///
+    /// Dependencies declared by packages on the old name are treated as dependencies on the
+    /// new name, so they are satisfied by the new package and merged with requirements for it.
+    /// Dependencies of the project itself are not renamed.
+    pub fn aliases(mut self, aliases: HashMap<&'a str, &'a str>) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// Sets the callback to rewrite ranges of dependencies declared by packages.
+    ///
+    /// The callback receives the name of the package, the name of the dependency, and the declared range.
+    /// If the callback returns a range, the range is used instead of the declared one.
+    /// This is useful to work around too strict ranges of published packages.
+    pub fn range_rewriter(
+        mut self,
+        range_rewriter: impl Fn(&str, &str, &VersionRange) -> Option<VersionRange> + 'a,
+    ) -> Self {
+        self.range_rewriter = Some(Box::new(range_rewriter));
+        self
+    }
+
+    /// Sets the callback to read the actual dependencies of locked packages installed on disk.
+    ///
+    /// The callback receives the name of the locked package and returns the dependencies declared
+    /// in the `package.json` on disk, or `None` if they cannot be read.
+    /// Locked packages whose dependencies differ from the lockfile are reported as lockfile drift.
+    /// The resolution itself still uses the dependencies recorded in the lockfile.
+    pub fn on_disk_dependencies(
+        mut self,
+        on_disk_dependencies: impl Fn(&str) -> Option<IndexMap<Box<str>, VersionRange>> + 'a,
+    ) -> Self {
+        self.on_disk_dependencies = Some(Box::new(on_disk_dependencies));
+        self
+    }
+
+    /// Returns whether the results with the options can be reused for the same inputs.
+    ///
+    /// Callbacks may change the result without changing the inputs, so options with callbacks or
+    /// stateful conflict strategies are not cacheable. Options with event sinks are not cacheable
+    /// either since the events would not be sent.
+    pub(crate) fn is_cacheable(&self) -> bool {
+        self.approve_removal.is_none()
+            && self.event_sink.is_none()
+            && self.range_rewriter.is_none()
+            && self.on_disk_dependencies.is_none()
+            && !self.collect_stats
+            && self.conflict_strategy.is_deterministic()
+            && self.violation_handler.is_none()
+    }
+
+    pub(crate) fn requirement_priority_of(&self, package: &str) -> Option<u32> {
+        match self.requirement_priorities.get(package) {
+            Some(&priority) => Some(priority),
+            None if package.is_empty() => Some(u32::MAX),
+            None => None,
+        }
+    }
+}
+
+/// The requirement on a dependency not found in the collection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingRequirement {
+    // the name of the dependency not found
+    pub dependency: Box<str>,
+    // the name of the package requiring the dependency
+    pub source: Box<str>,
+    // the range the package requires
+    pub range: VersionRange,
+}
+
+#[derive(Clone)]
+pub(crate) struct MissingDependencies {
+    pub dependencies: HashSet<Box<str>>,
+    // the requirements on the dependencies, for the ones whose source is known
+    pub requirements: Vec<MissingRequirement>,
+}
+
+impl MissingDependencies {
+    pub fn new() -> Self {
+        Self {
+            dependencies: HashSet::new(),
+            requirements: Vec::new(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dependencies.is_empty()
+    }
 
     pub fn add(&mut self, dependency: &str) {
         self.dependencies.insert(dependency.into());
     }
 
-    pub fn into_vec(self) -> Vec<Box<str>> {
-        self.dependencies.into_iter().collect()
+    /// Adds the dependency with the package requiring it.
+    pub fn add_required(&mut self, dependency: &str, source: &str, range: &VersionRange) {
+        self.add(dependency);
+        let requirement = MissingRequirement {
+            dependency: dependency.into(),
+            source: source.into(),
+            range: range.clone(),
+        };
+        if !self.requirements.contains(&requirement) {
+            self.requirements.push(requirement);
+        }
+    }
+
+    pub fn merge(&mut self, other: Self) {
+        self.dependencies.extend(other.dependencies);
+        for requirement in other.requirements {
+            if !self.requirements.contains(&requirement) {
+                self.requirements.push(requirement);
+            }
+        }
+    }
+
+    pub fn into_vec(self) -> Vec<Box<str>> {
+        self.dependencies.into_iter().collect()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn collect_adding_packages<'a, 'env>(
+    dependencies: impl Iterator<Item = (&'a str, &'a DependencyRange)>,
+    locked_dependencies: impl Iterator<Item = LockedDependencyInfo<'a>>,
+    unlocked_packages: impl Iterator<Item = &'a (Box<str>, Option<PackageManifest>)>,
+    get_locked: impl Fn(&str) -> Option<LockedDependencyInfo<'a>>,
+    unity_version: Option<UnityVersion>,
+    env: &'env impl PackageCollection,
+    packages: Vec<PackageInfo<'env>>,
+    options: &'a ResolutionOptions,
+    project_config: &'a ProjectConfig,
+    missing_dependencies: &mut MissingDependencies,
+) -> PackageResolutionResult<'env> {
+    let Some(cache) = env.resolution_cache().filter(|_| options.is_cacheable()) else {
+        return collect_adding_packages_uncached(
+            dependencies,
+            locked_dependencies,
+            unlocked_packages,
+            get_locked,
+            unity_version,
+            env,
+            packages,
+            options,
+            project_config,
+            missing_dependencies,
+        );
+    };
+
+    let dependencies = dependencies.collect::<Vec<_>>();
+    let locked_dependencies = locked_dependencies.collect::<Vec<_>>();
+    let unlocked_packages = unlocked_packages.collect::<Vec<_>>();
+
+    let key = resolution_cache::fingerprint_inputs(
+        &dependencies,
+        &locked_dependencies,
+        &unlocked_packages,
+        unity_version,
+        &packages,
+        options,
+        project_config,
+    );
+
+    if let Some(result) = cache.get(key, env, missing_dependencies) {
+        return result;
+    }
+
+    let mut missing = MissingDependencies::new();
+    let result = collect_adding_packages_uncached(
+        dependencies.into_iter(),
+        locked_dependencies.into_iter(),
+        unlocked_packages.into_iter(),
+        get_locked,
+        unity_version,
+        env,
+        packages,
+        options,
+        project_config,
+        &mut missing,
+    );
+    cache.insert(key, &result, &missing);
+    missing_dependencies.merge(missing);
+    result
+}
+
+#[allow(clippy::too_many_arguments)]
+fn collect_adding_packages_uncached<'a, 'env>(
+    dependencies: impl Iterator<Item = (&'a str, &'a DependencyRange)>,
+    locked_dependencies: impl Iterator<Item = LockedDependencyInfo<'a>>,
+    unlocked_packages: impl Iterator<Item = &'a (Box<str>, Option<PackageManifest>)>,
+    get_locked: impl Fn(&str) -> Option<LockedDependencyInfo<'a>>,
+    unity_version: Option<UnityVersion>,
+    env: &'env impl PackageCollection,
+    packages: Vec<PackageInfo<'env>>,
+    options: &'a ResolutionOptions,
+    project_config: &'a ProjectConfig,
+    missing_dependencies: &mut MissingDependencies,
+) -> PackageResolutionResult<'env> {
+    let context = prepare_context(
+        dependencies,
+        locked_dependencies,
+        unlocked_packages,
+        get_locked,
+        unity_version,
+        env,
+        packages,
+        options,
+        project_config,
+        missing_dependencies,
+    );
+    let context = resolve_pending(context, env, unity_version, options, missing_dependencies);
+    context.build_result(env, unity_version, options)
+}
+
+/// Returns the packages the package depends on directly or transitively, sorted by name.
+///
+/// The versions are chosen by the same resolution as installing the package to an empty project,
+/// without reading or changing any project.
+pub fn dependency_closure<'env>(
+    package: PackageInfo<'env>,
+    env: &'env impl PackageCollection,
+    unity_version: Option<UnityVersion>,
+    options: &ResolutionOptions,
+) -> Result<Vec<PackageInfo<'env>>, ResolvePackageErr> {
+    let mut missing_dependencies = MissingDependencies::new();
+    let result = collect_adding_packages(
+        std::iter::empty(),
+        std::iter::empty(),
+        std::iter::empty(),
+        |_| None,
+        unity_version,
+        env,
+        vec![package],
+        options,
+        &ProjectConfig::default(),
+        &mut missing_dependencies,
+    );
+
+    if !missing_dependencies.is_empty() {
+        return Err(ResolvePackageErr::DependenciesNotFound {
+            dependencies: missing_dependencies.into_vec(),
+        });
+    }
+
+    Ok((result.new_packages.into_iter())
+        .filter(|x| x.name() != package.name())
+        .collect())
+}
+
+/// Returns the highest minimum unity version the packages require,
+/// with the name of the package requiring it.
+///
+/// The requirement is the one used to filter versions by the unity version of the project.
+/// If multiple packages require the same version, the package with the smallest name is returned.
+/// Returns None if no packages have the minimum unity version.
+pub fn minimum_unity_version<'env>(
+    packages: impl IntoIterator<Item = PackageInfo<'env>>,
+) -> Option<(UnityVersion, &'env str)> {
+    (packages.into_iter())
+        .filter_map(|x| {
+            unity_requirement(x.package_json())
+                .minimum()
+                .map(|v| (v, x.name()))
+        })
+        .max_by(|a, b| a.0.cmp(&b.0).then_with(|| b.1.cmp(a.1)))
+}
+
+/// Resolves the root dependencies as if they are the dependencies of an empty project.
+///
+/// The version satisfying each root range is chosen with the options, the latest one by default,
+/// and installed with the dependencies.
+/// This reads nothing but the collection, so this can be used with any collection,
+/// including the in-memory ones for testing the resolution.
+pub fn resolve_dependencies<'a, 'env>(
+    dependencies: impl IntoIterator<Item = (&'a str, &'a DependencyRange)>,
+    env: &'env impl PackageCollection,
+    unity_version: Option<UnityVersion>,
+    options: &'a ResolutionOptions,
+) -> Result<PackageResolutionResult<'env>, ResolvePackageErr> {
+    let dependencies = dependencies.into_iter().collect::<Vec<_>>();
+    let mut missing_dependencies = MissingDependencies::new();
+    let mut to_install = vec![];
+
+    for &(name, range) in &dependencies {
+        if let Some(pkg) = find_root_package(name, range, env, unity_version, options) {
+            to_install.push(pkg);
+        } else {
+            missing_dependencies.add(name);
+        }
+    }
+
+    let result = collect_adding_packages(
+        dependencies.into_iter(),
+        std::iter::empty(),
+        std::iter::empty(),
+        |_| None,
+        unity_version,
+        env,
+        to_install,
+        options,
+        &ProjectConfig::default(),
+        &mut missing_dependencies,
+    );
+
+    if !missing_dependencies.is_empty() {
+        return Err(ResolvePackageErr::DependenciesNotFound {
+            dependencies: missing_dependencies.into_vec(),
+        });
+    }
+
+    Ok(result)
+}
+
+/// Finds the version of the root dependency to install with the options.
+///
+/// Like the dependencies of packages, the pinned or overridden version is the only candidate,
+/// and the prerelease policy, the release channel, and the lowest-version strategy are honored.
+fn find_root_package<'env>(
+    name: &str,
+    range: &DependencyRange,
+    env: &'env impl PackageCollection,
+    unity_version: Option<UnityVersion>,
+    options: &ResolutionOptions,
+) -> Option<PackageInfo<'env>> {
+    if let Some(version) = (options.overrides.get(name)).or_else(|| options.pins.get(name)) {
+        return env.fetch_package(name, version);
+    }
+
+    let range = range.as_range();
+    let selector = |acceptance: PrereleaseAcceptance| {
+        let selector = if options.minimal_versions {
+            VersionSelector::lowest_range_for(unity_version, &range, acceptance)
+        } else {
+            VersionSelector::range_for(unity_version, &range, acceptance)
+        };
+        (selector.published_before(options.published_before))
+            .target_platform(options.target_platform)
+            .target_unity_versions(&options.target_unity_versions)
+    };
+
+    if let Some(channel) = &options.channel {
+        // the release channel decides prereleases by itself
+        return find_in_channel(env, name, selector(PrereleaseAcceptance::Allow), channel);
+    }
+
+    let prerelease_override = options.prerelease_overrides.get(name).copied();
+    let allow_pre = prerelease_override.unwrap_or(options.allow_prerelease || range.contains_pre());
+    if !allow_pre && prerelease_override.is_none() && !options.prerelease_repositories.is_empty() {
+        // prereleases from the repositories allowing them are preferred
+        let selector = (selector(PrereleaseAcceptance::Allow))
+            .prerelease_only_from(Some(options.prerelease_repositories.as_slice()));
+        if let Some(found) = env.find_package_by_name(name, selector) {
+            return Some(found);
+        }
+    }
+    let acceptance = (options.prerelease_matching).allow_or_minimum(allow_pre);
+    env.find_package_by_name(name, selector(acceptance))
+}
+
+/// The result of [`check_compatibility`].
+#[derive(Debug)]
+pub struct CompatibilityReport<'env> {
+    // true if all the packages can be installed together at the versions
+    pub compatible: bool,
+    // the resolution with the packages as the roots. the reasons of the incompatibility are in conflicts
+    pub resolution: PackageResolutionResult<'env>,
+}
+
+/// Checks if the packages can be installed together at the exact versions.
+///
+/// The packages are resolved as the dependencies of an empty project, pinned to the versions.
+/// The packages are compatible if no requirements, from the packages or their dependencies,
+/// conflict with the versions selected.
+pub fn check_compatibility<'a, 'env>(
+    packages: &'a [(&'a str, Version)],
+    env: &'env impl PackageCollection,
+    unity_version: Option<UnityVersion>,
+) -> Result<CompatibilityReport<'env>, ResolvePackageErr> {
+    let mut missing_dependencies = MissingDependencies::new();
+    let mut to_install = vec![];
+
+    for (name, version) in packages {
+        if let Some(pkg) = env.fetch_package(name, version) {
+            to_install.push(pkg);
+        } else {
+            missing_dependencies.add(name);
+        }
+    }
+
+    if !missing_dependencies.is_empty() {
+        return Err(ResolvePackageErr::DependenciesNotFound {
+            dependencies: missing_dependencies.into_vec(),
+        });
+    }
+
+    let ranges = (packages.iter())
+        .map(|(_, version)| DependencyRange::exact(version.clone()))
+        .collect::<Vec<_>>();
+    let options = ResolutionOptions::new().pins(
+        (packages.iter())
+            .map(|(name, version)| (*name, version.clone()))
+            .collect(),
+    );
+
+    let resolution = collect_adding_packages(
+        (packages.iter().map(|(name, _)| *name)).zip(&ranges),
+        std::iter::empty(),
+        std::iter::empty(),
+        |_| None,
+        unity_version,
+        env,
+        to_install,
+        &options,
+        &ProjectConfig::default(),
+        &mut missing_dependencies,
+    );
+
+    if !missing_dependencies.is_empty() {
+        return Err(ResolvePackageErr::DependenciesNotFound {
+            dependencies: missing_dependencies.into_vec(),
+        });
+    }
+
+    Ok(CompatibilityReport {
+        compatible: resolution.conflicts.is_empty(),
+        resolution,
+    })
+}
+
+/// The sorted names of the dependencies differing between the recorded and other dependencies.
+struct DependencyDiff {
+    // the dependencies missing or with another range in the other dependencies
+    only_recorded: Vec<Box<str>>,
+    // the dependencies missing or with another range in the recorded dependencies
+    only_other: Vec<Box<str>>,
+}
+
+/// Compares the recorded dependencies with `other`, returning None if they are the same.
+fn diff_dependencies(
+    recorded: &IndexMap<Box<str>, VersionRange>,
+    other: &IndexMap<Box<str>, VersionRange>,
+) -> Option<DependencyDiff> {
+    fn only_in(
+        a: &IndexMap<Box<str>, VersionRange>,
+        b: &IndexMap<Box<str>, VersionRange>,
+    ) -> Vec<Box<str>> {
+        let mut names = (a.iter())
+            .filter(|(name, range)| b.get(*name) != Some(*range))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+        names
+    }
+
+    let only_recorded = only_in(recorded, other);
+    let only_other = only_in(other, recorded);
+    if only_recorded.is_empty() && only_other.is_empty() {
+        None
+    } else {
+        Some(DependencyDiff {
+            only_recorded,
+            only_other,
+        })
     }
 }
 
+/// Creates the context with the requirements from the project, before resolving the packages.
 #[allow(clippy::too_many_arguments)]
-pub(crate) fn collect_adding_packages<'a, 'env>(
+fn prepare_context<'a, 'env, 'c>(
     dependencies: impl Iterator<Item = (&'a str, &'a DependencyRange)>,
     locked_dependencies: impl Iterator<Item = LockedDependencyInfo<'a>>,
     unlocked_packages: impl Iterator<Item = &'a (Box<str>, Option<PackageManifest>)>,
@@ -431,12 +4426,17 @@ pub(crate) fn collect_adding_packages<'a, 'env>(
     unity_version: Option<UnityVersion>,
     env: &'env impl PackageCollection,
     packages: Vec<PackageInfo<'env>>,
-    allow_prerelease: bool,
+    options: &'a ResolutionOptions,
+    project_config: &'a ProjectConfig,
     missing_dependencies: &mut MissingDependencies,
-) -> PackageResolutionResult<'env> {
-    let mut context = ResolutionContext::<'env, '_>::new(allow_prerelease, packages);
+) -> ResolutionContext<'env, 'c>
+where
+    'a: 'c,
+    'env: 'c,
+{
+    // with the release channel, prerelease versions are chosen without allow_prerelease
+    let allow_prerelease = options.allow_prerelease || options.channel.is_some();
 
-    // first, add dependencies
     let root_dependencies = dependencies
         .into_iter()
         .map(|(name, dependency)| {
@@ -446,7 +4446,10 @@ pub(crate) fn collect_adding_packages<'a, 'env>(
                 allow_pre = min_ver.is_pre();
                 if let Some(locked) = get_locked(name) {
                     allow_pre |= !locked.version().pre.is_empty();
-                    if locked.version() < &min_ver {
+                    // unless the locked version is ignored, the locked version is the minimum
+                    if options.locked_preference != LockedPreference::Ignore
+                        && locked.version() < &min_ver
+                    {
                         min_ver = locked.version().clone();
                     }
                 }
@@ -459,14 +4462,45 @@ pub(crate) fn collect_adding_packages<'a, 'env>(
             (name, range, allow_pre)
         })
         .collect::<Vec<_>>();
+    let locked_dependencies = locked_dependencies.collect::<Vec<_>>();
 
-    for (name, range, allow_pre) in &root_dependencies {
-        context.add_root_dependency(name, range, *allow_pre);
+    // package ids are case-insensitive, so other spellings are renamed to the canonical one
+    let project_names = (root_dependencies.iter().map(|&(name, _, _)| name))
+        .chain(locked_dependencies.iter().map(|x| x.name()))
+        .collect::<HashSet<_>>();
+    let case_aliases = case_aliases(env, &project_names);
+    let packages = (packages.into_iter())
+        .map(|package| {
+            (case_aliases.get(package.name()))
+                .and_then(|&name| env.fetch_package(name, package.version()))
+                .unwrap_or(package)
+        })
+        .collect::<Vec<_>>();
+    let requested_names = (packages.iter()).map(|x| x.name()).collect::<HashSet<_>>();
+
+    let mut context = ResolutionContext::<'env, '_>::new(allow_prerelease, packages);
+    context.prerelease_overrides = options.prerelease_overrides.clone();
+    context.stats = options.collect_stats.then(ResolutionStats::default);
+    context.prerelease_repositories = options.prerelease_repositories.clone();
+    context.prerelease_propagation = options.prerelease_propagation;
+    context.aliases = case_aliases;
+
+    // first, add dependencies
+    for (name, range, allow_pre) in root_dependencies {
+        context.add_root_dependency(name, Cow::Owned(range), allow_pre);
+    }
+
+    // aliases are used only if the collection has the renamed package
+    for (&old, &new) in &options.aliases {
+        if let Some(package) = env.find_packages(new).next() {
+            let new = context.canonical_name(package.name());
+            context.aliases.insert(old.into(), new);
+        }
     }
 
     // then, add locked dependencies info
     for locked in locked_dependencies {
-        context.add_locked_dependency(locked, env);
+        context.add_locked_dependency(locked, env, options);
     }
 
     // add unlocked packages
@@ -474,92 +4508,868 @@ pub(crate) fn collect_adding_packages<'a, 'env>(
         context.add_unlocked_name(unlocked_name.as_ref());
         if let Some(unlocked_package) = unlocked_manifest {
             context.add_unlocked_name(unlocked_package.name());
-            context.add_unlocked_manifest(unlocked_package);
+            context.add_unlocked_manifest(unlocked_package, options);
+        }
+    }
+
+    // the options take precedence over the project config for each package
+    let configured_by_options = |name: &str| {
+        options.vendored.contains_key(name)
+            || options.provided.contains_key(name)
+            || options.pins.contains_key(name)
+            || options.overrides.contains_key(name)
+            || options.excluded.contains(name)
+    };
+
+    // add vendored packages
+    for (name, version) in &options.vendored {
+        context.add_vendored_package(name, version);
+    }
+
+    // add packages managed by the user, kept at the locked versions
+    for name in project_config.user_managed() {
+        let name = name.as_ref();
+        if configured_by_options(name) {
+            continue;
+        }
+        if let Some(&version) = context.locked_versions.get(name) {
+            context.add_vendored_package(name, version);
+            context.user_managed_names.insert(name);
+        }
+    }
+
+    // add packages provided outside of vpm
+    for (name, version) in &options.provided {
+        context.add_provided_package(name, version);
+    }
+
+    // add pinned packages, and replace locked ones with other versions
+    let project_pins = (project_config.pins().iter())
+        .map(|(name, version)| (name.as_ref(), version))
+        .filter(|&(name, _)| !configured_by_options(name));
+    let pins = (options.pins.iter())
+        .filter(|&(name, _)| !options.overrides.contains_key(name))
+        .map(|(&name, version)| (name, version));
+    let overrides = (options.overrides.iter()).map(|(&name, version)| (name, version));
+    context.overridden_names = options.overrides.keys().copied().collect();
+    for (name, version) in pins.chain(overrides).chain(project_pins) {
+        context.add_pinned_package(name, version);
+        let current = context.dependencies[name].current;
+        if current.is_some_and(|current| current != version) {
+            match env.fetch_package(name, version) {
+                Some(package) => context.request_package(package),
+                None => missing_dependencies.add(name),
+            }
+        }
+    }
+
+    context.excluded_names = (options.excluded.iter().copied())
+        .chain(
+            (project_config.exclude().iter())
+                .map(|name| name.as_ref())
+                .filter(|&name| !configured_by_options(name)),
+        )
+        .collect();
+    context.prerelease_matching = options.prerelease_matching;
+    context.enabled_features = options.enabled_features.clone();
+    // the dependencies depend on the aliases and the features
+    context.set_queue_order(options.queue_order);
+
+    // hold the locked packages not requested as the pinned packages
+    if options.resolve_subset {
+        let held = (context.locked_versions.iter())
+            .filter(|&(&name, _)| {
+                !requested_names.contains(name)
+                    && !options.must_include.contains(&name)
+                    && !context.pins.contains_key(name)
+                    && !context.excluded_names.contains(name)
+                    && !context.user_managed_names.contains(name)
+                    && !configured_by_options(name)
+            })
+            .map(|(&name, &version)| (name, version))
+            .collect::<Vec<_>>();
+        for (name, version) in held {
+            context.add_pinned_package(name, version);
+        }
+    }
+
+    // reselect locked root dependencies the locked version does not satisfy
+    if options.locked_preference == LockedPreference::Ignore {
+        let locked_roots = (context.dependencies[""].requirements.keys().copied())
+            .filter(|x| context.dependencies[x].current.is_some())
+            .collect::<Vec<_>>();
+        for name in locked_roots {
+            if !context.add_must_include_package(name, env, unity_version, options) {
+                missing_dependencies.add(name);
+            }
+        }
+    }
+
+    // add packages that must be included
+    for &name in &options.must_include {
+        if !context.add_must_include_package(name, env, unity_version, options) {
+            missing_dependencies.add(name);
+        }
+    }
+
+    context
+}
+
+/// Finds the package ids spelled with different cases in the collection, and returns the aliases
+/// from the other spellings to the canonical one.
+///
+/// The spelling used by the project is canonical if the collection has it, otherwise the spelling
+/// of the package found first in the collection is.
+fn case_aliases<'env>(
+    env: &'env impl PackageCollection,
+    project_names: &HashSet<&str>,
+) -> HashMap<Box<str>, &'env str> {
+    let mut spellings = IndexMap::<String, Vec<&'env str>>::new();
+    for package in env.get_all_packages() {
+        let names = (spellings.entry(package.name().to_ascii_lowercase())).or_default();
+        if !names.contains(&package.name()) {
+            names.push(package.name());
+        }
+    }
+
+    let mut aliases = HashMap::new();
+    for names in spellings.values().filter(|x| x.len() > 1) {
+        let canonical = (names.iter().copied())
+            .find(|x| project_names.contains(x))
+            .unwrap_or(names[0]);
+        log::warn!(
+            "package id {canonical} is spelled differently among repositories: {}; using {canonical}",
+            names.join(", ")
+        );
+        for &name in names {
+            if name != canonical {
+                aliases.insert(name.into(), canonical);
+            }
+        }
+    }
+    aliases
+}
+
+/// Resolves the pending packages in the context.
+fn resolve_pending<'env, 'a>(
+    mut context: ResolutionContext<'env, 'a>,
+    env: &'env impl PackageCollection,
+    unity_version: Option<UnityVersion>,
+    options: &ResolutionOptions,
+    missing_dependencies: &mut MissingDependencies,
+) -> ResolutionContext<'env, 'a>
+where
+    'env: 'a,
+{
+    if options.backtracking {
+        let initial = context.clone();
+        let mut missing = MissingDependencies::new();
+        process_pending_queue(&mut context, env, unity_version, options, &mut missing);
+        context = backtrack(context, initial, env, unity_version, options, &mut missing);
+        missing_dependencies.merge(missing);
+    } else {
+        process_pending_queue(
+            &mut context,
+            env,
+            unity_version,
+            options,
+            missing_dependencies,
+        );
+    }
+
+    if options.include_recommended {
+        context.add_recommended_packages(env, unity_version, options);
+    }
+
+    context.activate_optional_dependencies(env, unity_version, options, missing_dependencies);
+
+    context
+}
+
+/// The package resolution which can be updated without resolving everything again.
+///
+/// Adding packages or root dependencies continues from the previous resolution, so packages
+/// already resolved are not resolved again unless the requirements for them change.
+/// Removing packages or root dependencies resolves again from the requirements of the project,
+/// without reading the locked packages again.
+///
+/// Use [`UnityProject::incremental_resolution`] to create this.
+///
+/// [`UnityProject::incremental_resolution`]: crate::UnityProject::incremental_resolution
+pub struct IncrementalResolution<'env, 'o, E: PackageCollection> {
+    env: &'env E,
+    unity_version: Option<UnityVersion>,
+    options: &'env ResolutionOptions<'o>,
+    // the context with the requirements from the project only
+    base: ResolutionContext<'env, 'env>,
+    base_missing: MissingDependencies,
+    // the context with the changes resolved. None if it should be rebuilt from base
+    resolved: Option<(ResolutionContext<'env, 'env>, MissingDependencies)>,
+    // name -> range of root dependencies changed. None for removed ones
+    root_changes: IndexMap<&'env str, Option<VersionRange>>,
+    requested: Vec<PackageInfo<'env>>,
+}
+
+impl<'env, 'o, E: PackageCollection> IncrementalResolution<'env, 'o, E> {
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn new(
+        dependencies: impl Iterator<Item = (&'env str, &'env DependencyRange)>,
+        locked_dependencies: impl Iterator<Item = LockedDependencyInfo<'env>>,
+        unlocked_packages: impl Iterator<Item = &'env (Box<str>, Option<PackageManifest>)>,
+        get_locked: impl Fn(&str) -> Option<LockedDependencyInfo<'env>>,
+        unity_version: Option<UnityVersion>,
+        env: &'env E,
+        options: &'env ResolutionOptions<'o>,
+        project_config: &'env ProjectConfig,
+    ) -> Self {
+        let mut base_missing = MissingDependencies::new();
+        let base = prepare_context(
+            dependencies,
+            locked_dependencies,
+            unlocked_packages,
+            get_locked,
+            unity_version,
+            env,
+            Vec::new(),
+            options,
+            project_config,
+            &mut base_missing,
+        );
+
+        Self {
+            env,
+            unity_version,
+            options,
+            base,
+            base_missing,
+            resolved: None,
+            root_changes: IndexMap::new(),
+            requested: Vec::new(),
+        }
+    }
+
+    /// Adds or replaces the root dependency.
+    ///
+    /// This only adds the requirement. Request the package with [`Self::request_package`] to
+    /// install a version satisfying the range.
+    pub fn add_root_dependency(&mut self, name: &'env str, range: VersionRange) {
+        if let Some((resolved, _)) = &mut self.resolved {
+            resolved.update_root_dependency(name, range.clone());
+        }
+        self.root_changes.insert(name, Some(range));
+    }
+
+    /// Adds or replaces the root dependencies at once.
+    ///
+    /// Ranges for the same package are merged into one matching both. If no version can match
+    /// both ranges, nothing is added and [`AddPackageErr::IncompatibleRootDependencies`] is returned.
+    pub fn add_root_dependencies(
+        &mut self,
+        dependencies: impl IntoIterator<Item = (&'env str, VersionRange)>,
+    ) -> Result<(), AddPackageErr> {
+        for (name, range) in merge_root_dependencies(dependencies)? {
+            self.add_root_dependency(name, range);
+        }
+        Ok(())
+    }
+
+    /// Removes the root dependency.
+    pub fn remove_root_dependency(&mut self, name: &'env str) {
+        self.root_changes.insert(name, None);
+        self.resolved = None;
+    }
+
+    /// Requests the package to be installed.
+    pub fn request_package(&mut self, package: PackageInfo<'env>) {
+        if let Some((resolved, _)) = &mut self.resolved {
+            resolved.request_package(package);
+        }
+        self.requested.retain(|x| x.name() != package.name());
+        self.requested.push(package);
+    }
+
+    /// Cancels the request of the package.
+    pub fn remove_requested_package(&mut self, name: &str) {
+        self.requested.retain(|x| x.name() != name);
+        self.resolved = None;
+    }
+
+    /// Resolves the changes since the previous call.
+    pub fn resolve(&mut self) -> Result<PackageResolutionResult<'env>, ResolvePackageErr> {
+        let (context, mut missing) = match self.resolved.take() {
+            Some(resolved) => resolved,
+            None => {
+                let mut context = self.base.clone();
+                for (&name, range) in &self.root_changes {
+                    match range {
+                        Some(range) => context.update_root_dependency(name, range.clone()),
+                        None => context.remove_root_dependency(name),
+                    }
+                }
+                for &package in &self.requested {
+                    context.request_package(package);
+                }
+                (context, self.base_missing.clone())
+            }
+        };
+
+        let context = resolve_pending(
+            context,
+            self.env,
+            self.unity_version,
+            self.options,
+            &mut missing,
+        );
+        self.resolved = Some((context.clone(), missing.clone()));
+
+        if !missing.is_empty() {
+            return Err(ResolvePackageErr::DependenciesNotFound {
+                dependencies: missing.into_vec(),
+            });
+        }
+
+        Ok(context.build_result(self.env, self.unity_version, self.options))
+    }
+}
+
+/// Merges the ranges of root dependencies for the same package.
+///
+/// Returns error for the first range incompatible with the ranges before it.
+fn merge_root_dependencies<'a>(
+    dependencies: impl IntoIterator<Item = (&'a str, VersionRange)>,
+) -> Result<IndexMap<&'a str, VersionRange>, AddPackageErr> {
+    let mut merged = IndexMap::<&str, VersionRange>::new();
+    for (name, range) in dependencies {
+        match merged.get_mut(name) {
+            Some(existing) => match existing.intersection(&range) {
+                Some(intersection) => *existing = intersection,
+                None => {
+                    return Err(AddPackageErr::IncompatibleRootDependencies {
+                        package_name: name.into(),
+                        range: existing.clone(),
+                        other_range: range,
+                    });
+                }
+            },
+            None => {
+                merged.insert(name, range);
+            }
         }
     }
+    Ok(merged)
+}
+
+/// Returns the release channel of the version.
+///
+/// The channel is the leading prerelease identifier, or `stable` for versions without prerelease.
+fn release_channel(version: &Version) -> &str {
+    if version.is_stable() {
+        "stable"
+    } else {
+        version.pre.as_str().split('.').next().unwrap()
+    }
+}
+
+/// Finds the newest package satisfying the selector in the release channel.
+///
+/// The oldest one is found instead if the selector prefers the lowest version.
+fn find_in_channel<'env>(
+    env: &'env impl PackageCollection,
+    name: &str,
+    selector: VersionSelector,
+    channel: &str,
+) -> Option<PackageInfo<'env>> {
+    let candidates = (env.find_packages(name))
+        .filter(|x| selector.satisfies_package(x))
+        .filter(|x| release_channel(x.version()) == channel);
+    let preferred = |x: &PackageInfo| selector.is_preferred(x.package_json());
+    let deprioritized = |x: &PackageInfo| selector.is_deprioritized(x.package_json());
+    if selector.prefers_lowest() {
+        candidates.min_by_key(|x| (!preferred(x), deprioritized(x), x.version()))
+    } else {
+        candidates.max_by_key(|x| (preferred(x), !deprioritized(x), x.version()))
+    }
+}
 
+/// Returns the number of leading dot-separated segments shared by the names.
+fn shared_segments(a: &str, b: &str) -> usize {
+    (a.split('.').zip(b.split('.')))
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// The number of recently processed packages reported when the iteration limit is exceeded.
+const RECENTLY_PROCESSED_LEN: usize = 10;
+
+fn process_pending_queue<'env, 'a>(
+    context: &mut ResolutionContext<'env, 'a>,
+    env: &'env impl PackageCollection,
+    unity_version: Option<UnityVersion>,
+    options: &ResolutionOptions,
+    missing_dependencies: &mut MissingDependencies,
+) where
+    'env: 'a,
+{
     while let Some((x, force)) = context.pending_queue.next_package() {
+        if context.aborted_at.is_some() {
+            break;
+        }
+        if context.processed_count >= options.iteration_limit {
+            log::debug!("processing package {}: iteration limit exceeded", x.name());
+            context.limit_exceeded = true;
+            break;
+        }
+        context.processed_count += 1;
+        if let Some(stats) = &mut context.stats {
+            stats.queue_pops += 1;
+        }
+        if context.recently_processed.len() == RECENTLY_PROCESSED_LEN {
+            context.recently_processed.pop_front();
+        }
+        context.recently_processed.push_back(x.name());
+
         log::debug!("processing package {} version {}", x.name(), x.version());
+        if let Some(sink) = options.event_sink {
+            sink.on_package_processed(x);
+        }
         let name = x.name();
-        let vpm_dependencies = &x.vpm_dependencies();
+        let vpm_dependencies = context.effective_dependencies(x);
+
+        let added = context.add_package(x, force, options);
+        let dependencies_len = context.dependencies.len();
+        if let Some(stats) = &mut context.stats {
+            stats.add_package_calls += 1;
+            if added {
+                stats.packages_added += 1;
+            } else {
+                stats.packages_skipped += 1;
+            }
+            stats.peak_dependencies = stats.peak_dependencies.max(dependencies_len);
+        }
 
-        if context.add_package(x, force) {
+        if added {
             // add new dependencies
-            for (dependency, range) in vpm_dependencies.iter() {
+            for &(dependency, range) in &vpm_dependencies {
+                if context.aborted_at.is_some() {
+                    break;
+                }
+                let dependency = context.canonical_name(dependency);
+                let rewritten = context.rewritten_range(dependency, name);
+                let range = rewritten.as_ref().unwrap_or(range);
+                let capped = (context.version_caps.get(dependency)).map(|cap| range.below(cap));
+                let range = capped.as_ref().unwrap_or(range);
                 log::debug!("processing package {name}: dependency {dependency} version {range}");
+                if let Some(sink) = options.event_sink {
+                    sink.on_dependency_considered(name, dependency, range);
+                }
 
-                if context.should_add_package(dependency, range) {
-                    fn get_package<'env>(
-                        env: &'env impl PackageCollection,
-                        dependency: &str,
-                        unity_version: Option<UnityVersion>,
-                        range: &VersionRange,
-                        allow_prerelease: PrereleaseAcceptance,
-                    ) -> Option<PackageInfo<'env>> {
-                        env.find_package_by_name(
-                            dependency,
-                            VersionSelector::range_for(unity_version, range, allow_prerelease),
-                        )
-                    }
+                if let Some(path) = context.find_cycle(name, dependency) {
+                    log::debug!("processing package {name}: dependency {dependency}: cycle found");
+                    context.dependency_cycles.push(path);
+                }
 
+                if let Some(reason) = context.skip_reason(dependency, range) {
+                    if let Some(sink) = options.event_sink {
+                        sink.on_skip_reason(name, dependency, &reason);
+                    }
+                } else {
                     struct PackageFinder<'env, 'a, C: PackageCollection> {
                         dependency: &'a str,
                         env: &'env C,
-                        range: &'env VersionRange,
+                        range: &'a VersionRange,
+                        lowest: bool,
+                        published_before: Option<SystemTime>,
+                        target_platform: Option<&'a str>,
+                        target_unity_versions: &'a [UnityVersion],
+                        preferred: Option<&'a Version>,
+                        prerelease_repositories: &'a [&'a str],
+                        lookups: Cell<usize>,
                     }
 
                     impl<'env, C: PackageCollection> PackageFinder<'env, '_, C> {
+                        fn selector(
+                            &self,
+                            unity_version: Option<UnityVersion>,
+                            allow_prerelease: PrereleaseAcceptance,
+                        ) -> VersionSelector<'_> {
+                            let selector = if self.lowest {
+                                VersionSelector::lowest_range_for(
+                                    unity_version,
+                                    self.range,
+                                    allow_prerelease,
+                                )
+                            } else {
+                                VersionSelector::range_for(
+                                    unity_version,
+                                    self.range,
+                                    allow_prerelease,
+                                )
+                            };
+                            selector
+                                .published_before(self.published_before)
+                                .target_platform(self.target_platform)
+                                .target_unity_versions(self.target_unity_versions)
+                                .prefer_version(self.preferred)
+                        }
+
                         fn find(
                             &self,
                             unity_version: Option<UnityVersion>,
                             allow_prerelease: PrereleaseAcceptance,
                         ) -> Option<PackageInfo<'env>> {
-                            get_package(
+                            self.lookups.set(self.lookups.get() + 1);
+                            self.env.find_package_by_name(
+                                self.dependency,
+                                self.selector(unity_version, allow_prerelease),
+                            )
+                        }
+
+                        /// Finds among stable versions and prerelease versions from the repositories
+                        /// allowing prereleases. Returns None if no repository allows prereleases.
+                        fn find_in_prerelease_repositories(
+                            &self,
+                            unity_version: Option<UnityVersion>,
+                        ) -> Option<PackageInfo<'env>> {
+                            if self.prerelease_repositories.is_empty() {
+                                return None;
+                            }
+                            self.lookups.set(self.lookups.get() + 1);
+                            self.env.find_package_by_name(
+                                self.dependency,
+                                self.selector(unity_version, PrereleaseAcceptance::Allow)
+                                    .prerelease_only_from(Some(self.prerelease_repositories)),
+                            )
+                        }
+
+                        fn find_in_channel(
+                            &self,
+                            unity_version: Option<UnityVersion>,
+                            channel: &str,
+                        ) -> Option<PackageInfo<'env>> {
+                            self.lookups.set(self.lookups.get() + 1);
+                            find_in_channel(
                                 self.env,
                                 self.dependency,
-                                unity_version,
-                                self.range,
-                                allow_prerelease,
+                                self.selector(unity_version, PrereleaseAcceptance::Allow),
+                                channel,
                             )
                         }
                     }
 
-                    let finder = PackageFinder {
-                        dependency,
-                        env,
-                        range,
-                    };
+                    let mut retry_range = None::<VersionRange>;
+                    let mut attempt = 0;
 
-                    let found;
-                    if allow_prerelease {
-                        // prerelease is allowed, so we find the best match
-                        found = (finder.find(unity_version, PrereleaseAcceptance::Allow))
-                            .or_else(|| finder.find(None, PrereleaseAcceptance::Allow));
-                    } else if x.version().is_pre() {
-                        // if the package is prerelease, allow prerelease, but prefer stable
-                        found = (finder.find(unity_version, PrereleaseAcceptance::Deny))
-                            .or_else(|| finder.find(unity_version, PrereleaseAcceptance::Minimum))
-                            .or_else(|| finder.find(unity_version, PrereleaseAcceptance::Allow))
-                            .or_else(|| finder.find(None, PrereleaseAcceptance::Deny))
-                            .or_else(|| finder.find(None, PrereleaseAcceptance::Minimum))
-                            .or_else(|| finder.find(None, PrereleaseAcceptance::Allow));
-                    } else {
-                        // if the package is stable, prefer stable, and allow minimum
-                        found = (finder.find(unity_version, PrereleaseAcceptance::Deny))
-                            .or_else(|| finder.find(unity_version, PrereleaseAcceptance::Minimum))
-                            .or_else(|| finder.find(None, PrereleaseAcceptance::Deny))
-                            .or_else(|| finder.find(None, PrereleaseAcceptance::Minimum));
-                    }
+                    loop {
+                        let range = retry_range.as_ref().unwrap_or(range);
+                        let finder = PackageFinder {
+                            dependency,
+                            env,
+                            range,
+                            lowest: options.minimal_versions,
+                            published_before: options.published_before,
+                            target_platform: options.target_platform,
+                            target_unity_versions: &options.target_unity_versions,
+                            preferred: (context.locked_versions.get(dependency).copied())
+                                .filter(|_| options.locked_preference == LockedPreference::Prefer)
+                                .filter(|&locked| {
+                                    context.satisfies_requirements(dependency, locked)
+                                }),
+                            prerelease_repositories: &options.prerelease_repositories,
+                            lookups: Cell::new(0),
+                        };
+
+                        let prerelease_override =
+                            options.prerelease_overrides.get(dependency).copied();
+                        let propagates_pre = context.propagates_pre_to(dependency);
+                        let allow_prerelease = options.allow_prerelease && propagates_pre;
+
+                        let found;
+                        if let Some(&pin) = context.pins.get(dependency) {
+                            // the pinned version is the only candidate
+                            finder.lookups.set(1);
+                            found = env.fetch_package(dependency, pin);
+                        } else if let Some(channel) = &options.channel {
+                            // the release channel decides prereleases by itself
+                            found = (finder.find_in_channel(unity_version, channel))
+                                .or_else(|| finder.find_in_channel(None, channel));
+                        } else if prerelease_override == Some(false) {
+                            // prerelease is denied for the package
+                            found = (finder.find(unity_version, PrereleaseAcceptance::Deny))
+                                .or_else(|| finder.find(None, PrereleaseAcceptance::Deny));
+                        } else if allow_prerelease || prerelease_override == Some(true) {
+                            // prerelease is allowed, so we find the best match
+                            found = (finder.find(unity_version, PrereleaseAcceptance::Allow))
+                                .or_else(|| finder.find(None, PrereleaseAcceptance::Allow));
+                        } else if x.version().is_pre() && propagates_pre {
+                            // if the package is prerelease, allow prerelease, but prefer stable
+                            found = (finder.find_in_prerelease_repositories(unity_version))
+                                .or_else(|| finder.find(unity_version, PrereleaseAcceptance::Deny))
+                                .or_else(|| {
+                                    finder
+                                        .find(unity_version, options.prerelease_matching.minimum())
+                                })
+                                .or_else(|| finder.find(unity_version, PrereleaseAcceptance::Allow))
+                                .or_else(|| finder.find(None, PrereleaseAcceptance::Deny))
+                                .or_else(|| {
+                                    finder.find(None, options.prerelease_matching.minimum())
+                                })
+                                .or_else(|| finder.find(None, PrereleaseAcceptance::Allow));
+                        } else {
+                            // if the package is stable, prefer stable, and allow minimum
+                            found = (finder.find_in_prerelease_repositories(unity_version))
+                                .or_else(|| finder.find(unity_version, PrereleaseAcceptance::Deny))
+                                .or_else(|| {
+                                    finder
+                                        .find(unity_version, options.prerelease_matching.minimum())
+                                })
+                                .or_else(|| finder.find(None, PrereleaseAcceptance::Deny))
+                                .or_else(|| {
+                                    finder.find(None, options.prerelease_matching.minimum())
+                                });
+                        }
+                        if let Some(stats) = &mut context.stats {
+                            stats.package_lookups += finder.lookups.get();
+                        }
+
+                        if let Some(found) = found {
+                            if let Some(found) = context.arbitrate_priority(
+                                dependency,
+                                name,
+                                range,
+                                found,
+                                options,
+                                env,
+                                unity_version,
+                            ) {
+                                let found = context.tiebreak_by_repository(env, found, options);
+                                let Some(found) = context.decide_violation(
+                                    dependency,
+                                    name,
+                                    range,
+                                    found,
+                                    options,
+                                    env,
+                                    unity_version,
+                                ) else {
+                                    log::debug!(
+                                        "processing package {name}: dependency {dependency}: aborted by the violation handler"
+                                    );
+                                    context.aborted_at = Some(dependency);
+                                    break;
+                                };
+                                // the version requiring newer unity is found only if no version is for the project
+                                let required = unity_requirement(found.package_json()).minimum();
+                                if let (Some(unity), Some(required)) = (unity_version, required) {
+                                    if unity < required {
+                                        log::debug!(
+                                            "processing package {name}: dependency {dependency}: requires unity {required}"
+                                        );
+                                        context.unity_gated.push((found.name(), required));
+                                    }
+                                }
+                                if let Some(sink) = options.event_sink {
+                                    sink.on_package_selected(name, found);
+                                }
+                                context.parents.insert(found.name(), name);
+                                let dependencies = context.queued_dependencies(found);
+                                context
+                                    .pending_queue
+                                    .add_pending_package(found, dependencies);
+                            } else {
+                                log::debug!(
+                                    "processing package {name}: dependency {dependency}: sacrificing requirement for higher-priority ones"
+                                );
+                                context.sacrificed_requirements.push((dependency, name));
+                            }
+                            break;
+                        }
+
+                        let conflict = ConflictContext {
+                            package: name,
+                            package_version: x.version(),
+                            dependency,
+                            range,
+                            attempt,
+                        };
 
-                    if let Some(found) = found {
-                        context.pending_queue.add_pending_package(found);
-                    } else {
-                        missing_dependencies.add(dependency);
+                        let decision = if attempt < MAX_CONFLICT_RETRIES {
+                            options.conflict_strategy.on_conflict(&conflict)
+                        } else {
+                            log::warn!(
+                                "processing package {name}: dependency {dependency}: giving up after {attempt} retries"
+                            );
+                            ConflictDecision::GiveUp
+                        };
+
+                        match decision {
+                            ConflictDecision::RetryWithRange(range) => {
+                                log::debug!(
+                                    "processing package {name}: dependency {dependency}: retrying with {range}"
+                                );
+                                retry_range = Some(range);
+                                attempt += 1;
+                            }
+                            ConflictDecision::DropRequirement => {
+                                log::debug!(
+                                    "processing package {name}: dependency {dependency}: dropping requirement"
+                                );
+                                break;
+                            }
+                            ConflictDecision::GiveUp => {
+                                // the package is known but no version matches the range
+                                // regardless of unity or prerelease
+                                let mut versions = env.find_packages(dependency).peekable();
+                                let known = versions.peek().is_some();
+                                if known
+                                    && !versions.any(|x| {
+                                        range.match_pre(x.version(), PrereleaseAcceptance::Allow)
+                                    })
+                                {
+                                    context.unsatisfiable_requirements.push((
+                                        dependency,
+                                        name,
+                                        range.clone(),
+                                    ));
+                                }
+                                // the versions in the range are all for other platforms
+                                if let Some(platform) = options.target_platform {
+                                    let mut in_range = (env.find_packages(dependency))
+                                        .filter(|x| {
+                                            range
+                                                .match_pre(x.version(), PrereleaseAcceptance::Allow)
+                                        })
+                                        .peekable();
+                                    if in_range.peek().is_some()
+                                        && in_range.all(|x| {
+                                            !x.package_json().is_compatible_with_platform(platform)
+                                        })
+                                    {
+                                        context.platform_incompatible.push(dependency);
+                                    }
+                                }
+                                // the versions in the range are not compatible with all the target unity versions
+                                if !options.target_unity_versions.is_empty() {
+                                    let mut in_range = (env.find_packages(dependency))
+                                        .filter(|x| {
+                                            range
+                                                .match_pre(x.version(), PrereleaseAcceptance::Allow)
+                                        })
+                                        .peekable();
+                                    if in_range.peek().is_some()
+                                        && in_range.all(|x| {
+                                            !(options.target_unity_versions.iter()).all(|&unity| {
+                                                unity_compatible(x.package_json(), unity)
+                                            })
+                                        })
+                                    {
+                                        context.target_unity_incompatible.push(dependency);
+                                    }
+                                }
+                                // the versions in the range are all yanked
+                                let mut in_range = (env.find_packages(dependency))
+                                    .filter(|x| {
+                                        range.match_pre(x.version(), PrereleaseAcceptance::Allow)
+                                    })
+                                    .peekable();
+                                if in_range.peek().is_some() && in_range.all(|x| x.is_yanked()) {
+                                    context.all_candidates_yanked.push(dependency);
+                                }
+                                missing_dependencies.add_required(dependency, name, range);
+                                context.dead_ends.push(name);
+                                break;
+                            }
+                        }
                     }
                 }
             }
         }
     }
+}
+
+/// The maximum number of retries by the backtracking resolution
+const BACKTRACK_LIMIT: usize = 64;
+
+/// Retries resolving with older versions of packages causing missing dependencies or conflicts.
+///
+/// The packages explicitly requested are never replaced.
+/// Returns the first resolution without missing dependencies nor conflicts,
+/// or the original resolution if no such resolution is found.
+fn backtrack<'env, 'a>(
+    resolved: ResolutionContext<'env, 'a>,
+    initial: ResolutionContext<'env, 'a>,
+    env: &'env impl PackageCollection,
+    unity_version: Option<UnityVersion>,
+    options: &ResolutionOptions,
+    missing_dependencies: &mut MissingDependencies,
+) -> ResolutionContext<'env, 'a>
+where
+    'env: 'a,
+{
+    let mut caps = HashMap::new();
+    let mut attempt = None::<(ResolutionContext<'env, 'a>, MissingDependencies)>;
+
+    for _ in 0..BACKTRACK_LIMIT {
+        let (context, missing) = match &attempt {
+            Some((context, missing)) => (context, missing),
+            None => (&resolved, &*missing_dependencies),
+        };
+
+        // retrying does not help if the resolution stopped at the iteration limit or by the handler
+        let conflicts = context.collect_conflicts();
+        if context.limit_exceeded
+            || context.aborted_at.is_some()
+            || missing.is_empty() && conflicts.is_empty()
+        {
+            return match attempt {
+                Some((context, missing)) => {
+                    *missing_dependencies = missing;
+                    context
+                }
+                None => resolved,
+            };
+        }
+
+        // the packages whose dependencies caused the dead end
+        let mut culprits = context.dead_ends.clone();
+        for sources in conflicts.values() {
+            for source in sources {
+                if let Some((&name, _)) =
+                    context.dependencies.get_key_value(source.package.as_ref())
+                {
+                    culprits.push(name);
+                }
+            }
+        }
+
+        let culprit = culprits.into_iter().find_map(|name| {
+            if context.requested_names.contains(name) {
+                return None;
+            }
+            let using = context.dependencies.get(name)?.using?;
+            Some((using.name(), using.version()))
+        });
+
+        let Some((culprit, version)) = culprit else {
+            log::debug!("backtracking: no package to backtrack");
+            break;
+        };
+
+        log::debug!("backtracking: retrying with {culprit} older than {version}");
+        caps.insert(culprit, version.clone());
+
+        let mut retry = initial.clone();
+        let mut retry_missing = MissingDependencies::new();
+        retry.version_caps = caps.clone();
+        // the stats count the work of all the attempts
+        retry.stats = context.stats.clone();
+        process_pending_queue(&mut retry, env, unity_version, options, &mut retry_missing);
+        attempt = Some((retry, retry_missing));
+    }
 
-    context.build_result()
+    // no resolution without dead ends is found, so keep the original one
+    let mut resolved = resolved;
+    if let Some((attempt, _)) = attempt {
+        resolved.stats = attempt.stats;
+    }
+    resolved
 }
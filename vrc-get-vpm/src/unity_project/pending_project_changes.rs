@@ -1,6 +1,7 @@
 use crate::io::{DirEntry, ProjectIo};
-use crate::traits::AbortCheck;
+use crate::traits::{AbortCheck, CancellationToken};
 use crate::unity_project::find_legacy_assets::collect_legacy_assets;
+use crate::unity_project::package_resolution::PackageResolutionResult;
 use crate::utils::walk_dir_relative;
 use crate::version::DependencyRange;
 use crate::{PackageInfo, UnityProject, unity_compatible};
@@ -25,14 +26,21 @@ use std::sync::OnceLock;
 /// - Apply collected changes
 ///
 /// This is done to ask users before removing packages
+///
+/// Collecting modifications, like `add_package_request`, `remove_request`, or `resolve_request`,
+/// only reads the project and never writes any file, so the changes can be previewed without
+/// touching the project. The changes are written by `apply_pending_changes`.
 #[derive(Debug)]
 pub struct PendingProjectChanges<'env> {
     pub(crate) package_changes: HashMap<Box<str>, PackageChange<'env>>,
 
     pub(crate) remove_legacy_files: Vec<(Box<Path>, &'env str)>,
     pub(crate) remove_legacy_folders: Vec<(Box<Path>, &'env str)>,
+    pub(crate) refused_legacy_assets: Vec<(Box<str>, &'env str)>,
 
     pub(crate) conflicts: HashMap<Box<str>, ConflictInfo>,
+
+    pub(crate) resolution: Option<PackageResolutionResult<'env>>,
 }
 
 #[derive(Debug)]
@@ -122,6 +130,9 @@ impl ConflictInfo {
 pub(crate) struct Builder<'env> {
     package_changes: HashMap<Box<str>, PackageChange<'env>>,
     conflicts: HashMap<Box<str>, ConflictInfo>,
+    resolution: Option<PackageResolutionResult<'env>>,
+    // if true, locked packages no longer used are kept
+    keep_unused: bool,
 }
 
 impl<'env> Builder<'env> {
@@ -129,9 +140,21 @@ impl<'env> Builder<'env> {
         Self {
             package_changes: HashMap::new(),
             conflicts: HashMap::new(),
+            resolution: None,
+            keep_unused: false,
         }
     }
 
+    pub fn keep_unused(&mut self) -> &mut Self {
+        self.keep_unused = true;
+        self
+    }
+
+    pub fn set_resolution(&mut self, resolution: PackageResolutionResult<'env>) -> &mut Self {
+        self.resolution = Some(resolution);
+        self
+    }
+
     pub fn add_to_dependencies(&mut self, name: Box<str>, version: DependencyRange) -> &mut Self {
         match self.package_changes.entry(name) {
             Entry::Occupied(mut e) => match e.get_mut() {
@@ -335,6 +358,9 @@ impl<'env> Builder<'env> {
 
             remove_legacy_files: vec![],
             remove_legacy_folders: vec![],
+            refused_legacy_assets: vec![],
+
+            resolution: self.resolution,
         }
     }
 
@@ -362,9 +388,11 @@ impl<'env> Builder<'env> {
             }
         }
 
-        debug!("Finding unused packages");
+        if !self.keep_unused {
+            debug!("Finding unused packages");
 
-        self.mark_and_sweep_packages(unity_project);
+            self.mark_and_sweep_packages(unity_project);
+        }
 
         debug!("Collecting legacy assets");
 
@@ -379,6 +407,9 @@ impl<'env> Builder<'env> {
 
             remove_legacy_files: legacy_assets.files,
             remove_legacy_folders: legacy_assets.folders,
+            refused_legacy_assets: legacy_assets.refused,
+
+            resolution: self.resolution,
         }
     }
 
@@ -471,9 +502,20 @@ impl<'env> Builder<'env> {
             // keep installing packages even if the package is not used by any dependencies
             let package_changes = installing_packages.iter().map(Box::as_ref);
 
+            // packages managed by the user are never removed as unused
+            let user_managed = (unity_project.project_config().user_managed().iter())
+                .map(Box::as_ref)
+                .filter(|name| {
+                    self.package_changes
+                        .get(*name)
+                        .and_then(|change| change.as_remove())
+                        .is_none()
+                });
+
             let entry_points = unlocked_dependencies
                 .chain(dependencies)
-                .chain(package_changes);
+                .chain(package_changes)
+                .chain(user_managed);
 
             mark_recursive(entry_points, |dep_name| {
                 if let Some(to_install) = self
@@ -519,9 +561,31 @@ impl PendingProjectChanges<'_> {
         self.remove_legacy_folders.as_slice()
     }
 
+    /// Returns the (declared path, package) of the `legacyFolders` and `legacyFiles` refused
+    /// since the paths may escape the project, like absolute paths or paths with `..`.
+    ///
+    /// Those are never removed, even if an asset with the declared GUID is found.
+    pub fn refused_legacy_assets(&self) -> &[(Box<str>, &str)] {
+        self.refused_legacy_assets.as_slice()
+    }
+
     pub fn conflicts(&self) -> &HashMap<Box<str>, ConflictInfo> {
         &self.conflicts
     }
+
+    /// Returns the names of the packages to be removed with the reasons, sorted by the name.
+    pub fn removed_packages(&self) -> Vec<(&str, RemoveReason)> {
+        let mut removed = (self.package_changes.iter())
+            .filter_map(|(name, change)| Some((name.as_ref(), change.as_remove()?.reason())))
+            .collect::<Vec<_>>();
+        removed.sort_by_key(|&(name, _)| name);
+        removed
+    }
+
+    /// The result of the package resolution if the changes are collected with resolution
+    pub fn resolution(&self) -> Option<&PackageResolutionResult<'_>> {
+        self.resolution.as_ref()
+    }
 }
 
 static TEMP_DIR: &str = "Temp";
@@ -535,6 +599,131 @@ impl<IO: ProjectIo> UnityProject<IO> {
         &mut self,
         env: &'env Env,
         request: PendingProjectChanges<'env>,
+    ) -> io::Result<()> {
+        self.apply_pending_changes_impl(env, request, None).await
+    }
+
+    /// Applies the changes like [`Self::apply_pending_changes`], but can be cancelled with the token.
+    ///
+    /// Unlike [`Self::apply_pending_changes`], packages are installed before saving the manifest.
+    /// If installing packages fails or the token is cancelled while downloading or extracting,
+    /// the installed and partially extracted packages are removed, the removed packages are
+    /// restored, and the manifest is not changed. Cancellation results in an error with
+    /// [`io::ErrorKind::Interrupted`].
+    pub async fn apply_pending_changes_cancellable<'env, Env: PackageInstaller>(
+        &mut self,
+        env: &'env Env,
+        request: PendingProjectChanges<'env>,
+        cancellation: &CancellationToken,
+    ) -> io::Result<()> {
+        self.apply_pending_changes_impl(env, request, Some(cancellation))
+            .await
+    }
+
+    /// Applies the changes like [`Self::apply_pending_changes`], and then calls `on_post_install`
+    /// with the packages installed with declared post-install actions.
+    ///
+    /// The actions are never run by vrc-get. This only surfaces the declarations so the caller
+    /// can prompt the user to run them. The callback is not called if applying the changes fails.
+    pub async fn apply_pending_changes_with_post_install<'env, Env: PackageInstaller>(
+        &mut self,
+        env: &'env Env,
+        request: PendingProjectChanges<'env>,
+        mut on_post_install: impl FnMut(PackageInfo<'env>, &'env str),
+    ) -> io::Result<()> {
+        let mut declared = (request.package_changes.values())
+            .filter_map(|change| change.as_install()?.package)
+            .filter_map(|package| Some((package, package.post_install()?)))
+            .collect::<Vec<_>>();
+        declared.sort_by_key(|(package, _)| package.name());
+
+        self.apply_pending_changes_impl(env, request, None).await?;
+
+        for (package, action) in declared {
+            on_post_install(package, action);
+        }
+        Ok(())
+    }
+
+    /// Applies the changes to the manifest and saves it, without downloading nor extracting packages.
+    ///
+    /// This is useful to commit the lockfile resolved in CI and install packages later, for example
+    /// with [`Self::resolve_request`]. Before changing the manifest, this checks all the packages
+    /// to be installed can be downloaded with [`PackageInstaller::check_package`], so the
+    /// lockfile never refers to unavailable packages.
+    /// Nothing in the `Packages` folder nor legacy assets are changed, so removed packages are
+    /// left on disk until they're removed by hand.
+    ///
+    /// [`Self::resolve_request`]: UnityProject::resolve_request
+    pub async fn apply_pending_changes_lockfile_only<'env, Env: PackageInstaller>(
+        &mut self,
+        env: &'env Env,
+        request: PendingProjectChanges<'env>,
+    ) -> io::Result<()> {
+        let installs = (request.package_changes.values())
+            .filter_map(|change| change.as_install()?.package)
+            .collect::<Vec<_>>();
+
+        let checked = join_all(installs.iter().map(|&package| env.check_package(package))).await;
+        for (package, result) in installs.iter().zip(checked) {
+            if let Err(err) = result {
+                return Err(io::Error::new(
+                    err.kind(),
+                    format!(
+                        "{}@{} is not available: {err}",
+                        package.name(),
+                        package.version()
+                    ),
+                ));
+            }
+        }
+
+        self.apply_to_manifest(&request);
+        self.save().await
+    }
+
+    /// Applies the changes to the manifest without saving it.
+    fn apply_to_manifest(&mut self, request: &PendingProjectChanges) {
+        let mut remove_names = Vec::new();
+
+        for (name, change) in &request.package_changes {
+            match change {
+                PackageChange::Install(change) => {
+                    if let Some(package) = change.package {
+                        if change.add_to_locked {
+                            let repository = (package.repo())
+                                .and_then(|x| x.url())
+                                .or(package.git_source().map(|x| x.url()));
+                            self.manifest.add_locked(
+                                package.name(),
+                                package.version().clone(),
+                                package.vpm_dependencies().clone(),
+                                repository.map(|x| x.as_str().into()),
+                            );
+                            if let Some(git) = package.git_source() {
+                                self.manifest.set_git_commit(package.name(), git.commit());
+                            }
+                        }
+                    }
+
+                    if let Some(version) = &change.to_dependencies {
+                        self.manifest.add_dependency(name, version.clone());
+                    }
+                }
+                PackageChange::Remove(_) => {
+                    remove_names.push(name.as_ref());
+                }
+            }
+        }
+
+        self.manifest.remove_packages(remove_names.into_iter());
+    }
+
+    async fn apply_pending_changes_impl<'env, Env: PackageInstaller>(
+        &mut self,
+        env: &'env Env,
+        request: PendingProjectChanges<'env>,
+        cancellation: Option<&CancellationToken>,
     ) -> io::Result<()> {
         /*
         Apply pending changes consists of following steps:
@@ -563,6 +752,9 @@ impl<IO: ProjectIo> UnityProject<IO> {
           - If we install packages before removing legacy assets,
             failing to remove legacy assets will duplicate legacy assets.
           - Both cases are not desirable, but the latter is less harmful.
+        - If the operation is cancellable, we install packages before applying changes to manifest
+          so that cancelling doesn't leave the manifest changed. Instead, we roll back installed
+          packages and removed packages on failure.
          */
 
         let mut installs = Vec::new();
@@ -594,7 +786,7 @@ impl<IO: ProjectIo> UnityProject<IO> {
 
         self.io.create_dir_all(remove_temp_dir).await?;
 
-        move_packages_to_temp(
+        let moved = move_packages_to_temp(
             &self.io,
             (remove_names.iter().copied())
                 .chain(installs.iter().map(|x| x.name()))
@@ -603,29 +795,20 @@ impl<IO: ProjectIo> UnityProject<IO> {
         )
         .await?;
 
-        // apply changes to manifest
-        for (name, change) in &request.package_changes {
-            match change {
-                PackageChange::Install(change) => {
-                    if let Some(package) = change.package {
-                        if change.add_to_locked {
-                            self.manifest.add_locked(
-                                package.name(),
-                                package.version().clone(),
-                                package.vpm_dependencies().clone(),
-                            );
-                        }
-                    }
-
-                    if let Some(version) = &change.to_dependencies {
-                        self.manifest.add_dependency(name, version.clone());
-                    }
+        if cancellation.is_some() {
+            // install packages before changing manifest
+            if let Err(err) = install_packages(&self.io, env, &installs, cancellation).await {
+                for package in &installs {
+                    let package_dir = format!("Packages/{}", package.name());
+                    self.io.remove_dir_all(package_dir.as_ref()).await.ok();
                 }
-                PackageChange::Remove(_) => {}
+                restore_remove(&self.io, remove_temp_dir, moved.iter().copied()).await;
+                return Err(err);
             }
         }
 
-        self.manifest.remove_packages(remove_names.iter().copied());
+        // apply changes to manifest
+        self.apply_to_manifest(&request);
 
         // save manifest
 
@@ -633,7 +816,9 @@ impl<IO: ProjectIo> UnityProject<IO> {
 
         // add packages
 
-        install_packages(&self.io, env, &installs).await?;
+        if cancellation.is_none() {
+            install_packages(&self.io, env, &installs, None).await?;
+        }
 
         self.io.remove_dir_all(remove_temp_dir).await.ok();
         self.io.remove_dir_all(PKG_TEMP_DIR.as_ref()).await.ok();
@@ -772,8 +957,9 @@ async fn install_packages<Env: PackageInstaller>(
     io: &impl ProjectIo,
     env: &Env,
     packages: &[PackageInfo<'_>],
+    cancellation: Option<&CancellationToken>,
 ) -> io::Result<()> {
-    let abort = AbortCheck::new();
+    let abort = AbortCheck::new(cancellation);
     let mut error_store = OnceLock::new();
 
     // resolve all packages
@@ -0,0 +1,154 @@
+use crate::unity_project::{
+    AddPackageErr, AddPackageOperation, PendingProjectChanges, RemovePackageErr, ResolutionOptions,
+    ResolvePackageErr,
+};
+use crate::version::Version;
+use crate::{PackageCollection, PackageInfo, PackageInstaller, UnityProject, io};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The operation on the project planned with [`UnityProject::plan`].
+#[non_exhaustive]
+pub enum ProjectOperation<'a, 'env> {
+    /// Adds or upgrades the packages like [`UnityProject::add_package_request_with_options`].
+    Add {
+        packages: &'a [PackageInfo<'env>],
+        operation: AddPackageOperation,
+        options: &'a ResolutionOptions<'a>,
+    },
+    /// Removes the packages and the orphaned dependencies like [`UnityProject::remove_request`].
+    Remove { packages: &'a [&'a str] },
+    /// Installs the locked packages missing in the project like [`UnityProject::resolve_request`].
+    Resolve,
+}
+
+/// The changes to the project planned without touching any file.
+///
+/// The plan can be previewed with [`Self::changes`], and applied with [`UnityProject::apply`].
+#[derive(Debug)]
+pub struct ResolutionPlan<'env> {
+    changes: PendingProjectChanges<'env>,
+    // name -> version of the locked packages the plan is made on
+    locked: HashMap<Box<str>, Version>,
+}
+
+impl<'env> ResolutionPlan<'env> {
+    /// Returns the changes to be applied, including the conflicts and the resolution.
+    pub fn changes(&self) -> &PendingProjectChanges<'env> {
+        &self.changes
+    }
+
+    /// Returns the changes without checking the project is not changed since planning.
+    pub fn into_changes(self) -> PendingProjectChanges<'env> {
+        self.changes
+    }
+}
+
+/// The error planning the operation with [`UnityProject::plan`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum PlanErr {
+    Add(AddPackageErr),
+    Remove(RemovePackageErr),
+    Resolve(ResolvePackageErr),
+}
+
+impl fmt::Display for PlanErr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlanErr::Add(err) => fmt::Display::fmt(err, f),
+            PlanErr::Remove(err) => fmt::Display::fmt(err, f),
+            PlanErr::Resolve(err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+impl std::error::Error for PlanErr {}
+
+/// The error returned by [`UnityProject::apply`] if the locked packages are changed since planning.
+///
+/// This is wrapped in [`io::Error`] with [`io::ErrorKind::InvalidInput`].
+#[derive(Debug)]
+pub struct ProjectChangedSincePlan {
+    // the names of the packages locked, unlocked, or locked to another version, sorted
+    pub packages: Vec<Box<str>>,
+}
+
+impl fmt::Display for ProjectChangedSincePlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the project is changed since planning: {}",
+            self.packages.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ProjectChangedSincePlan {}
+
+impl<IO: io::ProjectIo> UnityProject<IO> {
+    /// Plans the operation on the project.
+    ///
+    /// This only reads the project and never writes any file, so the plan can be previewed
+    /// before applying it with [`Self::apply`].
+    pub async fn plan<'env>(
+        &self,
+        env: &'env impl PackageCollection,
+        operation: ProjectOperation<'_, 'env>,
+    ) -> Result<ResolutionPlan<'env>, PlanErr> {
+        let changes = match operation {
+            ProjectOperation::Add {
+                packages,
+                operation,
+                options,
+            } => self
+                .add_package_request_with_options(env, packages, operation, options)
+                .await
+                .map_err(PlanErr::Add)?,
+            ProjectOperation::Remove { packages } => self
+                .remove_request(packages)
+                .await
+                .map_err(PlanErr::Remove)?,
+            ProjectOperation::Resolve => {
+                self.resolve_request(env).await.map_err(PlanErr::Resolve)?
+            }
+        };
+
+        Ok(ResolutionPlan {
+            changes,
+            locked: self.locked_versions(),
+        })
+    }
+
+    /// Applies the plan made with [`Self::plan`] like [`Self::apply_pending_changes`].
+    ///
+    /// This fails with [`ProjectChangedSincePlan`] without changing anything if the locked
+    /// packages are changed since planning, since the plan may be outdated.
+    pub async fn apply<'env>(
+        &mut self,
+        installer: &'env impl PackageInstaller,
+        plan: ResolutionPlan<'env>,
+    ) -> io::Result<()> {
+        let locked = self.locked_versions();
+        let mut changed = (plan.locked.iter())
+            .filter(|(name, version)| locked.get(*name) != Some(*version))
+            .chain((locked.iter()).filter(|(name, _)| !plan.locked.contains_key(*name)))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<_>>();
+        if !changed.is_empty() {
+            changed.sort();
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                ProjectChangedSincePlan { packages: changed },
+            ));
+        }
+
+        self.apply_pending_changes(installer, plan.changes).await
+    }
+
+    fn locked_versions(&self) -> HashMap<Box<str>, Version> {
+        (self.manifest.all_locked())
+            .map(|locked| (locked.name().into(), locked.version().clone()))
+            .collect()
+    }
+}
@@ -0,0 +1,53 @@
+use crate::io;
+use crate::io::ProjectIo;
+use crate::utils::load_json_or_default;
+use crate::version::Version;
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+const CONFIG_PATH: &str = "Packages/vrc-get-project.json";
+
+/// The per-project configuration of the resolution in `Packages/vrc-get-project.json`.
+///
+/// The configuration is applied to every resolution of the project in addition to the
+/// [`ResolutionOptions`]. For each package, the options take precedence: if the options pin,
+/// exclude, vendor, or provide the package, the entries for the package in this file are ignored.
+///
+/// [`ResolutionOptions`]: crate::unity_project::ResolutionOptions
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectConfig {
+    #[serde(default)]
+    user_managed: Vec<Box<str>>,
+    #[serde(default)]
+    pins: IndexMap<Box<str>, Version>,
+    #[serde(default)]
+    exclude: Vec<Box<str>>,
+}
+
+impl ProjectConfig {
+    pub(super) async fn load(io: &impl ProjectIo) -> io::Result<Self> {
+        load_json_or_default(io, CONFIG_PATH.as_ref()).await
+    }
+
+    /// The locked packages managed by the user.
+    ///
+    /// Those are kept at the locked versions like vendored packages, and never removed as unused.
+    pub fn user_managed(&self) -> &[Box<str>] {
+        &self.user_managed
+    }
+
+    /// The packages pinned to the exact versions. See [`ResolutionOptions::pins`].
+    ///
+    /// [`ResolutionOptions::pins`]: crate::unity_project::ResolutionOptions::pins
+    pub fn pins(&self) -> &IndexMap<Box<str>, Version> {
+        &self.pins
+    }
+
+    /// The packages excluded from the resolution. See [`ResolutionOptions::exclude`].
+    ///
+    /// [`ResolutionOptions::exclude`]: crate::unity_project::ResolutionOptions::exclude
+    pub fn exclude(&self) -> &[Box<str>] {
+        &self.exclude
+    }
+}
@@ -0,0 +1,106 @@
+use crate::io::ProjectIo;
+use crate::unity_project::package_resolution::{ConflictSource, minimum_unity_version};
+use crate::unity_project::{OutdatedPackage, ResolutionOptions, ResolvePackageErr};
+use crate::version::{UnityVersion, Version};
+use crate::{PackageCollection, UnityProject};
+use std::collections::HashMap;
+
+/// The result of [`UnityProject::project_health`]
+///
+/// The sub-reports from the resolution are None if resolving the project failed, for example
+/// since some repository is offline. Other sub-reports are collected even in that case.
+#[derive(Debug)]
+pub struct HealthReport {
+    // the error resolving the project, if any
+    pub resolution_error: Option<ResolvePackageErr>,
+    // conflict dependency -> requirements not satisfied by the selected version
+    pub conflicts: Option<HashMap<Box<str>, Vec<ConflictSource>>>,
+    // list of names of locked packages no longer required by any package
+    pub orphans: Option<Vec<Box<str>>>,
+    // (package, version, message) of deprecated versions selected since no other version satisfies
+    // the requirements
+    #[allow(clippy::type_complexity)]
+    pub deprecated_selected: Option<Vec<(Box<str>, Version, Box<str>)>>,
+    // (package, version) of prerelease versions selected without prerelease requested at the root
+    pub transitive_prereleases: Option<Vec<(Box<str>, Version)>>,
+    // locked packages with newer version excluded by the range of another package
+    pub held_back: Vec<OutdatedPackage>,
+    // (minimum unity, package) of the locked package requiring the newest unity, if any
+    pub minimum_unity_version: Option<(UnityVersion, Box<str>)>,
+    // locked packages not found in the collection, which are not checked or considered for
+    // the minimum unity. orphans are not listed
+    pub missing_packages: Vec<Box<str>>,
+}
+
+impl HealthReport {
+    /// Returns true if the project resolves without conflicts and all locked packages are found.
+    pub fn is_ok(&self) -> bool {
+        self.resolution_error.is_none()
+            && self.conflicts.as_ref().is_none_or(|x| x.is_empty())
+            && self.missing_packages.is_empty()
+    }
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Diagnoses the dependency graph of the project with the packages in the `collection`.
+    ///
+    /// The project is resolved incrementally with the default options, first as is to find orphans,
+    /// then with the locked packages requested again to check their dependencies.
+    /// This does not change the project.
+    pub fn project_health(&self, collection: &impl PackageCollection) -> HealthReport {
+        let options = ResolutionOptions::new();
+
+        let mut report = HealthReport {
+            resolution_error: None,
+            conflicts: None,
+            orphans: None,
+            deprecated_selected: None,
+            transitive_prereleases: None,
+            held_back: Vec::new(),
+            minimum_unity_version: None,
+            missing_packages: Vec::new(),
+        };
+
+        // orphans are found before requesting the locked packages, which makes them used
+        let mut resolution = self.incremental_resolution(collection, &options);
+        match resolution.resolve() {
+            Ok(resolution) => report.orphans = Some(resolution.into_details().unused_locked),
+            Err(err) => report.resolution_error = Some(err),
+        }
+
+        // then the locked packages are requested again to check the dependencies of them
+        for dependency in self.locked_packages() {
+            if (report.orphans.iter().flatten()).any(|x| x.as_ref() == dependency.name()) {
+                continue;
+            }
+            match collection.fetch_package(dependency.name(), dependency.version()) {
+                Some(package) => resolution.request_package(package),
+                None => report.missing_packages.push(dependency.name().into()),
+            }
+        }
+        report.missing_packages.sort();
+
+        if report.resolution_error.is_none() {
+            match resolution.resolve() {
+                Ok(resolution) => {
+                    let details = resolution.into_details();
+                    report.conflicts = Some(details.conflicts);
+                    report.deprecated_selected = Some(details.deprecated_selected);
+                    report.transitive_prereleases = Some(details.transitive_prereleases);
+                }
+                Err(err) => report.resolution_error = Some(err),
+            }
+        }
+
+        report.held_back = (self.check_outdated(collection).into_iter())
+            .filter(|x| x.is_held_back())
+            .collect();
+
+        let locked = (self.locked_packages())
+            .filter_map(|x| collection.fetch_package(x.name(), x.version()));
+        report.minimum_unity_version =
+            minimum_unity_version(locked).map(|(unity, package)| (unity, package.into()));
+
+        report
+    }
+}
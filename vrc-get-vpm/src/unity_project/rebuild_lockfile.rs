@@ -0,0 +1,100 @@
+use crate::io::ProjectIo;
+use crate::version::Version;
+use crate::{PackageCollection, UnityProject, VersionSelector};
+
+/// The result of [`UnityProject::rebuild_lockfile`]
+#[derive(Debug, Default)]
+pub struct RebuildLockfileResult {
+    // packages installed without lock entries, now locked with the package.json on disk
+    pub locked: Vec<(Box<str>, Version)>,
+    // locked packages whose lock entries did not match the package.json on disk
+    pub updated: Vec<(Box<str>, Version)>,
+    // directories in Packages which can't be matched to any known package. those are not locked.
+    pub unmatched: Vec<Box<str>>,
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Reconstructs the locked packages of `vpm-manifest.json` from the packages on disk.
+    ///
+    /// This is useful for projects created by the legacy VPM, which may have packages installed
+    /// in `Packages` without lock entries, or lock entries not matching installed packages.
+    /// Packages not found in the `collection` are reported as unmatched and not locked since
+    /// those may be embedded UPM packages.
+    /// You have to call [`UnityProject::save`] to write the rebuilt lockfile.
+    pub fn rebuild_lockfile(
+        &mut self,
+        collection: &impl PackageCollection,
+    ) -> RebuildLockfileResult {
+        let mut result = RebuildLockfileResult::default();
+
+        for (name, installed) in &self.installed_packages {
+            let Some(locked) = self.manifest.get_locked(name) else {
+                continue;
+            };
+            if locked.version() == installed.version()
+                && locked.dependencies() == installed.vpm_dependencies()
+            {
+                continue;
+            }
+
+            let repository = collection
+                .find_package_by_name(name, VersionSelector::specific_version(installed.version()))
+                .and_then(|pkg| pkg.repo()?.url())
+                .map(|url| url.as_str().into())
+                .or_else(|| locked.repository_url().map(Into::into));
+
+            self.manifest.add_locked(
+                name,
+                installed.version().clone(),
+                installed.vpm_dependencies().clone(),
+                repository,
+            );
+            result
+                .updated
+                .push((name.clone(), installed.version().clone()));
+        }
+
+        let mut unlocked_packages = Vec::with_capacity(self.unlocked_packages.len());
+
+        for (dir_name, manifest) in std::mem::take(&mut self.unlocked_packages) {
+            let found = manifest
+                .as_ref()
+                .filter(|manifest| manifest.name() == dir_name.as_ref())
+                .and_then(|manifest| {
+                    collection.find_package_by_name(
+                        manifest.name(),
+                        VersionSelector::specific_version(manifest.version()),
+                    )
+                });
+
+            let (Some(manifest), Some(found)) = (manifest.as_ref(), found) else {
+                result.unmatched.push(dir_name.clone());
+                unlocked_packages.push((dir_name, manifest));
+                continue;
+            };
+
+            let repository = (found.repo())
+                .and_then(|repo| repo.url())
+                .map(|url| url.as_str().into());
+
+            self.manifest.add_locked(
+                manifest.name(),
+                manifest.version().clone(),
+                manifest.vpm_dependencies().clone(),
+                repository,
+            );
+            result
+                .locked
+                .push((dir_name.clone(), manifest.version().clone()));
+            self.installed_packages.insert(dir_name, manifest.clone());
+        }
+
+        self.unlocked_packages = unlocked_packages;
+
+        result.updated.sort();
+        result.locked.sort();
+        result.unmatched.sort();
+
+        result
+    }
+}
@@ -1,8 +1,11 @@
 use std::fmt;
+use std::path::PathBuf;
 
 use crate::io::ProjectIo;
+use crate::package_manifest::LooseManifest;
 use crate::unity_project::package_resolution::MissingDependencies;
 use crate::unity_project::{PendingProjectChanges, pending_project_changes};
+use crate::utils::{PathBufExt, try_load_json};
 use crate::{PackageCollection, UnityProject, VersionSelector};
 
 #[derive(Debug)]
@@ -37,6 +40,10 @@ impl fmt::Display for ReinstalPackagesError {
 impl std::error::Error for ReinstalPackagesError {}
 
 impl<IO: ProjectIo> UnityProject<IO> {
+    /// Creates the request to download and extract the locked versions of the packages again.
+    ///
+    /// This doesn't resolve anything, so versions in the lockfile are kept and
+    /// no other packages are installed nor removed.
     pub async fn reinstall_request<'env>(
         &self,
         env: &'env impl PackageCollection,
@@ -44,6 +51,7 @@ impl<IO: ProjectIo> UnityProject<IO> {
     ) -> Result<PendingProjectChanges<'env>, ReinstalPackagesError> {
         let mut changes = pending_project_changes::Builder::new();
         let mut missing_dependencies = MissingDependencies::new();
+        changes.keep_unused();
 
         for &package in packages {
             let Some(locked) = self.manifest.get_locked(package) else {
@@ -70,4 +78,48 @@ impl<IO: ProjectIo> UnityProject<IO> {
 
         Ok(changes.build_resolve(self).await)
     }
+
+    /// Creates the request to reinstall all the locked packages.
+    pub async fn reinstall_all_request<'env>(
+        &self,
+        env: &'env impl PackageCollection,
+    ) -> Result<PendingProjectChanges<'env>, ReinstalPackagesError> {
+        let packages = self
+            .manifest
+            .all_locked()
+            .map(|x| x.name())
+            .collect::<Vec<_>>();
+        self.reinstall_request(env, &packages).await
+    }
+
+    /// Returns the names of the packages whose `package.json` on disk doesn't declare the locked version.
+    ///
+    /// This reads the `package.json` again, so you can use this to check the packages
+    /// installed by [`UnityProject::apply_pending_changes`].
+    /// Packages not locked are ignored.
+    pub async fn verify_installed(&self, packages: &[&str]) -> Vec<Box<str>> {
+        let mut broken = Vec::new();
+
+        for &package in packages {
+            let Some(locked) = self.manifest.get_locked(package) else {
+                continue;
+            };
+
+            let package_json_path = PathBuf::from("Packages")
+                .joined(package)
+                .joined("package.json");
+            let installed = try_load_json::<LooseManifest>(&self.io, &package_json_path)
+                .await
+                .ok()
+                .flatten();
+
+            let matches = installed
+                .is_some_and(|x| x.0.name() == locked.name() && x.0.version() == locked.version());
+            if !matches {
+                broken.push(package.into());
+            }
+        }
+
+        broken
+    }
 }
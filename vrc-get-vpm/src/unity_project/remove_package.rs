@@ -11,9 +11,24 @@ impl<IO: ProjectIo> UnityProject<IO> {
     /// Remove specified package from self project.
     ///
     /// This doesn't look packages not listed in vpm-maniefst.json.
+    /// Locked packages no longer required by remaining packages are also removed.
     pub async fn remove_request(
         &self,
         remove: &[&str],
+    ) -> Result<PendingProjectChanges<'static>, RemovePackageErr> {
+        self.remove_request_with_orphans(remove, true).await
+    }
+
+    /// Remove specified package from self project like [`Self::remove_request`].
+    ///
+    /// If `remove_orphans` is true, locked packages only required by the removed packages are
+    /// also removed with [`RemoveReason::Unused`], otherwise they are kept.
+    /// Packages still required by remaining packages are never removed.
+    /// See [`PendingProjectChanges::removed_packages`] for the packages to be removed.
+    pub async fn remove_request_with_orphans(
+        &self,
+        remove: &[&str],
+        remove_orphans: bool,
     ) -> Result<PendingProjectChanges<'static>, RemovePackageErr> {
         use RemovePackageErr::*;
 
@@ -58,6 +73,10 @@ impl<IO: ProjectIo> UnityProject<IO> {
 
         // there's no conflicts. So do remove
 
+        if !remove_orphans {
+            changes.keep_unused();
+        }
+
         for x in remove {
             changes.remove(x.into(), RemoveReason::Requested);
         }
@@ -0,0 +1,364 @@
+use crate::unity_project::package_resolution::{
+    MissingDependencies, PackageResolutionResult, ResolutionOptions,
+};
+use crate::unity_project::{LockedDependencyInfo, ProjectConfig};
+use crate::version::{DependencyRange, UnityVersion, Version, VersionRange};
+use crate::{PackageCollection, PackageInfo, PackageManifest, VersionSelector};
+use indexmap::IndexMap;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// The cache of resolution results used by [`CachedPackageCollection`].
+///
+/// The cache remembers the fingerprint of the collection it is used with, and the cached results
+/// are discarded when it's used with a collection with other packages.
+/// You can keep the cache across reloading the collection with [`CachedPackageCollection::into_cache`]
+/// and [`CachedPackageCollection::with_cache`].
+#[derive(Default)]
+pub struct ResolutionCache {
+    fingerprint: u64,
+    entries: RefCell<HashMap<u64, CachedResolution>>,
+}
+
+struct CachedResolution {
+    // the second fingerprint of the inputs to detect collisions of the key
+    check: u64,
+    result: PackageResolutionResult<'static>,
+    // (name, identity) of new_packages
+    packages: Vec<(Box<str>, u64)>,
+    missing_dependencies: MissingDependencies,
+}
+
+impl ResolutionCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of cached results.
+    pub fn len(&self) -> usize {
+        self.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.borrow().is_empty()
+    }
+
+    /// Discards all cached results.
+    pub fn clear(&self) {
+        self.entries.borrow_mut().clear();
+    }
+
+    pub(crate) fn get<'env>(
+        &self,
+        key: ResolutionKey,
+        env: &'env impl PackageCollection,
+        missing_dependencies: &mut MissingDependencies,
+    ) -> Option<PackageResolutionResult<'env>> {
+        let entries = self.entries.borrow();
+        let cached = (entries.get(&key.hash)).filter(|x| x.check == key.check)?;
+
+        let mut packages = Vec::with_capacity(cached.packages.len());
+        for (name, identity) in &cached.packages {
+            let package = (env.find_packages(name)).find(|&x| package_identity(x) == *identity)?;
+            packages.push(package);
+        }
+
+        missing_dependencies.merge(cached.missing_dependencies.clone());
+        Some(cached.result.clone().with_packages(packages))
+    }
+
+    pub(crate) fn insert(
+        &self,
+        key: ResolutionKey,
+        result: &PackageResolutionResult,
+        missing_dependencies: &MissingDependencies,
+    ) {
+        let packages = (result.new_packages.iter())
+            .map(|&x| (x.name().into(), package_identity(x)))
+            .collect();
+        let cached = CachedResolution {
+            check: key.check,
+            result: result.clone().with_packages(Vec::new()),
+            packages,
+            missing_dependencies: missing_dependencies.clone(),
+        };
+        self.entries.borrow_mut().insert(key.hash, cached);
+    }
+}
+
+/// The package collection reusing resolution results for unchanged inputs.
+///
+/// This is useful for GUIs resolving the same project again and again. The result is reused if
+/// the dependencies, the locked and unlocked packages, the unity version, the requested packages,
+/// and the options are the same as the previous resolution.
+/// Results with options with callbacks, non-deterministic conflict strategies, or event sinks are
+/// never cached.
+pub struct CachedPackageCollection<'a, C: PackageCollection> {
+    base: &'a C,
+    cache: ResolutionCache,
+}
+
+impl<'a, C: PackageCollection> CachedPackageCollection<'a, C> {
+    pub fn new(base: &'a C) -> Self {
+        Self::with_cache(base, ResolutionCache::new())
+    }
+
+    /// Creates the collection with the cache used with another collection.
+    ///
+    /// Cached results are kept only if the collection has the same packages as the previous one.
+    pub fn with_cache(base: &'a C, mut cache: ResolutionCache) -> Self {
+        let fingerprint = fingerprint_collection(base);
+        if cache.fingerprint != fingerprint {
+            cache.entries.get_mut().clear();
+            cache.fingerprint = fingerprint;
+        }
+        Self { base, cache }
+    }
+
+    pub fn cache(&self) -> &ResolutionCache {
+        &self.cache
+    }
+
+    pub fn into_cache(self) -> ResolutionCache {
+        self.cache
+    }
+}
+
+impl<C: PackageCollection> PackageCollection for CachedPackageCollection<'_, C> {
+    fn get_curated_packages(
+        &self,
+        version_selector: VersionSelector,
+    ) -> impl Iterator<Item = PackageInfo> {
+        self.base.get_curated_packages(version_selector)
+    }
+
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        self.base.get_all_packages()
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        self.base.find_packages(package)
+    }
+
+    fn find_packages_by_name(&self, package: &str) -> Vec<PackageInfo> {
+        self.base.find_packages_by_name(package)
+    }
+
+    fn find_package_by_name(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+    ) -> Option<PackageInfo> {
+        self.base.find_package_by_name(package, package_selector)
+    }
+
+    fn fetch_package(&self, package: &str, version: &Version) -> Option<PackageInfo> {
+        self.base.fetch_package(package, version)
+    }
+
+    fn find_package_by_name_with_priority(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+        priority: &[&str],
+    ) -> Option<PackageInfo> {
+        (self.base).find_package_by_name_with_priority(package, package_selector, priority)
+    }
+
+    fn resolution_cache(&self) -> Option<&ResolutionCache> {
+        Some(&self.cache)
+    }
+}
+
+fn package_identity(package: PackageInfo) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    package.name().hash(&mut hasher);
+    package.version().hash(&mut hasher);
+    package.hash_source(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_ranges(ranges: &IndexMap<Box<str>, VersionRange>, state: &mut impl Hasher) {
+    ranges.len().hash(state);
+    for (name, range) in ranges {
+        name.hash(state);
+        range.to_string().hash(state);
+    }
+}
+
+fn hash_manifest(manifest: &PackageManifest, state: &mut impl Hasher) {
+    manifest.name().hash(state);
+    manifest.version().hash(state);
+    manifest.is_yanked().hash(state);
+    manifest.is_deprecated().hash(state);
+    manifest.deprecation_message().hash(state);
+    (manifest.unity())
+        .map(|x| (x.major(), x.minor()))
+        .hash(state);
+    manifest.license().hash(state);
+    (manifest.supported_unity().iter())
+        .map(ToString::to_string)
+        .for_each(|x| x.hash(state));
+    manifest.legacy_packages().hash(state);
+    manifest.aliases().hash(state);
+    manifest.published_at().hash(state);
+    manifest.incompatible_platforms().hash(state);
+    (manifest.unity_excludes().iter())
+        .map(ToString::to_string)
+        .for_each(|x| x.hash(state));
+    hash_ranges(manifest.vpm_dependencies(), state);
+    hash_ranges(manifest.recommended_packages(), state);
+    for feature in manifest.features() {
+        feature.hash(state);
+        hash_ranges(manifest.feature_dependencies(feature).unwrap(), state);
+    }
+}
+
+/// Computes the fingerprint of the packages in the collection regardless of their order.
+fn fingerprint_collection(collection: &impl PackageCollection) -> u64 {
+    let mut packages = (collection.get_all_packages())
+        .map(|package| {
+            let mut hasher = DefaultHasher::new();
+            package_identity(package).hash(&mut hasher);
+            hash_manifest(package.package_json(), &mut hasher);
+            hasher.finish()
+        })
+        .collect::<Vec<_>>();
+    packages.sort_unstable();
+
+    let mut hasher = DefaultHasher::new();
+    packages.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_sorted<K: Ord + Hash, V: Hash>(
+    map: impl IntoIterator<Item = (K, V)>,
+    state: &mut impl Hasher,
+) {
+    let mut entries = map.into_iter().collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    entries.hash(state);
+}
+
+/// The fingerprints of the inputs of the resolution.
+///
+/// The results are keyed by `hash`, and `check` is compared before returning the cached result
+/// so a collision of `hash` doesn't return the result for other inputs.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ResolutionKey {
+    hash: u64,
+    check: u64,
+}
+
+/// Computes the fingerprints of the inputs of the resolution.
+pub(crate) fn fingerprint_inputs(
+    dependencies: &[(&str, &DependencyRange)],
+    locked_dependencies: &[LockedDependencyInfo],
+    unlocked_packages: &[&(Box<str>, Option<PackageManifest>)],
+    unity_version: Option<UnityVersion>,
+    packages: &[PackageInfo],
+    options: &ResolutionOptions,
+    project_config: &ProjectConfig,
+) -> ResolutionKey {
+    let hash_inputs = |state: &mut DefaultHasher| {
+        dependencies.len().hash(state);
+        for (name, range) in dependencies {
+            name.hash(state);
+            range.to_string().hash(state);
+        }
+
+        locked_dependencies.len().hash(state);
+        for locked in locked_dependencies {
+            locked.name().hash(state);
+            locked.version().hash(state);
+            locked.repository_url().hash(state);
+            locked.git_commit().hash(state);
+            hash_ranges(locked.dependencies(), state);
+        }
+
+        unlocked_packages.len().hash(state);
+        for (name, manifest) in unlocked_packages {
+            name.hash(state);
+            manifest.is_some().hash(state);
+            if let Some(manifest) = manifest {
+                hash_manifest(manifest, state);
+            }
+        }
+
+        unity_version.map(|x| x.to_string()).hash(state);
+
+        packages.len().hash(state);
+        for &package in packages {
+            package_identity(package).hash(state);
+        }
+
+        options.allow_prerelease.hash(state);
+        // deterministic conflict strategies decide only with the context, so the reference is enough
+        std::ptr::hash(options.conflict_strategy, state);
+        options.must_include.hash(state);
+        hash_sorted(&options.requirement_priorities, state);
+        options.include_recommended.hash(state);
+        options.tiebreak_repo_priority.hash(state);
+        options.prefer_naming_convention.hash(state);
+        options.backtracking.hash(state);
+        options.iteration_limit.hash(state);
+        options.queue_order.hash(state);
+        options.locked_preference.hash(state);
+        options.resolve_subset.hash(state);
+        options.diagnostics.hash(state);
+        options.detect_stuck_prereleases.hash(state);
+        options.detect_arbitrary_choices.hash(state);
+        options.detect_kept_yanked.hash(state);
+        options.collect_slack.hash(state);
+        options.minimal_versions.hash(state);
+        options.published_before.hash(state);
+        options.target_platform.hash(state);
+        (options.target_unity_versions.iter())
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>()
+            .hash(state);
+        hash_sorted(&options.prerelease_overrides, state);
+        let mut prerelease_repositories = options.prerelease_repositories.clone();
+        prerelease_repositories.sort();
+        prerelease_repositories.hash(state);
+        let mut enabled_features = options.enabled_features.iter().collect::<Vec<_>>();
+        enabled_features.sort();
+        enabled_features.hash(state);
+        options.license_policy.hash(state);
+        options.channel.hash(state);
+        hash_sorted(&options.vendored, state);
+        hash_sorted(&options.provided, state);
+        hash_sorted(&options.pins, state);
+        hash_sorted(&options.overrides, state);
+        let mut excluded = options.excluded.iter().collect::<Vec<_>>();
+        excluded.sort();
+        excluded.hash(state);
+        let mut keep_legacy = options.keep_legacy.iter().collect::<Vec<_>>();
+        keep_legacy.sort();
+        keep_legacy.hash(state);
+        hash_sorted(&options.aliases, state);
+        options.prerelease_matching.hash(state);
+        options.prerelease_propagation.hash(state);
+
+        project_config.user_managed().hash(state);
+        project_config.pins().len().hash(state);
+        for (name, version) in project_config.pins() {
+            name.hash(state);
+            version.hash(state);
+        }
+        project_config.exclude().hash(state);
+    };
+
+    let mut hasher = DefaultHasher::new();
+    hash_inputs(&mut hasher);
+    let hash = hasher.finish();
+
+    // the salt makes the second fingerprint independent from the first one
+    let mut hasher = DefaultHasher::new();
+    "resolution check".hash(&mut hasher);
+    hash_inputs(&mut hasher);
+    let check = hasher.finish();
+
+    ResolutionKey { hash, check }
+}
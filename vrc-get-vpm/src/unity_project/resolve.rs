@@ -4,11 +4,13 @@ use std::fmt;
 use itertools::Itertools;
 
 use crate::io::ProjectIo;
-use crate::unity_project::package_resolution::MissingDependencies;
+use crate::unity_project::package_resolution::{
+    IncrementalResolution, MissingDependencies, PackageResolutionResult, ResolutionOptions,
+};
 use crate::unity_project::{
     LockedDependencyInfo, PendingProjectChanges, package_resolution, pending_project_changes,
 };
-use crate::version::{DependencyRange, PrereleaseAcceptance};
+use crate::version::{DependencyRange, PrereleaseAcceptance, Version};
 use crate::{PackageCollection, UnityProject, VersionSelector};
 
 #[derive(Debug)]
@@ -38,7 +40,36 @@ impl fmt::Display for ResolvePackageErr {
 
 impl std::error::Error for ResolvePackageErr {}
 
+/// The result of [`UnityProject::resolve_update_all`]
+#[derive(Debug)]
+pub struct UpdateAllResult<'env> {
+    // the resolution with every root dependency bumped to the latest version
+    pub resolution: PackageResolutionResult<'env>,
+    // (package, locked version, new version) of packages to be changed, sorted by the name.
+    // the locked version is None for packages newly added
+    pub changes: Vec<(Box<str>, Option<Version>, Version)>,
+}
+
 impl<IO: ProjectIo> UnityProject<IO> {
+    /// Creates the package resolution with the requirements from the project, which can be
+    /// updated incrementally.
+    pub fn incremental_resolution<'env, 'o, E: PackageCollection>(
+        &'env self,
+        env: &'env E,
+        options: &'env ResolutionOptions<'o>,
+    ) -> IncrementalResolution<'env, 'o, E> {
+        IncrementalResolution::new(
+            self.manifest.dependencies(),
+            self.manifest.all_locked(),
+            self.unlocked_packages.iter(),
+            |pkg| self.manifest.get_locked(pkg),
+            self.unity_version(),
+            env,
+            options,
+            &self.config,
+        )
+    }
+
     /// Returns whether the project should be resolved.
     ///
     /// The project will be resolved if: (not exhaustive)
@@ -142,6 +173,125 @@ impl<IO: ProjectIo> UnityProject<IO> {
         }
     }
 
+    /// Resolves the dependencies as if the project is fresh, ignoring all locked packages.
+    ///
+    /// This does not change the project. Comparing the result with the locked packages shows
+    /// how the lock drifted from the pristine resolution.
+    pub fn resolve_from_scratch<'env>(
+        &self,
+        env: &'env impl PackageCollection,
+    ) -> Result<PackageResolutionResult<'env>, ResolvePackageErr> {
+        let mut missing_dependencies = MissingDependencies::new();
+        let mut to_install = vec![];
+
+        for (name, range) in self.manifest.dependencies() {
+            if let Some(pkg) = env.find_package_by_name(
+                name,
+                VersionSelector::range_for(
+                    self.unity_version(),
+                    &range.as_range(),
+                    PrereleaseAcceptance::allow_or_minimum(range.as_range().contains_pre()),
+                ),
+            ) {
+                to_install.push(pkg);
+            } else {
+                missing_dependencies.add(name);
+            }
+        }
+
+        let allow_prerelease = to_install.iter().any(|x| !x.version().pre.is_empty());
+
+        let result = package_resolution::collect_adding_packages(
+            self.manifest.dependencies(),
+            std::iter::empty(),
+            self.unlocked_packages.iter(),
+            |_| None,
+            self.unity_version(),
+            env,
+            to_install,
+            &ResolutionOptions::new().allow_prerelease(allow_prerelease),
+            &self.config,
+            &mut missing_dependencies,
+        );
+
+        if missing_dependencies.is_empty() {
+            Ok(result)
+        } else {
+            Err(ResolvePackageErr::DependenciesNotFound {
+                dependencies: missing_dependencies.into_vec(),
+            })
+        }
+    }
+
+    /// Resolves the project with every root dependency bumped to the latest version satisfying
+    /// the range in `dependencies` section.
+    ///
+    /// Locked packages not in `dependencies` are kept unless the new root versions require
+    /// other versions. Conflicts introduced by the update are reported in `conflicts` of the
+    /// resolution, so check them before applying the changes.
+    /// This does not change the project.
+    pub fn resolve_update_all<'env>(
+        &self,
+        env: &'env impl PackageCollection,
+        options: &ResolutionOptions,
+    ) -> Result<UpdateAllResult<'env>, ResolvePackageErr> {
+        let mut missing_dependencies = MissingDependencies::new();
+        let mut to_install = vec![];
+
+        for (name, range) in self.manifest.dependencies() {
+            let locked = self.manifest.get_locked(name).map(|x| x.version());
+            let allow_prerelease =
+                range.as_range().contains_pre() || locked.is_some_and(Version::is_pre);
+            match env.find_package_by_name(
+                name,
+                VersionSelector::range_for(
+                    self.unity_version(),
+                    &range.as_range(),
+                    PrereleaseAcceptance::allow_or_minimum(allow_prerelease),
+                ),
+            ) {
+                Some(pkg) if locked.is_some_and(|locked| locked >= pkg.version()) => {}
+                Some(pkg) => to_install.push(pkg),
+                None => missing_dependencies.add(name),
+            }
+        }
+
+        let resolution = package_resolution::collect_adding_packages(
+            self.manifest.dependencies(),
+            self.manifest.all_locked(),
+            self.unlocked_packages.iter(),
+            |pkg| self.manifest.get_locked(pkg),
+            self.unity_version(),
+            env,
+            to_install,
+            options,
+            &self.config,
+            &mut missing_dependencies,
+        );
+
+        if !missing_dependencies.is_empty() {
+            return Err(ResolvePackageErr::DependenciesNotFound {
+                dependencies: missing_dependencies.into_vec(),
+            });
+        }
+
+        let mut changes = (resolution.new_packages.iter())
+            .filter_map(|pkg| {
+                let locked = self.manifest.get_locked(pkg.name()).map(|x| x.version());
+                if locked == Some(pkg.version()) {
+                    return None;
+                }
+                Some((pkg.name().into(), locked.cloned(), pkg.version().clone()))
+            })
+            .collect::<Vec<(Box<str>, _, _)>>();
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+
+        Ok(UpdateAllResult {
+            resolution,
+            changes,
+        })
+    }
+
     fn add_just_dependency<'env>(
         &self,
         env: &'env impl PackageCollection,
@@ -183,7 +333,8 @@ impl<IO: ProjectIo> UnityProject<IO> {
             self.unity_version(),
             env,
             to_install,
-            allow_prerelease,
+            &ResolutionOptions::new().allow_prerelease(allow_prerelease),
+            &self.config,
             missing_dependencies,
         );
 
@@ -198,7 +349,7 @@ impl<IO: ProjectIo> UnityProject<IO> {
         }
 
         for (package, conflicts_with) in result.conflicts {
-            changes.conflict_multiple(package, conflicts_with);
+            changes.conflict_multiple(package, conflicts_with.into_iter().map(|x| x.package));
         }
 
         Ok(())
@@ -260,7 +411,7 @@ impl<IO: ProjectIo> UnityProject<IO> {
         for x in changes.get_all_installing() {
             virtual_locked_dependencies.insert(
                 x.name(),
-                LockedDependencyInfo::new(x.name(), x.version(), Some(x.vpm_dependencies())),
+                LockedDependencyInfo::new(x.name(), x.version(), Some(x.vpm_dependencies()), None),
             );
         }
 
@@ -301,7 +452,8 @@ impl<IO: ProjectIo> UnityProject<IO> {
             self.unity_version(),
             env,
             unlocked_dependencies,
-            allow_prerelease,
+            &ResolutionOptions::new().allow_prerelease(allow_prerelease),
+            &self.config,
             missing_dependencies,
         );
 
@@ -310,7 +462,7 @@ impl<IO: ProjectIo> UnityProject<IO> {
         }
 
         for (package, conflicts_with) in result.conflicts {
-            changes.conflict_multiple(package, conflicts_with);
+            changes.conflict_multiple(package, conflicts_with.into_iter().map(|x| x.package));
         }
 
         Ok(())
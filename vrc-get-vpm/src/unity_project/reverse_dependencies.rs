@@ -0,0 +1,38 @@
+use crate::UnityProject;
+use crate::io::ProjectIo;
+
+/// The packages requiring a package. Returned by [`UnityProject::reverse_dependencies`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct ReverseDependencies {
+    // true if the package is in the dependencies section of vpm-manifest.json
+    pub root: bool,
+    // locked or unlocked packages declaring the package as dependency, sorted by name
+    pub packages: Vec<Box<str>>,
+}
+
+impl ReverseDependencies {
+    /// Returns true if nothing requires the package.
+    pub fn is_empty(&self) -> bool {
+        !self.root && self.packages.is_empty()
+    }
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Returns what requires the package directly.
+    ///
+    /// This is the inverse of the dependencies recorded in the locked packages and `package.json`
+    /// of unlocked packages. Dependencies of packages not installed are not considered.
+    pub fn reverse_dependencies(&self, name: &str) -> ReverseDependencies {
+        let root = self.manifest.get_dependency(name).is_some();
+
+        let mut packages = (self.all_packages())
+            .filter(|dependant| dependant.name() != name)
+            .filter(|dependant| dependant.dependencies().contains_key(name))
+            .map(|dependant| dependant.name().into())
+            .collect::<Vec<Box<str>>>();
+        packages.sort();
+        packages.dedup();
+
+        ReverseDependencies { root, packages }
+    }
+}
@@ -0,0 +1,64 @@
+use crate::io::ProjectIo;
+use crate::{PackageCollection, UnityProject};
+
+/// A dependency in `Packages/manifest.json` resolved from a scoped registry of UPM
+///
+/// The package may be better managed by VPM if it's found in the VPM repositories, and
+/// it's managed twice if it's locked in vpm-manifest.json as well.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopedRegistryPackage {
+    // the name of the package in manifest.json
+    pub name: Box<str>,
+    // the name of the scoped registry whose scope matches the package
+    pub registry_name: Box<str>,
+    // the url of the scoped registry
+    pub registry_url: Box<str>,
+    // true if the package is locked in vpm-manifest.json
+    pub locked_in_vpm: bool,
+    // true if the package is found in the VPM repositories
+    pub available_in_vpm: bool,
+}
+
+impl ScopedRegistryPackage {
+    /// Returns true if both UPM and VPM manage the package.
+    pub fn is_managed_twice(&self) -> bool {
+        self.locked_in_vpm
+    }
+
+    /// Returns true if the package can be managed by VPM instead of the scoped registry.
+    pub fn can_migrate_to_vpm(&self) -> bool {
+        !self.locked_in_vpm && self.available_in_vpm
+    }
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Lists the dependencies in `Packages/manifest.json` resolved from scoped registries
+    /// with how they overlap with VPM.
+    ///
+    /// This doesn't change the manifest. If scopes of multiple registries match a package,
+    /// the registry with the most specific scope is reported like UPM.
+    pub fn scoped_registry_packages(
+        &self,
+        collection: &impl PackageCollection,
+    ) -> Vec<ScopedRegistryPackage> {
+        let registries = self.upm_manifest.scoped_registries();
+
+        let mut packages = (self.upm_manifest.dependencies())
+            .filter_map(|(name, _)| {
+                // max_by_key returns the last one of the same length
+                let (registry, _) = (registries.iter().rev())
+                    .filter_map(|x| Some((x, x.matching_scope_len(name)?)))
+                    .max_by_key(|(_, len)| *len)?;
+                Some(ScopedRegistryPackage {
+                    name: name.into(),
+                    registry_name: registry.name().into(),
+                    registry_url: registry.url().into(),
+                    locked_in_vpm: self.manifest.get_locked(name).is_some(),
+                    available_in_vpm: collection.find_packages(name).next().is_some(),
+                })
+            })
+            .collect::<Vec<_>>();
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+        packages
+    }
+}
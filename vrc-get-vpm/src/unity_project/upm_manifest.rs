@@ -12,9 +12,58 @@ use std::str::FromStr;
 const MANIFEST_PATH: &str = "Packages/manifest.json";
 
 #[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct Parsed {
     #[serde(default)]
     dependencies: HashMap<Box<str>, UpmDependency>,
+    #[serde(default)]
+    #[serde(deserialize_with = "deserialize_scoped_registries")]
+    scoped_registries: Vec<ScopedRegistry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub(super) struct ScopedRegistry {
+    #[serde(default)]
+    name: Box<str>,
+    #[serde(default)]
+    url: Box<str>,
+    #[serde(default)]
+    scopes: Vec<Box<str>>,
+}
+
+impl ScopedRegistry {
+    pub(super) fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub(super) fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Returns the length of the longest scope matching the package, if any.
+    ///
+    /// A scope matches if it is the package name or a prefix of it separated at a dot.
+    pub(super) fn matching_scope_len(&self, package: &str) -> Option<usize> {
+        (self.scopes.iter())
+            .filter(|scope| {
+                package
+                    .strip_prefix(scope.as_ref())
+                    .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+            })
+            .map(|scope| scope.len())
+            .max()
+    }
+}
+
+/// Ignores malformed scoped registries since vrc-get doesn't use them to change the manifest.
+fn deserialize_scoped_registries<'de, D>(deserializer: D) -> Result<Vec<ScopedRegistry>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let values = Option::<Vec<Value>>::deserialize(deserializer).unwrap_or_default();
+    Ok((values.into_iter().flatten())
+        .filter_map(|value| ScopedRegistry::deserialize(value).ok())
+        .collect())
 }
 
 #[derive(Debug)]
@@ -112,7 +161,6 @@ impl UpmManifest {
         })
     }
 
-    #[allow(dead_code)]
     pub(super) fn dependencies(&self) -> impl Iterator<Item = (&str, &UpmDependency)> {
         self.controller
             .as_json
@@ -121,6 +169,10 @@ impl UpmManifest {
             .map(|(name, dep)| (name.as_ref(), dep))
     }
 
+    pub(super) fn scoped_registries(&self) -> &[ScopedRegistry] {
+        &self.controller.as_json.scoped_registries
+    }
+
     #[allow(dead_code)]
     pub(super) fn get_dependency(&self, package: &str) -> Option<&UpmDependency> {
         self.controller.as_json.dependencies.get(package)
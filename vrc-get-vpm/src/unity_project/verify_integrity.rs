@@ -0,0 +1,57 @@
+use crate::UnityProject;
+use crate::io::ProjectIo;
+use crate::version::Version;
+
+/// The result of [`UnityProject::verify_integrity`]
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IntegrityReport {
+    // locked packages not installed, or installed with unreadable package.json
+    pub missing: Vec<Box<str>>,
+    // locked packages installed with another version: (name, locked, installed)
+    pub version_mismatches: Vec<(Box<str>, Version, Version)>,
+    // directories in Packages not locked in vpm-manifest.json
+    pub unlocked: Vec<Box<str>>,
+}
+
+impl IntegrityReport {
+    /// Returns true if the installed packages exactly match the lockfile.
+    pub fn is_ok(&self) -> bool {
+        self.missing.is_empty() && self.version_mismatches.is_empty() && self.unlocked.is_empty()
+    }
+}
+
+impl<IO: ProjectIo> UnityProject<IO> {
+    /// Verifies the packages installed in `Packages` match the locked packages.
+    ///
+    /// This is an audit of the current state of the project. Nothing is resolved or changed.
+    pub fn verify_integrity(&self) -> IntegrityReport {
+        let mut report = IntegrityReport::default();
+
+        for locked in self.manifest.all_locked() {
+            match self.installed_packages.get(locked.name()) {
+                None => report.missing.push(locked.name().into()),
+                Some(installed) if installed.version() != locked.version() => {
+                    report.version_mismatches.push((
+                        locked.name().into(),
+                        locked.version().clone(),
+                        installed.version().clone(),
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+
+        for (dir_name, _) in &self.unlocked_packages {
+            // locked packages with unreadable package.json are reported as missing
+            if self.manifest.get_locked(dir_name).is_none() {
+                report.unlocked.push(dir_name.clone());
+            }
+        }
+
+        report.missing.sort();
+        report.version_mismatches.sort();
+        report.unlocked.sort();
+
+        report
+    }
+}
@@ -1,32 +1,170 @@
 use crate::io;
 use crate::io::ProjectIo;
 use crate::unity_project::LockedDependencyInfo;
-use crate::utils::{SaveController, load_json_or_default, save_json};
+use crate::utils::{SaveController, load_json_or_default, save_json, try_load_json};
 use crate::version::{DependencyRange, Version, VersionRange};
 use indexmap::IndexMap;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::{Map, Value};
+use std::fmt;
 
 const MANIFEST_PATH: &str = "Packages/vpm-manifest.json";
 
+const MANIFEST_VERSION_KEY: &str = "vrc-get-manifest-version";
+
+/// The newest layout version of vpm-manifest.json this crate understands.
+///
+/// Manifests without the version are in the layout VCC writes, which is the version 1.
+const MANIFEST_VERSION: u64 = 1;
+
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct AsJson {
-    #[serde(default)]
+    /// The layout version of the manifest, None for the layout VCC writes
+    #[serde(
+        default,
+        rename = "vrc-get-manifest-version",
+        skip_serializing_if = "Option::is_none"
+    )]
+    manifest_version: Option<u64>,
+    #[serde(default, serialize_with = "serialize_sorted")]
     dependencies: IndexMap<Box<str>, VpmDependency>,
-    #[serde(default)]
+    #[serde(default, serialize_with = "serialize_sorted")]
     locked: IndexMap<Box<str>, VpmLockedDependency>,
+    /// The fields unknown to this crate, written back as is
+    #[serde(flatten)]
+    others: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct VpmDependency {
     pub version: DependencyRange,
+    /// The fields unknown to this crate, written back as is
+    #[serde(flatten)]
+    pub others: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct VpmLockedDependency {
     pub version: Version,
-    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_sorted_option"
+    )]
     pub dependencies: Option<IndexMap<Box<str>, VersionRange>>,
+    /// The package manifest embedded for resolving without repositories
+    #[serde(
+        default,
+        rename = "vrc-get-embedded",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub embedded: Option<Map<String, Value>>,
+    /// The URL of the repository the package is installed from
+    #[serde(
+        default,
+        rename = "vrc-get-repository",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub repository: Option<Box<str>>,
+    /// The hash of the commit the package is installed from if the package is from git
+    #[serde(
+        default,
+        rename = "vrc-get-git-commit",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub git_commit: Option<Box<str>>,
+    /// The fields unknown to this crate, written back as is
+    #[serde(flatten)]
+    pub others: Map<String, Value>,
+}
+
+/// Serializes the map with the keys sorted, so the manifest is written in the same order
+/// regardless of the order the packages are added, for clean diffs in VCS.
+fn serialize_sorted<S: Serializer, V: Serialize>(
+    map: &IndexMap<Box<str>, V>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut entries = map.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|&(name, _)| name);
+    serializer.collect_map(entries)
+}
+
+fn serialize_sorted_option<S: Serializer, V: Serialize>(
+    map: &Option<IndexMap<Box<str>, V>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    struct Sorted<'a, V>(&'a IndexMap<Box<str>, V>);
+
+    impl<V: Serialize> Serialize for Sorted<'_, V> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serialize_sorted(self.0, serializer)
+        }
+    }
+
+    match map {
+        Some(map) => serializer.serialize_some(&Sorted(map)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// The error loading vpm-manifest.json in the layout newer than this crate understands.
+///
+/// [`UnityProject::load`](crate::UnityProject::load) fails with [`io::ErrorKind::InvalidData`]
+/// with this as the inner error, so saving the manifest never drops the fields of newer layouts.
+#[derive(Debug)]
+pub struct UnsupportedManifestVersion {
+    version: u64,
+}
+
+impl UnsupportedManifestVersion {
+    /// The layout version of the manifest
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// The newest layout version this crate understands
+    pub fn supported_version(&self) -> u64 {
+        MANIFEST_VERSION
+    }
+}
+
+impl fmt::Display for UnsupportedManifestVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "vpm-manifest.json is in the version {} which is newer than supported version {}",
+            self.version, MANIFEST_VERSION
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedManifestVersion {}
+
+/// Migrates the manifest in older layouts to the current layout in place.
+///
+/// No layout older than the version 1 is known yet, so this only checks the version.
+/// When the layout changes, bump [`MANIFEST_VERSION`] and add the step from the previous version
+/// here, and the migrated manifest is saved in the current layout on the next save.
+fn migrate(json: &mut Map<String, Value>) -> io::Result<()> {
+    let version = match json.get(MANIFEST_VERSION_KEY) {
+        None => return Ok(()),
+        Some(version) => version.as_u64().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{MANIFEST_VERSION_KEY} of {MANIFEST_PATH} is not a version number"),
+            )
+        })?,
+    };
+
+    if version == 0 || version > MANIFEST_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            UnsupportedManifestVersion { version },
+        ));
+    }
+
+    Ok(())
 }
 
 #[derive(Debug)]
@@ -36,10 +174,20 @@ pub(super) struct VpmManifest {
 
 impl VpmManifest {
     pub(super) async fn load(io: &impl ProjectIo) -> io::Result<Self> {
+        let mut json =
+            load_json_or_default::<Map<String, Value>>(io, MANIFEST_PATH.as_ref()).await?;
+        migrate(&mut json)?;
+        let parsed = match serde_path_to_error::deserialize(Value::Object(json)) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("syntax error loading {MANIFEST_PATH}: {e}"),
+                ));
+            }
+        };
         Ok(Self {
-            controller: SaveController::new(
-                load_json_or_default(io, MANIFEST_PATH.as_ref()).await?,
-            ),
+            controller: SaveController::new(parsed),
         })
     }
 
@@ -59,7 +207,13 @@ impl VpmManifest {
 
     pub(super) fn all_locked(&self) -> impl Iterator<Item = LockedDependencyInfo> {
         self.controller.locked.iter().map(|(name, dep)| {
-            LockedDependencyInfo::new(name.as_ref(), &dep.version, dep.dependencies.as_ref())
+            LockedDependencyInfo::new(
+                name.as_ref(),
+                &dep.version,
+                dep.dependencies.as_ref(),
+                dep.repository.as_deref(),
+            )
+            .with_git_commit(dep.git_commit.as_deref())
         })
     }
 
@@ -68,15 +222,24 @@ impl VpmManifest {
             .locked
             .get_key_value(package)
             .map(|(package, x)| {
-                LockedDependencyInfo::new(package, &x.version, x.dependencies.as_ref())
+                LockedDependencyInfo::new(
+                    package,
+                    &x.version,
+                    x.dependencies.as_ref(),
+                    x.repository.as_deref(),
+                )
+                .with_git_commit(x.git_commit.as_deref())
             })
     }
 
     pub(super) fn add_dependency(&mut self, name: &str, version: DependencyRange) {
-        self.controller
-            .as_mut()
-            .dependencies
-            .insert(name.into(), VpmDependency { version });
+        self.controller.as_mut().dependencies.insert(
+            name.into(),
+            VpmDependency {
+                version,
+                others: Map::new(),
+            },
+        );
     }
 
     pub(super) fn add_locked(
@@ -84,16 +247,40 @@ impl VpmManifest {
         name: &str,
         version: Version,
         dependencies: IndexMap<Box<str>, VersionRange>,
+        repository: Option<Box<str>>,
     ) {
         self.controller.as_mut().locked.insert(
             name.into(),
             VpmLockedDependency {
                 version,
                 dependencies: Some(dependencies),
+                embedded: None,
+                repository,
+                git_commit: None,
+                others: Map::new(),
             },
         );
     }
 
+    pub(super) fn set_git_commit(&mut self, name: &str, commit: &str) {
+        if let Some(locked) = self.controller.as_mut().locked.get_mut(name) {
+            locked.git_commit = Some(commit.into());
+        }
+    }
+
+    pub(super) fn all_embedded(&self) -> impl Iterator<Item = &Map<String, Value>> {
+        self.controller
+            .locked
+            .values()
+            .filter_map(|x| x.embedded.as_ref())
+    }
+
+    pub(super) fn set_embedded(&mut self, name: &str, embedded: Map<String, Value>) {
+        if let Some(locked) = self.controller.as_mut().locked.get_mut(name) {
+            locked.embedded = Some(embedded);
+        }
+    }
+
     pub(crate) fn remove_packages<'a>(&mut self, names: impl Iterator<Item = &'a str>) {
         for name in names {
             self.controller.as_mut().locked.shift_remove(name);
@@ -107,7 +294,16 @@ impl VpmManifest {
 
     pub(super) async fn save(&mut self, io: &impl ProjectIo) -> io::Result<()> {
         self.controller
-            .save(|json| save_json(io, MANIFEST_PATH.as_ref(), json))
+            .save(|json| async move {
+                // the lockfile is not rewritten if nothing is changed, to avoid churn in VCS
+                let existing = try_load_json::<Value>(io, MANIFEST_PATH.as_ref()).await;
+                if let Ok(Some(existing)) = existing {
+                    if serde_json::to_value(json).is_ok_and(|x| x == existing) {
+                        return Ok(());
+                    }
+                }
+                save_json(io, MANIFEST_PATH.as_ref(), json).await
+            })
             .await
     }
 }
@@ -0,0 +1,85 @@
+use crate::repository::LoadRepositoryErr;
+use std::borrow::Cow;
+
+/// Replaces `${NAME}` in the value with the environment variable by `lookup`.
+///
+/// `$%7BNAME%7D` is also replaced since the braces are percent-encoded in the path of urls.
+/// Names are ASCII alphanumerics and underscores, and `$` not followed by the placeholder is kept.
+pub(crate) fn substitute_env_vars<'a>(
+    value: &'a str,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> Result<Cow<'a, str>, LoadRepositoryErr> {
+    fn strip_prefix_ignore_case<'s>(value: &'s str, prefix: &str) -> Option<&'s str> {
+        let head = value.get(..prefix.len())?;
+        head.eq_ignore_ascii_case(prefix)
+            .then(|| &value[prefix.len()..])
+    }
+
+    fn placeholder(rest: &str) -> Option<(&str, &str)> {
+        let rest = (rest.strip_prefix('{')).or_else(|| strip_prefix_ignore_case(rest, "%7B"))?;
+        let len = (rest.bytes())
+            .take_while(|&b| b.is_ascii_alphanumeric() || b == b'_')
+            .count();
+        let (name, rest) = rest.split_at(len);
+        let rest = (rest.strip_prefix('}')).or_else(|| strip_prefix_ignore_case(rest, "%7D"))?;
+        (!name.is_empty()).then_some((name, rest))
+    }
+
+    if !value.contains('$') {
+        return Ok(Cow::Borrowed(value));
+    }
+
+    let mut result = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(index) = rest.find('$') {
+        result.push_str(&rest[..index]);
+        let after_dollar = &rest[index + 1..];
+        match placeholder(after_dollar) {
+            Some((name, after)) => {
+                let Some(variable) = lookup(name) else {
+                    return Err(LoadRepositoryErr::MissingEnvVar { name: name.into() });
+                };
+                result.push_str(&variable);
+                rest = after;
+            }
+            None => {
+                result.push('$');
+                rest = after_dollar;
+            }
+        }
+    }
+    result.push_str(rest);
+    Ok(Cow::Owned(result))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "TOKEN" => Some("secret".to_owned()),
+            "HOST" => Some("example.com".to_owned()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn substitute() {
+        let substitute = |value| substitute_env_vars(value, lookup).unwrap().into_owned();
+        assert_eq!(substitute("Bearer ${TOKEN}"), "Bearer secret");
+        assert_eq!(
+            substitute("https://${HOST}/$%7BTOKEN%7D/vpm.json?key=${TOKEN}"),
+            "https://example.com/secret/vpm.json?key=secret"
+        );
+        assert_eq!(substitute("$5 ${} $TOKEN ${TOKEN"), "$5 ${} $TOKEN ${TOKEN");
+    }
+
+    #[test]
+    fn missing() {
+        let err = substitute_env_vars("${TOKEN}${UNSET_VAR}", lookup).unwrap_err();
+        assert!(
+            matches!(err, LoadRepositoryErr::MissingEnvVar { name } if name.as_ref() == "UNSET_VAR")
+        );
+    }
+}
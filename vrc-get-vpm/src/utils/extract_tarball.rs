@@ -0,0 +1,90 @@
+use crate::io;
+use crate::io::ProjectIo;
+use crate::traits::AbortCheck;
+use crate::utils::extract_zip::is_complete_relative;
+use futures::prelude::*;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+
+/// Returns true if the path is of a gzipped tarball, which is `.tgz` or `.tar.gz`.
+pub(crate) fn is_tarball_path(path: &str) -> bool {
+    let path = path.to_ascii_lowercase();
+    path.ends_with(".tgz") || path.ends_with(".tar.gz")
+}
+
+/// An entry in the tarball. `content` is `None` for directories.
+pub(crate) struct TarballEntry {
+    pub path: PathBuf,
+    pub content: Option<Vec<u8>>,
+}
+
+/// Reads the entries of the gzipped tarball.
+///
+/// Like npm, the first component of the paths is stripped since the files of the package are in
+/// the top-level folder, which is `package` for the tarballs created by npm or Unity.
+/// Links and other special files are not a part of packages, so they are skipped.
+pub(crate) fn read_tarball(tgz: &[u8]) -> io::Result<Vec<TarballEntry>> {
+    let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(tgz));
+    let mut entries = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let raw_path = entry.path()?.into_owned();
+        if !is_complete_relative(&raw_path) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("directory traversal detected: {}", raw_path.display()),
+            ));
+        }
+
+        let path = (raw_path.components())
+            .filter(|x| !matches!(x, Component::CurDir))
+            .skip(1)
+            .collect::<PathBuf>();
+        if path.as_os_str().is_empty() {
+            // the top-level folder itself
+            continue;
+        }
+
+        let content = match entry.header().entry_type() {
+            tar::EntryType::Directory => None,
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let mut buffer = Vec::with_capacity(entry.size() as usize);
+                entry.read_to_end(&mut buffer)?;
+                Some(buffer)
+            }
+            _ => continue,
+        };
+        entries.push(TarballEntry { path, content });
+    }
+    Ok(entries)
+}
+
+pub(crate) async fn extract_tarball(
+    mut tgz_file: impl AsyncRead + Unpin,
+    io: &impl ProjectIo,
+    dest_folder: &Path,
+    abort: &AbortCheck,
+    mut on_progress: impl FnMut(usize, usize),
+) -> io::Result<()> {
+    let mut tgz = Vec::new();
+    tgz_file.read_to_end(&mut tgz).await?;
+    // the entries are read at once since the tar reader is not async
+    let entries = read_tarball(&tgz)?;
+
+    let total = entries.len();
+    for (i, entry) in entries.into_iter().enumerate() {
+        abort.check()?;
+        let path = dest_folder.join(&entry.path);
+        if let Some(content) = entry.content {
+            io.create_dir_all(path.parent().unwrap()).await?;
+            let mut dest_file = io.create(path.as_ref()).await?;
+            dest_file.write_all(&content).await?;
+            dest_file.flush().await?;
+        } else {
+            io.create_dir_all(path.as_ref()).await?;
+        }
+        on_progress(i + 1, total);
+    }
+
+    Ok(())
+}
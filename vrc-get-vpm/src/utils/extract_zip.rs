@@ -1,6 +1,7 @@
 use crate::io;
 use crate::io::ProjectIo;
 use crate::io::SeekFrom;
+use crate::traits::AbortCheck;
 use crate::utils::MapResultExt;
 use async_zip::base::read::seek::ZipFileReader;
 use futures::prelude::*;
@@ -10,12 +11,16 @@ pub(crate) async fn extract_zip(
     mut zip_file: impl AsyncBufRead + AsyncSeek + Unpin,
     io: &impl ProjectIo,
     dest_folder: &Path,
+    abort: &AbortCheck,
+    mut on_progress: impl FnMut(usize, usize),
 ) -> io::Result<()> {
     // extract zip file
     zip_file.seek(SeekFrom::Start(0)).await?;
 
     let mut zip_reader = ZipFileReader::new(zip_file).await.err_mapped()?;
-    for i in 0..zip_reader.file().entries().len() {
+    let total = zip_reader.file().entries().len();
+    for i in 0..total {
+        abort.check()?;
         let entry = &zip_reader.file().entries()[i];
         let Some(filename) = entry.filename().as_str().ok() else {
             return Err(io::Error::new(
@@ -41,12 +46,13 @@ pub(crate) async fn extract_zip(
             io::copy(&mut reader, &mut dest_file).await?;
             dest_file.flush().await?;
         }
+        on_progress(i + 1, total);
     }
 
     Ok(())
 }
 
-fn is_complete_relative(path: &Path) -> bool {
+pub(super) fn is_complete_relative(path: &Path) -> bool {
     for x in path.components() {
         match x {
             Component::Prefix(_) => return false,
@@ -1,6 +1,8 @@
 mod copy_recursive;
 mod crlf_json_formatter;
 mod deup_deserializer;
+mod env_vars;
+mod extract_tarball;
 mod extract_zip;
 mod save_controller;
 mod sha256_async_write;
@@ -12,6 +14,8 @@ pub(crate) use copy_recursive::copy_recursive;
 pub(crate) use crlf_json_formatter::to_vec_pretty_os_eol;
 pub(crate) use deup_deserializer::DedupForwarder;
 use either::Either;
+pub(crate) use env_vars::substitute_env_vars;
+pub(crate) use extract_tarball::{extract_tarball, is_tarball_path, read_tarball};
 pub(crate) use extract_zip::extract_zip;
 use futures::prelude::*;
 use futures::stream::FuturesUnordered;
@@ -262,13 +266,6 @@ pub(crate) fn deserialize_json<T: serde::de::DeserializeOwned>(value: Value) ->
     serde_path_to_error::deserialize(&value).map_err(to_io_err)
 }
 
-pub(crate) fn deserialize_json_slice<T: serde::de::DeserializeOwned>(
-    slice: &[u8],
-) -> io::Result<T> {
-    let mut deserializer = serde_json::Deserializer::from_slice(slice);
-    serde_path_to_error::deserialize(&mut deserializer).map_err(to_io_err)
-}
-
 pub(crate) fn to_io_err(err: serde_path_to_error::Error<serde_json::Error>) -> io::Error {
     match err.inner().classify() {
         Category::Io => err.into_inner().into(),
@@ -355,6 +352,26 @@ pub(crate) async fn save_json(
 ) -> io::Result<()> {
     io.create_dir_all(path.parent().unwrap_or("".as_ref()))
         .await?;
-    io.write_sync(path, &to_vec_pretty_os_eol(&data)?).await?;
+    write_atomic(io, path, &to_vec_pretty_os_eol(&data)?).await?;
+    Ok(())
+}
+
+/// Writes the content to the file so that the file is either the old or the new content even on crash.
+///
+/// The content is written to a temporary file in the same directory and then renamed over the file.
+pub(crate) async fn write_atomic(io: &impl IoTrait, path: &Path, content: &[u8]) -> io::Result<()> {
+    let temp_path = atomic_temp_path(path);
+    io.write_sync(&temp_path, content).await?;
+    if let Err(e) = io.rename(&temp_path, path).await {
+        io.remove_file(&temp_path).await.ok();
+        return Err(e);
+    }
     Ok(())
 }
+
+/// Returns the unique temporary path in the same directory to write the file renamed over `path`.
+pub(crate) fn atomic_temp_path(path: &Path) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    // the leading dot makes unity ignore the temporary file
+    path.with_file_name(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()))
+}
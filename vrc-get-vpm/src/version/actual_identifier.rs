@@ -73,7 +73,7 @@ impl Ord for Prerelease {
 
         for lhs in lhs {
             let rhs = match rhs.next() {
-                // shorter releases are greater
+                // A larger set of identifiers has a higher precedence.
                 None => return Ordering::Greater,
                 Some(rhs) => rhs,
             };
@@ -82,8 +82,15 @@ impl Ord for Prerelease {
                 lhs.bytes().all(|b| b.is_ascii_digit()),
                 rhs.bytes().all(|b| b.is_ascii_digit()),
             ) {
-                // numeric ordering
-                (true, true) => Ord::cmp(&lhs.len(), &rhs.len()).then_with(|| Ord::cmp(lhs, rhs)),
+                // Identifiers consisting of only digits are compared numerically.
+                // Leading zeros are ignored, and only used to keep the ordering consistent with Eq.
+                (true, true) => {
+                    let lhs_digits = lhs.trim_start_matches('0');
+                    let rhs_digits = rhs.trim_start_matches('0');
+                    Ord::cmp(&lhs_digits.len(), &rhs_digits.len())
+                        .then_with(|| Ord::cmp(lhs_digits, rhs_digits))
+                        .then_with(|| Ord::cmp(lhs, rhs))
+                }
                 // Numeric identifiers always have lower precedence than non-numeric identifiers.
                 (true, false) => return Ordering::Less,
                 (false, true) => return Ordering::Greater,
@@ -99,7 +106,7 @@ impl Ord for Prerelease {
         if rhs.next().is_none() {
             Ordering::Equal
         } else {
-            // shorter releases are greater
+            // A larger set of identifiers has a higher precedence.
             Ordering::Less
         }
     }
@@ -213,3 +220,45 @@ fn parse_id<'a>(
         Ok(&buf[..len])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::version::Version;
+
+    #[test]
+    fn prerelease_precedence() {
+        // the example in the semver specification
+        let ordered = [
+            "1.0.0-alpha",
+            "1.0.0-alpha.1",
+            "1.0.0-alpha.beta",
+            "1.0.0-beta",
+            "1.0.0-beta.2",
+            "1.0.0-beta.11",
+            "1.0.0-rc.1",
+            "1.0.0",
+        ];
+        let ordered = (ordered.iter())
+            .map(|x| x.parse::<Version>().unwrap())
+            .collect::<Vec<_>>();
+        for (i, lhs) in ordered.iter().enumerate() {
+            for (j, rhs) in ordered.iter().enumerate() {
+                assert_eq!(lhs.cmp(rhs), i.cmp(&j), "{lhs} vs {rhs}");
+            }
+        }
+
+        let version = |s: &str| s.parse::<Version>().unwrap();
+        assert!(version("1.0.0-rc.2") < version("1.0.0-rc.10"));
+        assert!(version("1.0.0-rc.9") < version("1.0.0-rc.10"));
+        assert!(version("1.0.0-1") < version("1.0.0-a"));
+        assert!(version("1.0.0-rc.99") < version("1.0.0-rc.a"));
+        assert!(version("1.0.0-RC") < version("1.0.0-rc"));
+        assert!(version("1.0.0-rc.1") < version("1.0.0-rc-1"));
+        assert_eq!(version("1.0.0-rc.1+build"), version("1.0.0-rc.1"));
+
+        // leading zeros are not allowed by the specification, but compared numerically if found
+        assert!(version("1.0.0-rc.01") < version("1.0.0-rc.2"));
+        assert!(version("1.0.0-rc.10") > version("1.0.0-rc.09"));
+        assert!(version("1.0.0-rc.01") != version("1.0.0-rc.1"));
+    }
+}
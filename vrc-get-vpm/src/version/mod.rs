@@ -7,10 +7,12 @@
 
 pub use range::DependencyRange;
 pub use range::PrereleaseAcceptance;
+pub use range::PrereleaseMatching;
 pub use range::VersionRange;
 use std::fmt::Debug;
 pub use unity_version::ReleaseType;
 pub use unity_version::UnityVersion;
+pub use unity_version::UnityVersionRange;
 pub use version::Version;
 
 macro_rules! from_str_impl {
@@ -20,9 +22,10 @@ macro_rules! from_str_impl {
 
             fn from_str(s: &str) -> Result<Self, Self::Err> {
                 let mut buffer = ParsingBuf::new(s);
-                let result = FromParsingBuf::parse(&mut buffer)?;
+                let result = FromParsingBuf::parse(&mut buffer)
+                    .map_err(|e: ParseVersionError| e.at(s, buffer.position()))?;
                 if buffer.first().is_some() {
-                    return Err(ParseVersionError::invalid());
+                    return Err(ParseVersionError::invalid().at(s, buffer.position()));
                 }
                 Ok(result)
             }
@@ -85,7 +88,8 @@ use segment::Segment;
 pub use actual_identifier::BuildMetadata;
 pub use actual_identifier::Prerelease;
 use parsing_buf::FromParsingBuf;
-use parsing_buf::ParseVersionError;
+pub use parsing_buf::ParseVersionError;
+pub use parsing_buf::ParseVersionErrorKind;
 use parsing_buf::ParsingBuf;
 
 #[cfg(test)]
@@ -137,4 +141,26 @@ mod tests {
         test("1.2.3-r2", "1.2.3-r100");
         test("1.2.3-r100", "1.2.3-R2");
     }
+
+    #[test]
+    fn test_build_metadata() {
+        let versions =
+            ["1.0.0", "1.0.0+a", "1.0.0+b", "1.0.0-rc.1+a"].map(|x| Version::from_str(x).expect(x));
+        let [stable, stable_a, stable_b, rc_a] = &versions;
+
+        // build metadata is ignored for precedence
+        assert_eq!(stable, stable_a);
+        assert_eq!(stable_a, stable_b);
+        assert_eq!(stable_a.cmp(stable_b), std::cmp::Ordering::Equal);
+        assert!(rc_a < stable_a);
+        assert!(rc_a < stable);
+
+        // but preserved for display and exact matching
+        assert_eq!(stable_a.to_string(), "1.0.0+a");
+        assert_eq!(rc_a.to_string(), "1.0.0-rc.1+a");
+        assert!(stable_a.is_same_build(stable_a));
+        assert!(!stable_a.is_same_build(stable_b));
+        assert!(!stable.is_same_build(stable_a));
+        assert!(!rc_a.is_same_build(stable_a));
+    }
 }
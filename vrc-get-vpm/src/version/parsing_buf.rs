@@ -1,4 +1,5 @@
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
 
 pub(super) trait FromParsingBuf: Sized {
     fn parse(buffer: &mut ParsingBuf) -> Result<Self, ParseVersionError>;
@@ -6,11 +7,20 @@ pub(super) trait FromParsingBuf: Sized {
 
 pub(super) struct ParsingBuf<'a> {
     pub(super) buf: &'a str,
+    source: &'a str,
 }
 
 impl<'a> ParsingBuf<'a> {
     pub fn new(source: &'a str) -> Self {
-        Self { buf: source }
+        Self {
+            buf: source,
+            source,
+        }
+    }
+
+    /// The byte offset of the current position in the source.
+    pub(super) fn position(&self) -> usize {
+        self.source.len() - self.buf.len()
     }
 
     pub(super) fn is_empty(&self) -> bool {
@@ -54,44 +64,94 @@ impl<'a> ParsingBuf<'a> {
     }
 }
 
+/// The error parsing versions or version ranges.
+///
+/// The error has the position where parsing failed and the offending part of the input,
+/// which is the part from the position to the next whitespace.
 #[derive(Debug)]
 pub struct ParseVersionError {
-    inner: Inner,
+    inner: ParseVersionErrorKind,
+    position: usize,
+    offending: Box<str>,
 }
 
 impl Display for ParseVersionError {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         match self.inner {
-            Inner::VersionSegmentTooBig => f.write_str("version segment too big"),
-            Inner::UnexpectedEnd => f.write_str("unexpected end"),
-            Inner::Invalid => write!(f, "invalid"),
+            ParseVersionErrorKind::VersionSegmentTooBig => {
+                f.write_str("version segment too big")?
+            }
+            ParseVersionErrorKind::UnexpectedEnd => f.write_str("unexpected end")?,
+            ParseVersionErrorKind::Invalid => write!(f, "invalid")?,
+        }
+        if self.offending.is_empty() {
+            write!(f, " at {}", self.position)
+        } else {
+            write!(f, " at {}: `{}`", self.position, self.offending)
         }
     }
 }
 
 impl std::error::Error for ParseVersionError {}
 
-#[derive(Debug)]
-enum Inner {
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseVersionErrorKind {
     VersionSegmentTooBig,
     UnexpectedEnd,
     Invalid,
 }
 
 impl ParseVersionError {
-    pub(super) fn too_big() -> Self {
+    fn new(inner: ParseVersionErrorKind) -> Self {
         Self {
-            inner: Inner::VersionSegmentTooBig,
+            inner,
+            position: 0,
+            offending: "".into(),
         }
     }
+
+    pub(super) fn too_big() -> Self {
+        Self::new(ParseVersionErrorKind::VersionSegmentTooBig)
+    }
     pub(super) fn invalid() -> Self {
-        Self {
-            inner: Inner::Invalid,
-        }
+        Self::new(ParseVersionErrorKind::Invalid)
     }
     pub(super) fn unexpected_end() -> ParseVersionError {
-        Self {
-            inner: Inner::UnexpectedEnd,
-        }
+        Self::new(ParseVersionErrorKind::UnexpectedEnd)
+    }
+
+    /// Sets the position in the source where parsing failed.
+    pub(super) fn at(mut self, source: &str, position: usize) -> Self {
+        let rest = &source[position..];
+        let len = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        self.position = position;
+        self.offending = rest[..len].into();
+        self
+    }
+
+    /// Moves the position for the source the parsed part is at the offset in.
+    pub(super) fn offset(mut self, offset: usize) -> Self {
+        self.position += offset;
+        self
+    }
+
+    pub fn kind(&self) -> ParseVersionErrorKind {
+        self.inner
+    }
+
+    /// The byte offset in the input where parsing failed.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    /// The part of the input from the position to the next whitespace. Empty at the end of the input.
+    pub fn offending(&self) -> &str {
+        &self.offending
+    }
+
+    /// The byte range of the offending part in the input, for highlighting it.
+    pub fn span(&self) -> Range<usize> {
+        self.position..self.position + self.offending.len()
     }
 }
@@ -17,6 +17,19 @@ impl DependencyRange {
         })
     }
 
+    /// Returns the range matching only the version, like `=1.2.3`.
+    ///
+    /// Unlike [`Self::version`], which means the version or later, this doesn't match later versions.
+    pub(crate) fn exact(version: Version) -> DependencyRange {
+        Self(VersionRange::exact(version))
+    }
+
+    /// Returns the version if the range is a single full version without operator, like `1.2.3`.
+    ///
+    /// Unlike in [`VersionRange`], a single version in dependencies means the version or later,
+    /// and the locked version is used as the minimum if it's older.
+    /// Partial versions like `1.2` and versions with operator like `=1.2.3` are ranges, so this
+    /// returns None for them.
     pub fn as_single_version(&self) -> Option<Version> {
         let [ComparatorSet(the_set)] = self.0.comparators.as_slice() else {
             return None;
@@ -39,11 +52,40 @@ impl DependencyRange {
         }
     }
 
+    /// Returns the range of versions this matches.
+    ///
+    /// A single version returned by [`Self::as_single_version`] becomes [`VersionRange::same_or_later`],
+    /// so `1.2.3-beta` becomes `>=1.2.3-beta` and accepts prerelease versions as before.
+    /// Other ranges are returned as is.
     pub fn as_range(&self) -> VersionRange {
         self.as_single_version()
             .map(VersionRange::same_or_later)
             .unwrap_or_else(|| self.0.clone())
     }
+
+    /// Returns the equivalent range in the canonical form.
+    ///
+    /// A single version like `1.2.3` becomes `>=1.2.3` as in [`Self::as_range`], and duplicated or
+    /// redundant comparators and comparator sets are removed, like `>=1.0.0 >=1.2.0` to `>=1.2.0`.
+    /// Comparators mentioning prerelease versions are removed only if duplicated, so the
+    /// prerelease versions matched are preserved.
+    /// If the result is a single full version, it's written as `=1.2.3` so it's not read as the
+    /// version or later.
+    ///
+    /// Note that the canonical form of a single version is no longer lowered to the locked version
+    /// nor upgraded on installing newer versions since it's not a single version anymore.
+    pub fn canonicalize(&self) -> DependencyRange {
+        if let Some(single) = self.as_single_version() {
+            return Self(VersionRange::same_or_later(single));
+        }
+
+        let canonical = Self(self.0.canonicalize());
+        match canonical.as_single_version() {
+            // a single version without operator would be read as the version or later
+            Some(single) => Self(VersionRange::exact(single)),
+            None => canonical,
+        }
+    }
 }
 
 impl Display for DependencyRange {
@@ -52,16 +94,32 @@ impl Display for DependencyRange {
     }
 }
 
+impl FromStr for DependencyRange {
+    type Err = ParseVersionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        VersionRange::from_str(s).map(Self)
+    }
+}
+
+/// The range of versions in the npm-style notation.
+///
+/// The [`Display`] form is canonical: parsing it with [`FromStr`] yields the equal range, so it
+/// is safe to persist ranges in the string form. Serialization uses the string form.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct VersionRange {
     comparators: Vec<ComparatorSet>,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy)]
 pub enum PrereleaseAcceptance {
     Deny,
     Allow,
+    /// Accepts prerelease versions if the comparator mentions any prerelease.
     Minimum,
+    /// Accepts prerelease versions if the comparator mentions a prerelease of the same
+    /// `major.minor.patch` as the version.
+    MinimumSameVersion,
 }
 
 impl PrereleaseAcceptance {
@@ -74,6 +132,42 @@ impl PrereleaseAcceptance {
     }
 }
 
+/// The rule for when prerelease versions match ranges without prerelease allowed.
+///
+/// With both rules, stable versions match as usual, and `>=1.0.0` matches no prerelease versions.
+/// The rules differ for comparators mentioning prerelease versions like `>=1.0.0-0`.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum PrereleaseMatching {
+    /// A prerelease version matches if the comparator mentions any prerelease version.
+    ///
+    /// `>=1.0.0-0` matches both `1.0.0-beta.1` and `1.2.0-rc.1`.
+    /// This is the rule vrc-get has been using.
+    #[default]
+    AnyPrerelease,
+    /// A prerelease version matches only if the comparator mentions a prerelease of the same
+    /// `major.minor.patch`, as semver and npm do.
+    ///
+    /// `>=1.0.0-0` matches `1.0.0-beta.1` but not `1.2.0-rc.1`.
+    SameVersion,
+}
+
+impl PrereleaseMatching {
+    pub(crate) fn minimum(self) -> PrereleaseAcceptance {
+        match self {
+            PrereleaseMatching::AnyPrerelease => PrereleaseAcceptance::Minimum,
+            PrereleaseMatching::SameVersion => PrereleaseAcceptance::MinimumSameVersion,
+        }
+    }
+
+    pub(crate) fn allow_or_minimum(self, allow: bool) -> PrereleaseAcceptance {
+        if allow {
+            PrereleaseAcceptance::Allow
+        } else {
+            self.minimum()
+        }
+    }
+}
+
 impl VersionRange {
     pub fn same_or_later(version: Version) -> Self {
         Self {
@@ -83,6 +177,15 @@ impl VersionRange {
         }
     }
 
+    /// Returns the range matching only the version.
+    pub(crate) fn exact(version: Version) -> Self {
+        Self {
+            comparators: vec![ComparatorSet(vec![Comparator::Exact(
+                PartialVersion::from(version),
+            )])],
+        }
+    }
+
     pub fn contains_pre(&self) -> bool {
         self.comparators.iter().any(ComparatorSet::contains_pre)
     }
@@ -96,6 +199,86 @@ impl VersionRange {
             .iter()
             .any(|x| x.matches(version, allow_prerelease))
     }
+
+    /// Returns the lowest version this range can match, or None if it's not bounded below.
+    ///
+    /// For exclusive bounds like `>1.0.0`, the bound itself is returned.
+    pub(crate) fn lower_bound(&self) -> Option<Version> {
+        let mut lowest = None::<Version>;
+        for set in &self.comparators {
+            let bound = set.lower_bound()?;
+            if lowest.as_ref().is_none_or(|lowest| &bound < lowest) {
+                lowest = Some(bound);
+            }
+        }
+        lowest
+    }
+
+    /// Returns true if some version matches both ranges.
+    ///
+    /// Like [`Self::matches`], prerelease versions are considered only if every comparator
+    /// bounding them mentions a prerelease.
+    pub fn intersects(&self, other: &VersionRange) -> bool {
+        (self.comparators.iter()).any(|a| (other.comparators.iter()).any(|b| a.intersects(b)))
+    }
+
+    /// Returns the range matching versions matched by both ranges.
+    ///
+    /// Returns None if no version matches both ranges.
+    pub fn intersection(&self, other: &VersionRange) -> Option<VersionRange> {
+        let comparators = (self.comparators.iter())
+            .flat_map(|a| (other.comparators.iter()).map(move |b| (a, b)))
+            .filter(|(a, b)| a.intersects(b))
+            .map(|(a, b)| ComparatorSet(a.0.iter().chain(&b.0).cloned().collect()))
+            .collect::<Vec<_>>();
+        if comparators.is_empty() {
+            None
+        } else {
+            Some(Self { comparators })
+        }
+    }
+
+    /// Returns the equivalent range without duplicated or redundant comparators and comparator sets.
+    fn canonicalize(&self) -> VersionRange {
+        let mut sets = Vec::<ComparatorSet>::new();
+        for set in &self.comparators {
+            let set = set.canonicalize();
+            if sets.iter().any(|kept| set.is_covered_by(kept)) {
+                continue;
+            }
+            sets.retain(|kept| !kept.is_covered_by(&set));
+            sets.push(set);
+        }
+        Self { comparators: sets }
+    }
+
+    /// Returns the part of this range greater than the version.
+    pub(crate) fn above(&self, version: &Version) -> VersionRange {
+        Self {
+            comparators: (self.comparators.iter())
+                .map(|set| {
+                    let mut set = set.clone();
+                    (set.0).push(Comparator::GreaterThan(PartialVersion::from(
+                        version.clone(),
+                    )));
+                    set
+                })
+                .collect(),
+        }
+    }
+
+    /// Returns the part of this range less than the version.
+    pub(crate) fn below(&self, version: &Version) -> VersionRange {
+        Self {
+            comparators: (self.comparators.iter())
+                .map(|set| {
+                    let mut set = set.clone();
+                    (set.0).push(Comparator::LessThan(PartialVersion::from(version.clone())));
+                    set
+                })
+                .collect(),
+        }
+    }
 }
 
 serialize_to_string!(VersionRange);
@@ -120,12 +303,14 @@ impl FromStr for VersionRange {
     type Err = ParseVersionError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self {
-            comparators: s
-                .split("||")
-                .map(FromStr::from_str)
-                .collect::<Result<_, _>>()?,
-        })
+        let mut offset = 0;
+        let mut comparators = Vec::new();
+        for set in s.split("||") {
+            let parsed = ComparatorSet::from_str(set).map_err(|e| e.offset(offset))?;
+            comparators.push(parsed);
+            offset += set.len() + "||".len();
+        }
+        Ok(Self { comparators })
     }
 }
 
@@ -153,6 +338,7 @@ impl FromParsingBuf for ComparatorSet {
     fn parse(buffer: &mut ParsingBuf) -> Result<Self, ParseVersionError> {
         let mut result = Vec::<Comparator>::new();
 
+        buffer.skip_ws();
         while !buffer.is_empty() {
             result.push(Comparator::parse(buffer)?);
             buffer.skip_ws();
@@ -170,6 +356,162 @@ impl ComparatorSet {
     fn contains_pre(&self) -> bool {
         self.0.iter().any(Comparator::contains_pre)
     }
+
+    fn lower_bound(&self) -> Option<Version> {
+        self.0.iter().filter_map(Comparator::lower_bound).max()
+    }
+
+    fn intersects(&self, other: &ComparatorSet) -> bool {
+        let (lower, upper) = tightest_bounds(self.0.iter().chain(&other.0));
+
+        let Some(upper) = upper else {
+            return true;
+        };
+
+        // prerelease versions only match if every comparator mentions a prerelease
+        let accepts_pre = self.0.iter().chain(&other.0).all(Comparator::contains_pre);
+        let lowest = match lower {
+            Some(lower) if accepts_pre || lower.version.is_stable() => lower,
+            // the lowest stable version above the prerelease
+            Some(lower) => Bound::inclusive(Version::new(
+                lower.version.major,
+                lower.version.minor,
+                lower.version.patch,
+            )),
+            None => return true,
+        };
+        let lowest = if lowest.inclusive || accepts_pre {
+            lowest
+        } else {
+            // the lowest stable version above the exclusive stable bound
+            Bound::inclusive(Version::new(
+                lowest.version.major,
+                lowest.version.minor,
+                lowest.version.patch + 1,
+            ))
+        };
+
+        lowest.version < upper.version
+            || lowest.version == upper.version && lowest.inclusive && upper.inclusive
+    }
+
+    /// Returns the set without comparators implied by other comparators.
+    fn canonicalize(&self) -> ComparatorSet {
+        let mut kept = Vec::<Comparator>::new();
+        for comparator in &self.0 {
+            if (kept.iter()).any(|x| x == comparator || x.implies(comparator)) {
+                continue;
+            }
+            kept.retain(|x| !comparator.implies(x));
+            kept.push(comparator.clone());
+        }
+        ComparatorSet(kept)
+    }
+
+    /// Returns true if every version matched by this set is matched by the other set.
+    ///
+    /// Sets mentioning prerelease versions are only covered by the equal set since they change
+    /// which prerelease versions match.
+    fn is_covered_by(&self, other: &ComparatorSet) -> bool {
+        if self == other {
+            return true;
+        }
+        // an empty set matches prerelease versions even if no comparator mentions a prerelease
+        if self.0.is_empty() || self.contains_pre() {
+            return false;
+        }
+        let (lower, upper) = tightest_bounds(self.0.iter());
+        let (other_lower, other_upper) = tightest_bounds(other.0.iter());
+        Bound::lower_within(lower.as_ref(), other_lower.as_ref())
+            && Bound::upper_within(upper.as_ref(), other_upper.as_ref())
+    }
+}
+
+/// Returns the tightest lower and upper bounds of the comparators, ignoring prerelease acceptance.
+fn tightest_bounds<'a>(
+    comparators: impl Iterator<Item = &'a Comparator>,
+) -> (Option<Bound>, Option<Bound>) {
+    let mut lower = None::<Bound>;
+    let mut upper = None::<Bound>;
+    for (lower_of, upper_of) in comparators.map(Comparator::bounds) {
+        if let Some(bound) = lower_of {
+            if lower.as_ref().is_none_or(|x| bound.is_tighter_lower(x)) {
+                lower = Some(bound);
+            }
+        }
+        if let Some(bound) = upper_of {
+            if upper.as_ref().is_none_or(|x| bound.is_tighter_upper(x)) {
+                upper = Some(bound);
+            }
+        }
+    }
+    (lower, upper)
+}
+
+/// The end of the interval of versions matched by a comparator.
+struct Bound {
+    version: Version,
+    inclusive: bool,
+}
+
+impl Bound {
+    fn inclusive(version: Version) -> Self {
+        Self {
+            version,
+            inclusive: true,
+        }
+    }
+
+    fn exclusive(version: Version) -> Self {
+        Self {
+            version,
+            inclusive: false,
+        }
+    }
+
+    /// The lowest prerelease of the version, which is exclusive upper bound of the previous versions.
+    fn before(major: u64, minor: u64, patch: u64) -> Self {
+        Self::exclusive(Version::new_pre(
+            major,
+            minor,
+            patch,
+            Prerelease::new("0").unwrap(),
+        ))
+    }
+
+    fn is_tighter_lower(&self, other: &Bound) -> bool {
+        self.version > other.version || self.version == other.version && !self.inclusive
+    }
+
+    fn is_tighter_upper(&self, other: &Bound) -> bool {
+        self.version < other.version || self.version == other.version && !self.inclusive
+    }
+
+    /// Returns true if the lower bound excludes every version the other lower bound excludes.
+    /// None is unbounded.
+    fn lower_within(this: Option<&Bound>, other: Option<&Bound>) -> bool {
+        match (this, other) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(this), Some(other)) => {
+                this.version > other.version
+                    || this.version == other.version && (other.inclusive || !this.inclusive)
+            }
+        }
+    }
+
+    /// Returns true if the upper bound excludes every version the other upper bound excludes.
+    /// None is unbounded.
+    fn upper_within(this: Option<&Bound>, other: Option<&Bound>) -> bool {
+        match (this, other) {
+            (_, None) => true,
+            (None, Some(_)) => false,
+            (Some(this), Some(other)) => {
+                this.version < other.version
+                    || this.version == other.version && (other.inclusive || !this.inclusive)
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq)]
@@ -200,7 +542,8 @@ impl Display for Comparator {
             Comparator::GreaterThanOrEqual(v) => write!(f, ">={v}"),
             Comparator::LessThan(v) => write!(f, "<{v}"),
             Comparator::LessThanOrEqual(v) => write!(f, "<={v}"),
-            Comparator::Hyphen(a, b) => write!(f, "{a}-{b}"),
+            // spaces are required since `{a}-{b}` is parsed as a prerelease
+            Comparator::Hyphen(a, b) => write!(f, "{a} - {b}"),
             Comparator::Star(v) => Display::fmt(v, f),
         }
     }
@@ -220,7 +563,9 @@ impl Comparator {
         match allow_prerelease {
             PrereleaseAcceptance::Deny => false,
             PrereleaseAcceptance::Allow => true,
-            PrereleaseAcceptance::Minimum => {
+            PrereleaseAcceptance::Minimum | PrereleaseAcceptance::MinimumSameVersion => {
+                let same_version =
+                    matches!(allow_prerelease, PrereleaseAcceptance::MinimumSameVersion);
                 let in_versions: &[&PartialVersion] = match self {
                     Self::Tilde(c) => &[c],
                     Self::Caret(c) => &[c],
@@ -233,9 +578,11 @@ impl Comparator {
                     Self::Hyphen(c, d) => &[c, d],
                 };
 
-                for version in in_versions {
-                    let version = version.to_zeros();
-                    if version.is_pre() && version.base_version() == version.base_version() {
+                for in_version in in_versions {
+                    let in_version = in_version.to_zeros();
+                    if in_version.is_pre()
+                        && (!same_version || in_version.base_version() == version.base_version())
+                    {
                         return true;
                     }
                 }
@@ -324,6 +671,100 @@ impl Comparator {
         }
     }
 
+    fn lower_bound(&self) -> Option<Version> {
+        match self {
+            Comparator::Tilde(v)
+            | Comparator::Caret(v)
+            | Comparator::Exact(v)
+            | Comparator::GreaterThanOrEqual(v)
+            | Comparator::Star(v)
+            | Comparator::Hyphen(v, _) => Some(v.to_zeros()),
+            Comparator::GreaterThan(v) => Some(v.to_full_or_next().0),
+            Comparator::LessThan(_) | Comparator::LessThanOrEqual(_) => None,
+        }
+    }
+
+    /// Returns the lower and upper bounds of the versions matched, ignoring prerelease acceptance.
+    fn bounds(&self) -> (Option<Bound>, Option<Bound>) {
+        fn greater_than(v: &PartialVersion) -> Bound {
+            match v.to_full_or_next() {
+                (full, true) => Bound::exclusive(full),
+                (next, false) => Bound::inclusive(next),
+            }
+        }
+        fn greater_than_or_equal(v: &PartialVersion) -> Bound {
+            match v.to_full() {
+                Some(v) => Bound::inclusive(v),
+                None => Bound::inclusive(v.to_zeros_with_pre()),
+            }
+        }
+        fn less_than(v: &PartialVersion) -> Bound {
+            match v.to_full() {
+                Some(v) => Bound::exclusive(v),
+                None => Bound::exclusive(v.to_zeros_with_pre()),
+            }
+        }
+        fn less_than_or_equal(v: &PartialVersion) -> Bound {
+            match v.to_full_or_next() {
+                (full, true) => Bound::inclusive(full),
+                (next, false) => Bound::exclusive(next),
+            }
+        }
+
+        match self {
+            Comparator::Tilde(v) => {
+                let major = v.major_or(0);
+                let upper = match v.minor() {
+                    Some(minor) => Bound::before(major, minor + 1, 0),
+                    None => Bound::before(major + 1, 0, 0),
+                };
+                (Some(Bound::inclusive(v.to_zeros())), Some(upper))
+            }
+            Comparator::Caret(v) => {
+                let upper = match (v.major(), v.minor(), v.patch()) {
+                    // ^* is always true
+                    (None, _, _) => None,
+                    (Some(0), Some(0), Some(patch)) => Some(Bound::before(0, 0, patch + 1)),
+                    (Some(0), Some(minor), _) => Some(Bound::before(0, minor + 1, 0)),
+                    (Some(major), _, _) => Some(Bound::before(major + 1, 0, 0)),
+                };
+                (Some(Bound::inclusive(v.to_zeros())), upper)
+            }
+            Comparator::Star(v) | Comparator::Exact(v) => match v.to_full_or_next() {
+                (full, true) => (
+                    Some(Bound::inclusive(full.clone())),
+                    Some(Bound::inclusive(full)),
+                ),
+                (next, false) => (
+                    Some(Bound::inclusive(v.to_zeros_with_pre())),
+                    Some(Bound::exclusive(next)),
+                ),
+            },
+            Comparator::GreaterThan(v) => (Some(greater_than(v)), None),
+            Comparator::GreaterThanOrEqual(v) => (Some(greater_than_or_equal(v)), None),
+            Comparator::LessThan(v) => (None, Some(less_than(v))),
+            Comparator::LessThanOrEqual(v) => (None, Some(less_than_or_equal(v))),
+            Comparator::Hyphen(lower, upper) => (
+                Some(greater_than_or_equal(lower)),
+                Some(less_than_or_equal(upper)),
+            ),
+        }
+    }
+
+    /// Returns true if every version matched by this comparator is matched by the other.
+    ///
+    /// Comparators mentioning prerelease versions never imply others since removing them changes
+    /// which prerelease versions match.
+    fn implies(&self, other: &Comparator) -> bool {
+        if self.contains_pre() || other.contains_pre() {
+            return false;
+        }
+        let (lower, upper) = self.bounds();
+        let (other_lower, other_upper) = other.bounds();
+        Bound::lower_within(lower.as_ref(), other_lower.as_ref())
+            && Bound::upper_within(upper.as_ref(), other_upper.as_ref())
+    }
+
     fn contains_pre(&self) -> bool {
         match self {
             Comparator::Tilde(v)
@@ -599,16 +1040,17 @@ impl FromParsingBuf for PartialVersion {
                     while let Some(b'0'..=b'9') = bytes.get(i) {
                         i += 1;
                     }
-                    let str = bytes.take(i);
-                    let value = Segment::from_str(str).map_err(|_| ParseVersionError::too_big())?;
+                    let value = Segment::from_str(&bytes.buf[..i])
+                        .map_err(|_| ParseVersionError::too_big())?;
+                    bytes.take(i);
                     Ok(value)
                 }
                 Some(b'0') => {
-                    bytes.skip();
                     // if 0\d, 0 is invalid char
-                    if let Some(b'0'..=b'9') = bytes.first() {
+                    if let Some(b'0'..=b'9') = bytes.get(1) {
                         return Err(ParseVersionError::invalid());
                     }
+                    bytes.skip();
                     Ok(Segment::ZERO)
                 }
                 Some(_) => Err(ParseVersionError::invalid()),
@@ -830,6 +1272,9 @@ mod tests {
         test("^0.0.1-alpha", "0.0.1");
         test("^0.1.1-alpha", "0.1.1-beta");
         test("^x", "1.2.3");
+        test("^0.2.3", "0.2.9");
+        test("~1.2", "1.2.9");
+        test("~1.2", "1.2.0");
         test("x - 1.0.0", "0.9.7");
         test("x - 1.x", "0.9.7");
         test("1.0.0 - x", "1.9.7");
@@ -991,5 +1436,299 @@ mod tests {
         test_pre(">=1.0.0 <1.1.0", "1.1.0");
         test(">=1.0.0 <1.1.0", "1.1.0-pre");
         test(">=1.0.0 <1.1.0-pre", "1.1.0-pre");
+
+        // caret and tilde shorthand used in hand-written manifests
+        test("^0.2.3", "0.3.0");
+        test("^0.2.3", "0.2.2");
+        test("~1.2", "1.3.0");
+        test("~1.2", "1.1.9");
+    }
+
+    #[test]
+    fn test_lower_bound() {
+        fn test(range: &str, expected: Option<&str>) {
+            let range = VersionRange::from_str(range).expect(range);
+            let expected = expected.map(|x| Version::from_str(x).expect(x));
+            assert_eq!(range.lower_bound(), expected, "lower bound of {range}");
+        }
+
+        test(">=1.2.0", Some("1.2.0"));
+        test("^1.2", Some("1.2.0"));
+        test("~1.2.3", Some("1.2.3"));
+        test(">1.2.3", Some("1.2.3"));
+        test(">1.2", Some("1.3.0-0"));
+        test(">=1.0.0 >=1.5.0 <2.0.0", Some("1.5.0"));
+        test("^2.0.0 || ^1.1.0", Some("1.1.0"));
+        test("<2.0.0", None);
+        test("^1.0.0 || <0.5.0", None);
+    }
+
+    #[test]
+    fn test_prerelease_matching() {
+        fn test(range: &str, version: &str, matching: PrereleaseMatching, expected: bool) {
+            let range = VersionRange::from_str(range).expect(range);
+            let version = Version::from_str(version).expect(version);
+            assert_eq!(
+                range.match_pre(&version, matching.minimum()),
+                expected,
+                "{range} matches {version} with {matching:?}"
+            );
+        }
+
+        for matching in [
+            PrereleaseMatching::AnyPrerelease,
+            PrereleaseMatching::SameVersion,
+        ] {
+            test(">=1.0.0", "1.2.0-rc.1", matching, false);
+            test(">=1.0.0", "1.2.0", matching, true);
+            test(">=1.0.0-0", "1.0.0-beta.1", matching, true);
+            test(">=1.2.0-0", "1.2.0-rc.1", matching, true);
+        }
+
+        test(
+            ">=1.0.0-0",
+            "1.2.0-rc.1",
+            PrereleaseMatching::AnyPrerelease,
+            true,
+        );
+        test(
+            ">=1.0.0-0",
+            "1.2.0-rc.1",
+            PrereleaseMatching::SameVersion,
+            false,
+        );
+    }
+
+    #[test]
+    fn test_intersects() {
+        fn test(a: &str, b: &str, expected: bool) {
+            let a = VersionRange::from_str(a).expect(a);
+            let b = VersionRange::from_str(b).expect(b);
+            assert_eq!(a.intersects(&b), expected, "{a} intersects {b}");
+            assert_eq!(b.intersects(&a), expected, "{b} intersects {a}");
+        }
+
+        test(">=2.0.0", "<1.5.0", false);
+        test(">=1.0.0", "<1.5.0", true);
+        test("^1.0.0", "^2.0.0", false);
+        test("^1.0.0", "~1.2.3", true);
+        test("^0.1.0", "^0.2.0", false);
+        test("^0.0.1", "0.0.2", false);
+        test(">1.0.0", "<=1.0.0", false);
+        test(">=1.0.0", "<=1.0.0", true);
+        test("1.0.0", "1.0.1", false);
+        test("1.x", ">=1.9.0", true);
+        test("1.0.0 - 1.2.0", ">1.2.0", false);
+        test("^1.0.0 || ^3.0.0", ">=2.0.0 <4.0.0", true);
+        test("^1.0.0 || ^3.0.0", "^2.0.0", false);
+        test("*", "<0.1.0", true);
+        test(">1.0.0", "<1.0.1", false);
+        test(">=1.0.0-beta.1", "<1.0.0", false);
+        test(">=1.0.0-beta.1", "<1.0.0-beta.3", true);
+        test(">=1.0.0-beta.1", "<=1.0.0", true);
+    }
+
+    #[test]
+    fn test_intersection() {
+        fn test(a: &str, b: &str, expected: Option<&str>) {
+            let a = VersionRange::from_str(a).expect(a);
+            let b = VersionRange::from_str(b).expect(b);
+            let actual = a.intersection(&b).map(|x| x.to_string());
+            assert_eq!(actual.as_deref(), expected, "intersection of {a} and {b}");
+        }
+
+        test(">=1.0.0", "<1.5.0", Some(">=1.0.0 <1.5.0"));
+        test(
+            "^1.0.0 || ^3.0.0",
+            ">=1.2.0",
+            Some("^1.0.0 >=1.2.0 || ^3.0.0 >=1.2.0"),
+        );
+        test("^1.0.0 || ^3.0.0", "^3.1.0", Some("^3.0.0 ^3.1.0"));
+        test(">=2.0.0", "<1.5.0", None);
+    }
+
+    #[test]
+    fn test_display_canonical() {
+        fn test(range: &str, expected: &str) {
+            let parsed = VersionRange::from_str(range).expect(range);
+            assert_eq!(parsed.to_string(), expected, "display of {range}");
+            let reparsed = VersionRange::from_str(expected).expect(expected);
+            assert_eq!(reparsed, parsed, "round trip of {range}");
+        }
+
+        test("^1.2.3", "^1.2.3");
+        test("^ 0.2.3", "^0.2.3");
+        test("~ 1.2", "~1.2");
+        test("~1.2.3 || ^2", "~1.2.3 || ^2");
+        test("*", "*");
+        test("", "");
+        test("1.x.X", "1.x.X");
+        test("=1.0.0", "=1.0.0");
+        test("v1.0.0", "1.0.0");
+        test("1.0.0beta", "1.0.0-beta");
+        test("1.0.0 -2.0.0", "1.0.0 - 2.0.0");
+        test("1.0.0-beta.1 - 2", "1.0.0-beta.1 - 2");
+        test(">= 1.0.0-rc.1 < 2.0.0-0", ">=1.0.0-rc.1 <2.0.0-0");
+        test("1.0.0+build.1 || ", "1.0.0+build.1 || ");
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        fn test(range: &str, expected: &str) {
+            let parsed = DependencyRange::from_str(range).expect(range);
+            let canonical = parsed.canonicalize();
+            assert_eq!(canonical.to_string(), expected, "canonical form of {range}");
+            assert_eq!(
+                canonical.canonicalize(),
+                canonical,
+                "canonical form of {expected}"
+            );
+            assert_eq!(
+                canonical.as_range().contains_pre(),
+                parsed.as_range().contains_pre()
+            );
+        }
+
+        test("1.2.3", ">=1.2.3");
+        test("1.2.3-beta.1", ">=1.2.3-beta.1");
+        test("1.2", "1.2");
+        test(">=1.0.0 >=1.2.0", ">=1.2.0");
+        test(">=1.0.0 <2.0.0 <3.0.0 >0.5.0", ">=1.0.0 <2.0.0");
+        test("^1.2.3 <2.0.0", "^1.2.3");
+        test("^1.2.3 || ^1.2.3", "^1.2.3");
+        test("^1.2.3 || ~1.4.0", "^1.2.3");
+        test("~1.4.0 || ^1.2.3", "^1.2.3");
+        test("^1.0.0 || ^2.0.0", "^1.0.0 || ^2.0.0");
+        test(">=1.0.0-0 >=1.2.0", ">=1.0.0-0 >=1.2.0");
+        test(">=1.0.0-0 >=1.0.0-0", ">=1.0.0-0");
+        test("^1.2.3-beta || ^1.0.0", "^1.2.3-beta || ^1.0.0");
+        test("^1.0.0 || ^1.2.3-beta", "^1.0.0 || ^1.2.3-beta");
+        test("^1.0.0 || ", "");
+        test("1.2.3 || 1.2.3", "=1.2.3");
+        test("1.2.3 >=1.0.0", "=1.2.3");
+        test("=1.2.3", "=1.2.3");
+    }
+
+    #[test]
+    fn test_serde_round_trip() {
+        let range = VersionRange::from_str(">=1.0.0-beta <2 || 3.0.0 - 3.2").unwrap();
+        let json = serde_json::to_string(&range).unwrap();
+        assert_eq!(json, r#"">=1.0.0-beta <2 || 3.0.0 - 3.2""#);
+        assert_eq!(serde_json::from_str::<VersionRange>(&json).unwrap(), range);
+    }
+
+    #[test]
+    fn test_display_round_trip_random() {
+        // xorshift to generate the same ranges every run
+        struct Random(u64);
+        impl Random {
+            fn next(&mut self, bound: u64) -> u64 {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                self.0 % bound
+            }
+        }
+
+        fn segment(random: &mut Random) -> Segment {
+            match random.next(8) {
+                0 => Segment::STAR,
+                1 => Segment::UPPER_X,
+                2 => Segment::LOWER_X,
+                _ => Segment::new(random.next(20)).unwrap(),
+            }
+        }
+
+        fn partial_version(random: &mut Random) -> PartialVersion {
+            const IDENTIFIERS: &[&str] = &["0", "1", "beta", "rc.1", "alpha.0.x", "-", "a-b"];
+
+            let major = segment(random);
+            let (minor, patch) = match random.next(3) {
+                0 => (Segment::NOT_EXISTS, Segment::NOT_EXISTS),
+                1 => (segment(random), Segment::NOT_EXISTS),
+                _ => (segment(random), segment(random)),
+            };
+            let (pre, build) = if patch == Segment::NOT_EXISTS {
+                (Prerelease::EMPTY, BuildMetadata::EMPTY)
+            } else {
+                let mut identifier = || match random.next(IDENTIFIERS.len() as u64 + 1) {
+                    0 => None,
+                    i => Some(IDENTIFIERS[i as usize - 1]),
+                };
+                let pre = identifier().map_or(Prerelease::EMPTY, |x| Prerelease::new(x).unwrap());
+                let build =
+                    identifier().map_or(BuildMetadata::EMPTY, |x| BuildMetadata::new(x).unwrap());
+                (pre, build)
+            };
+            PartialVersion {
+                major,
+                minor,
+                patch,
+                pre,
+                build,
+            }
+        }
+
+        fn comparator(random: &mut Random) -> Comparator {
+            let version = partial_version(random);
+            match random.next(9) {
+                0 => Comparator::Tilde(version),
+                1 => Comparator::Caret(version),
+                2 => Comparator::Exact(version),
+                3 => Comparator::GreaterThan(version),
+                4 => Comparator::GreaterThanOrEqual(version),
+                5 => Comparator::LessThan(version),
+                6 => Comparator::LessThanOrEqual(version),
+                7 => Comparator::Hyphen(version, partial_version(random)),
+                _ => Comparator::Star(version),
+            }
+        }
+
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for _ in 0..10000 {
+            let range = VersionRange {
+                comparators: (0..1 + random.next(3))
+                    .map(|_| {
+                        ComparatorSet(
+                            (0..random.next(4))
+                                .map(|_| comparator(&mut random))
+                                .collect(),
+                        )
+                    })
+                    .collect(),
+            };
+            let display = range.to_string();
+            let parsed = VersionRange::from_str(&display).expect(&display);
+            assert_eq!(parsed, range, "round trip of {display}");
+        }
+    }
+
+    #[test]
+    fn test_parse_error_span() {
+        fn test(range: &str, kind: ParseVersionErrorKind, position: usize, offending: &str) {
+            let err = VersionRange::from_str(range).unwrap_err();
+            assert_eq!(err.kind(), kind, "kind for {range}");
+            assert_eq!(err.position(), position, "position for {range}");
+            assert_eq!(err.offending(), offending, "offending for {range}");
+            assert_eq!(&range[err.span()], offending, "span for {range}");
+        }
+
+        use ParseVersionErrorKind::*;
+        test(">=1.0.0 <", Invalid, 9, "");
+        test("1.2.x.y", Invalid, 5, ".y");
+        test("^1.0.0 || 1.0.0.0.1", Invalid, 15, ".0.1");
+        test("01.0.0", Invalid, 0, "01.0.0");
+        test("1.0.0 ~abc", Invalid, 7, "abc");
+        test(
+            "99999999999999999999999.0.0",
+            VersionSegmentTooBig,
+            0,
+            "99999999999999999999999.0.0",
+        );
+        test(">=1.0.0 || <2.0.0 !", Invalid, 18, "!");
+
+        let err = DependencyRange::from_str("1.0.0 - ").unwrap_err();
+        assert_eq!(err.position(), 8);
+        assert_eq!(err.offending(), "");
     }
 }
@@ -16,12 +16,13 @@ pub struct UnityVersion {
     minor: u8,
     // revision number, updated every few weeks
     revision: u8,
-    // release type. a < b < f = c < p < x
+    // release type. a < b < f = c < p < x < unknown
     //   'a' for alpha (expects revision to be zero)
     //   'b' for beta (expects revision to be zero)
     //   'f' and 'c' for normal ('c' is for china)
     //   'p' for patches
     //   'x' for experimental
+    //   other letters are unknown types, sorted after known ones
     type_: ReleaseType,
     // revision increment
     increment: u8,
@@ -83,10 +84,17 @@ impl UnityVersion {
         let major = u16::from_str(major).ok()?;
         let (minor, rest) = rest.split_once('.')?;
         let minor = u8::from_str(minor).ok()?;
-        let revision_delimiter = rest.find(is_release_type_char)?;
+        let revision_delimiter = rest.find(|c: char| c.is_ascii_alphabetic())?;
         let revision = &rest[..revision_delimiter];
         let revision = u8::from_str(revision).ok()?;
-        let type_ = ReleaseType::try_from(rest.as_bytes()[revision_delimiter]).ok()?;
+        let type_char = rest.as_bytes()[revision_delimiter];
+        let type_ = ReleaseType::try_from(type_char).unwrap_or_else(|_| {
+            log::warn!(
+                "unknown release type '{}' in unity version {input}",
+                type_char as char
+            );
+            ReleaseType::Unknown(type_char)
+        });
         let rest = &rest[revision_delimiter + 1..];
 
         let (increment_part, _rest) = rest.split_once('-').unwrap_or((rest, ""));
@@ -100,18 +108,14 @@ impl UnityVersion {
             china_increment = None;
         }
 
-        return Some(Self {
+        Some(Self {
             major,
             minor,
             revision,
             type_,
             increment,
             china_increment,
-        });
-
-        fn is_release_type_char(c: char) -> bool {
-            c == 'a' || c == 'b' || c == 'f' || c == 'c' || c == 'p' || c == 'x'
-        }
+        })
     }
 
     // expects major.minor.revision
@@ -222,6 +226,8 @@ pub enum ReleaseType {
     China,
     Patch,
     Experimental,
+    /// The release type with the letter vrc-get doesn't know. This is sorted after all known types.
+    Unknown(u8),
 }
 
 impl PartialEq for ReleaseType {
@@ -233,6 +239,7 @@ impl PartialEq for ReleaseType {
             (Self::China, Self::China) => true,
             (Self::Patch, Self::Patch) => true,
             (Self::Experimental, Self::Experimental) => true,
+            (Self::Unknown(a), Self::Unknown(b)) => a == b,
 
             // exceptions!
             (Self::Normal, Self::China) => true,
@@ -275,6 +282,10 @@ impl Ord for ReleaseType {
             (_, Patch) => Greater,
 
             (Experimental, Experimental) => Equal,
+            (Experimental, _) => Less,
+            (_, Experimental) => Greater,
+
+            (Unknown(a), Unknown(b)) => a.cmp(&b),
         }
     }
 }
@@ -290,6 +301,7 @@ impl fmt::Display for ReleaseType {
             ReleaseType::China => f.write_str("c"),
             ReleaseType::Patch => f.write_str("p"),
             ReleaseType::Experimental => f.write_str("x"),
+            ReleaseType::Unknown(c) => write!(f, "{}", *c as char),
         }
     }
 }
@@ -310,6 +322,91 @@ impl TryFrom<u8> for ReleaseType {
     }
 }
 
+/// The range of unity versions. The minimum is inclusive and the maximum is exclusive.
+///
+/// `None` means no bound for the side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct UnityVersionRange {
+    minimum: Option<UnityVersion>,
+    maximum: Option<UnityVersion>,
+}
+
+impl UnityVersionRange {
+    pub const fn new(minimum: Option<UnityVersion>, maximum: Option<UnityVersion>) -> Self {
+        Self { minimum, maximum }
+    }
+
+    pub fn minimum(&self) -> Option<UnityVersion> {
+        self.minimum
+    }
+
+    pub fn maximum(&self) -> Option<UnityVersion> {
+        self.maximum
+    }
+
+    pub fn contains(&self, version: UnityVersion) -> bool {
+        self.minimum.is_none_or(|min| min <= version)
+            && self.maximum.is_none_or(|max| version < max)
+    }
+
+    /// Parses the range like `>=2019.4 <2022.3`.
+    ///
+    /// Each bound is `>=` or `<` followed by a unity version like `2022.3.6f1`, or
+    /// `major.minor` for the first release of the minor version. An empty string means any version.
+    pub fn parse(input: &str) -> Option<Self> {
+        fn parse_version(input: &str) -> Option<UnityVersion> {
+            if let Some(version) = UnityVersion::parse(input) {
+                return Some(version);
+            }
+            let (major, minor) = input.split_once('.').unwrap_or((input, "0"));
+            let major = u16::from_str(major).ok()?;
+            let minor = u8::from_str(minor).ok()?;
+            Some(UnityVersion::new(major, minor, 0, ReleaseType::Alpha, 0))
+        }
+
+        let mut range = Self::default();
+        for bound in input.split_whitespace() {
+            if let Some(version) = bound.strip_prefix(">=") {
+                range.minimum = Some(parse_version(version)?);
+            } else if let Some(version) = bound.strip_prefix('<') {
+                range.maximum = Some(parse_version(version)?);
+            } else {
+                return None;
+            }
+        }
+        Some(range)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        match (self.minimum, self.maximum) {
+            (Some(min), Some(max)) => max <= min,
+            _ => false,
+        }
+    }
+
+    /// Returns the range both ranges contain. The result may be empty.
+    pub fn intersect(&self, other: &Self) -> Self {
+        Self {
+            minimum: self.minimum.max(other.minimum),
+            maximum: match (self.maximum, other.maximum) {
+                (Some(a), Some(b)) => Some(a.min(b)),
+                (a, b) => a.or(b),
+            },
+        }
+    }
+}
+
+impl fmt::Display for UnityVersionRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.minimum, self.maximum) {
+            (None, None) => f.write_str("any"),
+            (Some(min), None) => write!(f, ">={min}"),
+            (None, Some(max)) => write!(f, "<{max}"),
+            (Some(min), Some(max)) => write!(f, ">={min} <{max}"),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -363,6 +460,11 @@ mod tests {
 
         good_cn!("2022.3.22f1c1", 2022, 3, 22, Normal, 1, 1);
 
+        let unknown = UnityVersion::parse("2023.3.6r2").unwrap();
+        assert_eq!(unknown.type_, ReleaseType::Unknown(b'r'));
+        assert_eq!(unknown.increment, 2);
+        assert_eq!(unknown.to_string(), "2023.3.6r2");
+
         bad!("2022");
         bad!("2019.0");
         bad!("5.6.6");
@@ -384,6 +486,42 @@ mod tests {
         test!("5.6.6f1" < "5.6.6f2");
         test!("5.6.6f1" < "2022.1.0f1");
         test!("2022.1.0a1" < "2022.1.0f1");
+
+        // release types with the same revision
+        test!("2022.3.6a2" < "2022.3.6b1");
+        test!("2022.3.6b5" < "2022.3.6f1");
+        test!("2022.3.6f1" < "2022.3.6f2");
+        test!("2022.3.6f2" < "2022.3.6p1");
+        test!("2022.3.6p4" < "2022.3.6x1");
+        test!("2022.3.6x1" < "2022.3.6r1");
+
+        // increments in each release type
+        test!("2022.3.6a1" < "2022.3.6a2");
+        test!("2022.3.6b1" < "2022.3.6b3");
+        test!("2022.3.6p1" < "2022.3.6p4");
+
+        // revisions take precedence over release types
+        test!("2022.3.5p4" < "2022.3.6a1");
+        test!("2022.3.6r9" < "2022.3.7a1");
+    }
+
+    #[test]
+    fn parse_unity_version_range() {
+        let range = UnityVersionRange::parse(">=2019.4 <2022.3.6f1").unwrap();
+        assert_eq!(
+            range.minimum(),
+            Some(UnityVersion::new(2019, 4, 0, ReleaseType::Alpha, 0))
+        );
+        assert_eq!(range.maximum(), Some(UnityVersion::new_f1(2022, 3, 6)));
+        assert!(range.contains(UnityVersion::new_f1(2019, 4, 31)));
+        assert!(!range.contains(UnityVersion::new_f1(2022, 3, 6)));
+
+        assert_eq!(
+            UnityVersionRange::parse(""),
+            Some(UnityVersionRange::default())
+        );
+        assert_eq!(UnityVersionRange::parse(">=2022.x"), None);
+        assert_eq!(UnityVersionRange::parse("2022.3"), None);
     }
 
     #[test]
@@ -443,6 +581,11 @@ mod tests {
         test!(Experimental China Greater);
         test!(Experimental Patch Greater);
         test!(Experimental Experimental Equal);
+
+        assert!(Experimental < Unknown(b'r'));
+        assert!(Unknown(b'r') < Unknown(b's'));
+        assert_eq!(Unknown(b'r').cmp(&Unknown(b'r')), Ordering::Equal);
+        assert_eq!(Unknown(b'r').cmp(&Alpha), Ordering::Greater);
     }
 
     #[test]
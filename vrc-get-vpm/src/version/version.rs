@@ -75,19 +75,19 @@ impl FromParsingBuf for Version {
                     while let Some(b'0'..=b'9') = bytes.get(i) {
                         i += 1;
                     }
-                    let str = bytes.take(i);
-                    let value = Segment::from_str(str)
+                    let value = Segment::from_str(&bytes.buf[..i])
                         .map_err(|_| ParseVersionError::too_big())?
                         .as_number()
                         .unwrap();
+                    bytes.take(i);
                     Ok(value)
                 }
                 Some(b'0') => {
-                    bytes.skip();
                     // if 0\d, 0 is invalid char
-                    if let Some(b'0'..=b'9') = bytes.first() {
+                    if let Some(b'0'..=b'9') = bytes.get(1) {
                         return Err(ParseVersionError::invalid());
                     }
+                    bytes.skip();
                     Ok(0)
                 }
                 Some(_) => Err(ParseVersionError::invalid()),
@@ -165,4 +165,11 @@ impl Version {
     pub fn is_stable(&self) -> bool {
         self.pre.is_empty()
     }
+
+    /// Returns true if the versions are equal including the build metadata.
+    ///
+    /// `==` ignores the build metadata since it has no precedence.
+    pub fn is_same_build(&self, other: &Version) -> bool {
+        self == other && self.build == other.build
+    }
 }
@@ -1,9 +1,16 @@
 use crate::version::{PrereleaseAcceptance, UnityVersion, Version, VersionRange};
-use crate::{PackageManifest, unity_compatible};
+use crate::{PackageInfo, PackageManifest, unity_compatible};
+use std::time::SystemTime;
 
 #[derive(Clone, Copy)]
 pub struct VersionSelector<'a> {
     inner: SelectorInner<'a>,
+    published_before: Option<SystemTime>,
+    target_platform: Option<&'a str>,
+    target_unity_versions: &'a [UnityVersion],
+    preferred: Option<&'a Version>,
+    prerelease_repositories: Option<&'a [&'a str]>,
+    repository: Option<&'a str>,
 }
 
 #[derive(Clone, Copy)]
@@ -18,6 +25,11 @@ enum SelectorInner<'a> {
         range: &'a VersionRange,
         allow_prerelease: PrereleaseAcceptance,
     },
+    LowestRange {
+        project_unity: Option<UnityVersion>,
+        range: &'a VersionRange,
+        allow_prerelease: PrereleaseAcceptance,
+    },
     Ranges {
         project_unity: Option<UnityVersion>,
         ranges: &'a [&'a VersionRange],
@@ -28,12 +40,24 @@ enum SelectorInner<'a> {
 impl<'a> VersionSelector<'a> {
     pub fn specific_version(version: &'a Version) -> Self {
         Self {
+            published_before: None,
+            target_platform: None,
+            target_unity_versions: &[],
+            preferred: None,
+            prerelease_repositories: None,
+            repository: None,
             inner: SelectorInner::Specific(version),
         }
     }
 
     pub fn latest_for(unity_version: Option<UnityVersion>, include_prerelease: bool) -> Self {
         Self {
+            published_before: None,
+            target_platform: None,
+            target_unity_versions: &[],
+            preferred: None,
+            prerelease_repositories: None,
+            repository: None,
             inner: SelectorInner::Latest {
                 project_unity: unity_version,
                 include_prerelease,
@@ -47,6 +71,12 @@ impl<'a> VersionSelector<'a> {
         allow_prerelease: PrereleaseAcceptance,
     ) -> Self {
         Self {
+            published_before: None,
+            target_platform: None,
+            target_unity_versions: &[],
+            preferred: None,
+            prerelease_repositories: None,
+            repository: None,
             inner: SelectorInner::Range {
                 project_unity: unity_version,
                 range,
@@ -55,12 +85,39 @@ impl<'a> VersionSelector<'a> {
         }
     }
 
+    /// Selects the lowest version in the range instead of the highest.
+    pub fn lowest_range_for(
+        unity_version: Option<UnityVersion>,
+        range: &'a VersionRange,
+        allow_prerelease: PrereleaseAcceptance,
+    ) -> Self {
+        Self {
+            published_before: None,
+            target_platform: None,
+            target_unity_versions: &[],
+            preferred: None,
+            prerelease_repositories: None,
+            repository: None,
+            inner: SelectorInner::LowestRange {
+                project_unity: unity_version,
+                range,
+                allow_prerelease,
+            },
+        }
+    }
+
     pub fn ranges_for(
         unity_version: Option<UnityVersion>,
         ranges: &'a [&'a VersionRange],
         allow_prerelease: PrereleaseAcceptance,
     ) -> Self {
         Self {
+            published_before: None,
+            target_platform: None,
+            target_unity_versions: &[],
+            preferred: None,
+            prerelease_repositories: None,
+            repository: None,
             inner: SelectorInner::Ranges {
                 project_unity: unity_version,
                 ranges,
@@ -68,32 +125,138 @@ impl<'a> VersionSelector<'a> {
             },
         }
     }
+
+    /// Prefers the version, like the version already installed, over any other version if the
+    /// version satisfies the selector.
+    ///
+    /// This doesn't affect [`Self::specific_version`].
+    pub fn prefer_version(mut self, version: Option<&'a Version>) -> Self {
+        self.preferred = version;
+        self
+    }
+
+    /// Accepts prerelease versions only from the repositories with the ids.
+    ///
+    /// This restricts the prerelease versions accepted by the prerelease acceptance,
+    /// so stable versions are accepted from any repository as usual.
+    /// Since [`Self::satisfies`] doesn't know the repository, see [`Self::in_repository`].
+    pub fn prerelease_only_from(mut self, repositories: Option<&'a [&'a str]>) -> Self {
+        self.prerelease_repositories = repositories;
+        self
+    }
+
+    /// Skips versions declaring not to work on the platform, like the unity version of the project.
+    ///
+    /// Versions without the declaration are compatible with any platform.
+    /// This doesn't affect [`Self::specific_version`].
+    pub fn target_platform(mut self, platform: Option<&'a str>) -> Self {
+        self.target_platform = platform;
+        self
+    }
+
+    /// Skips versions not compatible with any of the unity versions, in addition to the project one.
+    ///
+    /// This is for packages supporting multiple unity versions at once.
+    /// This doesn't affect [`Self::specific_version`].
+    pub fn target_unity_versions(mut self, unity_versions: &'a [UnityVersion]) -> Self {
+        self.target_unity_versions = unity_versions;
+        self
+    }
+
+    /// Sets the id of the repository the packages checked with [`Self::satisfies`] are from.
+    ///
+    /// Packages not from any repository, like user packages, have no id.
+    pub fn in_repository(mut self, repository: Option<&'a str>) -> Self {
+        self.repository = repository;
+        self
+    }
 }
 
 impl VersionSelector<'_> {
+    /// Skips versions published after the time, for resolving as if it were the time.
+    ///
+    /// Versions without the publish time are not skipped.
+    /// This doesn't affect [`Self::specific_version`].
+    pub fn published_before(mut self, published_before: Option<SystemTime>) -> Self {
+        self.published_before = published_before;
+        self
+    }
+
     pub(crate) fn as_specific(&self) -> Option<&Version> {
         match self.inner {
             SelectorInner::Specific(version) => Some(version),
             _ => None,
         }
     }
+
+    /// Returns true if the lowest satisfying version should be selected instead of the highest.
+    pub fn prefers_lowest(&self) -> bool {
+        matches!(self.inner, SelectorInner::LowestRange { .. })
+    }
+
+    /// Returns true if the package should be selected only if no other version satisfies the selector.
+    ///
+    /// Deprecated versions are sorted after non-deprecated ones but never hidden.
+    /// This doesn't affect [`Self::specific_version`].
+    pub fn is_deprioritized(&self, package: &PackageManifest) -> bool {
+        self.as_specific().is_none() && package.is_deprecated()
+    }
+
+    /// Returns true if the package should be selected over the other versions satisfying the selector.
+    ///
+    /// See [`Self::prefer_version`].
+    pub fn is_preferred(&self, package: &PackageManifest) -> bool {
+        self.preferred.is_some_and(|x| x == package.version())
+    }
 }
 
 impl VersionSelector<'_> {
+    /// Returns true if the package satisfies the selector, considering the repository of the package.
+    pub fn satisfies_package(&self, package: &PackageInfo) -> bool {
+        self.in_repository(package.repository_id())
+            .satisfies(package.package_json())
+    }
+
     pub fn satisfies(&self, package: &PackageManifest) -> bool {
-        fn unity_and_yank(package: &PackageManifest, project_unity: Option<UnityVersion>) -> bool {
+        if let Some(repositories) = self.prerelease_repositories {
+            let trusted = (self.repository).is_some_and(|id| repositories.contains(&id));
+            if !trusted && package.version().is_pre() {
+                return false;
+            }
+        }
+
+        let published_before = self.published_before;
+        let target_platform = self.target_platform;
+        let target_unity_versions = self.target_unity_versions;
+        let unity_and_yank = |package: &PackageManifest, project_unity: Option<UnityVersion>| {
             if package.is_yanked() {
                 return false;
             }
 
+            if let Some(platform) = target_platform {
+                if !package.is_compatible_with_platform(platform) {
+                    return false;
+                }
+            }
+
+            if let (Some(cutoff), Some(published_at)) = (published_before, package.published_at()) {
+                if published_at > cutoff {
+                    return false;
+                }
+            }
+
             if let Some(unity) = project_unity {
                 if !unity_compatible(package, unity) {
                     return false;
                 }
             }
 
+            if !(target_unity_versions.iter()).all(|&unity| unity_compatible(package, unity)) {
+                return false;
+            }
+
             true
-        }
+        };
 
         match self.inner {
             SelectorInner::Specific(finding) => finding == package.version(),
@@ -109,6 +272,11 @@ impl VersionSelector<'_> {
                 range,
                 project_unity,
                 allow_prerelease,
+            }
+            | SelectorInner::LowestRange {
+                range,
+                project_unity,
+                allow_prerelease,
             } => {
                 range.match_pre(package.version(), allow_prerelease)
                     && unity_and_yank(package, project_unity)
@@ -1,13 +1,37 @@
 use common::*;
 use futures::executor::block_on;
-use std::collections::HashSet;
+use futures::{AsyncReadExt, TryStreamExt};
+use indexmap::IndexMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
 use std::io;
 use std::path::Path;
-use vrc_get_vpm::PackageManifest;
-use vrc_get_vpm::io::IoTrait;
+use std::time::{Duration, SystemTime};
+use vrc_get_vpm::git_package::{GitPackage, GitPackageCollection};
+use vrc_get_vpm::io::{DirEntry, IoTrait, ProjectIo};
+use vrc_get_vpm::repository::{LocalCachedRepository, RemoteRepository};
 use vrc_get_vpm::unity_project::pending_project_changes::RemoveReason;
-use vrc_get_vpm::unity_project::{AddPackageErr, AddPackageOperation};
-use vrc_get_vpm::version::Version;
+use vrc_get_vpm::unity_project::{
+    AbsenceReason, AmbiguousLegacyReplacement, ConflictContext, ConflictDecision, ConflictPolicy,
+    ConflictSource, ConflictSuggestion, DropRequirement, LicensePolicy, LockedDependencyMismatch,
+    LockfileDrift, MAX_CONFLICT_RETRIES, MissingRequirement, MissingSourceRepository,
+    PackageOrigin, PrereleasePropagation, PrereleaseReason, QueueOrder, RejectionReason,
+    RelaxToAnyVersion, ResolutionDiff, ResolutionEventSink, SkipReason, ViolationContext,
+    ViolationDecision, check_compatibility, minimum_unity_version, resolve_dependencies,
+};
+use vrc_get_vpm::unity_project::{
+    AddPackageErr, AddPackageOperation, ChangesJson, LegacyRemoval, ResolutionOptions,
+    ResolvePackageErr,
+};
+use vrc_get_vpm::version::{
+    DependencyRange, PrereleaseMatching, ReleaseType, UnityVersion, UnityVersionRange, Version,
+    VersionRange,
+};
+use vrc_get_vpm::{
+    AbortCheck, CancellationToken, PackageAvailability, PackageCollection as _, PackageInfo,
+    PackageInstaller, PackageManifest, VersionSelector,
+};
 
 mod common;
 
@@ -335,11 +359,17 @@ fn do_not_remove_transitively_when_untouched() {
 }
 
 #[test]
-fn remove_legacy_package_when_install() {
+fn diff_resolution_against_locked() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
-            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.orphan", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
@@ -347,8 +377,12 @@ fn remove_legacy_package_when_install() {
         let collection = PackageCollectionBuilder::new()
             .add(
                 PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
-                    .add_legacy_package("com.anatawa12.legacy-package"),
+                    .add_vpm_dependency("com.anatawa12.new-library", "^1.0.0"),
             )
+            .add(PackageManifest::new(
+                "com.anatawa12.new-library",
+                Version::new(1, 0, 0),
+            ))
             .build();
 
         let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
@@ -357,33 +391,50 @@ fn remove_legacy_package_when_install() {
             .add_package_request(
                 &collection,
                 &[package],
-                AddPackageOperation::InstallToDependencies,
+                AddPackageOperation::UpgradeLocked,
                 false,
             )
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 2);
-        assert_eq!(result.remove_legacy_folders().len(), 0);
-        assert_eq!(result.remove_legacy_files().len(), 0);
-        assert_eq!(result.conflicts().len(), 0);
-
-        assert_installing_to_both(&result, &package);
-        assert_removed(
-            &result,
-            "com.anatawa12.legacy-package",
-            RemoveReason::Legacy,
+        let locked = project.locked_packages().collect::<Vec<_>>();
+        let diff = result.resolution().unwrap().diff_against_locked(&locked);
+        assert_eq!(
+            diff,
+            ResolutionDiff {
+                added: vec![("com.anatawa12.new-library".into(), Version::new(1, 0, 0))],
+                upgraded: vec![(
+                    "com.anatawa12.package".into(),
+                    Version::new(1, 0, 0),
+                    Version::new(1, 1, 0)
+                )],
+                // the orphan is not required before the resolution
+                orphaned: vec!["com.anatawa12.library".into()],
+                ..ResolutionDiff::default()
+            }
+        );
+        assert_eq!(
+            diff.to_string(),
+            "+ com.anatawa12.new-library 1.0.0\n\
+             ^ com.anatawa12.package 1.0.0 -> 1.1.0\n\
+             ? com.anatawa12.library is no longer required\n"
         );
     })
 }
 
 #[test]
-fn remove_legacy_package_when_upgrade() {
+fn report_origins_of_packages() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
             .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
-            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
-            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .add_dependency("com.anatawa12.other", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.other", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
@@ -391,57 +442,82 @@ fn remove_legacy_package_when_upgrade() {
         let collection = PackageCollectionBuilder::new()
             .add(
                 PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
-                    .add_legacy_package("com.anatawa12.legacy-package"),
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.new-library", "^1.0.0"),
             )
+            .add(PackageManifest::new(
+                "com.anatawa12.new-library",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
             .build();
 
         let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
 
         let result = project
-            .add_package_request(
+            .add_package_request_with_options(
                 &collection,
                 &[package],
                 AddPackageOperation::UpgradeLocked,
-                false,
+                &ResolutionOptions::new().diagnostics(true),
             )
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 2);
-        assert_eq!(result.remove_legacy_folders().len(), 0);
-        assert_eq!(result.remove_legacy_files().len(), 0);
-        assert_eq!(result.conflicts().len(), 0);
-
-        assert_installing_to_locked_only(&result, &package);
-        assert_removed(
-            &result,
-            "com.anatawa12.legacy-package",
-            RemoveReason::Legacy,
+        let diagnostics = result.resolution().unwrap().diagnostics.as_ref();
+        let origins = &diagnostics.unwrap().origins;
+        assert_eq!(
+            origins.get("com.anatawa12.package"),
+            Some(&PackageOrigin::Changed {
+                locked: Version::new(1, 0, 0)
+            })
+        );
+        assert_eq!(
+            origins.get("com.anatawa12.new-library"),
+            Some(&PackageOrigin::Added)
+        );
+        assert_eq!(
+            origins.get("com.anatawa12.library"),
+            Some(&PackageOrigin::Locked)
+        );
+        // not touched by the resolution
+        assert_eq!(
+            origins.get("com.anatawa12.other"),
+            Some(&PackageOrigin::Locked)
         );
+        assert_eq!(origins.len(), 4);
     })
 }
 
 #[test]
-fn remove_referenced_legacy_package_when_install() {
+fn report_unused_locked_packages() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dependency("com.anatawa12.user", Version::new(1, 0, 0))
-            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
             .add_locked(
-                "com.anatawa12.user",
+                "com.anatawa12.package",
                 Version::new(1, 0, 0),
-                &[("com.anatawa12.legacy-package", "^1.0.0")],
+                &[("com.anatawa12.library", "1.0.0")],
             )
-            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .add_locked(
+                "com.anatawa12.orphan",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.orphan_library", "1.0.0")],
+            )
+            .add_locked("com.anatawa12.orphan_library", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
-            .add(
-                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
-                    .add_legacy_package("com.anatawa12.legacy-package"),
-            )
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 1, 0),
+            ))
             .build();
 
         let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
@@ -450,52 +526,42 @@ fn remove_referenced_legacy_package_when_install() {
             .add_package_request(
                 &collection,
                 &[package],
-                AddPackageOperation::InstallToDependencies,
+                AddPackageOperation::UpgradeLocked,
                 false,
             )
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 2);
-        assert_eq!(result.remove_legacy_folders().len(), 0);
-        assert_eq!(result.remove_legacy_files().len(), 0);
-        assert_eq!(result.conflicts().len(), 0);
-
-        assert_installing_to_both(&result, &package);
-        assert_removed(
-            &result,
-            "com.anatawa12.legacy-package",
-            RemoveReason::Legacy,
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.unused_locked,
+            vec![
+                Box::<str>::from("com.anatawa12.library"),
+                Box::<str>::from("com.anatawa12.orphan"),
+                Box::<str>::from("com.anatawa12.orphan_library"),
+            ]
         );
     })
 }
 
-// endregion
-
-//region legacy assets
-
 #[test]
-fn legacy_assets_by_path() {
+fn remove_legacy_package_when_install() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dir("Assets/LegacyFolder")
-            .add_dir("Packages/legacy.package") // VRCSDK Worlds uses legacy dir for removing package
-            .add_file("Assets/LegacyAsset.cs", "// empty file")
+            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
             .add(
-                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
-                    .add_legacy_folder("Assets\\LegacyFolder", "")
-                    .add_legacy_folder("Assets\\NotExists", "")
-                    .add_legacy_folder("Packages\\legacy.package", "")
-                    .add_legacy_file("Assets\\LegacyAsset.cs", ""),
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package"),
             )
             .build();
 
-        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
 
         let result = project
             .add_package_request(
@@ -507,68 +573,38 @@ fn legacy_assets_by_path() {
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.package_changes().len(), 2);
+        assert_eq!(result.remove_legacy_folders().len(), 0);
+        assert_eq!(result.remove_legacy_files().len(), 0);
         assert_eq!(result.conflicts().len(), 0);
 
-        assert_eq!(
-            result
-                .remove_legacy_folders()
-                .iter()
-                .collect::<HashSet<_>>(),
-            [
-                (
-                    Path::new("Assets/LegacyFolder").into(),
-                    "com.anatawa12.package",
-                ),
-                (
-                    Path::new("Packages/legacy.package").into(),
-                    "com.anatawa12.package",
-                ),
-            ]
-            .iter()
-            .collect::<HashSet<_>>()
-        );
-
-        assert_eq!(
-            result.remove_legacy_files().iter().collect::<HashSet<_>>(),
-            [(
-                Path::new("Assets/LegacyAsset.cs").into(),
-                "com.anatawa12.package",
-            )]
-            .iter()
-            .collect::<HashSet<_>>()
+        assert_installing_to_both(&result, &package);
+        assert_removed(
+            &result,
+            "com.anatawa12.legacy-package",
+            RemoveReason::Legacy,
         );
     })
 }
 
 #[test]
-fn legacy_assets_by_guid() {
+fn serialize_changes_to_json() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dir("Assets/MovedLegacyFolder")
-            .add_file(
-                "Assets/MovedLegacyFolder.meta",
-                "guid: 1c54b633da4d4d2abc01c6dedae67e09",
-            )
-            .add_file("Assets/MovedLegacyAsset.cs", "// empty file")
-            .add_file(
-                "Assets/MovedLegacyAsset.cs.meta",
-                "guid: ca06b0788d62432083b3577cc2346126",
-            )
+            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
             .add(
-                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
-                    .add_legacy_folder("Assets\\LegacyFolder", "1c54b633da4d4d2abc01c6dedae67e09")
-                    .add_legacy_folder("Assets\\NotExists", "62a9615044174c818622c19d0181d036")
-                    .add_legacy_file("Assets\\LegacyAsset.cs", "ca06b0788d62432083b3577cc2346126"),
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package"),
             )
             .build();
 
-        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
 
         let result = project
             .add_package_request(
@@ -580,123 +616,145 @@ fn legacy_assets_by_guid() {
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.conflicts().len(), 0);
-
-        assert_eq!(
-            result
-                .remove_legacy_folders()
-                .iter()
-                .collect::<HashSet<_>>(),
-            [(
-                Path::new("Assets/MovedLegacyFolder").into(),
-                "com.anatawa12.package",
-            )]
-            .iter()
-            .collect::<HashSet<_>>()
-        );
-
+        assert_eq!(ChangesJson::FORMAT_VERSION, 1);
         assert_eq!(
-            result.remove_legacy_files().iter().collect::<HashSet<_>>(),
-            [(
-                Path::new("Assets/MovedLegacyAsset.cs").into(),
-                "com.anatawa12.package",
-            )]
-            .iter()
-            .collect::<HashSet<_>>()
+            serde_json::to_value(ChangesJson::new(&result)).unwrap(),
+            serde_json::json!({
+                "new_packages": [{
+                    "name": "com.anatawa12.package",
+                    "version": "1.1.0",
+                    "repository": null,
+                    "is_yanked": false,
+                }],
+                "adding_to_dependencies": [{
+                    "name": "com.anatawa12.package",
+                    "range": "1.1.0",
+                }],
+                "removed_packages": [{
+                    "name": "com.anatawa12.legacy-package",
+                    "reason": "legacy",
+                }],
+                "removed_legacy_assets": [],
+                "conflicts": [],
+                "found_legacy_packages": ["com.anatawa12.legacy-package"],
+                "warnings": [],
+            })
         );
     })
 }
 
 #[test]
-fn deny_remove_files_not_in_assets_or_packages() {
+fn report_ambiguous_legacy_replacement() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dir("Assets1/LegacyFolder")
-            .add_dir("Packages1/legacy.package") // VRCSDK Worlds uses legacy dir for removing package
-            .add_file("Assets1/LegacyAsset.cs", "// empty file")
+            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
             .add(
-                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
-                    .add_legacy_folder("Assets1\\LegacyFolder", "")
-                    .add_legacy_folder("Assets1\\NotExists", "")
-                    .add_legacy_folder("Packages1\\legacy.package", "")
-                    .add_legacy_file("Assets1\\LegacyAsset.cs", ""),
+                PackageManifest::new("com.anatawa12.package-a", Version::new(1, 0, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.package-b", Version::new(1, 0, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package"),
             )
             .build();
 
-        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let package_a = collection.get_package("com.anatawa12.package-a", Version::new(1, 0, 0));
+        let package_b = collection.get_package("com.anatawa12.package-b", Version::new(1, 0, 0));
 
         let result = project
             .add_package_request(
                 &collection,
-                &[package],
+                &[package_a, package_b],
                 AddPackageOperation::InstallToDependencies,
                 false,
             )
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.conflicts().len(), 0);
+        assert_eq!(
+            result.resolution().unwrap().ambiguous_legacy_replacements,
+            vec![AmbiguousLegacyReplacement {
+                legacy: "com.anatawa12.legacy-package".into(),
+                modern: vec![
+                    "com.anatawa12.package-a".into(),
+                    "com.anatawa12.package-b".into(),
+                ],
+            }]
+        );
+        assert_removed(
+            &result,
+            "com.anatawa12.legacy-package",
+            RemoveReason::Legacy,
+        );
 
-        assert_eq!(result.remove_legacy_folders(), &[]);
-        assert_eq!(result.remove_legacy_files(), &[]);
+        // a single replacement is not ambiguous
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package_a],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(
+            result.resolution().unwrap().ambiguous_legacy_replacements,
+            vec![]
+        );
     })
 }
 
 #[test]
-fn deny_remove_parent_folders() {
+fn add_dependencies_of_enabled_features() {
     block_on(async {
         let project = VirtualProjectBuilder::new().build().await.unwrap();
 
         let collection = PackageCollectionBuilder::new()
             .add(
                 PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
-                    .add_legacy_folder("..", "")
-                    .add_legacy_folder("Assets/..", "")
-                    .add_legacy_folder("", ""),
+                    .add_feature_dependency("udon-sharp", "com.vrchat.udonsharp", "^1.0.0")
+                    .add_feature_dependency("quest", "com.anatawa12.quest-tools", "^1.0.0"),
             )
+            .add(PackageManifest::new(
+                "com.vrchat.udonsharp",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.quest-tools",
+                Version::new(1, 0, 0),
+            ))
             .build();
 
         let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let udonsharp = collection.get_package("com.vrchat.udonsharp", Version::new(1, 1, 0));
 
         let result = project
-            .add_package_request(
+            .add_package_request_with_options(
                 &collection,
                 &[package],
                 AddPackageOperation::InstallToDependencies,
-                false,
+                &ResolutionOptions::new()
+                    .enable_feature("udon-sharp")
+                    .enable_feature("unused"),
             )
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.conflicts().len(), 0);
-
-        assert_eq!(result.remove_legacy_folders(), &[]);
-        assert_eq!(result.remove_legacy_files(), &[]);
-    })
-}
-
-#[test]
-fn deny_absolute_legacy_assets() {
-    block_on(async {
-        let project = VirtualProjectBuilder::new().build().await.unwrap();
-
-        let collection = PackageCollectionBuilder::new()
-            .add(
-                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
-                    .add_legacy_folder("/", ""),
-            )
-            .build();
-
-        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &package);
+        assert_installing_to_locked_only(&result, &udonsharp);
+        assert_eq!(
+            result.resolution().unwrap().active_features,
+            vec![("com.anatawa12.package".into(), "udon-sharp".into())]
+        );
 
+        // without the feature, the conditional dependencies are not added
         let result = project
             .add_package_request(
                 &collection,
@@ -708,36 +766,21 @@ fn deny_absolute_legacy_assets() {
             .unwrap();
 
         assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.conflicts().len(), 0);
-
-        assert_eq!(result.remove_legacy_folders(), &[]);
-        assert_eq!(result.remove_legacy_files(), &[]);
+        assert_installing_to_both(&result, &package);
+        assert!(result.resolution().unwrap().active_features.is_empty());
     })
 }
 
 #[test]
-fn do_remove_legacy_files_that_guid_mismatches() {
+fn plan_legacy_removals_before_install() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dir("Assets/LegacyGuidMismatchFolder")
-            .add_file(
-                "Assets/LegacyGuidMismatchFolder.meta",
-                "guid: 75ead46ee7514680afd1ed9008423371",
-            )
-            .add_dir("Assets/LegacyGuidMatchFolder")
-            .add_file(
-                "Assets/LegacyGuidMatchFolder.meta",
-                "guid: cbac8a2877e64d75af9b3b61fe946b40",
-            )
-            .add_file("Assets/LegacyGuidMismatchAsset.cs", "// empty file")
-            .add_file(
-                "Assets/LegacyGuidMismatchAsset.cs.meta",
-                "guid: 85cc942c4a8a487ebe7df9937d15bdf8",
-            )
-            .add_file("Assets/LegacyGuidMatchAsset.cs", "// empty file")
+            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.legacy-missing", Version::new(1, 0, 0), &[])
             .add_file(
-                "Assets/LegacyGuidMatchAsset.cs.meta",
-                "guid: 85cc942c4a8a487ebe7df9937d15bdf8",
+                "Packages/com.anatawa12.legacy-package/package.json",
+                r#"{"name":"com.anatawa12.legacy-package","version":"1.0.0"}"#,
             )
             .build()
             .await
@@ -745,194 +788,157 @@ fn do_remove_legacy_files_that_guid_mismatches() {
 
         let collection = PackageCollectionBuilder::new()
             .add(
-                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
-                    .add_legacy_folder(
-                        "Assets\\LegacyGuidMismatchFolder",
-                        "39bfddf4f3ac48c6845997a81fc5262c",
-                    )
-                    .add_legacy_folder(
-                        "Assets\\LegacyGuidMatchFolder",
-                        "cbac8a2877e64d75af9b3b61fe946b40",
-                    )
-                    .add_legacy_file(
-                        "Assets\\LegacyGuidMismatchAsset.cs",
-                        "417a8085479b433792a67b2dfefb1982",
-                    )
-                    .add_legacy_file(
-                        "Assets\\LegacyGuidMatchAsset.cs",
-                        "85cc942c4a8a487ebe7df9937d15bdf8",
-                    ),
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package")
+                    .add_legacy_package("com.anatawa12.legacy-missing"),
             )
             .build();
 
-        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
 
-        let result = project
-            .add_package_request(
+        let removals = project
+            .plan_legacy_removals(
                 &collection,
                 &[package],
                 AddPackageOperation::InstallToDependencies,
-                false,
+                &ResolutionOptions::new(),
             )
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.conflicts().len(), 0);
-
-        assert_eq!(
-            result
-                .remove_legacy_folders()
-                .iter()
-                .collect::<HashSet<_>>(),
-            [
-                (
-                    Path::new("Assets/LegacyGuidMatchFolder").into(),
-                    "com.anatawa12.package",
-                ),
-                (
-                    Path::new("Assets/LegacyGuidMismatchFolder").into(),
-                    "com.anatawa12.package",
-                ),
-            ]
-            .iter()
-            .collect::<HashSet<_>>()
-        );
-
         assert_eq!(
-            result.remove_legacy_files().iter().collect::<HashSet<_>>(),
-            [
-                (
-                    Path::new("Assets/LegacyGuidMatchAsset.cs").into(),
-                    "com.anatawa12.package",
-                ),
-                (
-                    Path::new("Assets/LegacyGuidMismatchAsset.cs").into(),
-                    "com.anatawa12.package",
-                ),
+            removals,
+            vec![
+                LegacyRemoval {
+                    package: "com.anatawa12.legacy-missing".into(),
+                    superseded_by: vec!["com.anatawa12.package".into()],
+                    installed: false,
+                },
+                LegacyRemoval {
+                    package: "com.anatawa12.legacy-package".into(),
+                    superseded_by: vec!["com.anatawa12.package".into()],
+                    installed: true,
+                },
             ]
-            .iter()
-            .collect::<HashSet<_>>()
         );
     })
 }
 
 #[test]
-fn do_not_remove_udonsharp_folder_if_guid_mismatch() {
+fn suppress_legacy_removal_by_callback() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dir("Assets/UdonSharp")
-            .add_file(
-                "Assets/UdonSharp.meta",
-                "guid: e2095dec983b4d9481723c263fd5b6c0",
-            )
+            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
             .add(
-                PackageManifest::new("com.vrchat.worlds", Version::new(1, 0, 0))
-                    .add_legacy_folder("Assets\\UdonSharp", "b031f928e5c709b4887f6513084aaa51"),
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package"),
             )
             .build();
 
-        let package = collection.get_package("com.vrchat.worlds", Version::new(1, 0, 0));
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
+
+        let mut asked = vec![];
+        let options = ResolutionOptions::new().approve_removal(|legacy, modern| {
+            asked.push((legacy.to_owned(), modern.to_owned()));
+            false
+        });
 
         let result = project
-            .add_package_request(
+            .add_package_request_with_options(
                 &collection,
                 &[package],
                 AddPackageOperation::InstallToDependencies,
-                false,
+                &options,
             )
             .await
             .unwrap();
 
         assert_eq!(result.package_changes().len(), 1);
         assert_eq!(result.conflicts().len(), 0);
-        assert_eq!(result.remove_legacy_folders().len(), 0);
-        assert_eq!(result.remove_legacy_files().len(), 0);
-    })
-}
-
-//endregion
-
-// region errors
-
-#[test]
-fn not_found_err() {
-    block_on(async {
-        let project = VirtualProjectBuilder::new().build().await.unwrap();
-
-        let collection = PackageCollectionBuilder::new()
-            .add(
-                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
-                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
-            )
-            .build();
-
-        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        assert_installing_to_both(&result, &package);
 
-        let err = project
-            .add_package_request(
-                &collection,
-                &[avatars_package],
-                AddPackageOperation::InstallToDependencies,
-                false,
-            )
-            .await
-            .expect_err("should fail");
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.suppressed_removals,
+            vec![(
+                "com.anatawa12.legacy-package".into(),
+                "com.anatawa12.package".into()
+            )]
+        );
 
-        match &err {
-            AddPackageErr::DependenciesNotFound { dependencies } => {
-                assert_eq!(dependencies.len(), 1);
-                assert_eq!(dependencies[0].as_ref(), "com.vrchat.base");
-            }
-            _ => panic!("unexpected error: {:?}", err),
-        }
+        drop(options);
+        assert_eq!(
+            asked,
+            vec![(
+                "com.anatawa12.legacy-package".to_owned(),
+                "com.anatawa12.package".to_owned()
+            )]
+        );
     })
 }
 
 #[test]
-fn updating_non_locked_package_should_cause_error() {
+fn keep_legacy_package_by_option() {
     block_on(async {
         let project = VirtualProjectBuilder::new().build().await.unwrap();
 
         let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package"),
+            )
             .add(PackageManifest::new(
-                "com.vrchat.avatars",
+                "com.anatawa12.legacy-package",
                 Version::new(1, 0, 0),
             ))
             .build();
 
-        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
+        let legacy = collection.get_package("com.anatawa12.legacy-package", Version::new(1, 0, 0));
 
-        let err = project
-            .add_package_request(
+        let options = ResolutionOptions::new()
+            .keep_legacy(["com.anatawa12.legacy-package"].into_iter().collect());
+
+        let result = project
+            .add_package_request_with_options(
                 &collection,
-                &[avatars_package],
-                AddPackageOperation::UpgradeLocked,
-                false,
+                &[package, legacy],
+                AddPackageOperation::InstallToDependencies,
+                &options,
             )
             .await
-            .expect_err("should fail");
+            .unwrap();
 
-        match &err {
-            AddPackageErr::UpgradingNonLockedPackage { package_name } => {
-                assert_eq!(package_name.as_ref(), "com.vrchat.avatars");
-            }
-            _ => panic!("unexpected error: {:?}", err),
-        }
+        // the legacy package is installed with the package replacing it
+        assert_eq!(result.package_changes().len(), 2);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &package);
+        assert_installing_to_both(&result, &legacy);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.suppressed_removals,
+            vec![(
+                "com.anatawa12.legacy-package".into(),
+                "com.anatawa12.package".into()
+            )]
+        );
     })
 }
 
 #[test]
-fn downgrade_basic() {
+fn keep_vendored_packages() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_locked("com.vrchat.base", Version::new(1, 1, 0), &[])
             .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
@@ -940,224 +946,5754 @@ fn downgrade_basic() {
         let collection = PackageCollectionBuilder::new()
             .add(PackageManifest::new(
                 "com.vrchat.base",
-                Version::new(1, 0, 0),
+                Version::new(1, 1, 0),
             ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0")
+                    .add_legacy_package("com.anatawa12.legacy-package"),
+            )
             .build();
 
-        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 0, 0));
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let options = ResolutionOptions::new().vendored(HashMap::from([
+            ("com.vrchat.base".into(), Version::new(1, 0, 0)),
+            ("com.anatawa12.legacy-package".into(), Version::new(1, 0, 0)),
+        ]));
 
         let result = project
-            .add_package_request(
+            .add_package_request_with_options(
                 &collection,
-                &[base_package],
-                AddPackageOperation::Downgrade,
-                false,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
             )
             .await
             .unwrap();
 
         assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.remove_legacy_folders().len(), 0);
-        assert_eq!(result.remove_legacy_files().len(), 0);
-        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &avatars_package);
 
-        assert_installing_to_locked_only(&result, &base_package);
+        let resolution = result.resolution().unwrap();
+        assert!(resolution.found_legacy_packages.is_empty());
+        let conflicts = &resolution.conflicts["com.vrchat.base"];
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].package.as_ref(), "com.vrchat.avatars");
+        assert_eq!(conflicts[0].range.to_string(), "^1.1.0");
+        assert_eq!(conflicts[0].selected, Version::new(1, 0, 0));
     })
 }
 
 #[test]
-fn downgrade_dependencies() {
+fn satisfy_with_provided_packages() {
     block_on(async {
-        let project = VirtualProjectBuilder::new()
-            .add_locked("com.vrchat.base", Version::new(1, 1, 0), &[])
-            .add_dependency("com.vrchat.base", Version::new(1, 1, 0))
-            .build()
-            .await
-            .unwrap();
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
 
         let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.newer-library", "^2.0.0"),
+            )
             .add(PackageManifest::new(
-                "com.vrchat.base",
-                Version::new(1, 0, 0),
+                "com.anatawa12.library",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.newer-library",
+                Version::new(2, 0, 0),
             ))
             .build();
 
-        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 0, 0));
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let options = ResolutionOptions::new()
+            .provided(HashMap::from([
+                ("com.anatawa12.library".into(), Version::new(1, 0, 0)),
+                ("com.anatawa12.newer-library".into(), Version::new(1, 0, 0)),
+            ]))
+            .diagnostics(true);
 
         let result = project
-            .add_package_request(
+            .add_package_request_with_options(
                 &collection,
-                &[base_package],
-                AddPackageOperation::Downgrade,
-                false,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
             )
             .await
             .unwrap();
 
         assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.remove_legacy_folders().len(), 0);
-        assert_eq!(result.remove_legacy_files().len(), 0);
-        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &package);
 
-        assert_installing_to_both(&result, &base_package);
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.explain_absence("com.anatawa12.library"),
+            AbsenceReason::Provided {
+                version: Version::new(1, 0, 0)
+            }
+        );
+        assert!(!resolution.conflicts.contains_key("com.anatawa12.library"));
+
+        // the provided version is too old
+        let conflicts = &resolution.conflicts["com.anatawa12.newer-library"];
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].package.as_ref(), "com.anatawa12.package");
+        assert_eq!(conflicts[0].selected, Version::new(1, 0, 0));
     })
 }
 
-// endregion
+#[test]
+fn process_dependents_first() {
+    #[derive(Default)]
+    struct Counter(RefCell<usize>);
 
-// region conflicts
+    impl ResolutionEventSink for Counter {
+        fn on_package_processed(&self, _: PackageInfo) {
+            *self.0.borrow_mut() += 1;
+        }
+    }
+
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        // layers of packages where each package depends on all packages in the next layer
+        // with the upper bounds, so the dependencies found early need to be processed again
+        const LAYERS: usize = 6;
+        const WIDTH: usize = 5;
+        const VERSIONS: u64 = 4;
+        let mut builder = PackageCollectionBuilder::new();
+        for layer in 0..LAYERS {
+            for index in 0..WIDTH {
+                for minor in (0..VERSIONS).rev() {
+                    let mut manifest = PackageManifest::new(
+                        format!("com.anatawa12.layer{layer}.package{index}"),
+                        Version::new(1, minor, 0),
+                    );
+                    if layer + 1 < LAYERS {
+                        for dependency in 0..WIDTH {
+                            manifest = manifest.add_vpm_dependency(
+                                format!("com.anatawa12.layer{}.package{dependency}", layer + 1),
+                                &format!("<=1.{}.0", (index + dependency) as u64 % VERSIONS),
+                            );
+                        }
+                    }
+                    builder = builder.add(manifest);
+                }
+            }
+        }
+        let collection = builder.build();
+
+        let roots = (0..WIDTH)
+            .map(|index| {
+                collection.get_package(
+                    &format!("com.anatawa12.layer0.package{index}"),
+                    Version::new(1, VERSIONS - 1, 0),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut results = vec![];
+        for order in [QueueOrder::LastAdded, QueueOrder::DependentsFirst] {
+            let counter = Counter::default();
+            let options = ResolutionOptions::new()
+                .event_sink(&counter)
+                .queue_order(order);
+            let result = project
+                .add_package_request_with_options(
+                    &collection,
+                    &roots,
+                    AddPackageOperation::InstallToDependencies,
+                    &options,
+                )
+                .await
+                .unwrap();
+            let mut installed = (result.resolution().unwrap().new_packages.iter())
+                .map(|x| (x.name().to_owned(), x.version().clone()))
+                .collect::<Vec<_>>();
+            installed.sort();
+            results.push((installed, *counter.0.borrow()));
+        }
+
+        let (last_added, last_added_count) = &results[0];
+        let (dependents_first, dependents_first_count) = &results[1];
+        assert_eq!(last_added.len(), LAYERS * WIDTH);
+        assert_eq!(last_added, dependents_first);
+        // each package is processed only once, while the dependencies are processed again
+        // for each newly found upper bound in the last added order
+        assert_eq!(*dependents_first_count, LAYERS * WIDTH);
+        assert!(last_added_count > dependents_first_count);
+    })
+}
 
 #[test]
-fn conflict_requirements_of_installed_and_installing() {
+fn process_dependents_first_with_aliases_and_features() {
+    #[derive(Default)]
+    struct Counter(RefCell<usize>);
+
+    impl ResolutionEventSink for Counter {
+        fn on_package_processed(&self, _: PackageInfo) {
+            *self.0.borrow_mut() += 1;
+        }
+    }
+
     block_on(async {
-        let project = VirtualProjectBuilder::new()
-            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
-            .add_locked(
-                "com.vrchat.avatars",
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        // the library is the last dependency of the root, and the other dependencies depend on
+        // the library with the renamed id, or with the feature
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.root", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.aliased", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.featured", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.aliased", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.old-library", "<=1.1.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.featured", Version::new(1, 0, 0))
+                    .add_feature_dependency("extra", "com.anatawa12.library", "<=1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 2, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
                 Version::new(1, 0, 0),
-                &[("com.vrchat.base", "1.0.0")],
+            ))
+            .build();
+
+        let root_package = collection.get_package("com.anatawa12.root", Version::new(1, 0, 0));
+        let library_package =
+            collection.get_package("com.anatawa12.library", Version::new(1, 0, 0));
+
+        let counter = Counter::default();
+        let options = ResolutionOptions::new()
+            .event_sink(&counter)
+            .queue_order(QueueOrder::DependentsFirst)
+            .enable_feature("extra")
+            .aliases(HashMap::from([(
+                "com.anatawa12.old-library",
+                "com.anatawa12.library",
+            )]));
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[root_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
             )
+            .await
+            .unwrap();
+
+        assert_installing_to_locked_only(&result, &library_package);
+        // the library waits for both dependents, so each package is processed only once
+        assert_eq!(*counter.0.borrow(), 4);
+    })
+}
+
+#[test]
+fn send_resolution_events() {
+    #[derive(Default)]
+    struct Recorder(RefCell<Vec<String>>);
+
+    impl ResolutionEventSink for Recorder {
+        fn on_package_processed(&self, package: PackageInfo) {
+            (self.0.borrow_mut()).push(format!(
+                "processed {} {}",
+                package.name(),
+                package.version()
+            ));
+        }
+
+        fn on_dependency_considered(&self, package: &str, dependency: &str, range: &VersionRange) {
+            (self.0.borrow_mut()).push(format!("considered {package} {dependency} {range}"));
+        }
+
+        fn on_package_selected(&self, package: &str, selected: PackageInfo) {
+            (self.0.borrow_mut()).push(format!(
+                "selected {package} {} {}",
+                selected.name(),
+                selected.version()
+            ));
+        }
+
+        fn on_skip_reason(&self, package: &str, dependency: &str, reason: &SkipReason) {
+            (self.0.borrow_mut()).push(format!("skipped {package} {dependency} {reason:?}"));
+        }
+    }
+
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
             .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
-            .add(
-                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
-                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
-            )
             .add(PackageManifest::new(
                 "com.vrchat.base",
                 Version::new(1, 0, 0),
             ))
             .add(PackageManifest::new(
-                "com.vrchat.base",
-                Version::new(1, 1, 0),
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
             ))
             .add(
                 PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
-                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
             )
             .build();
 
         let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
-        let base_1_1_0 = collection.get_package("com.vrchat.base", Version::new(1, 1, 0));
 
-        let resolve = project
-            .add_package_request(
+        let recorder = Recorder::default();
+        let result = project
+            .add_package_request_with_options(
                 &collection,
                 &[tool],
                 AddPackageOperation::InstallToDependencies,
-                false,
+                &ResolutionOptions::new().event_sink(&recorder),
             )
             .await
             .unwrap();
 
-        assert_eq!(resolve.package_changes().len(), 2);
-        assert_eq!(resolve.remove_legacy_folders().len(), 0);
-        assert_eq!(resolve.remove_legacy_files().len(), 0);
-        assert_eq!(resolve.conflicts().len(), 1);
-
-        assert_installing_to_both(&resolve, &tool);
-        assert_installing_to_locked_only(&resolve, &base_1_1_0);
-
-        let base_conflict = resolve.conflicts().get("com.vrchat.base").unwrap();
+        assert_eq!(result.package_changes().len(), 2);
         assert_eq!(
-            base_conflict.conflicting_packages(),
-            &["com.vrchat.avatars".into()]
-        )
+            recorder.0.into_inner(),
+            vec![
+                "processed com.anatawa12.tool 1.0.0",
+                "considered com.anatawa12.tool com.vrchat.base ^1.0.0",
+                "skipped com.anatawa12.tool com.vrchat.base ExistingMatches",
+                "considered com.anatawa12.tool com.anatawa12.library ^1.0.0",
+                "selected com.anatawa12.tool com.anatawa12.library 1.0.0",
+                "processed com.anatawa12.library 1.0.0",
+            ]
+        );
     })
 }
 
 #[test]
-fn conflict_already_conflicted_and_no_new_conflict() {
+fn keep_pinned_packages() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
-            .add_locked(
-                "com.vrchat.avatars",
+            .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
+            .add_dependency("com.anatawa12.tool", Version::new(1, 0, 0))
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.tool", Version::new(1, 1, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
                 Version::new(1, 0, 0),
-                &[("com.vrchat.base", "1.0.0")],
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
             )
-            .add_locked("com.vrchat.base", Version::new(1, 1, 0), &[])
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let options = ResolutionOptions::new().pins(HashMap::from([
+            ("com.vrchat.base", Version::new(1, 0, 0)),
+            ("com.anatawa12.tool", Version::new(1, 0, 0)),
+        ]));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap();
+
+        // base is kept at the pinned version, and tool is replaced with the pinned version
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &tool_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(resolution.pin_violations.len(), 1);
+        let violation = &resolution.pin_violations[0];
+        assert_eq!(violation.package.as_ref(), "com.vrchat.base");
+        assert_eq!(violation.pinned, Version::new(1, 0, 0));
+        assert_eq!(violation.source.as_ref(), "com.vrchat.avatars");
+        assert_eq!(violation.range.to_string(), "^1.1.0");
+
+        let conflicts = &resolution.conflicts["com.vrchat.base"];
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].package.as_ref(), "com.vrchat.avatars");
+    })
+}
+
+#[test]
+fn hold_unrequested_locked_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
+            .add_dependency("com.anatawa12.tool", Version::new(1, 0, 0))
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.tool", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 1, 0),
+            ))
             .add(
                 PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
-                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 1, 0));
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 1, 0));
+
+        // without the option, base is upgraded for avatars
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_locked_only(&result, &base_package);
+
+        // with the option, only the requested packages change and base is held
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package, tool_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().resolve_subset(true),
             )
+            .await
+            .unwrap();
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_both(&result, &tool_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(resolution.pin_violations.len(), 1);
+        let violation = &resolution.pin_violations[0];
+        assert_eq!(violation.package.as_ref(), "com.vrchat.base");
+        assert_eq!(violation.pinned, Version::new(1, 0, 0));
+        assert_eq!(violation.source.as_ref(), "com.vrchat.avatars");
+    })
+}
+
+#[test]
+fn override_versions_of_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
             .add(PackageManifest::new(
                 "com.vrchat.base",
                 Version::new(1, 0, 0),
             ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let versions = HashMap::from([("com.vrchat.base", Version::new(1, 0, 0))]);
+
+        // the pin contradicting the requirement fails with the policy
+        let options = ResolutionOptions::new()
+            .pins(versions.clone())
+            .conflict_policy(ConflictPolicy::Fail);
+        let err = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, AddPackageErr::UnresolvableConflicts { .. }),
+            "{err:?}"
+        );
+
+        // the override wins over the requirement and reports it
+        let options = ResolutionOptions::new()
+            .pins(HashMap::from([("com.vrchat.base", Version::new(1, 1, 0))]))
+            .overrides(versions)
+            .conflict_policy(ConflictPolicy::Fail);
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_both(&result, &avatars_package);
+
+        let resolution = result.resolution().unwrap();
+        assert!(resolution.pin_violations.is_empty());
+        assert_eq!(resolution.override_violations.len(), 1);
+        let violation = &resolution.override_violations[0];
+        assert_eq!(violation.package.as_ref(), "com.vrchat.base");
+        assert_eq!(violation.pinned, Version::new(1, 0, 0));
+        assert_eq!(violation.source.as_ref(), "com.vrchat.avatars");
+        assert_eq!(violation.range.to_string(), "^1.1.0");
+        assert!(resolution.conflicts.contains_key("com.vrchat.base"));
+    })
+}
+
+#[test]
+fn honor_project_config() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.tool", Version::new(1, 0, 0), &[])
+            .add_file(
+                "Packages/vrc-get-project.json",
+                r#"{
+                    "userManaged": ["com.anatawa12.tool"],
+                    "pins": { "com.vrchat.base": "1.0.0" }
+                }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
             .add(PackageManifest::new(
                 "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
                 Version::new(1, 1, 0),
             ))
             .add(
-                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
-                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0")
+                    .add_vpm_dependency("com.anatawa12.tool", "^1.1.0"),
             )
             .build();
 
-        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 1, 0));
 
-        let resolve = project
+        assert_eq!(
+            project.project_config().user_managed(),
+            &["com.anatawa12.tool".into()]
+        );
+
+        // base is pinned by the config, and tool managed by the user is kept at the locked version
+        let result = project
             .add_package_request(
                 &collection,
-                &[tool],
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_both(&result, &avatars_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(resolution.pin_violations.len(), 1);
+        assert_eq!(
+            resolution.pin_violations[0].package.as_ref(),
+            "com.vrchat.base"
+        );
+        assert!(resolution.conflicts.contains_key("com.anatawa12.tool"));
+        assert!(resolution.unused_locked.is_empty());
+
+        // the pin in the options takes precedence over the config
+        let options = ResolutionOptions::new()
+            .pins(HashMap::from([("com.vrchat.base", Version::new(1, 1, 0))]));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &base_package);
+
+        let resolution = result.resolution().unwrap();
+        assert!(resolution.pin_violations.is_empty());
+        assert!(resolution.conflicts.contains_key("com.anatawa12.tool"));
+    })
+}
+
+#[test]
+fn fail_on_conflicts_by_policy() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency_range("com.anatawa12.tool", "^1.1.0")
+            .add_locked("com.anatawa12.tool", Version::new(1, 1, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let request = async |policy, pins: &[(&'static str, Version)]| {
+            let options = ResolutionOptions::new()
+                .pins(pins.iter().cloned().collect())
+                .conflict_policy(policy);
+            project
+                .add_package_request_with_options(
+                    &collection,
+                    &[avatars_package],
+                    AddPackageOperation::InstallToDependencies,
+                    &options,
+                )
+                .await
+        };
+
+        // avatars conflicts with the pinned base, which is not required by the project
+        let transitive = [("com.vrchat.base", Version::new(1, 0, 0))];
+
+        let result = request(ConflictPolicy::Report, &transitive).await.unwrap();
+        assert_eq!(result.conflicts().len(), 1);
+
+        let result = request(ConflictPolicy::FailOnDirect, &transitive)
+            .await
+            .unwrap();
+        assert_eq!(result.conflicts().len(), 1);
+
+        let err = request(ConflictPolicy::Fail, &transitive)
+            .await
+            .unwrap_err();
+        let AddPackageErr::UnresolvableConflicts { conflicts } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(
+            conflicts["com.vrchat.base"][0].package.as_ref(),
+            "com.vrchat.avatars"
+        );
+
+        // the pinned tool conflicts with the dependency of the project
+        let direct = [
+            ("com.vrchat.base", Version::new(1, 1, 0)),
+            ("com.anatawa12.tool", Version::new(1, 0, 0)),
+        ];
+
+        let result = request(ConflictPolicy::Report, &direct).await.unwrap();
+        assert_eq!(result.conflicts().len(), 1);
+
+        let err = request(ConflictPolicy::FailOnDirect, &direct)
+            .await
+            .unwrap_err();
+        let AddPackageErr::UnresolvableConflicts { conflicts } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(conflicts["com.anatawa12.tool"][0].package.as_ref(), "");
+        let err = request(ConflictPolicy::Fail, &direct).await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Following packages have conflicts: com.anatawa12.tool (required by the project)"
+        );
+    })
+}
+
+#[test]
+fn downgrade_locked_root_dependency() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency_range("com.vrchat.base", "<1.5.0")
+            .add_locked("com.vrchat.base", Version::new(1, 5, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 5, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 4, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 4, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[tool_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().allow_downgrade(true),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &tool_package);
+        assert_installing_to_locked_only(&result, &base_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.downgrades,
+            vec![(
+                Box::<str>::from("com.vrchat.base"),
+                Version::new(1, 5, 0),
+                Version::new(1, 4, 0)
+            )]
+        );
+    })
+}
+
+#[test]
+fn report_lockfile_drift() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "^1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let options = ResolutionOptions::new().on_disk_dependencies(|name| match name {
+            "com.vrchat.avatars" => Some(IndexMap::from([
+                (
+                    "com.vrchat.base".into(),
+                    "^1.1.0".parse::<VersionRange>().unwrap(),
+                ),
+                (
+                    "com.vrchat.worlds".into(),
+                    "^1.0.0".parse::<VersionRange>().unwrap(),
+                ),
+            ])),
+            "com.vrchat.base" => Some(IndexMap::new()),
+            _ => None,
+        });
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[tool_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_both(&result, &tool_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.lockfile_drift,
+            vec![LockfileDrift {
+                package: "com.vrchat.avatars".into(),
+                only_locked: vec!["com.vrchat.base".into()],
+                only_on_disk: vec!["com.vrchat.base".into(), "com.vrchat.worlds".into()],
+            }]
+        );
+    })
+}
+
+#[test]
+fn report_locked_dependency_mismatch() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "^1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        // the avatars 1.0.0 is re-published with other dependencies
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0")
+                    .add_vpm_dependency("com.vrchat.core", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[tool_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        // the recorded dependencies are used
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_both(&result, &tool_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.locked_dependency_mismatches,
+            vec![LockedDependencyMismatch {
+                package: "com.vrchat.avatars".into(),
+                version: Version::new(1, 0, 0),
+                only_locked: vec![],
+                only_in_collection: vec!["com.vrchat.core".into()],
+            }]
+        );
+    })
+}
+
+#[test]
+fn report_missing_source_repository() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "Packages/vpm-manifest.json",
+                r#"{
+                    "dependencies": {
+                        "com.anatawa12.removed": { "version": "1.0.0" }
+                    },
+                    "locked": {
+                        "com.anatawa12.removed": {
+                            "version": "1.0.0",
+                            "dependencies": {},
+                            "vrc-get-repository": "https://example.com/removed.json"
+                        }
+                    }
+                }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[tool_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_both(&result, &tool_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.missing_source_repositories,
+            vec![MissingSourceRepository {
+                package: "com.anatawa12.removed".into(),
+                url: "https://example.com/removed.json".into(),
+            }]
+        );
+    })
+}
+
+#[test]
+fn exclude_packages_from_resolution() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.manual", "^1.0.0"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new()
+                    .exclude(HashSet::from(["com.anatawa12.manual"]))
+                    .diagnostics(true),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &package);
+
+        let resolution = result.resolution().unwrap();
+        assert!(resolution.conflicts.is_empty());
+        assert_eq!(
+            resolution.explain_absence("com.anatawa12.manual"),
+            AbsenceReason::Excluded
+        );
+    })
+}
+
+#[test]
+fn remove_legacy_package_when_upgrade() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::UpgradeLocked,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_eq!(result.remove_legacy_folders().len(), 0);
+        assert_eq!(result.remove_legacy_files().len(), 0);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_installing_to_locked_only(&result, &package);
+        assert_removed(
+            &result,
+            "com.anatawa12.legacy-package",
+            RemoveReason::Legacy,
+        );
+    })
+}
+
+#[test]
+fn summarize_resolution_against_locked() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_legacy_package("com.anatawa12.legacy-package")
+                    .add_legacy_package("com.anatawa12.not-locked-legacy"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::UpgradeLocked,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let resolution = result.resolution().unwrap();
+        let summary = resolution.summary(|name| project.get_locked(name));
+        assert_eq!(summary.added, vec!["com.anatawa12.library".into()]);
+        assert_eq!(summary.updated, vec!["com.anatawa12.package".into()]);
+        assert_eq!(summary.downgraded, Vec::<Box<str>>::new());
+        // legacy packages not locked are not removed
+        assert_eq!(summary.removed, vec!["com.anatawa12.legacy-package".into()]);
+        assert_eq!(summary.unchanged, Vec::<Box<str>>::new());
+        assert_eq!(
+            summary.to_string(),
+            "1 to install, 1 to update, 0 to downgrade, 1 to remove"
+        );
+
+        // the diff classifies the packages the same way
+        let locked = project.locked_packages().collect::<Vec<_>>();
+        let diff = resolution.diff_against_locked(&locked);
+        assert_eq!(
+            diff.added,
+            vec![("com.anatawa12.library".into(), Version::new(1, 0, 0))]
+        );
+        assert_eq!(
+            diff.upgraded,
+            vec![(
+                "com.anatawa12.package".into(),
+                Version::new(1, 0, 0),
+                Version::new(1, 1, 0)
+            )]
+        );
+        assert_eq!(diff.removed, summary.removed);
+    })
+}
+
+#[test]
+fn remove_referenced_legacy_package_when_install() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.user", Version::new(1, 0, 0))
+            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.user",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.legacy-package", "^1.0.0")],
+            )
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_legacy_package("com.anatawa12.legacy-package"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_eq!(result.remove_legacy_folders().len(), 0);
+        assert_eq!(result.remove_legacy_files().len(), 0);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_installing_to_both(&result, &package);
+        assert_removed(
+            &result,
+            "com.anatawa12.legacy-package",
+            RemoveReason::Legacy,
+        );
+    })
+}
+
+// endregion
+
+//region legacy assets
+
+#[test]
+fn legacy_assets_by_path() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dir("Assets/LegacyFolder")
+            .add_dir("Packages/legacy.package") // VRCSDK Worlds uses legacy dir for removing package
+            .add_file("Assets/LegacyAsset.cs", "// empty file")
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_legacy_folder("Assets\\LegacyFolder", "")
+                    .add_legacy_folder("Assets\\NotExists", "")
+                    .add_legacy_folder("Packages\\legacy.package", "")
+                    .add_legacy_file("Assets\\LegacyAsset.cs", ""),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_eq!(
+            result
+                .remove_legacy_folders()
+                .iter()
+                .collect::<HashSet<_>>(),
+            [
+                (
+                    Path::new("Assets/LegacyFolder").into(),
+                    "com.anatawa12.package",
+                ),
+                (
+                    Path::new("Packages/legacy.package").into(),
+                    "com.anatawa12.package",
+                ),
+            ]
+            .iter()
+            .collect::<HashSet<_>>()
+        );
+
+        assert_eq!(
+            result.remove_legacy_files().iter().collect::<HashSet<_>>(),
+            [(
+                Path::new("Assets/LegacyAsset.cs").into(),
+                "com.anatawa12.package",
+            )]
+            .iter()
+            .collect::<HashSet<_>>()
+        );
+    })
+}
+
+#[test]
+fn legacy_assets_by_guid() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dir("Assets/MovedLegacyFolder")
+            .add_file(
+                "Assets/MovedLegacyFolder.meta",
+                "guid: 1c54b633da4d4d2abc01c6dedae67e09",
+            )
+            .add_file("Assets/MovedLegacyAsset.cs", "// empty file")
+            .add_file(
+                "Assets/MovedLegacyAsset.cs.meta",
+                "guid: ca06b0788d62432083b3577cc2346126",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_legacy_folder("Assets\\LegacyFolder", "1c54b633da4d4d2abc01c6dedae67e09")
+                    .add_legacy_folder("Assets\\NotExists", "62a9615044174c818622c19d0181d036")
+                    .add_legacy_file("Assets\\LegacyAsset.cs", "ca06b0788d62432083b3577cc2346126"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_eq!(
+            result
+                .remove_legacy_folders()
+                .iter()
+                .collect::<HashSet<_>>(),
+            [(
+                Path::new("Assets/MovedLegacyFolder").into(),
+                "com.anatawa12.package",
+            )]
+            .iter()
+            .collect::<HashSet<_>>()
+        );
+
+        assert_eq!(
+            result.remove_legacy_files().iter().collect::<HashSet<_>>(),
+            [(
+                Path::new("Assets/MovedLegacyAsset.cs").into(),
+                "com.anatawa12.package",
+            )]
+            .iter()
+            .collect::<HashSet<_>>()
+        );
+    })
+}
+
+#[test]
+fn deny_remove_files_not_in_assets_or_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dir("Assets1/LegacyFolder")
+            .add_dir("Packages1/legacy.package") // VRCSDK Worlds uses legacy dir for removing package
+            .add_file("Assets1/LegacyAsset.cs", "// empty file")
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_legacy_folder("Assets1\\LegacyFolder", "")
+                    .add_legacy_folder("Assets1\\NotExists", "")
+                    .add_legacy_folder("Packages1\\legacy.package", "")
+                    .add_legacy_file("Assets1\\LegacyAsset.cs", ""),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_eq!(result.remove_legacy_folders(), &[]);
+        assert_eq!(result.remove_legacy_files(), &[]);
+    })
+}
+
+#[test]
+fn deny_remove_parent_folders() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_legacy_folder("..", "")
+                    .add_legacy_folder("Assets/..", "")
+                    .add_legacy_folder("", ""),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_eq!(result.remove_legacy_folders(), &[]);
+        assert_eq!(result.remove_legacy_files(), &[]);
+    })
+}
+
+#[test]
+fn refuse_legacy_assets_escaping_project() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dir("Assets/LegacyFolder")
+            .add_file("Assets/MovedLegacyAsset.cs", "// empty file")
+            .add_file(
+                "Assets/MovedLegacyAsset.cs.meta",
+                "guid: ca06b0788d62432083b3577cc2346126",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_legacy_folder("Assets\\LegacyFolder", "")
+                    .add_legacy_folder("Assets\\..\\..\\Other", "")
+                    .add_legacy_file("../LegacyAsset.cs", "ca06b0788d62432083b3577cc2346126"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        assert_eq!(package.legacy_folders().len(), 2);
+        assert_eq!(package.legacy_files().len(), 1);
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.remove_legacy_folders(),
+            &[(
+                Path::new("Assets/LegacyFolder").into(),
+                "com.anatawa12.package"
+            )]
+        );
+        // the asset with the guid is not removed since the declared path escapes the project
+        assert_eq!(result.remove_legacy_files(), &[]);
+        assert_eq!(
+            result.refused_legacy_assets(),
+            &[
+                ("../LegacyAsset.cs".into(), "com.anatawa12.package"),
+                ("Assets\\..\\..\\Other".into(), "com.anatawa12.package"),
+            ]
+        );
+    })
+}
+
+#[test]
+fn deny_absolute_legacy_assets() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_legacy_folder("/", ""),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_eq!(result.remove_legacy_folders(), &[]);
+        assert_eq!(result.remove_legacy_files(), &[]);
+    })
+}
+
+#[test]
+fn do_remove_legacy_files_that_guid_mismatches() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dir("Assets/LegacyGuidMismatchFolder")
+            .add_file(
+                "Assets/LegacyGuidMismatchFolder.meta",
+                "guid: 75ead46ee7514680afd1ed9008423371",
+            )
+            .add_dir("Assets/LegacyGuidMatchFolder")
+            .add_file(
+                "Assets/LegacyGuidMatchFolder.meta",
+                "guid: cbac8a2877e64d75af9b3b61fe946b40",
+            )
+            .add_file("Assets/LegacyGuidMismatchAsset.cs", "// empty file")
+            .add_file(
+                "Assets/LegacyGuidMismatchAsset.cs.meta",
+                "guid: 85cc942c4a8a487ebe7df9937d15bdf8",
+            )
+            .add_file("Assets/LegacyGuidMatchAsset.cs", "// empty file")
+            .add_file(
+                "Assets/LegacyGuidMatchAsset.cs.meta",
+                "guid: 85cc942c4a8a487ebe7df9937d15bdf8",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_legacy_folder(
+                        "Assets\\LegacyGuidMismatchFolder",
+                        "39bfddf4f3ac48c6845997a81fc5262c",
+                    )
+                    .add_legacy_folder(
+                        "Assets\\LegacyGuidMatchFolder",
+                        "cbac8a2877e64d75af9b3b61fe946b40",
+                    )
+                    .add_legacy_file(
+                        "Assets\\LegacyGuidMismatchAsset.cs",
+                        "417a8085479b433792a67b2dfefb1982",
+                    )
+                    .add_legacy_file(
+                        "Assets\\LegacyGuidMatchAsset.cs",
+                        "85cc942c4a8a487ebe7df9937d15bdf8",
+                    ),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_eq!(
+            result
+                .remove_legacy_folders()
+                .iter()
+                .collect::<HashSet<_>>(),
+            [
+                (
+                    Path::new("Assets/LegacyGuidMatchFolder").into(),
+                    "com.anatawa12.package",
+                ),
+                (
+                    Path::new("Assets/LegacyGuidMismatchFolder").into(),
+                    "com.anatawa12.package",
+                ),
+            ]
+            .iter()
+            .collect::<HashSet<_>>()
+        );
+
+        assert_eq!(
+            result.remove_legacy_files().iter().collect::<HashSet<_>>(),
+            [
+                (
+                    Path::new("Assets/LegacyGuidMatchAsset.cs").into(),
+                    "com.anatawa12.package",
+                ),
+                (
+                    Path::new("Assets/LegacyGuidMismatchAsset.cs").into(),
+                    "com.anatawa12.package",
+                ),
+            ]
+            .iter()
+            .collect::<HashSet<_>>()
+        );
+    })
+}
+
+#[test]
+fn do_not_remove_udonsharp_folder_if_guid_mismatch() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dir("Assets/UdonSharp")
+            .add_file(
+                "Assets/UdonSharp.meta",
+                "guid: e2095dec983b4d9481723c263fd5b6c0",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.worlds", Version::new(1, 0, 0))
+                    .add_legacy_folder("Assets\\UdonSharp", "b031f928e5c709b4887f6513084aaa51"),
+            )
+            .build();
+
+        let package = collection.get_package("com.vrchat.worlds", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_eq!(result.remove_legacy_folders().len(), 0);
+        assert_eq!(result.remove_legacy_files().len(), 0);
+    })
+}
+
+//endregion
+
+// region errors
+
+#[test]
+fn not_found_err() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let err = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .expect_err("should fail");
+
+        match &err {
+            AddPackageErr::DependenciesNotFound {
+                dependencies,
+                requirements,
+            } => {
+                assert_eq!(dependencies.len(), 1);
+                assert_eq!(dependencies[0].as_ref(), "com.vrchat.base");
+                assert_eq!(
+                    requirements,
+                    &[MissingRequirement {
+                        dependency: "com.vrchat.base".into(),
+                        source: "com.vrchat.avatars".into(),
+                        range: "1.0.0".parse().unwrap(),
+                    }]
+                );
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn dependency_cycle_err() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.a", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.b", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.b", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.c", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.c", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.a", "^1.0.0"),
+            )
+            .build();
+
+        let a_package = collection.get_package("com.anatawa12.a", Version::new(1, 0, 0));
+
+        let err = project
+            .add_package_request(
+                &collection,
+                &[a_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .expect_err("should fail");
+
+        match &err {
+            AddPackageErr::DependencyCycle { path } => {
+                assert_eq!(
+                    path.iter().map(|x| x.as_ref()).collect::<Vec<_>>(),
+                    vec![
+                        "com.anatawa12.a",
+                        "com.anatawa12.b",
+                        "com.anatawa12.c",
+                        "com.anatawa12.a"
+                    ]
+                );
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn partial_result_on_not_found_err() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.missing", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let failure = project
+            .add_package_request_with_partial_result(
+                &collection,
+                &[tool_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new(),
+            )
+            .await
+            .expect_err("should fail");
+
+        match &failure.error {
+            AddPackageErr::DependenciesNotFound { dependencies, .. } => {
+                assert_eq!(
+                    dependencies,
+                    &vec![Box::<str>::from("com.anatawa12.missing")]
+                );
+            }
+            _ => panic!("unexpected error: {:?}", failure.error),
+        }
+
+        let partial = failure.partial_result.expect("partial result");
+        assert_eq!(
+            (partial.new_packages.iter())
+                .map(|x| x.name())
+                .collect::<Vec<_>>(),
+            vec!["com.anatawa12.library", "com.anatawa12.tool"]
+        );
+    })
+}
+
+#[test]
+fn resolution_limit_exceeded_err() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.a", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.b", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.b", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.c", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.c",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let a_package = collection.get_package("com.anatawa12.a", Version::new(1, 0, 0));
+
+        let err = project
+            .add_package_request_with_options(
+                &collection,
+                &[a_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().iteration_limit(2),
+            )
+            .await
+            .expect_err("should fail");
+
+        match &err {
+            AddPackageErr::ResolutionLimitExceeded { recent_packages } => {
+                assert_eq!(
+                    recent_packages
+                        .iter()
+                        .map(|x| x.as_ref())
+                        .collect::<Vec<_>>(),
+                    vec!["com.anatawa12.a", "com.anatawa12.b"]
+                );
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn upm_dependencies_are_not_resolved() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_upm_dependency("com.anatawa12.library", "2.0.0")
+                    .add_upm_dependency("com.unity.nuget.newtonsoft-json", "3.2.1"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        assert_eq!(
+            package.upm_dependencies()["com.anatawa12.library"].as_ref(),
+            "2.0.0"
+        );
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &package);
+        assert_installing_to_locked_only(
+            &result,
+            &collection.get_package("com.anatawa12.library", Version::new(1, 0, 0)),
+        );
+    })
+}
+
+#[test]
+fn skip_versions_not_supporting_unity() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "ProjectSettings/ProjectVersion.txt",
+                "m_EditorVersion: 2022.3.6f1\n",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 2, 0))
+                    .with_supported_unity(">=2019.4 <2022.3 || >=2022.3.20f1"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 1, 0))
+                    .with_supported_unity(">=2022.3"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_installing_to_locked_only(
+            &result,
+            &collection.get_package("com.anatawa12.library", Version::new(1, 1, 0)),
+        );
+    })
+}
+
+#[test]
+fn skip_versions_for_other_platforms() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 1, 0))
+                    .add_incompatible_platform("Android"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.desktop", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.native", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.native", Version::new(1, 0, 0))
+                    .add_incompatible_platform("android"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let desktop = collection.get_package("com.anatawa12.desktop", Version::new(1, 0, 0));
+        let options = ResolutionOptions::new().target_platform("Android");
+
+        // the newest version for the platform is chosen
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_installing_to_locked_only(
+            &result,
+            &collection.get_package("com.anatawa12.library", Version::new(1, 0, 0)),
+        );
+
+        // without the target platform, the newest version is chosen as usual
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        assert_installing_to_locked_only(
+            &result,
+            &collection.get_package("com.anatawa12.library", Version::new(1, 1, 0)),
+        );
+
+        let err = project
+            .add_package_request_with_options(
+                &collection,
+                &[desktop],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .expect_err("should fail");
+        match &err {
+            AddPackageErr::PlatformIncompatible {
+                dependency_name,
+                platform,
+            } => {
+                assert_eq!(dependency_name.as_ref(), "com.anatawa12.native");
+                assert_eq!(platform.as_ref(), "Android");
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn resolve_for_multiple_unity_versions() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 1, 0))
+                    .with_unity(2022, 3),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 0, 0))
+                    .with_unity(2019, 4),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.modern", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.new-only", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.new-only", Version::new(1, 0, 0))
+                    .with_unity(2022, 3),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let modern = collection.get_package("com.anatawa12.modern", Version::new(1, 0, 0));
+        let unity_versions = vec![
+            UnityVersion::new_f1(2019, 4, 31),
+            UnityVersion::new_f1(2021, 3, 45),
+            UnityVersion::new_f1(2022, 3, 22),
+        ];
+        let options = ResolutionOptions::new().target_unity_versions(unity_versions.clone());
+
+        // the newest version compatible with all the unity versions is chosen
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap();
+        assert_installing_to_locked_only(
+            &result,
+            &collection.get_package("com.anatawa12.library", Version::new(1, 0, 0)),
+        );
+
+        let err = project
+            .add_package_request_with_options(
+                &collection,
+                &[modern],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .expect_err("should fail");
+        match &err {
+            AddPackageErr::TargetUnityIncompatible {
+                dependency_name,
+                unity_versions: reported,
+            } => {
+                assert_eq!(dependency_name.as_ref(), "com.anatawa12.new-only");
+                assert_eq!(reported, &unity_versions);
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn keep_locked_yanked_versions() {
+    block_on(async {
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 0, 0))
+                    .with_yanked("broken"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .build();
+        let library = collection.get_package("com.anatawa12.library", Version::new(1, 0, 0));
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+        assert_eq!(
+            library.availability(),
+            PackageAvailability::Yanked(Some("broken"))
+        );
+
+        // the locked yanked version is kept
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.library", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().detect_kept_yanked(true),
+            )
+            .await
+            .unwrap();
+        assert_installing_to_both(&result, &tool);
+        assert_eq!(
+            result.resolution().unwrap().kept_yanked,
+            vec![("com.anatawa12.library".into(), Version::new(1, 0, 0))]
+        );
+
+        // the yanked version is never selected for new installs
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+        let err = project
+            .add_package_request(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .expect_err("should fail");
+        match &err {
+            AddPackageErr::AllCandidatesYanked { dependency_name } => {
+                assert_eq!(dependency_name.as_ref(), "com.anatawa12.library");
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn unity_version_too_old_err() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "ProjectSettings/ProjectVersion.txt",
+                "m_EditorVersion: 2019.4.31f1\n",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 0, 0))
+                    .with_unity(2022, 3),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let err = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .expect_err("should fail");
+
+        match &err {
+            AddPackageErr::UnityVersionTooOld {
+                dependency_name,
+                required_unity,
+                project_unity,
+            } => {
+                assert_eq!(dependency_name.as_ref(), "com.anatawa12.library");
+                assert_eq!(
+                    *required_unity,
+                    UnityVersion::new(2022, 3, 0, ReleaseType::Alpha, 0)
+                );
+                assert_eq!(
+                    *project_unity,
+                    UnityVersion::new(2019, 4, 31, ReleaseType::Normal, 1)
+                );
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn unsatisfiable_requirement_err() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^2.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let err = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .expect_err("should fail");
+
+        match &err {
+            AddPackageErr::UnsatisfiableRequirements { requirements } => {
+                assert_eq!(requirements.len(), 1);
+                let (dependency, source, range) = &requirements[0];
+                assert_eq!(dependency.as_ref(), "com.vrchat.base");
+                assert_eq!(source.as_ref(), "com.vrchat.avatars");
+                assert_eq!(range.to_string(), "^2.0.0");
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn disallowed_license_err() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .with_license("MIT")
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.vrchat.base", Version::new(1, 0, 0))
+                    .with_license("GPL-3.0-only"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        // non-strict policy just reports the license
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().license_policy(LicensePolicy::new(["MIT"])),
+            )
+            .await
+            .unwrap();
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(resolution.licenses.len(), 2);
+        assert_eq!(
+            resolution.disallowed_licenses,
+            vec![Box::<str>::from("com.vrchat.base")]
+        );
+
+        let err = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().license_policy(LicensePolicy::new(["MIT"]).strict(true)),
+            )
+            .await
+            .expect_err("should fail");
+
+        match &err {
+            AddPackageErr::DisallowedLicense {
+                package_name,
+                license,
+            } => {
+                assert_eq!(package_name.as_ref(), "com.vrchat.base");
+                assert_eq!(license.as_deref(), Some("GPL-3.0-only"));
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn updating_non_locked_package_should_cause_error() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let err = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::UpgradeLocked,
+                false,
+            )
+            .await
+            .expect_err("should fail");
+
+        match &err {
+            AddPackageErr::UpgradingNonLockedPackage { package_name } => {
+                assert_eq!(package_name.as_ref(), "com.vrchat.avatars");
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn downgrade_basic() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_locked("com.vrchat.base", Version::new(1, 1, 0), &[])
+            .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[base_package],
+                AddPackageOperation::Downgrade,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.remove_legacy_folders().len(), 0);
+        assert_eq!(result.remove_legacy_files().len(), 0);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_installing_to_locked_only(&result, &base_package);
+    })
+}
+
+#[test]
+fn downgrade_dependencies() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_locked("com.vrchat.base", Version::new(1, 1, 0), &[])
+            .add_dependency("com.vrchat.base", Version::new(1, 1, 0))
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[base_package],
+                AddPackageOperation::Downgrade,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.remove_legacy_folders().len(), 0);
+        assert_eq!(result.remove_legacy_files().len(), 0);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_installing_to_both(&result, &base_package);
+    })
+}
+
+// endregion
+
+// region conflicts
+
+#[test]
+fn conflict_requirements_of_installed_and_installing() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+            )
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+        let base_1_1_0 = collection.get_package("com.vrchat.base", Version::new(1, 1, 0));
+
+        let resolve = project
+            .add_package_request(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resolve.package_changes().len(), 2);
+        assert_eq!(resolve.remove_legacy_folders().len(), 0);
+        assert_eq!(resolve.remove_legacy_files().len(), 0);
+        assert_eq!(resolve.conflicts().len(), 1);
+
+        assert_installing_to_both(&resolve, &tool);
+        assert_installing_to_locked_only(&resolve, &base_1_1_0);
+
+        let base_conflict = resolve.conflicts().get("com.vrchat.base").unwrap();
+        assert_eq!(
+            base_conflict.conflicting_packages(),
+            &["com.vrchat.avatars".into()]
+        )
+    })
+}
+
+#[test]
+fn conflict_already_conflicted_and_no_new_conflict() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 1, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+            )
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let resolve = project
+            .add_package_request(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resolve.package_changes().len(), 1);
+        assert_eq!(resolve.remove_legacy_folders().len(), 0);
+        assert_eq!(resolve.remove_legacy_files().len(), 0);
+        assert_eq!(resolve.conflicts().len(), 0);
+
+        assert_installing_to_both(&resolve, &tool);
+    })
+}
+
+#[test]
+fn conflict_requirements_of_installed_and_installing_related_to_dependencies() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency_range("com.vrchat.avatars", "~3.5.x")
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(3, 4, 2),
+                &[("com.vrchat.base", "3.4.2")],
+            )
+            .add_locked("com.vrchat.base", Version::new(3, 4, 2), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.base", Version::new(3, 4, 2))
+                    .add_vpm_dependency("com.vrchat.avatars", "3.4.2"),
+            )
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(3, 4, 2),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.avatars", "^3.3.0"),
+            )
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let resolve = project
+            .add_package_request(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resolve.package_changes().len(), 1);
+        assert_eq!(resolve.remove_legacy_folders().len(), 0);
+        assert_eq!(resolve.remove_legacy_files().len(), 0);
+        assert_eq!(resolve.conflicts().len(), 0);
+
+        assert_installing_to_both(&resolve, &tool);
+    })
+}
+
+#[test]
+fn report_ranges_of_conflicting_requirements() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "~1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+            )
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(resolution.conflicts.len(), 1);
+        assert_eq!(
+            resolution.conflicts["com.vrchat.base"],
+            vec![ConflictSource {
+                package: "com.vrchat.avatars".into(),
+                range: "~1.0.0".parse().unwrap(),
+                selected: Version::new(1, 1, 0),
+            }]
+        );
+    })
+}
+
+#[test]
+fn sort_resolution_result_by_name() {
+    let collection = PackageCollectionBuilder::new()
+        .add(
+            PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                .add_vpm_dependency("com.anatawa12.zeta", "^1.0.0")
+                .add_vpm_dependency("com.anatawa12.alpha", "^1.0.0")
+                .add_vpm_dependency("com.anatawa12.middle", "^1.0.0")
+                .add_vpm_dependency("com.vrchat.base", "^1.1.0")
+                .add_legacy_package("com.anatawa12.legacy-z")
+                .add_legacy_package("com.anatawa12.legacy-a"),
+        )
+        .add(PackageManifest::new(
+            "com.anatawa12.zeta",
+            Version::new(1, 0, 0),
+        ))
+        .add(PackageManifest::new(
+            "com.anatawa12.alpha",
+            Version::new(1, 0, 0),
+        ))
+        .add(PackageManifest::new(
+            "com.anatawa12.middle",
+            Version::new(1, 0, 0),
+        ))
+        .add(PackageManifest::new(
+            "com.vrchat.base",
+            Version::new(1, 1, 0),
+        ))
+        .build();
+    let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+    for _ in 0..10 {
+        block_on(async {
+            let project = VirtualProjectBuilder::new()
+                .add_dependency("com.vrchat.worlds", Version::new(1, 0, 0))
+                .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+                .add_locked(
+                    "com.vrchat.worlds",
+                    Version::new(1, 0, 0),
+                    &[("com.vrchat.base", "1.0.0")],
+                )
+                .add_locked(
+                    "com.vrchat.avatars",
+                    Version::new(1, 0, 0),
+                    &[("com.vrchat.base", "1.0.0")],
+                )
+                .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+                .add_locked("com.anatawa12.legacy-z", Version::new(1, 0, 0), &[])
+                .add_locked("com.anatawa12.legacy-a", Version::new(1, 0, 0), &[])
+                .build()
+                .await
+                .unwrap();
+
+            let result = project
+                .add_package_request(
+                    &collection,
+                    &[tool],
+                    AddPackageOperation::InstallToDependencies,
+                    false,
+                )
+                .await
+                .unwrap();
+
+            let resolution = result.resolution().unwrap();
+            assert_eq!(
+                (resolution.new_packages.iter())
+                    .map(|x| x.name())
+                    .collect::<Vec<_>>(),
+                vec![
+                    "com.anatawa12.alpha",
+                    "com.anatawa12.middle",
+                    "com.anatawa12.tool",
+                    "com.anatawa12.zeta",
+                    "com.vrchat.base",
+                ]
+            );
+            assert_eq!(
+                resolution.found_legacy_packages,
+                vec![
+                    "com.anatawa12.legacy-a".into(),
+                    "com.anatawa12.legacy-z".into()
+                ]
+            );
+            assert_eq!(
+                (resolution.conflicts["com.vrchat.base"].iter())
+                    .map(|x| x.package.as_ref())
+                    .collect::<Vec<_>>(),
+                vec!["com.vrchat.avatars", "com.vrchat.worlds"]
+            );
+        })
+    }
+}
+
+// endregion
+
+// region rollback on error
+
+#[test]
+fn collecting_changes_does_not_write_files() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency_range("com.vrchat.avatars", "~3.4.x")
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(3, 4, 2),
+                &[("com.vrchat.base", "3.4.2")],
+            )
+            .add_locked("com.vrchat.base", Version::new(3, 4, 2), &[])
+            .add_file(
+                "Packages/com.vrchat.avatars/package.json",
+                r#"{"name":"com.vrchat.avatars","version":"3.4.2"}"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(3, 5, 0))
+                    .add_vpm_dependency("com.vrchat.base", "3.5.0"),
+            )
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(3, 5, 0),
+            ))
+            .build();
+
+        async fn read_manifest(io: &impl IoTrait) -> String {
+            let mut manifest = String::new();
+            (io.open("Packages/vpm-manifest.json".as_ref())
+                .await
+                .unwrap())
+            .read_to_string(&mut manifest)
+            .await
+            .unwrap();
+            manifest
+        }
+
+        let before = read_manifest(project.io()).await;
+
+        let avatars = collection.get_package("com.vrchat.avatars", Version::new(3, 5, 0));
+        let add = project
+            .add_package_request(
+                &collection,
+                &[avatars],
+                AddPackageOperation::UpgradeLocked,
+                false,
+            )
+            .await
+            .unwrap();
+        assert!(!add.package_changes().is_empty());
+        let remove = project
+            .remove_request(&["com.vrchat.avatars"])
+            .await
+            .unwrap();
+        assert!(!remove.package_changes().is_empty());
+
+        assert_eq!(read_manifest(project.io()).await, before);
+        project
+            .io()
+            .metadata("Packages/com.vrchat.avatars/package.json".as_ref())
+            .await
+            .unwrap();
+        assert_eq!(
+            project
+                .io()
+                .metadata("Temp".as_ref())
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    })
+}
+
+#[test]
+fn no_temp_folder_after_add() {
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new()
+            .add_dependency_range("com.vrchat.avatars", "~3.5.x")
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(3, 4, 2),
+                &[("com.vrchat.base", "3.4.2")],
+            )
+            .add_locked("com.vrchat.base", Version::new(3, 4, 2), &[])
+            .add_file(
+                "Packages/com.vrchat.avatars/package.json",
+                r#"{"name":"com.vrchat.avatars","version":"3.4.2"}"#,
+            )
+            .add_file("Packages/com.vrchat.avatars/content.txt", "text")
+            .build()
+            .await
+            .unwrap();
+
+        let env_vfs = VirtualFileSystem::new();
+        let env = VirtualEnvironment::new(env_vfs);
+
+        let resolve = project
+            .remove_request(&["com.vrchat.avatars"])
+            .await
+            .unwrap();
+
+        project.apply_pending_changes(&env, resolve).await.unwrap();
+
+        assert_eq!(
+            project
+                .io()
+                .metadata("Temp".as_ref())
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+        assert_eq!(
+            project
+                .io()
+                .metadata("Packages/com.vrchat.avatars".as_ref())
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    })
+}
+
+#[test]
+fn locked_in_package_folder() {
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new()
+            .add_dependency_range("com.vrchat.avatars", "~3.5.x")
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(3, 4, 2),
+                &[("com.vrchat.base", "3.4.2")],
+            )
+            .add_locked("com.vrchat.base", Version::new(3, 4, 2), &[])
+            .add_file(
+                "Packages/com.vrchat.avatars/package.json",
+                r#"{"name":"com.vrchat.avatars","version":"3.4.2"}"#,
+            )
+            .add_file("Packages/com.vrchat.avatars/content.txt", "text")
+            .build()
+            .await
+            .unwrap();
+
+        project
+            .io()
+            .deny_deletion("Packages/com.vrchat.avatars/content.txt".as_ref())
+            .await
+            .unwrap();
+
+        let env_vfs = VirtualFileSystem::new();
+        let env = VirtualEnvironment::new(env_vfs);
+
+        let resolve = project
+            .remove_request(&["com.vrchat.avatars"])
+            .await
+            .unwrap();
+
+        project.apply_pending_changes(&env, resolve).await.unwrap();
+
+        assert_eq!(
+            project
+                .io()
+                .metadata("Packages/com.vrchat.avatars".as_ref())
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+        project.io().metadata("Temp".as_ref()).await.unwrap();
+        project
+            .io()
+            .metadata("Temp/vrc-get".as_ref())
+            .await
+            .unwrap();
+    })
+}
+
+#[test]
+fn rollback_cancelled_install() {
+    /// The installer cancelling in the middle of extracting the package
+    struct CancellingInstaller(CancellationToken);
+
+    impl PackageInstaller for CancellingInstaller {
+        async fn install_package(
+            &self,
+            io: &impl ProjectIo,
+            package: PackageInfo<'_>,
+            abort: &AbortCheck,
+        ) -> io::Result<()> {
+            let dest_folder = format!("Packages/{}", package.name());
+            io.create_dir_all(dest_folder.as_ref()).await?;
+            io.write(format!("{dest_folder}/partial.txt").as_ref(), b"partial")
+                .await?;
+            self.0.cancel();
+            abort.check()
+        }
+    }
+
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .add_file(
+                "Packages/com.anatawa12.package/package.json",
+                r#"{"name":"com.anatawa12.package","version":"1.0.0"}"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 1, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 1, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::UpgradeLocked,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let cancellation = CancellationToken::new();
+        let installer = CancellingInstaller(cancellation.clone());
+        let err = project
+            .apply_pending_changes_cancellable(&installer, result, &cancellation)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+
+        // the partially extracted package is removed and the previous one is restored
+        assert_eq!(
+            project
+                .io()
+                .metadata("Packages/com.anatawa12.package/partial.txt".as_ref())
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+        (project.io())
+            .metadata("Packages/com.anatawa12.package/package.json".as_ref())
+            .await
+            .unwrap();
+
+        // the manifest is not changed
+        assert_eq!(
+            project
+                .get_locked("com.anatawa12.package")
+                .unwrap()
+                .version(),
+            &Version::new(1, 0, 0)
+        );
+        let mut manifest = String::new();
+        (project
+            .io()
+            .open("Packages/vpm-manifest.json".as_ref())
+            .await)
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .await
+            .unwrap();
+        assert!(!manifest.contains("1.1.0"), "manifest is saved: {manifest}");
+    })
+}
+
+#[test]
+fn apply_changes_to_lockfile_only() {
+    let installer = CheckOnlyInstaller(&["com.anatawa12.unavailable"]);
+
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.unavailable",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let unavailable =
+            collection.get_package("com.anatawa12.unavailable", Version::new(1, 0, 0));
+
+        // the lockfile is not changed if some package is not available
+        let result = project
+            .add_package_request(
+                &collection,
+                &[unavailable],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        let err = project
+            .apply_pending_changes_lockfile_only(&installer, result)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(project.get_locked("com.anatawa12.unavailable").is_none());
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        project
+            .apply_pending_changes_lockfile_only(&installer, result)
+            .await
+            .unwrap();
+
+        // the manifest is saved, but packages are not installed
+        let mut manifest = String::new();
+        (project
+            .io()
+            .open("Packages/vpm-manifest.json".as_ref())
+            .await)
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .await
+            .unwrap();
+        assert!(manifest.contains("com.anatawa12.package"));
+        assert!(manifest.contains("com.anatawa12.library"));
+        assert_eq!(
+            project
+                .io()
+                .metadata("Packages/com.anatawa12.package".as_ref())
+                .await
+                .unwrap_err()
+                .kind(),
+            io::ErrorKind::NotFound
+        );
+    })
+}
+
+#[test]
+fn reject_lockfile_only_changes_without_package_checks() {
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        // the installer without check_package can't tell the package is available
+        let env = VirtualEnvironment::new(VirtualFileSystem::new());
+        let err = project
+            .apply_pending_changes_lockfile_only(&env, result)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::Unsupported);
+        assert!(project.get_locked("com.anatawa12.package").is_none());
+    })
+}
+
+#[test]
+fn report_declared_post_install() {
+    struct WritingInstaller;
+
+    impl PackageInstaller for WritingInstaller {
+        async fn install_package(
+            &self,
+            io: &impl ProjectIo,
+            package: PackageInfo<'_>,
+            _: &AbortCheck,
+        ) -> io::Result<()> {
+            let dest_folder = format!("Packages/{}", package.name());
+            io.create_dir_all(dest_folder.as_ref()).await?;
+            io.write(format!("{dest_folder}/package.json").as_ref(), b"{}")
+                .await
+        }
+    }
+
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .with_post_install("Tools/Generate Links"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        assert_eq!(package.post_install(), Some("Tools/Generate Links"));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let mut declared = Vec::new();
+        project
+            .apply_pending_changes_with_post_install(
+                &WritingInstaller,
+                result,
+                |package, action| declared.push((package.name().to_owned(), action.to_owned())),
+            )
+            .await
+            .unwrap();
+
+        // only the packages declaring the actions are reported, after installed
+        assert_eq!(
+            declared,
+            vec![(
+                "com.anatawa12.package".to_owned(),
+                "Tools/Generate Links".to_owned()
+            )]
+        );
+        (project.io())
+            .metadata("Packages/com.anatawa12.package/package.json".as_ref())
+            .await
+            .unwrap();
+    })
+}
+
+#[test]
+fn keep_unchanged_lockfile() {
+    block_on(async {
+        // formatted differently from vrc-get to detect rewriting
+        let manifest = r#"{"dependencies":{"com.anatawa12.package":{"version":"1.0.0"}},"locked":{"com.anatawa12.package":{"version":"1.0.0","dependencies":{}}}}"#;
+        let mut project = VirtualProjectBuilder::new()
+            .add_file("Packages/vpm-manifest.json", manifest)
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        // reinstalling the locked version writes the same lock entry again
+        for _ in 0..2 {
+            let result = project
+                .add_package_request(
+                    &collection,
+                    &[package],
+                    AddPackageOperation::AutoDetected,
+                    false,
+                )
+                .await
+                .unwrap();
+            project
+                .apply_pending_changes_lockfile_only(&CheckOnlyInstaller(&[]), result)
+                .await
+                .unwrap();
+
+            let mut written = String::new();
+            (project
+                .io()
+                .open("Packages/vpm-manifest.json".as_ref())
+                .await)
+                .unwrap()
+                .read_to_string(&mut written)
+                .await
+                .unwrap();
+            assert_eq!(written, manifest);
+        }
+    })
+}
+
+#[test]
+fn write_lockfile_in_sorted_order() {
+    block_on(async {
+        let manifest = r#"{"dependencies":{"com.anatawa12.zeta":{"version":"1.0.0"}},"locked":{"com.anatawa12.zeta":{"version":"1.0.0","dependencies":{"com.anatawa12.lib-b":"1.0.0","com.anatawa12.lib-a":"1.0.0"}},"com.anatawa12.lib-b":{"version":"1.0.0"},"com.anatawa12.lib-a":{"version":"1.0.0"}}}"#;
+        let mut project = VirtualProjectBuilder::new()
+            .add_file("Packages/vpm-manifest.json", manifest)
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.alpha", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.lib-c", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.lib-a", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.lib-c",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.alpha", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        project
+            .apply_pending_changes_lockfile_only(&CheckOnlyInstaller(&[]), result)
+            .await
+            .unwrap();
+
+        let mut written = String::new();
+        (project
+            .io()
+            .open("Packages/vpm-manifest.json".as_ref())
+            .await)
+            .unwrap()
+            .read_to_string(&mut written)
+            .await
+            .unwrap();
+
+        // the packages and the dependencies of locked packages are sorted by the name
+        let golden = r#"{
+  "dependencies": {
+    "com.anatawa12.alpha": {
+      "version": "1.0.0"
+    },
+    "com.anatawa12.zeta": {
+      "version": "1.0.0"
+    }
+  },
+  "locked": {
+    "com.anatawa12.alpha": {
+      "version": "1.0.0",
+      "dependencies": {
+        "com.anatawa12.lib-a": "^1.0.0",
+        "com.anatawa12.lib-c": "^1.0.0"
+      }
+    },
+    "com.anatawa12.lib-a": {
+      "version": "1.0.0"
+    },
+    "com.anatawa12.lib-b": {
+      "version": "1.0.0"
+    },
+    "com.anatawa12.lib-c": {
+      "version": "1.0.0",
+      "dependencies": {}
+    },
+    "com.anatawa12.zeta": {
+      "version": "1.0.0",
+      "dependencies": {
+        "com.anatawa12.lib-a": "1.0.0",
+        "com.anatawa12.lib-b": "1.0.0"
+      }
+    }
+  }
+}"#;
+        let eol = if cfg!(windows) { "\r\n" } else { "\n" };
+        assert_eq!(written, golden.replace('\n', eol));
+    })
+}
+
+#[test]
+fn save_lockfile_atomically() {
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.library", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        project
+            .apply_pending_changes_lockfile_only(&CheckOnlyInstaller(&[]), result)
+            .await
+            .unwrap();
+
+        // the existing lockfile is replaced and no temporary file is left
+        let mut manifest = String::new();
+        (project
+            .io()
+            .open("Packages/vpm-manifest.json".as_ref())
+            .await)
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .await
+            .unwrap();
+        assert!(manifest.contains("com.anatawa12.package"));
+        assert!(manifest.contains("com.anatawa12.library"));
+
+        let entries = (project.io().read_dir("Packages".as_ref()).await.unwrap())
+            .map_ok(|x| x.file_name())
+            .try_collect::<Vec<_>>()
+            .await
+            .unwrap();
+        assert!(
+            entries
+                .iter()
+                .all(|x| !x.to_string_lossy().ends_with(".tmp")),
+            "{entries:?}"
+        );
+    })
+}
+
+// endregion
+
+// region unlocked
+
+#[test]
+fn install_depends_on_unlocked() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "Packages/base/package.json",
+                r#"{"name":"com.vrchat.base","version":"1.0.0"}"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.remove_legacy_folders().len(), 0);
+        assert_eq!(result.remove_legacy_files().len(), 0);
+        assert_eq!(result.conflicts().len(), 0);
+
+        assert_installing_to_both(&result, &avatars_package);
+    })
+}
+
+#[test]
+fn install_conflict_with_unlocked() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "Packages/base/package.json",
+                r#"{"name":"com.vrchat.base","version":"1.0.0"}"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 1, 0))
+                    .add_vpm_dependency("com.vrchat.base", "1.1.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 1, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.remove_legacy_folders().len(), 0);
+        assert_eq!(result.remove_legacy_files().len(), 0);
+        assert_eq!(result.conflicts().len(), 1);
+
+        assert_installing_to_both(&result, &avatars_package);
+
+        let base_conflict = result.conflicts().get("com.vrchat.base").unwrap();
+        assert_eq!(
+            base_conflict.conflicting_packages(),
+            &["com.vrchat.avatars".into()]
+        )
+    })
+}
+
+// endregion
+
+// region conflict strategy
+
+#[test]
+fn conflict_strategy_drop_requirement() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^2.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().conflict_strategy(&DropRequirement),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &avatars_package);
+    })
+}
+
+#[test]
+fn conflict_strategy_relax_to_any_version() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^2.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().conflict_strategy(&RelaxToAnyVersion),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &base_package);
+
+        // the relaxed requirement is reported as a conflict
+        let base_conflict = result.conflicts().get("com.vrchat.base").unwrap();
+        assert_eq!(
+            base_conflict.conflicting_packages(),
+            &["com.vrchat.avatars".into()]
+        );
+    })
+}
+
+#[test]
+fn conflict_strategy_retries_are_capped() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^2.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        // the strategy ignoring the attempt retries forever if not capped
+        let attempts = Cell::new(0);
+        let always_retry = |conflict: &ConflictContext| {
+            attempts.set(conflict.attempt + 1);
+            ConflictDecision::RetryWithRange(conflict.range.clone())
+        };
+
+        let err = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().conflict_strategy(&always_retry),
+            )
+            .await
+            .unwrap_err();
+
+        assert!(matches!(
+            err,
+            AddPackageErr::UnsatisfiableRequirements { .. }
+        ));
+        assert_eq!(attempts.get(), MAX_CONFLICT_RETRIES);
+    })
+}
+
+#[test]
+fn violation_handler_decides_version() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.world", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.world",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "^1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.world", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(2, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^2.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let violations = RefCell::new(Vec::new());
+        let select = |violation: &ViolationContext| {
+            violations.borrow_mut().push((
+                violation.package.to_owned(),
+                violation.dependency.to_owned(),
+                violation.selecting.clone(),
+                (violation.violated.iter())
+                    .map(|&(source, range)| (source.to_owned(), range.clone()))
+                    .collect::<Vec<_>>(),
+                (violation.candidates.iter())
+                    .map(|&x| x.clone())
+                    .collect::<Vec<_>>(),
+            ));
+            ViolationDecision::Select(Version::new(2, 0, 0))
+        };
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().violation_handler(&select),
+            )
+            .await
+            .unwrap();
+
+        assert_installing_to_locked_only(
+            &result,
+            &collection.get_package("com.vrchat.base", Version::new(2, 0, 0)),
+        );
+        assert_eq!(
+            violations.into_inner(),
+            vec![(
+                "com.vrchat.avatars".to_owned(),
+                "com.vrchat.base".to_owned(),
+                Version::new(2, 1, 0),
+                vec![(
+                    "com.vrchat.world".to_owned(),
+                    "^1.0.0".parse::<VersionRange>().unwrap()
+                )],
+                vec![
+                    Version::new(2, 1, 0),
+                    Version::new(2, 0, 0),
+                    Version::new(1, 0, 0)
+                ],
+            )],
+        );
+
+        // aborting stops the resolution
+        let abort = |_: &ViolationContext| ViolationDecision::Abort;
+        let err = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().violation_handler(&abort),
+            )
+            .await
+            .expect_err("should fail");
+        match &err {
+            AddPackageErr::ResolutionAborted { dependency_name } => {
+                assert_eq!(dependency_name.as_ref(), "com.vrchat.base");
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+
+        // without the handler, the newest version is chosen and reported as a conflict
+        let result = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        assert_installing_to_locked_only(
+            &result,
+            &collection.get_package("com.vrchat.base", Version::new(2, 1, 0)),
+        );
+        assert!(result.conflicts().contains_key("com.vrchat.base"));
+    })
+}
+
+#[test]
+fn sacrifice_lower_priority_requirement() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency_range("com.vrchat.base", "^1.0.0")
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^2.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().requirement_priority("com.vrchat.avatars", 0),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_both(&result, &avatars_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.sacrificed_requirements,
+            vec![("com.vrchat.base".into(), "com.vrchat.avatars".into())]
+        );
+    })
+}
+
+// endregion
+
+// region backtracking
+
+#[test]
+fn backtrack_to_older_version_for_missing_dependency() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 1, 0))
+                    .add_vpm_dependency("com.anatawa12.missing", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.tool", "^1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let err = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .expect_err("should fail without backtracking");
+        assert!(matches!(err, AddPackageErr::DependenciesNotFound { .. }));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().backtracking(true),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &tool_package);
+    })
+}
+
+// endregion
+
+// region prerelease
+
+#[test]
+fn scope_prerelease_to_roots() {
+    block_on(async {
+        let tool_beta = Version::new_pre(1, 0, 0, "beta.1".parse().unwrap());
+        let library_beta = Version::new_pre(1, 1, 0, "beta.1".parse().unwrap());
+
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                library_beta.clone(),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", tool_beta.clone())
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .build();
+
+        let tool_package = collection.get_package("com.anatawa12.tool", tool_beta);
+
+        for (propagation, library_version) in [
+            (PrereleasePropagation::Propagate, library_beta.clone()),
+            (PrereleasePropagation::RootOnly, Version::new(1, 0, 0)),
+        ] {
+            let options = ResolutionOptions::new()
+                .allow_prerelease(true)
+                .prerelease_propagation(propagation);
+
+            let result = project
+                .add_package_request_with_options(
+                    &collection,
+                    &[tool_package],
+                    AddPackageOperation::InstallToDependencies,
+                    &options,
+                )
+                .await
+                .unwrap();
+
+            let library_package = collection.get_package("com.anatawa12.library", library_version);
+
+            assert_eq!(result.package_changes().len(), 2);
+            assert_installing_to_both(&result, &tool_package);
+            assert_installing_to_locked_only(&result, &library_package);
+        }
+    })
+}
+
+#[test]
+fn report_stuck_on_prerelease() {
+    block_on(async {
+        let base_beta = Version::new_pre(1, 0, 0, "beta.3".parse().unwrap());
+
+        let project = VirtualProjectBuilder::new()
+            .add_locked("com.vrchat.base", base_beta.clone(), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new("com.vrchat.base", base_beta.clone()))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", ">=1.0.0-beta.1"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_both(&result, &avatars_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.stuck_on_prerelease,
+            vec![Box::<str>::from("com.vrchat.base")]
+        );
+    })
+}
+
+#[test]
+fn not_stuck_on_prerelease_with_stable_for_newer_unity() {
+    block_on(async {
+        let base_beta = Version::new_pre(1, 0, 0, "beta.3".parse().unwrap());
+
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "ProjectSettings/ProjectVersion.txt",
+                "m_EditorVersion: 2019.4.31f1\n",
+            )
+            .add_locked("com.vrchat.base", base_beta.clone(), &[])
+            .build()
+            .await
+            .unwrap();
+
+        // the stable version satisfies the requirements but not the unity version of the project
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new("com.vrchat.base", base_beta.clone()))
+            .add(PackageManifest::new("com.vrchat.base", Version::new(1, 0, 0)).with_unity(2022, 3))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", ">=1.0.0-beta.1"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_installing_to_both(&result, &avatars_package);
+
+        let resolution = result.resolution().unwrap();
+        assert!(resolution.stuck_on_prerelease.is_empty());
+    })
+}
+
+#[test]
+fn prerelease_matching_rule() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let rc = Version::new_pre(1, 2, 0, "rc.1".parse().unwrap());
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new("com.vrchat.base", rc.clone()))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", ">=1.0.0-0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", rc.clone());
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().prerelease_matching(PrereleaseMatching::AnyPrerelease),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &base_package);
+
+        let err = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().prerelease_matching(PrereleaseMatching::SameVersion),
+            )
+            .await
+            .expect_err("should fail");
+
+        match &err {
+            AddPackageErr::DependenciesNotFound { dependencies, .. } => {
+                assert_eq!(dependencies, &vec![Box::<str>::from("com.vrchat.base")]);
+            }
+            _ => panic!("unexpected error: {:?}", err),
+        }
+    })
+}
+
+#[test]
+fn report_transitive_prereleases() {
+    block_on(async {
+        let base_beta = Version::new_pre(1, 0, 0, "beta.3".parse().unwrap());
+
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new("com.vrchat.base", base_beta.clone()))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", ">=1.0.0-beta.1"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", base_beta.clone());
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &base_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.transitive_prereleases,
+            vec![(Box::<str>::from("com.vrchat.base"), base_beta)]
+        );
+    })
+}
+
+#[test]
+fn resolve_in_release_channel() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let beta = Version::new_pre(1, 1, 0, "beta.1".parse().unwrap());
+        let nightly = Version::new_pre(1, 2, 0, "nightly.3".parse().unwrap());
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new("com.vrchat.base", beta.clone()))
+            .add(PackageManifest::new("com.vrchat.base", nightly.clone()))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", ">=1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", beta);
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().channel("beta"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &base_package);
+    })
+}
+
+#[test]
+fn allow_prerelease_per_package() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let base_beta = Version::new_pre(1, 1, 0, "beta.1".parse().unwrap());
+        let udon_beta = Version::new_pre(1, 1, 0, "beta.1".parse().unwrap());
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new("com.vrchat.base", base_beta.clone()))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new("com.vrchat.udon", udon_beta))
+            .add(PackageManifest::new(
+                "com.vrchat.udon",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", ">=1.0.0")
+                    .add_vpm_dependency("com.vrchat.udon", ">=1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", base_beta);
+        let udon_package = collection.get_package("com.vrchat.udon", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().prerelease_override("com.vrchat.base", true),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 3);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &base_package);
+        assert_installing_to_locked_only(&result, &udon_package);
+    })
+}
+
+// endregion
+
+// region requirement reports
+
+#[test]
+fn report_slack_of_requirements() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.vrchat.worlds", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let worlds_package = collection.get_package("com.vrchat.worlds", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package, worlds_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().collect_slack(true),
+            )
+            .await
+            .unwrap();
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            (resolution.new_packages.iter())
+                .map(|x| x.name())
+                .collect::<Vec<_>>(),
+            vec!["com.vrchat.avatars", "com.vrchat.base", "com.vrchat.worlds"]
+        );
+        assert_eq!(
+            resolution.version_map()["com.vrchat.base"],
+            Version::new(1, 0, 0)
+        );
+        assert_eq!(resolution.requirements["com.vrchat.base"].len(), 2);
+
+        let slack = resolution.slack("com.vrchat.base");
+        assert_eq!(slack.len(), 1);
+        assert_eq!(slack[0].0.as_ref(), "com.vrchat.avatars");
+        assert_eq!(slack[0].1.to_string(), "^1.0.0 >1.0.0");
+
+        let matrix = resolution.satisfaction_matrix();
+        let base_row = matrix
+            .rows
+            .iter()
+            .find(|x| x.package.as_ref() == "com.vrchat.base")
+            .unwrap();
+        assert_eq!(base_row.version, Version::new(1, 0, 0));
+        assert_eq!(base_row.cells.len(), 2);
+        assert!(base_row.cells.iter().all(|x| x.satisfied));
+        assert!(
+            matrix
+                .to_csv()
+                .contains("com.vrchat.base,1.0.0,com.vrchat.worlds,1.0.0,true\n")
+        );
+    })
+}
+
+#[test]
+fn trace_binding_requirement() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 2, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", ">=1.0.0")
+                    .add_vpm_dependency("com.anatawa12.tool", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", ">=1.1.0"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().diagnostics(true),
+            )
+            .await
+            .unwrap();
+
+        let diagnostics = result.resolution().unwrap().diagnostics.as_ref();
+        let trace = &diagnostics.unwrap().trace;
+        assert_eq!(
+            trace["com.anatawa12.library"]
+                .binding_requirement
+                .as_deref(),
+            Some("com.anatawa12.tool")
+        );
+        assert_eq!(
+            trace["com.anatawa12.tool"].binding_requirement.as_deref(),
+            Some("com.anatawa12.package")
+        );
+    })
+}
+
+#[test]
+fn map_versions_and_dependencies_of_resolution() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.legacy-package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_legacy_package("com.anatawa12.legacy-package"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 1, 0),
+            ))
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.found_legacy_packages,
+            vec!["com.anatawa12.legacy-package".into()]
+        );
+
+        // the legacy package removed by the resolution is not included
+        assert_eq!(
+            resolution.version_map(),
+            HashMap::from([
+                ("com.anatawa12.package".into(), Version::new(1, 0, 0)),
+                ("com.anatawa12.library".into(), Version::new(1, 1, 0)),
+            ])
+        );
+
+        let dependency_map = resolution.dependency_map();
+        assert_eq!(dependency_map.len(), 2);
+        assert_eq!(
+            dependency_map["com.anatawa12.package"]["com.anatawa12.library"].to_string(),
+            "^1.0.0"
+        );
+        assert!(dependency_map["com.anatawa12.library"].is_empty());
+    })
+}
+
+#[test]
+fn export_unsatisfied_requirements_in_matrix() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+            )
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let matrix = result.resolution().unwrap().satisfaction_matrix();
+        let base_row = (matrix.rows.iter())
+            .find(|x| x.package.as_ref() == "com.vrchat.base")
+            .unwrap();
+        assert_eq!(
+            serde_json::to_value(base_row).unwrap(),
+            serde_json::json!({
+                "package": "com.vrchat.base",
+                "version": "1.1.0",
+                "cells": [
+                    { "source": "com.anatawa12.tool", "range": "^1.1.0", "satisfied": true },
+                    { "source": "com.vrchat.avatars", "range": "1.0.0", "satisfied": false },
+                ],
+            })
+        );
+
+        let csv = matrix.to_csv();
+        assert!(csv.starts_with("package,version,source,range,satisfied\n"));
+        assert!(csv.contains("com.vrchat.base,1.1.0,com.anatawa12.tool,^1.1.0,true\n"));
+        assert!(csv.contains("com.vrchat.base,1.1.0,com.vrchat.avatars,1.0.0,false\n"));
+    })
+}
+
+#[test]
+fn match_prerelease_in_matrix_as_resolved() {
+    block_on(async {
+        let base_beta = Version::new_pre(1, 1, 0, "beta.1".parse().unwrap());
+
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", ">=1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new("com.vrchat.base", base_beta.clone()))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.1.0-beta.1"),
+            )
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().diagnostics(true),
+            )
+            .await
+            .unwrap();
+
+        // the range without prerelease doesn't accept the prerelease like the resolution
+        let matrix = result.resolution().unwrap().satisfaction_matrix();
+        let base_row = (matrix.rows.iter())
+            .find(|x| x.package.as_ref() == "com.vrchat.base")
+            .unwrap();
+        assert_eq!(base_row.version, base_beta);
+        assert_eq!(
+            (base_row.cells.iter())
+                .map(|x| (x.source.as_ref(), x.satisfied))
+                .collect::<Vec<_>>(),
+            vec![("com.anatawa12.tool", true), ("com.vrchat.avatars", false)]
+        );
+    })
+}
+
+#[test]
+fn resolve_renamed_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.new-library",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.new-library",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.old-library", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.another", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.another", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.new-library", "^1.1.0"),
+            )
+            .build();
+
+        let tool_package = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+        let another_package =
+            collection.get_package("com.anatawa12.another", Version::new(1, 0, 0));
+        let library_package =
+            collection.get_package("com.anatawa12.new-library", Version::new(1, 1, 0));
+
+        let options = ResolutionOptions::new().aliases(HashMap::from([(
+            "com.anatawa12.old-library",
+            "com.anatawa12.new-library",
+        )]));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[tool_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 3);
+        assert_installing_to_both(&result, &tool_package);
+        assert_installing_to_locked_only(&result, &another_package);
+        assert_installing_to_locked_only(&result, &library_package);
+
+        let resolution = result.resolution().unwrap();
+        let mut sources = resolution.requirements["com.anatawa12.new-library"]
+            .iter()
+            .map(|(source, _)| source.as_ref())
+            .collect::<Vec<_>>();
+        sources.sort();
+        assert_eq!(sources, vec!["com.anatawa12.another", "com.anatawa12.tool"]);
+    })
+}
+
+#[test]
+fn unify_package_ids_differing_in_case() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vendor.tool",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.Vendor.Tool",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.a", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vendor.tool", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.b", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.Vendor.Tool", "^1.0.0"),
+            )
+            .build();
+
+        let a_package = collection.get_package("com.anatawa12.a", Version::new(1, 0, 0));
+        let b_package = collection.get_package("com.anatawa12.b", Version::new(1, 0, 0));
+        let tool_package = collection.get_package("com.vendor.tool", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[a_package, b_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        // the spelling found first is used
+        assert_eq!(result.package_changes().len(), 3);
+        assert_installing_to_both(&result, &a_package);
+        assert_installing_to_both(&result, &b_package);
+        assert_installing_to_locked_only(&result, &tool_package);
+    })
+}
+
+#[test]
+fn unify_package_ids_differing_in_case_with_project_spelling() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_locked("com.Vendor.Tool", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vendor.tool",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.Vendor.Tool",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.a", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vendor.tool", "^1.0.0"),
+            )
+            .build();
+
+        let a_package = collection.get_package("com.anatawa12.a", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[a_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        // the locked package satisfies the requirement with another spelling
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_both(&result, &a_package);
+    })
+}
+
+#[test]
+fn rewrite_requirement_range() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 1, 0));
+
+        let options = ResolutionOptions::new().range_rewriter(|package, dependency, range| {
+            assert_eq!(package, "com.vrchat.avatars");
+            assert_eq!(dependency, "com.vrchat.base");
+            assert_eq!(range.to_string(), "1.0.0");
+            Some("^1.0.0".parse().unwrap())
+        });
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &options,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &base_package);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(resolution.rewritten_requirements.len(), 1);
+        assert_eq!(
+            resolution.rewritten_requirements[0].0.as_ref(),
+            "com.vrchat.base"
+        );
+        assert_eq!(
+            resolution.rewritten_requirements[0].1.as_ref(),
+            "com.vrchat.avatars"
+        );
+        assert_eq!(resolution.rewritten_requirements[0].2.to_string(), "^1.0.0");
+    })
+}
+
+#[test]
+fn explain_absence_of_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.legacy-package", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0")
+                    .add_legacy_package("com.anatawa12.legacy-package"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let resolve = async |options: &ResolutionOptions<'_>| {
+            project
+                .add_package_request_with_options(
+                    &collection,
+                    &[avatars_package],
+                    AddPackageOperation::InstallToDependencies,
+                    options,
+                )
+                .await
+                .unwrap()
+        };
+
+        let result = resolve(&ResolutionOptions::new().diagnostics(true)).await;
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.explain_absence("com.vrchat.avatars"),
+            AbsenceReason::Included
+        );
+        assert_eq!(
+            resolution.explain_absence("com.vrchat.base"),
+            AbsenceReason::Existing {
+                version: Version::new(1, 0, 0)
+            }
+        );
+        assert_eq!(
+            resolution.explain_absence("com.anatawa12.legacy-package"),
+            AbsenceReason::Legacy {
+                replaced_by: vec!["com.vrchat.avatars".into()]
+            }
+        );
+        assert_eq!(
+            resolution.explain_absence("com.anatawa12.unknown"),
+            AbsenceReason::NotRequired
+        );
+
+        // legacy packages are explained even without the diagnostics
+        let result = resolve(&ResolutionOptions::new()).await;
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.explain_absence("com.anatawa12.legacy-package"),
+            AbsenceReason::Legacy {
+                replaced_by: vec!["com.vrchat.avatars".into()]
+            }
+        );
+        assert_eq!(
+            resolution.explain_absence("com.vrchat.avatars"),
+            AbsenceReason::NotRequired
+        );
+    })
+}
+
+#[test]
+fn explain_holdback_of_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.example.mid", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.example.lib", ">=1.0.0 <2.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.example.mid", Version::new(2, 0, 0))
+                    .add_vpm_dependency("com.example.lib", "^2.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.example.lib",
+                Version::new(1, 4, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.example.lib",
+                Version::new(2, 0, 0),
+            ))
+            .build();
+
+        let mid_package = collection.get_package("com.example.mid", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[mid_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let resolution = result.resolution().unwrap();
+        let lib = resolution
+            .explain_holdback(&collection, "com.example.lib")
+            .unwrap();
+        assert!(lib.is_held_back());
+        assert_eq!(lib.version, Version::new(1, 4, 0));
+        assert_eq!(lib.newest, Version::new(2, 0, 0));
+        assert_eq!(lib.caps.len(), 1);
+        assert_eq!(lib.caps[0].source.as_ref(), "com.example.mid");
+        assert_eq!(lib.caps[0].range.to_string(), ">=1.0.0 <2.0.0");
+        assert_eq!(lib.caps[0].allows_up_to, Some(Version::new(1, 4, 0)));
+
+        // the mid package is held back by the request, not by requirements
+        let mid = lib.caps[0].source_explanation.as_deref().unwrap();
+        assert_eq!(mid.version, Version::new(1, 0, 0));
+        assert_eq!(mid.newest, Version::new(2, 0, 0));
+        assert!(mid.caps.is_empty());
+        assert_eq!(
+            mid.other_reasons,
+            vec![RejectionReason::OtherSelected {
+                version: Version::new(1, 0, 0)
+            }]
+        );
+
+        let explanation = resolution.explain_holdback(&collection, "com.example.unknown");
+        assert_eq!(explanation, None);
+    })
+}
+
+#[test]
+fn explain_rejection_of_versions() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "ProjectSettings/ProjectVersion.txt",
+                "m_EditorVersion: 2019.4.31f1\n",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let library_beta = Version::new_pre(1, 10, 0, "beta.1".parse().unwrap());
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                library_beta.clone(),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 9, 0))
+                    .with_unity(2022, 3),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 8, 0),
+            ))
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().diagnostics(true),
+            )
+            .await
+            .unwrap();
+
+        let resolution = result.resolution().unwrap();
+        let explain = |version: Version| {
+            resolution.explain_rejection(&collection, "com.anatawa12.library", &version)
+        };
+
+        assert_eq!(explain(Version::new(1, 8, 0)), vec![]);
+        assert_eq!(
+            explain(Version::new(2, 0, 0)),
+            vec![RejectionReason::Requirement {
+                source: "com.anatawa12.tool".into(),
+                range: "^1.0.0".parse().unwrap(),
+            }]
+        );
+        assert_eq!(
+            explain(library_beta.clone()),
+            vec![RejectionReason::PrereleaseNotAllowed]
+        );
+        let reasons = explain(Version::new(1, 9, 0));
+        assert!(
+            matches!(
+                reasons.as_slice(),
+                [RejectionReason::UnityIncompatible { .. }]
+            ),
+            "{reasons:?}"
+        );
+        assert_eq!(
+            explain(Version::new(1, 7, 0)),
+            vec![RejectionReason::NotFound]
+        );
+    })
+}
+
+#[test]
+fn skip_versions_excluding_project_unity() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "ProjectSettings/ProjectVersion.txt",
+                "m_EditorVersion: 2022.3.20f1\n",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 1, 0))
+                    .with_unity_excludes(">=2022.3.20f1 <2022.3.22f1"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+        let library = collection.get_package("com.anatawa12.library", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[tool],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        // the newer library is skipped on the excluded unity version
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_locked_only(&result, &library);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.explain_rejection(
+                &collection,
+                "com.anatawa12.library",
+                &Version::new(1, 1, 0)
+            ),
+            vec![RejectionReason::UnityExcluded {
+                excluded: UnityVersionRange::parse(">=2022.3.20f1 <2022.3.22f1").unwrap(),
+            }]
+        );
+    })
+}
+
+#[test]
+fn compare_release_types_of_project_unity() {
+    block_on(async {
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 1, 0))
+                    .with_unity_excludes(">=2022.3.6f2 <2022.3.7a1"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+
+        // returns the version of the library installed on the unity version
+        let library_on = async |unity: &str| {
+            let project = VirtualProjectBuilder::new()
+                .add_file(
+                    "ProjectSettings/ProjectVersion.txt",
+                    format!("m_EditorVersion: {unity}\n"),
+                )
+                .build()
+                .await
+                .unwrap();
+            let result = project
+                .add_package_request(
+                    &collection,
+                    &[tool],
+                    AddPackageOperation::InstallToDependencies,
+                    false,
+                )
+                .await
+                .unwrap();
+            let change = &result.package_changes()["com.anatawa12.library"];
+            let install = change.as_install().expect("the library is not installing");
+            install.install_package().unwrap().version().clone()
+        };
+
+        let newer = Version::new(1, 1, 0);
+        let older = Version::new(1, 0, 0);
+        assert_eq!(library_on("2022.3.6a2").await, newer);
+        assert_eq!(library_on("2022.3.6b5").await, newer);
+        assert_eq!(library_on("2022.3.6f1").await, newer);
+        assert_eq!(library_on("2022.3.6f2").await, older);
+        assert_eq!(library_on("2022.3.6p4").await, older);
+        // unknown release types are sorted after the known ones
+        assert_eq!(library_on("2022.3.6r1").await, older);
+        assert_eq!(library_on("2022.3.7a1").await, newer);
+    })
+}
+
+#[test]
+fn report_unity_compatibility_window() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new("com.vrchat.base", Version::new(3, 5, 0)).with_unity(2022, 3))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(3, 4, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .with_unity(2019, 4),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.new-package", Version::new(1, 0, 0))
+                    .with_unity(2022, 3),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let new_package =
+            collection.get_package("com.anatawa12.new-package", Version::new(1, 0, 0));
+
+        let new_base = collection.get_package("com.vrchat.base", Version::new(3, 5, 0));
+        let result = project
+            .add_package_request(
+                &collection,
+                &[new_base, package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        let window = result
+            .resolution()
+            .unwrap()
+            .unity_compatibility_window()
+            .unwrap();
+        assert_eq!(
+            window,
+            UnityVersionRange::new(
+                Some(UnityVersion::new(2022, 3, 0, ReleaseType::Alpha, 0)),
+                None
+            )
+        );
+
+        // VRCSDK 3.4.x is only for unity 2019
+        let old_base = collection.get_package("com.vrchat.base", Version::new(3, 4, 0));
+        let result = project
+            .add_package_request(
+                &collection,
+                &[old_base, new_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        // the packages setting the conflicting bounds are reported
+        let offending = result
+            .resolution()
+            .unwrap()
+            .unity_compatibility_window()
+            .unwrap_err();
+        assert_eq!(
+            offending,
+            vec![
+                (
+                    "com.anatawa12.new-package".into(),
+                    UnityVersionRange::new(
+                        Some(UnityVersion::new(2022, 3, 0, ReleaseType::Alpha, 0)),
+                        None
+                    )
+                ),
+                (
+                    "com.vrchat.base".into(),
+                    UnityVersionRange::new(
+                        Some(UnityVersion::new(2019, 0, 0, ReleaseType::Alpha, 0)),
+                        Some(UnityVersion::new(2020, 0, 0, ReleaseType::Alpha, 0))
+                    )
+                ),
+            ]
+        );
+    })
+}
+
+#[test]
+fn trace_requirements_and_prerelease_of_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                "1.1.0-beta.1".parse().unwrap(),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", ">=1.1.0-beta.1"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let resolve = async |options: &ResolutionOptions<'_>| {
+            project
+                .add_package_request_with_options(
+                    &collection,
+                    &[package],
+                    AddPackageOperation::InstallToDependencies,
+                    options,
+                )
+                .await
+                .unwrap()
+        };
+
+        // the diagnostics are not collected by default
+        let result = resolve(&ResolutionOptions::new()).await;
+        assert!(result.resolution().unwrap().diagnostics.is_none());
+
+        let result = resolve(&ResolutionOptions::new().diagnostics(true)).await;
+        let diagnostics = result.resolution().unwrap().diagnostics.as_ref();
+        let trace = &diagnostics.unwrap().trace;
+        assert_eq!(trace.len(), 2);
+
+        let library = &trace["com.anatawa12.library"];
+        assert_eq!(library.version, "1.1.0-beta.1".parse().unwrap());
+        assert_eq!(library.requirements.len(), 1);
+        assert_eq!(library.requirements[0].0.as_ref(), "com.anatawa12.package");
+        assert_eq!(library.requirements[0].1.to_string(), ">=1.1.0-beta.1");
+        assert_eq!(library.prerelease_reason, None);
+
+        let package = &trace["com.anatawa12.package"];
+        assert_eq!(package.version, Version::new(1, 0, 0));
+        // the requested package is not required by other packages
+        assert!(package.requirements.is_empty());
+        assert_eq!(package.prerelease_reason, Some(PrereleaseReason::Requested));
+
+        // why prerelease versions are allowed
+        let options = ResolutionOptions::new()
+            .diagnostics(true)
+            .allow_prerelease(true);
+        let result = resolve(&options).await;
+        let diagnostics = result.resolution().unwrap().diagnostics.as_ref();
+        let trace = &diagnostics.unwrap().trace;
+        assert_eq!(
+            trace["com.anatawa12.library"].prerelease_reason,
+            Some(PrereleaseReason::Global)
+        );
+    })
+}
+
+#[test]
+fn report_install_order() {
+    block_on(async {
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.avatars", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.cycle-a", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.cycle-a", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.cycle-b", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.cycle-b", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.cycle-a", "^1.0.0"),
+            )
+            .build();
+
+        // adding packages fails with cycles, so resolve directly
+        let range = "^1.0.0".parse::<DependencyRange>().unwrap();
+        let options = ResolutionOptions::new();
+        let result = resolve_dependencies(
+            [("com.anatawa12.tool", &range)],
+            &collection,
+            None,
+            &options,
+        )
+        .unwrap();
+
+        // the cycle is broken at the package with the smallest name after other packages
+        assert_eq!(
+            result.install_order(),
+            vec![
+                Box::from("com.vrchat.base"),
+                "com.vrchat.avatars".into(),
+                "com.anatawa12.cycle-a".into(),
+                "com.anatawa12.cycle-b".into(),
+                "com.anatawa12.tool".into(),
+            ]
+        );
+    })
+}
+
+#[test]
+fn resolve_root_dependencies_with_options() {
+    let beta = Version::new_pre(1, 2, 0, "beta.1".parse().unwrap());
+    let collection = PackageCollectionBuilder::new()
+        .add(PackageManifest::new(
+            "com.anatawa12.tool",
+            Version::new(1, 0, 0),
+        ))
+        .add(PackageManifest::new(
+            "com.anatawa12.tool",
+            Version::new(1, 1, 0),
+        ))
+        .add(PackageManifest::new("com.anatawa12.tool", beta.clone()))
+        .build();
+
+    let range = "^1.0.0".parse::<DependencyRange>().unwrap();
+    let resolve = |options: &ResolutionOptions| {
+        let roots = [("com.anatawa12.tool", &range)];
+        let result = resolve_dependencies(roots, &collection, None, options).unwrap();
+        assert_eq!(result.new_packages.len(), 1);
+        result.new_packages[0].version().clone()
+    };
+
+    // the latest stable version is chosen by default
+    assert_eq!(resolve(&ResolutionOptions::new()), Version::new(1, 1, 0));
+    // the options choose the root version like the dependencies of packages
+    let pins = HashMap::from([("com.anatawa12.tool", Version::new(1, 0, 0))]);
+    assert_eq!(
+        resolve(&ResolutionOptions::new().pins(pins)),
+        Version::new(1, 0, 0)
+    );
+    assert_eq!(
+        resolve(&ResolutionOptions::new().allow_prerelease(true)),
+        beta
+    );
+    assert_eq!(
+        resolve(&ResolutionOptions::new().minimal_versions(true)),
+        Version::new(1, 0, 0)
+    );
+}
+
+#[test]
+fn check_packages_compatibility() {
+    let collection = PackageCollectionBuilder::new()
+        .add(PackageManifest::new(
+            "com.vrchat.base",
+            Version::new(1, 1, 0),
+        ))
+        .add(PackageManifest::new(
+            "com.vrchat.base",
+            Version::new(1, 0, 0),
+        ))
+        .add(
+            PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                .add_vpm_dependency("com.vrchat.base", "^1.0.0"),
+        )
+        .add(
+            PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                .add_vpm_dependency("com.vrchat.base", "^1.1.0"),
+        )
+        .build();
+
+    // base is also pinned, so the dependency of avatars is satisfied by the pinned version
+    let report = check_compatibility(
+        &[
+            ("com.vrchat.avatars", Version::new(1, 0, 0)),
+            ("com.vrchat.base", Version::new(1, 0, 0)),
+        ],
+        &collection,
+        None,
+    )
+    .unwrap();
+    assert!(report.compatible);
+    let base = (report.resolution.new_packages.iter())
+        .find(|x| x.name() == "com.vrchat.base")
+        .unwrap();
+    assert_eq!(base.version(), &Version::new(1, 0, 0));
+
+    // tool requires the newer base than the pinned version
+    let report = check_compatibility(
+        &[
+            ("com.anatawa12.tool", Version::new(1, 0, 0)),
+            ("com.vrchat.base", Version::new(1, 0, 0)),
+        ],
+        &collection,
+        None,
+    )
+    .unwrap();
+    assert!(!report.compatible);
+    let conflicts = &report.resolution.conflicts["com.vrchat.base"];
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].package.as_ref(), "com.anatawa12.tool");
+
+    // the versions must exist in the collection
+    let err = check_compatibility(
+        &[("com.vrchat.avatars", Version::new(2, 0, 0))],
+        &collection,
+        None,
+    )
+    .unwrap_err();
+    assert!(matches!(
+        err,
+        ResolvePackageErr::DependenciesNotFound { .. }
+    ));
+}
+
+#[test]
+fn report_minimum_unity_version() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new("com.vrchat.base", Version::new(3, 5, 0)).with_unity(2022, 3))
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .with_unity(2019, 4),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.no-unity",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let base = collection.get_package("com.vrchat.base", Version::new(3, 5, 0));
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let no_unity = collection.get_package("com.anatawa12.no-unity", Version::new(1, 0, 0));
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package, base, no_unity],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.resolution().unwrap().minimum_unity_version(),
+            Some((
+                UnityVersion::new(2022, 3, 0, ReleaseType::Alpha, 0),
+                "com.vrchat.base"
+            ))
+        );
+
+        assert_eq!(minimum_unity_version([no_unity]), None);
+    })
+}
+
+// endregion
+
+// region reproducibility
+
+#[test]
+fn report_arbitrary_choice_among_same_version() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "^1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().detect_arbitrary_choices(true),
+            )
+            .await
+            .unwrap();
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(resolution.is_reproducible(), Some(false));
+        assert_eq!(
+            resolution.arbitrary_choices,
+            vec![Box::<str>::from("com.vrchat.base")]
+        );
+
+        // the candidates are not looked up unless requested
+        let result = project
+            .add_package_request(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        let resolution = result.resolution().unwrap();
+        assert!(resolution.arbitrary_choices.is_empty());
+        assert_eq!(resolution.is_reproducible(), None);
+    })
+}
+
+#[test]
+fn resolve_minimal_versions() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_locked("com.anatawa12.installed", Version::new(1, 2, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 2, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.installed",
+                Version::new(1, 2, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.installed",
+                Version::new(1, 1, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", ">=1.1.0")
+                    .add_vpm_dependency("com.anatawa12.installed", ">=1.1.0"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let library = collection.get_package("com.anatawa12.library", Version::new(1, 1, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().minimal_versions(true),
+            )
+            .await
+            .unwrap();
+
+        // the installed package already satisfies the requirement
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &package);
+        assert_installing_to_locked_only(&result, &library);
+    })
+}
+
+#[test]
+fn resolve_versions_published_before() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let at = |secs| SystemTime::UNIX_EPOCH + Duration::from_secs(secs);
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 2, 0))
+                    .with_published_at(at(3000)),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 1, 0))
+                    .with_published_at(at(1000)),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 0, 0))
+                    .with_published_at(at(500)),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let library = collection.get_package("com.anatawa12.library", Version::new(1, 1, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().published_before(at(2000)),
+            )
+            .await
+            .unwrap();
+
+        // the newest version published before the time is chosen
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &package);
+        assert_installing_to_locked_only(&result, &library);
+    })
+}
+
+#[test]
+fn prefer_non_deprecated_versions() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 2, 0))
+                    .with_deprecated("broken build, use 1.1.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 1, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.newer-library", Version::new(2, 0, 0))
+                    .with_deprecated("use com.anatawa12.library instead"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.newer-library",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.newer-library", "^2.0.0"),
+            )
+            .build();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let library = collection.get_package("com.anatawa12.library", Version::new(1, 1, 0));
+        let newer_library =
+            collection.get_package("com.anatawa12.newer-library", Version::new(2, 0, 0));
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+
+        // the deprecated version is installed only if no other version satisfies the range
+        assert_eq!(result.package_changes().len(), 3);
+        assert_installing_to_both(&result, &package);
+        assert_installing_to_locked_only(&result, &library);
+        assert_installing_to_locked_only(&result, &newer_library);
+
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.deprecated_selected,
+            vec![(
+                "com.anatawa12.newer-library".into(),
+                Version::new(2, 0, 0),
+                "use com.anatawa12.library instead".into()
+            )]
+        );
+    })
+}
+
+// endregion
+
+// region must include
+
+#[test]
+fn must_include_newest_without_requirements() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 1, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().must_include("com.vrchat.base"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 1);
+        assert_installing_to_locked_only(&result, &base_package);
+    })
+}
+
+#[test]
+fn must_include_tightened_by_requirements() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.vrchat.base", "~1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let base_package = collection.get_package("com.vrchat.base", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().must_include("com.vrchat.base"),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_eq!(result.conflicts().len(), 0);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &base_package);
+    })
+}
+
+// endregion
+
+// region recommended
+
+#[test]
+fn include_recommended_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                    .add_recommended_package("com.anatawa12.gists", "^1.0.0")
+                    .add_recommended_package("com.anatawa12.broken", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.gists",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.broken", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.missing", "^1.0.0"),
+            )
+            .build();
+
+        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
+        let gists_package = collection.get_package("com.anatawa12.gists", Version::new(1, 0, 0));
+
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[avatars_package],
                 AddPackageOperation::InstallToDependencies,
-                false,
+                &ResolutionOptions::new().include_recommended(true),
             )
             .await
             .unwrap();
 
-        assert_eq!(resolve.package_changes().len(), 1);
-        assert_eq!(resolve.remove_legacy_folders().len(), 0);
-        assert_eq!(resolve.remove_legacy_files().len(), 0);
-        assert_eq!(resolve.conflicts().len(), 0);
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &avatars_package);
+        assert_installing_to_locked_only(&result, &gists_package);
 
-        assert_installing_to_both(&resolve, &tool);
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.added_recommendations,
+            vec![Box::<str>::from("com.anatawa12.gists")]
+        );
+        assert_eq!(
+            resolution.skipped_recommendations,
+            vec![Box::<str>::from("com.anatawa12.broken")]
+        );
     })
 }
 
+// endregion
+
+// region optional dependencies
+
 #[test]
-fn conflict_requirements_of_installed_and_installing_related_to_dependencies() {
+fn activate_optional_dependencies_on_present_packages() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_dependency_range("com.vrchat.avatars", "~3.5.x")
-            .add_locked(
-                "com.vrchat.avatars",
-                Version::new(3, 4, 2),
-                &[("com.vrchat.base", "3.4.2")],
-            )
-            .add_locked("com.vrchat.base", Version::new(3, 4, 2), &[])
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked("com.vrchat.avatars", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
             .add(
-                PackageManifest::new("com.vrchat.base", Version::new(3, 4, 2))
-                    .add_vpm_dependency("com.vrchat.avatars", "3.4.2"),
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_optional_dependency("com.vrchat.avatars", "^1.1.0")
+                    .add_vpm_optional_dependency("com.vrchat.worlds", "^1.0.0"),
             )
             .add(PackageManifest::new(
-                "com.vrchat.base",
-                Version::new(3, 4, 2),
+                "com.vrchat.avatars",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.worlds",
+                Version::new(1, 0, 0),
             ))
-            .add(
-                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
-                    .add_vpm_dependency("com.vrchat.avatars", "^3.3.0"),
-            )
             .build();
 
         let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
-
-        let resolve = project
+        let result = project
             .add_package_request(
                 &collection,
                 &[tool],
@@ -1167,212 +6703,374 @@ fn conflict_requirements_of_installed_and_installing_related_to_dependencies() {
             .await
             .unwrap();
 
-        assert_eq!(resolve.package_changes().len(), 1);
-        assert_eq!(resolve.remove_legacy_folders().len(), 0);
-        assert_eq!(resolve.remove_legacy_files().len(), 0);
-        assert_eq!(resolve.conflicts().len(), 0);
+        // the locked avatars package is upgraded for the optional dependency
+        assert_installing_to_both(&result, &tool);
+        assert_installing_to_locked_only(
+            &result,
+            &collection.get_package("com.vrchat.avatars", Version::new(1, 1, 0)),
+        );
+        // the optional dependency on the package not in the project is skipped
+        assert!(!result.package_changes().contains_key("com.vrchat.worlds"));
 
-        assert_installing_to_both(&resolve, &tool);
+        let resolution = result.resolution().unwrap();
+        assert_eq!(
+            resolution.activated_optionals,
+            vec![("com.anatawa12.tool".into(), "com.vrchat.avatars".into())]
+        );
+        assert!(resolution.conflicts.is_empty());
     })
 }
 
 // endregion
 
-// region rollback on error
+// region git packages
 
 #[test]
-fn no_temp_folder_after_add() {
+fn git_package_shadows_repository_version() {
     block_on(async {
-        let mut project = VirtualProjectBuilder::new()
-            .add_dependency_range("com.vrchat.avatars", "~3.5.x")
-            .add_locked(
-                "com.vrchat.avatars",
-                Version::new(3, 4, 2),
-                &[("com.vrchat.base", "3.4.2")],
-            )
-            .add_locked("com.vrchat.base", Version::new(3, 4, 2), &[])
-            .add_file(
-                "Packages/com.vrchat.avatars/package.json",
-                r#"{"name":"com.vrchat.avatars","version":"3.4.2"}"#,
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let git_packages = [GitPackage::new(
+            "https://example.com/package.git".parse().unwrap(),
+            "main",
+            "0123456789abcdef0123456789abcdef01234567",
+            Path::new("Repos/git/package"),
+            PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+        )];
+        let collection = GitPackageCollection::new(&collection, &git_packages);
+
+        let package = collection
+            .find_package_by_name(
+                "com.anatawa12.package",
+                VersionSelector::specific_version(&Version::new(1, 0, 0)),
             )
-            .add_file("Packages/com.vrchat.avatars/content.txt", "text")
-            .build()
-            .await
             .unwrap();
+        assert_eq!(
+            package.git_source().map(|x| x.commit()),
+            Some("0123456789abcdef0123456789abcdef01234567")
+        );
+        assert_eq!(collection.find_packages("com.anatawa12.package").count(), 1);
 
-        let env_vfs = VirtualFileSystem::new();
-        let env = VirtualEnvironment::new(env_vfs);
-
-        let resolve = project
-            .remove_request(&["com.vrchat.avatars"])
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
             .await
             .unwrap();
 
-        project.apply_pending_changes(&env, resolve).await.unwrap();
-
-        assert_eq!(
-            project
-                .io()
-                .metadata("Temp".as_ref())
-                .await
-                .unwrap_err()
-                .kind(),
-            io::ErrorKind::NotFound
-        );
-        assert_eq!(
-            project
-                .io()
-                .metadata("Packages/com.vrchat.avatars".as_ref())
-                .await
-                .unwrap_err()
-                .kind(),
-            io::ErrorKind::NotFound
-        );
+        assert_installing_to_both(&result, &package);
+        let library = collection
+            .find_package_by_name(
+                "com.anatawa12.library",
+                VersionSelector::specific_version(&Version::new(1, 0, 0)),
+            )
+            .unwrap();
+        assert_installing_to_locked_only(&result, &library);
     })
 }
 
 #[test]
-fn locked_in_package_folder() {
+fn install_git_package() {
     block_on(async {
-        let mut project = VirtualProjectBuilder::new()
-            .add_dependency_range("com.vrchat.avatars", "~3.5.x")
-            .add_locked(
-                "com.vrchat.avatars",
-                Version::new(3, 4, 2),
-                &[("com.vrchat.base", "3.4.2")],
+        let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let env = VirtualFileSystem::new();
+        env.add_file(
+            "Repos/git/package/package.json".as_ref(),
+            br#"{"name":"com.anatawa12.package","version":"1.0.0"}"#,
+        )
+        .await
+        .unwrap();
+        env.add_file("Repos/git/package/Runtime/code.cs".as_ref(), b"code")
+            .await
+            .unwrap();
+        env.add_file("Repos/git/package/.git/HEAD".as_ref(), b"ref")
+            .await
+            .unwrap();
+
+        let base = PackageCollectionBuilder::new().build();
+        let git_packages = [GitPackage::new(
+            "https://example.com/package.git".parse().unwrap(),
+            "main",
+            "0123456789abcdef0123456789abcdef01234567",
+            Path::new("Repos/git/package"),
+            PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0)),
+        )];
+        let collection = GitPackageCollection::new(&base, &git_packages);
+        let package = collection
+            .find_package_by_name(
+                "com.anatawa12.package",
+                VersionSelector::specific_version(&Version::new(1, 0, 0)),
             )
-            .add_locked("com.vrchat.base", Version::new(3, 4, 2), &[])
-            .add_file(
-                "Packages/com.vrchat.avatars/package.json",
-                r#"{"name":"com.vrchat.avatars","version":"3.4.2"}"#,
+            .unwrap();
+
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
             )
-            .add_file("Packages/com.vrchat.avatars/content.txt", "text")
-            .build()
             .await
             .unwrap();
-
+        let installer = vrc_get_vpm::environment::PackageInstaller::new(&env, None::<&Infallible>);
         project
-            .io()
-            .deny_deletion("Packages/com.vrchat.avatars/content.txt".as_ref())
+            .apply_pending_changes(&installer, result)
             .await
             .unwrap();
 
-        let env_vfs = VirtualFileSystem::new();
-        let env = VirtualEnvironment::new(env_vfs);
-
-        let resolve = project
-            .remove_request(&["com.vrchat.avatars"])
+        let io = project.io();
+        io.metadata("Packages/com.anatawa12.package/package.json".as_ref())
             .await
             .unwrap();
-
-        project.apply_pending_changes(&env, resolve).await.unwrap();
-
+        io.metadata("Packages/com.anatawa12.package/Runtime/code.cs".as_ref())
+            .await
+            .unwrap();
+        // the git metadata is not copied to the project
         assert_eq!(
-            project
-                .io()
-                .metadata("Packages/com.vrchat.avatars".as_ref())
+            io.metadata("Packages/com.anatawa12.package/.git".as_ref())
                 .await
                 .unwrap_err()
                 .kind(),
             io::ErrorKind::NotFound
         );
-        project.io().metadata("Temp".as_ref()).await.unwrap();
-        project
-            .io()
-            .metadata("Temp/vrc-get".as_ref())
+
+        // the commit is recorded to the lockfile and read back
+        let mut manifest = String::new();
+        (io.open("Packages/vpm-manifest.json".as_ref())
+            .await
+            .unwrap())
+        .read_to_string(&mut manifest)
+        .await
+        .unwrap();
+        assert!(
+            manifest
+                .contains(r#""vrc-get-git-commit": "0123456789abcdef0123456789abcdef01234567""#)
+        );
+
+        let reloaded = VirtualProjectBuilder::new()
+            .add_file("Packages/vpm-manifest.json", manifest.as_str())
+            .build()
             .await
             .unwrap();
+        let locked = reloaded.get_locked("com.anatawa12.package").unwrap();
+        assert_eq!(
+            locked.git_commit(),
+            Some("0123456789abcdef0123456789abcdef01234567")
+        );
+        assert_eq!(locked.version(), &Version::new(1, 0, 0));
     })
 }
 
 // endregion
 
-// region unlocked
-
 #[test]
-fn install_depends_on_unlocked() {
+fn suggest_fixes_for_conflicts() {
     block_on(async {
         let project = VirtualProjectBuilder::new()
-            .add_file(
-                "Packages/base/package.json",
-                r#"{"name":"com.vrchat.base","version":"1.0.0"}"#,
+            .add_dependency("com.anatawa12.other", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.other",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "^1.0.0")],
             )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
             .build()
             .await
             .unwrap();
 
         let collection = PackageCollectionBuilder::new()
             .add(
-                PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
-                    .add_vpm_dependency("com.vrchat.base", "1.0.0"),
-            )
-            .build();
-
-        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 0, 0));
-
-        let result = project
-            .add_package_request(
-                &collection,
-                &[avatars_package],
-                AddPackageOperation::InstallToDependencies,
-                false,
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^2.0.0"),
             )
-            .await
-            .unwrap();
-
-        assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.remove_legacy_folders().len(), 0);
-        assert_eq!(result.remove_legacy_files().len(), 0);
-        assert_eq!(result.conflicts().len(), 0);
-
-        assert_installing_to_both(&result, &avatars_package);
-    })
-}
-
-#[test]
-fn install_conflict_with_unlocked() {
-    block_on(async {
-        let project = VirtualProjectBuilder::new()
-            .add_file(
-                "Packages/base/package.json",
-                r#"{"name":"com.vrchat.base","version":"1.0.0"}"#,
+            .add(
+                PackageManifest::new("com.anatawa12.other", Version::new(1, 2, 0))
+                    .add_vpm_dependency("com.anatawa12.library", ">=1.0.0 <3.0.0"),
             )
-            .build()
-            .await
-            .unwrap();
-
-        let collection = PackageCollectionBuilder::new()
             .add(
-                PackageManifest::new("com.vrchat.avatars", Version::new(1, 1, 0))
-                    .add_vpm_dependency("com.vrchat.base", "1.1.0"),
+                PackageManifest::new("com.anatawa12.other", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
             )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
             .build();
 
-        let avatars_package = collection.get_package("com.vrchat.avatars", Version::new(1, 1, 0));
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
 
         let result = project
             .add_package_request(
                 &collection,
-                &[avatars_package],
+                &[package],
                 AddPackageOperation::InstallToDependencies,
                 false,
             )
             .await
             .unwrap();
 
-        assert_eq!(result.package_changes().len(), 1);
-        assert_eq!(result.remove_legacy_folders().len(), 0);
-        assert_eq!(result.remove_legacy_files().len(), 0);
-        assert_eq!(result.conflicts().len(), 1);
-
-        assert_installing_to_both(&result, &avatars_package);
+        let resolution = result.resolution().unwrap();
+        assert!(resolution.conflicts.contains_key("com.anatawa12.library"));
 
-        let base_conflict = result.conflicts().get("com.vrchat.base").unwrap();
+        let suggestions = resolution.suggest_conflict_fixes(&collection);
         assert_eq!(
-            base_conflict.conflicting_packages(),
-            &["com.vrchat.avatars".into()]
-        )
+            suggestions,
+            vec![ConflictSuggestion::ChangeRequirer {
+                package: "com.anatawa12.other".into(),
+                version: Version::new(1, 2, 0),
+                dependency: "com.anatawa12.library".into(),
+                dependency_version: Version::new(2, 0, 0),
+            }]
+        );
+        assert_eq!(
+            suggestions[0].to_string(),
+            "change com.anatawa12.other to 1.2.0 to use com.anatawa12.library 2.0.0"
+        );
     })
 }
 
-// endregion
+#[test]
+fn allow_prerelease_per_repository() {
+    struct RepositoryCollection(Vec<LocalCachedRepository>);
+
+    impl vrc_get_vpm::PackageCollection for RepositoryCollection {
+        fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+            self.0.iter().flat_map(|repo| repo.all_packages())
+        }
+
+        fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+            self.get_all_packages()
+                .filter(move |pkg| pkg.name() == package)
+        }
+    }
+
+    fn repository(packages: serde_json::Value) -> LocalCachedRepository {
+        let serde_json::Value::Object(json) = packages else {
+            unreachable!()
+        };
+        LocalCachedRepository::new(RemoteRepository::parse_lazy(json).unwrap(), IndexMap::new())
+    }
+
+    fn stable_and_beta(package: &str) -> serde_json::Value {
+        serde_json::json!({
+            "versions": {
+                "1.0.0": { "name": package, "version": "1.0.0" },
+                "1.1.0-beta.1": { "name": package, "version": "1.1.0-beta.1" },
+            },
+        })
+    }
+
+    let tool = serde_json::json!({
+        "name": "com.anatawa12.tool",
+        "version": "1.0.0",
+        "vpmDependencies": {
+            "com.anatawa12.library": "^1.0.0",
+            "com.anatawa12.helper": "^1.0.0",
+        },
+    });
+    let collection = RepositoryCollection(vec![
+        repository(serde_json::json!({
+            "id": "com.example.official",
+            "packages": {
+                "com.anatawa12.tool": { "versions": { "1.0.0": tool } },
+                "com.anatawa12.library": stable_and_beta("com.anatawa12.library"),
+            },
+        })),
+        repository(serde_json::json!({
+            "id": "com.example.community",
+            "packages": {
+                "com.anatawa12.helper": stable_and_beta("com.anatawa12.helper"),
+            },
+        })),
+    ]);
+
+    let range = "^1.0.0".parse::<DependencyRange>().unwrap();
+    let roots = [("com.anatawa12.tool", &range)];
+    let resolve = |options: &ResolutionOptions| {
+        let result = resolve_dependencies(roots, &collection, None, options).unwrap();
+        assert!(result.conflicts.is_empty(), "{:?}", result.conflicts);
+        let mut installed = (result.new_packages.iter())
+            .map(|x| (x.name(), x.version().to_string()))
+            .collect::<Vec<_>>();
+        installed.sort();
+        installed
+    };
+    let beta = "1.1.0-beta.1".to_string();
+    let stable = "1.0.0".to_string();
+
+    // prereleases are not chosen by default
+    assert_eq!(
+        resolve(&ResolutionOptions::new()),
+        vec![
+            ("com.anatawa12.helper", stable.clone()),
+            ("com.anatawa12.library", stable.clone()),
+            ("com.anatawa12.tool", stable.clone()),
+        ]
+    );
+
+    // prereleases only from the official repository are chosen
+    let options = ResolutionOptions::new()
+        .allow_prerelease_from("com.example.official")
+        .diagnostics(true);
+    assert_eq!(
+        resolve(&options),
+        vec![
+            ("com.anatawa12.helper", stable.clone()),
+            ("com.anatawa12.library", beta.clone()),
+            ("com.anatawa12.tool", stable.clone()),
+        ]
+    );
+    let result = resolve_dependencies(roots, &collection, None, &options).unwrap();
+    assert_eq!(
+        result.diagnostics.as_ref().unwrap().trace["com.anatawa12.library"].prerelease_reason,
+        Some(PrereleaseReason::Repository)
+    );
+
+    // the global allowance also applies to other repositories
+    assert_eq!(
+        resolve(
+            &ResolutionOptions::new()
+                .allow_prerelease_from("com.example.official")
+                .allow_prerelease(true)
+        ),
+        vec![
+            ("com.anatawa12.helper", beta.clone()),
+            ("com.anatawa12.library", beta.clone()),
+            ("com.anatawa12.tool", stable.clone()),
+        ]
+    );
+
+    // the override denying prereleases takes precedence
+    assert_eq!(
+        resolve(
+            &ResolutionOptions::new()
+                .allow_prerelease_from("com.example.official")
+                .prerelease_override("com.anatawa12.library", false)
+        ),
+        vec![
+            ("com.anatawa12.helper", stable.clone()),
+            ("com.anatawa12.library", stable.clone()),
+            ("com.anatawa12.tool", stable.clone()),
+        ]
+    );
+}
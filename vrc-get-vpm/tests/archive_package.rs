@@ -0,0 +1,212 @@
+#![cfg(feature = "tokio")]
+
+use common::*;
+use futures::AsyncReadExt;
+use std::convert::Infallible;
+use std::io;
+use std::path::Path;
+use url::Url;
+use vrc_get_vpm::environment::{ArchivePackage, PackageInstaller};
+use vrc_get_vpm::io::{DefaultEnvironmentIo, IoTrait};
+use vrc_get_vpm::unity_project::AddPackageOperation;
+use vrc_get_vpm::version::Version;
+
+mod common;
+
+const PACKAGE_JSON: &[u8] = br#"{"name":"com.anatawa12.package","version":"1.0.0"}"#;
+
+async fn write_archive(path: &Path, files: &[(&str, &[u8])]) {
+    tokio::fs::write(path, build_zip(files).await)
+        .await
+        .unwrap();
+}
+
+fn build_tarball(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    let mut builder = tar::Builder::new(encoder);
+    for &(name, content) in files {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        builder.append_data(&mut header, name, content).unwrap();
+    }
+    builder.into_inner().unwrap().finish().unwrap()
+}
+
+#[test]
+fn install_archive_package() {
+    let root = temp_dir("install_archive_package");
+    let archive = root.join("package.zip");
+    let io = DefaultEnvironmentIo::new(root.join("env").into_boxed_path());
+
+    block_on_tokio(async {
+        write_archive(
+            &archive,
+            &[("package.json", PACKAGE_JSON), ("Runtime/code.cs", b"code")],
+        )
+        .await;
+
+        let archive_package = ArchivePackage::load(&io, &archive).await.unwrap();
+        let package = archive_package.package_info();
+        assert_eq!(package.name(), "com.anatawa12.package");
+        assert_eq!(package.version(), &Version::new(1, 0, 0));
+
+        let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+        let collection = PackageCollectionBuilder::new().build();
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        let installer = PackageInstaller::new(&io, None::<&Infallible>);
+        project
+            .apply_pending_changes(&installer, result)
+            .await
+            .unwrap();
+
+        let mut code = String::new();
+        (project
+            .io()
+            .open("Packages/com.anatawa12.package/Runtime/code.cs".as_ref())
+            .await
+            .unwrap())
+        .read_to_string(&mut code)
+        .await
+        .unwrap();
+        assert_eq!(code, "code");
+
+        // the lockfile points at the archive on the local file system
+        let url = Url::from_file_path(&archive).unwrap();
+        let locked = project.get_locked("com.anatawa12.package").unwrap();
+        assert_eq!(locked.version(), &Version::new(1, 0, 0));
+        assert_eq!(locked.repository_url(), Some(url.as_str()));
+    })
+}
+
+#[test]
+fn install_tarball_package() {
+    let root = temp_dir("install_tarball_package");
+    let archive = root.join("package.tgz");
+    let io = DefaultEnvironmentIo::new(root.join("env").into_boxed_path());
+
+    block_on_tokio(async {
+        let tarball = build_tarball(&[
+            ("package/package.json", PACKAGE_JSON),
+            ("package/Runtime/code.cs", b"code"),
+        ]);
+        tokio::fs::write(&archive, tarball).await.unwrap();
+
+        let archive_package = ArchivePackage::load(&io, &archive).await.unwrap();
+        let package = archive_package.package_info();
+        assert_eq!(package.name(), "com.anatawa12.package");
+        assert_eq!(package.version(), &Version::new(1, 0, 0));
+
+        let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+        let collection = PackageCollectionBuilder::new().build();
+        let result = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        let installer = PackageInstaller::new(&io, None::<&Infallible>);
+        project
+            .apply_pending_changes(&installer, result)
+            .await
+            .unwrap();
+
+        // the top-level folder of the tarball is stripped
+        assert_eq!(
+            read_to_string(
+                project.io(),
+                "Packages/com.anatawa12.package/Runtime/code.cs"
+            )
+            .await,
+            "code"
+        );
+        assert_eq!(
+            read_to_string(project.io(), "Packages/com.anatawa12.package/package.json")
+                .await
+                .as_bytes(),
+            PACKAGE_JSON
+        );
+
+        let url = Url::from_file_path(&archive).unwrap();
+        let locked = project.get_locked("com.anatawa12.package").unwrap();
+        assert_eq!(locked.repository_url(), Some(url.as_str()));
+    })
+}
+
+#[test]
+fn load_yanked_archive_package() {
+    let root = temp_dir("load_yanked_archive_package");
+    let archive = root.join("package.zip");
+    let io = DefaultEnvironmentIo::new(root.join("env").into_boxed_path());
+
+    block_on_tokio(async {
+        write_archive(
+            &archive,
+            &[(
+                "package.json",
+                br#"{"name":"com.anatawa12.package","version":"1.0.0","vrc-get":{"yanked":true}}"#,
+            )],
+        )
+        .await;
+
+        let archive_package = ArchivePackage::load(&io, &archive).await.unwrap();
+        let package = archive_package.package_info();
+        assert_eq!(package.name(), "com.anatawa12.package");
+        assert!(package.package_json().is_yanked());
+    })
+}
+
+#[test]
+fn reject_invalid_archive_package() {
+    let root = temp_dir("reject_invalid_archive_package");
+    let io = DefaultEnvironmentIo::new(root.join("env").into_boxed_path());
+
+    block_on_tokio(async {
+        let missing = root.join("missing.zip");
+        write_archive(&missing, &[("Runtime/code.cs", b"code")]).await;
+        let err = ArchivePackage::load(&io, &missing).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        // package.json must be at the root of the archive
+        let nested = root.join("nested.zip");
+        write_archive(
+            &nested,
+            &[("com.anatawa12.package/package.json", PACKAGE_JSON)],
+        )
+        .await;
+        let err = ArchivePackage::load(&io, &nested).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let broken = root.join("broken.tgz");
+        tokio::fs::write(&broken, b"tarball").await.unwrap();
+        ArchivePackage::load(&io, &broken).await.unwrap_err();
+
+        // package.json must be in the top-level folder of the tarball
+        let flat = root.join("flat.tar.gz");
+        tokio::fs::write(&flat, build_tarball(&[("package.json", PACKAGE_JSON)]))
+            .await
+            .unwrap();
+        let err = ArchivePackage::load(&io, &flat).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+
+        let no_version = root.join("no_version.zip");
+        write_archive(
+            &no_version,
+            &[("package.json", br#"{"name":"com.anatawa12.package"}"#)],
+        )
+        .await;
+        let err = ArchivePackage::load(&io, &no_version).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    })
+}
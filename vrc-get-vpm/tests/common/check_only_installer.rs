@@ -0,0 +1,27 @@
+use std::io;
+use vrc_get_vpm::io::ProjectIo;
+use vrc_get_vpm::{AbortCheck, PackageInfo, PackageInstaller};
+
+/// The installer only checking the packages, for applying changes only to the lockfile.
+///
+/// Checking the packages with the names fails as if they are not available,
+/// and installing any package panics.
+pub struct CheckOnlyInstaller<'a>(pub &'a [&'a str]);
+
+impl PackageInstaller for CheckOnlyInstaller<'_> {
+    async fn install_package(
+        &self,
+        _: &impl ProjectIo,
+        package: PackageInfo<'_>,
+        _: &AbortCheck,
+    ) -> io::Result<()> {
+        panic!("{} should not be installed", package.name())
+    }
+
+    async fn check_package(&self, package: PackageInfo<'_>) -> io::Result<()> {
+        if self.0.contains(&package.name()) {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "404 Not Found"));
+        }
+        Ok(())
+    }
+}
@@ -2,17 +2,20 @@
 #![allow(dead_code)]
 #![allow(unused_imports)]
 
+mod check_only_installer;
 mod package_collection;
 mod virtual_environment;
 mod virtual_file_system;
 mod virtual_project_builder;
 
+pub use check_only_installer::CheckOnlyInstaller;
 pub use package_collection::PackageCollection;
 pub use package_collection::PackageCollectionBuilder;
 pub use virtual_environment::VirtualEnvironment;
 pub use virtual_file_system::VirtualFileSystem;
 pub use virtual_project_builder::VirtualProjectBuilder;
 
+use std::path::{Path, PathBuf};
 use vrc_get_vpm::PackageInfo;
 use vrc_get_vpm::unity_project::PendingProjectChanges;
 use vrc_get_vpm::unity_project::pending_project_changes::RemoveReason;
@@ -95,3 +98,45 @@ pub fn assert_installing_to_dependencies_only(
         .expect("not installing to dependencies");
     assert_eq!(base_range, &DependencyRange::version(version));
 }
+
+/// Creates the zip file with the files for installing packages from zip files.
+pub async fn build_zip(files: &[(&str, &[u8])]) -> Vec<u8> {
+    use async_zip::base::write::ZipFileWriter;
+    use async_zip::{Compression, ZipEntryBuilder};
+
+    let mut writer = ZipFileWriter::new(Vec::<u8>::new());
+    for &(name, content) in files {
+        let entry = ZipEntryBuilder::new(name.to_owned().into(), Compression::Stored);
+        writer.write_entry_whole(entry, content).await.unwrap();
+    }
+    writer.close().await.unwrap()
+}
+
+/// Reads the whole file at the path as a string.
+pub async fn read_to_string(io: &impl vrc_get_vpm::io::IoTrait, path: &str) -> String {
+    use futures::AsyncReadExt;
+    let mut content = String::new();
+    (io.open(path.as_ref()).await.unwrap())
+        .read_to_string(&mut content)
+        .await
+        .unwrap();
+    content
+}
+
+/// Runs the future on the tokio runtime for tests with the real file system.
+#[cfg(feature = "tokio")]
+pub fn block_on_tokio<F: Future>(future: F) -> F::Output {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap()
+        .block_on(future)
+}
+
+/// Creates the empty temporary directory for tests with the real file system.
+pub fn temp_dir(name: &str) -> PathBuf {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
@@ -52,7 +52,26 @@ impl vrc_get_vpm::PackageCollection for PackageCollection {
         name: &str,
         version: VersionSelector,
     ) -> Option<PackageInfo<'_>> {
+        if let Some(preferred) = self
+            .find_packages(name)
+            .filter(|pkg| version.satisfies_package(pkg))
+            .find(|pkg| version.is_preferred(pkg.package_json()))
+        {
+            return Some(preferred);
+        }
+        let deprioritized = |pkg: &PackageInfo| version.is_deprioritized(pkg.package_json());
+        if version.prefers_lowest() {
+            return self
+                .find_packages(name)
+                .filter(|pkg| version.satisfies_package(pkg))
+                .min_by_key(|pkg| (deprioritized(pkg), pkg.version()));
+        }
         self.find_packages(name)
-            .find(|pkg| version.satisfies(pkg.package_json()))
+            .filter(|pkg| version.satisfies_package(pkg))
+            .find(|pkg| !deprioritized(pkg))
+            .or_else(|| {
+                self.find_packages(name)
+                    .find(|pkg| version.satisfies_package(pkg))
+            })
     }
 }
@@ -187,12 +187,18 @@ impl vrc_get_vpm::io::IoTrait for VirtualFileSystem {
 
         let mut to_dir = to_dir.backed.lock().unwrap();
 
-        let to_entry = match to_dir.entry(to_last.to_os_string()) {
-            Entry::Occupied(_) => return err(ErrorKind::AlreadyExists, "file exists"),
-            Entry::Vacant(e) => e,
-        };
-
-        to_entry.insert(original);
+        match to_dir.entry(to_last.to_os_string()) {
+            // like real file systems, renaming a file replaces the existing file
+            Entry::Occupied(mut e) => match (e.get_mut(), original) {
+                (existing @ FileSystemEntry::File(_), original @ FileSystemEntry::File(_)) => {
+                    *existing = original;
+                }
+                _ => return err(ErrorKind::AlreadyExists, "file exists"),
+            },
+            Entry::Vacant(e) => {
+                e.insert(original);
+            }
+        }
 
         drop(to_dir);
 
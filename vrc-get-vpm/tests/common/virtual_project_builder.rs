@@ -107,11 +107,13 @@ impl VirtualProjectBuilder {
         };
 
         let fs = VirtualFileSystem::new();
-        fs.add_file(
-            "Packages/vpm-manifest.json".as_ref(),
-            vpm_manifest.to_string().as_bytes(),
-        )
-        .await?;
+        if !self.files.contains_key("Packages/vpm-manifest.json") {
+            fs.add_file(
+                "Packages/vpm-manifest.json".as_ref(),
+                vpm_manifest.to_string().as_bytes(),
+            )
+            .await?;
+        }
 
         for (name, contents) in &self.files {
             fs.add_file(name.as_ref(), contents.as_bytes()).await?;
@@ -0,0 +1,112 @@
+use crate::common::*;
+use futures::executor::block_on;
+use vrc_get_vpm::PackageManifest;
+use vrc_get_vpm::unity_project::{FrozenPackage, FrozenPackages, InstallFrozenError};
+use vrc_get_vpm::version::Version;
+
+mod common;
+
+#[test]
+fn install_frozen_versions() {
+    block_on(async {
+        let frozen_project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "^1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .build();
+
+        let frozen = frozen_project.freeze(&collection);
+        assert_eq!(
+            frozen,
+            FrozenPackages {
+                dependencies: vec!["com.anatawa12.package".into()],
+                packages: vec![
+                    FrozenPackage {
+                        name: "com.anatawa12.library".into(),
+                        version: Version::new(1, 0, 0),
+                        repository_url: None,
+                    },
+                    FrozenPackage {
+                        name: "com.anatawa12.package".into(),
+                        version: Version::new(1, 0, 0),
+                        repository_url: None,
+                    },
+                ],
+            }
+        );
+
+        let json = serde_json::to_string(&frozen).unwrap();
+        let frozen = serde_json::from_str::<FrozenPackages>(&json).unwrap();
+
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+        let result = project
+            .install_frozen_request(&collection, &frozen)
+            .await
+            .unwrap();
+
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+        let library = collection.get_package("com.anatawa12.library", Version::new(1, 0, 0));
+
+        // the frozen version is installed even if newer one exists
+        assert_eq!(result.package_changes().len(), 2);
+        assert_installing_to_both(&result, &package);
+        assert_installing_to_locked_only(&result, &library);
+    })
+}
+
+#[test]
+fn fail_installing_frozen_versions_not_found() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 1, 0),
+            ))
+            .build();
+
+        let frozen = FrozenPackages {
+            dependencies: vec!["com.anatawa12.package".into()],
+            packages: vec![FrozenPackage {
+                name: "com.anatawa12.package".into(),
+                version: Version::new(1, 0, 0),
+                repository_url: None,
+            }],
+        };
+
+        let err = project
+            .install_frozen_request(&collection, &frozen)
+            .await
+            .unwrap_err();
+
+        let InstallFrozenError::VersionsNotFound { packages } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(
+            packages,
+            vec![("com.anatawa12.package".into(), Version::new(1, 0, 0))]
+        );
+    })
+}
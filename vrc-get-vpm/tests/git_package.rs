@@ -0,0 +1,101 @@
+#![cfg(feature = "tokio")]
+
+use common::*;
+use std::path::Path;
+use std::process::Command;
+use url::Url;
+use vrc_get_vpm::git_package::GitPackage;
+use vrc_get_vpm::io::{DefaultEnvironmentIo, EnvironmentIo};
+use vrc_get_vpm::version::Version;
+
+mod common;
+
+fn git(repository: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repository)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8(output.stdout).unwrap().trim().to_owned()
+}
+
+fn commit_package(repository: &Path, version: &str) -> String {
+    let package = repository.join("Packages/com.anatawa12.package");
+    std::fs::create_dir_all(&package).unwrap();
+    std::fs::write(
+        package.join("package.json"),
+        format!(r#"{{"name":"com.anatawa12.package","version":"{version}"}}"#),
+    )
+    .unwrap();
+    git(repository, &["add", "."]);
+    git(repository, &["commit", "--quiet", "-m", version]);
+    git(repository, &["rev-parse", "HEAD"])
+}
+
+async fn read_manifest(io: &DefaultEnvironmentIo, package: &GitPackage) -> String {
+    let path = io.resolve(&package.path().join("package.json"));
+    tokio::fs::read_to_string(path).await.unwrap()
+}
+
+#[test]
+fn fetch_git_package_at_refs() {
+    let root = temp_dir("fetch_git_package_at_refs");
+    let upstream = root.join("upstream");
+    std::fs::create_dir_all(&upstream).unwrap();
+    git(&upstream, &["init", "--quiet", "--initial-branch", "main"]);
+    let first = commit_package(&upstream, "1.0.0");
+    git(&upstream, &["tag", "v1.0.0"]);
+    let second = commit_package(&upstream, "1.1.0");
+
+    let url = Url::from_directory_path(&upstream).unwrap();
+    let io = DefaultEnvironmentIo::new(root.join("env").into_boxed_path());
+    let subdirectory = Path::new("Packages/com.anatawa12.package");
+
+    block_on_tokio(async {
+        let tagged = GitPackage::fetch(&io, &url, "v1.0.0", Some(subdirectory))
+            .await
+            .unwrap();
+        let main = GitPackage::fetch(&io, &url, "main", Some(subdirectory))
+            .await
+            .unwrap();
+
+        assert_eq!(tagged.commit(), first);
+        assert_eq!(tagged.git_ref(), "v1.0.0");
+        assert_eq!(tagged.manifest().name(), "com.anatawa12.package");
+        assert_eq!(tagged.manifest().version(), &Version::new(1, 0, 0));
+        assert_eq!(main.commit(), second);
+        assert_eq!(main.manifest().version(), &Version::new(1, 1, 0));
+
+        // fetching another ref of the same repository keeps the tree of the previous fetch
+        assert_ne!(tagged.path(), main.path());
+        assert!(read_manifest(&io, &tagged).await.contains("1.0.0"));
+        assert!(read_manifest(&io, &main).await.contains("1.1.0"));
+    })
+}
+
+#[test]
+fn fetch_git_package_rejects_unsafe_input() {
+    let root = temp_dir("fetch_git_package_rejects_unsafe_input");
+    let io = DefaultEnvironmentIo::new(root.join("env").into_boxed_path());
+    let url = Url::from_directory_path(root.join("upstream")).unwrap();
+
+    block_on_tokio(async {
+        let marker = root.join("marker");
+        let upload_pack = format!("--upload-pack=touch {}", marker.display());
+        let err = GitPackage::fetch(&io, &url, &upload_pack, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        assert!(!marker.exists());
+
+        for subdirectory in ["../outside", "/absolute"] {
+            let err = GitPackage::fetch(&io, &url, "main", Some(Path::new(subdirectory)))
+                .await
+                .unwrap_err();
+            assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+        }
+    })
+}
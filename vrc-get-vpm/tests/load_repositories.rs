@@ -0,0 +1,1868 @@
+use crate::common::*;
+use futures::executor::block_on;
+use futures::future;
+use futures::io::Cursor;
+use indexmap::IndexMap;
+use serde_json::json;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+use url::Url;
+use vrc_get_vpm::environment::{PackageCollection, Settings};
+use vrc_get_vpm::repositories_file::RepositoriesFile;
+use vrc_get_vpm::repository::{
+    LoadRepositoryErr, LocalCachedRepository, RemoteRepository, diff_repositories,
+    validate_repository,
+};
+use vrc_get_vpm::version::Version;
+use vrc_get_vpm::{
+    HttpClient, HttpStatusError, PackageCollection as _, PackageInfo, PackageManifest,
+    RetryingClient, UrlRewritingClient, VersionSelector,
+};
+
+mod common;
+
+const DELAY: Duration = Duration::from_millis(300);
+
+/// The http client responding every repository after [`DELAY`]
+struct SlowHttpClient;
+
+impl SlowHttpClient {
+    async fn respond(url: &Url) -> io::Result<Vec<u8>> {
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        std::thread::spawn(move || {
+            std::thread::sleep(DELAY);
+            sender.send(()).ok();
+        });
+        receiver.await.ok();
+
+        if url.host_str() == Some("unreachable.example.com") {
+            return Err(io::Error::new(io::ErrorKind::NotFound, "unreachable"));
+        }
+
+        Ok(serde_json::to_vec(&json!({
+            "name": url.as_str(),
+            "id": url.as_str(),
+            "url": url.as_str(),
+            "packages": {},
+        }))
+        .unwrap())
+    }
+}
+
+impl HttpClient for SlowHttpClient {
+    async fn get(
+        &self,
+        url: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Ok(Cursor::new(Self::respond(url).await?))
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        Ok(Some((Cursor::new(Self::respond(url).await?), None)))
+    }
+}
+
+#[test]
+fn fetch_repositories_concurrently() {
+    block_on(async {
+        let hosts = ["a", "b", "c", "d", "unreachable"];
+        let user_repos = (hosts.iter())
+            .map(|host| {
+                json!({
+                    "localPath": format!("Repos/{host}.json"),
+                    "url": format!("https://{host}.example.com/vpm.json"),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({ "userRepos": user_repos })).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let settings = Settings::load(&vfs).await.unwrap();
+
+        let start = Instant::now();
+        let collection = PackageCollection::load(&settings, &vfs, Some(&SlowHttpClient))
+            .await
+            .unwrap();
+        let elapsed = start.elapsed();
+
+        // official, curated, and 4 user repositories
+        assert_eq!(collection.get_remote().count(), 6);
+        assert!(
+            elapsed < DELAY * 3,
+            "loading repositories took {elapsed:?}, which is not concurrent"
+        );
+
+        let errors = collection.repository_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].url().and_then(Url::host_str),
+            Some("unreachable.example.com")
+        );
+    })
+}
+
+/// The http client responding `304 Not Modified` if the validators match the current version
+struct ValidatingHttpClient {
+    version: std::sync::Mutex<&'static str>,
+    // (etag, last modified) sent with the requests
+    requests: std::sync::Mutex<Vec<(Option<String>, Option<String>)>>,
+}
+
+impl ValidatingHttpClient {
+    const LAST_MODIFIED: &'static str = "Wed, 14 Oct 2026 00:00:00 GMT";
+
+    fn respond(&self) -> Vec<u8> {
+        let version = *self.version.lock().unwrap();
+        let json = json!({
+            "url": "https://validated.example.com/vpm.json",
+            "packages": {
+                "com.example.package": {
+                    "versions": {
+                        version: { "name": "com.example.package", "version": version },
+                    },
+                },
+            },
+        });
+        serde_json::to_vec(&json).unwrap()
+    }
+}
+
+impl HttpClient for ValidatingHttpClient {
+    async fn get(
+        &self,
+        _: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Ok(Cursor::new(self.respond()))
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        Ok(
+            HttpClient::get_with_validators(self, url, headers, current_etag, None)
+                .await?
+                .map(|(stream, etag, _)| (stream, etag)),
+        )
+    }
+
+    async fn get_with_validators(
+        &self,
+        _: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        current_etag: Option<&str>,
+        current_last_modified: Option<&str>,
+    ) -> io::Result<
+        Option<(
+            impl futures::AsyncRead + Send,
+            Option<Box<str>>,
+            Option<Box<str>>,
+        )>,
+    > {
+        self.requests.lock().unwrap().push((
+            current_etag.map(Into::into),
+            current_last_modified.map(Into::into),
+        ));
+        let etag = format!("\"{}\"", self.version.lock().unwrap());
+        if current_etag == Some(etag.as_str()) && current_last_modified == Some(Self::LAST_MODIFIED)
+        {
+            return Ok(None);
+        }
+        Ok(Some((
+            Cursor::new(self.respond()),
+            Some(etag.into()),
+            Some(Self::LAST_MODIFIED.into()),
+        )))
+    }
+}
+
+#[test]
+fn revalidate_cached_repository_with_validators() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [
+                    {
+                        "localPath": "Repos/validated.json",
+                        "url": "https://validated.example.com/vpm.json",
+                    },
+                ],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+        vfs.add_file(
+            "vrc-get/settings.json".as_ref(),
+            br#"{ "ignoreOfficialRepository": true, "ignoreCuratedRepository": true }"#,
+        )
+        .await
+        .unwrap();
+        let http = ValidatingHttpClient {
+            version: std::sync::Mutex::new("1.0.0"),
+            requests: std::sync::Mutex::new(Vec::new()),
+        };
+
+        let load = async || {
+            let settings = Settings::load(&vfs).await.unwrap();
+            let collection = PackageCollection::load(&settings, &vfs, Some(&http))
+                .await
+                .unwrap();
+            let repo = collection.get_remote().next().unwrap();
+            let versions = (repo.get_packages())
+                .flat_map(|x| x.all_versions())
+                .map(|x| x.version().to_string())
+                .collect::<Vec<_>>();
+            let cache = read_to_string(&vfs, "Repos/validated.json").await;
+            let cache = serde_json::from_str::<serde_json::Value>(&cache).unwrap();
+            (versions, cache["vrc-get"].clone())
+        };
+
+        // the validators of the first download are stored with the cache
+        let (versions, validators) = load().await;
+        assert_eq!(versions, vec!["1.0.0"]);
+        assert_eq!(
+            validators,
+            json!({ "etag": "\"1.0.0\"", "lastModified": ValidatingHttpClient::LAST_MODIFIED })
+        );
+
+        // the cache is used if the server responds not modified to the validators
+        let (versions, _) = load().await;
+        assert_eq!(versions, vec!["1.0.0"]);
+
+        // or the repository is downloaded again with the new validators
+        *http.version.lock().unwrap() = "1.1.0";
+        let (versions, validators) = load().await;
+        assert_eq!(versions, vec!["1.1.0"]);
+        assert_eq!(validators["etag"], "\"1.1.0\"");
+
+        let validators = (
+            Some("\"1.0.0\"".to_owned()),
+            Some(ValidatingHttpClient::LAST_MODIFIED.to_owned()),
+        );
+        assert_eq!(
+            *http.requests.lock().unwrap(),
+            vec![(None, None), validators.clone(), validators]
+        );
+    })
+}
+
+/// The http client responding `304 Not Modified` even without validators
+struct AlwaysNotModifiedClient;
+
+impl HttpClient for AlwaysNotModifiedClient {
+    async fn get(
+        &self,
+        _: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Ok(Cursor::new(Vec::new()))
+    }
+
+    async fn get_with_etag(
+        &self,
+        _: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        Ok(None::<(Cursor<Vec<u8>>, _)>)
+    }
+}
+
+#[test]
+fn report_not_modified_without_validators() {
+    block_on(async {
+        let url = Url::parse("https://broken.example.com/vpm.json").unwrap();
+        let err = RemoteRepository::download(&AlwaysNotModifiedClient, &url, &IndexMap::new())
+            .await
+            .unwrap_err();
+        match err {
+            LoadRepositoryErr::Network(err) => assert_eq!(err.kind(), io::ErrorKind::InvalidData),
+            err => panic!("unexpected error: {err:?}"),
+        }
+    })
+}
+
+#[test]
+fn keep_secret_headers_out_of_export() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [{
+                    "localPath": "Repos/private.json",
+                    "url": "https://private.example.com/vpm.json",
+                    "headers": {
+                        "Authorization": "Bearer secret-token",
+                        "X-Channel": "beta",
+                    },
+                }],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let mut settings = Settings::load(&vfs).await.unwrap();
+        let exported = settings.export_repositories();
+        assert!(exported.contains("secret-token"), "{exported}");
+
+        let unknown = Url::parse("https://unknown.example.com/vpm.json").unwrap();
+        assert!(!settings.set_secret_headers(&unknown, vec!["Authorization".into()]));
+
+        let url = Url::parse("https://private.example.com/vpm.json").unwrap();
+        assert!(settings.set_secret_headers(&url, vec!["authorization".into()]));
+        let exported = settings.export_repositories();
+        assert!(!exported.contains("secret-token"), "{exported}");
+        assert!(exported.contains("X-Channel"), "{exported}");
+    })
+}
+
+/// Serves `responses` one by one with the status in the http on the localhost.
+#[cfg(feature = "tokio")]
+fn serve_statuses(responses: &'static [&'static str]) -> Url {
+    use std::io::{BufRead, Write};
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!(
+        "http://{}/vpm.json",
+        listener.local_addr().unwrap()
+    ))
+    .unwrap();
+    std::thread::spawn(move || {
+        for status in responses {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = io::BufReader::new(stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+            )
+            .unwrap();
+        }
+    });
+    url
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+fn map_unauthorized_responses_to_auth_failed() {
+    use vrc_get_vpm::RepositoryAuthFailed;
+
+    let url = serve_statuses(&["401 Unauthorized", "403 Forbidden", "404 Not Found"]);
+    block_on_tokio(async {
+        let client = reqwest::Client::new();
+        for auth_failed in [true, true, false] {
+            let err = HttpClient::get_with_etag(&client, &url, &IndexMap::new(), None)
+                .await
+                .err()
+                .unwrap();
+            let detected = RepositoryAuthFailed::from_io_error(&err);
+            assert_eq!(detected.is_some(), auth_failed, "{err}");
+            if let Some(detected) = detected {
+                assert_eq!(err.kind(), io::ErrorKind::PermissionDenied);
+                assert_eq!(detected.url(), &url);
+            } else {
+                // other error statuses are reported with the status
+                assert_eq!(err.kind(), io::ErrorKind::NotFound);
+                let status = HttpStatusError::from_io_error(&err).unwrap();
+                assert_eq!(status.status(), 404);
+                assert_eq!(status.url(), &url);
+            }
+        }
+    })
+}
+
+#[test]
+fn load_only_overridden_repositories() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [{
+                    "localPath": "Repos/user.json",
+                    "url": "https://user.example.com/vpm.json",
+                }],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let settings = Settings::load(&vfs).await.unwrap();
+        let repositories = RepositoriesFile::parse(
+            "# pinned repositories\nhttps://a.example.com/vpm.json\nhttps://b.example.com/vpm.json\n",
+        );
+
+        let collection = PackageCollection::load_with_repositories(
+            &settings,
+            &vfs,
+            Some(&SlowHttpClient),
+            repositories.parsed(),
+        )
+        .await
+        .unwrap();
+
+        let mut hosts = (collection.get_remote())
+            .filter_map(|x| x.url()?.host_str().map(str::to_owned))
+            .collect::<Vec<_>>();
+        hosts.sort();
+        assert_eq!(hosts, vec!["a.example.com", "b.example.com"]);
+    })
+}
+
+#[test]
+fn read_package_descriptions_from_repository() {
+    let json = json!({
+        "name": "descriptions",
+        "url": "https://descriptions.example.com/vpm.json",
+        "packages": {
+            "com.anatawa12.package": {
+                "versions": {
+                    "1.0.0": {
+                        "name": "com.anatawa12.package",
+                        "version": "1.0.0",
+                        "displayName": "Package",
+                        "description": "The package with descriptions",
+                        "documentationUrl": "https://descriptions.example.com/docs/",
+                    },
+                    "1.1.0": {
+                        "name": "com.anatawa12.package",
+                        "version": "1.1.0",
+                    },
+                },
+            },
+        },
+    });
+    let serde_json::Value::Object(json) = json else {
+        unreachable!()
+    };
+
+    let repo = LocalCachedRepository::new(RemoteRepository::parse(json).unwrap(), IndexMap::new());
+    let package = |version: Version| {
+        (repo.get_packages().flat_map(|x| x.all_versions()))
+            .find(|x| x.version() == &version)
+            .map(|x| PackageInfo::remote(x, &repo))
+            .unwrap()
+    };
+
+    let described = package(Version::new(1, 0, 0));
+    assert_eq!(described.display_name(), Some("Package"));
+    assert_eq!(
+        described.description(),
+        Some("The package with descriptions")
+    );
+    assert_eq!(
+        described.documentation_url().map(Url::as_str),
+        Some("https://descriptions.example.com/docs/")
+    );
+
+    let bare = package(Version::new(1, 1, 0));
+    assert_eq!(bare.display_name(), None);
+    assert_eq!(bare.description(), None);
+    assert_eq!(bare.documentation_url(), None);
+}
+
+#[test]
+fn rewrite_repository_urls() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [
+                    {
+                        "localPath": "Repos/a.json",
+                        "url": "https://a.example.com/vpm.json",
+                    },
+                    {
+                        "localPath": "Repos/b.json",
+                        "url": "https://b.example.com/vpm.json",
+                    },
+                ],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let settings = Settings::load(&vfs).await.unwrap();
+        let repositories = RepositoriesFile::parse(
+            "https://a.example.com/vpm.json\nhttps://b.example.com/vpm.json\n",
+        );
+
+        let http = UrlRewritingClient::new(SlowHttpClient, |url: &str| {
+            url.replace("a.example.com", "mirror.example.com")
+                .replace("b.example.com", "unreachable.example.com")
+        });
+        let collection = PackageCollection::load_with_repositories(
+            &settings,
+            &vfs,
+            Some(&http),
+            repositories.parsed(),
+        )
+        .await
+        .unwrap();
+
+        // the repository is fetched from the mirror
+        let names = (collection.get_remote())
+            .filter_map(|x| x.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["https://mirror.example.com/vpm.json"]);
+
+        let errors = collection.repository_errors();
+        assert_eq!(errors.len(), 1);
+        let message = errors[0].error().to_string();
+        assert!(
+            message.contains("https://unreachable.example.com/vpm.json")
+                && message.contains("https://b.example.com/vpm.json"),
+            "the error should mention both urls: {message}"
+        );
+    })
+}
+
+/// The http client responding the repository with packages of vendor and vrchat for vendor.example.com
+struct VendorHttpClient;
+
+impl VendorHttpClient {
+    fn respond(url: &Url) -> Vec<u8> {
+        let package = |name: &str| {
+            json!({
+                "versions": {
+                    "1.0.0": { "name": name, "version": "1.0.0" },
+                },
+            })
+        };
+        let packages = if url.host_str() == Some("vendor.example.com") {
+            json!({
+                "com.vendor.tool": package("com.vendor.tool"),
+                "com.vrchat.base": package("com.vrchat.base"),
+            })
+        } else {
+            json!({})
+        };
+        serde_json::to_vec(&json!({
+            "name": url.as_str(),
+            "id": url.as_str(),
+            "url": url.as_str(),
+            "packages": packages,
+        }))
+        .unwrap()
+    }
+}
+
+impl HttpClient for VendorHttpClient {
+    async fn get(
+        &self,
+        url: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Ok(Cursor::new(Self::respond(url)))
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        Ok(Some((Cursor::new(Self::respond(url)), None)))
+    }
+}
+
+#[test]
+fn filter_packages_by_allowed_prefixes() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [{
+                    "localPath": "Repos/vendor.json",
+                    "url": "https://vendor.example.com/vpm.json",
+                    "vrc-get-allowed-package-prefixes": ["com.vendor.*"],
+                }],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let settings = Settings::load(&vfs).await.unwrap();
+        assert_eq!(
+            settings.get_user_repos()[0].allowed_package_prefixes(),
+            &["com.vendor.*".into()]
+        );
+
+        let collection = PackageCollection::load(&settings, &vfs, Some(&VendorHttpClient))
+            .await
+            .unwrap();
+
+        // the repository cannot provide packages outside of the prefixes
+        assert_eq!(collection.find_packages("com.vendor.tool").count(), 1);
+        assert_eq!(collection.find_packages("com.vrchat.base").count(), 0);
+        assert!(
+            (collection
+                .find_package_by_name("com.vrchat.base", VersionSelector::latest_for(None, false)))
+            .is_none()
+        );
+
+        // the cache keeps all the packages, so changing the prefixes takes effect immediately
+        let mut settings = settings;
+        let url = Url::parse("https://vendor.example.com/vpm.json").unwrap();
+        assert!(settings.set_allowed_package_prefixes(&url, vec![]));
+        let collection = PackageCollection::load(&settings, &vfs, None::<&VendorHttpClient>)
+            .await
+            .unwrap();
+        assert_eq!(collection.find_packages("com.vrchat.base").count(), 1);
+    })
+}
+
+/// The http client responding the broken repository for the path
+struct BrokenHttpClient;
+
+impl BrokenHttpClient {
+    fn respond(url: &Url) -> io::Result<Vec<u8>> {
+        match url.path() {
+            "/offline.json" => Err(io::Error::new(io::ErrorKind::TimedOut, "timed out")),
+            "/gone.json" => Err(HttpStatusError::new(url.clone(), 410).into_io_error()),
+            "/unavailable.json" => Err(HttpStatusError::new(url.clone(), 503).into_io_error()),
+            "/empty.json" => Ok(b" \n".to_vec()),
+            "/truncated.json" => Ok(b"{\n  \"name\": ".to_vec()),
+            "/array.json" => Ok(b"[]".to_vec()),
+            "/bad-packages.json" => Ok(br#"{ "packages": 1 }"#.to_vec()),
+            _ => Ok(br#"{ "packages": {} }"#.to_vec()),
+        }
+    }
+}
+
+impl HttpClient for BrokenHttpClient {
+    async fn get(
+        &self,
+        url: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Self::respond(url).map(Cursor::new)
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        Self::respond(url).map(|body| Some((Cursor::new(body), None)))
+    }
+}
+
+#[test]
+fn report_repository_load_errors() {
+    block_on(async {
+        let download = async |path: &str| {
+            let url = Url::parse("https://broken.example.com")
+                .unwrap()
+                .join(path)
+                .unwrap();
+            RemoteRepository::download(&BrokenHttpClient, &url, &IndexMap::new()).await
+        };
+
+        let err = download("offline.json").await.unwrap_err();
+        assert!(matches!(err, LoadRepositoryErr::Network(_)));
+        assert!(err.is_transient());
+        // network errors are converted to the original error
+        let io_err = io::Error::from(err);
+        assert_eq!(io_err.kind(), io::ErrorKind::TimedOut);
+        assert!(LoadRepositoryErr::from_io_error(&io_err).is_none());
+
+        // the status is reported by the client
+        let err = download("gone.json").await.unwrap_err();
+        assert!(matches!(err, LoadRepositoryErr::HttpStatus(410)));
+        assert!(!err.is_transient());
+        let err = download("unavailable.json").await.unwrap_err();
+        assert!(matches!(err, LoadRepositoryErr::HttpStatus(503)));
+        assert!(err.is_transient());
+
+        let err = download("empty.json").await.unwrap_err();
+        assert!(matches!(err, LoadRepositoryErr::Empty));
+        assert!(!err.is_transient());
+
+        let err = download("truncated.json").await.unwrap_err();
+        assert!(matches!(
+            err,
+            LoadRepositoryErr::InvalidJson {
+                line: 2,
+                column: 10
+            }
+        ));
+        let io_err = io::Error::from(err);
+        assert_eq!(io_err.kind(), io::ErrorKind::InvalidData);
+        assert!(matches!(
+            LoadRepositoryErr::from_io_error(&io_err),
+            Some(LoadRepositoryErr::InvalidJson { .. })
+        ));
+
+        let err = download("array.json").await.unwrap_err();
+        assert!(matches!(err, LoadRepositoryErr::UnsupportedSchema(_)));
+
+        let err = download("bad-packages.json").await.unwrap_err();
+        assert!(matches!(err, LoadRepositoryErr::UnsupportedSchema(_)));
+
+        assert!(download("vpm.json").await.is_ok());
+    })
+}
+
+#[test]
+fn load_only_cached_repositories_offline() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [
+                    {
+                        "localPath": "Repos/cached.json",
+                        "url": "https://cached.example.com/vpm.json",
+                    },
+                    {
+                        "localPath": "Repos/uncached.json",
+                        "url": "https://uncached.example.com/vpm.json",
+                    },
+                ],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+        vfs.add_file(
+            "vrc-get/settings.json".as_ref(),
+            br#"{ "ignoreOfficialRepository": true, "ignoreCuratedRepository": true }"#,
+        )
+        .await
+        .unwrap();
+        let cached = LocalCachedRepository::new(
+            RemoteRepository::parse(
+                json!({
+                    "url": "https://cached.example.com/vpm.json",
+                    "packages": {},
+                })
+                .as_object()
+                .unwrap()
+                .clone(),
+            )
+            .unwrap(),
+            IndexMap::new(),
+        );
+        vfs.add_file(
+            "Repos/cached.json".as_ref(),
+            &serde_json::to_vec(&cached).unwrap(),
+        )
+        .await
+        .unwrap();
+
+        let settings = Settings::load(&vfs).await.unwrap();
+        let collection = PackageCollection::load(&settings, &vfs, None::<&BrokenHttpClient>)
+            .await
+            .unwrap();
+
+        assert_eq!(collection.get_remote().count(), 1);
+        let errors = collection.repository_errors();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].url().map(Url::as_str),
+            Some("https://uncached.example.com/vpm.json")
+        );
+        assert_eq!(errors[0].error().kind(), io::ErrorKind::NotFound);
+        assert!(matches!(
+            errors[0].load_error(),
+            Some(LoadRepositoryErr::RepositoryNotCached { url })
+                if url.as_str() == "https://uncached.example.com/vpm.json"
+        ));
+    })
+}
+
+/// The http client responding the repository with packages hosted on another host
+struct CdnHttpClient;
+
+impl CdnHttpClient {
+    fn respond() -> Vec<u8> {
+        serde_json::to_vec(&json!({
+            "name": "listing",
+            "id": "com.example.listing",
+            "packages": {
+                "com.example.cdn": {
+                    "versions": {
+                        "1.0.0": {
+                            "name": "com.example.cdn",
+                            "version": "1.0.0",
+                            "url": "https://cdn.example.net/packages/com.example.cdn-1.0.0.zip",
+                        },
+                    },
+                },
+                "com.example.relative": {
+                    "versions": {
+                        "1.0.0": {
+                            "name": "com.example.relative",
+                            "version": "1.0.0",
+                            "url": "zips/com.example.relative-1.0.0.zip",
+                        },
+                    },
+                },
+            },
+        }))
+        .unwrap()
+    }
+}
+
+impl HttpClient for CdnHttpClient {
+    async fn get(
+        &self,
+        _: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Ok(Cursor::new(Self::respond()))
+    }
+
+    async fn get_with_etag(
+        &self,
+        _: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        Ok(Some((Cursor::new(Self::respond()), None)))
+    }
+}
+
+#[test]
+fn resolve_package_urls_of_downloaded_repository() {
+    block_on(async {
+        let url = Url::parse("https://listing.example.com/vpm/index.json").unwrap();
+        let (repo, _) = RemoteRepository::download(&CdnHttpClient, &url, &IndexMap::new())
+            .await
+            .unwrap();
+
+        let url_of = |name: &str| {
+            let package = repo.get_versions_of(name).next().unwrap();
+            package.url().unwrap().as_str().to_owned()
+        };
+
+        assert_eq!(
+            url_of("com.example.cdn"),
+            "https://cdn.example.net/packages/com.example.cdn-1.0.0.zip"
+        );
+        assert_eq!(
+            url_of("com.example.relative"),
+            "https://listing.example.com/vpm/zips/com.example.relative-1.0.0.zip"
+        );
+    })
+}
+
+/// The http client failing with the error until the number of requests reaches `succeed_at`
+struct FlakyHttpClient {
+    requests: AtomicU32,
+    succeed_at: u32,
+    error: io::ErrorKind,
+}
+
+impl FlakyHttpClient {
+    fn new(succeed_at: u32, error: io::ErrorKind) -> Self {
+        Self {
+            requests: AtomicU32::new(0),
+            succeed_at,
+            error,
+        }
+    }
+
+    fn respond(&self, url: &Url) -> io::Result<Vec<u8>> {
+        let requests = self.requests.fetch_add(1, Ordering::SeqCst) + 1;
+        if requests < self.succeed_at {
+            return Err(io::Error::new(self.error, "flaky"));
+        }
+        Ok(serde_json::to_vec(&json!({
+            "name": url.as_str(),
+            "id": url.as_str(),
+            "url": url.as_str(),
+            "packages": {},
+        }))
+        .unwrap())
+    }
+}
+
+impl HttpClient for FlakyHttpClient {
+    async fn get(
+        &self,
+        url: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Ok(Cursor::new(self.respond(url)?))
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        Ok(Some((Cursor::new(self.respond(url)?), None)))
+    }
+}
+
+#[test]
+fn retry_transient_errors() {
+    block_on(async {
+        let url = Url::parse("https://flaky.example.com/vpm.json").unwrap();
+        let delays = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let retrying = |inner| {
+            let delays = delays.clone();
+            RetryingClient::new(inner, move |delay| {
+                delays.lock().unwrap().push(delay);
+                future::ready(())
+            })
+        };
+
+        // succeeds at the third attempt
+        let client = retrying(FlakyHttpClient::new(3, io::ErrorKind::TimedOut));
+        RemoteRepository::download(&client, &url, &IndexMap::new())
+            .await
+            .unwrap();
+        assert_eq!(client.inner().requests.load(Ordering::SeqCst), 3);
+        // the delay is doubled for each retry
+        assert_eq!(
+            delays.lock().unwrap().drain(..).collect::<Vec<_>>(),
+            vec![Duration::from_millis(500), Duration::from_millis(1000)]
+        );
+
+        // fails after all attempts
+        let client =
+            retrying(FlakyHttpClient::new(10, io::ErrorKind::ConnectionReset)).with_max_attempts(4);
+        let err = RemoteRepository::download(&client, &url, &IndexMap::new())
+            .await
+            .unwrap_err();
+        assert_eq!(client.inner().requests.load(Ordering::SeqCst), 4);
+        assert!(err.to_string().contains("4 attempts"), "{err}");
+
+        // not transient errors are not retried
+        let client = retrying(FlakyHttpClient::new(3, io::ErrorKind::NotFound));
+        RemoteRepository::download(&client, &url, &IndexMap::new())
+            .await
+            .unwrap_err();
+        assert_eq!(client.inner().requests.load(Ordering::SeqCst), 1);
+    })
+}
+
+#[test]
+fn parse_packages_lazily() {
+    let json = json!({
+        "name": "lazy",
+        "url": "https://lazy.example.com/vpm.json",
+        "packages": {
+            "com.anatawa12.package": {
+                "versions": {
+                    "1.0.0": {
+                        "name": "com.anatawa12.package",
+                        "version": "1.0.0",
+                    },
+                    "1.1.0": {
+                        "name": "com.anatawa12.package",
+                        "version": "1.1.0",
+                    },
+                },
+            },
+            "com.anatawa12.broken": "not a package",
+            "com.anatawa12.broken-versions": { "versions": { "not a version": {} } },
+        },
+    });
+    let serde_json::Value::Object(json) = json else {
+        unreachable!()
+    };
+
+    // malformed packages are reported in the same way without the lazy parsing
+    let eager = RemoteRepository::parse(json.clone()).unwrap();
+    assert_eq!(eager.get_versions_of("com.anatawa12.package").count(), 2);
+    assert_eq!(eager.get_versions_of("com.anatawa12.broken").count(), 0);
+    assert_eq!(
+        eager
+            .get_versions_of("com.anatawa12.broken-versions")
+            .count(),
+        0
+    );
+    assert_eq!(eager.get_packages().count(), 3);
+
+    let repo = RemoteRepository::parse_lazy(json.clone()).unwrap();
+    assert_eq!(repo.name(), Some("lazy"));
+    assert_eq!(repo.get_versions_of("com.anatawa12.package").count(), 2);
+    assert!((repo.get_package_version("com.anatawa12.package", &Version::new(1, 1, 0))).is_some());
+    // broken packages are reported when looked up
+    assert_eq!(repo.get_versions_of("com.anatawa12.broken").count(), 0);
+    assert_eq!(
+        repo.get_versions_of("com.anatawa12.broken-versions")
+            .count(),
+        0
+    );
+    assert!(repo.get_package("com.anatawa12.missing").is_none());
+    assert_eq!(repo.get_packages().count(), 3);
+
+    // the json is kept as is
+    assert_eq!(
+        serde_json::to_value(&repo).unwrap(),
+        serde_json::Value::Object(json)
+    );
+}
+
+#[test]
+fn enumerate_all_packages_of_repository() {
+    let json = json!({
+        "name": "catalog",
+        "url": "https://catalog.example.com/vpm.json",
+        "packages": {
+            "com.anatawa12.package": {
+                "versions": {
+                    "1.0.0": { "name": "com.anatawa12.package", "version": "1.0.0" },
+                    "1.1.0": { "name": "com.anatawa12.package", "version": "1.1.0" },
+                },
+            },
+            "com.anatawa12.another": {
+                "versions": {
+                    "2.0.0": {
+                        "name": "com.anatawa12.another",
+                        "version": "2.0.0",
+                        "vrc-get": { "yanked": true },
+                    },
+                },
+            },
+        },
+    });
+    let serde_json::Value::Object(json) = json else {
+        unreachable!()
+    };
+
+    let repo =
+        LocalCachedRepository::new(RemoteRepository::parse_lazy(json).unwrap(), IndexMap::new());
+    let mut packages = (repo.all_packages())
+        .map(|x| {
+            assert_eq!(x.repo().map(|x| x.id()), Some(repo.id()));
+            format!("{}@{}", x.name(), x.version())
+        })
+        .collect::<Vec<_>>();
+    packages.sort();
+
+    // yanked versions are also returned
+    assert_eq!(
+        packages,
+        vec![
+            "com.anatawa12.another@2.0.0",
+            "com.anatawa12.package@1.0.0",
+            "com.anatawa12.package@1.1.0",
+        ]
+    );
+}
+
+#[test]
+fn parse_repository_shapes() {
+    fn manifest(name: &str, version: &str) -> serde_json::Value {
+        json!({ "name": name, "version": version })
+    }
+
+    let nested = json!({
+        "name": "nested",
+        "packages": {
+            "com.anatawa12.package": {
+                "versions": {
+                    "1.0.0": manifest("com.anatawa12.package", "1.0.0"),
+                    "1.1.0": manifest("com.anatawa12.package", "1.1.0"),
+                },
+            },
+            "com.anatawa12.another": {
+                "versions": {
+                    "2.0.0": manifest("com.anatawa12.another", "2.0.0"),
+                },
+            },
+        },
+    });
+    // the versions of each package are listed
+    let versions_list = json!({
+        "name": "versions list",
+        "packages": {
+            "com.anatawa12.package": {
+                "versions": [
+                    manifest("com.anatawa12.package", "1.0.0"),
+                    manifest("com.anatawa12.package", "1.1.0"),
+                ],
+            },
+            "com.anatawa12.another": {
+                "versions": [manifest("com.anatawa12.another", "2.0.0")],
+            },
+        },
+    });
+    // all the manifests are listed, with an unknown key which is ignored
+    let flat_list = json!({
+        "name": "flat list",
+        "futureKey": { "anything": true },
+        "packages": [
+            manifest("com.anatawa12.package", "1.0.0"),
+            manifest("com.anatawa12.another", "2.0.0"),
+            manifest("com.anatawa12.package", "1.1.0"),
+            { "version": "1.0.0" },
+        ],
+    });
+
+    for json in [nested, versions_list, flat_list] {
+        let serde_json::Value::Object(json) = json else {
+            unreachable!()
+        };
+        let repo = RemoteRepository::parse(json).unwrap();
+        let mut packages = (repo.get_packages())
+            .flat_map(|x| x.all_versions())
+            .map(|x| format!("{}@{}", x.name(), x.version()))
+            .collect::<Vec<_>>();
+        packages.sort();
+        assert_eq!(
+            packages,
+            vec![
+                "com.anatawa12.another@2.0.0",
+                "com.anatawa12.package@1.0.0",
+                "com.anatawa12.package@1.1.0",
+            ],
+            "{:?}",
+            repo.name()
+        );
+        assert!(
+            (repo.get_package_version("com.anatawa12.package", &Version::new(1, 1, 0))).is_some()
+        );
+    }
+}
+
+#[test]
+fn parse_release_notes_metadata() {
+    let json = json!({
+        "name": "notes",
+        "url": "https://notes.example.com/vpm.json",
+        "packages": {
+            "com.anatawa12.package": {
+                "versions": {
+                    "1.0.0": {
+                        "name": "com.anatawa12.package",
+                        "version": "1.0.0",
+                        "url": "https://notes.example.com/package-1.0.0.zip",
+                        "changelogUrl": "https://notes.example.com/CHANGELOG.md",
+                        "author": "anatawa12",
+                    },
+                    "1.1.0": {
+                        "name": "com.anatawa12.package",
+                        "version": "1.1.0",
+                    },
+                },
+            },
+        },
+    });
+    let serde_json::Value::Object(json) = json else {
+        unreachable!()
+    };
+
+    let repo = RemoteRepository::parse(json).unwrap();
+    let package =
+        (repo.get_package_version("com.anatawa12.package", &Version::new(1, 0, 0))).unwrap();
+    assert_eq!(
+        package.changelog_url().map(Url::as_str),
+        Some("https://notes.example.com/CHANGELOG.md")
+    );
+    assert_eq!(
+        package.url().map(Url::as_str),
+        Some("https://notes.example.com/package-1.0.0.zip")
+    );
+    assert_eq!(package.author(), Some("anatawa12"));
+
+    let package =
+        (repo.get_package_version("com.anatawa12.package", &Version::new(1, 1, 0))).unwrap();
+    assert_eq!(package.changelog_url(), None);
+    assert_eq!(package.author(), None);
+}
+
+#[test]
+fn validate_repository_listing() {
+    block_on(async {
+        let json = json!({
+            "name": "example",
+            "id": "com.example",
+            "url": "https://example.com/vpm.json",
+            "packages": {
+                "com.example.a": {
+                    "versions": {
+                        "1.0.0": {
+                            "name": "com.example.a",
+                            "version": "1.0.0",
+                            "url": "https://cdn.example.com/a.zip",
+                            "vpmDependencies": {
+                                "com.example.b": "^1.0.0",
+                                "com.vrchat.base": "^3.0.0",
+                                "com.example.missing": "^1.0.0",
+                            },
+                        },
+                    },
+                },
+                "com.example.b": {
+                    "versions": {
+                        "1.0.0": {
+                            "name": "com.example.b",
+                            "version": "1.0.0",
+                            "url": "https://cdn.example.com/offline.json",
+                            "legacyPackages": ["com.example.b"],
+                        },
+                    },
+                },
+                "com.example.c": {
+                    "versions": {
+                        "1.0.0": {
+                            "name": "com.example.c",
+                            "version": "1.0.0",
+                            "url": "https://cdn.example.com/c.zip",
+                        },
+                        "latest": { "name": "com.example.c", "version": "latest" },
+                    },
+                },
+                "com.example.d": {
+                    "versions": {
+                        "1.0.0": { "name": "com.example.d", "version": "1.0.0" },
+                    },
+                },
+            },
+        });
+        let serde_json::Value::Object(json) = json else {
+            unreachable!()
+        };
+        let listing = RemoteRepository::parse_lazy(json).unwrap();
+
+        let upstream = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(3, 5, 0),
+            ))
+            .build();
+
+        // the invalid version makes the whole package unavailable, so other checks skip it
+        let warnings = validate_repository(&listing, &upstream, Some(&BrokenHttpClient)).await;
+        let warnings = warnings.iter().map(ToString::to_string).collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            vec![
+                "com.example.c: invalid version 'latest'",
+                "com.example.a@1.0.0: dependency com.example.missing not found",
+                "com.example.b@1.0.0: the package is a legacy package of itself",
+                "com.example.b@1.0.0: https://cdn.example.com/offline.json is not reachable: timed out",
+                "com.example.d@1.0.0: no url of the zip file",
+            ]
+        );
+
+        // without the http client, urls are not requested
+        let warnings = validate_repository(&listing, &upstream, None::<&BrokenHttpClient>).await;
+        assert_eq!(warnings.len(), 4);
+    })
+}
+
+#[test]
+fn diff_repository_listings() {
+    fn listing(packages: serde_json::Value) -> RemoteRepository {
+        let serde_json::Value::Object(json) = json!({
+            "name": "example",
+            "id": "com.example",
+            "url": "https://example.com/vpm.json",
+            "packages": packages,
+        }) else {
+            unreachable!()
+        };
+        RemoteRepository::parse(json).unwrap()
+    }
+
+    let old = listing(json!({
+        "com.example.a": {
+            "versions": {
+                "1.0.0": { "name": "com.example.a", "version": "1.0.0" },
+                "1.1.0": {
+                    "name": "com.example.a",
+                    "version": "1.1.0",
+                    "vpmDependencies": { "com.example.b": "^1.0.0" },
+                },
+                "1.2.0": { "name": "com.example.a", "version": "1.2.0" },
+            },
+        },
+        "com.example.old": {
+            "versions": {
+                "1.0.0": { "name": "com.example.old", "version": "1.0.0" },
+            },
+        },
+    }));
+    let new = listing(json!({
+        "com.example.a": {
+            "versions": {
+                "1.1.0": {
+                    "name": "com.example.a",
+                    "version": "1.1.0",
+                    "vpmDependencies": { "com.example.evil": "^1.0.0" },
+                },
+                "1.2.0": {
+                    "name": "com.example.a",
+                    "version": "1.2.0",
+                    "vrc-get": { "yanked": true },
+                },
+                "2.0.0": { "name": "com.example.a", "version": "2.0.0" },
+            },
+        },
+        "com.example.new": {
+            "versions": {
+                "1.0.0": { "name": "com.example.new", "version": "1.0.0" },
+            },
+        },
+    }));
+
+    let diff = diff_repositories(&old, &new);
+    assert_eq!(diff.added_packages, vec!["com.example.new".into()]);
+    assert_eq!(diff.removed_packages, vec!["com.example.old".into()]);
+    assert_eq!(
+        diff.added_versions,
+        vec![
+            ("com.example.a".into(), Version::new(2, 0, 0)),
+            ("com.example.new".into(), Version::new(1, 0, 0)),
+        ]
+    );
+    assert_eq!(
+        diff.removed_versions,
+        vec![
+            ("com.example.a".into(), Version::new(1, 0, 0)),
+            ("com.example.old".into(), Version::new(1, 0, 0)),
+        ]
+    );
+    assert_eq!(
+        diff.yanked_versions,
+        vec![("com.example.a".into(), Version::new(1, 2, 0))]
+    );
+    assert_eq!(
+        (diff.changed_versions.iter())
+            .map(|x| (x.version.clone(), x.changes_dependencies()))
+            .collect::<Vec<_>>(),
+        vec![
+            (Version::new(1, 1, 0), true),
+            (Version::new(1, 2, 0), false)
+        ]
+    );
+    assert_eq!(
+        diff.to_string(),
+        "+ com.example.new (new package)\n\
+         - com.example.old (removed package)\n\
+         + com.example.a 2.0.0\n\
+         + com.example.new 1.0.0\n\
+         - com.example.a 1.0.0\n\
+         - com.example.old 1.0.0\n\
+         ! com.example.a 1.2.0 yanked\n\
+         ~ com.example.a 1.1.0 changed: vpmDependencies\n\
+         ~ com.example.a 1.2.0 changed: vrc-get\n"
+    );
+
+    assert!(diff_repositories(&old, &old).is_empty());
+}
+
+/// The http client responding `changes` as the changes of the repository since r1,
+/// or failing the changes if it's None
+struct ChangesHttpClient {
+    requests: std::sync::Mutex<Vec<String>>,
+    changes: Option<serde_json::Value>,
+}
+
+impl ChangesHttpClient {
+    fn new(changes: Option<serde_json::Value>) -> Self {
+        Self {
+            requests: std::sync::Mutex::new(Vec::new()),
+            changes,
+        }
+    }
+
+    fn respond(&self, url: &Url) -> io::Result<Vec<u8>> {
+        self.requests.lock().unwrap().push(url.to_string());
+        let manifest = |version: &str| json!({ "name": "com.example.changed", "version": version });
+        let json = match url.path() {
+            "/changes.json" => match &self.changes {
+                Some(changes) => changes.clone(),
+                None => return Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+            },
+            _ => json!({
+                "url": url.as_str(),
+                "changesUrl": "changes.json",
+                "revision": "r3",
+                "packages": {
+                    "com.example.changed": { "versions": { "2.0.0": manifest("2.0.0") } },
+                },
+            }),
+        };
+        Ok(serde_json::to_vec(&json).unwrap())
+    }
+}
+
+impl HttpClient for ChangesHttpClient {
+    async fn get(
+        &self,
+        url: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Ok(Cursor::new(self.respond(url)?))
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        Ok(Some((Cursor::new(self.respond(url)?), None)))
+    }
+}
+
+#[test]
+fn update_cached_repository_with_changes() {
+    block_on(async {
+        async fn load(http: &ChangesHttpClient) -> Vec<String> {
+            let vfs = VirtualFileSystem::new();
+            vfs.add_file(
+                "settings.json".as_ref(),
+                &serde_json::to_vec(&json!({
+                    "userRepos": [
+                        {
+                            "localPath": "Repos/changes.json",
+                            "url": "https://changes.example.com/vpm.json",
+                        },
+                    ],
+                }))
+                .unwrap(),
+            )
+            .await
+            .unwrap();
+            vfs.add_file(
+                "vrc-get/settings.json".as_ref(),
+                br#"{ "ignoreOfficialRepository": true, "ignoreCuratedRepository": true }"#,
+            )
+            .await
+            .unwrap();
+            let manifest = |name: &str, version: &str| json!({ "name": name, "version": version });
+            let cached = LocalCachedRepository::new(
+                RemoteRepository::parse(
+                    json!({
+                        "url": "https://changes.example.com/vpm.json",
+                        "changesUrl": "changes.json",
+                        "revision": "r1",
+                        "packages": {
+                            "com.example.changed": {
+                                "versions": { "1.0.0": manifest("com.example.changed", "1.0.0") },
+                            },
+                            "com.example.removed": {
+                                "versions": { "1.0.0": manifest("com.example.removed", "1.0.0") },
+                            },
+                        },
+                    })
+                    .as_object()
+                    .unwrap()
+                    .clone(),
+                )
+                .unwrap(),
+                IndexMap::new(),
+            );
+            vfs.add_file(
+                "Repos/changes.json".as_ref(),
+                &serde_json::to_vec(&cached).unwrap(),
+            )
+            .await
+            .unwrap();
+
+            let settings = Settings::load(&vfs).await.unwrap();
+            let collection = PackageCollection::load(&settings, &vfs, Some(http))
+                .await
+                .unwrap();
+
+            let repo = collection.get_remote().next().unwrap();
+            let mut versions = (repo.all_packages())
+                .map(|x| format!("{}@{}", x.name(), x.version()))
+                .collect::<Vec<_>>();
+            versions.sort();
+            versions.push(repo.repo().revision().unwrap().to_owned());
+            versions
+        }
+
+        let manifest = |version: &str| json!({ "name": "com.example.changed", "version": version });
+
+        // only the changes are downloaded and merged into the cache
+        let http = ChangesHttpClient::new(Some(json!({
+            "revision": "r2",
+            "packages": {
+                "com.example.changed": {
+                    "versions": { "1.0.0": null, "1.1.0": manifest("1.1.0") },
+                },
+                "com.example.removed": null,
+            },
+        })));
+        assert_eq!(load(&http).await, vec!["com.example.changed@1.1.0", "r2"]);
+        assert_eq!(
+            *http.requests.lock().unwrap(),
+            vec!["https://changes.example.com/changes.json?since=r1"]
+        );
+
+        // the whole repository is downloaded if the changes are not available
+        let http = ChangesHttpClient::new(None);
+        assert_eq!(load(&http).await, vec!["com.example.changed@2.0.0", "r3"]);
+        assert_eq!(
+            *http.requests.lock().unwrap(),
+            vec![
+                "https://changes.example.com/changes.json?since=r1",
+                "https://changes.example.com/vpm.json",
+            ]
+        );
+
+        // or the changes are malformed, instead of skipping the malformed package
+        let http = ChangesHttpClient::new(Some(json!({
+            "revision": "r2",
+            "packages": {
+                "com.example.changed": { "1.1.0": manifest("1.1.0") },
+            },
+        })));
+        assert_eq!(load(&http).await, vec!["com.example.changed@2.0.0", "r3"]);
+        assert_eq!(http.requests.lock().unwrap().len(), 2);
+    })
+}
+
+#[test]
+fn share_repository_cache_with_cache_root() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [
+                    {
+                        "localPath": "Repos/shared.json",
+                        "url": "https://shared.example.com/vpm.json",
+                    },
+                ],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+        vfs.add_file(
+            "vrc-get/settings.json".as_ref(),
+            br#"{ "ignoreOfficialRepository": true, "ignoreCuratedRepository": true }"#,
+        )
+        .await
+        .unwrap();
+        let settings = Settings::load(&vfs).await.unwrap();
+        let cache_root = "shared-cache".as_ref();
+
+        // warm the shared cache
+        let collection = PackageCollection::load_with_cache_root(
+            &settings,
+            &vfs,
+            Some(&SlowHttpClient),
+            cache_root,
+        )
+        .await
+        .unwrap();
+        assert_eq!(collection.get_remote().count(), 1);
+        assert!(collection.repository_errors().is_empty());
+
+        // the cache is not written to the local path in the settings
+        let collection = PackageCollection::load(&settings, &vfs, None::<&BrokenHttpClient>)
+            .await
+            .unwrap();
+        assert_eq!(collection.get_remote().count(), 0);
+        assert_eq!(collection.repository_errors().len(), 1);
+
+        // the shared cache is used offline
+        let collection = PackageCollection::load_with_cache_root(
+            &settings,
+            &vfs,
+            None::<&BrokenHttpClient>,
+            cache_root,
+        )
+        .await
+        .unwrap();
+        let names = (collection.get_remote())
+            .filter_map(|x| x.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["https://shared.example.com/vpm.json"]);
+        assert!(collection.repository_errors().is_empty());
+    })
+}
+
+#[test]
+fn substitute_environment_variables() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [
+                    {
+                        "localPath": "Repos/env.json",
+                        // cargo sets the variable on running the tests
+                        "url": "https://env.example.com/${CARGO_PKG_NAME}/vpm.json",
+                    },
+                    {
+                        "localPath": "Repos/unset.json",
+                        "url": "https://unset.example.com/vpm.json",
+                        "headers": {
+                            "Authorization": "Bearer ${VRC_GET_TEST_UNSET_TOKEN}",
+                        },
+                    },
+                    {
+                        "localPath": "Repos/unset-url.json",
+                        "url": "https://unset-url.example.com/${VRC_GET_TEST_UNSET_PATH}/vpm.json",
+                    },
+                ],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+        vfs.add_file(
+            "vrc-get/settings.json".as_ref(),
+            br#"{ "ignoreOfficialRepository": true, "ignoreCuratedRepository": true }"#,
+        )
+        .await
+        .unwrap();
+
+        let settings = Settings::load(&vfs).await.unwrap();
+        let collection = PackageCollection::load(&settings, &vfs, Some(&SlowHttpClient))
+            .await
+            .unwrap();
+
+        // the repository is requested at the substituted url
+        let names = (collection.get_remote())
+            .filter_map(|x| x.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["https://env.example.com/vrc-get-vpm/vpm.json"]);
+
+        // the repositories referencing unset variables are reported with the name of the variable
+        let errors = collection.repository_errors();
+        assert_eq!(errors.len(), 2);
+        for (path, host, variable) in [
+            (
+                "Repos/unset.json",
+                "unset.example.com",
+                "VRC_GET_TEST_UNSET_TOKEN",
+            ),
+            (
+                "Repos/unset-url.json",
+                "unset-url.example.com",
+                "VRC_GET_TEST_UNSET_PATH",
+            ),
+        ] {
+            let error = (errors.iter()).find(|x| x.path().ends_with(path)).unwrap();
+            assert_eq!(error.url().and_then(|x| x.host_str()), Some(host));
+            assert!(
+                matches!(
+                    error.load_error(),
+                    Some(LoadRepositoryErr::MissingEnvVar { name }) if name.as_ref() == variable
+                ),
+                "{path}: {:?}",
+                error.error()
+            );
+            assert_eq!(error.error().kind(), io::ErrorKind::InvalidInput);
+            assert!(error.error().to_string().contains(variable));
+        }
+    })
+}
+
+#[test]
+fn restrict_collection_to_repositories() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [
+                    {
+                        "localPath": "Repos/public.json",
+                        "url": "https://public.example.com/vpm.json",
+                    },
+                    {
+                        "localPath": "Repos/private.json",
+                        "url": "https://private.example.com/vpm.json",
+                    },
+                ],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+        vfs.add_file(
+            "vrc-get/settings.json".as_ref(),
+            br#"{ "ignoreOfficialRepository": true, "ignoreCuratedRepository": true }"#,
+        )
+        .await
+        .unwrap();
+        for (host, package) in [
+            ("public", "com.example.tool"),
+            ("private", "com.example.lib"),
+        ] {
+            let cached = LocalCachedRepository::new(
+                RemoteRepository::parse(
+                    json!({
+                        "id": format!("com.example.{host}"),
+                        "url": format!("https://{host}.example.com/vpm.json"),
+                        "packages": {
+                            package: {
+                                "versions": {
+                                    "1.0.0": { "name": package, "version": "1.0.0" },
+                                },
+                            },
+                        },
+                    })
+                    .as_object()
+                    .unwrap()
+                    .clone(),
+                )
+                .unwrap(),
+                IndexMap::new(),
+            );
+            vfs.add_file(
+                format!("Repos/{host}.json").as_ref(),
+                &serde_json::to_vec(&cached).unwrap(),
+            )
+            .await
+            .unwrap();
+        }
+
+        let settings = Settings::load(&vfs).await.unwrap();
+        let collection = PackageCollection::load(&settings, &vfs, None::<&BrokenHttpClient>)
+            .await
+            .unwrap();
+        assert_eq!(collection.get_remote().count(), 2);
+
+        // by the id
+        let public = collection.only_repositories(&["com.example.public"]);
+        assert_eq!(public.get_remote().count(), 1);
+        assert_eq!(public.find_packages("com.example.tool").count(), 1);
+        assert_eq!(public.find_packages("com.example.lib").count(), 0);
+
+        // by the url
+        let private = collection.only_repositories(&["https://private.example.com/vpm.json"]);
+        assert_eq!(private.find_packages("com.example.tool").count(), 0);
+        assert_eq!(private.find_packages("com.example.lib").count(), 1);
+
+        // the original collection is not changed
+        assert_eq!(collection.find_packages("com.example.lib").count(), 1);
+    })
+}
+
+/// The http client recording the headers of the requests
+struct HeaderRecordingClient {
+    requests: std::sync::Mutex<Vec<IndexMap<Box<str>, Box<str>>>>,
+}
+
+impl HttpClient for HeaderRecordingClient {
+    async fn get(
+        &self,
+        url: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        Ok(Cursor::new(SlowHttpClient::respond(url).await?))
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        headers: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        self.requests.lock().unwrap().push(headers.clone());
+        Ok(Some((
+            Cursor::new(SlowHttpClient::respond(url).await?),
+            None,
+        )))
+    }
+}
+
+#[test]
+fn keep_secret_headers_out_of_cache() {
+    block_on(async {
+        let vfs = VirtualFileSystem::new();
+        vfs.add_file(
+            "settings.json".as_ref(),
+            &serde_json::to_vec(&json!({
+                "userRepos": [{
+                    "localPath": "Repos/private.json",
+                    "url": "https://private.example.com/vpm.json",
+                    "headers": {
+                        "Authorization": "Bearer secret-token",
+                        "X-Channel": "beta",
+                    },
+                    "vrc-get-secret-headers": ["authorization"],
+                }],
+            }))
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+        vfs.add_file(
+            "vrc-get/settings.json".as_ref(),
+            br#"{ "ignoreOfficialRepository": true, "ignoreCuratedRepository": true }"#,
+        )
+        .await
+        .unwrap();
+
+        let settings = Settings::load(&vfs).await.unwrap();
+        let http = HeaderRecordingClient {
+            requests: Default::default(),
+        };
+        // the first load downloads the repository, and the second one updates the cache
+        for _ in 0..2 {
+            let collection = PackageCollection::load(&settings, &vfs, Some(&http))
+                .await
+                .unwrap();
+            let repo = collection.get_remote().next().unwrap();
+            // the package downloads use the secret headers
+            assert_eq!(
+                repo.headers().get("Authorization").map(AsRef::as_ref),
+                Some("Bearer secret-token")
+            );
+
+            let cache = read_to_string(&vfs, "Repos/private.json").await;
+            assert!(!cache.contains("secret-token"), "{cache}");
+            assert!(cache.contains("X-Channel"), "{cache}");
+        }
+
+        // the requests from the cache still have the secret headers
+        let requests = std::mem::take(&mut *http.requests.lock().unwrap());
+        assert_eq!(requests.len(), 2);
+        for headers in &requests {
+            assert_eq!(
+                headers.get("Authorization").map(AsRef::as_ref),
+                Some("Bearer secret-token")
+            );
+        }
+
+        // the secret headers are not exported or rewritten in the settings
+        let exported = settings.export_repositories();
+        assert!(!exported.contains("secret-token"), "{exported}");
+        assert!(exported.contains("X-Channel"), "{exported}");
+        let settings = read_to_string(&vfs, "settings.json").await;
+        assert_eq!(settings.matches("secret-token").count(), 1, "{settings}");
+    })
+}
@@ -0,0 +1,149 @@
+#![cfg(feature = "tokio")]
+
+use common::*;
+use serde_json::json;
+use std::convert::Infallible;
+use std::path::Path;
+use url::Url;
+use vrc_get_vpm::PackageCollection as _;
+use vrc_get_vpm::environment::{PackageCollection, PackageInstaller, Settings};
+use vrc_get_vpm::io::DefaultEnvironmentIo;
+use vrc_get_vpm::unity_project::AddPackageOperation;
+
+mod common;
+
+/// Writes the repository with the package whose zip file is next to the repository json.
+async fn write_repository(dir: &Path, repo: serde_json::Value) {
+    tokio::fs::create_dir_all(dir).await.unwrap();
+    let zip = build_zip(&[
+        (
+            "package.json",
+            br#"{"name":"com.anatawa12.package","version":"1.0.0"}"#,
+        ),
+        ("Runtime/code.cs", b"code"),
+    ])
+    .await;
+    tokio::fs::write(dir.join("package.zip"), zip)
+        .await
+        .unwrap();
+    tokio::fs::write(dir.join("vpm.json"), serde_json::to_vec(&repo).unwrap())
+        .await
+        .unwrap();
+}
+
+fn repository_json() -> serde_json::Value {
+    json!({
+        "name": "Local",
+        "id": "com.example.local",
+        "packages": {
+            "com.anatawa12.package": {
+                "versions": {
+                    "1.0.0": {
+                        "name": "com.anatawa12.package",
+                        "version": "1.0.0",
+                        "url": "package.zip",
+                    },
+                },
+            },
+        },
+    })
+}
+
+async fn write_settings(env: &Path, repos: serde_json::Value) {
+    tokio::fs::create_dir_all(env.join("vrc-get"))
+        .await
+        .unwrap();
+    let settings = json!({ "userRepos": repos });
+    tokio::fs::write(
+        env.join("settings.json"),
+        serde_json::to_vec(&settings).unwrap(),
+    )
+    .await
+    .unwrap();
+    tokio::fs::write(
+        env.join("vrc-get/settings.json"),
+        br#"{ "ignoreOfficialRepository": true, "ignoreCuratedRepository": true }"#,
+    )
+    .await
+    .unwrap();
+}
+
+#[test]
+fn install_package_from_file_url_repository() {
+    let root = temp_dir("install_package_from_file_url_repository");
+    let repo_url = Url::from_file_path(root.join("repo/vpm.json")).unwrap();
+    let zip_url = Url::from_file_path(root.join("repo/package.zip")).unwrap();
+    let io = DefaultEnvironmentIo::new(root.join("env").into_boxed_path());
+
+    block_on_tokio(async {
+        write_repository(&root.join("repo"), repository_json()).await;
+        write_settings(
+            &root.join("env"),
+            json!([{ "localPath": "Repos/local.json", "url": repo_url.as_str() }]),
+        )
+        .await;
+
+        // the repository is read without the http client
+        let settings = Settings::load(&io).await.unwrap();
+        let collection = PackageCollection::load(&settings, &io, None::<&Infallible>)
+            .await
+            .unwrap();
+        let package = collection
+            .find_packages("com.anatawa12.package")
+            .next()
+            .unwrap();
+        // the url of the package is relative to the repository file
+        assert_eq!(package.package_json().url(), Some(&zip_url));
+
+        // and the zip file is copied in offline mode
+        let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+        let changes = project
+            .add_package_request(
+                &collection,
+                &[package],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        let installer = PackageInstaller::new(&io, None::<&Infallible>);
+        project
+            .apply_pending_changes(&installer, changes)
+            .await
+            .unwrap();
+
+        let code = read_to_string(
+            project.io(),
+            "Packages/com.anatawa12.package/Runtime/code.cs",
+        )
+        .await;
+        assert_eq!(code, "code");
+    })
+}
+
+#[test]
+fn load_plain_repository_json_at_local_path() {
+    let root = temp_dir("load_plain_repository_json_at_local_path");
+    let zip_url = Url::from_file_path(root.join("repo/package.zip")).unwrap();
+    let io = DefaultEnvironmentIo::new(root.join("env").into_boxed_path());
+
+    block_on_tokio(async {
+        write_repository(&root.join("repo"), repository_json()).await;
+        write_settings(
+            &root.join("env"),
+            json!([{ "localPath": root.join("repo/vpm.json") }]),
+        )
+        .await;
+
+        let settings = Settings::load(&io).await.unwrap();
+        let collection = PackageCollection::load(&settings, &io, None::<&Infallible>)
+            .await
+            .unwrap();
+        let package = collection
+            .find_packages("com.anatawa12.package")
+            .next()
+            .unwrap();
+        assert_eq!(package.package_json().url(), Some(&zip_url));
+        assert_eq!(package.repository_id(), Some("com.example.local"));
+    })
+}
@@ -0,0 +1,467 @@
+use common::*;
+use futures::TryStreamExt;
+use futures::executor::block_on;
+use futures::io::Cursor;
+use indexmap::IndexMap;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use url::Url;
+use vrc_get_vpm::environment::{PackageChecksumMismatch, PackageInstaller, PackageSizeMismatch};
+use vrc_get_vpm::io::IoTrait;
+use vrc_get_vpm::repository::{LocalCachedRepository, RemoteRepository};
+use vrc_get_vpm::unity_project::AddPackageOperation;
+use vrc_get_vpm::{CancellationToken, HttpClient, InstallProgress, PackageInfo, UnityProject};
+
+mod common;
+
+const ZIP_URL: &str = "https://example.com/com.anatawa12.package-1.0.0.zip";
+const ZIP_PATH: &str = "Repos/com.anatawa12.package/vrc-get-com.anatawa12.package-1.0.0.zip";
+
+/// The http client serving the zip files, which can be replaced to simulate re-publishing.
+struct ZipHttpClient {
+    files: Mutex<HashMap<Box<str>, Vec<u8>>>,
+}
+
+impl ZipHttpClient {
+    fn new(zip: Vec<u8>) -> Self {
+        Self {
+            files: Mutex::new(HashMap::from([(ZIP_URL.into(), zip)])),
+        }
+    }
+
+    fn republish(&self, zip: Vec<u8>) {
+        self.files.lock().unwrap().insert(ZIP_URL.into(), zip);
+    }
+
+    fn respond(&self, url: &Url) -> io::Result<Cursor<Vec<u8>>> {
+        match self.files.lock().unwrap().get(url.as_str()) {
+            Some(zip) => Ok(Cursor::new(zip.clone())),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "not found")),
+        }
+    }
+}
+
+impl HttpClient for ZipHttpClient {
+    async fn get(
+        &self,
+        url: &Url,
+        _: &IndexMap<&str, &str>,
+    ) -> io::Result<impl futures::AsyncRead + Send> {
+        self.respond(url)
+    }
+
+    async fn get_with_etag(
+        &self,
+        url: &Url,
+        _: &IndexMap<Box<str>, Box<str>>,
+        _: Option<&str>,
+    ) -> io::Result<Option<(impl futures::AsyncRead + Send, Option<Box<str>>)>> {
+        self.respond(url).map(|body| Some((body, None)))
+    }
+}
+
+async fn package_zip(content: &str) -> Vec<u8> {
+    build_zip(&[
+        (
+            "package.json",
+            br#"{"name":"com.anatawa12.package","version":"1.0.0"}"#,
+        ),
+        ("Runtime/code.cs", content.as_bytes()),
+    ])
+    .await
+}
+
+/// Creates the repository with the package with the zip file at [`ZIP_URL`].
+///
+/// `integrity` is merged into the package.json in the repository for `zipSHA256` and `zipSize`.
+fn repository(integrity: serde_json::Value) -> LocalCachedRepository {
+    let mut package = json!({
+        "name": "com.anatawa12.package",
+        "version": "1.0.0",
+        "url": ZIP_URL,
+    });
+    (package.as_object_mut().unwrap()).extend(integrity.as_object().unwrap().clone());
+    let serde_json::Value::Object(json) = json!({
+        "name": "Example",
+        "id": "com.example",
+        "url": "https://example.com/vpm.json",
+        "packages": {
+            "com.anatawa12.package": { "versions": { "1.0.0": package } },
+        },
+    }) else {
+        unreachable!()
+    };
+    LocalCachedRepository::new(RemoteRepository::parse(json).unwrap(), IndexMap::new())
+}
+
+fn package(repository: &LocalCachedRepository) -> PackageInfo<'_> {
+    repository.all_packages().next().unwrap()
+}
+
+async fn install(
+    installer: &PackageInstaller<'_, ZipHttpClient, VirtualFileSystem>,
+    package: PackageInfo<'_>,
+) -> io::Result<UnityProject<VirtualFileSystem>> {
+    let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+    let collection = PackageCollectionBuilder::new().build();
+    let changes = project
+        .add_package_request(
+            &collection,
+            &[package],
+            AddPackageOperation::InstallToDependencies,
+            false,
+        )
+        .await
+        .unwrap();
+    project.apply_pending_changes(installer, changes).await?;
+    Ok(project)
+}
+
+async fn assert_not_found(io: &impl IoTrait, path: &str) {
+    let err = io.metadata(Path::new(path)).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::NotFound, "{path}");
+}
+
+#[test]
+fn install_package_from_repository() {
+    block_on(async {
+        let zip = package_zip("code").await;
+        let http = ZipHttpClient::new(zip.clone());
+        let env = VirtualFileSystem::new();
+        let installer = PackageInstaller::new(&env, Some(&http));
+
+        let repository = repository(json!({
+            "zipSHA256": hex::encode(Sha256::digest(&zip)),
+            "zipSize": zip.len(),
+        }));
+        let project = install(&installer, package(&repository)).await.unwrap();
+
+        let code = read_to_string(
+            project.io(),
+            "Packages/com.anatawa12.package/Runtime/code.cs",
+        )
+        .await;
+        assert_eq!(code, "code");
+        // the zip file is cached with the hash
+        env.metadata(ZIP_PATH.as_ref()).await.unwrap();
+        let sha = read_to_string(&env, &format!("{ZIP_PATH}.sha256")).await;
+        assert!(sha.starts_with(&hex::encode(Sha256::digest(&zip))));
+    })
+}
+
+#[test]
+fn cache_package_zip_in_cache_root() {
+    block_on(async {
+        let zip = package_zip("code").await;
+        let http = ZipHttpClient::new(zip.clone());
+        let env = VirtualFileSystem::new();
+        let installer =
+            PackageInstaller::new(&env, Some(&http)).with_cache_root("shared-cache".as_ref());
+
+        let repository = repository(json!({ "zipSHA256": hex::encode(Sha256::digest(&zip)) }));
+        install(&installer, package(&repository)).await.unwrap();
+
+        let cached = "shared-cache/com.anatawa12.package/vrc-get-com.anatawa12.package-1.0.0.zip";
+        env.metadata(cached.as_ref()).await.unwrap();
+        env.metadata(format!("{cached}.sha256").as_ref())
+            .await
+            .unwrap();
+        assert_not_found(&env, ZIP_PATH).await;
+
+        // no temporary file is left after the zip file is renamed into the cache
+        let entries = env.read_dir(Path::new(cached).parent().unwrap()).await;
+        let entries = entries.unwrap().try_collect::<Vec<_>>().await.unwrap();
+        assert_eq!(entries.len(), 2);
+    })
+}
+
+#[test]
+fn trust_on_first_use_rejects_republished_download() {
+    block_on(async {
+        let http = ZipHttpClient::new(package_zip("code").await);
+        let env = VirtualFileSystem::new();
+        let installer = PackageInstaller::new(&env, Some(&http)).with_trust_on_first_use(true);
+        let repository = repository(json!({}));
+
+        install(&installer, package(&repository)).await.unwrap();
+        let records = read_to_string(&env, "vrc-get/package-integrity.json").await;
+        assert!(records.contains("com.anatawa12.package@1.0.0"), "{records}");
+
+        // the same version re-published with another content of the same size is rejected on download
+        http.republish(package_zip("evil").await);
+        env.remove_file(ZIP_PATH.as_ref()).await.unwrap();
+        let err = install(&installer, package(&repository))
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            (err.get_ref())
+                .and_then(|x| x.downcast_ref::<PackageChecksumMismatch>())
+                .is_some(),
+            "{err}"
+        );
+        assert_not_found(&env, ZIP_PATH).await;
+    })
+}
+
+#[test]
+fn trust_on_first_use_verifies_cached_zip() {
+    block_on(async {
+        let http = ZipHttpClient::new(package_zip("code").await);
+        let env = VirtualFileSystem::new();
+        let repository = repository(json!({}));
+
+        let trusting = PackageInstaller::new(&env, Some(&http)).with_trust_on_first_use(true);
+        install(&trusting, package(&repository)).await.unwrap();
+
+        // the re-published zip is cached by the installer without trust-on-first-use
+        http.republish(package_zip("evil").await);
+        env.remove_file(ZIP_PATH.as_ref()).await.unwrap();
+        let plain = PackageInstaller::new(&env, Some(&http));
+        install(&plain, package(&repository)).await.unwrap();
+
+        // the cached zip is verified against the first download
+        let trusting = PackageInstaller::new(&env, Some(&http)).with_trust_on_first_use(true);
+        let err = install(&trusting, package(&repository))
+            .await
+            .err()
+            .unwrap();
+        assert!(
+            (err.get_ref())
+                .and_then(|x| x.downcast_ref::<PackageChecksumMismatch>())
+                .is_some(),
+            "{err}"
+        );
+    })
+}
+
+#[test]
+fn trust_on_first_use_saves_only_new_records() {
+    block_on(async {
+        let http = ZipHttpClient::new(package_zip("code").await);
+        let env = VirtualFileSystem::new();
+        let repository = repository(json!({}));
+
+        let installer = PackageInstaller::new(&env, Some(&http)).with_trust_on_first_use(true);
+        install(&installer, package(&repository)).await.unwrap();
+
+        // reformat the records to detect rewriting
+        let records = read_to_string(&env, "vrc-get/package-integrity.json").await;
+        let reformatted =
+            serde_json::to_string(&serde_json::from_str::<serde_json::Value>(&records).unwrap())
+                .unwrap();
+        assert_ne!(records, reformatted);
+        (env.write(
+            "vrc-get/package-integrity.json".as_ref(),
+            reformatted.as_bytes(),
+        )
+        .await)
+            .unwrap();
+
+        // the matching installs from the cache and from the download don't touch the records
+        let installer = PackageInstaller::new(&env, Some(&http)).with_trust_on_first_use(true);
+        install(&installer, package(&repository)).await.unwrap();
+        env.remove_file(ZIP_PATH.as_ref()).await.unwrap();
+        install(&installer, package(&repository)).await.unwrap();
+
+        let records = read_to_string(&env, "vrc-get/package-integrity.json").await;
+        assert_eq!(records, reformatted);
+    })
+}
+
+#[test]
+fn install_package_without_checksum() {
+    block_on(async {
+        let http = ZipHttpClient::new(package_zip("code").await);
+        let env = VirtualFileSystem::new();
+        let installer = PackageInstaller::new(&env, Some(&http));
+
+        // the zip file is installed without verification if the repository has no hash
+        let repository = repository(json!({}));
+        let project = install(&installer, package(&repository)).await.unwrap();
+
+        let code = read_to_string(
+            project.io(),
+            "Packages/com.anatawa12.package/Runtime/code.cs",
+        )
+        .await;
+        assert_eq!(code, "code");
+    })
+}
+
+#[test]
+fn reject_package_with_checksum_mismatch() {
+    block_on(async {
+        let zip = package_zip("code").await;
+        let http = ZipHttpClient::new(zip.clone());
+        let env = VirtualFileSystem::new();
+        let installer = PackageInstaller::new(&env, Some(&http));
+
+        let expected = hex::encode(Sha256::digest(b"other zip"));
+        let repository = repository(json!({ "zipSHA256": expected.to_ascii_uppercase() }));
+        let err = install(&installer, package(&repository))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mismatch = (err.get_ref())
+            .and_then(|x| x.downcast_ref::<PackageChecksumMismatch>())
+            .unwrap();
+        assert_eq!(mismatch.expected.as_ref(), expected);
+        assert_eq!(mismatch.actual.as_ref(), hex::encode(Sha256::digest(&zip)));
+        // the downloaded file is not kept as the cache
+        assert_not_found(&env, ZIP_PATH).await;
+        assert_not_found(&env, &format!("{ZIP_PATH}.sha256")).await;
+    })
+}
+
+#[test]
+fn reject_package_with_size_mismatch() {
+    block_on(async {
+        let zip = package_zip("code").await;
+        let http = ZipHttpClient::new(zip.clone());
+        let env = VirtualFileSystem::new();
+        let installer = PackageInstaller::new(&env, Some(&http));
+
+        // the size is checked even if the hash matches
+        let repository = repository(json!({
+            "zipSHA256": hex::encode(Sha256::digest(&zip)),
+            "zipSize": zip.len() + 1,
+        }));
+        let err = install(&installer, package(&repository))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let mismatch = (err.get_ref())
+            .and_then(|x| x.downcast_ref::<PackageSizeMismatch>())
+            .unwrap();
+        assert_eq!(mismatch.expected, zip.len() as u64 + 1);
+        assert_eq!(mismatch.actual, zip.len() as u64);
+        assert_not_found(&env, ZIP_PATH).await;
+    })
+}
+
+#[test]
+fn reject_file_url_in_remote_repository() {
+    block_on(async {
+        let zip = package_zip("code").await;
+        let http = ZipHttpClient::new(zip.clone());
+        let env = VirtualFileSystem::new();
+        let installer = PackageInstaller::new(&env, Some(&http));
+
+        // remote repositories must not refer to the local files
+        let url = "file:///packages/com.anatawa12.package-1.0.0.zip";
+        let repository = repository(json!({ "url": url }));
+        let err = install(&installer, package(&repository))
+            .await
+            .err()
+            .unwrap();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(
+            err.to_string()
+                .contains("file urls are allowed only in local repositories")
+        );
+        assert_not_found(&env, ZIP_PATH).await;
+    })
+}
+
+/// The progress recording the reports, and cancelling the installation on the first download report
+#[derive(Default)]
+struct RecordingProgress {
+    downloads: Mutex<Vec<(String, u64, Option<u64>)>>,
+    extracts: Mutex<Vec<(String, usize, usize)>>,
+    cancel_on_download: Option<CancellationToken>,
+}
+
+impl InstallProgress for RecordingProgress {
+    fn download(&self, package_name: &str, bytes_done: u64, bytes_total: Option<u64>) {
+        (self.downloads.lock().unwrap()).push((package_name.into(), bytes_done, bytes_total));
+        if let Some(token) = &self.cancel_on_download {
+            token.cancel();
+        }
+    }
+
+    fn extract(&self, package_name: &str, files_done: usize, files_total: usize) {
+        (self.extracts.lock().unwrap()).push((package_name.into(), files_done, files_total));
+    }
+}
+
+#[test]
+fn report_install_progress() {
+    block_on(async {
+        let zip = package_zip("code").await;
+        let http = ZipHttpClient::new(zip.clone());
+        let env = VirtualFileSystem::new();
+        let progress = RecordingProgress::default();
+        let installer = PackageInstaller::new(&env, Some(&http)).with_progress(&progress);
+
+        let repository = repository(json!({ "zipSize": zip.len() }));
+        install(&installer, package(&repository)).await.unwrap();
+
+        let size = zip.len() as u64;
+        let downloads = progress.downloads.lock().unwrap();
+        assert!(!downloads.is_empty());
+        assert!(
+            (downloads.iter())
+                .all(|(name, _, total)| name == "com.anatawa12.package" && *total == Some(size))
+        );
+        assert!(downloads.windows(2).all(|x| x[0].1 < x[1].1));
+        assert_eq!(downloads.last().unwrap().1, size);
+
+        // package.json and the code
+        assert_eq!(
+            *progress.extracts.lock().unwrap(),
+            vec![
+                ("com.anatawa12.package".into(), 1, 2),
+                ("com.anatawa12.package".into(), 2, 2),
+            ]
+        );
+    })
+}
+
+#[test]
+fn cancel_install_while_downloading() {
+    block_on(async {
+        let http = ZipHttpClient::new(package_zip("code").await);
+        let env = VirtualFileSystem::new();
+        let token = CancellationToken::new();
+        let progress = RecordingProgress {
+            cancel_on_download: Some(token.clone()),
+            ..RecordingProgress::default()
+        };
+        let installer = PackageInstaller::new(&env, Some(&http)).with_progress(&progress);
+        let repository = repository(json!({}));
+
+        let mut project = VirtualProjectBuilder::new().build().await.unwrap();
+        let collection = PackageCollectionBuilder::new().build();
+        let changes = project
+            .add_package_request(
+                &collection,
+                &[package(&repository)],
+                AddPackageOperation::InstallToDependencies,
+                false,
+            )
+            .await
+            .unwrap();
+        let err = project
+            .apply_pending_changes_cancellable(&installer, changes, &token)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::Interrupted);
+        // the download is stopped after the first report, and nothing is extracted
+        assert_eq!(progress.downloads.lock().unwrap().len(), 1);
+        assert!(progress.extracts.lock().unwrap().is_empty());
+        // the partially downloaded file is not kept as the cache
+        assert_not_found(&env, ZIP_PATH).await;
+        assert_not_found(project.io(), "Packages/com.anatawa12.package").await;
+    })
+}
@@ -1,6 +1,13 @@
-use crate::common::VirtualProjectBuilder;
+use crate::common::{
+    PackageCollectionBuilder, VirtualEnvironment, VirtualFileSystem, VirtualProjectBuilder,
+};
+use futures::AsyncReadExt;
 use futures::executor::block_on;
+use std::io;
+use vrc_get_vpm::io::IoTrait;
+use vrc_get_vpm::unity_project::{EmbeddedCollection, UnsupportedManifestVersion};
 use vrc_get_vpm::version::{ReleaseType, UnityVersion, Version};
+use vrc_get_vpm::{PackageCollection, PackageManifest, VersionSelector};
 
 mod common;
 
@@ -40,6 +47,29 @@ fn read_version_name() {
     })
 }
 
+#[test]
+fn read_version_name_with_unknown_release_type() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "ProjectSettings/ProjectVersion.txt",
+                "m_EditorVersion: 2022.3.6r2\n",
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let unity = project.unity_version().unwrap();
+        assert_eq!(
+            unity,
+            UnityVersion::new(2022, 3, 6, ReleaseType::Unknown(b'r'), 2)
+        );
+        // unknown release types are sorted after the known ones
+        assert!(unity > UnityVersion::new(2022, 3, 6, ReleaseType::Experimental, 9));
+        assert!(unity < UnityVersion::new(2022, 3, 7, ReleaseType::Alpha, 1));
+    })
+}
+
 #[test]
 fn read_package_json_with_bad_url() {
     block_on(async {
@@ -66,3 +96,450 @@ fn read_package_json_with_bad_url() {
         assert_eq!(package_json.name(), "com.anatawa12.package");
     })
 }
+
+#[test]
+fn read_embedded_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "Packages/vpm-manifest.json",
+                r#"{
+                    "dependencies": {
+                        "com.anatawa12.package": { "version": "1.0.0" }
+                    },
+                    "locked": {
+                        "com.anatawa12.package": {
+                            "version": "1.0.0",
+                            "dependencies": {},
+                            "vrc-get-embedded": {
+                                "name": "com.anatawa12.package",
+                                "version": "1.0.0",
+                                "url": "https://example.com/package-1.0.0.zip"
+                            }
+                        },
+                        "com.anatawa12.not-embedded": {
+                            "version": "1.0.0",
+                            "dependencies": {}
+                        }
+                    }
+                }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = EmbeddedCollection::from_lockfile(&project).unwrap();
+
+        let package = collection
+            .find_package_by_name(
+                "com.anatawa12.package",
+                VersionSelector::specific_version(&Version::new(1, 0, 0)),
+            )
+            .unwrap();
+        assert_eq!(package.name(), "com.anatawa12.package");
+        assert_eq!(
+            package.package_json().url().map(|x| x.as_str()),
+            Some("https://example.com/package-1.0.0.zip")
+        );
+        assert!(
+            collection
+                .find_packages("com.anatawa12.not-embedded")
+                .next()
+                .is_none()
+        );
+    })
+}
+
+#[test]
+fn rebuild_lockfile_from_disk() {
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new()
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .add_package_json(
+                "com.anatawa12.package",
+                r#"{
+                    "name": "com.anatawa12.package",
+                    "version": "1.1.0",
+                    "vpmDependencies": { "com.anatawa12.library": "^1.0.0" }
+                }"#,
+            )
+            .add_package_json(
+                "com.anatawa12.library",
+                r#"{ "name": "com.anatawa12.library", "version": "1.0.0" }"#,
+            )
+            .add_package_json(
+                "com.anatawa12.unknown",
+                r#"{ "name": "com.anatawa12.unknown", "version": "1.0.0" }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let result = project.rebuild_lockfile(&collection);
+
+        assert_eq!(
+            result.updated,
+            vec![("com.anatawa12.package".into(), Version::new(1, 1, 0))]
+        );
+        assert_eq!(
+            result.locked,
+            vec![("com.anatawa12.library".into(), Version::new(1, 0, 0))]
+        );
+        assert_eq!(result.unmatched, vec!["com.anatawa12.unknown".into()]);
+
+        let package = project.get_locked("com.anatawa12.package").unwrap();
+        assert_eq!(package.version(), &Version::new(1, 1, 0));
+        assert!(package.dependencies().contains_key("com.anatawa12.library"));
+        assert!(project.is_locked("com.anatawa12.library"));
+        assert!(!project.is_locked("com.anatawa12.unknown"));
+        assert_eq!(project.unlocked_packages().len(), 1);
+    })
+}
+
+#[test]
+fn verify_integrity_of_installed_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.outdated", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.missing", Version::new(1, 0, 0), &[])
+            .add_package_json(
+                "com.anatawa12.package",
+                r#"{ "name": "com.anatawa12.package", "version": "1.0.0" }"#,
+            )
+            .add_package_json(
+                "com.anatawa12.outdated",
+                r#"{ "name": "com.anatawa12.outdated", "version": "0.9.0" }"#,
+            )
+            .add_package_json(
+                "com.anatawa12.unlocked",
+                r#"{ "name": "com.anatawa12.unlocked", "version": "1.0.0" }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let report = project.verify_integrity();
+
+        assert!(!report.is_ok());
+        assert_eq!(report.missing, vec!["com.anatawa12.missing".into()]);
+        assert_eq!(
+            report.version_mismatches,
+            vec![(
+                "com.anatawa12.outdated".into(),
+                Version::new(1, 0, 0),
+                Version::new(0, 9, 0)
+            )]
+        );
+        assert_eq!(report.unlocked, vec!["com.anatawa12.unlocked".into()]);
+    })
+}
+
+#[test]
+fn remove_broken_packages() {
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new()
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.partial", Version::new(1, 0, 0), &[])
+            .add_package_json(
+                "com.anatawa12.package",
+                r#"{ "name": "com.anatawa12.package", "version": "1.0.0" }"#,
+            )
+            .add_file("Packages/com.anatawa12.partial/Runtime/Script.cs", "")
+            .add_package_json("com.anatawa12.invalid", "{")
+            .add_package_json(
+                "com.anatawa12.embedded",
+                r#"{ "name": "com.anatawa12.embedded", "version": "1.0.0" }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let broken = project.scan_broken_packages();
+        assert_eq!(broken.len(), 2);
+        assert_eq!(broken[0].dir_name.as_ref(), "com.anatawa12.invalid");
+        assert!(!broken[0].locked);
+        assert_eq!(broken[1].dir_name.as_ref(), "com.anatawa12.partial");
+        assert!(broken[1].locked);
+
+        project.remove_broken_packages(&broken).await.unwrap();
+
+        assert!(project.scan_broken_packages().is_empty());
+        let io = project.io();
+        assert!(
+            io.metadata("Packages/com.anatawa12.invalid".as_ref())
+                .await
+                .is_err()
+        );
+        assert!(
+            io.metadata("Packages/com.anatawa12.partial".as_ref())
+                .await
+                .is_err()
+        );
+        assert!(
+            io.metadata("Packages/com.anatawa12.embedded".as_ref())
+                .await
+                .is_ok()
+        );
+        assert!(
+            io.metadata("Packages/com.anatawa12.package".as_ref())
+                .await
+                .is_ok()
+        );
+        assert_eq!(
+            project.verify_integrity().missing,
+            vec!["com.anatawa12.partial".into()]
+        );
+    })
+}
+
+#[test]
+fn list_packages_from_scoped_registries() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_locked("com.example.both", Version::new(1, 0, 0), &[])
+            .add_file(
+                "Packages/manifest.json",
+                r#"{
+                    "dependencies": {
+                        "com.example.both": "1.0.0",
+                        "com.example.tools.migratable": "1.0.0",
+                        "com.example.upm-only": "1.0.0",
+                        "com.unity.timeline": "1.2.3"
+                    },
+                    "scopedRegistries": [
+                        {
+                            "name": "Example",
+                            "url": "https://registry.example.com",
+                            "scopes": ["com.example"]
+                        },
+                        {
+                            "name": "Example Tools",
+                            "url": "https://tools.example.com",
+                            "scopes": ["com.example.tools"]
+                        },
+                        { "name": "Broken", "scopes": "com.unity" }
+                    ]
+                }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.example.both",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.example.tools.migratable",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let packages = project.scoped_registry_packages(&collection);
+        let names = packages.iter().map(|x| x.name.as_ref()).collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec![
+                "com.example.both",
+                "com.example.tools.migratable",
+                "com.example.upm-only",
+            ]
+        );
+
+        assert!(packages[0].is_managed_twice());
+        assert_eq!(packages[0].registry_name.as_ref(), "Example");
+
+        assert!(packages[1].can_migrate_to_vpm());
+        assert_eq!(packages[1].registry_name.as_ref(), "Example Tools");
+        assert_eq!(
+            packages[1].registry_url.as_ref(),
+            "https://tools.example.com"
+        );
+
+        assert!(!packages[2].is_managed_twice());
+        assert!(!packages[2].can_migrate_to_vpm());
+    })
+}
+
+#[test]
+fn dependency_tree_of_locked_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[
+                    ("com.vrchat.base", "1.0.0"),
+                    ("com.anatawa12.cycle", "1.0.0"),
+                ],
+            )
+            .add_locked(
+                "com.anatawa12.cycle",
+                Version::new(1, 0, 0),
+                &[
+                    ("com.vrchat.avatars", "1.0.0"),
+                    ("com.vrchat.base", "^2.0.0"),
+                ],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.orphan", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let tree = project.dependency_tree();
+
+        assert_eq!(tree.len(), 2);
+
+        let avatars = &tree[0];
+        assert_eq!(avatars.name.as_ref(), "com.vrchat.avatars");
+        assert!(avatars.satisfied);
+        assert!(!avatars.deduplicated);
+        assert_eq!(avatars.children.len(), 2);
+
+        let base = &avatars.children[0];
+        assert_eq!(base.name.as_ref(), "com.vrchat.base");
+        assert_eq!(base.version, Some(Version::new(1, 0, 0)));
+        assert!(base.satisfied);
+
+        let cycle = &avatars.children[1];
+        assert_eq!(cycle.name.as_ref(), "com.anatawa12.cycle");
+        assert_eq!(cycle.children.len(), 2);
+        assert_eq!(cycle.children[0].name.as_ref(), "com.vrchat.avatars");
+        assert!(cycle.children[0].deduplicated);
+        assert!(cycle.children[0].children.is_empty());
+        assert_eq!(cycle.children[1].name.as_ref(), "com.vrchat.base");
+        assert!(cycle.children[1].deduplicated);
+        assert!(!cycle.children[1].satisfied);
+
+        let orphan = &tree[1];
+        assert_eq!(orphan.name.as_ref(), "com.anatawa12.orphan");
+        assert_eq!(orphan.range, None);
+        assert!(orphan.satisfied);
+    })
+}
+
+#[test]
+fn load_manifest_without_version() {
+    block_on(async {
+        // the layout VCC writes, with locked packages without dependencies
+        let project = VirtualProjectBuilder::new()
+            .add_file(
+                "Packages/vpm-manifest.json",
+                r#"{
+                    "dependencies": {
+                        "com.anatawa12.package": { "version": "1.0.0" }
+                    },
+                    "locked": {
+                        "com.anatawa12.package": { "version": "1.0.0" }
+                    }
+                }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        assert_eq!(project.dependencies().count(), 1);
+        let locked = project.get_locked("com.anatawa12.package").unwrap();
+        assert_eq!(locked.version(), &Version::new(1, 0, 0));
+    })
+}
+
+#[test]
+fn fail_loading_newer_manifest_version() {
+    block_on(async {
+        let Err(err) = VirtualProjectBuilder::new()
+            .add_file(
+                "Packages/vpm-manifest.json",
+                r#"{
+                    "vrc-get-manifest-version": 2,
+                    "dependencies": {},
+                    "locked": {}
+                }"#,
+            )
+            .build()
+            .await
+        else {
+            panic!("loading the manifest should fail");
+        };
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let unsupported = (err.get_ref())
+            .and_then(|x| x.downcast_ref::<UnsupportedManifestVersion>())
+            .unwrap();
+        assert_eq!(unsupported.version(), 2);
+        assert_eq!(unsupported.supported_version(), 1);
+    })
+}
+
+#[test]
+fn keep_unknown_manifest_fields() {
+    block_on(async {
+        let mut project = VirtualProjectBuilder::new()
+            .add_file(
+                "Packages/vpm-manifest.json",
+                r#"{
+                    "vrc-get-manifest-version": 1,
+                    "unknownTopLevel": "kept",
+                    "dependencies": {
+                        "com.anatawa12.package": { "version": "1.0.0", "unknownDependency": 1 }
+                    },
+                    "locked": {
+                        "com.anatawa12.package": {
+                            "version": "1.0.0",
+                            "dependencies": {},
+                            "unknownLocked": true
+                        },
+                        "com.anatawa12.removed": { "version": "1.0.0", "dependencies": {} }
+                    }
+                }"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let env = VirtualEnvironment::new(VirtualFileSystem::new());
+        let request = project
+            .remove_request(&["com.anatawa12.removed"])
+            .await
+            .unwrap();
+        project.apply_pending_changes(&env, request).await.unwrap();
+
+        let mut manifest = String::new();
+        (project
+            .io()
+            .open("Packages/vpm-manifest.json".as_ref())
+            .await)
+            .unwrap()
+            .read_to_string(&mut manifest)
+            .await
+            .unwrap();
+        let manifest = serde_json::from_str::<serde_json::Value>(&manifest).unwrap();
+
+        assert_eq!(manifest["vrc-get-manifest-version"], 1);
+        assert_eq!(manifest["unknownTopLevel"], "kept");
+        assert_eq!(
+            manifest["dependencies"]["com.anatawa12.package"]["unknownDependency"],
+            1
+        );
+        assert_eq!(
+            manifest["locked"]["com.anatawa12.package"]["unknownLocked"],
+            true
+        );
+        assert!(manifest["locked"].get("com.anatawa12.removed").is_none());
+    })
+}
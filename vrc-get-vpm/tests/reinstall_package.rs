@@ -0,0 +1,84 @@
+use crate::common::*;
+use futures::executor::block_on;
+use vrc_get_vpm::PackageManifest;
+use vrc_get_vpm::version::Version;
+
+mod common;
+
+#[test]
+fn reinstall_all_keeps_unused() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.unused", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.unused",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let result = project.reinstall_all_request(&collection).await.unwrap();
+
+        assert_eq!(result.package_changes().len(), 2);
+        assert_install_only(
+            &result,
+            &collection.get_package("com.anatawa12.package", Version::new(1, 0, 0)),
+        );
+        assert_install_only(
+            &result,
+            &collection.get_package("com.anatawa12.unused", Version::new(1, 0, 0)),
+        );
+    })
+}
+
+#[test]
+fn verify_installed_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.outdated", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.missing", Version::new(1, 0, 0), &[])
+            .add_package_json(
+                "com.anatawa12.package",
+                r#"{"name":"com.anatawa12.package","version":"1.0.0"}"#,
+            )
+            .add_package_json(
+                "com.anatawa12.outdated",
+                r#"{"name":"com.anatawa12.outdated","version":"0.9.0"}"#,
+            )
+            .build()
+            .await
+            .unwrap();
+
+        let broken = project
+            .verify_installed(&[
+                "com.anatawa12.package",
+                "com.anatawa12.outdated",
+                "com.anatawa12.missing",
+                "com.anatawa12.not-locked",
+            ])
+            .await;
+
+        assert_eq!(
+            broken,
+            vec![
+                Box::<str>::from("com.anatawa12.outdated"),
+                "com.anatawa12.missing".into(),
+            ]
+        );
+    })
+}
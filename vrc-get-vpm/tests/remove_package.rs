@@ -138,3 +138,91 @@ fn remove_referenced_legacy_package() {
         );
     })
 }
+
+#[test]
+fn reverse_dependencies_of_package() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_dependency("com.vrchat.base", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "1.0.0")],
+            )
+            .add_locked(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let base = project.reverse_dependencies("com.vrchat.base");
+        assert!(base.root);
+        assert_eq!(
+            base.packages,
+            vec!["com.anatawa12.tool".into(), "com.vrchat.avatars".into()]
+        );
+
+        let avatars = project.reverse_dependencies("com.vrchat.avatars");
+        assert!(avatars.root);
+        assert!(avatars.packages.is_empty());
+
+        assert!(
+            project
+                .reverse_dependencies("com.anatawa12.tool")
+                .is_empty()
+        );
+    })
+}
+
+#[test]
+fn remove_keeping_orphans() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.vrchat.avatars", Version::new(1, 0, 0))
+            .add_dependency("com.anatawa12.tool", Version::new(1, 0, 0))
+            .add_locked(
+                "com.vrchat.avatars",
+                Version::new(1, 0, 0),
+                &[("com.vrchat.base", "1.0.0")],
+            )
+            .add_locked(
+                "com.anatawa12.tool",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "1.0.0")],
+            )
+            .add_locked("com.vrchat.base", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let result = project
+            .remove_request_with_orphans(&["com.vrchat.avatars"], false)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            result.removed_packages(),
+            vec![("com.vrchat.avatars", RemoveReason::Requested)]
+        );
+
+        let result = project
+            .remove_request_with_orphans(&["com.vrchat.avatars"], true)
+            .await
+            .unwrap();
+
+        // library is still required by tool
+        assert_eq!(
+            result.removed_packages(),
+            vec![
+                ("com.vrchat.avatars", RemoveReason::Requested),
+                ("com.vrchat.base", RemoveReason::Unused),
+            ]
+        );
+    })
+}
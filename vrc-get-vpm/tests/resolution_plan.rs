@@ -0,0 +1,106 @@
+use crate::common::*;
+use futures::executor::block_on;
+use std::io;
+use std::path::Path;
+use vrc_get_vpm::io::IoTrait;
+use vrc_get_vpm::unity_project::{ProjectChangedSincePlan, ProjectOperation};
+use vrc_get_vpm::version::Version;
+
+mod common;
+
+async fn build_project() -> vrc_get_vpm::UnityProject<VirtualFileSystem> {
+    VirtualProjectBuilder::new()
+        .add_dependency("com.anatawa12.gists", Version::new(1, 0, 0))
+        .add_dependency("com.anatawa12.tool", Version::new(1, 0, 0))
+        .add_locked("com.anatawa12.gists", Version::new(1, 0, 0), &[])
+        .add_locked("com.anatawa12.tool", Version::new(1, 0, 0), &[])
+        .add_file(
+            "Packages/com.anatawa12.gists/package.json",
+            r#"{"name":"com.anatawa12.gists","version":"1.0.0"}"#,
+        )
+        .build()
+        .await
+        .unwrap()
+}
+
+#[test]
+fn plan_does_not_touch_files() {
+    block_on(async {
+        let mut project = build_project().await;
+        let collection = PackageCollectionBuilder::new().build();
+        let manifest = read_to_string(project.io(), "Packages/vpm-manifest.json").await;
+
+        let plan = project
+            .plan(
+                &collection,
+                ProjectOperation::Remove {
+                    packages: &["com.anatawa12.gists"],
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(plan.changes().package_changes().len(), 1);
+        assert!(project.is_locked("com.anatawa12.gists"));
+        let metadata = (project.io())
+            .metadata(Path::new("Packages/com.anatawa12.gists"))
+            .await;
+        assert!(metadata.is_ok());
+        assert_eq!(
+            read_to_string(project.io(), "Packages/vpm-manifest.json").await,
+            manifest
+        );
+
+        project.apply(&CheckOnlyInstaller(&[]), plan).await.unwrap();
+
+        assert!(!project.is_locked("com.anatawa12.gists"));
+        let metadata = (project.io())
+            .metadata(Path::new("Packages/com.anatawa12.gists"))
+            .await;
+        assert_eq!(metadata.unwrap_err().kind(), io::ErrorKind::NotFound);
+    })
+}
+
+#[test]
+fn refuse_plan_made_before_project_changes() {
+    block_on(async {
+        let mut project = build_project().await;
+        let collection = PackageCollectionBuilder::new().build();
+
+        let remove_gists = project
+            .plan(
+                &collection,
+                ProjectOperation::Remove {
+                    packages: &["com.anatawa12.gists"],
+                },
+            )
+            .await
+            .unwrap();
+        let remove_tool = project
+            .plan(
+                &collection,
+                ProjectOperation::Remove {
+                    packages: &["com.anatawa12.tool"],
+                },
+            )
+            .await
+            .unwrap();
+
+        project
+            .apply(&CheckOnlyInstaller(&[]), remove_tool)
+            .await
+            .unwrap();
+        let err = project
+            .apply(&CheckOnlyInstaller(&[]), remove_gists)
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        let changed = (err.get_ref())
+            .and_then(|x| x.downcast_ref::<ProjectChangedSincePlan>())
+            .unwrap();
+        assert_eq!(changed.packages, vec!["com.anatawa12.tool".into()]);
+        // the outdated plan is not applied
+        assert!(project.is_locked("com.anatawa12.gists"));
+    })
+}
@@ -1,7 +1,18 @@
 use crate::common::*;
 use futures::executor::block_on;
-use vrc_get_vpm::PackageManifest;
-use vrc_get_vpm::version::Version;
+use serde_json::json;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use vrc_get_vpm::repository::{LocalCachedRepository, RemoteRepository};
+use vrc_get_vpm::unity_project::{
+    AddPackageErr, AddPackageOperation, CachedPackageCollection, ConflictContext, ConflictDecision,
+    ConflictStrategy, DependencyEdge, DropRequirement, HypotheticalCollection, LockedPreference,
+    PackageResolutionResult, ResolutionOptions, ResolvePackageErr, dependency_closure,
+};
+use vrc_get_vpm::version::{ReleaseType, UnityVersion, Version};
+use vrc_get_vpm::{
+    DynPackageCollection, PackageCollection as _, PackageInfo, PackageManifest, VersionSelector,
+};
 
 mod common;
 
@@ -252,3 +263,1333 @@ fn resolve_both_dependencies_of_unlocked_and_dependencies() {
         assert_installing_to_both(&result, &library2);
     })
 }
+
+#[test]
+fn resolve_from_scratch_ignores_locked() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "1.1.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 1, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let result = project.resolve_from_scratch(&collection).unwrap();
+
+        let version_map = result.version_map();
+        assert_eq!(version_map.len(), 2);
+        assert_eq!(version_map["com.anatawa12.library"], Version::new(1, 1, 0));
+        assert_eq!(version_map["com.anatawa12.package"], Version::new(1, 1, 0));
+        assert_eq!(result.conflicts.len(), 0);
+
+        let dependency_map = result.dependency_map();
+        assert_eq!(
+            dependency_map["com.anatawa12.package"]["com.anatawa12.library"].to_string(),
+            "1.1.0"
+        );
+    })
+}
+
+/// The collection of the packages in the repositories, searched in the order of the repositories.
+struct RepositoryCollection(Vec<LocalCachedRepository>);
+
+/// Creates the repository with the id and the packages, which are `name@version` with dependencies.
+fn repository(id: &str, packages: &[(&str, serde_json::Value)]) -> LocalCachedRepository {
+    let mut json = serde_json::Map::new();
+    for (package, dependencies) in packages {
+        let (name, version) = package.split_once('@').unwrap();
+        let manifest = json!({
+            "name": name,
+            "version": version,
+            "vpmDependencies": dependencies,
+        });
+        let versions = json
+            .entry(name)
+            .or_insert_with(|| json!({ "versions": {} }));
+        versions["versions"][version] = manifest;
+    }
+    let serde_json::Value::Object(repo) = json!({
+        "id": id,
+        "url": format!("https://{id}.example.com/vpm.json"),
+        "packages": json,
+    }) else {
+        unreachable!()
+    };
+    LocalCachedRepository::new(RemoteRepository::parse(repo).unwrap(), Default::default())
+}
+
+impl vrc_get_vpm::PackageCollection for RepositoryCollection {
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        self.0.iter().flat_map(|repo| {
+            (repo.get_packages())
+                .flat_map(|x| x.all_versions())
+                .map(move |pkg| PackageInfo::remote(pkg, repo))
+        })
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        self.get_all_packages()
+            .filter(move |pkg| pkg.name() == package)
+    }
+}
+
+#[test]
+fn tiebreak_repositories_by_priority_only_among_newest() {
+    // resolves the app requiring the library, with the library in the public and private repositories
+    fn resolve(public: &[&'static str], private: &[&'static str]) -> (Version, Option<Box<str>>) {
+        let libraries = |packages: &[&'static str]| {
+            (packages.iter())
+                .map(|&package| (package, json!({})))
+                .collect::<Vec<_>>()
+        };
+        let mut public = libraries(public);
+        public.push((
+            "com.vendor.app@1.0.0",
+            json!({ "com.shared.lib": ">=1.0.0" }),
+        ));
+        let collection = RepositoryCollection(vec![
+            repository("com.public", &public),
+            repository("com.private", &libraries(private)),
+        ]);
+        block_on(async {
+            let project = VirtualProjectBuilder::new().build().await.unwrap();
+            let app = collection.find_packages("com.vendor.app").next().unwrap();
+            let options = ResolutionOptions::new().tiebreak_repo_priority(vec!["com.private"]);
+            let result = project
+                .add_package_request_with_options(
+                    &collection,
+                    &[app],
+                    AddPackageOperation::InstallToDependencies,
+                    &options,
+                )
+                .await
+                .unwrap();
+            let resolution = result.resolution().unwrap();
+            let version = resolution.version_map()["com.shared.lib"].clone();
+            let source = resolution.package_sources()["com.shared.lib"].clone();
+            (version, source)
+        })
+    }
+
+    // the same version is chosen from the private repository
+    assert_eq!(
+        resolve(
+            &["com.shared.lib@1.0.0", "com.shared.lib@1.1.0"],
+            &["com.shared.lib@1.1.0"]
+        ),
+        (Version::new(1, 1, 0), Some("com.private".into()))
+    );
+
+    // the newest version is still chosen even if the private repository only has older ones
+    assert_eq!(
+        resolve(
+            &["com.shared.lib@1.0.0", "com.shared.lib@1.1.0"],
+            &["com.shared.lib@1.0.0"]
+        ),
+        (Version::new(1, 1, 0), Some("com.public".into()))
+    );
+}
+
+#[test]
+fn tiebreak_repositories_by_naming_convention() {
+    // resolves the app from the first repository with the library in both repositories, and returns
+    // the repository the library is chosen from with the recorded tiebreaks
+    fn resolve(app: &str, mirror: &str, prefer_naming_convention: bool) -> (String, Vec<String>) {
+        let app_version = format!("{app}@1.0.0");
+        let lib = ("com.shared.lib@1.0.0", json!({}));
+        let collection = RepositoryCollection(vec![
+            repository(
+                mirror,
+                &[
+                    (&app_version, json!({ "com.shared.lib": "^1.0.0" })),
+                    lib.clone(),
+                ],
+            ),
+            repository("com.vendor", &[lib]),
+        ]);
+        block_on(async {
+            let project = VirtualProjectBuilder::new().build().await.unwrap();
+            let app = collection.find_packages(app).next().unwrap();
+            let options =
+                ResolutionOptions::new().prefer_naming_convention(prefer_naming_convention);
+            let result = project
+                .add_package_request_with_options(
+                    &collection,
+                    &[app],
+                    AddPackageOperation::InstallToDependencies,
+                    &options,
+                )
+                .await
+                .unwrap();
+            let resolution = result.resolution().unwrap();
+            let source = resolution.package_sources()["com.shared.lib"].clone();
+            let tiebreaks = (resolution.naming_tiebreaks.iter())
+                .map(|(package, id, installed)| format!("{package} from {id} like {installed}"))
+                .collect();
+            (source.unwrap().into(), tiebreaks)
+        })
+    }
+
+    // the first repository is used without the naming convention
+    assert_eq!(
+        resolve("com.vendor.app", "org.mirror", false),
+        ("org.mirror".into(), vec![])
+    );
+
+    // the repository sharing the most segments with the installed packages is preferred
+    assert_eq!(
+        resolve("com.vendor.app", "org.mirror", true),
+        (
+            "com.vendor".into(),
+            vec!["com.shared.lib from com.vendor like com.vendor.app".into()]
+        )
+    );
+
+    // no repository shares the segments
+    assert_eq!(
+        resolve("net.example.app", "org.mirror", true),
+        ("org.mirror".into(), vec![])
+    );
+
+    // both repositories share two segments, so the first one is kept
+    assert_eq!(
+        resolve("com.vendor.app", "com.vendor.mirror", true),
+        ("com.vendor.mirror".into(), vec![])
+    );
+}
+
+#[test]
+fn find_package_from_repository_with_priority() {
+    let collection = RepositoryCollection(vec![
+        repository(
+            "com.public",
+            &[
+                ("com.shared.lib@1.0.0", json!({})),
+                ("com.shared.lib@1.1.0", json!({})),
+            ],
+        ),
+        repository("com.private", &[("com.shared.lib@1.0.0", json!({}))]),
+    ]);
+    let find = |version: &Version, priority: &[&str]| {
+        collection
+            .find_package_by_name_with_priority(
+                "com.shared.lib",
+                VersionSelector::specific_version(version),
+                priority,
+            )
+            .map(|x| x.repository_id().unwrap())
+    };
+
+    // the found version from the repository listed first in the priority
+    assert_eq!(find(&Version::new(1, 0, 0), &[]), Some("com.public"));
+    assert_eq!(
+        find(&Version::new(1, 0, 0), &["com.private"]),
+        Some("com.private")
+    );
+    assert_eq!(
+        find(&Version::new(1, 0, 0), &["com.public", "com.private"]),
+        Some("com.public")
+    );
+    // the priority does not change the version
+    assert_eq!(
+        find(&Version::new(1, 1, 0), &["com.private"]),
+        Some("com.public")
+    );
+    assert_eq!(find(&Version::new(2, 0, 0), &["com.private"]), None);
+}
+
+#[test]
+fn trace_repository_of_resolved_packages() {
+    let collection = RepositoryCollection(vec![
+        repository(
+            "com.vendor",
+            &[(
+                "com.vendor.app@1.0.0",
+                json!({ "com.shared.lib": "^1.0.0" }),
+            )],
+        ),
+        repository("com.shared", &[("com.shared.lib@1.0.0", json!({}))]),
+    ]);
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+        let app = collection.find_packages("com.vendor.app").next().unwrap();
+        let result = project
+            .add_package_request_with_options(
+                &collection,
+                &[app],
+                AddPackageOperation::InstallToDependencies,
+                &ResolutionOptions::new().diagnostics(true),
+            )
+            .await
+            .unwrap();
+
+        let diagnostics = result.resolution().unwrap().diagnostics.as_ref();
+        let trace = &diagnostics.unwrap().trace;
+        assert_eq!(
+            trace["com.vendor.app"].repository.as_deref(),
+            Some("com.vendor")
+        );
+        assert_eq!(
+            trace["com.shared.lib"].repository.as_deref(),
+            Some("com.shared")
+        );
+    })
+}
+
+#[test]
+fn prefer_locked_versions() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "^1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 3, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 2, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 2, 0))
+                    .add_vpm_dependency("com.anatawa12.library", ">=1.0.0 <1.3.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 1, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.3.0"),
+            )
+            .build();
+
+        let old_tool = collection.get_package("com.anatawa12.tool", Version::new(1, 1, 0));
+        let new_tool = collection.get_package("com.anatawa12.tool", Version::new(1, 2, 0));
+
+        // the library is upgraded for the old tool, and then the new tool accepts the locked one
+        let library_version = |prefer_locked| {
+            let options = ResolutionOptions::new().prefer_locked_versions(prefer_locked);
+            let mut resolution = project.incremental_resolution(&collection, &options);
+            resolution.request_package(old_tool);
+            resolution.resolve().unwrap();
+            resolution.request_package(new_tool);
+            let result = resolution.resolve().unwrap();
+            assert!(result.conflicts.is_empty());
+            (result.new_packages.iter())
+                .find(|x| x.name() == "com.anatawa12.library")
+                .map(|x| x.version().clone())
+        };
+
+        assert_eq!(library_version(false), Some(Version::new(1, 2, 0)));
+        assert_eq!(library_version(true), Some(Version::new(1, 0, 0)));
+    })
+}
+
+#[test]
+fn locked_preference_of_root_dependencies() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency_range("com.vrchat.base", "<1.5.0")
+            .add_locked("com.vrchat.base", Version::new(1, 5, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 5, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.vrchat.base",
+                Version::new(1, 4, 0),
+            ))
+            .build();
+
+        let base_version = |preference| {
+            let options = ResolutionOptions::new().locked_preference(preference);
+            let mut resolution = project.incremental_resolution(&collection, &options);
+            let result = resolution.resolve().unwrap();
+            (result.new_packages.iter())
+                .find(|x| x.name() == "com.vrchat.base")
+                .map(|x| x.version().clone())
+        };
+
+        // the locked version is kept as is
+        assert_eq!(base_version(LockedPreference::Floor), None);
+        assert_eq!(base_version(LockedPreference::Prefer), None);
+        // the requested range drives the selection
+        assert_eq!(
+            base_version(LockedPreference::Ignore),
+            Some(Version::new(1, 4, 0))
+        );
+    })
+}
+
+/// The collection recording the packages fetched by the exact version
+struct FetchRecordingCollection {
+    base: PackageCollection,
+    fetched: RefCell<Vec<(String, Version)>>,
+}
+
+impl vrc_get_vpm::PackageCollection for FetchRecordingCollection {
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        self.base.get_all_packages()
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        self.base.find_packages(package)
+    }
+
+    fn find_package_by_name(
+        &self,
+        package: &str,
+        package_selector: VersionSelector,
+    ) -> Option<PackageInfo> {
+        self.base.find_package_by_name(package, package_selector)
+    }
+
+    fn fetch_package(&self, package: &str, version: &Version) -> Option<PackageInfo> {
+        (self.fetched.borrow_mut()).push((package.to_owned(), version.clone()));
+        self.base.fetch_package(package, version)
+    }
+}
+
+#[test]
+fn fetch_pinned_packages_by_version() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "^1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = FetchRecordingCollection {
+            base: PackageCollectionBuilder::new()
+                .add(
+                    PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                        .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+                )
+                .add(PackageManifest::new(
+                    "com.anatawa12.library",
+                    Version::new(1, 2, 0),
+                ))
+                .add(PackageManifest::new(
+                    "com.anatawa12.library",
+                    Version::new(1, 0, 0),
+                ))
+                .build(),
+            fetched: RefCell::new(Vec::new()),
+        };
+
+        let options = ResolutionOptions::new().pins(HashMap::from([(
+            "com.anatawa12.library",
+            Version::new(1, 2, 0),
+        )]));
+        let mut resolution = project.incremental_resolution(&collection, &options);
+        let result = resolution.resolve().unwrap();
+
+        let library = (result.new_packages.iter())
+            .find(|x| x.name() == "com.anatawa12.library")
+            .unwrap();
+        assert_eq!(library.version(), &Version::new(1, 2, 0));
+        assert!(
+            (collection.fetched.borrow().iter())
+                .any(|(name, version)| name == "com.anatawa12.library"
+                    && version == &Version::new(1, 2, 0))
+        );
+    })
+}
+
+/// The collection merging the collections chosen at runtime
+struct MergedCollection<'a> {
+    collections: Vec<&'a dyn DynPackageCollection>,
+}
+
+impl vrc_get_vpm::PackageCollection for MergedCollection<'_> {
+    fn get_all_packages(&self) -> impl Iterator<Item = PackageInfo> {
+        (self.collections.iter()).flat_map(|x| x.dyn_get_all_packages())
+    }
+
+    fn find_packages(&self, package: &str) -> impl Iterator<Item = PackageInfo> {
+        (self.collections.iter()).flat_map(move |x| x.dyn_find_packages(package))
+    }
+
+    fn fetch_package(&self, package: &str, version: &Version) -> Option<PackageInfo> {
+        (self.collections.iter()).find_map(|x| x.dyn_fetch_package(package, version))
+    }
+}
+
+#[test]
+fn resolve_with_dyn_collection() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "^1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let packages = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .build();
+        let libraries = FetchRecordingCollection {
+            base: PackageCollectionBuilder::new()
+                .add(PackageManifest::new(
+                    "com.anatawa12.library",
+                    Version::new(1, 2, 0),
+                ))
+                .add(PackageManifest::new(
+                    "com.anatawa12.library",
+                    Version::new(1, 0, 0),
+                ))
+                .build(),
+            fetched: RefCell::new(Vec::new()),
+        };
+
+        let merged = MergedCollection {
+            collections: vec![&packages, &libraries],
+        };
+        let collection: &dyn DynPackageCollection = &merged;
+
+        let options = ResolutionOptions::new().pins(HashMap::from([(
+            "com.anatawa12.library",
+            Version::new(1, 2, 0),
+        )]));
+        let mut resolution = project.incremental_resolution(&collection, &options);
+        let result = resolution.resolve().unwrap();
+
+        let library = (result.new_packages.iter())
+            .find(|x| x.name() == "com.anatawa12.library")
+            .unwrap();
+        assert_eq!(library.version(), &Version::new(1, 2, 0));
+        // the overridden method is called through the trait object
+        assert!(!libraries.fetched.borrow().is_empty());
+    })
+}
+
+#[test]
+fn resolve_with_hypothetical_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "^1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.helper",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        // the unreleased version of the library
+        let unreleased = PackageManifest::new("com.anatawa12.library", Version::new(2, 0, 0))
+            .add_vpm_dependency("com.anatawa12.helper", "^1.0.0");
+        let hypothetical =
+            HypotheticalCollection::new(&collection, vec![PackageInfo::hypothetical(&unreleased)]);
+
+        let library = hypothetical
+            .find_package_by_name(
+                "com.anatawa12.library",
+                VersionSelector::latest_for(None, false),
+            )
+            .unwrap();
+        assert!(library.is_hypothetical());
+        assert_eq!(library.version(), &Version::new(2, 0, 0));
+
+        let options = ResolutionOptions::new();
+        let mut resolution = project.incremental_resolution(&hypothetical, &options);
+        resolution.request_package(library);
+        let result = resolution.resolve().unwrap();
+
+        let hypothetical_packages = (result.new_packages.iter())
+            .filter(|x| x.is_hypothetical())
+            .map(|x| x.name())
+            .collect::<Vec<_>>();
+        assert_eq!(hypothetical_packages, vec!["com.anatawa12.library"]);
+        // the dependencies of the hypothetical package are resolved from the base collection
+        let helper = (result.new_packages.iter())
+            .find(|x| x.name() == "com.anatawa12.helper")
+            .unwrap();
+        assert!(!helper.is_hypothetical());
+        // the package requiring the older version conflicts with the new version
+        assert_eq!(
+            result.conflicts["com.anatawa12.library"][0]
+                .package
+                .as_ref(),
+            "com.anatawa12.package"
+        );
+    })
+}
+
+#[test]
+fn collect_resolution_stats() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.helper", "^1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.library", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.helper", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.helper",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let options = ResolutionOptions::new();
+        let mut resolution = project.incremental_resolution(&collection, &options);
+        resolution.request_package(package);
+        assert_eq!(resolution.resolve().unwrap().stats, None);
+
+        let options = ResolutionOptions::new().collect_stats(true);
+        let mut resolution = project.incremental_resolution(&collection, &options);
+        resolution.request_package(package);
+        let result = resolution.resolve().unwrap();
+
+        assert_eq!(result.new_packages.len(), 3);
+        // the helper required by both of the package and the library is looked up once
+        let stats = result.stats.unwrap();
+        assert_eq!(stats.queue_pops, 3);
+        assert_eq!(stats.add_package_calls, 3);
+        assert_eq!(stats.packages_added, 3);
+        assert_eq!(stats.packages_skipped, 0);
+        assert_eq!(stats.package_lookups, 2);
+        assert!(stats.peak_dependencies >= 3);
+    })
+}
+
+#[test]
+fn collect_dependency_edges() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.helper", Version::new(2, 0, 0))
+            .add_locked("com.anatawa12.helper", Version::new(2, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                    .add_vpm_dependency("com.anatawa12.helper", "^1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.helper",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.helper",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let options = ResolutionOptions::new();
+        let mut resolution = project.incremental_resolution(&collection, &options);
+        resolution.request_package(package);
+        let result = resolution.resolve().unwrap();
+
+        // the package requires the older helper than the project does
+        let edge = |from: &str, to: &str, range: &str, satisfied: bool| DependencyEdge {
+            from: from.into(),
+            to: to.into(),
+            range: range.parse().unwrap(),
+            satisfied,
+        };
+        assert_eq!(
+            result.dependency_edges,
+            vec![
+                edge("", "com.anatawa12.helper", ">=2.0.0", false),
+                edge(
+                    "com.anatawa12.package",
+                    "com.anatawa12.helper",
+                    "^1.0.0",
+                    true
+                ),
+                edge(
+                    "com.anatawa12.package",
+                    "com.anatawa12.library",
+                    "^1.0.0",
+                    true
+                ),
+            ]
+        );
+        assert!(result.conflicts.contains_key("com.anatawa12.helper"));
+    })
+}
+
+#[cfg(feature = "graph")]
+#[test]
+fn render_dependency_graph() {
+    use vrc_get_vpm::graph::{to_dot, to_mermaid};
+
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.helper", Version::new(2, 0, 0))
+            .add_locked("com.anatawa12.helper", Version::new(2, 0, 0), &[])
+            .add_locked("com.anatawa12.legacy", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.helper", "^1.0.0")
+                    .add_legacy_package("com.anatawa12.legacy"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.helper",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.helper",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+        let package = collection.get_package("com.anatawa12.package", Version::new(1, 0, 0));
+
+        let options = ResolutionOptions::new();
+        let mut resolution = project.incremental_resolution(&collection, &options);
+        resolution.request_package(package);
+        let result = resolution.resolve().unwrap();
+
+        // the project requires the newer helper than the package does
+        assert_eq!(
+            to_dot(&result),
+            r#"digraph dependencies {
+    "" [label="(project)"];
+    "com.anatawa12.helper" [label="com.anatawa12.helper@1.0.0"];
+    "com.anatawa12.legacy" [label="com.anatawa12.legacy"];
+    "com.anatawa12.package" [label="com.anatawa12.package@1.0.0"];
+    "" -> "com.anatawa12.helper" [label=">=2.0.0", color=red, style=dashed];
+    "com.anatawa12.package" -> "com.anatawa12.helper" [label="^1.0.0"];
+    "com.anatawa12.package" -> "com.anatawa12.legacy" [label="replaces", style=dotted];
+}
+"#
+        );
+        assert_eq!(
+            to_mermaid(&result),
+            r#"flowchart LR
+    n0["(project)"]
+    n1["com.anatawa12.helper@1.0.0"]
+    n2["com.anatawa12.legacy"]
+    n3["com.anatawa12.package@1.0.0"]
+    n0 --x|">=2.0.0"| n1
+    n3 -->|"^1.0.0"| n1
+    n3 -.->|"replaces"| n2
+"#
+        );
+    })
+}
+
+#[test]
+fn resolve_incrementally() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked("com.anatawa12.package", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+            ))
+            .add(
+                PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.another",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+        let another = collection.get_package("com.anatawa12.another", Version::new(1, 0, 0));
+
+        fn new_package_names<'a>(result: &PackageResolutionResult<'a>) -> Vec<&'a str> {
+            result.new_packages.iter().map(|x| x.name()).collect()
+        }
+
+        let options = ResolutionOptions::new();
+        let mut resolution = project.incremental_resolution(&collection, &options);
+
+        resolution.request_package(tool);
+        let result = resolution.resolve().unwrap();
+        assert_eq!(
+            new_package_names(&result),
+            vec!["com.anatawa12.library", "com.anatawa12.tool"]
+        );
+
+        resolution.request_package(another);
+        let result = resolution.resolve().unwrap();
+        assert_eq!(
+            new_package_names(&result),
+            vec![
+                "com.anatawa12.another",
+                "com.anatawa12.library",
+                "com.anatawa12.tool"
+            ]
+        );
+
+        resolution.remove_requested_package(tool.name());
+        let result = resolution.resolve().unwrap();
+        assert_eq!(new_package_names(&result), vec!["com.anatawa12.another"]);
+    })
+}
+
+#[test]
+fn list_versions_of_package_newest_first() {
+    let collection = RepositoryCollection(vec![
+        repository(
+            "com.public",
+            &[
+                ("com.shared.lib@1.0.0", json!({})),
+                ("com.shared.lib@1.2.5", json!({})),
+            ],
+        ),
+        repository(
+            "com.private",
+            &[
+                ("com.shared.lib@1.3.0", json!({})),
+                ("com.shared.lib@1.0.0", json!({})),
+            ],
+        ),
+    ]);
+
+    let versions = (collection
+        .find_packages_by_name("com.shared.lib")
+        .into_iter())
+    .map(|x| (x.version().to_string(), x.repository_id().unwrap()))
+    .collect::<Vec<_>>();
+    // every version in every repository is listed
+    assert_eq!(versions.len(), 4);
+    assert_eq!(versions[0], ("1.3.0".into(), "com.private"));
+    assert_eq!(versions[1], ("1.2.5".into(), "com.public"));
+    assert_eq!(versions[2].0, "1.0.0");
+    assert_eq!(versions[3].0, "1.0.0");
+    assert!(collection.find_packages_by_name("com.unknown").is_empty());
+
+    // the selected version is the newest one in the list
+    let found = collection
+        .find_package_by_name("com.shared.lib", VersionSelector::latest_for(None, false))
+        .unwrap();
+    assert_eq!(found.version(), &Version::new(1, 3, 0));
+}
+
+#[test]
+fn add_root_dependencies_at_once() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new().build().await.unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let library = collection.get_package("com.anatawa12.library", Version::new(1, 0, 0));
+
+        let options = ResolutionOptions::new();
+        let mut resolution = project.incremental_resolution(&collection, &options);
+
+        let err = resolution
+            .add_root_dependencies([
+                ("com.anatawa12.library", ">=2.0.0".parse().unwrap()),
+                ("com.anatawa12.library", "<1.5.0".parse().unwrap()),
+            ])
+            .unwrap_err();
+        let AddPackageErr::IncompatibleRootDependencies {
+            package_name,
+            range,
+            other_range,
+        } = err
+        else {
+            panic!("unexpected error: {err}");
+        };
+        assert_eq!(package_name.as_ref(), "com.anatawa12.library");
+        assert_eq!(range.to_string(), ">=2.0.0");
+        assert_eq!(other_range.to_string(), "<1.5.0");
+
+        resolution
+            .add_root_dependencies([
+                ("com.anatawa12.library", ">=1.0.0".parse().unwrap()),
+                ("com.anatawa12.library", "<1.5.0".parse().unwrap()),
+            ])
+            .unwrap();
+        resolution.request_package(library);
+        let result = resolution.resolve().unwrap();
+        let requirements = &result.requirements["com.anatawa12.library"];
+        assert_eq!(requirements.len(), 1);
+        assert_eq!(requirements[0].1.to_string(), ">=1.0.0 <1.5.0");
+    })
+}
+
+#[test]
+fn dependency_closure_of_package() {
+    let collection = PackageCollectionBuilder::new()
+        .add(
+            PackageManifest::new("com.anatawa12.tool", Version::new(1, 0, 0))
+                .add_vpm_dependency("com.anatawa12.library", "^1.0.0")
+                .add_vpm_dependency("com.anatawa12.another", "^1.0.0"),
+        )
+        .add(
+            PackageManifest::new("com.anatawa12.library", Version::new(1, 1, 0))
+                .add_vpm_dependency("com.anatawa12.core", "^1.0.0"),
+        )
+        .add(PackageManifest::new(
+            "com.anatawa12.library",
+            Version::new(1, 0, 0),
+        ))
+        .add(
+            PackageManifest::new("com.anatawa12.another", Version::new(1, 0, 0))
+                .add_vpm_dependency("com.anatawa12.core", "^1.0.0"),
+        )
+        .add(PackageManifest::new(
+            "com.anatawa12.core",
+            Version::new(1, 0, 0),
+        ))
+        .add(
+            PackageManifest::new("com.anatawa12.broken", Version::new(1, 0, 0))
+                .add_vpm_dependency("com.anatawa12.missing", "^1.0.0"),
+        )
+        .build();
+
+    let tool = collection.get_package("com.anatawa12.tool", Version::new(1, 0, 0));
+    let closure = dependency_closure(tool, &collection, None, &ResolutionOptions::new()).unwrap();
+    assert_eq!(
+        (closure.iter())
+            .map(|x| (x.name(), x.version().to_string()))
+            .collect::<Vec<_>>(),
+        vec![
+            ("com.anatawa12.another", "1.0.0".to_string()),
+            ("com.anatawa12.core", "1.0.0".to_string()),
+            ("com.anatawa12.library", "1.1.0".to_string()),
+        ]
+    );
+
+    let broken = collection.get_package("com.anatawa12.broken", Version::new(1, 0, 0));
+    let err = dependency_closure(broken, &collection, None, &ResolutionOptions::new()).unwrap_err();
+    let ResolvePackageErr::DependenciesNotFound { dependencies } = err else {
+        panic!("unexpected error: {err}");
+    };
+    assert_eq!(
+        dependencies,
+        vec![Box::<str>::from("com.anatawa12.missing")]
+    );
+}
+
+#[test]
+fn check_outdated_packages() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_dependency("com.anatawa12.uptodate", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.uptodate", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let beta = Version::new_pre(1, 2, 0, "beta.1".parse().unwrap());
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", beta.clone())
+                    .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 1, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.uptodate",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let outdated = project.check_outdated(&collection);
+
+        assert_eq!(outdated.len(), 2);
+
+        assert_eq!(outdated[0].name.as_ref(), "com.anatawa12.library");
+        assert_eq!(outdated[0].installed, Version::new(1, 0, 0));
+        assert_eq!(outdated[0].latest_stable, Some(Version::new(2, 0, 0)));
+        assert_eq!(outdated[0].latest_prerelease, None);
+        assert_eq!(
+            outdated[0].held_back_by.as_deref(),
+            Some("com.anatawa12.package")
+        );
+
+        assert_eq!(outdated[1].name.as_ref(), "com.anatawa12.package");
+        assert_eq!(outdated[1].installed, Version::new(1, 0, 0));
+        assert_eq!(outdated[1].latest_stable, Some(Version::new(1, 1, 0)));
+        assert_eq!(outdated[1].latest_prerelease, Some(beta));
+        assert!(!outdated[1].is_held_back());
+    })
+}
+
+#[test]
+fn resolve_update_all_roots() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_dependency("com.anatawa12.other", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "1.0.0")],
+            )
+            .add_locked(
+                "com.anatawa12.other",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(2, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "2.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+            )
+            .add(
+                PackageManifest::new("com.anatawa12.other", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(2, 0, 0),
+            ))
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let result = project
+            .resolve_update_all(&collection, &ResolutionOptions::new())
+            .unwrap();
+
+        assert_eq!(
+            result.changes,
+            vec![
+                (
+                    "com.anatawa12.library".into(),
+                    Some(Version::new(1, 0, 0)),
+                    Version::new(2, 0, 0)
+                ),
+                (
+                    "com.anatawa12.package".into(),
+                    Some(Version::new(1, 0, 0)),
+                    Version::new(2, 0, 0)
+                ),
+            ]
+        );
+
+        // the requirement from com.anatawa12.other is not satisfied by the update
+        let conflicts = &result.resolution.conflicts["com.anatawa12.library"];
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].package.as_ref(), "com.anatawa12.other");
+    })
+}
+
+#[test]
+fn reuse_cached_resolution() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(
+                PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+                    .add_vpm_dependency("com.anatawa12.library", "1.0.0"),
+            )
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+        let collection = CachedPackageCollection::new(&collection);
+
+        let first = project
+            .resolve_update_all(&collection, &ResolutionOptions::new())
+            .unwrap();
+        assert_eq!(collection.cache().len(), 1);
+
+        let second = project
+            .resolve_update_all(&collection, &ResolutionOptions::new())
+            .unwrap();
+        assert_eq!(collection.cache().len(), 1);
+        assert_eq!(
+            first.resolution.version_map(),
+            second.resolution.version_map()
+        );
+        assert_eq!(second.resolution.new_packages.len(), 2);
+
+        // other options are resolved again
+        let options = ResolutionOptions::new().allow_prerelease(true);
+        project.resolve_update_all(&collection, &options).unwrap();
+        assert_eq!(collection.cache().len(), 2);
+    })
+}
+
+#[test]
+fn cache_only_deterministic_conflict_strategies() {
+    // the decision depends on the field, so this is not deterministic
+    struct Configured {
+        drop: bool,
+    }
+
+    impl ConflictStrategy for Configured {
+        fn on_conflict(&self, _: &ConflictContext) -> ConflictDecision {
+            if self.drop {
+                ConflictDecision::DropRequirement
+            } else {
+                ConflictDecision::GiveUp
+            }
+        }
+    }
+
+    struct AlwaysDrop(#[allow(dead_code)] u32);
+
+    impl ConflictStrategy for AlwaysDrop {
+        fn on_conflict(&self, _: &ConflictContext) -> ConflictDecision {
+            ConflictDecision::DropRequirement
+        }
+
+        fn is_deterministic(&self) -> bool {
+            true
+        }
+    }
+
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .build()
+            .await
+            .unwrap();
+
+        let collection = PackageCollectionBuilder::new()
+            .add(PackageManifest::new(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+        let collection = CachedPackageCollection::new(&collection);
+
+        let configured = Configured { drop: true };
+        let options = ResolutionOptions::new().conflict_strategy(&configured);
+        project.resolve_update_all(&collection, &options).unwrap();
+        assert_eq!(collection.cache().len(), 0);
+
+        let closure = |_: &ConflictContext| ConflictDecision::DropRequirement;
+        let options = ResolutionOptions::new().conflict_strategy(&closure);
+        project.resolve_update_all(&collection, &options).unwrap();
+        assert_eq!(collection.cache().len(), 0);
+
+        let always_drop = AlwaysDrop(1);
+        let options = ResolutionOptions::new().conflict_strategy(&always_drop);
+        project.resolve_update_all(&collection, &options).unwrap();
+        project.resolve_update_all(&collection, &options).unwrap();
+        assert_eq!(collection.cache().len(), 1);
+
+        let options = ResolutionOptions::new().conflict_strategy(&DropRequirement);
+        project.resolve_update_all(&collection, &options).unwrap();
+        assert_eq!(collection.cache().len(), 2);
+    })
+}
+
+#[test]
+fn report_project_health() {
+    block_on(async {
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "~1.0.0")],
+            )
+            .add_locked("com.anatawa12.library", Version::new(1, 0, 0), &[])
+            .add_locked("com.anatawa12.orphan", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+
+        let package = PackageManifest::new("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_vpm_dependency("com.anatawa12.library", "~1.0.0");
+        let library = PackageManifest::new("com.anatawa12.library", Version::new(1, 0, 0))
+            .with_unity(2022, 3);
+
+        let collection = PackageCollectionBuilder::new()
+            .add(package.clone())
+            .add(PackageManifest::new(
+                "com.anatawa12.library",
+                Version::new(1, 1, 0),
+            ))
+            .add(library.clone())
+            .add(PackageManifest::new(
+                "com.anatawa12.orphan",
+                Version::new(1, 0, 0),
+            ))
+            .build();
+
+        let report = project.project_health(&collection);
+        assert!(report.is_ok(), "{report:?}");
+        assert_eq!(report.conflicts, Some(HashMap::new()));
+        assert_eq!(report.orphans, Some(vec!["com.anatawa12.orphan".into()]));
+        assert_eq!(report.deprecated_selected, Some(vec![]));
+        assert_eq!(report.transitive_prereleases, Some(vec![]));
+        let held_back = (report.held_back.iter())
+            .map(|x| x.name.as_ref())
+            .collect::<Vec<_>>();
+        assert_eq!(held_back, vec!["com.anatawa12.library"]);
+        assert_eq!(
+            report.minimum_unity_version,
+            Some((
+                UnityVersion::new(2022, 3, 0, ReleaseType::Alpha, 0),
+                "com.anatawa12.library".into()
+            ))
+        );
+
+        // the report degrades without the resolution if some dependency is not found
+        let project = VirtualProjectBuilder::new()
+            .add_dependency("com.anatawa12.package", Version::new(1, 0, 0))
+            .add_locked(
+                "com.anatawa12.package",
+                Version::new(1, 0, 0),
+                &[("com.anatawa12.library", "~1.0.0")],
+            )
+            .add_locked("com.anatawa12.orphan", Version::new(1, 0, 0), &[])
+            .build()
+            .await
+            .unwrap();
+        let collection = PackageCollectionBuilder::new().add(package).build();
+
+        let report = project.project_health(&collection);
+        assert!(!report.is_ok());
+        assert!(report.resolution_error.is_some());
+        assert_eq!(report.conflicts, None);
+        assert_eq!(report.orphans, Some(vec!["com.anatawa12.orphan".into()]));
+        assert_eq!(report.held_back, vec![]);
+        assert_eq!(report.minimum_unity_version, None);
+        assert!(report.missing_packages.is_empty());
+    })
+}
@@ -0,0 +1,92 @@
+use crate::common::*;
+use vrc_get_vpm::PackageManifest;
+use vrc_get_vpm::search::{SearchMatch, SearchOptions, search_packages};
+use vrc_get_vpm::version::Version;
+
+mod common;
+
+fn collection() -> PackageCollection {
+    PackageCollectionBuilder::new()
+        .add(
+            PackageManifest::new("com.anatawa12.avatar-optimizer", Version::new(1, 0, 0))
+                .with_display_name("AAO: Avatar Optimizer"),
+        )
+        .add(
+            PackageManifest::new("com.anatawa12.avatar-optimizer", Version::new(1, 1, 0))
+                .with_display_name("AAO: Avatar Optimizer"),
+        )
+        .add(
+            PackageManifest::new(
+                "com.anatawa12.avatar-optimizer",
+                Version::new_pre(1, 2, 0, "beta.1".parse().unwrap()),
+            )
+            .with_display_name("AAO: Avatar Optimizer"),
+        )
+        .add(
+            PackageManifest::new("com.anatawa12.gists", Version::new(1, 0, 0))
+                .with_display_name("anatawa12's gists")
+                .with_description("Small tools including an optimizer for avatars"),
+        )
+        .add(PackageManifest::new("optimizer", Version::new(1, 0, 0)))
+        .add(
+            PackageManifest::new("com.vrchat.avatars", Version::new(1, 0, 0))
+                .with_display_name("VRChat SDK - Avatars"),
+        )
+        .build()
+}
+
+#[test]
+fn search_by_id_display_name_and_description() {
+    let collection = collection();
+
+    let result = search_packages(&collection, "Optimizer", &SearchOptions::new());
+    assert_eq!(result.total, 3);
+
+    let found = (result.packages.iter())
+        .map(|x| (x.package.name(), x.package.version().clone(), x.matched))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        found,
+        vec![
+            ("optimizer", Version::new(1, 0, 0), SearchMatch::ExactId),
+            (
+                "com.anatawa12.avatar-optimizer",
+                Version::new(1, 1, 0),
+                SearchMatch::Id
+            ),
+            (
+                "com.anatawa12.gists",
+                Version::new(1, 0, 0),
+                SearchMatch::Description
+            ),
+        ]
+    );
+
+    let result = search_packages(&collection, "aao avatar", &SearchOptions::new());
+    assert_eq!(result.total, 1);
+    assert_eq!(result.packages[0].matched, SearchMatch::DisplayName);
+
+    let result = search_packages(
+        &collection,
+        "avatar-optimizer",
+        &SearchOptions::new().allow_prerelease(true),
+    );
+    assert!(result.packages[0].package.version().is_pre());
+}
+
+#[test]
+fn paginate_search_result() {
+    let collection = collection();
+
+    let options = SearchOptions::new().limit(2);
+    let result = search_packages(&collection, "", &options);
+    assert_eq!(result.total, 4);
+    assert_eq!(result.packages.len(), 2);
+    assert!(result.has_more(&options));
+
+    let options = SearchOptions::new().offset(2).limit(2);
+    let result = search_packages(&collection, "", &options);
+    assert_eq!(result.packages.len(), 2);
+    assert!(!result.has_more(&options));
+    assert_eq!(result.packages[1].package.name(), "optimizer");
+}
@@ -18,18 +18,20 @@ use std::process::exit;
 use std::str::FromStr;
 use tokio::fs::read_to_string;
 use vrc_get_vpm::environment::{
-    AddRepositoryErr, AddUserPackageResult, PackageCollection, PackageInstaller, Settings,
-    UserPackageCollection, add_remote_repo, cleanup_repos_folder, clear_package_cache,
+    AddRepositoryErr, AddUserPackageResult, ArchivePackage, PackageCollection, PackageInstaller,
+    Settings, UserPackageCollection, add_remote_repo, cleanup_repos_folder, clear_package_cache,
 };
 use vrc_get_vpm::io::{DefaultEnvironmentIo, DefaultProjectIo, IoTrait};
 use vrc_get_vpm::repositories_file::RepositoriesFile;
 use vrc_get_vpm::repository::RemoteRepository;
+use vrc_get_vpm::search::{SearchOptions, search_packages};
+use vrc_get_vpm::unity_project::PrereleasePropagation;
 use vrc_get_vpm::unity_project::pending_project_changes::{PackageChange, RemoveReason};
-use vrc_get_vpm::unity_project::{AddPackageOperation, PendingProjectChanges};
-use vrc_get_vpm::version::Version;
-use vrc_get_vpm::{
-    PackageCollection as _, PackageInfo, PackageManifest, UserRepoSetting, VersionSelector,
+use vrc_get_vpm::unity_project::{
+    AddPackageOperation, ChangesJson, PendingProjectChanges, ResolutionOptions, TreeNode,
 };
+use vrc_get_vpm::version::Version;
+use vrc_get_vpm::{PackageCollection as _, PackageInfo, UserRepoSetting, VersionSelector};
 
 type UnityProject = vrc_get_vpm::UnityProject<DefaultProjectIo>;
 
@@ -77,21 +79,50 @@ macro_rules! exit_with {
 }
 
 #[derive(Args, Default)]
-struct EnvArgs {
+pub(crate) struct EnvArgs {
     /// do not connect to remote servers, use local caches only. implicitly --no-update
     #[arg(long)]
-    offline: bool,
+    pub(crate) offline: bool,
     /// do not update local repository cache.
     #[arg(long)]
     no_update: bool,
+    /// use only the repositories listed in the file instead of the repositories in the settings.
+    /// the file has the same format as `repo import`
+    #[arg(long)]
+    repositories_file: Option<PathBuf>,
+    /// the number of attempts of http requests failed with network errors or 5xx responses.
+    /// defaults to 3. 1 disables retrying
+    #[arg(long)]
+    pub(crate) max_attempts: Option<u32>,
 }
 
 async fn load_collection(
     io: &DefaultEnvironmentIo,
-    http: Option<&reqwest::Client>,
-    no_update: bool,
+    http: Option<&crate::Client>,
+    env_args: &EnvArgs,
 ) -> PackageCollection {
+    let no_update = env_args.no_update;
     let mut settings = Settings::load(io).await.exit_context("loading settings");
+
+    if let Some(path) = &env_args.repositories_file {
+        let file = read_to_string(path)
+            .await
+            .exit_context("reading repositories file");
+        let parsed = RepositoriesFile::parse(&file);
+        for line in parsed.unparseable_lines() {
+            warn!("unparseable line in repositories file: {line}");
+        }
+        // the settings are not updated since the repositories in the settings are not used
+        return PackageCollection::load_with_repositories(
+            &settings,
+            io,
+            http.filter(|_| !no_update),
+            parsed.parsed(),
+        )
+        .await
+        .exit_context("loading repositories");
+    }
+
     let mut collection = PackageCollection::load(&settings, io, http.filter(|_| !no_update))
         .await
         .exit_context("loading repositories");
@@ -226,6 +257,15 @@ fn print_prompt_install(changes: &PendingProjectChanges) {
         }
     }
 
+    if let Some(resolution) = changes.resolution() {
+        if !resolution.downgrades.is_empty() {
+            println!("You're downgrading the following packages:");
+            for (name, locked, new) in &resolution.downgrades {
+                println!("- {} version {} -> {}", name, locked, new);
+            }
+        }
+    }
+
     if !adding_to_dependencies.is_empty() {
         println!("You're adding the following packages to dependencies:");
         for (name, range) in &adding_to_dependencies {
@@ -309,6 +349,22 @@ fn print_prompt_install(changes: &PendingProjectChanges) {
     }
 }
 
+/// Parses `--json-format` of commands changing the project. None for human readable output.
+fn changes_json_format(json_format: Option<NonZeroU32>) -> Option<u32> {
+    match json_format.map(|x| x.get()) {
+        None => None,
+        Some(supported @ 1..=ChangesJson::FORMAT_VERSION) => Some(supported),
+        Some(unsupported) => exit_with!("unsupported json version: {unsupported}"),
+    }
+}
+
+fn print_changes_json(changes: &PendingProjectChanges, format_version: u32) {
+    debug_assert_eq!(format_version, ChangesJson::FORMAT_VERSION);
+
+    let info = ChangesJson::new(changes);
+    println!("{}", serde_json::to_string(&info).unwrap());
+}
+
 fn prompt_install(yes: bool) {
     if yes {
         println!("--yes is set. skipping confirm");
@@ -317,6 +373,80 @@ fn prompt_install(yes: bool) {
     }
 }
 
+fn print_conflict_suggestions(
+    changes: &PendingProjectChanges,
+    collection: &impl vrc_get_vpm::PackageCollection,
+) {
+    let Some(resolution) = changes.resolution() else {
+        return;
+    };
+
+    let suggestions = resolution.suggest_conflict_fixes(collection);
+    if !suggestions.is_empty() {
+        println!("Suggestions to fix the conflicts:");
+        for suggestion in suggestions {
+            println!("- {suggestion}");
+        }
+    }
+}
+
+fn print_transitive_prereleases(changes: &PendingProjectChanges) {
+    let Some(resolution) = changes.resolution() else {
+        return;
+    };
+
+    for (name, version) in &resolution.transitive_prereleases {
+        let sources = (resolution.requirements.get(name).into_iter().flatten())
+            .map(|(source, _)| source.as_ref())
+            .filter(|source| !source.is_empty())
+            .sorted()
+            .join(", ");
+        if sources.is_empty() {
+            println!("Note: you're installing prerelease {name} version {version}");
+        } else {
+            println!(
+                "Note: you're installing prerelease {name} version {version} because {sources} requires it"
+            );
+        }
+    }
+}
+
+fn print_resolution_trace(changes: &PendingProjectChanges) {
+    let Some(diagnostics) = changes.resolution().and_then(|x| x.diagnostics.as_ref()) else {
+        return;
+    };
+
+    println!("Versions are chosen for the following reasons:");
+    for (name, reason) in diagnostics.trace.iter().sorted_by_key(|(name, _)| *name) {
+        match reason.prerelease_reason {
+            Some(prerelease) => println!(
+                "- {} version {} (prerelease allowed: {})",
+                name, reason.version, prerelease
+            ),
+            None => println!("- {} version {}", name, reason.version),
+        }
+        if let Some(repository) = &reason.repository {
+            println!("  - from repository {}", repository);
+        }
+        match reason.binding_requirement.as_deref() {
+            Some("") => println!("  - lower bound pinned by dependencies"),
+            Some(source) => println!("  - lower bound pinned by {}", source),
+            None => {}
+        }
+        for (source, range) in reason
+            .requirements
+            .iter()
+            .sorted_by_key(|(source, _)| source)
+        {
+            if source.is_empty() {
+                println!("  - required by dependencies: {}", range);
+            } else {
+                println!("  - required by {}: {}", source, range);
+            }
+        }
+    }
+}
+
 fn require_prompt_for_install(
     changes: &PendingProjectChanges,
     name: &str,
@@ -326,6 +456,7 @@ fn require_prompt_for_install(
     if !changes.remove_legacy_folders().is_empty()
         || !changes.remove_legacy_files().is_empty()
         || !changes.conflicts().is_empty()
+        || (changes.resolution()).is_some_and(|x| !x.downgrades.is_empty())
     {
         return true;
     }
@@ -388,8 +519,11 @@ pub enum Command {
     #[command(alias = "rm")]
     Remove(Remove),
     Reinstall(Reinstall),
+    Freeze(Freeze),
+    InstallFrozen(InstallFrozen),
     Update(Update),
     Outdated(Outdated),
+    Tree(Tree),
     Upgrade(Upgrade),
     Downgrade(Downgrade),
     Search(Search),
@@ -418,8 +552,11 @@ multi_command!(Command is
     Resolve,
     Remove,
     Reinstall,
+    Freeze,
+    InstallFrozen,
     Update,
     Outdated,
+    Tree,
     Upgrade,
     Downgrade,
     Search,
@@ -449,18 +586,40 @@ pub struct Install {
     #[arg(long = "prerelease")]
     prerelease: bool,
 
+    /// Allow prerelease only for the installing packages and dependencies of the project,
+    /// not for their dependencies
+    #[arg(long = "prerelease-root-only")]
+    prerelease_root_only: bool,
+
     /// Install package by display name instead of name
     ///
     /// This option is experimental and behavior may change in the future.
     #[arg(long = "name", short = 'n')]
     name: bool,
 
+    /// Install package from the zip file or the tarball at the path instead of id
+    #[arg(long = "archive", conflicts_with_all = ["name", "VERSION"])]
+    archive: bool,
+
+    /// Show why each version of packages is chosen
+    #[arg(long = "explain")]
+    explain: bool,
+
+    /// Only updates vpm-manifest.json without downloading and extracting packages.
+    /// Packages are checked to be downloadable before updating
+    #[arg(long = "no-install", requires = "id")]
+    no_install: bool,
+
     /// Path to project dir. by default CWD or parents of CWD will be used
     #[arg(short = 'p', long = "project")]
     project: Option<Box<Path>>,
     #[command(flatten)]
     env_args: EnvArgs,
 
+    /// With this option, changes are printed in json format instead of human readable text
+    #[arg(long = "json-format", requires = "yes")]
+    json_format: Option<NonZeroU32>,
+
     /// skip confirm
     #[arg(short, long)]
     yes: bool,
@@ -473,14 +632,16 @@ impl Install {
             return Resolve {
                 project: self.project,
                 env_args: self.env_args,
+                json_format: self.json_format,
             }
             .run()
             .await;
         };
 
-        let client = crate::create_client(self.env_args.offline);
+        let json_format = changes_json_format(self.json_format);
+        let client = crate::create_client(&self.env_args);
         let io = DefaultEnvironmentIo::new_default();
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
         let installer = PackageInstaller::new(&io, client.as_ref());
         let mut unity = load_unity(self.project).await;
 
@@ -488,7 +649,13 @@ impl Install {
             None => VersionSelector::latest_for(unity.unity_version(), self.prerelease),
             Some(ref version) => VersionSelector::specific_version(version),
         };
-        let packages = if self.name {
+        let archive;
+        let packages = if self.archive {
+            archive = ArchivePackage::load(&io, &absolute_path(&name))
+                .await
+                .exit_context("loading package archive");
+            vec![archive.package_info()]
+        } else if self.name {
             warn!("--name is experimental and behavior may change in the future.");
 
             fn normalize_name(name: &str) -> String {
@@ -515,26 +682,55 @@ impl Install {
             vec![get_package(&collection, &name, version_selector)]
         };
 
+        let propagation = if self.prerelease_root_only {
+            PrereleasePropagation::RootOnly
+        } else {
+            PrereleasePropagation::Propagate
+        };
+        let options = ResolutionOptions::new()
+            .allow_prerelease(self.prerelease)
+            .prerelease_propagation(propagation)
+            .detect_stuck_prereleases(true)
+            .detect_kept_yanked(true)
+            .diagnostics(self.explain);
         let changes = unity
-            .add_package_request(
+            .add_package_request_with_options(
                 &collection,
                 &packages,
                 AddPackageOperation::InstallToDependencies,
-                self.prerelease,
+                &options,
             )
             .await
             .exit_context("collecting packages to be installed");
 
-        print_prompt_install(&changes);
+        if let Some(format_version) = json_format {
+            // --json-format requires --yes so no prompt here
+            print_changes_json(&changes, format_version);
+        } else {
+            print_prompt_install(&changes);
+            print_conflict_suggestions(&changes, &collection);
+            print_transitive_prereleases(&changes);
 
-        if require_prompt_for_install(&changes, name.as_str(), None) {
-            prompt_install(self.yes);
+            if self.explain {
+                print_resolution_trace(&changes);
+            }
+
+            if require_prompt_for_install(&changes, name.as_str(), None) {
+                prompt_install(self.yes);
+            }
         }
 
-        unity
-            .apply_pending_changes(&installer, changes)
-            .await
-            .exit_context("adding package");
+        if self.no_install {
+            unity
+                .apply_pending_changes_lockfile_only(&installer, changes)
+                .await
+                .exit_context("updating lockfile");
+        } else {
+            unity
+                .apply_pending_changes(&installer, changes)
+                .await
+                .exit_context("adding package");
+        }
 
         update_project_last_modified(&io, unity.project_dir()).await;
     }
@@ -552,13 +748,18 @@ pub struct Resolve {
     project: Option<Box<Path>>,
     #[command(flatten)]
     env_args: EnvArgs,
+
+    /// With this option, changes are printed in json format instead of human readable text
+    #[arg(long = "json-format")]
+    json_format: Option<NonZeroU32>,
 }
 
 impl Resolve {
     pub async fn run(self) {
-        let client = crate::create_client(self.env_args.offline);
+        let json_format = changes_json_format(self.json_format);
+        let client = crate::create_client(&self.env_args);
         let io = DefaultEnvironmentIo::new_default();
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
         let mut unity = load_unity(self.project).await;
 
         let installer = PackageInstaller::new(&io, client.as_ref());
@@ -568,7 +769,10 @@ impl Resolve {
             .await
             .exit_context("collecting packages to be installed");
 
-        print_prompt_install(&changes);
+        match json_format {
+            Some(format_version) => print_changes_json(&changes, format_version),
+            None => print_prompt_install(&changes),
+        }
 
         unity
             .apply_pending_changes(&installer, changes)
@@ -585,6 +789,10 @@ pub struct Remove {
     #[arg()]
     names: Vec<String>,
 
+    /// Keep packages no longer required by remaining packages
+    #[arg(long = "keep-orphans")]
+    keep_orphans: bool,
+
     /// Path to project dir. by default CWD or parents of CWD will be used
     #[arg(short = 'p', long = "project")]
     project: Option<Box<Path>>,
@@ -602,10 +810,13 @@ impl Remove {
         let mut unity = load_unity(self.project).await;
 
         let changes = unity
-            .remove_request(&self.names.iter().map(String::as_ref).collect::<Vec<_>>())
+            .remove_request_with_orphans(
+                &self.names.iter().map(String::as_ref).collect::<Vec<_>>(),
+                !self.keep_orphans,
+            )
             .await
             .exit_context("collecting packages to be removed");
-        let installer = PackageInstaller::new(&io, None::<&reqwest::Client>);
+        let installer = PackageInstaller::new(&io, None::<&crate::Client>);
 
         print_prompt_install(&changes);
 
@@ -630,9 +841,13 @@ impl Remove {
 #[command(author, version)]
 pub struct Reinstall {
     /// Name of Packages to reinstall
-    #[arg()]
+    #[arg(required_unless_present = "all")]
     names: Vec<String>,
 
+    /// Reinstall all locked packages
+    #[arg(long, conflicts_with = "names")]
+    all: bool,
+
     /// Path to project dir. by default CWD or parents of CWD will be used
     #[arg(short = 'p', long = "project")]
     project: Option<Box<Path>>,
@@ -647,18 +862,23 @@ pub struct Reinstall {
 impl Reinstall {
     pub async fn run(self) {
         let io = DefaultEnvironmentIo::new_default();
-        let client = crate::create_client(self.env_args.offline);
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let client = crate::create_client(&self.env_args);
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
         let installer = PackageInstaller::new(&io, client.as_ref());
 
         let mut unity = load_unity(self.project).await;
 
-        let names = self.names.iter().map(String::as_ref).collect::<Vec<_>>();
+        let changes = if self.all {
+            unity.reinstall_all_request(&collection).await
+        } else {
+            let names = self.names.iter().map(String::as_ref).collect::<Vec<_>>();
+            unity.reinstall_request(&collection, &names).await
+        }
+        .exit_context("collecting packages to be reinstalled");
 
-        let changes = unity
-            .reinstall_request(&collection, &names)
-            .await
-            .exit_context("collecting packages to be removed");
+        let reinstalled = (changes.package_changes().keys())
+            .map(|x| x.to_string())
+            .collect::<Vec<_>>();
 
         print_prompt_install(&changes);
 
@@ -672,7 +892,101 @@ impl Reinstall {
         unity
             .apply_pending_changes(&installer, changes)
             .await
-            .exit_context("removing packages");
+            .exit_context("reinstalling packages");
+
+        let reinstalled = reinstalled.iter().map(String::as_str).collect::<Vec<_>>();
+        for broken in unity.verify_installed(&reinstalled).await {
+            warn!("{broken} is not installed with the locked version after reinstalling");
+        }
+
+        update_project_last_modified(&io, unity.project_dir()).await;
+    }
+}
+
+/// Export the exact versions of the locked packages to share the same environment
+#[derive(Parser)]
+#[command(author, version)]
+pub struct Freeze {
+    /// Path to the file to write. by default the snapshot is printed to stdout
+    #[arg(short = 'o', long = "output")]
+    output: Option<Box<Path>>,
+
+    /// Path to project dir. by default CWD or parents of CWD will be used
+    #[arg(short = 'p', long = "project")]
+    project: Option<Box<Path>>,
+    #[command(flatten)]
+    env_args: EnvArgs,
+}
+
+impl Freeze {
+    pub async fn run(self) {
+        let io = DefaultEnvironmentIo::new_default();
+        let client = crate::create_client(&self.env_args);
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
+
+        let unity = load_unity(self.project).await;
+
+        let frozen = unity.freeze(&collection);
+        let json = serde_json::to_string_pretty(&frozen).unwrap();
+
+        match self.output {
+            Some(output) => tokio::fs::write(output, json + "\n")
+                .await
+                .exit_context("writing the snapshot"),
+            None => println!("{json}"),
+        }
+    }
+}
+
+/// Install exactly the versions exported by the freeze command
+#[derive(Parser)]
+#[command(author, version)]
+pub struct InstallFrozen {
+    /// Path to the file written by the freeze command
+    #[arg()]
+    file: Box<Path>,
+
+    /// Path to project dir. by default CWD or parents of CWD will be used
+    #[arg(short = 'p', long = "project")]
+    project: Option<Box<Path>>,
+    #[command(flatten)]
+    env_args: EnvArgs,
+
+    /// skip confirm
+    #[arg(short, long)]
+    yes: bool,
+}
+
+impl InstallFrozen {
+    pub async fn run(self) {
+        let file = read_to_string(&self.file)
+            .await
+            .exit_context("reading the snapshot");
+        let frozen = serde_json::from_str::<vrc_get_vpm::unity_project::FrozenPackages>(&file)
+            .exit_context("parsing the snapshot");
+
+        let io = DefaultEnvironmentIo::new_default();
+        let client = crate::create_client(&self.env_args);
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
+        let installer = PackageInstaller::new(&io, client.as_ref());
+
+        let mut unity = load_unity(self.project).await;
+
+        let changes = unity
+            .install_frozen_request(&collection, &frozen)
+            .await
+            .exit_context("collecting packages to be installed");
+
+        print_prompt_install(&changes);
+
+        if !changes.package_changes().is_empty() || !changes.conflicts().is_empty() {
+            prompt_install(self.yes);
+        }
+
+        unity
+            .apply_pending_changes(&installer, changes)
+            .await
+            .exit_context("installing packages");
 
         update_project_last_modified(&io, unity.project_dir()).await;
     }
@@ -685,9 +999,10 @@ pub struct Update {}
 
 impl Update {
     pub async fn run(self) {
-        let client = crate::create_client(false);
+        let env_args = EnvArgs::default();
+        let client = crate::create_client(&env_args);
         let io = DefaultEnvironmentIo::new_default();
-        load_collection(&io, client.as_ref(), false).await;
+        load_collection(&io, client.as_ref(), &env_args).await;
     }
 }
 
@@ -712,9 +1027,9 @@ pub struct Outdated {
 
 impl Outdated {
     pub async fn run(self) {
-        let client = crate::create_client(self.env_args.offline);
+        let client = crate::create_client(&self.env_args);
         let io = DefaultEnvironmentIo::new_default();
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
         let unity = load_unity(self.project).await;
 
         let mut outdated_packages = HashMap::new();
@@ -775,6 +1090,51 @@ impl Outdated {
     }
 }
 
+/// Show the dependency tree of locked packages
+#[derive(Parser)]
+#[command(author, version)]
+pub struct Tree {
+    /// Path to project dir. by default CWD or parents of CWD will be used
+    #[arg(short = 'p', long = "project")]
+    project: Option<Box<Path>>,
+}
+
+impl Tree {
+    pub async fn run(self) {
+        let unity = load_unity(self.project).await;
+
+        fn print_node(node: &TreeNode, prefix: &str, last: bool) {
+            let (branch, indent) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let version = match &node.version {
+                Some(version) => version.to_string(),
+                None => "not locked".to_string(),
+            };
+            let mut line = format!("{prefix}{branch}{} {version}", node.name);
+            if let (false, Some(range)) = (node.satisfied, &node.range) {
+                line.push_str(&format!(" (requires {range})"));
+            }
+            if node.deduplicated && node.version.is_some() {
+                line.push_str(" (*)");
+            }
+            println!("{line}");
+
+            let prefix = format!("{prefix}{indent}");
+            for (i, child) in node.children.iter().enumerate() {
+                print_node(child, &prefix, i == node.children.len() - 1);
+            }
+        }
+
+        let tree = unity.dependency_tree();
+        for (i, node) in tree.iter().enumerate() {
+            print_node(node, "", i == tree.len() - 1);
+        }
+    }
+}
+
 /// Upgrade specified package or all packages to latest or specified version.
 ///
 /// With install command, you'll add to dependencies. With upgrade command,
@@ -806,8 +1166,8 @@ pub struct Upgrade {
 impl Upgrade {
     pub async fn run(self) {
         let io = DefaultEnvironmentIo::new_default();
-        let client = crate::create_client(self.env_args.offline);
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let client = crate::create_client(&self.env_args);
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
         let installer = PackageInstaller::new(&io, client.as_ref());
         let mut unity = load_unity(self.project).await;
 
@@ -840,6 +1200,7 @@ impl Upgrade {
             .exit_context("collecting packages to be upgraded");
 
         print_prompt_install(&changes);
+        print_conflict_suggestions(&changes, &collection);
 
         let require_prompt = if let Some(name) = &self.name {
             require_prompt_for_install(&changes, name.as_str(), None)
@@ -900,9 +1261,9 @@ pub struct Downgrade {
 
 impl Downgrade {
     pub async fn run(self) {
-        let client = crate::create_client(self.env_args.offline);
+        let client = crate::create_client(&self.env_args);
         let io = DefaultEnvironmentIo::new_default();
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
         let installer = PackageInstaller::new(&io, client.as_ref());
         let mut unity = load_unity(self.project).await;
 
@@ -923,6 +1284,7 @@ impl Downgrade {
             .exit_context("collecting packages to be upgraded");
 
         print_prompt_install(&changes);
+        print_conflict_suggestions(&changes, &collection);
 
         if require_prompt_for_install(&changes, self.name.as_str(), None) {
             prompt_install(self.yes)
@@ -963,39 +1325,21 @@ pub struct Search {
 
 impl Search {
     pub async fn run(self) {
-        let client = crate::create_client(self.env_args.offline);
+        let client = crate::create_client(&self.env_args);
         let io = DefaultEnvironmentIo::new_default();
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
-
-        let mut queries = self.queries;
-        for query in &mut queries {
-            query.make_ascii_lowercase();
-        }
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
 
-        fn search_targets(pkg: &PackageManifest) -> Vec<String> {
-            let mut sources = Vec::with_capacity(3);
-
-            sources.push(pkg.name().to_ascii_lowercase());
-            sources.extend(pkg.display_name().map(|x| x.to_ascii_lowercase()));
-            sources.extend(pkg.description().map(|x| x.to_ascii_lowercase()));
-
-            sources
-        }
-
-        let found_packages =
-            collection.find_whole_all_packages(VersionSelector::latest_for(None, true), |pkg| {
-                // filtering
-                let search_targets = search_targets(pkg);
-
-                queries
-                    .iter()
-                    .all(|query| search_targets.iter().any(|x| x.contains(query)))
-            });
+        let query = self.queries.join(" ");
+        let options = SearchOptions::new()
+            .allow_prerelease(true)
+            .limit(usize::MAX);
+        let found_packages = search_packages(&collection, &query, &options).packages;
 
         if found_packages.is_empty() {
             println!("No matching package found!")
         } else {
             for x in found_packages {
+                let x = x.package;
                 if let Some(name) = x.package_json().display_name() {
                     println!("{} version {}", name, x.version());
                     println!("({})", x.name());
@@ -1067,6 +1411,10 @@ pub struct RepoAdd {
     #[arg(short='H', long, value_parser = HeaderPair::from_str)]
     header: Vec<HeaderPair>,
 
+    /// Headers with secret values like auth tokens. Those values are not exported.
+    #[arg(long, value_parser = HeaderPair::from_str)]
+    secret_header: Vec<HeaderPair>,
+
     #[command(flatten)]
     env_args: EnvArgs,
 }
@@ -1124,7 +1472,7 @@ impl StdError for HeaderPairErr {
 
 impl RepoAdd {
     pub async fn run(self) {
-        let http = crate::create_client(self.env_args.offline);
+        let http = crate::create_client(&self.env_args);
         let io = DefaultEnvironmentIo::new_default();
         let mut settings = Settings::load(&io).await.exit_context("loading settings");
 
@@ -1133,16 +1481,32 @@ impl RepoAdd {
             for HeaderPair(name, value) in self.header {
                 headers.insert(name.as_str().into(), value.to_str().unwrap().into());
             }
+            let mut secret_headers = Vec::<Box<str>>::new();
+            for HeaderPair(name, value) in self.secret_header {
+                headers.insert(name.as_str().into(), value.to_str().unwrap().into());
+                secret_headers.push(name.as_str().into());
+            }
+            let is_file_url = url.scheme() == "file";
             add_remote_repo(
                 &mut settings,
-                url,
+                url.clone(),
                 self.name.as_deref(),
                 headers,
                 &io,
-                &http.unwrap_or_else(|| exit_with!("offline mode")),
+                &http.unwrap_or_else(|| {
+                    if is_file_url {
+                        // local repositories don't need network access
+                        crate::Client::new(reqwest::Client::new(), tokio::time::sleep)
+                    } else {
+                        exit_with!("offline mode")
+                    }
+                }),
             )
             .await
-            .exit_context("adding repository")
+            .exit_context("adding repository");
+            if !secret_headers.is_empty() {
+                settings.set_secret_headers(&url, secret_headers);
+            }
         } else {
             let normalized = absolute_path(&self.path_or_url);
             if !normalized.exists() {
@@ -1319,16 +1683,16 @@ impl RepoPackages {
             if self.env_args.offline {
                 exit_with!("remote repository specified but offline mode.");
             }
-            let client = crate::create_client(self.env_args.offline).unwrap();
+            let client = crate::create_client(&self.env_args).unwrap();
             let (repo, _) = RemoteRepository::download(&client, &url, &IndexMap::new())
                 .await
                 .exit_context("downloading repository");
 
             print_repo(&repo);
         } else {
-            let client = crate::create_client(self.env_args.offline);
+            let client = crate::create_client(&self.env_args);
             let io = DefaultEnvironmentIo::new_default();
-            let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+            let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
 
             let some_name = Some(self.name_or_url.as_str());
             let mut found = false;
@@ -1363,7 +1727,7 @@ pub struct RepoImport {
 
 impl RepoImport {
     pub async fn run(self) {
-        let http = crate::create_client(self.env_args.offline);
+        let http = crate::create_client(&self.env_args);
         let io = DefaultEnvironmentIo::new_default();
         let mut settings = Settings::load(&io).await.exit_context("loading settings");
         let repositories_file = read_to_string(self.repositories_file)
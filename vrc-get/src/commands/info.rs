@@ -183,7 +183,7 @@ impl Package {
     pub async fn run(self) {
         let client = crate::create_client(self.env_args.offline);
         let io = DefaultEnvironmentIo::new_default();
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
 
         let format_version = match self.json_format.map(|x| x.get()).unwrap_or_default() {
             0 => {
@@ -50,7 +50,7 @@ impl Unity2022 {
 
         let client = crate::create_client(self.env_args.offline);
         let io = DefaultEnvironmentIo::new_default();
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
         let installer = PackageInstaller::new(&io, client.as_ref());
 
         #[cfg(feature = "experimental-vcc")]
@@ -139,7 +139,7 @@ impl Vpm {
 
         let client = crate::create_client(self.env_args.offline);
         let io = DefaultEnvironmentIo::new_default();
-        let collection = load_collection(&io, client.as_ref(), self.env_args.no_update).await;
+        let collection = load_collection(&io, client.as_ref(), &self.env_args).await;
         let installer = PackageInstaller::new(&io, client.as_ref());
 
         project
@@ -3,7 +3,7 @@
 extern crate core;
 
 use clap::Parser;
-use reqwest::Client;
+use vrc_get_vpm::RetryingClient;
 
 mod commands;
 
@@ -43,8 +43,11 @@ fn init_log() {
     builder.init();
 }
 
-pub(crate) fn create_client(offline: bool) -> Option<Client> {
-    if offline {
+/// The http client retrying requests failed with transient errors
+pub(crate) type Client = RetryingClient<reqwest::Client>;
+
+pub(crate) fn create_client(env_args: &commands::EnvArgs) -> Option<Client> {
+    if env_args.offline {
         None
     } else {
         let authors = env!("CARGO_PKG_AUTHORS");
@@ -58,11 +61,14 @@ pub(crate) fn create_client(offline: bool) -> Option<Client> {
 
         log::debug!("using user agent: {}", user_agent);
 
-        Some(
-            Client::builder()
-                .user_agent(user_agent)
-                .build()
-                .expect("building client"),
-        )
+        let client = reqwest::Client::builder()
+            .user_agent(user_agent)
+            .build()
+            .expect("building client");
+        let mut client = RetryingClient::new(client, tokio::time::sleep);
+        if let Some(max_attempts) = env_args.max_attempts {
+            client = client.with_max_attempts(max_attempts);
+        }
+        Some(client)
     }
 }